@@ -0,0 +1,79 @@
+//! Loudness normalization for generated audio.
+//!
+//! Uses an RMS-based approximation of integrated loudness (no K-weighting or
+//! gating), which is close enough to LUFS for evening out track levels.
+
+/// Peak ceiling applied after gain (-1 dBFS) to avoid clipping.
+const PEAK_CEILING: f32 = 0.891;
+
+/// Loudness assigned to silent input, in LUFS.
+const SILENCE_LUFS: f32 = -120.0;
+
+/// Estimates the loudness of a signal in LUFS.
+///
+/// Returns a very low value for silent input.
+pub fn measure_loudness(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return SILENCE_LUFS;
+    }
+
+    let mean_square = samples
+        .iter()
+        .map(|&s| (s as f64) * (s as f64))
+        .sum::<f64>()
+        / samples.len() as f64;
+    if mean_square <= 0.0 {
+        return SILENCE_LUFS;
+    }
+
+    // -0.691 offset from the BS.1770 loudness formula
+    (-0.691 + 10.0 * mean_square.log10()) as f32
+}
+
+/// Scales samples in place towards `target_lufs`.
+///
+/// The applied gain is limited so the output peak stays below -1 dBFS.
+/// Silent input is left untouched. Returns the gain that was applied.
+pub fn normalize_loudness(samples: &mut [f32], target_lufs: f32) -> f32 {
+    let current = measure_loudness(samples);
+    if current <= SILENCE_LUFS {
+        return 1.0;
+    }
+
+    let mut gain = 10f32.powf((target_lufs - current) / 20.0);
+
+    let peak = samples.iter().fold(0.0f32, |max, &s| max.max(s.abs()));
+    if peak * gain > PEAK_CEILING {
+        gain = PEAK_CEILING / peak;
+    }
+
+    for sample in samples.iter_mut() {
+        *sample *= gain;
+    }
+    gain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_reaches_target() {
+        // Quiet sine wave well below the peak ceiling after gain
+        let mut samples: Vec<f32> = (0..32000).map(|i| 0.01 * (i as f32 * 0.05).sin()).collect();
+
+        normalize_loudness(&mut samples, -20.0);
+        assert!((measure_loudness(&samples) + 20.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn normalize_limits_peak_and_skips_silence() {
+        let mut loud = vec![0.5, -0.5, 0.01, -0.01];
+        normalize_loudness(&mut loud, 0.0);
+        assert!(loud.iter().all(|s| s.abs() <= PEAK_CEILING + 1e-6));
+
+        let mut silent = vec![0.0; 16];
+        assert_eq!(normalize_loudness(&mut silent, -14.0), 1.0);
+        assert!(silent.iter().all(|&s| s == 0.0));
+    }
+}
@@ -1,13 +1,30 @@
 //! Audio output module.
 //!
-//! Provides WAV file writing and resampling for generated audio.
+//! Provides WAV file writing and validation, resampling, reverb, loudness
+//! normalization, silence trimming, spectrogram thumbnails, and summary
+//! statistics for generated audio.
 
+pub mod loudness;
 pub mod resample;
+pub mod reverb;
+pub mod spectrogram;
+pub mod stats;
+pub mod trim;
 pub mod wav;
 
 // Re-export commonly used items
-pub use resample::{resample, resample_44100_to_48000};
+pub use loudness::{measure_loudness, normalize_loudness};
+pub use resample::{resample, resample_44100_to_48000, OUTPUT_SAMPLE_RATES};
+pub use reverb::{apply_reverb, Reverb, ReverbPreset};
+pub use spectrogram::{
+    write_spectrogram_png, write_spectrogram_png_with_options, SpectrogramOptions,
+};
+pub use stats::AudioStats;
+pub use trim::{
+    trim_silence, SilenceTrim, DEFAULT_TRIM_MIN_SILENCE_SEC, DEFAULT_TRIM_THRESHOLD_DB,
+};
 pub use wav::{
-    samples_to_duration, write_wav, write_wav_to_buffer, CHANNELS, SAMPLE_RATE,
+    append_info_comment, read_info_comment, read_wav, samples_to_duration, write_wav,
+    write_wav_to_buffer, WavValidationReport, WavValidator, CHANNELS, SAMPLE_RATE,
     SAMPLE_RATE_ACE_STEP, SAMPLE_RATE_MUSICGEN,
 };
@@ -7,6 +7,9 @@ use rubato::{FftFixedIn, Resampler};
 
 use crate::error::{DaemonError, Result};
 
+/// Sample rates that generated audio can be delivered at.
+pub const OUTPUT_SAMPLE_RATES: &[u32] = &[22050, 32000, 44100, 48000];
+
 /// Resamples audio from one sample rate to another.
 ///
 /// Uses FFT-based resampling for high quality. This is primarily used
@@ -0,0 +1,264 @@
+//! Reverb for generated audio.
+//!
+//! A mono Schroeder reverb in the Freeverb layout: eight damped comb filters
+//! in parallel feed four allpass filters in series. Delay lengths are the
+//! Freeverb tunings scaled to the sample rate, so a preset sounds the same at
+//! 32kHz and 48kHz. Each sample costs twelve delay-line updates, which is
+//! negligible next to generation.
+
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Comb filter delays in samples at 44.1kHz.
+const COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+
+/// Allpass filter delays in samples at 44.1kHz.
+const ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+
+/// Sample rate the tunings were chosen for.
+const TUNING_SAMPLE_RATE: f32 = 44100.0;
+
+/// Gain applied to the input before the comb filters.
+const INPUT_GAIN: f32 = 0.015;
+
+/// Gain applied to the reverberated signal, bringing it back to about the
+/// level of the input.
+const WET_GAIN: f32 = 3.0;
+
+/// Comb feedback at room size 0; room size 1 adds [`ROOM_SCALE`].
+const ROOM_OFFSET: f32 = 0.7;
+
+/// Comb feedback added between room size 0 and 1.
+const ROOM_SCALE: f32 = 0.28;
+
+/// High-frequency damping in the comb feedback path (0 = bright, 1 = dark).
+const DAMPING: f32 = 0.2;
+
+/// Allpass filter feedback.
+const ALLPASS_FEEDBACK: f32 = 0.5;
+
+/// Named reverb settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReverbPreset {
+    /// Small room: short, subtle tail.
+    Room,
+    /// Concert hall: long, spacious tail.
+    Hall,
+    /// Medium space mixed low, like a tape echo chamber.
+    LofiTape,
+}
+
+impl ReverbPreset {
+    /// All presets, in the order they are listed to users.
+    pub const ALL: [ReverbPreset; 3] =
+        [ReverbPreset::Room, ReverbPreset::Hall, ReverbPreset::LofiTape];
+
+    /// Parses a preset name, case-insensitively. Accepts `lofi_tape` as well
+    /// as `lofi-tape`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "room" => Some(ReverbPreset::Room),
+            "hall" => Some(ReverbPreset::Hall),
+            "lofi-tape" => Some(ReverbPreset::LofiTape),
+            _ => None,
+        }
+    }
+
+    /// Returns the preset name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReverbPreset::Room => "room",
+            ReverbPreset::Hall => "hall",
+            ReverbPreset::LofiTape => "lofi-tape",
+        }
+    }
+
+    /// Returns the room size (0.0-1.0) of the preset.
+    pub fn room_size(&self) -> f32 {
+        match self {
+            ReverbPreset::Room => 0.4,
+            ReverbPreset::Hall => 0.85,
+            ReverbPreset::LofiTape => 0.6,
+        }
+    }
+
+    /// Returns the wet mix (0.0-1.0) used when none is given.
+    pub fn default_wet(&self) -> f32 {
+        match self {
+            ReverbPreset::Room => 0.2,
+            ReverbPreset::Hall => 0.3,
+            ReverbPreset::LofiTape => 0.15,
+        }
+    }
+}
+
+impl fmt::Display for ReverbPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A reverb preset and the wet mix to apply it at.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Reverb {
+    /// Preset providing the room size.
+    pub preset: ReverbPreset,
+    /// Fraction of reverberated signal in the output (0.0-1.0).
+    pub wet: f32,
+}
+
+impl Reverb {
+    /// Creates a reverb, using the preset's default wet mix if `wet` is None.
+    pub fn new(preset: ReverbPreset, wet: Option<f32>) -> Self {
+        Self {
+            preset,
+            wet: wet.unwrap_or_else(|| preset.default_wet()),
+        }
+    }
+
+    /// Applies the reverb to mono samples in place.
+    pub fn apply(&self, samples: &mut [f32], sample_rate: u32) {
+        apply_reverb(samples, sample_rate, self.preset.room_size(), self.wet);
+    }
+
+    /// Returns a label identifying the settings, e.g. `reverb:hall@0.30`.
+    ///
+    /// Used in provenance and to derive the track ID of reverberated tracks.
+    pub fn label(&self) -> String {
+        format!("reverb:{}@{:.2}", self.preset, self.wet)
+    }
+}
+
+/// Feedback delay line with a one-pole lowpass in the loop.
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    filter_store: f32,
+}
+
+impl Comb {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len],
+            index: 0,
+            filter_store: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32) -> f32 {
+        let output = self.buffer[self.index];
+        self.filter_store = output * (1.0 - DAMPING) + self.filter_store * DAMPING;
+        self.buffer[self.index] = input + self.filter_store * feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+}
+
+/// Delay line that diffuses echoes without coloring the spectrum.
+struct Allpass {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+impl Allpass {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len],
+            index: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.index];
+        self.buffer[self.index] = input + delayed * ALLPASS_FEEDBACK;
+        self.index = (self.index + 1) % self.buffer.len();
+        delayed - input
+    }
+}
+
+/// Applies reverb to mono samples in place.
+///
+/// `room_size` (0.0-1.0) sets how long the tail rings; `wet` (0.0-1.0) is the
+/// fraction of reverberated signal mixed into the output. Both are clamped to
+/// their ranges. A wet mix of 0 leaves the samples untouched. The tail is cut
+/// off at the end of the input rather than extending the clip.
+pub fn apply_reverb(samples: &mut [f32], sample_rate: u32, room_size: f32, wet: f32) {
+    let wet = wet.clamp(0.0, 1.0);
+    if wet == 0.0 || samples.is_empty() {
+        return;
+    }
+
+    let scale = sample_rate as f32 / TUNING_SAMPLE_RATE;
+    let delay = |tuning: usize| ((tuning as f32 * scale).round() as usize).max(1);
+    let mut combs: Vec<Comb> = COMB_TUNINGS.iter().map(|&t| Comb::new(delay(t))).collect();
+    let mut allpasses: Vec<Allpass> =
+        ALLPASS_TUNINGS.iter().map(|&t| Allpass::new(delay(t))).collect();
+    let feedback = ROOM_OFFSET + ROOM_SCALE * room_size.clamp(0.0, 1.0);
+
+    for sample in samples.iter_mut() {
+        let input = *sample * INPUT_GAIN;
+        let mut reverberated: f32 =
+            combs.iter_mut().map(|comb| comb.process(input, feedback)).sum();
+        for allpass in &mut allpasses {
+            reverberated = allpass.process(reverberated);
+        }
+        *sample = *sample * (1.0 - wet) + reverberated * WET_GAIN * wet;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Energy of an impulse's reverb between `from` and `to` seconds.
+    fn tail_energy(room_size: f32, from: f32, to: f32) -> f32 {
+        let sample_rate = 32000;
+        let mut samples = vec![0.0; sample_rate as usize * 2];
+        samples[0] = 1.0;
+        apply_reverb(&mut samples, sample_rate, room_size, 1.0);
+        let range = (from * sample_rate as f32) as usize..(to * sample_rate as f32) as usize;
+        samples[range].iter().map(|s| s * s).sum()
+    }
+
+    #[test]
+    fn dry_mix_leaves_samples_untouched() {
+        let original: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.1).sin()).collect();
+        let mut samples = original.clone();
+        apply_reverb(&mut samples, 48000, 0.8, 0.0);
+        assert_eq!(samples, original);
+
+        apply_reverb(&mut [], 48000, 0.8, 0.5);
+    }
+
+    #[test]
+    fn impulse_rings_longer_in_larger_rooms() {
+        assert!(tail_energy(0.4, 0.2, 1.0) > 0.0);
+        assert!(tail_energy(0.9, 0.5, 2.0) > 4.0 * tail_energy(0.1, 0.5, 2.0));
+    }
+
+    #[test]
+    fn output_stays_bounded() {
+        // Full-scale alternating input at maximum settings
+        let mut samples: Vec<f32> =
+            (0..96000).map(|i| if i % 2 == 0 { 1.0 } else { -1.0 }).collect();
+        apply_reverb(&mut samples, 48000, 1.0, 1.0);
+        assert!(samples.iter().all(|s| s.is_finite() && s.abs() < 4.0));
+    }
+
+    #[test]
+    fn presets_parse_and_label() {
+        for preset in ReverbPreset::ALL {
+            assert_eq!(ReverbPreset::parse(preset.as_str()), Some(preset));
+        }
+        assert_eq!(ReverbPreset::parse("LOFI_TAPE"), Some(ReverbPreset::LofiTape));
+        assert_eq!(ReverbPreset::parse("cathedral"), None);
+
+        let reverb = Reverb::new(ReverbPreset::Hall, None);
+        assert_eq!(reverb.wet, 0.3);
+        assert_eq!(reverb.label(), "reverb:hall@0.30");
+        assert_eq!(Reverb::new(ReverbPreset::Room, Some(0.5)).wet, 0.5);
+    }
+}
@@ -0,0 +1,249 @@
+//! Spectrogram thumbnail rendering.
+//!
+//! Computes a short-time Fourier transform of generated audio and writes it
+//! as a colormapped PNG image for library views.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use realfft::RealFftPlanner;
+
+use crate::error::{DaemonError, ErrorCode, Result};
+
+/// Default FFT window size in samples.
+pub const DEFAULT_FFT_SIZE: usize = 1024;
+
+/// Default hop between successive FFT windows in samples.
+pub const DEFAULT_HOP_SIZE: usize = 512;
+
+/// Maximum image width in pixels; longer tracks are averaged into this many columns.
+const MAX_WIDTH: usize = 1024;
+
+/// Highest frequency shown in the image. Little musical energy sits above this.
+const MAX_DISPLAY_HZ: f32 = 16000.0;
+
+/// Dynamic range shown in the image, in decibels below the loudest bin.
+const DYNAMIC_RANGE_DB: f32 = 80.0;
+
+/// Colormap stops from quiet to loud (black, purple, orange, pale yellow).
+const COLORMAP: [[f32; 3]; 5] = [
+    [0.0, 0.0, 4.0],
+    [87.0, 16.0, 110.0],
+    [188.0, 55.0, 84.0],
+    [249.0, 142.0, 9.0],
+    [252.0, 255.0, 164.0],
+];
+
+/// STFT settings for spectrogram rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpectrogramOptions {
+    /// FFT window size in samples.
+    pub fft_size: usize,
+    /// Hop between successive windows in samples.
+    pub hop_size: usize,
+}
+
+impl Default for SpectrogramOptions {
+    fn default() -> Self {
+        Self {
+            fft_size: DEFAULT_FFT_SIZE,
+            hop_size: DEFAULT_HOP_SIZE,
+        }
+    }
+}
+
+/// Writes a spectrogram of `samples` to a PNG file using default STFT settings.
+pub fn write_spectrogram_png(samples: &[f32], sample_rate: u32, path: &Path) -> Result<()> {
+    write_spectrogram_png_with_options(samples, sample_rate, path, SpectrogramOptions::default())
+}
+
+/// Writes a spectrogram of `samples` to a PNG file.
+///
+/// Time runs left to right and frequency bottom to top, up to 16 kHz.
+pub fn write_spectrogram_png_with_options(
+    samples: &[f32],
+    sample_rate: u32,
+    path: &Path,
+    options: SpectrogramOptions,
+) -> Result<()> {
+    let magnitudes = stft_magnitudes_db(samples, sample_rate, options);
+    let width = magnitudes.len().clamp(1, MAX_WIDTH);
+    let height = magnitudes.first().map_or(1, Vec::len).max(1);
+    let columns = downsample_columns(&magnitudes, width, height);
+
+    let peak_db = columns
+        .iter()
+        .flatten()
+        .fold(f32::NEG_INFINITY, |max, &v| max.max(v));
+
+    let mut pixels = vec![0u8; width * height * 3];
+    for (x, column) in columns.iter().enumerate() {
+        for (bin, &db) in column.iter().enumerate() {
+            let level = ((db - peak_db + DYNAMIC_RANGE_DB) / DYNAMIC_RANGE_DB).clamp(0.0, 1.0);
+            let y = height - 1 - bin;
+            let offset = (y * width + x) * 3;
+            pixels[offset..offset + 3].copy_from_slice(&colormap(level));
+        }
+    }
+
+    let file = File::create(path).map_err(|e| {
+        DaemonError::with_source(
+            ErrorCode::ModelInferenceFailed,
+            format!("Failed to create spectrogram {}", path.display()),
+            e,
+        )
+    })?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(|e| {
+            DaemonError::with_source(
+                ErrorCode::ModelInferenceFailed,
+                "Failed to encode spectrogram PNG",
+                e,
+            )
+        })?;
+
+    Ok(())
+}
+
+/// Computes Hann-windowed STFT magnitudes in decibels.
+///
+/// Returns one column per window, each holding the bins up to the display limit.
+fn stft_magnitudes_db(
+    samples: &[f32],
+    sample_rate: u32,
+    options: SpectrogramOptions,
+) -> Vec<Vec<f32>> {
+    let fft_size = options.fft_size.max(2);
+    let hop_size = options.hop_size.max(1);
+
+    let nyquist = sample_rate as f32 / 2.0;
+    let total_bins = fft_size / 2 + 1;
+    let display_bins = if nyquist > MAX_DISPLAY_HZ {
+        ((MAX_DISPLAY_HZ / nyquist) * total_bins as f32).ceil() as usize
+    } else {
+        total_bins
+    };
+
+    let window: Vec<f32> = (0..fft_size)
+        .map(|i| {
+            let phase = 2.0 * std::f32::consts::PI * i as f32 / fft_size as f32;
+            0.5 - 0.5 * phase.cos()
+        })
+        .collect();
+
+    let fft = RealFftPlanner::<f32>::new().plan_fft_forward(fft_size);
+    let mut input = fft.make_input_vec();
+    let mut spectrum = fft.make_output_vec();
+
+    let mut columns = Vec::new();
+    let mut start = 0;
+    loop {
+        for (i, slot) in input.iter_mut().enumerate() {
+            // Zero-pad the final partial window
+            *slot = samples.get(start + i).copied().unwrap_or(0.0) * window[i];
+        }
+        // Buffer lengths come from the plan, so processing cannot fail
+        fft.process(&mut input, &mut spectrum).ok();
+
+        columns.push(
+            spectrum[..display_bins]
+                .iter()
+                .map(|c| 20.0 * (c.norm() + 1e-10).log10())
+                .collect(),
+        );
+
+        start += hop_size;
+        if start >= samples.len() {
+            break;
+        }
+    }
+    columns
+}
+
+/// Averages STFT columns into at most `width` image columns.
+fn downsample_columns(columns: &[Vec<f32>], width: usize, height: usize) -> Vec<Vec<f32>> {
+    if columns.len() <= width {
+        return columns.to_vec();
+    }
+
+    (0..width)
+        .map(|x| {
+            let start = x * columns.len() / width;
+            let end = ((x + 1) * columns.len() / width).max(start + 1);
+            let group = &columns[start..end];
+            (0..height)
+                .map(|bin| group.iter().map(|c| c[bin]).sum::<f32>() / group.len() as f32)
+                .collect()
+        })
+        .collect()
+}
+
+/// Maps a level in `0.0..=1.0` to an RGB color.
+fn colormap(level: f32) -> [u8; 3] {
+    let scaled = level.clamp(0.0, 1.0) * (COLORMAP.len() - 1) as f32;
+    let index = (scaled.floor() as usize).min(COLORMAP.len() - 2);
+    let t = scaled - index as f32;
+    let (from, to) = (COLORMAP[index], COLORMAP[index + 1]);
+    [0, 1, 2].map(|c| (from[c] + (to[c] - from[c]) * t).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colormap_endpoints() {
+        assert_eq!(colormap(0.0), [0, 0, 4]);
+        assert_eq!(colormap(1.0), [252, 255, 164]);
+    }
+
+    #[test]
+    fn stft_finds_sine_peak() {
+        let sample_rate = 32000;
+        let freq = 1000.0;
+        let samples: Vec<f32> = (0..sample_rate)
+            .map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate as f32).sin())
+            .collect();
+
+        let columns = stft_magnitudes_db(&samples, sample_rate, SpectrogramOptions::default());
+        let column = &columns[columns.len() / 2];
+        let peak_bin = column
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, _)| i)
+            .unwrap();
+
+        let expected_bin = (freq * DEFAULT_FFT_SIZE as f32 / sample_rate as f32).round() as usize;
+        assert_eq!(peak_bin, expected_bin);
+        // Nyquist is exactly 16 kHz here, so every bin is kept
+        assert_eq!(column.len(), DEFAULT_FFT_SIZE / 2 + 1);
+    }
+
+    #[test]
+    fn writes_png_with_expected_dimensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("track.png");
+        let samples: Vec<f32> = (0..48000).map(|i| (i as f32 * 0.1).sin()).collect();
+        let options = SpectrogramOptions {
+            fft_size: 512,
+            hop_size: 256,
+        };
+
+        write_spectrogram_png_with_options(&samples, 48000, &path, options).unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        // 48 kHz audio shows bins up to 16 kHz: ceil(257 / 1.5)
+        assert_eq!(info.height, 172);
+        assert_eq!(info.width, 188);
+    }
+}
@@ -0,0 +1,73 @@
+//! Summary statistics for generated audio.
+//!
+//! Used to compare two renders of the same request, e.g. when checking a new
+//! daemon release for quality regressions.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::loudness::measure_loudness;
+
+/// Level and length of a mono signal.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AudioStats {
+    /// Length in seconds.
+    pub duration_sec: f32,
+    /// Largest absolute sample value.
+    pub peak: f32,
+    /// Root mean square level.
+    pub rms: f32,
+    /// Estimated loudness in LUFS (see [`measure_loudness`]).
+    pub loudness_lufs: f32,
+}
+
+impl AudioStats {
+    /// Computes statistics for `samples` at `sample_rate`.
+    pub fn from_samples(samples: &[f32], sample_rate: u32) -> Self {
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        let rms = if samples.is_empty() {
+            0.0
+        } else {
+            let mean_square =
+                samples.iter().map(|&s| s as f64 * s as f64).sum::<f64>() / samples.len() as f64;
+            mean_square.sqrt() as f32
+        };
+
+        Self {
+            duration_sec: samples.len() as f32 / sample_rate as f32,
+            peak,
+            rms,
+            loudness_lufs: measure_loudness(samples),
+        }
+    }
+
+    /// Returns `other - self` for every field.
+    pub fn delta(&self, other: &AudioStats) -> AudioStats {
+        AudioStats {
+            duration_sec: other.duration_sec - self.duration_sec,
+            peak: other.peak - self.peak,
+            rms: other.rms - self.rms,
+            loudness_lufs: other.loudness_lufs - self.loudness_lufs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_and_delta() {
+        let quiet = AudioStats::from_samples(&[0.25, -0.25, 0.25, -0.25], 4);
+        assert_eq!(quiet.duration_sec, 1.0);
+        assert_eq!(quiet.peak, 0.25);
+        assert!((quiet.rms - 0.25).abs() < 1e-6);
+
+        let loud = AudioStats::from_samples(&[0.5, -0.5, 0.5, -0.5, 0.5, -0.5, 0.5, -0.5], 4);
+        let delta = quiet.delta(&loud);
+        assert_eq!(delta.duration_sec, 1.0);
+        assert_eq!(delta.peak, 0.25);
+        // Doubling the amplitude adds about 6 dB
+        assert!((delta.loudness_lufs - 6.02).abs() < 0.01);
+    }
+}
@@ -0,0 +1,137 @@
+//! Trimming of near-silent padding at the ends of generated audio.
+//!
+//! Models sometimes pad the start or end of a track with near-silence. Only
+//! samples below the threshold count as silence, and only runs lasting at
+//! least the minimum silence duration are removed, so a quiet intro or a
+//! short pause before the first note is kept.
+
+use std::fmt;
+
+/// Default level below which samples count as silence (-60 dBFS).
+pub const DEFAULT_TRIM_THRESHOLD_DB: f32 = -60.0;
+
+/// Default shortest silent run that is trimmed (0.5 s).
+pub const DEFAULT_TRIM_MIN_SILENCE_SEC: f32 = 0.5;
+
+/// Settings for [`trim_silence`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SilenceTrim {
+    /// Level in dBFS below which samples count as silence.
+    pub threshold_db: f32,
+    /// Shortest silent run at either end that is trimmed, in seconds.
+    pub min_silence_sec: f32,
+}
+
+impl SilenceTrim {
+    /// Creates trim settings.
+    pub fn new(threshold_db: f32, min_silence_sec: f32) -> Self {
+        Self {
+            threshold_db,
+            min_silence_sec,
+        }
+    }
+
+    /// Returns a short description such as `trim_silence:-60dB/0.5s`, used in
+    /// provenance.
+    pub fn label(&self) -> String {
+        self.to_string()
+    }
+
+    /// Trims `samples` with these settings. See [`trim_silence`].
+    pub fn apply(&self, samples: &mut Vec<f32>, sample_rate: u32) -> usize {
+        trim_silence(samples, sample_rate, self.threshold_db, self.min_silence_sec)
+    }
+}
+
+impl Default for SilenceTrim {
+    fn default() -> Self {
+        Self::new(DEFAULT_TRIM_THRESHOLD_DB, DEFAULT_TRIM_MIN_SILENCE_SEC)
+    }
+}
+
+impl fmt::Display for SilenceTrim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "trim_silence:{}dB/{}s",
+            self.threshold_db, self.min_silence_sec
+        )
+    }
+}
+
+/// Removes leading and trailing samples below `threshold_db` dBFS.
+///
+/// Each end is trimmed only if its silent run lasts at least
+/// `min_silence_sec`. Audio that is silent throughout is left untouched.
+/// Returns the number of samples removed.
+pub fn trim_silence(
+    samples: &mut Vec<f32>,
+    sample_rate: u32,
+    threshold_db: f32,
+    min_silence_sec: f32,
+) -> usize {
+    let threshold = 10f32.powf(threshold_db / 20.0);
+    let is_sound = |s: &f32| s.abs() >= threshold;
+    let Some(first) = samples.iter().position(is_sound) else {
+        return 0;
+    };
+    let last = samples.iter().rposition(is_sound).unwrap_or(first);
+
+    let min_run = (min_silence_sec.max(0.0) * sample_rate as f32).round() as usize;
+    let start = if first >= min_run { first } else { 0 };
+    let trailing = samples.len() - 1 - last;
+    let end = if trailing >= min_run { last + 1 } else { samples.len() };
+
+    let removed = samples.len() - (end - start);
+    samples.truncate(end);
+    samples.drain(..start);
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seconds of `lead` and `tail` noise at -100 dBFS around `sound`
+    /// seconds of signal, at 1kHz.
+    fn padded(lead: f32, sound: f32, tail: f32) -> Vec<f32> {
+        let rate = 1000.0;
+        let noise = |i: usize| if i & 1 == 0 { 1e-5 } else { -1e-5 };
+        let mut samples: Vec<f32> = (0..(lead * rate) as usize).map(noise).collect();
+        samples.extend((0..(sound * rate) as usize).map(|i| 0.2 + 0.1 * (i as f32 * 0.7).sin()));
+        samples.extend((0..(tail * rate) as usize).map(noise));
+        samples
+    }
+
+    #[test]
+    fn trims_long_silence_at_both_ends() {
+        let mut samples = padded(1.0, 2.0, 1.5);
+        let removed = trim_silence(&mut samples, 1000, -60.0, 0.5);
+        assert_eq!(removed, 2500);
+        assert_eq!(samples.len(), 2000);
+        assert!(samples[0].abs() >= 1e-3 && samples[1999].abs() >= 1e-3);
+    }
+
+    #[test]
+    fn keeps_short_pauses_and_quiet_intros() {
+        // A pause shorter than the minimum stays
+        let mut samples = padded(0.2, 2.0, 1.0);
+        trim_silence(&mut samples, 1000, -60.0, 0.5);
+        assert_eq!(samples.len(), 2200);
+
+        // A soft intro above the threshold is sound, not silence
+        let mut intro: Vec<f32> = (0..1000).map(|i| 0.005 * (i as f32 * 0.3).sin()).collect();
+        intro.extend(padded(0.0, 1.0, 0.0));
+        let len = intro.len();
+        assert_eq!(trim_silence(&mut intro, 1000, -60.0, 0.5), 0);
+        assert_eq!(intro.len(), len);
+    }
+
+    #[test]
+    fn silent_audio_is_untouched() {
+        let mut samples = vec![0.0; 4000];
+        assert_eq!(SilenceTrim::default().apply(&mut samples, 1000), 0);
+        assert_eq!(samples.len(), 4000);
+        assert_eq!(SilenceTrim::default().label(), "trim_silence:-60dB/0.5s");
+    }
+}
@@ -2,11 +2,13 @@
 //!
 //! Writes audio samples to WAV format using the hound crate.
 
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
 use std::path::Path;
 
-use hound::{SampleFormat, WavSpec, WavWriter};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
 
-use crate::error::{DaemonError, Result};
+use crate::error::{DaemonError, ErrorCode, GenerationPhase, Result};
 
 /// Audio sample rate for MusicGen output (32kHz).
 pub const SAMPLE_RATE_MUSICGEN: u32 = 32000;
@@ -44,30 +46,37 @@ pub fn write_wav(samples: &[f32], path: &Path, sample_rate: u32) -> Result<()> {
         sample_format: SampleFormat::Float,
     };
 
-    let mut writer = WavWriter::create(path, spec).map_err(|e| {
-        DaemonError::model_inference_failed(format!("Failed to create WAV file: {}", e))
-    })?;
+    // Keep the hound error as the cause so it reaches clients as error details
+    let write_error = |action: &str, e: hound::Error| {
+        DaemonError::with_source(
+            ErrorCode::ModelInferenceFailed,
+            format!("Failed to {} {}", action, path.display()),
+            e,
+        )
+        .with_phase(GenerationPhase::Write)
+    };
+
+    let mut writer = WavWriter::create(path, spec).map_err(|e| write_error("create", e))?;
 
     for sample in samples {
         // Write same sample to both left and right channels
-        writer.write_sample(*sample).map_err(|e| {
-            DaemonError::model_inference_failed(format!("Failed to write sample: {}", e))
-        })?;
-        writer.write_sample(*sample).map_err(|e| {
-            DaemonError::model_inference_failed(format!("Failed to write sample: {}", e))
-        })?;
+        writer
+            .write_sample(*sample)
+            .map_err(|e| write_error("write samples to", e))?;
+        writer
+            .write_sample(*sample)
+            .map_err(|e| write_error("write samples to", e))?;
     }
 
-    writer.finalize().map_err(|e| {
-        DaemonError::model_inference_failed(format!("Failed to finalize WAV file: {}", e))
-    })?;
+    writer.finalize().map_err(|e| write_error("finalize", e))?;
 
     Ok(())
 }
 
 /// Writes audio samples to an in-memory WAV buffer.
 ///
-/// Returns the WAV file contents as a byte vector.
+/// Returns the WAV file contents as a byte vector, identical to what
+/// [`write_wav`] writes to disk for the same samples.
 pub fn write_wav_to_buffer(samples: &[f32], sample_rate: u32) -> Result<Vec<u8>> {
     let spec = WavSpec {
         channels: CHANNELS,
@@ -76,27 +85,222 @@ pub fn write_wav_to_buffer(samples: &[f32], sample_rate: u32) -> Result<Vec<u8>>
         sample_format: SampleFormat::Float,
     };
 
-    let mut buffer = Vec::new();
-    let cursor = std::io::Cursor::new(&mut buffer);
-    let buf_writer = std::io::BufWriter::new(cursor);
-
-    {
-        let mut writer = WavWriter::new(buf_writer, spec).map_err(|e| {
-            DaemonError::model_inference_failed(format!("Failed to create WAV writer: {}", e))
-        })?;
-
-        for sample in samples {
-            // Write same sample to both left and right channels
-            writer.write_sample(*sample).map_err(|e| {
-                DaemonError::model_inference_failed(format!("Failed to write sample: {}", e))
-            })?;
-            writer.write_sample(*sample).map_err(|e| {
-                DaemonError::model_inference_failed(format!("Failed to write sample: {}", e))
-            })?;
+    let write_error = |action: &str, e: hound::Error| {
+        DaemonError::with_source(
+            ErrorCode::ModelInferenceFailed,
+            format!("Failed to {} WAV buffer", action),
+            e,
+        )
+        .with_phase(GenerationPhase::Write)
+    };
+
+    let mut cursor = std::io::Cursor::new(Vec::new());
+    let mut writer = WavWriter::new(&mut cursor, spec).map_err(|e| write_error("create", e))?;
+
+    for sample in samples {
+        // Write same sample to both left and right channels
+        writer
+            .write_sample(*sample)
+            .map_err(|e| write_error("write samples to", e))?;
+        writer
+            .write_sample(*sample)
+            .map_err(|e| write_error("write samples to", e))?;
+    }
+
+    // Finalizing writes the RIFF and data chunk sizes into the header
+    writer.finalize().map_err(|e| write_error("finalize", e))?;
+
+    Ok(cursor.into_inner())
+}
+
+/// Appends a RIFF `LIST`/`INFO` chunk with an `ICMT` (comment) entry to a WAV file.
+///
+/// The chunk follows the audio data, where players and hound ignore it, and
+/// the RIFF size in the header is updated to include it.
+pub fn append_info_comment(path: &Path, comment: &str) -> Result<()> {
+    let io_error = |action: &str, e: std::io::Error| {
+        DaemonError::with_source(
+            ErrorCode::ModelInferenceFailed,
+            format!("Failed to {} {}", action, path.display()),
+            e,
+        )
+        .with_phase(GenerationPhase::Write)
+    };
+
+    // ICMT text is NUL-terminated and every chunk is padded to an even size
+    let mut text = comment.as_bytes().to_vec();
+    text.push(0);
+    let text_len = text.len() as u32;
+    if text.len() % 2 == 1 {
+        text.push(0);
+    }
+
+    let mut chunk = Vec::with_capacity(text.len() + 20);
+    chunk.extend_from_slice(b"LIST");
+    chunk.extend_from_slice(&(text.len() as u32 + 12).to_le_bytes());
+    chunk.extend_from_slice(b"INFO");
+    chunk.extend_from_slice(b"ICMT");
+    chunk.extend_from_slice(&text_len.to_le_bytes());
+    chunk.extend_from_slice(&text);
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|e| io_error("open", e))?;
+    let file_len = file
+        .seek(SeekFrom::End(0))
+        .map_err(|e| io_error("seek in", e))?;
+    file.write_all(&chunk)
+        .map_err(|e| io_error("append metadata to", e))?;
+
+    let riff_size = (file_len + chunk.len() as u64 - 8) as u32;
+    file.seek(SeekFrom::Start(4))
+        .and_then(|_| file.write_all(&riff_size.to_le_bytes()))
+        .map_err(|e| io_error("update header of", e))?;
+
+    Ok(())
+}
+
+/// Reads the `ICMT` comment from a WAV file's `LIST`/`INFO` chunk, if present.
+pub fn read_info_comment(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let body = bytes.get(offset + 8..offset + 8 + size)?;
+        if id == b"LIST" && body.starts_with(b"INFO") {
+            let mut sub = 4;
+            while sub + 8 <= body.len() {
+                let sub_id = &body[sub..sub + 4];
+                let sub_size =
+                    u32::from_le_bytes(body[sub + 4..sub + 8].try_into().ok()?) as usize;
+                let value = body.get(sub + 8..sub + 8 + sub_size)?;
+                if sub_id == b"ICMT" {
+                    let text = value.split(|&b| b == 0).next().unwrap_or_default();
+                    return String::from_utf8(text.to_vec()).ok();
+                }
+                sub += 8 + sub_size + sub_size % 2;
+            }
         }
+        offset += 8 + size + size % 2;
     }
+    None
+}
+
+/// Reads a WAV file written by [`write_wav`] back into mono samples.
+///
+/// Returns the samples of the first channel and the sample rate.
+pub fn read_wav(path: &Path) -> Result<(Vec<f32>, u32)> {
+    let read_error = |e: hound::Error| {
+        DaemonError::with_source(
+            ErrorCode::ModelInferenceFailed,
+            format!("Failed to read {}", path.display()),
+            e,
+        )
+    };
+
+    let mut reader = WavReader::open(path).map_err(read_error)?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+    let samples = reader
+        .samples::<f32>()
+        .step_by(channels)
+        .collect::<std::result::Result<Vec<f32>, _>>()
+        .map_err(read_error)?;
+
+    Ok((samples, spec.sample_rate))
+}
+
+/// Absolute sample value at or above which audio counts as clipped.
+pub const CLIPPING_THRESHOLD: f32 = 0.999;
+
+/// Absolute sample value below which a sample counts as silent.
+pub const SILENCE_THRESHOLD: f32 = 0.001;
+
+/// Fraction of silent samples above which a file is rejected as a likely
+/// failed generation.
+pub const MAX_SILENCE_RATIO: f32 = 0.95;
+
+/// Properties of a WAV file checked by [`WavValidator`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WavValidationReport {
+    /// Length in seconds.
+    pub duration_sec: f32,
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Number of channels.
+    pub channels: u16,
+    /// Number of samples per channel.
+    pub sample_count: usize,
+    /// Largest absolute sample value over all channels.
+    pub peak_amplitude: f32,
+    /// Whether any sample reaches [`CLIPPING_THRESHOLD`].
+    pub has_clipping: bool,
+    /// Fraction of samples below [`SILENCE_THRESHOLD`].
+    pub silence_ratio: f32,
+}
+
+/// Checks that a written WAV file holds usable audio.
+pub struct WavValidator;
+
+impl WavValidator {
+    /// Reads the WAV at `path` and reports on its contents.
+    ///
+    /// Fails if the file cannot be read, has no samples, or is almost
+    /// entirely silent (more than [`MAX_SILENCE_RATIO`]), which usually
+    /// means generation went wrong.
+    pub fn validate(path: &Path) -> Result<WavValidationReport> {
+        let invalid = |reason: String| {
+            DaemonError::model_inference_failed(format!("{}: {}", path.display(), reason))
+                .with_phase(GenerationPhase::Write)
+        };
+        let read_error = |e: hound::Error| {
+            DaemonError::model_inference_failed_with_source(
+                format!("Failed to read {}", path.display()),
+                e,
+            )
+            .with_phase(GenerationPhase::Write)
+        };
+
+        let mut reader = WavReader::open(path).map_err(read_error)?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            SampleFormat::Float => reader
+                .samples::<f32>()
+                .collect::<std::result::Result<Vec<f32>, _>>(),
+            SampleFormat::Int => {
+                let scale = (1u64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 / scale))
+                    .collect()
+            }
+        }
+        .map_err(read_error)?;
+        if samples.is_empty() {
+            return Err(invalid("file has no samples".to_string()));
+        }
 
-    Ok(buffer)
+        let peak_amplitude = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        let silent = samples.iter().filter(|s| s.abs() < SILENCE_THRESHOLD).count();
+        let silence_ratio = silent as f32 / samples.len() as f32;
+        if silence_ratio > MAX_SILENCE_RATIO {
+            return Err(invalid(format!("{:.0}% of the audio is silent", silence_ratio * 100.0)));
+        }
+
+        let sample_count = samples.len() / spec.channels.max(1) as usize;
+        Ok(WavValidationReport {
+            duration_sec: samples_to_duration(sample_count, spec.sample_rate),
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+            sample_count,
+            peak_amplitude,
+            has_clipping: peak_amplitude >= CLIPPING_THRESHOLD,
+            silence_ratio,
+        })
+    }
 }
 
 /// Calculates the duration of audio in seconds from sample count.
@@ -137,6 +341,85 @@ mod tests {
         assert_eq!(&buffer[0..4], b"RIFF");
     }
 
+    #[test]
+    fn write_wav_to_buffer_parses_back() {
+        let samples = vec![0.0f32, 0.25, -0.75, 1.0, -1.0];
+        let buffer = write_wav_to_buffer(&samples, SAMPLE_RATE_ACE_STEP).unwrap();
+
+        let mut reader = hound::WavReader::new(std::io::Cursor::new(buffer)).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.channels, CHANNELS);
+        assert_eq!(spec.sample_rate, SAMPLE_RATE_ACE_STEP);
+        assert_eq!(spec.sample_format, SampleFormat::Float);
+        assert_eq!(reader.duration() as usize, samples.len());
+
+        let decoded: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+        let expected: Vec<f32> = samples.iter().flat_map(|&s| [s, s]).collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn write_wav_to_buffer_matches_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.wav");
+
+        let samples: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.01).sin()).collect();
+        write_wav(&samples, &path, SAMPLE_RATE).unwrap();
+        let buffer = write_wav_to_buffer(&samples, SAMPLE_RATE).unwrap();
+
+        assert_eq!(buffer, std::fs::read(&path).unwrap());
+    }
+
+    #[test]
+    fn info_comment_round_trips() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.wav");
+        let samples = vec![0.0f32, 0.5, -0.5, 0.25];
+        write_wav(&samples, &path, SAMPLE_RATE).unwrap();
+        assert_eq!(read_info_comment(&path), None);
+
+        // Odd-length text exercises the chunk padding
+        let comment = r#"{"daemon_version":"0.1.0"}"#;
+        append_info_comment(&path, comment).unwrap();
+        assert_eq!(read_info_comment(&path).as_deref(), Some(comment));
+
+        // RIFF size covers the whole file and the audio still decodes
+        let bytes = std::fs::read(&path).unwrap();
+        let riff_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        assert_eq!(riff_size, bytes.len() - 8);
+        let (decoded, sample_rate) = read_wav(&path).unwrap();
+        assert_eq!(decoded, samples);
+        assert_eq!(sample_rate, SAMPLE_RATE);
+    }
+
+    #[test]
+    fn validator_reports_and_rejects_silence() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.wav");
+
+        let mut samples = vec![0.5f32; 900];
+        samples.extend([0.0; 99]);
+        samples.push(-1.0);
+        write_wav(&samples, &path, SAMPLE_RATE).unwrap();
+        let report = WavValidator::validate(&path).unwrap();
+        assert_eq!(report.sample_rate, SAMPLE_RATE);
+        assert_eq!(report.channels, CHANNELS);
+        assert_eq!(report.sample_count, 1000);
+        assert_eq!(report.duration_sec, samples_to_duration(1000, SAMPLE_RATE));
+        assert_eq!(report.peak_amplitude, 1.0);
+        assert!(report.has_clipping);
+        assert!((report.silence_ratio - 0.099).abs() < 1e-6);
+
+        write_wav(&[0.0005; 1000], &path, SAMPLE_RATE).unwrap();
+        let err = WavValidator::validate(&path).unwrap_err();
+        assert_eq!(err.code, ErrorCode::ModelInferenceFailed);
+        assert!(err.message.contains("silent"));
+
+        write_wav(&[], &path, SAMPLE_RATE).unwrap();
+        assert!(WavValidator::validate(&path).unwrap_err().message.contains("no samples"));
+        assert!(WavValidator::validate(&dir.path().join("missing.wav")).is_err());
+    }
+
     #[test]
     fn samples_to_duration_calculation() {
         assert_eq!(samples_to_duration(32000, 32000), 1.0);
@@ -0,0 +1,274 @@
+//! Lock files that keep concurrent daemon instances apart.
+//!
+//! The daemon holds `daemon.lock` in the cache directory for as long as it
+//! runs, and the model downloader holds a `<file>.partial.lock` next to each
+//! file it is downloading. Locks are advisory OS file locks, so they are
+//! released when their holder exits, even if it crashes. Each lock file
+//! records the holder's PID and start time. A lock file whose OS lock is free
+//! but which still names a process that is no longer alive was left behind by
+//! a crash and is reclaimed.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+
+use crate::error::Result;
+
+use super::now_playing::write_error;
+
+/// File name of the lock a daemon instance holds in the cache directory.
+pub const INSTANCE_LOCK_FILE: &str = "daemon.lock";
+
+/// How often acquiring retries when the lock file is replaced under it.
+const MAX_LOCK_ATTEMPTS: usize = 3;
+
+/// Process recorded in a lock file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockOwner {
+    /// Process ID of the holder.
+    pub pid: u32,
+
+    /// When the holder took the lock, in seconds since the Unix epoch.
+    pub started_at: u64,
+}
+
+impl LockOwner {
+    /// Returns the owner record for the current process.
+    pub fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+
+    /// Reads the owner recorded in the lock file at `path`.
+    ///
+    /// Returns None if the file is missing or does not hold a record, e.g.
+    /// because its holder is still writing it.
+    pub fn read(path: &Path) -> Option<Self> {
+        let bytes = std::fs::read(path).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    /// Returns true if the recorded process is still running.
+    pub fn is_alive(&self) -> bool {
+        if self.pid == std::process::id() {
+            return true;
+        }
+        let pid = Pid::from_u32(self.pid);
+        let mut system = System::new();
+        system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+        system.process(pid).is_some()
+    }
+
+    /// Returns how long ago the holder took the lock, in seconds.
+    pub fn age_sec(&self) -> u64 {
+        LockOwner::current().started_at.saturating_sub(self.started_at)
+    }
+}
+
+/// Outcome of trying to take a lock.
+#[derive(Debug)]
+pub enum LockAttempt {
+    /// The lock was taken and is held until the [`FileLock`] is dropped.
+    Acquired(FileLock),
+    /// A live process holds the lock. The owner is None if its record could
+    /// not be read.
+    Held(Option<LockOwner>),
+}
+
+/// An exclusive lock on a lock file, released and deleted when dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Tries to take the lock at `path` without waiting, creating the file
+    /// if needed, and records the current process in it.
+    ///
+    /// A lock file left behind by a process that is no longer alive is
+    /// reclaimed once its OS lock can be taken. A file whose OS lock is still
+    /// held is never reclaimed, whatever PID it records.
+    pub fn try_acquire(path: &Path) -> Result<LockAttempt> {
+        for _ in 0..MAX_LOCK_ATTEMPTS {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+                .map_err(|e| write_error(path, e))?;
+
+            if let Err(e) = file.try_lock_exclusive() {
+                if e.raw_os_error() == fs2::lock_contended_error().raw_os_error() {
+                    // Whoever holds the OS lock is alive, even if its recorded
+                    // PID is not visible to us, e.g. from another PID namespace
+                    return Ok(LockAttempt::Held(LockOwner::read(path)));
+                }
+                return Err(write_error(path, e));
+            }
+
+            // The previous holder may have deleted the file between our open
+            // and lock, leaving us holding a lock nobody else can see
+            if !same_file(&file, path) {
+                continue;
+            }
+
+            if let Some(previous) = LockOwner::read(path) {
+                if !previous.is_alive() {
+                    eprintln!(
+                        "Reclaiming stale lock {} of PID {}",
+                        path.display(),
+                        previous.pid
+                    );
+                }
+            }
+            let lock = Self {
+                file,
+                path: path.to_path_buf(),
+            };
+            lock.write_owner(LockOwner::current())?;
+            return Ok(LockAttempt::Acquired(lock));
+        }
+        Ok(LockAttempt::Held(LockOwner::read(path)))
+    }
+
+    /// Returns the path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write_owner(&self, owner: LockOwner) -> Result<()> {
+        let json = serde_json::to_vec(&owner).unwrap();
+        let mut file = &self.file;
+        file.set_len(0)
+            .and_then(|()| file.write_all(&json))
+            .and_then(|()| file.sync_all())
+            .map_err(|e| write_error(&self.path, e))
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Delete while still holding the lock so no one locks a file that
+        // is about to disappear
+        let _ = std::fs::remove_file(&self.path);
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Tries to take the daemon instance lock in `cache_dir`, creating the
+/// directory if needed.
+pub fn acquire_instance_lock(cache_dir: &Path) -> Result<LockAttempt> {
+    std::fs::create_dir_all(cache_dir).map_err(|e| write_error(cache_dir, e))?;
+    FileLock::try_acquire(&cache_dir.join(INSTANCE_LOCK_FILE))
+}
+
+/// Returns true if `file` is still the file at `path`.
+#[cfg(unix)]
+fn same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(open), Ok(current)) => open.dev() == current.dev() && open.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+/// Returns true if `file` is still the file at `path`.
+#[cfg(not(unix))]
+fn same_file(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PID above Linux's `pid_max`, so never a running process.
+    const DEAD_PID: u32 = u32::MAX - 1;
+
+    fn acquired(attempt: LockAttempt) -> FileLock {
+        match attempt {
+            LockAttempt::Acquired(lock) => lock,
+            LockAttempt::Held(owner) => panic!("lock held by {:?}", owner),
+        }
+    }
+
+    #[test]
+    fn second_instance_sees_the_holder() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = acquired(acquire_instance_lock(dir.path()).unwrap());
+        assert_eq!(lock.path(), dir.path().join(INSTANCE_LOCK_FILE));
+        let owner = LockOwner::read(lock.path()).unwrap();
+        assert_eq!(owner.pid, std::process::id());
+        assert!(owner.is_alive());
+
+        // Each acquire opens the file anew, so a second one in this process
+        // contends like another instance would
+        match acquire_instance_lock(dir.path()).unwrap() {
+            LockAttempt::Held(Some(holder)) => assert_eq!(holder, owner),
+            other => panic!("expected the lock to be held, got {:?}", other),
+        }
+
+        drop(lock);
+        assert!(!dir.path().join(INSTANCE_LOCK_FILE).exists());
+        let lock = acquired(acquire_instance_lock(dir.path()).unwrap());
+        assert!(lock.path().exists());
+    }
+
+    #[test]
+    fn stale_locks_are_reclaimed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INSTANCE_LOCK_FILE);
+        let dead = LockOwner {
+            pid: DEAD_PID,
+            started_at: 1,
+        };
+        assert!(!dead.is_alive());
+
+        // Left behind by a crashed instance: the file remains, the OS lock is gone
+        std::fs::write(&path, serde_json::to_vec(&dead).unwrap()).unwrap();
+        let lock = acquired(FileLock::try_acquire(&path).unwrap());
+        assert_eq!(LockOwner::read(&path).unwrap().pid, std::process::id());
+        drop(lock);
+
+        // Still locked although the recorded process looks gone, e.g. a
+        // holder in another PID namespace: the file must be left alone
+        std::fs::write(&path, serde_json::to_vec(&dead).unwrap()).unwrap();
+        let holder = File::open(&path).unwrap();
+        holder.try_lock_exclusive().unwrap();
+        match FileLock::try_acquire(&path).unwrap() {
+            LockAttempt::Held(Some(owner)) => assert_eq!(owner, dead),
+            other => panic!("expected the lock to be held, got {:?}", other),
+        }
+        assert_eq!(LockOwner::read(&path).unwrap(), dead);
+
+        // Reclaimed once the holder lets go
+        FileExt::unlock(&holder).unwrap();
+        let lock = acquired(FileLock::try_acquire(&path).unwrap());
+        assert_eq!(LockOwner::read(lock.path()).unwrap().pid, std::process::id());
+    }
+
+    #[test]
+    fn unreadable_owner_is_reported_as_unknown() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.onnx.partial.lock");
+        let other = File::create(&path).unwrap();
+        other.try_lock_exclusive().unwrap();
+        assert!(matches!(
+            FileLock::try_acquire(&path).unwrap(),
+            LockAttempt::Held(None)
+        ));
+    }
+}
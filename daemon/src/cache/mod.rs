@@ -1,8 +1,20 @@
 //! Cache module for track storage.
 //!
-//! Provides LRU-based caching for generated tracks.
+//! Provides LRU-based caching for generated tracks, persisted via JSON sidecars,
+//! the now-playing metadata written after each completed generation, the
+//! user's seed ratings per prompt, and the lock files that keep concurrent
+//! daemon instances from writing the same files.
 
+pub mod lock;
+pub mod now_playing;
+pub mod ratings;
 pub mod tracks;
 
 // Re-export commonly used types
-pub use tracks::TrackCache;
+pub use lock::{acquire_instance_lock, FileLock, LockAttempt, LockOwner, INSTANCE_LOCK_FILE};
+pub use now_playing::{read_history, read_now_playing, record_now_playing, NowPlaying};
+pub use ratings::{Ratings, SeedRating, MAX_RATING, MIN_RATING, RATINGS_FILE};
+pub use tracks::{
+    avoid_reserved_names, delete_track_files, save_sidecar, sidecar_path, DiskUsage, PruneReport,
+    TrackCache, RESERVED_FILES,
+};
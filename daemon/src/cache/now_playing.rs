@@ -0,0 +1,272 @@
+//! Now-playing metadata for status bars and editor integrations.
+//!
+//! After every completed generation the daemon writes `now_playing.json`
+//! describing the track and appends the same entry to `history.jsonl`, which
+//! keeps only the most recent entries. Both files are replaced atomically
+//! (written to a temporary file, then renamed), so a reader never sees a
+//! partially written file.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::AudioStats;
+use crate::error::{DaemonError, Result};
+use crate::types::{DisplayPrompt, Track};
+
+/// File name of the current track's metadata.
+pub const NOW_PLAYING_FILE: &str = "now_playing.json";
+
+/// File name of the completed track history, one JSON entry per line.
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// A completed track as written to `now_playing.json` and `history.jsonl`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NowPlaying {
+    /// Track metadata. The prompt is shortened and hashed when prompt
+    /// redaction is enabled.
+    pub track: Track,
+
+    /// Level and length of the generated audio.
+    pub audio: AudioStats,
+
+    /// Absolute path to the WAV file.
+    pub wav_path: PathBuf,
+
+    /// When the generation completed (Unix timestamp in seconds). Differs
+    /// from `track.created_at` when a cached track was returned.
+    pub completed_at: u64,
+}
+
+impl NowPlaying {
+    /// Creates an entry for `track`, completed now.
+    pub fn new(track: &Track, audio: AudioStats, redact_prompts: bool) -> Self {
+        let mut track = track.clone();
+        if redact_prompts {
+            track.prompt = DisplayPrompt::with_redaction(&track.prompt, true).to_string();
+            track.prompt_blend = None;
+        }
+        let wav_path = if track.path.is_absolute() {
+            track.path.clone()
+        } else {
+            std::env::current_dir()
+                .map(|dir| dir.join(&track.path))
+                .unwrap_or_else(|_| track.path.clone())
+        };
+        let completed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            track,
+            audio,
+            wav_path,
+            completed_at,
+        }
+    }
+}
+
+/// Writes `entry` to `now_playing.json` in `dir` and appends it to
+/// `history.jsonl`, keeping at most `max_history` entries.
+///
+/// Creates `dir` if needed.
+pub fn record_now_playing(dir: &Path, entry: &NowPlaying, max_history: usize) -> Result<()> {
+    std::fs::create_dir_all(dir).map_err(|e| write_error(dir, e))?;
+
+    let json = serde_json::to_vec_pretty(entry).map_err(serialize_error)?;
+    write_atomic(&dir.join(NOW_PLAYING_FILE), &json)?;
+
+    let mut history = read_history_lines(dir)?;
+    history.push(serde_json::to_string(entry).map_err(serialize_error)?);
+    let excess = history.len().saturating_sub(max_history);
+    let mut contents = history[excess..].join("\n");
+    contents.push('\n');
+    write_atomic(&dir.join(HISTORY_FILE), contents.as_bytes())
+}
+
+/// Reads `now_playing.json` from `dir`.
+///
+/// Returns None if no track has completed yet.
+pub fn read_now_playing(dir: &Path) -> Result<Option<NowPlaying>> {
+    let path = dir.join(NOW_PLAYING_FILE);
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(read_error(&path, e)),
+    };
+    serde_json::from_slice(&bytes)
+        .map(Some)
+        .map_err(|e| read_error(&path, e))
+}
+
+/// Reads up to `limit` entries from `history.jsonl` in `dir`, newest first.
+///
+/// Lines that cannot be parsed are skipped.
+pub fn read_history(dir: &Path, limit: usize) -> Result<Vec<NowPlaying>> {
+    Ok(read_history_lines(dir)?
+        .iter()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit)
+        .collect())
+}
+
+/// Returns the non-empty lines of `history.jsonl`, oldest first.
+fn read_history_lines(dir: &Path) -> Result<Vec<String>> {
+    let path = dir.join(HISTORY_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(read_error(&path, e)),
+    }
+}
+
+/// Replaces `path` with `contents` by writing a temporary file next to it
+/// and renaming it into place.
+pub(super) fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    std::fs::write(&tmp_path, contents).map_err(|e| write_error(&tmp_path, e))?;
+    std::fs::rename(&tmp_path, path).map_err(|e| {
+        std::fs::remove_file(&tmp_path).ok();
+        write_error(path, e)
+    })
+}
+
+fn serialize_error(e: serde_json::Error) -> DaemonError {
+    DaemonError::storage_failed(format!("Failed to serialize now playing metadata: {}", e))
+}
+
+pub(super) fn write_error(path: &Path, e: std::io::Error) -> DaemonError {
+    DaemonError::storage_failed(format!("Failed to write {}: {}", path.display(), e))
+}
+
+pub(super) fn read_error(path: &Path, e: impl std::fmt::Display) -> DaemonError {
+    DaemonError::storage_failed(format!("Failed to read {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Backend;
+
+    fn entry(seed: u64) -> NowPlaying {
+        let track = Track::new(
+            PathBuf::from("/tmp/track.wav"),
+            "lofi hip hop beats to relax to".to_string(),
+            10.0,
+            seed,
+            "musicgen-small-fp16-v1".to_string(),
+            Backend::MusicGen,
+            4.2,
+        );
+        let audio = AudioStats::from_samples(&[0.25, -0.25, 0.25, -0.25], 4);
+        NowPlaying::new(&track, audio, false)
+    }
+
+    #[test]
+    fn now_playing_json_shape() {
+        let dir = tempfile::tempdir().unwrap();
+        record_now_playing(dir.path(), &entry(42), 10).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.path().join(NOW_PLAYING_FILE)).unwrap())
+                .unwrap();
+        assert_eq!(json["track"]["seed"], 42);
+        assert_eq!(json["track"]["prompt"], "lofi hip hop beats to relax to");
+        assert_eq!(json["wav_path"], "/tmp/track.wav");
+        assert_eq!(json["audio"]["peak"], 0.25);
+        assert!(json["completed_at"].as_u64().unwrap() > 0);
+
+        let read = read_now_playing(dir.path()).unwrap().unwrap();
+        assert_eq!(read.track.seed, 42);
+        assert!(!dir.path().join("now_playing.json.tmp").exists());
+    }
+
+    #[test]
+    fn missing_files_read_as_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_now_playing(dir.path()).unwrap().is_none());
+        assert!(read_history(dir.path(), 10).unwrap().is_empty());
+
+        // Unreadable files are storage errors, not inference errors
+        std::fs::write(dir.path().join(NOW_PLAYING_FILE), b"{ truncated").unwrap();
+        let err = read_now_playing(dir.path()).unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::StorageFailed);
+    }
+
+    #[test]
+    fn redaction_hides_the_prompt() {
+        let track = entry(1).track;
+        let redacted = NowPlaying::new(&track, entry(1).audio, true);
+        assert!(!redacted.track.prompt.contains("relax"));
+        assert!(redacted.track.prompt.starts_with("lofi hip"));
+    }
+
+    #[test]
+    fn history_is_capped_and_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        for seed in 0..5 {
+            record_now_playing(dir.path(), &entry(seed), 3).unwrap();
+        }
+
+        let contents = std::fs::read_to_string(dir.path().join(HISTORY_FILE)).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+
+        let seeds: Vec<u64> = read_history(dir.path(), 10)
+            .unwrap()
+            .iter()
+            .map(|e| e.track.seed)
+            .collect();
+        assert_eq!(seeds, vec![4, 3, 2]);
+
+        let seeds: Vec<u64> = read_history(dir.path(), 1)
+            .unwrap()
+            .iter()
+            .map(|e| e.track.seed)
+            .collect();
+        assert_eq!(seeds, vec![4]);
+        assert_eq!(read_now_playing(dir.path()).unwrap().unwrap().track.seed, 4);
+    }
+
+    #[test]
+    fn readers_never_see_partial_files() {
+        let dir = tempfile::tempdir().unwrap();
+        record_now_playing(dir.path(), &entry(0), 5).unwrap();
+
+        let reader_dir = dir.path().to_path_buf();
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let reader_done = done.clone();
+        let reader = std::thread::spawn(move || {
+            let mut reads = 0;
+            loop {
+                let finished = reader_done.load(std::sync::atomic::Ordering::Relaxed);
+                let bytes = std::fs::read(reader_dir.join(NOW_PLAYING_FILE)).unwrap();
+                serde_json::from_slice::<NowPlaying>(&bytes).unwrap();
+                let history = std::fs::read_to_string(reader_dir.join(HISTORY_FILE)).unwrap();
+                for line in history.lines() {
+                    serde_json::from_str::<NowPlaying>(line).unwrap();
+                }
+                reads += 1;
+                if finished {
+                    return reads;
+                }
+            }
+        });
+
+        for seed in 1..200 {
+            record_now_playing(dir.path(), &entry(seed), 5).unwrap();
+        }
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert!(reader.join().unwrap() > 0);
+    }
+}
@@ -0,0 +1,187 @@
+//! User ratings of generated tracks, remembered per prompt and seed.
+//!
+//! Rating a track records its seed under the prompt's [`prompt_hash`] in
+//! `ratings.json` in the cache directory. The file outlives the tracks
+//! themselves, so a well-rated seed can be reused after its track has been
+//! evicted. The file is replaced atomically, like `now_playing.json`.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{DaemonError, Result};
+use crate::models::Backend;
+use crate::types::prompt_hash;
+
+use super::now_playing::{read_error, write_atomic, write_error};
+
+/// File name of the per-prompt seed ratings.
+pub const RATINGS_FILE: &str = "ratings.json";
+
+/// Lowest rating: the track sounds bad.
+pub const MIN_RATING: i8 = -1;
+
+/// Highest rating: the track sounds good.
+pub const MAX_RATING: i8 = 1;
+
+/// A rated seed of a prompt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SeedRating {
+    /// Backend the seed was generated with.
+    pub backend: Backend,
+
+    /// Rated seed.
+    pub seed: u64,
+
+    /// Rating from -1 (bad) to 1 (good); 0 is neutral.
+    pub rating: i8,
+}
+
+/// Seed ratings keyed by prompt hash, as stored in `ratings.json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Ratings {
+    /// Rated seeds of each prompt in the order they were rated, which
+    /// [`Ratings::best_seed`] relies on to break ties by recency.
+    prompts: BTreeMap<String, Vec<SeedRating>>,
+}
+
+impl Ratings {
+    /// Reads `ratings.json` from `dir`.
+    ///
+    /// A missing file holds no ratings.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(RATINGS_FILE);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(read_error(&path, e)),
+        };
+        serde_json::from_slice(&bytes).map_err(|e| read_error(&path, e))
+    }
+
+    /// Writes `ratings.json` to `dir`, creating `dir` if needed.
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir).map_err(|e| write_error(dir, e))?;
+        let json = serde_json::to_vec_pretty(self).map_err(|e| {
+            DaemonError::storage_failed(format!("Failed to serialize ratings: {}", e))
+        })?;
+        write_atomic(&dir.join(RATINGS_FILE), &json)
+    }
+
+    /// Records `rating` for `seed` of `prompt`, replacing an earlier rating
+    /// of the same seed and backend.
+    pub fn record(&mut self, prompt: &str, backend: Backend, seed: u64, rating: i8) {
+        let seeds = self.prompts.entry(prompt_hash(prompt)).or_default();
+        seeds.retain(|entry| entry.backend != backend || entry.seed != seed);
+        seeds.push(SeedRating { backend, seed, rating });
+    }
+
+    /// Returns the rated seeds of `prompt`, oldest rating first.
+    pub fn for_prompt(&self, prompt: &str) -> &[SeedRating] {
+        self.prompts
+            .get(&prompt_hash(prompt))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns the best positively rated seed of `prompt` on `backend`.
+    ///
+    /// Among equally rated seeds the most recently rated one wins.
+    pub fn best_seed(&self, prompt: &str, backend: Backend) -> Option<u64> {
+        self.best_seeds(prompt, backend).first().copied()
+    }
+
+    /// Returns the seeds of `prompt` on `backend` that share the highest
+    /// positive rating, most recently rated first.
+    pub fn best_seeds(&self, prompt: &str, backend: Backend) -> Vec<u64> {
+        let rated: Vec<&SeedRating> = self
+            .for_prompt(prompt)
+            .iter()
+            .filter(|entry| entry.backend == backend && entry.rating > 0)
+            .collect();
+        let Some(best) = rated.iter().map(|entry| entry.rating).max() else {
+            return Vec::new();
+        };
+        rated
+            .iter()
+            .rev()
+            .filter(|entry| entry.rating == best)
+            .map(|entry| entry.seed)
+            .collect()
+    }
+
+    /// Returns true if `seed` of `prompt` on `backend` was rated below neutral.
+    pub fn is_rated_down(&self, prompt: &str, backend: Backend, seed: u64) -> bool {
+        self.for_prompt(prompt)
+            .iter()
+            .any(|entry| entry.backend == backend && entry.seed == seed && entry.rating < 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ratings_survive_a_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Ratings::load(dir.path()).unwrap(), Ratings::default());
+
+        let mut ratings = Ratings::default();
+        ratings.record("rainy night", Backend::MusicGen, 7, 1);
+        ratings.record("rainy night", Backend::MusicGen, 8, -1);
+        ratings.record("rainy night", Backend::MusicGen, 7, 0);
+        ratings.save(dir.path()).unwrap();
+
+        let reloaded = Ratings::load(dir.path()).unwrap();
+        assert_eq!(reloaded, ratings);
+        assert_eq!(
+            reloaded.for_prompt("rainy night"),
+            [
+                SeedRating { backend: Backend::MusicGen, seed: 8, rating: -1 },
+                SeedRating { backend: Backend::MusicGen, seed: 7, rating: 0 },
+            ]
+        );
+        assert!(reloaded.for_prompt("sunny day").is_empty());
+    }
+
+    #[test]
+    fn best_seed_prefers_highest_then_latest_rating() {
+        let mut ratings = Ratings::default();
+        assert_eq!(ratings.best_seed("rain", Backend::MusicGen), None);
+
+        ratings.record("rain", Backend::MusicGen, 1, 0);
+        ratings.record("rain", Backend::MusicGen, 2, -1);
+        assert_eq!(ratings.best_seed("rain", Backend::MusicGen), None);
+        assert!(ratings.is_rated_down("rain", Backend::MusicGen, 2));
+        assert!(!ratings.is_rated_down("rain", Backend::AceStep, 2));
+
+        ratings.record("rain", Backend::MusicGen, 3, 1);
+        ratings.record("rain", Backend::MusicGen, 4, 1);
+        ratings.record("rain", Backend::AceStep, 5, 1);
+        assert_eq!(ratings.best_seed("rain", Backend::MusicGen), Some(4));
+        assert_eq!(ratings.best_seeds("rain", Backend::MusicGen), [4, 3]);
+        assert_eq!(ratings.best_seed("rain", Backend::AceStep), Some(5));
+    }
+
+    #[test]
+    fn best_seed_ties_go_to_the_latest_rating_not_the_largest_seed() {
+        let mut ratings = Ratings::default();
+        ratings.record("rain", Backend::MusicGen, 90, 1);
+        ratings.record("rain", Backend::MusicGen, 10, 1);
+        assert_eq!(ratings.best_seed("rain", Backend::MusicGen), Some(10));
+
+        // Rating a seed again makes it the most recent
+        ratings.record("rain", Backend::MusicGen, 90, 1);
+        assert_eq!(ratings.best_seed("rain", Backend::MusicGen), Some(90));
+
+        // The order survives a reload
+        let dir = tempfile::tempdir().unwrap();
+        ratings.save(dir.path()).unwrap();
+        let reloaded = Ratings::load(dir.path()).unwrap();
+        assert_eq!(reloaded.best_seed("rain", Backend::MusicGen), Some(90));
+    }
+}
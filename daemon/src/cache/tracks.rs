@@ -1,21 +1,136 @@
 //! Track cache with LRU eviction.
 //!
 //! Provides in-memory caching of generated tracks with hash-based deduplication.
+//! Eviction takes the lowest-rated tracks first, and the least recently used
+//! among those, so tracks the user liked are kept longest.
+//! Track metadata is persisted as JSON sidecars (`<track_id>.json`) next to each
+//! WAV so the cache survives restarts.
 
-use std::collections::HashMap;
-use std::time::Instant;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::error::{DaemonError, Result};
+use crate::models::Backend;
 use crate::types::Track;
 
+use super::now_playing::NOW_PLAYING_FILE;
+use super::ratings::RATINGS_FILE;
+
 /// Maximum number of tracks to keep in cache.
 const DEFAULT_MAX_ENTRIES: usize = 100;
 
+/// Minimum age of a WAV without sidecar before it is treated as orphaned.
+/// Younger files may belong to a generation that is still being written.
+const ORPHAN_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Minimum age of an unindexed WAV before [`TrackCache::prune_orphans`]
+/// deletes it. Younger files may still be written by another daemon process.
+const PRUNE_MIN_AGE: Duration = Duration::from_secs(60);
+
+/// Files kept in the cache directory next to the tracks, which a sidecar
+/// must never replace.
+pub const RESERVED_FILES: [&str; 2] = [NOW_PLAYING_FILE, RATINGS_FILE];
+
+/// Returns the metadata sidecar path for a track's WAV file.
+pub fn sidecar_path(wav_path: &Path) -> PathBuf {
+    wav_path.with_extension("json")
+}
+
+/// Returns `wav_path`, with `-track` appended to its file stem if its sidecar
+/// would be one of the [`RESERVED_FILES`].
+///
+/// A `{prompt}` filename template names the track for a prompt like
+/// "ratings" `ratings.wav`, whose sidecar would overwrite the ratings. Case
+/// is ignored, since some filesystems do too.
+pub fn avoid_reserved_names(wav_path: &Path) -> PathBuf {
+    let sidecar = sidecar_path(wav_path);
+    let reserved = sidecar.file_name().and_then(|name| name.to_str()).is_some_and(|name| {
+        RESERVED_FILES
+            .iter()
+            .any(|reserved| name.eq_ignore_ascii_case(reserved))
+    });
+    if !reserved {
+        return wav_path.to_path_buf();
+    }
+    let stem = wav_path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match wav_path.extension() {
+        Some(extension) => format!("{}-track.{}", stem, extension.to_string_lossy()),
+        None => format!("{}-track", stem),
+    };
+    wav_path.with_file_name(name)
+}
+
+/// Writes a track's metadata sidecar next to its WAV file.
+pub fn save_sidecar(track: &Track) -> Result<()> {
+    let path = sidecar_path(&track.path);
+    let json = serde_json::to_vec_pretty(track).map_err(|e| {
+        DaemonError::storage_failed(format!("Failed to serialize track metadata: {}", e))
+    })?;
+    std::fs::write(&path, json).map_err(|e| {
+        DaemonError::storage_failed(format!(
+            "Failed to write track metadata {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Deletes a track's WAV file along with its sidecar and thumbnail.
+///
+/// Files that are already gone are ignored; other failures are logged.
+pub fn delete_track_files(track: &Track) {
+    for path in track_files(&track.path) {
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("Failed to delete {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Returns the files belonging to a track: its WAV, sidecar and thumbnail.
+fn track_files(wav_path: &Path) -> [PathBuf; 3] {
+    [
+        wav_path.to_path_buf(),
+        sidecar_path(wav_path),
+        wav_path.with_extension("png"),
+    ]
+}
+
+/// WAV files in the cache directory that no cached track refers to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Orphaned WAV files, deleted unless the prune was a dry run.
+    pub files: Vec<PathBuf>,
+    /// Combined size of the WAVs and their sidecars and thumbnails in bytes.
+    pub bytes_freed: u64,
+}
+
+/// Files in the cache directory, including those of no cached track.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// Number of files in the cache directory.
+    pub file_count: usize,
+    /// Combined size of those files in bytes.
+    pub total_bytes: u64,
+    /// Orphaned WAV files that [`TrackCache::prune_orphans`] would delete.
+    pub orphan_count: usize,
+    /// Combined size of the orphans and their sidecars and thumbnails in bytes.
+    pub orphan_bytes: u64,
+}
+
 /// Track cache with LRU eviction policy.
 pub struct TrackCache {
     /// Tracks indexed by track_id.
     tracks: HashMap<String, CacheEntry>,
+    /// `(created_at, track_id)` of every cached track, in creation order.
+    /// The track ID keeps tracks created at the same instant apart.
+    by_created_at: BTreeSet<(SystemTime, String)>,
     /// Maximum number of entries to keep.
     max_entries: usize,
+    /// Maximum combined file size in bytes, if bounded.
+    max_bytes: Option<u64>,
 }
 
 /// A cached track with access timestamp.
@@ -24,6 +139,14 @@ struct CacheEntry {
     last_accessed: Instant,
 }
 
+impl CacheEntry {
+    /// Orders entries for eviction: lowest rating first (unrated counts as
+    /// neutral), then least recently used.
+    fn eviction_key(&self) -> (i8, Instant) {
+        (self.track.rating.unwrap_or(0), self.last_accessed)
+    }
+}
+
 impl TrackCache {
     /// Creates a new cache with default capacity.
     pub fn new() -> Self {
@@ -34,10 +157,53 @@ impl TrackCache {
     pub fn with_capacity(max_entries: usize) -> Self {
         Self {
             tracks: HashMap::new(),
+            by_created_at: BTreeSet::new(),
             max_entries,
+            max_bytes: None,
         }
     }
 
+    /// Returns the byte budget, if any.
+    pub fn max_bytes(&self) -> Option<u64> {
+        self.max_bytes
+    }
+
+    /// Sets the byte budget and evicts least recently used tracks until the
+    /// cache fits within it.
+    ///
+    /// Returns the evicted tracks.
+    pub fn set_max_bytes(&mut self, max_bytes: Option<u64>) -> Vec<Track> {
+        self.max_bytes = max_bytes;
+        self.enforce_byte_budget(None)
+    }
+
+    /// Evicts the lowest-rated, least recently used tracks while over the
+    /// byte budget.
+    ///
+    /// The track named by `keep` is never evicted, so a single track larger
+    /// than the budget still remains available.
+    fn enforce_byte_budget(&mut self, keep: Option<&str>) -> Vec<Track> {
+        let Some(max_bytes) = self.max_bytes else {
+            return Vec::new();
+        };
+
+        let mut evicted = Vec::new();
+        while self.total_size_bytes() > max_bytes {
+            let oldest_key = self
+                .tracks
+                .iter()
+                .filter(|(k, _)| Some(k.as_str()) != keep)
+                .min_by_key(|(_, entry)| entry.eviction_key())
+                .map(|(k, _)| k.clone());
+
+            match oldest_key.and_then(|k| self.remove(&k)) {
+                Some(track) => evicted.push(track),
+                None => break,
+            }
+        }
+        evicted
+    }
+
     /// Returns a track by ID, updating its access time.
     pub fn get(&mut self, track_id: &str) -> Option<&Track> {
         if let Some(entry) = self.tracks.get_mut(track_id) {
@@ -50,7 +216,9 @@ impl TrackCache {
 
     /// Inserts a track into the cache.
     ///
-    /// If the cache is full, the least recently used entry is evicted first.
+    /// If the cache is full, the least recently used of the lowest-rated
+    /// entries is evicted first.
+    /// If a byte budget is set, older entries are evicted until it fits.
     pub fn put(&mut self, track: Track) {
         // Evict if at capacity and this is a new entry
         if self.tracks.len() >= self.max_entries && !self.tracks.contains_key(&track.track_id) {
@@ -58,13 +226,86 @@ impl TrackCache {
         }
 
         let track_id = track.track_id.clone();
+        if let Some(existing) = self.tracks.get(&track_id) {
+            self.by_created_at
+                .remove(&(existing.track.created_at, track_id.clone()));
+        }
+        self.by_created_at.insert((track.created_at, track_id.clone()));
         self.tracks.insert(
-            track_id,
+            track_id.clone(),
             CacheEntry {
                 track,
                 last_accessed: Instant::now(),
             },
         );
+        self.enforce_byte_budget(Some(&track_id));
+    }
+
+    /// Returns a track by ID without updating its access time.
+    pub fn peek(&self, track_id: &str) -> Option<&Track> {
+        self.tracks.get(track_id).map(|entry| &entry.track)
+    }
+
+    /// Returns an iterator over all cached tracks in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = &Track> {
+        self.tracks.values().map(|entry| &entry.track)
+    }
+
+    /// Returns up to `n` tracks, most recently created first.
+    pub fn most_recent(&self, n: usize) -> Vec<&Track> {
+        self.by_created_at
+            .iter()
+            .rev()
+            .take(n)
+            .filter_map(|(_, track_id)| self.peek(track_id))
+            .collect()
+    }
+
+    /// Returns up to `n` tracks, least recently created first.
+    pub fn oldest_n(&self, n: usize) -> Vec<&Track> {
+        self.by_created_at
+            .iter()
+            .take(n)
+            .filter_map(|(_, track_id)| self.peek(track_id))
+            .collect()
+    }
+
+    /// Returns the creation times of the oldest and newest cached tracks.
+    pub fn created_at_range(&self) -> Option<(SystemTime, SystemTime)> {
+        let (oldest, _) = self.by_created_at.first()?;
+        let (newest, _) = self.by_created_at.last()?;
+        Some((*oldest, *newest))
+    }
+
+    /// Returns all cached tracks with the given tag (case-insensitive).
+    pub fn find_by_tag(&self, tag: &str) -> Vec<&Track> {
+        self.iter().filter(|track| track.has_tag(tag)).collect()
+    }
+
+    /// Returns the combined size of all cached track files in bytes.
+    pub fn total_size_bytes(&self) -> u64 {
+        self.iter().map(|track| track.file_size_bytes).sum()
+    }
+
+    /// Returns the number of cached tracks generated by `backend`.
+    pub fn count_by_backend(&self, backend: Backend) -> usize {
+        self.iter().filter(|track| track.backend == backend).count()
+    }
+
+    /// Returns the combined duration of the tracks generated by `backend` in seconds.
+    pub fn total_duration_by_backend(&self, backend: Backend) -> f32 {
+        self.iter()
+            .filter(|track| track.backend == backend)
+            .map(|track| track.duration_sec)
+            .sum()
+    }
+
+    /// Returns the combined file size of the tracks generated by `backend` in bytes.
+    pub fn total_size_bytes_by_backend(&self, backend: Backend) -> u64 {
+        self.iter()
+            .filter(|track| track.backend == backend)
+            .map(|track| track.file_size_bytes)
+            .sum()
     }
 
     /// Checks if a track ID exists in the cache.
@@ -82,7 +323,7 @@ impl TrackCache {
         self.tracks.is_empty()
     }
 
-    /// Evicts the least recently used entry.
+    /// Evicts the least recently used entry among the lowest-rated ones.
     ///
     /// Returns the evicted track if any.
     pub fn evict_lru(&mut self) -> Option<Track> {
@@ -90,27 +331,231 @@ impl TrackCache {
             return None;
         }
 
-        // Find the entry with the oldest access time
+        // Find the lowest-rated entry with the oldest access time
         let oldest_key = self
             .tracks
             .iter()
-            .min_by_key(|(_, entry)| entry.last_accessed)
+            .min_by_key(|(_, entry)| entry.eviction_key())
             .map(|(k, _)| k.clone())?;
 
-        self.tracks.remove(&oldest_key).map(|entry| entry.track)
+        self.remove(&oldest_key)
+    }
+
+    /// Sets the user rating of a cached track without updating its access time.
+    ///
+    /// Returns the updated track, or None if it is not cached.
+    pub fn set_rating(&mut self, track_id: &str, rating: i8) -> Option<&Track> {
+        let entry = self.tracks.get_mut(track_id)?;
+        entry.track.rating = Some(rating);
+        Some(&entry.track)
     }
 
     /// Removes a specific track from the cache.
     pub fn remove(&mut self, track_id: &str) -> Option<Track> {
-        self.tracks.remove(track_id).map(|entry| entry.track)
+        let entry = self.tracks.remove(track_id)?;
+        self.by_created_at
+            .remove(&(entry.track.created_at, entry.track.track_id.clone()));
+        Some(entry.track)
+    }
+
+    /// Removes every track generated by `backend`.
+    ///
+    /// Files are left on disk; pass the returned tracks to
+    /// [`delete_track_files`] to remove them as well.
+    pub fn invalidate_backend(&mut self, backend: Backend) -> Vec<Track> {
+        let track_ids: Vec<String> = self
+            .iter()
+            .filter(|track| track.backend == backend)
+            .map(|track| track.track_id.clone())
+            .collect();
+
+        track_ids
+            .iter()
+            .filter_map(|track_id| self.remove(track_id))
+            .collect()
     }
 
     /// Clears all entries from the cache.
     pub fn clear(&mut self) {
         self.tracks.clear();
+        self.by_created_at.clear();
+    }
+
+    /// Loads tracks from the JSON sidecars in `cache_dir`.
+    ///
+    /// Sidecars that cannot be parsed or whose WAV no longer exists are skipped,
+    /// as is the now-playing file, which may share the directory.
+    /// Returns the number of tracks loaded.
+    pub fn load_sidecars(&mut self, cache_dir: &Path) -> Result<usize> {
+        let mut loaded = 0;
+        for path in list_files(cache_dir, "json")? {
+            if path
+                .file_name()
+                .is_some_and(|name| RESERVED_FILES.iter().any(|reserved| name == *reserved))
+            {
+                continue;
+            }
+            let track: Track = match std::fs::read(&path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            {
+                Some(track) => track,
+                None => {
+                    eprintln!("Skipping unreadable track metadata {}", path.display());
+                    continue;
+                }
+            };
+            if track.path.exists() {
+                self.put(track);
+                loaded += 1;
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Saves sidecars for all cached tracks, then deletes orphaned WAV files.
+    ///
+    /// A WAV in `cache_dir` is orphaned if it has no sidecar and is older than
+    /// one hour, which happens when the daemon stops between writing the audio
+    /// and caching the track. Its spectrogram thumbnail is removed as well.
+    /// Returns the number of WAV files deleted.
+    pub fn save_and_evict_orphaned(&self, cache_dir: &Path) -> Result<usize> {
+        for track in self.iter() {
+            if let Err(e) = save_sidecar(track) {
+                eprintln!("{}", e);
+            }
+        }
+
+        let now = SystemTime::now();
+        let mut deleted = 0;
+        for wav_path in list_files(cache_dir, "wav")? {
+            if sidecar_path(&wav_path).exists() {
+                continue;
+            }
+
+            let Some(age) = std::fs::metadata(&wav_path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+            else {
+                continue;
+            };
+            if age < ORPHAN_MIN_AGE {
+                continue;
+            }
+
+            if std::fs::remove_file(&wav_path).is_ok() {
+                eprintln!("Deleted orphaned track {}", wav_path.display());
+                std::fs::remove_file(wav_path.with_extension("png")).ok();
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Deletes WAV files in `cache_dir` that no cached track refers to.
+    ///
+    /// Tracks evicted from the cache keep their files on disk, so without
+    /// pruning the directory grows past the cache's limits. A WAV is removed
+    /// together with its sidecar and thumbnail; WAVs modified within the last
+    /// minute are kept. With `dry_run` set, nothing is deleted and the report
+    /// lists what would be.
+    pub fn prune_orphans(&self, cache_dir: &Path, dry_run: bool) -> Result<PruneReport> {
+        let indexed: HashSet<&std::ffi::OsStr> =
+            self.iter().filter_map(|track| track.path.file_name()).collect();
+
+        let now = SystemTime::now();
+        let mut report = PruneReport::default();
+        let mut orphans = list_files(cache_dir, "wav")?;
+        orphans.sort();
+        for wav_path in orphans {
+            if wav_path.file_name().is_some_and(|name| indexed.contains(name)) {
+                continue;
+            }
+
+            let Some(age) = std::fs::metadata(&wav_path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+            else {
+                continue;
+            };
+            if age < PRUNE_MIN_AGE {
+                continue;
+            }
+
+            let files = track_files(&wav_path);
+            let bytes: u64 = files
+                .iter()
+                .filter_map(|path| std::fs::metadata(path).ok())
+                .map(|m| m.len())
+                .sum();
+            if !dry_run {
+                if let Err(e) = std::fs::remove_file(&wav_path) {
+                    eprintln!("Failed to delete {}: {}", wav_path.display(), e);
+                    continue;
+                }
+                for path in &files[1..] {
+                    std::fs::remove_file(path).ok();
+                }
+            }
+            report.files.push(wav_path);
+            report.bytes_freed += bytes;
+        }
+        Ok(report)
+    }
+
+    /// Measures the files in `cache_dir`.
+    ///
+    /// Unlike [`total_size_bytes`](Self::total_size_bytes), this counts
+    /// sidecars, thumbnails and orphaned WAVs, so it reads the whole directory.
+    pub fn disk_usage(&self, cache_dir: &Path) -> Result<DiskUsage> {
+        let orphans = self.prune_orphans(cache_dir, true)?;
+        let sizes: Vec<u64> = list_all_files(cache_dir)?
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|m| m.len())
+            .collect();
+        Ok(DiskUsage {
+            file_count: sizes.len(),
+            total_bytes: sizes.iter().sum(),
+            orphan_count: orphans.files.len(),
+            orphan_bytes: orphans.bytes_freed,
+        })
     }
 }
 
+/// Lists the files in `dir` with the given extension.
+///
+/// A missing directory has no files.
+fn list_files(dir: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+    Ok(list_all_files(dir)?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+        .collect())
+}
+
+/// Lists the files in `dir`. A missing directory has no files.
+fn list_all_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(DaemonError::storage_failed(format!(
+                "Failed to read cache directory {}: {}",
+                dir.display(),
+                e
+            )))
+        }
+    };
+
+    Ok(entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect())
+}
+
 impl Default for TrackCache {
     fn default() -> Self {
         Self::new()
@@ -120,7 +565,7 @@ impl Default for TrackCache {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::Backend;
+    use crate::types::Provenance;
     use std::thread;
     use std::time::Duration;
 
@@ -131,13 +576,21 @@ mod tests {
             track_id: id.to_string(),
             path: PathBuf::from(format!("/path/to/{}.wav", id)),
             prompt: "test prompt".to_string(),
+            prompt_blend: None,
             duration_sec: 10.0,
             sample_rate: 32000,
+            reverb: None,
+            trimmed_sec: None,
             seed: 12345,
             model_version: "musicgen-small-fp16-v1".to_string(),
             backend: Backend::MusicGen,
             generation_time_sec: 25.0,
+            file_size_bytes: 1024,
+            tags: Vec::new(),
+            rating: None,
+            derived_from_seed: None,
             created_at: SystemTime::now(),
+            provenance: None,
         }
     }
 
@@ -188,6 +641,62 @@ mod tests {
         assert!(cache.contains("third"));
     }
 
+    #[test]
+    fn eviction_keeps_highly_rated_tracks() {
+        let mut cache = TrackCache::with_capacity(3);
+        for id in ["liked", "unrated", "disliked"] {
+            cache.put(make_track(id));
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(cache.set_rating("liked", 1).unwrap().rating, Some(1));
+        cache.set_rating("disliked", -1);
+        assert!(cache.set_rating("missing", 1).is_none());
+
+        // The disliked track goes first even though it was used most recently,
+        // then the unrated one, although the liked track is older
+        cache.put(make_track("d"));
+        assert!(!cache.contains("disliked"));
+        thread::sleep(Duration::from_millis(5));
+        cache.put(make_track("e"));
+        assert!(!cache.contains("unrated"));
+        assert!(cache.contains("liked"));
+
+        // Among equally rated tracks the least recently used goes
+        cache.put(make_track("f"));
+        assert!(!cache.contains("d"));
+        assert!(cache.contains("liked") && cache.contains("e") && cache.contains("f"));
+    }
+
+    #[test]
+    fn most_recent_orders_by_creation_time() {
+        let mut cache = TrackCache::new();
+        let base = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        // Inserted out of creation order
+        for (id, offset) in [("c", 30), ("a", 10), ("e", 50), ("b", 20), ("d", 40)] {
+            let mut track = make_track(id);
+            track.created_at = base + Duration::from_secs(offset);
+            cache.put(track);
+        }
+
+        let ids = |tracks: Vec<&Track>| -> Vec<String> {
+            tracks.iter().map(|track| track.track_id.clone()).collect()
+        };
+        assert_eq!(ids(cache.most_recent(3)), ["e", "d", "c"]);
+        assert_eq!(ids(cache.oldest_n(2)), ["a", "b"]);
+        assert_eq!(cache.most_recent(10).len(), 5);
+
+        // Removal and re-insertion keep the index in sync
+        cache.remove("e");
+        let mut track = make_track("a");
+        track.created_at = base + Duration::from_secs(60);
+        cache.put(track);
+        assert_eq!(ids(cache.most_recent(3)), ["a", "d", "c"]);
+        assert_eq!(ids(cache.oldest_n(1)), ["b"]);
+
+        cache.clear();
+        assert!(cache.most_recent(3).is_empty());
+    }
+
     #[test]
     fn remove_track() {
         let mut cache = TrackCache::new();
@@ -209,4 +718,244 @@ mod tests {
 
         assert!(cache.is_empty());
     }
+
+    #[test]
+    fn total_size_sums_all_tracks() {
+        let mut cache = TrackCache::new();
+        cache.put(make_track("a"));
+        cache.put(make_track("b"));
+
+        assert_eq!(cache.iter().count(), 2);
+        assert_eq!(cache.total_size_bytes(), 2048);
+    }
+
+    #[test]
+    fn find_by_tag_filters_tracks() {
+        let mut cache = TrackCache::new();
+        cache.put(make_track("a").with_tags(vec!["lofi".to_string()]));
+        cache.put(make_track("b").with_tags(vec!["jazz".to_string()]));
+        cache.put(make_track("c").with_tags(vec!["Lofi".to_string(), "rain".to_string()]));
+
+        let mut ids: Vec<&str> = cache
+            .find_by_tag("LOFI")
+            .iter()
+            .map(|t| t.track_id.as_str())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "c"]);
+        assert!(cache.find_by_tag("ambient").is_empty());
+    }
+
+    /// Writes an empty WAV in `dir` and backdates it by `age`.
+    fn write_wav_with_age(dir: &Path, name: &str, age: Duration) -> PathBuf {
+        let path = dir.join(name);
+        let file = std::fs::File::create(&path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+        path
+    }
+
+    #[test]
+    fn sidecars_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut track = make_track("abc").with_provenance(
+            Provenance::new("CPU")
+                .with_post_processing("loudness:-14.0")
+                .with_timing("generate", 3.5),
+        );
+        track.path = write_wav_with_age(dir.path(), "abc.wav", Duration::ZERO);
+        save_sidecar(&track).unwrap();
+
+        let mut cache = TrackCache::new();
+        assert_eq!(cache.load_sidecars(dir.path()).unwrap(), 1);
+        assert_eq!(cache.peek("abc").unwrap().path, track.path);
+        assert_eq!(cache.peek("abc").unwrap().provenance, track.provenance);
+
+        // Ratings are saved with the sidecar, and ratings.json is not a track
+        cache.set_rating("abc", 1);
+        save_sidecar(cache.peek("abc").unwrap()).unwrap();
+        std::fs::write(dir.path().join(RATINGS_FILE), b"{}").unwrap();
+        let mut reloaded = TrackCache::new();
+        assert_eq!(reloaded.load_sidecars(dir.path()).unwrap(), 1);
+        assert_eq!(reloaded.peek("abc").unwrap().rating, Some(1));
+
+        // Sidecars of deleted WAVs are ignored
+        std::fs::remove_file(&track.path).unwrap();
+        let mut cache = TrackCache::new();
+        assert_eq!(cache.load_sidecars(dir.path()).unwrap(), 0);
+
+        // Failed writes are storage errors, not inference errors
+        track.path = dir.path().join("missing").join("abc.wav");
+        let err = save_sidecar(&track).unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::StorageFailed);
+    }
+
+    #[test]
+    fn evicts_only_old_orphans() {
+        let dir = tempfile::tempdir().unwrap();
+        let hours = Duration::from_secs(2 * 60 * 60);
+
+        let orphan = write_wav_with_age(dir.path(), "orphan.wav", hours);
+        std::fs::write(dir.path().join("orphan.png"), b"png").unwrap();
+        let in_progress = write_wav_with_age(dir.path(), "new.wav", Duration::ZERO);
+
+        let mut cache = TrackCache::new();
+        let mut track = make_track("kept");
+        track.path = write_wav_with_age(dir.path(), "kept.wav", hours);
+        cache.put(track);
+
+        assert_eq!(cache.save_and_evict_orphaned(dir.path()).unwrap(), 1);
+        assert!(!orphan.exists());
+        assert!(!dir.path().join("orphan.png").exists());
+        assert!(in_progress.exists());
+        assert!(dir.path().join("kept.wav").exists());
+        assert!(dir.path().join("kept.json").exists());
+
+        // Missing cache directory is not an error
+        let missing = dir.path().join("missing");
+        assert_eq!(cache.save_and_evict_orphaned(&missing).unwrap(), 0);
+    }
+
+    #[test]
+    fn prunes_unindexed_wavs() {
+        let dir = tempfile::tempdir().unwrap();
+        let minutes = Duration::from_secs(5 * 60);
+
+        // Evicted track: WAV and sidecar remain but the cache forgot it
+        let evicted = dir.path().join("evicted.wav");
+        std::fs::write(&evicted, [0u8; 100]).unwrap();
+        let file = std::fs::File::options().write(true).open(&evicted).unwrap();
+        file.set_modified(SystemTime::now() - minutes).unwrap();
+        std::fs::write(dir.path().join("evicted.json"), b"{}").unwrap();
+        std::fs::write(dir.path().join("evicted.png"), b"png").unwrap();
+        let in_progress = write_wav_with_age(dir.path(), "new.wav", Duration::ZERO);
+
+        let mut cache = TrackCache::new();
+        let mut track = make_track("kept");
+        track.path = write_wav_with_age(dir.path(), "kept.wav", minutes);
+        cache.put(track);
+
+        let report = cache.prune_orphans(dir.path(), true).unwrap();
+        assert_eq!(report.files, vec![evicted.clone()]);
+        assert_eq!(report.bytes_freed, 105);
+        assert!(evicted.exists());
+
+        assert_eq!(cache.prune_orphans(dir.path(), false).unwrap(), report);
+        assert!(!evicted.exists());
+        assert!(!dir.path().join("evicted.json").exists());
+        assert!(!dir.path().join("evicted.png").exists());
+        assert!(in_progress.exists());
+        assert!(dir.path().join("kept.wav").exists());
+
+        assert!(cache.prune_orphans(dir.path(), false).unwrap().files.is_empty());
+        let missing = dir.path().join("missing");
+        assert_eq!(cache.prune_orphans(&missing, false).unwrap(), PruneReport::default());
+    }
+
+    #[test]
+    fn disk_usage_counts_every_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let minutes = Duration::from_secs(5 * 60);
+        let orphan = dir.path().join("orphan.wav");
+        std::fs::write(&orphan, [0u8; 100]).unwrap();
+        let file = std::fs::File::options().write(true).open(&orphan).unwrap();
+        file.set_modified(SystemTime::now() - minutes).unwrap();
+        std::fs::write(dir.path().join("kept.wav"), [0u8; 40]).unwrap();
+        std::fs::write(dir.path().join("kept.json"), b"{}").unwrap();
+
+        let mut cache = TrackCache::new();
+        assert_eq!(cache.created_at_range(), None);
+        let mut track = make_track("kept");
+        track.path = dir.path().join("kept.wav");
+        cache.put(track);
+
+        let usage = cache.disk_usage(dir.path()).unwrap();
+        assert_eq!(
+            usage,
+            DiskUsage {
+                file_count: 3,
+                total_bytes: 142,
+                orphan_count: 1,
+                orphan_bytes: 100,
+            }
+        );
+        let created_at = cache.peek("kept").unwrap().created_at;
+        assert_eq!(cache.created_at_range(), Some((created_at, created_at)));
+    }
+
+    #[test]
+    fn shrinking_byte_budget_evicts_lru() {
+        let mut cache = TrackCache::new();
+        cache.put(make_track("first"));
+        thread::sleep(Duration::from_millis(10));
+        cache.put(make_track("second"));
+        thread::sleep(Duration::from_millis(10));
+        cache.put(make_track("third"));
+
+        let evicted = cache.set_max_bytes(Some(2048));
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].track_id, "first");
+        assert_eq!(cache.len(), 2);
+
+        // New tracks keep the cache within budget
+        cache.put(make_track("fourth"));
+        assert!(!cache.contains("second"));
+        assert!(cache.contains("fourth"));
+        assert_eq!(cache.total_size_bytes(), 2048);
+    }
+
+    #[test]
+    fn per_backend_stats() {
+        let mut cache = TrackCache::new();
+        for (i, duration) in [10.0, 20.0, 30.0].into_iter().enumerate() {
+            let mut track = make_track(&format!("musicgen{}", i));
+            track.duration_sec = duration;
+            cache.put(track);
+        }
+        for (i, duration) in [60.0, 120.0].into_iter().enumerate() {
+            let mut track = make_track(&format!("ace_step{}", i));
+            track.backend = Backend::AceStep;
+            track.duration_sec = duration;
+            track.file_size_bytes = 4096;
+            cache.put(track);
+        }
+
+        assert_eq!(cache.count_by_backend(Backend::MusicGen), 3);
+        assert_eq!(cache.count_by_backend(Backend::AceStep), 2);
+        assert_eq!(cache.total_duration_by_backend(Backend::MusicGen), 60.0);
+        assert_eq!(cache.total_duration_by_backend(Backend::AceStep), 180.0);
+        assert_eq!(cache.total_size_bytes_by_backend(Backend::MusicGen), 3 * 1024);
+        assert_eq!(cache.total_size_bytes_by_backend(Backend::AceStep), 2 * 4096);
+        assert_eq!(
+            cache.total_size_bytes(),
+            cache.total_size_bytes_by_backend(Backend::MusicGen)
+                + cache.total_size_bytes_by_backend(Backend::AceStep)
+        );
+
+        assert_eq!(TrackCache::new().count_by_backend(Backend::AceStep), 0);
+        assert_eq!(TrackCache::new().total_duration_by_backend(Backend::AceStep), 0.0);
+    }
+
+    #[test]
+    fn invalidate_backend_removes_only_that_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = TrackCache::new();
+        cache.put(make_track("musicgen"));
+        let mut ace_step = make_track("ace_step");
+        ace_step.backend = Backend::AceStep;
+        ace_step.path = write_wav_with_age(dir.path(), "ace_step.wav", Duration::ZERO);
+        save_sidecar(&ace_step).unwrap();
+        cache.put(ace_step);
+
+        let removed = cache.invalidate_backend(Backend::AceStep);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].track_id, "ace_step");
+        assert!(cache.contains("musicgen"));
+        assert!(!cache.contains("ace_step"));
+        assert!(removed[0].path.exists());
+
+        delete_track_files(&removed[0]);
+        assert!(!removed[0].path.exists());
+        assert!(!sidecar_path(&removed[0].path).exists());
+        assert!(cache.invalidate_backend(Backend::AceStep).is_empty());
+    }
 }
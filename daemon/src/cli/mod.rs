@@ -0,0 +1,1401 @@
+//! CLI argument parser for Phase 0 standalone mode.
+//!
+//! Provides command-line interface for testing music generation
+//! without the full daemon infrastructure.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use serde::Serialize;
+
+use crate::audio::{Reverb, ReverbPreset};
+use crate::config::{token_dump_path, AceStepConfig, DaemonConfig, ParamStrictness};
+use crate::error::DaemonError;
+use crate::models::ace_step::{
+    SchedulerType, MAX_GUIDANCE_SCALE, MAX_INFERENCE_STEPS, MIN_GUIDANCE_SCALE,
+    MIN_INFERENCE_STEPS,
+};
+use crate::models::Backend;
+use crate::output::{prepare_output_path, OutputTemplate, TemplateValues};
+#[cfg(feature = "metrics-http")]
+use crate::rpc::metrics_http::DEFAULT_METRICS_ADDR;
+use crate::types::{
+    clamp_duration, clamp_guidance_scale, clamp_inference_steps, Manifest, ParamAdjustment, StepsParam,
+};
+
+/// Available generation backends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BackendArg {
+    /// MusicGen: 5-30 second autoregressive generation at 32kHz
+    #[default]
+    Musicgen,
+    /// ACE-Step: 5-240 second diffusion generation at 48kHz
+    AceStep,
+}
+
+/// Available scheduler types for ACE-Step diffusion.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SchedulerArg {
+    /// Euler: Fast, deterministic ODE solver (1 model eval per step)
+    #[default]
+    Euler,
+    /// Heun: More accurate 2nd-order solver (2 model evals per step, 2x slower)
+    Heun,
+    /// PingPong: Stochastic SDE solver (best quality, adds noise each step)
+    Pingpong,
+}
+
+impl SchedulerArg {
+    /// Returns the scheduler name used in configs and generation parameters.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SchedulerArg::Euler => "euler",
+            SchedulerArg::Heun => "heun",
+            SchedulerArg::Pingpong => "pingpong",
+        }
+    }
+}
+
+/// Output path that writes the WAV to stdout instead of a file.
+pub const STDOUT_OUTPUT: &str = "-";
+
+/// Number of token frames generated per second of audio.
+/// MusicGen generates approximately 50 tokens per second.
+pub const TOKENS_PER_SECOND: usize = 50;
+
+/// Longest duration MusicGen produces reliably in CLI mode, in seconds.
+pub const MUSICGEN_CLI_MAX_DURATION_SEC: u32 = 30;
+
+/// lofi-daemon: AI music generation with MusicGen and ACE-Step backends
+#[derive(Parser, Debug, Clone)]
+#[command(name = "lofi-daemon")]
+#[command(about = "AI music generation daemon with MusicGen and ACE-Step backends")]
+#[command(version)]
+pub struct Cli {
+    /// Text prompt describing the music to generate
+    #[arg(short, long)]
+    pub prompt: Option<String>,
+
+    /// Duration of audio to generate in seconds (5-240 for ACE-Step, 5-30 for MusicGen)
+    #[arg(short, long, default_value = "10", value_parser = clap::value_parser!(u32).range(5..=240))]
+    pub duration: u32,
+
+    /// Output WAV file path, or "-" to write the WAV to stdout. Supports
+    /// {prompt}, {seed}, {duration}, {backend} and {track_id} placeholders and
+    /// %Y, %m, %d, %H, %M, %S, %F and %T (%H-%M-%S) date specifiers (UTC);
+    /// missing directories are created
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Replace an existing output file instead of appending -1, -2, ... to the name
+    #[arg(long)]
+    pub overwrite: bool,
+
+    /// Path to directory containing ONNX model files
+    #[arg(short, long)]
+    pub model_dir: Option<PathBuf>,
+
+    /// Random seed for reproducible generation
+    #[arg(short, long)]
+    pub seed: Option<u64>,
+
+    /// Generation backend to use
+    #[arg(short, long, value_enum, default_value_t = BackendArg::Musicgen)]
+    pub backend: BackendArg,
+
+    /// Number of diffusion steps (ACE-Step only, 1-200, default 60), or
+    /// "auto" to choose them from the duration
+    #[arg(long, default_value_t = StepsParam::Fixed(AceStepConfig::default().inference_steps))]
+    pub steps: StepsParam,
+
+    /// Scheduler type for diffusion (ACE-Step only)
+    #[arg(long, value_enum, default_value_t = SchedulerArg::Euler)]
+    pub scheduler: SchedulerArg,
+
+    /// Guidance scale for classifier-free guidance (ACE-Step only, 1.0-20.0, default 7.0)
+    #[arg(long, default_value_t = AceStepConfig::default().guidance_scale, value_parser = parse_guidance_scale)]
+    pub guidance: f32,
+
+    /// Reject out-of-range --steps and --guidance (strict), or use the nearest
+    /// valid value (clamp)
+    #[arg(long, value_name = "MODE", default_value = "strict", value_parser = parse_param_strictness)]
+    pub param_strictness: ParamStrictness,
+
+    /// Add reverb to the output: room, hall or lofi-tape
+    #[arg(long, value_name = "PRESET", value_parser = parse_reverb_preset, conflicts_with = "daemon")]
+    pub reverb: Option<ReverbPreset>,
+
+    /// Reverb wet mix (0.0-1.0, defaults to the preset's mix)
+    #[arg(long, value_name = "MIX", value_parser = parse_reverb_wet, requires = "reverb")]
+    pub reverb_wet: Option<f32>,
+
+    /// Trim near-silent padding from the start and end of the output (see
+    /// trim_silence_threshold_db and trim_min_silence_sec in the config)
+    #[arg(long, conflicts_with = "daemon")]
+    pub trim_silence: bool,
+
+    /// On Ctrl+C, stop generating and save the audio produced so far
+    #[arg(long)]
+    pub keep_partial: bool,
+
+    /// Write the generated MusicGen token frames to a CSV file (debugging;
+    /// defaults to LOFI_DUMP_TOKENS)
+    #[arg(long, value_name = "FILE", conflicts_with = "daemon")]
+    pub dump_tokens: Option<PathBuf>,
+
+    /// Validate the arguments, print the result, and exit without generating
+    #[arg(long, conflicts_with_all = ["daemon", "watch"])]
+    pub dry_run: bool,
+
+    /// Print how long each generation phase took
+    #[arg(long, conflicts_with_all = ["daemon", "watch"])]
+    pub profile: bool,
+
+    /// Print the result as a single JSON line on stdout: the track_id, path,
+    /// seed and timing of the track on success, or error_code, message and
+    /// recovery_hint on failure. The line goes to stderr with --output -, as
+    /// stdout carries the WAV. With --version-info, print the details as
+    /// JSON instead of a table
+    #[arg(long, conflicts_with = "daemon")]
+    pub json: bool,
+
+    /// Regenerate the track described by a manifest from export_manifest
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["prompt", "daemon", "watch"])]
+    pub manifest: Option<PathBuf>,
+
+    /// Run in daemon mode (JSON-RPC over stdio)
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Serve an HTTP liveness check (GET /health) on this port in daemon mode
+    #[arg(long, value_name = "PORT", requires = "daemon")]
+    pub metrics_port: Option<u16>,
+
+    /// Serve Prometheus metrics (GET /metrics) on this address in daemon
+    /// mode, 127.0.0.1:9184 if none is given
+    #[cfg(feature = "metrics-http")]
+    #[arg(
+        long,
+        value_name = "ADDR",
+        requires = "daemon",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_METRICS_ADDR
+    )]
+    pub metrics_addr: Option<std::net::SocketAddr>,
+
+    /// Start in daemon mode even if another instance holds the cache lock,
+    /// with the track cache read-only
+    #[arg(long, requires = "daemon")]
+    pub allow_multiple: bool,
+
+    /// Read the prompt from a file and regenerate whenever it changes
+    #[arg(long, value_name = "PROMPT_FILE", conflicts_with_all = ["prompt", "daemon"])]
+    pub watch: Option<PathBuf>,
+
+    /// Write the JSON Schema of the JSON-RPC protocol to a file and exit
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["prompt", "daemon", "watch"])]
+    pub dump_schema: Option<PathBuf>,
+
+    /// Delete WAV files in the daemon's cache directory that are no longer
+    /// cached and exit (with --dry-run, only list them)
+    #[arg(long, conflicts_with_all = ["prompt", "daemon", "watch"])]
+    pub prune_orphans: bool,
+
+    /// Print the versions of the daemon, compiler and ONNX Runtime, the
+    /// execution providers, model directories and backend capabilities, and
+    /// exit (as JSON with --json)
+    #[arg(long, conflicts_with_all = ["prompt", "daemon", "watch"])]
+    pub version_info: bool,
+
+    /// ACE-Step options left at their built-in defaults, which `LOFI_*`
+    /// variables may replace. Only set by [`Cli::try_parse_args_from`].
+    #[arg(skip)]
+    pub defaulted: DefaultedArgs,
+}
+
+/// ACE-Step options that were not given on the command line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DefaultedArgs {
+    /// `--steps` was not given.
+    pub steps: bool,
+    /// `--scheduler` was not given.
+    pub scheduler: bool,
+    /// `--guidance` was not given.
+    pub guidance: bool,
+}
+
+impl Cli {
+    /// Parses command-line arguments.
+    ///
+    /// Exits with a usage error if the arguments are parsed but inconsistent.
+    pub fn parse_args() -> Self {
+        let cli = Cli::try_parse_args_from(std::env::args_os()).unwrap_or_else(|e| e.exit());
+        if let Some(error) = cli.check_conflicts() {
+            Cli::command().error(ErrorKind::ArgumentConflict, error).exit();
+        }
+        cli
+    }
+
+    /// Parses `args` like [`Parser::try_parse_from`], also recording which
+    /// ACE-Step options were left at their defaults.
+    pub fn try_parse_args_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let matches = Cli::command().try_get_matches_from(args)?;
+        let mut cli = Cli::from_arg_matches(&matches)?;
+        let defaulted = |id: &str| matches.value_source(id) == Some(ValueSource::DefaultValue);
+        cli.defaulted = DefaultedArgs {
+            steps: defaulted("steps"),
+            scheduler: defaulted("scheduler"),
+            guidance: defaulted("guidance"),
+        };
+        Ok(cli)
+    }
+
+    /// Returns these arguments with the ACE-Step options that were not given
+    /// taken from `config`, e.g. from `LOFI_ACE_STEP_STEPS`.
+    ///
+    /// Options given on the command line are kept.
+    pub fn with_config_defaults(&self, config: &DaemonConfig) -> Cli {
+        let mut cli = self.clone();
+        let ace_step = &config.ace_step;
+        if self.defaulted.steps {
+            cli.steps = StepsParam::Fixed(ace_step.inference_steps);
+        }
+        if self.defaulted.scheduler {
+            if let Ok(scheduler) = SchedulerArg::from_str(&ace_step.scheduler, true) {
+                cli.scheduler = scheduler;
+            }
+        }
+        if self.defaulted.guidance {
+            cli.guidance = ace_step.guidance_scale;
+        }
+        cli
+    }
+
+    /// Exits with a usage error, formatted like clap's own errors.
+    pub fn exit_with_error(message: impl std::fmt::Display) -> ! {
+        Cli::command().error(ErrorKind::ValueValidation, message).exit()
+    }
+
+    /// Replaces an out-of-range `--duration`, `--steps` or `--guidance` with
+    /// the nearest valid value when `--param-strictness clamp` is given.
+    ///
+    /// Uses the same rules as the daemon's clamp mode, with the duration
+    /// limited to the selected backend's range. Returns the adjustments made;
+    /// nothing is changed in strict mode.
+    pub fn clamp_params(&mut self) -> Vec<ParamAdjustment> {
+        let mut adjustments = Vec::new();
+        if self.param_strictness == ParamStrictness::Clamp {
+            let backend = if self.is_ace_step() { Backend::AceStep } else { Backend::MusicGen };
+            self.duration = clamp_duration(self.duration, backend, &mut adjustments);
+            if let StepsParam::Fixed(steps) = self.steps {
+                self.steps = StepsParam::Fixed(clamp_inference_steps(steps, &mut adjustments));
+            }
+            let default_guidance = AceStepConfig::default().guidance_scale;
+            self.guidance = clamp_guidance_scale(self.guidance, default_guidance, &mut adjustments);
+        }
+        adjustments
+    }
+
+    /// Applies generation rules on top of clap's argument parsing.
+    ///
+    /// Returns an error for argument conflicts, an invalid `--output`
+    /// template, an out-of-range `--steps`, or a `--guidance` outside the
+    /// supported range. A MusicGen duration
+    /// over 30 seconds only prints a warning.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(error) = self.check_conflicts() {
+            return Err(error);
+        }
+        if let Some(output) = self.output.as_deref().filter(|_| !self.output_is_stdout()) {
+            OutputTemplate::parse(&output.to_string_lossy())
+                .map_err(|e| format!("--output: {}", e))?;
+        }
+        if let StepsParam::Fixed(steps) = self.steps {
+            if !(MIN_INFERENCE_STEPS..=MAX_INFERENCE_STEPS).contains(&steps) {
+                return Err(format!(
+                    "--steps must be between {} and {}, got {}",
+                    MIN_INFERENCE_STEPS, MAX_INFERENCE_STEPS, steps
+                ));
+            }
+        }
+        if !(MIN_GUIDANCE_SCALE..=MAX_GUIDANCE_SCALE).contains(&self.guidance) {
+            return Err(format!(
+                "--guidance must be between {:.1} and {:.1}, got {}",
+                MIN_GUIDANCE_SCALE, MAX_GUIDANCE_SCALE, self.guidance
+            ));
+        }
+        if self.backend == BackendArg::Musicgen && self.duration > MUSICGEN_CLI_MAX_DURATION_SEC {
+            eprintln!(
+                "Warning: MusicGen supports up to {}s. Consider using --backend ace-step for longer audio.",
+                MUSICGEN_CLI_MAX_DURATION_SEC
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks combinations of arguments that clap cannot express.
+    ///
+    /// Returns an error message if validation fails, None otherwise.
+    fn check_conflicts(&self) -> Option<String> {
+        if self.keep_partial && (self.daemon || self.watch.is_some()) {
+            return Some("--keep-partial can only be used for a single generation".to_string());
+        }
+        if self.dump_tokens.is_some() && self.is_ace_step() {
+            return Some("--dump-tokens is only supported by the MusicGen backend".to_string());
+        }
+        if self.output_is_stdout() {
+            if self.daemon {
+                return Some(
+                    "--output - cannot be used with --daemon (stdout carries JSON-RPC)"
+                        .to_string(),
+                );
+            }
+            if self.watch.is_some() {
+                return Some("--output - cannot be used with --watch".to_string());
+            }
+        }
+        None
+    }
+
+    /// Returns true if the WAV should be written to stdout (`--output -`).
+    pub fn output_is_stdout(&self) -> bool {
+        self.output
+            .as_deref()
+            .is_some_and(|path| path.as_os_str() == STDOUT_OUTPUT)
+    }
+
+    /// Returns true if running in CLI mode (not daemon mode).
+    pub fn is_cli_mode(&self) -> bool {
+        !self.daemon && self.prompt.is_some()
+    }
+
+    /// Returns true if running in watch mode (`--watch <prompt-file>`).
+    pub fn is_watch_mode(&self) -> bool {
+        !self.daemon && self.watch.is_some()
+    }
+
+    /// Returns true if running in daemon mode.
+    pub fn is_daemon_mode(&self) -> bool {
+        self.daemon
+    }
+
+    /// Calculates the number of tokens to generate based on duration.
+    pub fn tokens_to_generate(&self) -> usize {
+        self.duration as usize * TOKENS_PER_SECOND
+    }
+
+    /// Returns the effective output path.
+    ///
+    /// Defaults to "output.wav" in the current directory if not specified.
+    pub fn output_path(&self) -> PathBuf {
+        self.output.clone().unwrap_or_else(|| PathBuf::from("output.wav"))
+    }
+
+    /// Expands the `--output` template for one generation and creates its
+    /// parent directories.
+    ///
+    /// An existing file gets a `-1`, `-2`, ... suffix unless `overwrite` is
+    /// set. `--output -` is returned unchanged.
+    pub fn render_output_path(
+        &self,
+        values: &TemplateValues,
+        overwrite: bool,
+    ) -> crate::error::Result<PathBuf> {
+        let template = self.output_path();
+        if self.output_is_stdout() {
+            return Ok(template);
+        }
+        // validate() rejects invalid templates; a literal path is the fallback
+        let path = match OutputTemplate::parse(&template.to_string_lossy()) {
+            Ok(parsed) => PathBuf::from(parsed.expand(values)),
+            Err(_) => template,
+        };
+        prepare_output_path(&path, overwrite)
+    }
+
+    /// Returns the file to write MusicGen token frames to, if any.
+    ///
+    /// `--dump-tokens` takes precedence over `LOFI_DUMP_TOKENS`.
+    pub fn token_dump_path(&self) -> Option<PathBuf> {
+        self.dump_tokens.clone().or_else(token_dump_path)
+    }
+
+    /// Returns these arguments with the generation parameters of `manifest`.
+    ///
+    /// Output options such as `--output` are kept. Prompt blends need the
+    /// daemon and are rejected.
+    pub fn with_manifest(&self, manifest: &Manifest) -> Result<Cli, String> {
+        if manifest.prompt_blend.is_some() {
+            return Err(
+                "Manifests with a prompt blend can only be regenerated by the daemon \
+                 (generate_from_manifest)"
+                    .to_string(),
+            );
+        }
+
+        let mut cli = self.clone();
+        cli.prompt = Some(manifest.prompt.clone());
+        cli.duration = manifest.duration_sec;
+        cli.seed = Some(manifest.seed);
+        cli.backend = match manifest.backend {
+            Backend::MusicGen => BackendArg::Musicgen,
+            Backend::AceStep => BackendArg::AceStep,
+        };
+        let sampling = &manifest.sampling;
+        if let Some(steps) = sampling.inference_steps {
+            cli.steps = StepsParam::Fixed(steps);
+        }
+        if let Some(scheduler) = &sampling.scheduler {
+            cli.scheduler = SchedulerArg::from_str(scheduler, true)
+                .map_err(|_| format!("Unknown scheduler in manifest: {}", scheduler))?;
+        }
+        if let (Backend::AceStep, Some(guidance)) = (manifest.backend, sampling.guidance_scale) {
+            cli.guidance = guidance;
+        }
+        cli.reverb = manifest.reverb.map(|reverb| reverb.preset);
+        cli.reverb_wet = manifest.reverb.map(|reverb| reverb.wet);
+        cli.trim_silence = manifest.trim_silence;
+        Ok(cli)
+    }
+
+    /// Returns the reverb selected with `--reverb` and `--reverb-wet`, if any.
+    pub fn reverb_settings(&self) -> Option<Reverb> {
+        self.reverb.map(|preset| Reverb::new(preset, self.reverb_wet))
+    }
+
+    /// Returns the diffusion steps to run, choosing them from `--duration`
+    /// for `--steps auto`.
+    pub fn inference_steps(&self) -> u32 {
+        self.steps.resolve(self.duration as f32, &AceStepConfig::default())
+    }
+
+    /// Returns true if using ACE-Step backend.
+    pub fn is_ace_step(&self) -> bool {
+        self.backend == BackendArg::AceStep
+    }
+
+    /// Resolves these arguments against `config`, e.g. [`DaemonConfig::from_env`].
+    ///
+    /// Options given on the command line win over `config`, and options left
+    /// at their defaults take its values. Returns the resolved arguments and
+    /// configuration, which agree on every option they share.
+    pub fn resolve(&self, config: DaemonConfig) -> (Cli, DaemonConfig) {
+        let cli = self.with_config_defaults(&config);
+        let config = cli.apply_to_config(config);
+        (cli, config)
+    }
+
+    /// Returns the daemon configuration equivalent to these arguments.
+    ///
+    /// Same as [`Cli::apply_to_config`] on the default configuration.
+    pub fn daemon_config(&self) -> DaemonConfig {
+        self.apply_to_config(DaemonConfig::default())
+    }
+
+    /// Returns `config` with these arguments applied on top.
+    ///
+    /// `--model-dir` sets the model path of the selected backend, and the
+    /// ACE-Step options set the ACE-Step defaults. Everything else is kept.
+    pub fn apply_to_config(&self, mut config: DaemonConfig) -> DaemonConfig {
+        if self.is_ace_step() {
+            config.default_backend = Backend::AceStep;
+            if let Some(path) = &self.model_dir {
+                config.ace_step_model_path = Some(path.clone());
+            }
+            config.ace_step.inference_steps = self.inference_steps();
+            config.ace_step.scheduler = self.scheduler.as_str().to_string();
+            config.ace_step.guidance_scale = self.guidance;
+        } else if let Some(path) = &self.model_dir {
+            config.model_path = Some(path.clone());
+        }
+        config
+    }
+}
+
+/// A failure reported on stdout with `--json`.
+///
+/// Carries the same fields as the `generation_error` notification, so
+/// scripts can handle CLI and daemon failures alike.
+#[derive(Debug, Serialize)]
+pub struct CliErrorReport {
+    /// Error code, e.g. `MODEL_INFERENCE_FAILED`.
+    pub error_code: String,
+
+    /// Human-readable error message.
+    pub message: String,
+
+    /// Suggestion for resolving the error.
+    pub recovery_hint: String,
+
+    /// Whether running the same command again may succeed.
+    pub retriable: bool,
+
+    /// Pipeline phase that failed, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<String>,
+
+    /// Underlying cause chain, e.g. the ONNX Runtime error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+impl CliErrorReport {
+    /// Builds the report for `error`.
+    pub fn from_error(error: &DaemonError) -> Self {
+        Self {
+            error_code: error.code.as_str().to_string(),
+            message: error.message.clone(),
+            recovery_hint: error.code.recovery_hint().to_string(),
+            retriable: error.code.is_retriable(),
+            phase: error.phase.map(|phase| phase.as_str().to_string()),
+            details: error.details(),
+        }
+    }
+}
+
+/// A successful generation reported on stdout with `--json`.
+///
+/// Carries the fields of the `generation_complete` notification that apply
+/// to a CLI run, so scripts can handle CLI and daemon results alike.
+#[derive(Debug, Serialize)]
+pub struct CliResultReport {
+    /// Track identifier, the same one the daemon assigns to the request.
+    pub track_id: String,
+
+    /// Path the WAV was written to.
+    pub path: String,
+
+    /// Duration of the written audio in seconds.
+    pub duration_sec: f32,
+
+    /// Audio sample rate in Hz.
+    pub sample_rate: u32,
+
+    /// Prompt used, shortened and hashed when prompt redaction is enabled.
+    pub prompt: String,
+
+    /// Seed used for generation, which differs from the requested seed when
+    /// MusicGen retried a seed that produced invalid audio.
+    pub seed: u64,
+
+    /// Wall-clock time for generation.
+    pub generation_time_sec: f32,
+
+    /// Backend used for generation.
+    pub backend: String,
+
+    /// Whether generation was stopped early and the partial result written.
+    pub cancelled: bool,
+
+    /// `LOFI_*` environment variables that reproduce the non-default
+    /// settings used, as printed under `# Repro:` on stderr.
+    pub config_env: BTreeMap<String, String>,
+}
+
+/// Build, runtime and backend details printed by `--version-info`.
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionInfo {
+    /// Version of lofi-daemon.
+    pub daemon_version: String,
+
+    /// Compiler the daemon was built with.
+    pub rustc_version: String,
+
+    /// ONNX Runtime API version the daemon was built against.
+    pub ort_api_version: String,
+
+    /// Build information reported by the loaded ONNX Runtime.
+    pub ort_build_info: String,
+
+    /// Execution providers that can be registered, in priority order.
+    pub execution_providers: Vec<String>,
+
+    /// Directory generated tracks are cached in.
+    pub cache_path: PathBuf,
+
+    /// Library directory for tracks saved with `save_to_output`.
+    pub library_path: PathBuf,
+
+    /// Installation state and limits of each backend.
+    pub backends: Vec<BackendVersionInfo>,
+
+    /// What ACE-Step generation supports.
+    pub ace_step: AceStepCapabilities,
+}
+
+/// One backend's row in [`VersionInfo`].
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendVersionInfo {
+    /// Backend identifier, e.g. `ace_step`.
+    pub backend: String,
+
+    /// Whether all model files are present.
+    pub installed: bool,
+
+    /// Model version string, if installed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_version: Option<String>,
+
+    /// Directory the model files are loaded from.
+    pub model_path: PathBuf,
+
+    /// Shortest supported duration in seconds.
+    pub min_duration_sec: u32,
+
+    /// Longest supported duration in seconds.
+    pub max_duration_sec: u32,
+
+    /// Output sample rate in Hz.
+    pub sample_rate: u32,
+}
+
+/// ACE-Step generation capabilities in [`VersionInfo`].
+#[derive(Debug, Clone, Serialize)]
+pub struct AceStepCapabilities {
+    /// Names of the supported diffusion schedulers.
+    pub schedulers: Vec<String>,
+
+    /// Shortest supported duration in seconds.
+    pub min_duration_sec: u32,
+
+    /// Longest supported duration in seconds.
+    pub max_duration_sec: u32,
+
+    /// Output sample rate in Hz.
+    pub sample_rate: u32,
+
+    /// Fewest diffusion steps.
+    pub min_inference_steps: u32,
+
+    /// Most diffusion steps.
+    pub max_inference_steps: u32,
+
+    /// Smallest guidance scale.
+    pub min_guidance_scale: f32,
+
+    /// Largest guidance scale.
+    pub max_guidance_scale: f32,
+}
+
+impl AceStepCapabilities {
+    /// Returns the capabilities of this build.
+    pub fn current() -> Self {
+        let backend = Backend::AceStep;
+        Self {
+            schedulers: SchedulerType::ALL.iter().map(|s| s.as_str().to_string()).collect(),
+            min_duration_sec: backend.min_duration_sec(),
+            max_duration_sec: backend.max_duration_sec(),
+            sample_rate: backend.sample_rate(),
+            min_inference_steps: MIN_INFERENCE_STEPS,
+            max_inference_steps: MAX_INFERENCE_STEPS,
+            min_guidance_scale: MIN_GUIDANCE_SCALE,
+            max_guidance_scale: MAX_GUIDANCE_SCALE,
+        }
+    }
+}
+
+impl VersionInfo {
+    /// Formats the details as human-readable tables.
+    pub fn to_table(&self) -> String {
+        let mut lines = vec![
+            format!("lofi-daemon {}", self.daemon_version),
+            format!("  {:<14}{}", "Compiler", self.rustc_version),
+            format!("  {:<14}{}", "ONNX Runtime", self.ort_api_version),
+            format!("  {:<14}{}", "ORT build", self.ort_build_info),
+            format!("  {:<14}{}", "Providers", self.execution_providers.join(", ")),
+            format!("  {:<14}{}", "Cache", self.cache_path.display()),
+            format!("  {:<14}{}", "Library", self.library_path.display()),
+            String::new(),
+            format!(
+                "  {:<10}{:<15}{:<16}{:<10}{:<13}{}",
+                "Backend", "Status", "Version", "Duration", "Sample rate", "Model directory"
+            ),
+        ];
+        for backend in &self.backends {
+            lines.push(format!(
+                "  {:<10}{:<15}{:<16}{:<10}{:<13}{}",
+                backend.backend,
+                if backend.installed { "installed" } else { "not installed" },
+                backend.model_version.as_deref().unwrap_or("-"),
+                format!("{}-{}s", backend.min_duration_sec, backend.max_duration_sec),
+                format!("{} Hz", backend.sample_rate),
+                backend.model_path.display()
+            ));
+        }
+
+        let ace_step = &self.ace_step;
+        lines.extend([
+            String::new(),
+            "ACE-Step".to_string(),
+            format!("  {:<14}{}", "Schedulers", ace_step.schedulers.join(", ")),
+            format!(
+                "  {:<14}{}-{} s",
+                "Duration", ace_step.min_duration_sec, ace_step.max_duration_sec
+            ),
+            format!("  {:<14}{} Hz", "Sample rate", ace_step.sample_rate),
+            format!(
+                "  {:<14}{}-{} or \"auto\"",
+                "Steps", ace_step.min_inference_steps, ace_step.max_inference_steps
+            ),
+            format!(
+                "  {:<14}{:.1}-{:.1}",
+                "Guidance", ace_step.min_guidance_scale, ace_step.max_guidance_scale
+            ),
+        ]);
+        lines.join("\n")
+    }
+}
+
+/// Parses `--guidance`, enforcing the same range as the RPC `generate` method.
+/// Parses a guidance scale; the range is checked by [`Cli::validate`].
+fn parse_guidance_scale(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(scale) if scale.is_finite() => Ok(scale),
+        _ => Err(format!("'{}' is not a number", s)),
+    }
+}
+
+fn parse_reverb_preset(s: &str) -> Result<ReverbPreset, String> {
+    ReverbPreset::parse(s).ok_or_else(|| format!("'{}' is not 'room', 'hall' or 'lofi-tape'", s))
+}
+
+fn parse_reverb_wet(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(wet) if (0.0..=1.0).contains(&wet) => Ok(wet),
+        _ => Err(format!("'{}' is not a number between 0.0 and 1.0", s)),
+    }
+}
+
+fn parse_param_strictness(s: &str) -> Result<ParamStrictness, String> {
+    ParamStrictness::parse(s).ok_or_else(|| format!("'{}' is not 'strict' or 'clamp'", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_per_second_constant() {
+        assert_eq!(TOKENS_PER_SECOND, 50);
+    }
+
+    #[test]
+    fn default_model_path_is_valid() {
+        let cli = Cli::try_parse_args_from(["lofi-daemon", "--prompt", "rain"]).unwrap();
+        let path = cli.daemon_config().effective_model_path();
+        assert!(!path.as_os_str().is_empty());
+    }
+
+    #[test]
+    fn tokens_calculation() {
+        let cli = Cli {
+            prompt: Some("test".to_string()),
+            duration: 10,
+            output: None,
+            overwrite: false,
+            model_dir: None,
+            seed: None,
+            backend: BackendArg::Musicgen,
+            steps: StepsParam::Fixed(60),
+            scheduler: SchedulerArg::Euler,
+            guidance: 7.0,
+            keep_partial: false,
+            param_strictness: ParamStrictness::Strict,
+            dump_tokens: None,
+            dry_run: false,
+            profile: false,
+            json: false,
+            daemon: false,
+            watch: None,
+            dump_schema: None,
+            prune_orphans: false,
+            version_info: false,
+            manifest: None,
+            reverb: None,
+            reverb_wet: None,
+            trim_silence: false,
+            metrics_port: None,
+            #[cfg(feature = "metrics-http")]
+            metrics_addr: None,
+            allow_multiple: false,
+            defaulted: DefaultedArgs::default(),
+        };
+        assert_eq!(cli.tokens_to_generate(), 500);
+    }
+
+    #[test]
+    fn cli_mode_detection() {
+        let cli_mode = Cli {
+            prompt: Some("test".to_string()),
+            duration: 10,
+            output: None,
+            overwrite: false,
+            model_dir: None,
+            seed: None,
+            backend: BackendArg::Musicgen,
+            steps: StepsParam::Fixed(60),
+            scheduler: SchedulerArg::Euler,
+            guidance: 7.0,
+            keep_partial: false,
+            param_strictness: ParamStrictness::Strict,
+            dump_tokens: None,
+            dry_run: false,
+            profile: false,
+            json: false,
+            daemon: false,
+            watch: None,
+            dump_schema: None,
+            prune_orphans: false,
+            version_info: false,
+            manifest: None,
+            reverb: None,
+            reverb_wet: None,
+            trim_silence: false,
+            metrics_port: None,
+            #[cfg(feature = "metrics-http")]
+            metrics_addr: None,
+            allow_multiple: false,
+            defaulted: DefaultedArgs::default(),
+        };
+        assert!(cli_mode.is_cli_mode());
+        assert!(!cli_mode.is_daemon_mode());
+
+        let daemon_mode = Cli {
+            prompt: None,
+            duration: 10,
+            output: None,
+            overwrite: false,
+            model_dir: None,
+            seed: None,
+            backend: BackendArg::Musicgen,
+            steps: StepsParam::Fixed(60),
+            scheduler: SchedulerArg::Euler,
+            guidance: 7.0,
+            keep_partial: false,
+            param_strictness: ParamStrictness::Strict,
+            dump_tokens: None,
+            dry_run: false,
+            profile: false,
+            json: false,
+            daemon: true,
+            watch: None,
+            dump_schema: None,
+            prune_orphans: false,
+            version_info: false,
+            manifest: None,
+            reverb: None,
+            reverb_wet: None,
+            trim_silence: false,
+            metrics_port: None,
+            #[cfg(feature = "metrics-http")]
+            metrics_addr: None,
+            allow_multiple: false,
+            defaulted: DefaultedArgs::default(),
+        };
+        assert!(!daemon_mode.is_cli_mode());
+        assert!(daemon_mode.is_daemon_mode());
+    }
+
+    #[test]
+    fn watch_mode_detection() {
+        let cli = Cli::parse_from(["lofi-daemon", "--watch", "prompt.txt"]);
+        assert!(cli.is_watch_mode());
+        assert!(!cli.is_cli_mode());
+        assert_eq!(cli.watch, Some(PathBuf::from("prompt.txt")));
+
+        assert!(Cli::try_parse_from(["lofi-daemon", "--watch", "p.txt", "--prompt", "x"]).is_err());
+    }
+
+    #[test]
+    fn output_path_default() {
+        let cli = Cli {
+            prompt: Some("test".to_string()),
+            duration: 10,
+            output: None,
+            overwrite: false,
+            model_dir: None,
+            seed: None,
+            backend: BackendArg::Musicgen,
+            steps: StepsParam::Fixed(60),
+            scheduler: SchedulerArg::Euler,
+            guidance: 7.0,
+            keep_partial: false,
+            param_strictness: ParamStrictness::Strict,
+            dump_tokens: None,
+            dry_run: false,
+            profile: false,
+            json: false,
+            daemon: false,
+            watch: None,
+            dump_schema: None,
+            prune_orphans: false,
+            version_info: false,
+            manifest: None,
+            reverb: None,
+            reverb_wet: None,
+            trim_silence: false,
+            metrics_port: None,
+            #[cfg(feature = "metrics-http")]
+            metrics_addr: None,
+            allow_multiple: false,
+            defaulted: DefaultedArgs::default(),
+        };
+        assert_eq!(cli.output_path(), PathBuf::from("output.wav"));
+    }
+
+    #[test]
+    fn stdout_output() {
+        let cli = Cli::parse_from(["lofi-daemon", "--prompt", "rain", "--output", "-"]);
+        assert!(cli.output_is_stdout());
+        assert!(cli.validate().is_ok());
+
+        let cli = Cli::parse_from(["lofi-daemon", "--prompt", "rain", "--output", "./-"]);
+        assert!(!cli.output_is_stdout());
+
+        let cli = Cli::parse_from(["lofi-daemon", "--daemon", "--output", "-"]);
+        assert!(cli.validate().unwrap_err().contains("--daemon"));
+
+        let cli = Cli::parse_from(["lofi-daemon", "--watch", "p.txt", "--output", "-"]);
+        assert!(cli.validate().unwrap_err().contains("--watch"));
+    }
+
+    #[test]
+    fn keep_partial_requires_single_generation() {
+        let cli = Cli::parse_from(["lofi-daemon", "--prompt", "rain", "--keep-partial"]);
+        assert!(cli.keep_partial);
+        assert!(cli.validate().is_ok());
+
+        let cli = Cli::parse_from(["lofi-daemon", "--watch", "p.txt", "--keep-partial"]);
+        assert!(cli.validate().unwrap_err().contains("--keep-partial"));
+        let cli = Cli::parse_from(["lofi-daemon", "--daemon", "--keep-partial"]);
+        assert!(cli.validate().is_err());
+    }
+
+    #[test]
+    fn ace_step_backend_detection() {
+        let ace_step = Cli {
+            prompt: Some("test".to_string()),
+            duration: 60,
+            output: None,
+            overwrite: false,
+            model_dir: None,
+            seed: Some(42),
+            backend: BackendArg::AceStep,
+            steps: StepsParam::Fixed(60),
+            scheduler: SchedulerArg::Euler,
+            guidance: 7.0,
+            keep_partial: false,
+            param_strictness: ParamStrictness::Strict,
+            dump_tokens: None,
+            dry_run: false,
+            profile: false,
+            json: false,
+            daemon: false,
+            watch: None,
+            dump_schema: None,
+            prune_orphans: false,
+            version_info: false,
+            manifest: None,
+            reverb: None,
+            reverb_wet: None,
+            trim_silence: false,
+            metrics_port: None,
+            #[cfg(feature = "metrics-http")]
+            metrics_addr: None,
+            allow_multiple: false,
+            defaulted: DefaultedArgs::default(),
+        };
+        assert!(ace_step.is_ace_step());
+
+        let musicgen = Cli {
+            prompt: Some("test".to_string()),
+            duration: 10,
+            output: None,
+            overwrite: false,
+            model_dir: None,
+            seed: None,
+            backend: BackendArg::Musicgen,
+            steps: StepsParam::Fixed(60),
+            scheduler: SchedulerArg::Euler,
+            guidance: 7.0,
+            keep_partial: false,
+            param_strictness: ParamStrictness::Strict,
+            dump_tokens: None,
+            dry_run: false,
+            profile: false,
+            json: false,
+            daemon: false,
+            watch: None,
+            dump_schema: None,
+            prune_orphans: false,
+            version_info: false,
+            manifest: None,
+            reverb: None,
+            reverb_wet: None,
+            trim_silence: false,
+            metrics_port: None,
+            #[cfg(feature = "metrics-http")]
+            metrics_addr: None,
+            allow_multiple: false,
+            defaulted: DefaultedArgs::default(),
+        };
+        assert!(!musicgen.is_ace_step());
+    }
+
+    #[test]
+    fn ace_step_parameter_ranges() {
+        let cli = Cli::parse_from(["lofi-daemon", "--steps", "200", "--guidance", "1.0"]);
+        assert_eq!(cli.steps, StepsParam::Fixed(200));
+        assert_eq!(cli.guidance, 1.0);
+
+        // Ranges are checked by validate so that clamp mode can adjust them
+        assert!(Cli::parse_from(["lofi-daemon", "--steps", "0"]).validate().is_err());
+        assert!(Cli::parse_from(["lofi-daemon", "--steps", "201"]).validate().is_err());
+        assert!(Cli::parse_from(["lofi-daemon", "--guidance", "0.5"]).validate().is_err());
+        assert!(Cli::parse_from(["lofi-daemon", "--guidance", "100"]).validate().is_err());
+        assert!(Cli::try_parse_from(["lofi-daemon", "--guidance", "NaN"]).is_err());
+        assert!(Cli::try_parse_from(["lofi-daemon", "--scheduler", "ddim"]).is_err());
+    }
+
+    #[test]
+    fn auto_steps_follow_duration() {
+        let cli = Cli::parse_from(["lofi-daemon", "--steps", "auto", "--duration", "30"]);
+        assert_eq!(cli.steps, StepsParam::Auto);
+        assert_eq!(cli.inference_steps(), 34);
+        assert!(cli.validate().is_ok());
+
+        let cli = Cli::parse_from(["lofi-daemon", "--steps", "auto", "--duration", "240"]);
+        assert_eq!(cli.inference_steps(), 80);
+        assert_eq!(Cli::parse_from(["lofi-daemon", "--duration", "240"]).inference_steps(), 60);
+        assert!(Cli::try_parse_from(["lofi-daemon", "--steps", "fast"]).is_err());
+    }
+
+    #[test]
+    fn clamp_mode_adjusts_out_of_range_values() {
+        let mut cli = Cli::parse_from([
+            "lofi-daemon", "--backend", "ace-step", "--prompt", "rain", "--steps", "500",
+            "--guidance", "35", "--param-strictness", "clamp",
+        ]);
+        let adjustments = cli.clamp_params();
+        assert_eq!((cli.steps, cli.guidance), (StepsParam::Fixed(200), 20.0));
+        assert_eq!(
+            adjustments,
+            vec![
+                ParamAdjustment::new("inference_steps", 500, 200),
+                ParamAdjustment::new("guidance_scale", 35.0, 20.0),
+            ]
+        );
+        assert!(cli.validate().is_ok());
+
+        // clap accepts up to ACE-Step's 240s; MusicGen durations are clamped
+        // to its own range
+        let mut cli = Cli::parse_from([
+            "lofi-daemon", "--prompt", "rain", "--duration", "200", "--param-strictness", "clamp",
+        ]);
+        assert_eq!(cli.clamp_params(), vec![ParamAdjustment::new("duration_sec", 200, 120)]);
+        assert_eq!(cli.duration, 120);
+
+        // Strict mode leaves the values for validate to reject
+        let mut cli = Cli::parse_from(["lofi-daemon", "--prompt", "rain", "--guidance", "35"]);
+        assert!(cli.clamp_params().is_empty());
+        assert_eq!(cli.guidance, 35.0);
+        assert!(cli.validate().is_err());
+    }
+
+    fn generation_cli(backend: BackendArg, duration: u32, steps: u32, guidance: f32) -> Cli {
+        Cli {
+            prompt: Some("test".to_string()),
+            duration,
+            output: None,
+            overwrite: false,
+            model_dir: None,
+            seed: None,
+            backend,
+            steps: StepsParam::Fixed(steps),
+            scheduler: SchedulerArg::Euler,
+            guidance,
+            keep_partial: false,
+            param_strictness: ParamStrictness::Strict,
+            dump_tokens: None,
+            dry_run: true,
+            profile: false,
+            json: false,
+            daemon: false,
+            watch: None,
+            dump_schema: None,
+            prune_orphans: false,
+            version_info: false,
+            manifest: None,
+            reverb: None,
+            reverb_wet: None,
+            trim_silence: false,
+            metrics_port: None,
+            #[cfg(feature = "metrics-http")]
+            metrics_addr: None,
+            allow_multiple: false,
+            defaulted: DefaultedArgs::default(),
+        }
+    }
+
+    #[test]
+    fn validate_generation_rules() {
+        assert!(generation_cli(BackendArg::AceStep, 240, 60, 7.0).validate().is_ok());
+        // Long MusicGen durations only warn
+        assert!(generation_cli(BackendArg::Musicgen, 60, 60, 7.0).validate().is_ok());
+
+        let err = generation_cli(BackendArg::AceStep, 60, 201, 7.0).validate().unwrap_err();
+        assert!(err.contains("--steps"));
+
+        let err = generation_cli(BackendArg::AceStep, 60, 60, 0.5).validate().unwrap_err();
+        assert!(err.contains("between 1.0 and 20.0"));
+        assert!(generation_cli(BackendArg::AceStep, 60, 60, 25.0).validate().is_err());
+    }
+
+    #[test]
+    fn dry_run_is_single_generation_only() {
+        let cli = Cli::parse_from(["lofi-daemon", "--prompt", "rain", "--dry-run"]);
+        assert!(cli.dry_run && cli.is_cli_mode());
+        assert!(Cli::try_parse_from(["lofi-daemon", "--daemon", "--dry-run"]).is_err());
+    }
+
+    #[test]
+    fn daemon_config_reflects_ace_step_options() {
+        let cli = Cli::parse_from([
+            "lofi-daemon", "--backend", "ace-step", "--prompt", "rain", "--steps", "80",
+            "--scheduler", "heun",
+        ]);
+        let config = cli.daemon_config();
+        assert_eq!(config.default_backend, Backend::AceStep);
+        assert_eq!(config.ace_step.inference_steps, 80);
+        assert_eq!(config.ace_step.scheduler, "heun");
+
+        let cli = Cli::parse_from(["lofi-daemon", "--prompt", "rain"]);
+        assert!(cli.daemon_config().to_env_exports().is_empty());
+    }
+
+    #[test]
+    fn json_error_report() {
+        let cli = Cli::parse_from(["lofi-daemon", "--prompt", "rain", "--json"]);
+        assert!(cli.json && cli.validate().is_ok());
+        assert!(Cli::try_parse_from(["lofi-daemon", "--daemon", "--json"]).is_err());
+        let cli = Cli::parse_from(["lofi-daemon", "--prompt", "rain", "--json", "--output", "-"]);
+        assert!(cli.validate().is_ok());
+
+        let error = DaemonError::model_inference_failed("decoder returned NaN");
+        let report = serde_json::to_value(CliErrorReport::from_error(&error)).unwrap();
+        assert_eq!(report["error_code"], "MODEL_INFERENCE_FAILED");
+        assert_eq!(report["message"], error.message);
+        assert_eq!(report["recovery_hint"], error.code.recovery_hint());
+        assert_eq!(report["retriable"], error.code.is_retriable());
+        assert!(report.get("phase").is_none());
+
+        let report = serde_json::to_value(CliResultReport {
+            track_id: "abc".to_string(),
+            path: "/tmp/rain.wav".to_string(),
+            duration_sec: 10.0,
+            sample_rate: 32000,
+            prompt: "rain".to_string(),
+            seed: 43,
+            generation_time_sec: 2.5,
+            backend: "musicgen".to_string(),
+            cancelled: false,
+            config_env: [("LOFI_DEVICE".to_string(), "cpu".to_string())].into(),
+        })
+        .unwrap();
+        assert_eq!(report["track_id"], "abc");
+        assert_eq!(report["seed"], 43);
+        assert_eq!(report["cancelled"], false);
+        assert_eq!(report["config_env"]["LOFI_DEVICE"], "cpu");
+    }
+
+    #[test]
+    fn dump_tokens_is_musicgen_only() {
+        let cli = Cli::parse_from(["lofi-daemon", "--prompt", "rain", "--dump-tokens", "t.csv"]);
+        assert!(cli.validate().is_ok());
+        assert_eq!(cli.token_dump_path(), Some(PathBuf::from("t.csv")));
+
+        let cli = Cli::parse_from([
+            "lofi-daemon", "--backend", "ace-step", "--prompt", "rain", "--dump-tokens", "t.csv",
+        ]);
+        assert!(cli.validate().unwrap_err().contains("--dump-tokens"));
+        assert!(Cli::try_parse_from(["lofi-daemon", "--daemon", "--dump-tokens", "t.csv"]).is_err());
+    }
+
+    #[test]
+    fn dump_schema_is_standalone() {
+        let cli = Cli::parse_from(["lofi-daemon", "--dump-schema", "schema.json"]);
+        assert_eq!(cli.dump_schema, Some(PathBuf::from("schema.json")));
+        assert!(!cli.is_cli_mode() && !cli.is_daemon_mode());
+        assert!(Cli::try_parse_from(["lofi-daemon", "--daemon", "--dump-schema", "s.json"]).is_err());
+    }
+
+    #[test]
+    fn metrics_port_requires_daemon() {
+        let cli = Cli::parse_from(["lofi-daemon", "--daemon", "--metrics-port", "9090"]);
+        assert_eq!(cli.metrics_port, Some(9090));
+        assert!(Cli::try_parse_from(["lofi-daemon", "--prompt", "rain", "--metrics-port", "9090"]).is_err());
+    }
+
+    #[cfg(feature = "metrics-http")]
+    #[test]
+    fn metrics_addr_defaults_to_loopback() {
+        let cli = Cli::parse_from(["lofi-daemon", "--daemon", "--metrics-addr"]);
+        assert_eq!(cli.metrics_addr, Some(DEFAULT_METRICS_ADDR.parse().unwrap()));
+        let cli = Cli::parse_from(["lofi-daemon", "--daemon", "--metrics-addr", "0.0.0.0:9000"]);
+        assert_eq!(cli.metrics_addr, Some("0.0.0.0:9000".parse().unwrap()));
+        assert!(Cli::try_parse_from(["lofi-daemon", "--prompt", "rain", "--metrics-addr"]).is_err());
+    }
+
+    #[test]
+    fn allow_multiple_requires_daemon() {
+        let cli = Cli::parse_from(["lofi-daemon", "--daemon", "--allow-multiple"]);
+        assert!(cli.allow_multiple);
+        assert!(Cli::try_parse_from(["lofi-daemon", "--prompt", "rain", "--allow-multiple"]).is_err());
+    }
+
+    #[test]
+    fn reverb_options() {
+        let cli = Cli::parse_from(["lofi-daemon", "--prompt", "rain", "--reverb", "lofi-tape"]);
+        assert_eq!(cli.reverb_settings(), Some(Reverb::new(ReverbPreset::LofiTape, None)));
+
+        let cli = Cli::parse_from([
+            "lofi-daemon", "--prompt", "rain", "--reverb", "hall", "--reverb-wet", "0.5",
+        ]);
+        assert_eq!(cli.reverb_settings().unwrap().wet, 0.5);
+
+        assert!(Cli::try_parse_from(["lofi-daemon", "--prompt", "rain", "--reverb", "cave"]).is_err());
+        assert!(Cli::try_parse_from(["lofi-daemon", "--prompt", "rain", "--reverb-wet", "0.5"]).is_err());
+        assert!(Cli::try_parse_from([
+            "lofi-daemon", "--prompt", "rain", "--reverb", "room", "--reverb-wet", "2",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn manifest_sets_generation_parameters() {
+        let cli = Cli::parse_from(["lofi-daemon", "--manifest", "recipe.json", "--output", "a.wav"]);
+        assert!(!cli.is_cli_mode());
+        assert!(Cli::try_parse_from(["lofi-daemon", "--manifest", "a.json", "--prompt", "rain"]).is_err());
+
+        let manifest: Manifest = serde_json::from_value(serde_json::json!({
+            "manifest_version": 1,
+            "daemon_version": "0.1.0",
+            "backend": "ace_step",
+            "prompt": "rainy night",
+            "seed": 7,
+            "duration_sec": 90,
+            "model_version": "ace-step-v1-noise2",
+            "sampling": { "scheduler": "pingpong", "inference_steps": 80, "guidance_scale": 9.0 },
+            "reverb": { "preset": "hall", "wet": 0.4 },
+            "trim_silence": true
+        }))
+        .unwrap();
+        let applied = cli.with_manifest(&manifest).unwrap();
+        assert_eq!(applied.prompt.as_deref(), Some("rainy night"));
+        assert_eq!(applied.backend, BackendArg::AceStep);
+        assert_eq!((applied.duration, applied.seed), (90, Some(7)));
+        assert_eq!((applied.steps, applied.scheduler), (StepsParam::Fixed(80), SchedulerArg::Pingpong));
+        assert_eq!(applied.guidance, 9.0);
+        assert_eq!(applied.reverb_settings(), Some(Reverb::new(ReverbPreset::Hall, Some(0.4))));
+        assert!(applied.trim_silence);
+        assert_eq!(applied.output, Some(PathBuf::from("a.wav")));
+        assert!(applied.validate().is_ok());
+
+        let mut blended = manifest.clone();
+        blended.prompt_blend = Some(vec![("rain".to_string(), 1.0)]);
+        assert!(cli.with_manifest(&blended).is_err());
+    }
+
+    #[test]
+    fn scheduler_options() {
+        assert_eq!(SchedulerArg::Euler, SchedulerArg::default());
+    }
+
+    #[test]
+    fn ace_step_model_path_is_valid() {
+        let args = ["lofi-daemon", "--prompt", "rain", "--backend", "ace-step"];
+        let cli = Cli::try_parse_args_from(args).unwrap();
+        let path = cli.daemon_config().effective_ace_step_model_path();
+        assert!(!path.as_os_str().is_empty());
+        assert!(path.to_string_lossy().contains("ace-step"));
+    }
+
+    #[test]
+    fn version_info_formats() {
+        let cli = Cli::try_parse_from(["lofi-daemon", "--version-info", "--json"]).unwrap();
+        assert!(cli.version_info && cli.json);
+        assert!(Cli::try_parse_from(["lofi-daemon", "--version-info", "--daemon"]).is_err());
+
+        let info = VersionInfo {
+            daemon_version: "0.1.0".to_string(),
+            rustc_version: "rustc 1.80.0".to_string(),
+            ort_api_version: "1.20".to_string(),
+            ort_build_info: "ORT Build Info: test".to_string(),
+            execution_providers: vec!["CUDA".to_string(), "CPU".to_string()],
+            cache_path: PathBuf::from("/tmp/cache"),
+            library_path: PathBuf::from("/tmp/library"),
+            backends: vec![BackendVersionInfo {
+                backend: "ace_step".to_string(),
+                installed: false,
+                model_version: None,
+                model_path: PathBuf::from("/tmp/ace-step"),
+                min_duration_sec: 5,
+                max_duration_sec: 240,
+                sample_rate: 48000,
+            }],
+            ace_step: AceStepCapabilities::current(),
+        };
+
+        let table = info.to_table();
+        assert!(table.starts_with("lofi-daemon 0.1.0"));
+        assert!(table.contains("Providers     CUDA, CPU"));
+        assert!(table.contains("not installed"));
+        assert!(table.contains("euler, heun, pingpong"));
+
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["backends"][0]["installed"], false);
+        assert!(json["backends"][0].get("model_version").is_none());
+        assert_eq!(json["ace_step"]["sample_rate"], 48000);
+    }
+
+    fn env_config(vars: &[(&str, &str)]) -> DaemonConfig {
+        DaemonConfig::from_env_vars(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn flags_win_over_env_which_wins_over_defaults() {
+        let env = env_config(&[
+            ("LOFI_ACE_STEP_STEPS", "30"),
+            ("LOFI_ACE_STEP_GUIDANCE", "9.5"),
+            ("LOFI_MODEL_PATH", "/env/musicgen"),
+            ("LOFI_CACHE_PATH", "/env/cache"),
+        ]);
+
+        let args = ["lofi-daemon", "--prompt", "rain", "--backend", "ace-step", "--steps", "40"];
+        let (cli, config) = Cli::try_parse_args_from(args).unwrap().resolve(env.clone());
+        assert_eq!(cli.inference_steps(), 40);
+        assert_eq!(cli.guidance, 9.5);
+        assert_eq!(config.ace_step.inference_steps, 40);
+        assert_eq!(config.ace_step.guidance_scale, 9.5);
+        assert_eq!(config.cache_path, Some(PathBuf::from("/env/cache")));
+
+        let args = ["lofi-daemon", "--prompt", "rain", "--model-dir", "/flag/musicgen"];
+        let (_, config) = Cli::try_parse_args_from(args).unwrap().resolve(env.clone());
+        assert_eq!(config.model_path, Some(PathBuf::from("/flag/musicgen")));
+
+        let args = ["lofi-daemon", "--prompt", "rain"];
+        let (_, config) = Cli::try_parse_args_from(args).unwrap().resolve(env);
+        assert_eq!(config.model_path, Some(PathBuf::from("/env/musicgen")));
+    }
+}
@@ -3,15 +3,27 @@
 //! Contains the runtime configuration for the lofi-daemon, including
 //! execution device selection, backend selection, and path configuration.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::audio::spectrogram::{SpectrogramOptions, DEFAULT_FFT_SIZE, DEFAULT_HOP_SIZE};
+use crate::audio::trim::{SilenceTrim, DEFAULT_TRIM_MIN_SILENCE_SEC, DEFAULT_TRIM_THRESHOLD_DB};
+use crate::generation::{
+    default_dcae_parallelism, total_memory, QueuePolicy, DEFAULT_MAX_STARVATION_SEC,
+    MAX_QUEUE_SIZE,
+};
+use crate::models::ace_step::{
+    SchedulerType, DEFAULT_INFERENCE_STEPS, MAX_GUIDANCE_SCALE, MAX_INFERENCE_STEPS,
+    MIN_GUIDANCE_SCALE, MIN_INFERENCE_STEPS,
+};
 use crate::models::Backend;
+use crate::output::{OutputTemplate, DEFAULT_CACHE_FILENAME};
 
 /// Execution device for ONNX inference.
 ///
 /// Determines which hardware backend to use for model inference.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum Device {
     /// Automatically detect and use the best available device.
@@ -61,11 +73,198 @@ impl std::fmt::Display for Device {
     }
 }
 
+/// How control characters in prompts are handled.
+///
+/// Prompts are always NFC-normalized and trimmed; this only decides what
+/// happens to control characters other than newlines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptSanitization {
+    /// Remove control characters and accept the cleaned prompt.
+    #[default]
+    Strip,
+
+    /// Reject prompts containing control characters.
+    Reject,
+}
+
+impl PromptSanitization {
+    /// Returns the string representation of the mode.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PromptSanitization::Strip => "strip",
+            PromptSanitization::Reject => "reject",
+        }
+    }
+
+    /// Parses a sanitization mode from a string.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "strip" => Some(PromptSanitization::Strip),
+            "reject" => Some(PromptSanitization::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// How out-of-range generation parameters are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamStrictness {
+    /// Reject the request.
+    #[default]
+    Strict,
+
+    /// Use the nearest valid value and report the adjustment.
+    Clamp,
+}
+
+impl ParamStrictness {
+    /// Returns the string representation of the mode.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParamStrictness::Strict => "strict",
+            ParamStrictness::Clamp => "clamp",
+        }
+    }
+
+    /// Parses a strictness mode from a string.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "strict" => Some(ParamStrictness::Strict),
+            "clamp" => Some(ParamStrictness::Clamp),
+            _ => None,
+        }
+    }
+}
+
+/// How ONNX Runtime grows its memory arena when it runs out of space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OnnxArenaStrategy {
+    /// Grow by doubling. Fewest allocations, but can hold on to up to twice
+    /// the memory a generation needs.
+    #[default]
+    NextPowerOfTwo,
+
+    /// Grow by exactly the requested amount. Keeps memory close to the
+    /// working set at the cost of more, smaller allocations. ONNX Runtime
+    /// does not expose the strategy for CPU sessions, so on CPU this
+    /// disables the arena and allocates each buffer directly.
+    SameAsRequested,
+}
+
+impl OnnxArenaStrategy {
+    /// Returns the string representation of the strategy.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OnnxArenaStrategy::NextPowerOfTwo => "next_power_of_two",
+            OnnxArenaStrategy::SameAsRequested => "same_as_requested",
+        }
+    }
+
+    /// Parses a strategy from a string.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "next_power_of_two" => Some(OnnxArenaStrategy::NextPowerOfTwo),
+            "same_as_requested" => Some(OnnxArenaStrategy::SameAsRequested),
+            _ => None,
+        }
+    }
+}
+
+/// Default cap on total failure diagnostics size (100 MB).
+pub const DEFAULT_DEBUG_DUMP_MAX_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Default number of entries kept in `history.jsonl`.
+pub const DEFAULT_HISTORY_MAX_ENTRIES: usize = 100;
+
+/// Upper bound for `history_max_entries`.
+pub const MAX_HISTORY_ENTRIES: usize = 10_000;
+
+/// Default minimum interval between progress notifications (250 ms).
+pub const DEFAULT_MIN_NOTIFY_INTERVAL_MS: u64 = 250;
+
+/// Upper bound for `min_notify_interval_ms` (one minute).
+pub const MAX_NOTIFY_INTERVAL_MS: u64 = 60_000;
+
+/// Upper bound for `trim_min_silence_sec`.
+pub const MAX_TRIM_MIN_SILENCE_SEC: f32 = 30.0;
+
+/// Upper bound for `dcae_parallelism`.
+pub const MAX_DCAE_PARALLELISM: usize = 8;
+
+/// Default limit on how long loading a backend's models may take (5 minutes).
+pub const DEFAULT_MODEL_LOAD_TIMEOUT_SEC: u64 = 300;
+
+/// Default free memory kept in reserve when checking whether a generation fits (512 MB).
+pub const DEFAULT_MEMORY_SAFETY_MARGIN_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Settings that can be changed while the daemon is running via `set_config`.
+pub const RUNTIME_CONFIG_KEYS: &[&str] = &[
+    "default_backend",
+    "ace_step",
+    "normalize_audio",
+    "target_lufs",
+    "trim_silence_threshold_db",
+    "trim_min_silence_sec",
+    "progress_interval_percent",
+    "min_notify_interval_ms",
+    "cache_max_bytes",
+    "invalidate_on_model_update",
+    "max_queue_size",
+    "queue_policy",
+    "max_starvation_sec",
+    "parallel_backends",
+    "model_idle_timeout_sec",
+    "model_load_timeout_sec",
+    "generate_thumbnails",
+    "spectrogram_fft_size",
+    "spectrogram_hop_size",
+    "prompt_sanitization",
+    "param_strictness",
+    "redact_prompts",
+    "now_playing",
+    "now_playing_dir",
+    "library_path",
+    "cache_filename",
+    "history_max_entries",
+    "on_complete_command",
+    "on_error_command",
+    "debug_dump_max_bytes",
+    "memory_safety_margin_bytes",
+    "rate_limit_generate",
+    "rate_limit_other",
+];
+
+/// Settings that only take effect when models are reloaded or the daemon restarts.
+///
+/// Also holds settings that must not be changed by RPC clients, such as where
+/// diagnostics are written; those are read from the config file or the
+/// environment only.
+pub const RELOAD_REQUIRED_CONFIG_KEYS: &[&str] = &[
+    "device",
+    "model_path",
+    "ace_step_model_path",
+    "tokenizer_path",
+    "ace_step_tokenizer_path",
+    "cache_path",
+    "threads",
+    "dcae_parallelism",
+    "warm_up_on_load",
+    "onnx_disable_spinning",
+    "onnx_arena_extend_strategy",
+    "restrict_output_root",
+    "debug_dump_dir",
+];
+
 /// Runtime configuration for the daemon.
 ///
-/// This configuration is typically loaded from command-line arguments
-/// or environment variables at startup.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// This configuration is typically loaded from command-line arguments,
+/// environment variables, or a JSON config file at startup. Fields missing
+/// from a config file take their default values.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct DaemonConfig {
     /// Path to the directory containing MusicGen ONNX model files.
     /// If None, uses the platform-specific default cache location.
@@ -75,10 +274,37 @@ pub struct DaemonConfig {
     /// If None, uses the platform-specific default cache location.
     pub ace_step_model_path: Option<PathBuf>,
 
+    /// Path to a MusicGen `tokenizer.json` to use instead of the one in the
+    /// model directory.
+    pub tokenizer_path: Option<PathBuf>,
+
+    /// Path to an ACE-Step `tokenizer.json` to use instead of the one in the
+    /// model directory.
+    pub ace_step_tokenizer_path: Option<PathBuf>,
+
     /// Path to the directory for storing generated audio files.
     /// If None, uses the platform-specific default cache location.
     pub cache_path: Option<PathBuf>,
 
+    /// Library directory that `generate` requests with `save_to_output` copy
+    /// finished tracks into. Unlike the cache it is never pruned.
+    /// If None, uses the platform-specific default data location. Must lie
+    /// inside `restrict_output_root` when that is set. Only ever a directory;
+    /// the file a single request writes to is the `output_path` of
+    /// `generate`. Config files that still spell it `output_path` are read.
+    #[serde(alias = "output_path")]
+    pub library_path: Option<PathBuf>,
+
+    /// Directory that the `output_path` of `generate` requests must lie in,
+    /// for a daemon run as a service. Relative output paths are taken
+    /// relative to it. If None, any writable path is accepted.
+    pub restrict_output_root: Option<PathBuf>,
+
+    /// Filename template for tracks written to the cache directory, with the
+    /// placeholders and date specifiers of `--output` (see [`OutputTemplate`]).
+    /// Must not contain path separators. Defaults to `{track_id}.wav`.
+    pub cache_filename: String,
+
     /// Execution device for inference.
     pub device: Device,
 
@@ -89,12 +315,168 @@ pub struct DaemonConfig {
     /// If None, uses ONNX Runtime's default (typically number of CPU cores).
     pub threads: Option<u32>,
 
+    /// Number of ACE-Step DCAE chunks decoded in parallel. Each extra chunk
+    /// loads another DCAE session (about 600 MB).
+    /// If None, uses one per four cores (at most 2) on machines with at least
+    /// 16 GB of memory, and 1 otherwise.
+    pub dcae_parallelism: Option<usize>,
+
+    /// Whether to run a one-frame MusicGen generation after loading, so the
+    /// decoder graphs are compiled before the first request. A failed warmup
+    /// is logged and does not fail the load.
+    /// Default: true
+    pub warm_up_on_load: bool,
+
+    /// Whether idle ONNX Runtime worker threads block right away instead of
+    /// spinning briefly while waiting for work. Spinning shaves latency off
+    /// each operator but keeps every worker core busy for the whole
+    /// generation; disabling it frees those cores for other programs at the
+    /// cost of somewhat slower generation.
+    /// Default: false
+    pub onnx_disable_spinning: bool,
+
+    /// How ONNX Runtime grows its memory arena. `same_as_requested` trades
+    /// some allocation overhead for a smaller, more predictable footprint.
+    /// Default: next_power_of_two
+    pub onnx_arena_extend_strategy: OnnxArenaStrategy,
+
     /// ACE-Step specific configuration.
     pub ace_step: AceStepConfig,
+
+    /// Whether to loudness-normalize generated audio before writing it.
+    pub normalize_audio: bool,
+
+    /// Target integrated loudness in LUFS when `normalize_audio` is enabled.
+    /// Default: -14.0
+    pub target_lufs: f32,
+
+    /// Level in dBFS below which samples count as silence when a request
+    /// asks for `trim_silence`. Default: -60.0
+    pub trim_silence_threshold_db: f32,
+
+    /// Shortest near-silent run at the start or end of a track that
+    /// `trim_silence` removes, so that brief pauses are kept. Default: 0.5
+    pub trim_min_silence_sec: f32,
+
+    /// Minimum progress increment (in percent) between `generation_progress` notifications.
+    /// Default: 5
+    pub progress_interval_percent: u8,
+
+    /// Minimum time in milliseconds between `generation_progress`
+    /// notifications for a track, and between `download_progress`
+    /// notifications for a file. The first and final notifications are
+    /// always sent. 0 disables the limit.
+    /// Default: 250
+    pub min_notify_interval_ms: u64,
+
+    /// Maximum combined size of cached tracks in bytes.
+    /// If None, the cache is only bounded by its entry count.
+    pub cache_max_bytes: Option<u64>,
+
+    /// Whether cached tracks of a backend are removed when its models are
+    /// loaded after a fresh download, since they were made with old weights.
+    /// Default: false
+    pub invalidate_on_model_update: bool,
+
+    /// Maximum number of pending jobs in the generation queue.
+    /// Default: 10
+    pub max_queue_size: usize,
+
+    /// Order in which queued jobs are processed (fifo or backend_affinity).
+    pub queue_policy: QueuePolicy,
+
+    /// Longest a queued job may be passed over for jobs of the loaded backend
+    /// under the backend_affinity policy, in seconds.
+    /// Default: 300
+    pub max_starvation_sec: u64,
+
+    /// Whether to keep both backends loaded and generate a queued MusicGen
+    /// job and a queued ACE-Step job at the same time. Needs memory for both
+    /// models; a job that would not fit waits for its backend to be active
+    /// instead. Turning it off unloads the second backend.
+    /// Default: false
+    pub parallel_backends: bool,
+
+    /// Seconds of inactivity after which loaded models are unloaded to free memory.
+    /// If None, models stay loaded until shutdown.
+    pub model_idle_timeout_sec: Option<u64>,
+
+    /// Seconds to wait for a backend's models to load before the request
+    /// fails with TIMED_OUT. The load keeps running in the background, and the
+    /// next request waits for it rather than starting another.
+    /// Default: 300
+    pub model_load_timeout_sec: u64,
+
+    /// Whether to write a spectrogram PNG (`<track_id>.png`) next to each generated WAV.
+    pub generate_thumbnails: bool,
+
+    /// FFT window size for spectrogram thumbnails. Must be a power of two.
+    /// Default: 1024
+    pub spectrogram_fft_size: usize,
+
+    /// Hop between FFT windows for spectrogram thumbnails.
+    /// Default: 512
+    pub spectrogram_hop_size: usize,
+
+    /// How control characters in prompts are handled (strip or reject).
+    pub prompt_sanitization: PromptSanitization,
+
+    /// How out-of-range generation parameters are handled (strict or clamp).
+    pub param_strictness: ParamStrictness,
+
+    /// Whether prompts are redacted from logs, diagnostics, notifications, and
+    /// track listings. Generation and track IDs still use the full prompt.
+    pub redact_prompts: bool,
+
+    /// Whether to write `now_playing.json` and append to `history.jsonl`
+    /// after each completed generation.
+    /// Default: true
+    pub now_playing: bool,
+
+    /// Directory for `now_playing.json` and `history.jsonl`.
+    /// If None, the cache directory is used.
+    pub now_playing_dir: Option<PathBuf>,
+
+    /// Maximum number of entries kept in `history.jsonl`.
+    /// Default: 100
+    pub history_max_entries: usize,
+
+    /// Program run after every completed generation, with the track's
+    /// metadata in `LOFI_*` environment variables (see [`crate::hooks`]).
+    /// If None, nothing is run.
+    pub on_complete_command: Option<String>,
+
+    /// Program run after every failed generation, with the job and error in
+    /// `LOFI_*` environment variables. If None, nothing is run.
+    pub on_error_command: Option<String>,
+
+    /// Directory for failure diagnostics (inputs of failed decoder/codec runs).
+    /// If None, no diagnostics are written. Set from the config file or
+    /// `LOFI_DEBUG_DUMP_DIR` only, never by `set_config`.
+    pub debug_dump_dir: Option<PathBuf>,
+
+    /// Maximum combined size of failure diagnostics; oldest dumps are removed first.
+    /// Default: 100 MB
+    pub debug_dump_max_bytes: u64,
+
+    /// Free memory that must remain after a generation's estimated peak usage.
+    /// Requests that would cut into this reserve fail with INSUFFICIENT_MEMORY.
+    /// Default: 512 MB
+    pub memory_safety_margin_bytes: u64,
+
+    /// Maximum `generate` calls per 60 second window.
+    /// Default: 10
+    pub rate_limit_generate: u32,
+
+    /// Maximum calls per 60 second window for every other method, counted per method.
+    /// `shutdown` and `ping` are never limited.
+    /// Default: 60
+    pub rate_limit_other: u32,
 }
 
 /// ACE-Step specific configuration options.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct AceStepConfig {
     /// Number of diffusion inference steps.
     /// Higher values = better quality but slower generation.
@@ -109,14 +491,101 @@ pub struct AceStepConfig {
     /// Higher values = more adherence to prompt.
     /// Default: 7.0
     pub guidance_scale: f32,
+
+    /// Steps chosen for the shortest clips when a request asks for
+    /// `inference_steps: "auto"`.
+    /// Default: 27
+    pub auto_steps_min: u32,
+
+    /// Steps added per second of audio when choosing steps automatically.
+    /// Default: 0.25
+    pub auto_steps_per_sec: f32,
+
+    /// Most steps chosen automatically, however long the clip.
+    /// Default: 80
+    pub auto_steps_max: u32,
+}
+
+impl AceStepConfig {
+    /// Validates the ACE-Step defaults.
+    ///
+    /// Returns an error message if validation fails, None otherwise.
+    pub fn validate(&self) -> Option<String> {
+        if !(MIN_INFERENCE_STEPS..=MAX_INFERENCE_STEPS).contains(&self.inference_steps) {
+            return Some(format!(
+                "ace_step.inference_steps must be between {} and {}, got {}",
+                MIN_INFERENCE_STEPS, MAX_INFERENCE_STEPS, self.inference_steps
+            ));
+        }
+
+        if SchedulerType::parse(&self.scheduler).is_none() {
+            return Some(format!(
+                "ace_step.scheduler must be 'euler', 'heun', or 'pingpong', got '{}'",
+                self.scheduler
+            ));
+        }
+
+        if !(MIN_GUIDANCE_SCALE..=MAX_GUIDANCE_SCALE).contains(&self.guidance_scale) {
+            return Some(format!(
+                "ace_step.guidance_scale must be between {} and {}, got {}",
+                MIN_GUIDANCE_SCALE, MAX_GUIDANCE_SCALE, self.guidance_scale
+            ));
+        }
+
+        for (key, steps) in [
+            ("auto_steps_min", self.auto_steps_min),
+            ("auto_steps_max", self.auto_steps_max),
+        ] {
+            if !(MIN_INFERENCE_STEPS..=MAX_INFERENCE_STEPS).contains(&steps) {
+                return Some(format!(
+                    "ace_step.{} must be between {} and {}, got {}",
+                    key, MIN_INFERENCE_STEPS, MAX_INFERENCE_STEPS, steps
+                ));
+            }
+        }
+
+        if self.auto_steps_min > self.auto_steps_max {
+            return Some(format!(
+                "ace_step.auto_steps_min ({}) must not exceed ace_step.auto_steps_max ({})",
+                self.auto_steps_min, self.auto_steps_max
+            ));
+        }
+
+        if !self.auto_steps_per_sec.is_finite() || self.auto_steps_per_sec < 0.0 {
+            return Some(format!(
+                "ace_step.auto_steps_per_sec must be zero or more, got {}",
+                self.auto_steps_per_sec
+            ));
+        }
+
+        None
+    }
+
+    /// Returns the per-request inference steps, or the configured default.
+    pub fn effective_inference_steps(&self, per_request: Option<u32>) -> u32 {
+        per_request.unwrap_or(self.inference_steps)
+    }
+
+    /// Returns the per-request scheduler, or the configured default.
+    pub fn effective_scheduler<'a>(&'a self, per_request: Option<&'a str>) -> &'a str {
+        per_request.unwrap_or(&self.scheduler)
+    }
+
+    /// Returns the per-request guidance scale, or the configured default.
+    pub fn effective_guidance_scale(&self, per_request: Option<f32>) -> f32 {
+        per_request.unwrap_or(self.guidance_scale)
+    }
 }
 
 impl Default for AceStepConfig {
     fn default() -> Self {
         Self {
-            inference_steps: 60,
+            inference_steps: DEFAULT_INFERENCE_STEPS,
             scheduler: "euler".to_string(),
             guidance_scale: 7.0,
+            auto_steps_min: 27,
+            auto_steps_per_sec: 0.25,
+            auto_steps_max: 80,
         }
     }
 }
@@ -132,75 +601,395 @@ impl DaemonConfig {
     /// Reads the following environment variables:
     /// - `LOFI_MODEL_PATH` - Path to MusicGen model directory
     /// - `LOFI_ACE_STEP_MODEL_PATH` - Path to ACE-Step model directory
+    /// - `LOFI_TOKENIZER_PATH` - Path to a shared MusicGen tokenizer.json
+    /// - `LOFI_ACE_STEP_TOKENIZER_PATH` - Path to a shared ACE-Step tokenizer.json
     /// - `LOFI_CACHE_PATH` - Path to cache directory
+    /// - `LOFI_LIBRARY_PATH` - Path to the library directory for saved tracks
+    /// - `LOFI_RESTRICT_OUTPUT_ROOT` - Directory that generate output paths must lie in
+    /// - `LOFI_CACHE_FILENAME` - Filename template for cached tracks
     /// - `LOFI_DEVICE` - Device selection (auto, cpu, cuda, metal)
     /// - `LOFI_BACKEND` - Default backend (musicgen, ace_step)
     /// - `LOFI_THREADS` - Number of threads for CPU execution
+    /// - `LOFI_DCAE_PARALLELISM` - ACE-Step DCAE chunks decoded in parallel
+    /// - `LOFI_MODEL_LOAD_TIMEOUT_SEC` - Seconds to wait for models to load
+    /// - `LOFI_WARM_UP_ON_LOAD` - Warm up MusicGen after loading (1/true or 0/false)
+    /// - `LOFI_ONNX_DISABLE_SPINNING` - Stop ONNX Runtime threads spinning (1/true or 0/false)
+    /// - `LOFI_ONNX_ARENA_EXTEND_STRATEGY` - Arena growth (next_power_of_two, same_as_requested)
     /// - `LOFI_ACE_STEP_STEPS` - ACE-Step inference steps
     /// - `LOFI_ACE_STEP_SCHEDULER` - ACE-Step scheduler (euler, heun, pingpong)
     /// - `LOFI_ACE_STEP_GUIDANCE` - ACE-Step guidance scale
+    /// - `LOFI_DEBUG_DUMP_DIR` - Directory for failure diagnostics
+    /// - `LOFI_PROMPT_SANITIZATION` - Control character handling (strip, reject)
+    /// - `LOFI_PARAM_STRICTNESS` - Out-of-range parameter handling (strict, clamp)
+    /// - `LOFI_REDACT_PROMPTS` - Redact prompts from logs (1/true or 0/false)
+    /// - `LOFI_NOW_PLAYING` - Write now-playing metadata (1/true or 0/false)
+    /// - `LOFI_NOW_PLAYING_DIR` - Directory for now-playing metadata
+    /// - `LOFI_ON_COMPLETE` - Program run after each completed generation
+    /// - `LOFI_ON_ERROR` - Program run after each failed generation
     ///
     /// Falls back to defaults for unset variables.
     pub fn from_env() -> Self {
+        Self::default().with_env()
+    }
+
+    /// Creates a DaemonConfig from variables returned by `var`.
+    ///
+    /// Same as [`DaemonConfig::from_env`], with the lookup made explicit.
+    pub fn from_env_vars(var: impl Fn(&str) -> Option<String>) -> Self {
         let mut config = Self::default();
+        config.apply_env_vars(var);
+        config
+    }
+
+    /// Returns this configuration with the `LOFI_*` environment variables
+    /// applied on top; see [`DaemonConfig::from_env`].
+    pub fn with_env(mut self) -> Self {
+        self.apply_env_vars(|name| std::env::var(name).ok());
+        self
+    }
+
+    /// Overrides settings with the variables returned by `var`.
+    ///
+    /// Unset variables and unparsable values leave the setting unchanged.
+    pub fn apply_env_vars(&mut self, var: impl Fn(&str) -> Option<String>) {
+        if let Some(path) = var("LOFI_MODEL_PATH") {
+            self.model_path = Some(PathBuf::from(path));
+        }
 
-        if let Ok(path) = std::env::var("LOFI_MODEL_PATH") {
-            config.model_path = Some(PathBuf::from(path));
+        if let Some(path) = var("LOFI_ACE_STEP_MODEL_PATH") {
+            self.ace_step_model_path = Some(PathBuf::from(path));
         }
 
-        if let Ok(path) = std::env::var("LOFI_ACE_STEP_MODEL_PATH") {
-            config.ace_step_model_path = Some(PathBuf::from(path));
+        if let Some(path) = var("LOFI_TOKENIZER_PATH") {
+            self.tokenizer_path = Some(PathBuf::from(path));
         }
 
-        if let Ok(path) = std::env::var("LOFI_CACHE_PATH") {
-            config.cache_path = Some(PathBuf::from(path));
+        if let Some(path) = var("LOFI_ACE_STEP_TOKENIZER_PATH") {
+            self.ace_step_tokenizer_path = Some(PathBuf::from(path));
         }
 
-        if let Ok(device_str) = std::env::var("LOFI_DEVICE") {
+        if let Some(path) = var("LOFI_CACHE_PATH") {
+            self.cache_path = Some(PathBuf::from(path));
+        }
+
+        if let Some(path) = var("LOFI_LIBRARY_PATH") {
+            self.library_path = Some(PathBuf::from(path));
+        }
+
+        if let Some(path) = var("LOFI_RESTRICT_OUTPUT_ROOT") {
+            self.restrict_output_root = Some(PathBuf::from(path));
+        }
+
+        if let Some(template) = var("LOFI_CACHE_FILENAME") {
+            self.cache_filename = template;
+        }
+
+        if let Some(device_str) = var("LOFI_DEVICE") {
             if let Some(device) = Device::parse(&device_str) {
-                config.device = device;
+                self.device = device;
             }
         }
 
-        if let Ok(backend_str) = std::env::var("LOFI_BACKEND") {
+        if let Some(backend_str) = var("LOFI_BACKEND") {
             if let Some(backend) = Backend::parse(&backend_str) {
-                config.default_backend = backend;
+                self.default_backend = backend;
             }
         }
 
-        if let Ok(threads_str) = std::env::var("LOFI_THREADS") {
+        if let Some(threads_str) = var("LOFI_THREADS") {
             if let Ok(threads) = threads_str.parse::<u32>() {
                 if threads > 0 {
-                    config.threads = Some(threads);
+                    self.threads = Some(threads);
+                }
+            }
+        }
+
+        if let Some(parallelism_str) = var("LOFI_DCAE_PARALLELISM") {
+            if let Ok(parallelism) = parallelism_str.parse::<usize>() {
+                if parallelism > 0 && parallelism <= MAX_DCAE_PARALLELISM {
+                    self.dcae_parallelism = Some(parallelism);
                 }
             }
         }
 
+        if let Some(timeout_str) = var("LOFI_MODEL_LOAD_TIMEOUT_SEC") {
+            if let Ok(timeout) = timeout_str.parse::<u64>() {
+                if timeout > 0 {
+                    self.model_load_timeout_sec = timeout;
+                }
+            }
+        }
+
+        if let Some(enabled) = var("LOFI_WARM_UP_ON_LOAD").as_deref().and_then(parse_bool) {
+            self.warm_up_on_load = enabled;
+        }
+
+        if let Some(disable) = var("LOFI_ONNX_DISABLE_SPINNING").as_deref().and_then(parse_bool) {
+            self.onnx_disable_spinning = disable;
+        }
+
+        if let Some(strategy_str) = var("LOFI_ONNX_ARENA_EXTEND_STRATEGY") {
+            if let Some(strategy) = OnnxArenaStrategy::parse(&strategy_str) {
+                self.onnx_arena_extend_strategy = strategy;
+            }
+        }
+
         // ACE-Step specific env vars
-        if let Ok(steps_str) = std::env::var("LOFI_ACE_STEP_STEPS") {
+        if let Some(steps_str) = var("LOFI_ACE_STEP_STEPS") {
             if let Ok(steps) = steps_str.parse::<u32>() {
                 if steps > 0 && steps <= 200 {
-                    config.ace_step.inference_steps = steps;
+                    self.ace_step.inference_steps = steps;
                 }
             }
         }
 
-        if let Ok(scheduler) = std::env::var("LOFI_ACE_STEP_SCHEDULER") {
+        if let Some(scheduler) = var("LOFI_ACE_STEP_SCHEDULER") {
             let scheduler = scheduler.to_lowercase();
             if ["euler", "heun", "pingpong"].contains(&scheduler.as_str()) {
-                config.ace_step.scheduler = scheduler;
+                self.ace_step.scheduler = scheduler;
             }
         }
 
-        if let Ok(guidance_str) = std::env::var("LOFI_ACE_STEP_GUIDANCE") {
+        if let Some(guidance_str) = var("LOFI_ACE_STEP_GUIDANCE") {
             if let Ok(guidance) = guidance_str.parse::<f32>() {
                 if (1.0..=20.0).contains(&guidance) {
-                    config.ace_step.guidance_scale = guidance;
+                    self.ace_step.guidance_scale = guidance;
                 }
             }
         }
 
-        config
+        if let Some(path) = var("LOFI_DEBUG_DUMP_DIR") {
+            self.debug_dump_dir = Some(PathBuf::from(path));
+        }
+
+        if let Some(mode_str) = var("LOFI_PROMPT_SANITIZATION") {
+            if let Some(mode) = PromptSanitization::parse(&mode_str) {
+                self.prompt_sanitization = mode;
+            }
+        }
+
+        if let Some(mode_str) = var("LOFI_PARAM_STRICTNESS") {
+            if let Some(mode) = ParamStrictness::parse(&mode_str) {
+                self.param_strictness = mode;
+            }
+        }
+
+        if let Some(redact) = var("LOFI_REDACT_PROMPTS").as_deref().and_then(parse_bool) {
+            self.redact_prompts = redact;
+        }
+
+        if let Some(enabled) = var("LOFI_NOW_PLAYING").as_deref().and_then(parse_bool) {
+            self.now_playing = enabled;
+        }
+
+        if let Some(path) = var("LOFI_NOW_PLAYING_DIR") {
+            self.now_playing_dir = Some(PathBuf::from(path));
+        }
+
+        if let Some(command) = var("LOFI_ON_COMPLETE") {
+            self.on_complete_command = Some(command);
+        }
+
+        if let Some(command) = var("LOFI_ON_ERROR") {
+            self.on_error_command = Some(command);
+        }
+    }
+
+    /// Returns the `LOFI_*` environment variables that reproduce this config.
+    ///
+    /// Only settings that differ from the default and have an environment
+    /// variable (see [`DaemonConfig::from_env`]) are included, in the order
+    /// `from_env` reads them.
+    pub fn to_env_exports(&self) -> Vec<(String, String)> {
+        let defaults = Self::default();
+        let mut exports = Vec::new();
+        let mut export = |name: &str, value: String| exports.push((name.to_string(), value));
+
+        if let Some(path) = &self.model_path {
+            export("LOFI_MODEL_PATH", path.display().to_string());
+        }
+        if let Some(path) = &self.ace_step_model_path {
+            export("LOFI_ACE_STEP_MODEL_PATH", path.display().to_string());
+        }
+        if let Some(path) = &self.tokenizer_path {
+            export("LOFI_TOKENIZER_PATH", path.display().to_string());
+        }
+        if let Some(path) = &self.ace_step_tokenizer_path {
+            export("LOFI_ACE_STEP_TOKENIZER_PATH", path.display().to_string());
+        }
+        if let Some(path) = &self.cache_path {
+            export("LOFI_CACHE_PATH", path.display().to_string());
+        }
+        if let Some(path) = &self.library_path {
+            export("LOFI_LIBRARY_PATH", path.display().to_string());
+        }
+        if let Some(path) = &self.restrict_output_root {
+            export("LOFI_RESTRICT_OUTPUT_ROOT", path.display().to_string());
+        }
+        if self.cache_filename != defaults.cache_filename {
+            export("LOFI_CACHE_FILENAME", self.cache_filename.clone());
+        }
+        if self.device != defaults.device {
+            export("LOFI_DEVICE", self.device.as_str().to_string());
+        }
+        if self.default_backend != defaults.default_backend {
+            export("LOFI_BACKEND", self.default_backend.as_str().to_string());
+        }
+        if let Some(threads) = self.threads {
+            export("LOFI_THREADS", threads.to_string());
+        }
+        if let Some(parallelism) = self.dcae_parallelism {
+            export("LOFI_DCAE_PARALLELISM", parallelism.to_string());
+        }
+        if self.model_load_timeout_sec != defaults.model_load_timeout_sec {
+            export("LOFI_MODEL_LOAD_TIMEOUT_SEC", self.model_load_timeout_sec.to_string());
+        }
+        if self.warm_up_on_load != defaults.warm_up_on_load {
+            export("LOFI_WARM_UP_ON_LOAD", self.warm_up_on_load.to_string());
+        }
+        if self.onnx_disable_spinning != defaults.onnx_disable_spinning {
+            export("LOFI_ONNX_DISABLE_SPINNING", self.onnx_disable_spinning.to_string());
+        }
+        if self.onnx_arena_extend_strategy != defaults.onnx_arena_extend_strategy {
+            let strategy = self.onnx_arena_extend_strategy.as_str().to_string();
+            export("LOFI_ONNX_ARENA_EXTEND_STRATEGY", strategy);
+        }
+        if self.ace_step.inference_steps != defaults.ace_step.inference_steps {
+            export("LOFI_ACE_STEP_STEPS", self.ace_step.inference_steps.to_string());
+        }
+        if self.ace_step.scheduler != defaults.ace_step.scheduler {
+            export("LOFI_ACE_STEP_SCHEDULER", self.ace_step.scheduler.clone());
+        }
+        if self.ace_step.guidance_scale != defaults.ace_step.guidance_scale {
+            export("LOFI_ACE_STEP_GUIDANCE", self.ace_step.guidance_scale.to_string());
+        }
+        if let Some(path) = &self.debug_dump_dir {
+            export("LOFI_DEBUG_DUMP_DIR", path.display().to_string());
+        }
+        if self.prompt_sanitization != defaults.prompt_sanitization {
+            export("LOFI_PROMPT_SANITIZATION", self.prompt_sanitization.as_str().to_string());
+        }
+        if self.param_strictness != defaults.param_strictness {
+            export("LOFI_PARAM_STRICTNESS", self.param_strictness.as_str().to_string());
+        }
+        if self.redact_prompts != defaults.redact_prompts {
+            export("LOFI_REDACT_PROMPTS", self.redact_prompts.to_string());
+        }
+        if self.now_playing != defaults.now_playing {
+            export("LOFI_NOW_PLAYING", self.now_playing.to_string());
+        }
+        if let Some(path) = &self.now_playing_dir {
+            export("LOFI_NOW_PLAYING_DIR", path.display().to_string());
+        }
+        if let Some(command) = &self.on_complete_command {
+            export("LOFI_ON_COMPLETE", command.clone());
+        }
+        if let Some(command) = &self.on_error_command {
+            export("LOFI_ON_ERROR", command.clone());
+        }
+
+        exports
+    }
+
+    /// Loads a configuration from a JSON file.
+    ///
+    /// Missing fields take their default values. The result is validated.
+    pub fn load(path: &Path) -> std::result::Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        let config: Self = serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+        if let Some(error) = config.validate() {
+            return Err(format!("Invalid config file {}: {}", path.display(), error));
+        }
+        Ok(config)
+    }
+
+    /// Writes the configuration to a JSON file, creating parent directories.
+    pub fn save(&self, path: &Path) -> std::result::Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json)
+            .map_err(|e| format!("Failed to write config file {}: {}", path.display(), e))
+    }
+
+    /// Returns a copy of this configuration with runtime settings updated.
+    ///
+    /// Only keys in [`RUNTIME_CONFIG_KEYS`] are accepted; `ace_step` may be
+    /// partially specified. The merged configuration is validated before it
+    /// is returned. Returns an error message naming any rejected keys.
+    pub fn with_runtime_updates(
+        &self,
+        updates: &serde_json::Map<String, serde_json::Value>,
+    ) -> std::result::Result<Self, String> {
+        let reload_required: Vec<&str> = updates
+            .keys()
+            .map(String::as_str)
+            .filter(|k| RELOAD_REQUIRED_CONFIG_KEYS.contains(k))
+            .collect();
+        if !reload_required.is_empty() {
+            return Err(format!(
+                "Cannot change {} at runtime (requires restart)",
+                reload_required.join(", ")
+            ));
+        }
+
+        let unknown: Vec<&str> = updates
+            .keys()
+            .map(String::as_str)
+            .filter(|k| !RUNTIME_CONFIG_KEYS.contains(k))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!("Unknown config keys: {}", unknown.join(", ")));
+        }
+
+        let mut merged = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        for (key, value) in updates {
+            match key.as_str() {
+                // Accept the same spellings as the generate method
+                "default_backend" => continue,
+                "ace_step" => {
+                    let fields = value
+                        .as_object()
+                        .ok_or_else(|| "ace_step must be an object".to_string())?;
+                    for (field, field_value) in fields {
+                        merged["ace_step"][field.as_str()] = field_value.clone();
+                    }
+                }
+                _ => merged[key.as_str()] = value.clone(),
+            }
+        }
+
+        let mut config: Self = serde_json::from_value(merged)
+            .map_err(|e| format!("Invalid config value: {}", e))?;
+
+        if let Some(value) = updates.get("default_backend") {
+            config.default_backend = value
+                .as_str()
+                .and_then(Backend::parse)
+                .ok_or_else(|| format!("Invalid default_backend: {}", value))?;
+        }
+
+        match config.validate() {
+            Some(error) => Err(error),
+            None => Ok(config),
+        }
+    }
+
+    /// Returns the settings for requests that ask for `trim_silence`.
+    pub fn silence_trim(&self) -> SilenceTrim {
+        SilenceTrim::new(self.trim_silence_threshold_db, self.trim_min_silence_sec)
+    }
+
+    /// Returns the STFT settings for spectrogram thumbnails.
+    pub fn spectrogram_options(&self) -> SpectrogramOptions {
+        SpectrogramOptions {
+            fft_size: self.spectrogram_fft_size,
+            hop_size: self.spectrogram_hop_size,
+        }
     }
 
     /// Returns the effective MusicGen model path, using platform defaults if not specified.
@@ -221,6 +1010,14 @@ impl DaemonConfig {
         }
     }
 
+    /// Returns the tokenizer override for `backend`, if one is configured.
+    pub fn tokenizer_override(&self, backend: Backend) -> Option<&Path> {
+        match backend {
+            Backend::MusicGen => self.tokenizer_path.as_deref(),
+            Backend::AceStep => self.ace_step_tokenizer_path.as_deref(),
+        }
+    }
+
     /// Returns the effective cache path, using platform defaults if not specified.
     pub fn effective_cache_path(&self) -> PathBuf {
         if let Some(ref path) = self.cache_path {
@@ -230,6 +1027,44 @@ impl DaemonConfig {
         }
     }
 
+    /// Returns the library directory, using platform defaults if not specified.
+    pub fn effective_library_path(&self) -> PathBuf {
+        match self.library_path {
+            Some(ref path) => path.clone(),
+            None => default_library_path(),
+        }
+    }
+
+    /// Returns the parsed cache filename template, or the default template if
+    /// `cache_filename` is invalid.
+    pub fn cache_filename_template(&self) -> OutputTemplate {
+        OutputTemplate::parse(&self.cache_filename).unwrap_or_else(|_| {
+            OutputTemplate::parse(DEFAULT_CACHE_FILENAME).expect("default template is valid")
+        })
+    }
+
+    /// Returns the directory for now-playing metadata, defaulting to the cache path.
+    pub fn effective_now_playing_dir(&self) -> PathBuf {
+        match self.now_playing_dir {
+            Some(ref path) => path.clone(),
+            None => self.effective_cache_path(),
+        }
+    }
+
+    /// Returns the number of DCAE sessions to load for ACE-Step.
+    ///
+    /// Uses `dcae_parallelism` if set, otherwise a default based on total
+    /// memory and CPU cores.
+    pub fn effective_dcae_parallelism(&self) -> usize {
+        match self.dcae_parallelism {
+            Some(parallelism) => parallelism,
+            None => default_dcae_parallelism(
+                total_memory(),
+                std::thread::available_parallelism().map_or(1, |n| n.get()),
+            ),
+        }
+    }
+
     /// Validates the configuration.
     ///
     /// Returns an error message if validation fails, None otherwise.
@@ -244,6 +1079,137 @@ impl DaemonConfig {
             }
         }
 
+        if let Some(parallelism) = self.dcae_parallelism {
+            if !(1..=MAX_DCAE_PARALLELISM).contains(&parallelism) {
+                return Some(format!(
+                    "dcae_parallelism must be between 1 and {}, got {}",
+                    MAX_DCAE_PARALLELISM, parallelism
+                ));
+            }
+        }
+
+        if let Some(error) = self.ace_step.validate() {
+            return Some(error);
+        }
+
+        if !(-70.0..=0.0).contains(&self.target_lufs) {
+            return Some(format!(
+                "target_lufs must be between -70.0 and 0.0, got {}",
+                self.target_lufs
+            ));
+        }
+
+        if !(-120.0..=0.0).contains(&self.trim_silence_threshold_db) {
+            return Some(format!(
+                "trim_silence_threshold_db must be between -120.0 and 0.0, got {}",
+                self.trim_silence_threshold_db
+            ));
+        }
+
+        if !(0.0..=MAX_TRIM_MIN_SILENCE_SEC).contains(&self.trim_min_silence_sec) {
+            return Some(format!(
+                "trim_min_silence_sec must be between 0.0 and {}, got {}",
+                MAX_TRIM_MIN_SILENCE_SEC, self.trim_min_silence_sec
+            ));
+        }
+
+        if !(1..=50).contains(&self.progress_interval_percent) {
+            return Some(format!(
+                "progress_interval_percent must be between 1 and 50, got {}",
+                self.progress_interval_percent
+            ));
+        }
+
+        if self.min_notify_interval_ms > MAX_NOTIFY_INTERVAL_MS {
+            return Some(format!(
+                "min_notify_interval_ms must be at most {}, got {}",
+                MAX_NOTIFY_INTERVAL_MS, self.min_notify_interval_ms
+            ));
+        }
+
+        if self.cache_max_bytes == Some(0) {
+            return Some("cache_max_bytes must be > 0".to_string());
+        }
+
+        if let Some(root) = &self.restrict_output_root {
+            if !root.is_absolute() {
+                return Some(format!(
+                    "restrict_output_root must be an absolute path, got {}",
+                    root.display()
+                ));
+            }
+            // The library is written to as well, so it may not escape the root
+            if let Some(path) = &self.library_path {
+                let escapes = path
+                    .components()
+                    .any(|c| matches!(c, std::path::Component::ParentDir));
+                if escapes || !path.starts_with(root) {
+                    return Some(format!(
+                        "library_path {} must be inside restrict_output_root {}",
+                        path.display(),
+                        root.display()
+                    ));
+                }
+            }
+        }
+
+        match OutputTemplate::parse(&self.cache_filename) {
+            Ok(template) if template.has_separator() => {
+                return Some(format!(
+                    "cache_filename must not contain path separators, got '{}'",
+                    self.cache_filename
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => return Some(format!("cache_filename: {}", e)),
+        }
+
+        if !(1..=100).contains(&self.max_queue_size) {
+            return Some(format!(
+                "max_queue_size must be between 1 and 100, got {}",
+                self.max_queue_size
+            ));
+        }
+
+        if self.max_starvation_sec == 0 {
+            return Some("max_starvation_sec must be > 0".to_string());
+        }
+
+        if self.model_idle_timeout_sec == Some(0) {
+            return Some("model_idle_timeout_sec must be > 0".to_string());
+        }
+
+        if self.model_load_timeout_sec == 0 {
+            return Some("model_load_timeout_sec must be > 0".to_string());
+        }
+
+        if !self.spectrogram_fft_size.is_power_of_two()
+            || !(64..=16384).contains(&self.spectrogram_fft_size)
+        {
+            return Some(format!(
+                "spectrogram_fft_size must be a power of two between 64 and 16384, got {}",
+                self.spectrogram_fft_size
+            ));
+        }
+
+        if self.spectrogram_hop_size == 0 || self.spectrogram_hop_size > self.spectrogram_fft_size {
+            return Some(format!(
+                "spectrogram_hop_size must be between 1 and spectrogram_fft_size, got {}",
+                self.spectrogram_hop_size
+            ));
+        }
+
+        if !(1..=MAX_HISTORY_ENTRIES).contains(&self.history_max_entries) {
+            return Some(format!(
+                "history_max_entries must be between 1 and {}, got {}",
+                MAX_HISTORY_ENTRIES, self.history_max_entries
+            ));
+        }
+
+        if self.rate_limit_generate == 0 || self.rate_limit_other == 0 {
+            return Some("rate limits must be > 0".to_string());
+        }
+
         None
     }
 }
@@ -253,15 +1219,85 @@ impl Default for DaemonConfig {
         Self {
             model_path: None,
             ace_step_model_path: None,
+            tokenizer_path: None,
+            ace_step_tokenizer_path: None,
             cache_path: None,
+            library_path: None,
+            restrict_output_root: None,
+            cache_filename: DEFAULT_CACHE_FILENAME.to_string(),
             device: Device::Auto,
             default_backend: Backend::default(),
             threads: None,
+            dcae_parallelism: None,
+            warm_up_on_load: true,
+            onnx_disable_spinning: false,
+            onnx_arena_extend_strategy: OnnxArenaStrategy::default(),
             ace_step: AceStepConfig::default(),
+            normalize_audio: false,
+            target_lufs: -14.0,
+            trim_silence_threshold_db: DEFAULT_TRIM_THRESHOLD_DB,
+            trim_min_silence_sec: DEFAULT_TRIM_MIN_SILENCE_SEC,
+            progress_interval_percent: 5,
+            min_notify_interval_ms: DEFAULT_MIN_NOTIFY_INTERVAL_MS,
+            cache_max_bytes: None,
+            invalidate_on_model_update: false,
+            max_queue_size: MAX_QUEUE_SIZE,
+            queue_policy: QueuePolicy::default(),
+            max_starvation_sec: DEFAULT_MAX_STARVATION_SEC,
+            parallel_backends: false,
+            model_idle_timeout_sec: None,
+            model_load_timeout_sec: DEFAULT_MODEL_LOAD_TIMEOUT_SEC,
+            generate_thumbnails: false,
+            spectrogram_fft_size: DEFAULT_FFT_SIZE,
+            spectrogram_hop_size: DEFAULT_HOP_SIZE,
+            prompt_sanitization: PromptSanitization::default(),
+            param_strictness: ParamStrictness::default(),
+            redact_prompts: false,
+            now_playing: true,
+            now_playing_dir: None,
+            history_max_entries: DEFAULT_HISTORY_MAX_ENTRIES,
+            on_complete_command: None,
+            on_error_command: None,
+            debug_dump_dir: None,
+            debug_dump_max_bytes: DEFAULT_DEBUG_DUMP_MAX_BYTES,
+            memory_safety_margin_bytes: DEFAULT_MEMORY_SAFETY_MARGIN_BYTES,
+            rate_limit_generate: 10,
+            rate_limit_other: 60,
         }
     }
 }
 
+/// Returns the `LOFI_REDACT_PROMPTS` setting, if set to a recognized value.
+///
+/// Accepts 1/true/yes/on and 0/false/no/off, case-insensitively.
+pub fn redact_prompts_from_env() -> Option<bool> {
+    parse_bool(&std::env::var("LOFI_REDACT_PROMPTS").ok()?)
+}
+
+/// Parses a boolean environment variable value.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns the MusicGen token dump file from `LOFI_DUMP_TOKENS`, if set.
+///
+/// Debug setting: every MusicGen generation overwrites the file with its
+/// token frames.
+pub fn token_dump_path() -> Option<PathBuf> {
+    std::env::var_os("LOFI_DUMP_TOKENS")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Returns the config file path from `LOFI_CONFIG`, if set.
+pub fn config_file_path() -> Option<PathBuf> {
+    std::env::var_os("LOFI_CONFIG").map(PathBuf::from)
+}
+
 /// Returns the platform-specific default model storage path.
 ///
 /// Uses the `directories` crate to find appropriate locations:
@@ -292,6 +1328,21 @@ fn default_cache_path() -> PathBuf {
     }
 }
 
+/// Returns the platform-specific default library path for saved tracks.
+///
+/// Uses the `directories` crate to find appropriate locations:
+/// - macOS: ~/Library/Application Support/lofi.nvim/library
+/// - Linux: ~/.local/share/lofi.nvim/library
+/// - Windows: C:\Users\<user>\AppData\Roaming\lofi.nvim\data\library
+fn default_library_path() -> PathBuf {
+    if let Some(proj_dirs) = directories::ProjectDirs::from("", "", "lofi.nvim") {
+        proj_dirs.data_dir().join("library")
+    } else {
+        // Fallback to current directory
+        PathBuf::from("./library")
+    }
+}
+
 /// Returns the platform-specific default ACE-Step model storage path.
 ///
 /// Uses the `directories` crate to find appropriate locations:
@@ -321,6 +1372,30 @@ mod tests {
         assert_eq!(Device::parse("invalid"), None);
     }
 
+    #[test]
+    fn prompt_sanitization_parsing() {
+        assert_eq!(PromptSanitization::parse("STRIP"), Some(PromptSanitization::Strip));
+        assert_eq!(PromptSanitization::parse("reject"), Some(PromptSanitization::Reject));
+        assert_eq!(PromptSanitization::parse("ignore"), None);
+        assert_eq!(PromptSanitization::default().as_str(), "strip");
+    }
+
+    #[test]
+    fn param_strictness_parsing() {
+        assert_eq!(ParamStrictness::parse("CLAMP"), Some(ParamStrictness::Clamp));
+        assert_eq!(ParamStrictness::parse("strict"), Some(ParamStrictness::Strict));
+        assert_eq!(ParamStrictness::parse("lenient"), None);
+        assert_eq!(ParamStrictness::default(), ParamStrictness::Strict);
+    }
+
+    #[test]
+    fn bool_env_parsing() {
+        assert_eq!(parse_bool("1"), Some(true));
+        assert_eq!(parse_bool(" TRUE "), Some(true));
+        assert_eq!(parse_bool("off"), Some(false));
+        assert_eq!(parse_bool("maybe"), None);
+    }
+
     #[test]
     fn device_display() {
         assert_eq!(Device::Auto.to_string(), "auto");
@@ -337,6 +1412,140 @@ mod tests {
 
         config.threads = Some(4);
         assert!(config.validate().is_none());
+
+        config.dcae_parallelism = Some(0);
+        assert!(config.validate().unwrap().contains("dcae_parallelism"));
+        config.dcae_parallelism = Some(MAX_DCAE_PARALLELISM + 1);
+        assert!(config.validate().is_some());
+        config.dcae_parallelism = Some(2);
+        assert!(config.validate().is_none());
+        assert_eq!(config.effective_dcae_parallelism(), 2);
+    }
+
+    #[test]
+    fn config_validation_runtime_fields() {
+        let mut config = DaemonConfig::new();
+        config.ace_step.guidance_scale = 50.0;
+        assert!(config.validate().unwrap().contains("guidance_scale"));
+
+        let mut config = DaemonConfig::new();
+        config.ace_step.scheduler = "dpm".to_string();
+        assert!(config.validate().is_some());
+
+        let mut config = DaemonConfig::new();
+        config.ace_step.auto_steps_min = 90;
+        assert!(config.validate().unwrap().contains("auto_steps_min"));
+        config.ace_step.auto_steps_max = 201;
+        assert!(config.validate().unwrap().contains("auto_steps_max"));
+        config.ace_step.auto_steps_max = 120;
+        config.ace_step.auto_steps_per_sec = f32::NAN;
+        assert!(config.validate().unwrap().contains("auto_steps_per_sec"));
+
+        let mut config = DaemonConfig::new();
+        config.progress_interval_percent = 0;
+        assert!(config.validate().is_some());
+
+        let mut config = DaemonConfig::new();
+        config.min_notify_interval_ms = 0;
+        assert!(config.validate().is_none());
+        config.min_notify_interval_ms = MAX_NOTIFY_INTERVAL_MS + 1;
+        assert!(config.validate().unwrap().contains("min_notify_interval_ms"));
+
+        let mut config = DaemonConfig::new();
+        config.max_queue_size = 0;
+        assert!(config.validate().is_some());
+
+        let mut config = DaemonConfig::new();
+        config.model_load_timeout_sec = 0;
+        assert!(config.validate().unwrap().contains("model_load_timeout_sec"));
+
+        let mut config = DaemonConfig::new();
+        config.target_lufs = 3.0;
+        assert!(config.validate().is_some());
+
+        let mut config = DaemonConfig::new();
+        config.trim_silence_threshold_db = 6.0;
+        assert!(config.validate().unwrap().contains("trim_silence_threshold_db"));
+        config.trim_silence_threshold_db = -40.0;
+        config.trim_min_silence_sec = -1.0;
+        assert!(config.validate().unwrap().contains("trim_min_silence_sec"));
+
+        let mut config = DaemonConfig::new();
+        config.spectrogram_fft_size = 1000;
+        assert!(config.validate().unwrap().contains("power of two"));
+
+        let mut config = DaemonConfig::new();
+        config.spectrogram_hop_size = 2048;
+        assert!(config.validate().is_some());
+
+        let mut config = DaemonConfig::new();
+        config.history_max_entries = 0;
+        assert!(config.validate().unwrap().contains("history_max_entries"));
+
+        let mut config = DaemonConfig::new();
+        config.cache_filename = "{prompt}-{seed}.wav".to_string();
+        assert!(config.validate().is_none());
+        config.cache_filename = "%Y/{track_id}.wav".to_string();
+        assert!(config.validate().unwrap().contains("path separators"));
+        config.cache_filename = "{title}.wav".to_string();
+        assert!(config.validate().unwrap().contains("cache_filename"));
+        assert!(config.cache_filename_template().uses_track_id());
+
+        let mut config = DaemonConfig::new();
+        config.restrict_output_root = Some(PathBuf::from("renders"));
+        assert!(config.validate().unwrap().contains("restrict_output_root"));
+
+        config.restrict_output_root = Some(PathBuf::from("/srv/lofi"));
+        config.library_path = Some(PathBuf::from("/srv/lofi/library"));
+        assert!(config.validate().is_none());
+        for outside in ["/home/me/Music", "/srv/lofi/../elsewhere", "/srv/lofi-other"] {
+            config.library_path = Some(PathBuf::from(outside));
+            assert!(config.validate().unwrap().contains("library_path"), "{}", outside);
+        }
+        config.library_path = None;
+    }
+
+    #[test]
+    fn runtime_updates_merge_whitelisted_keys() {
+        let config = DaemonConfig::new();
+        let updates = serde_json::json!({
+            "default_backend": "ace-step",
+            "ace_step": { "guidance_scale": 9.0 },
+            "max_queue_size": 4,
+        });
+
+        let updated = config
+            .with_runtime_updates(updates.as_object().unwrap())
+            .unwrap();
+        assert_eq!(updated.default_backend, Backend::AceStep);
+        assert_eq!(updated.ace_step.guidance_scale, 9.0);
+        assert_eq!(updated.ace_step.inference_steps, 60);
+        assert_eq!(updated.max_queue_size, 4);
+    }
+
+    #[test]
+    fn runtime_updates_reject_restart_and_unknown_keys() {
+        let config = DaemonConfig::new();
+
+        let updates = serde_json::json!({ "device": "cpu", "threads": 4 });
+        let err = config
+            .with_runtime_updates(updates.as_object().unwrap())
+            .unwrap_err();
+        assert!(err.contains("device"));
+        assert!(err.contains("threads"));
+
+        // Clients cannot redirect diagnostics dumps
+        let updates = serde_json::json!({ "debug_dump_dir": "/tmp/elsewhere" });
+        let err = config
+            .with_runtime_updates(updates.as_object().unwrap())
+            .unwrap_err();
+        assert!(err.contains("debug_dump_dir"));
+
+        let updates = serde_json::json!({ "bogus": true });
+        let err = config
+            .with_runtime_updates(updates.as_object().unwrap())
+            .unwrap_err();
+        assert!(err.contains("bogus"));
     }
 
     #[test]
@@ -350,6 +1559,20 @@ mod tests {
         assert!(!model_path.as_os_str().is_empty());
         assert!(!cache_path.as_os_str().is_empty());
         assert!(!ace_step_path.as_os_str().is_empty());
+        assert_eq!(config.effective_now_playing_dir(), cache_path);
+        // The library is kept apart from the prunable cache
+        assert_ne!(config.effective_library_path(), cache_path);
+
+        let mut config = DaemonConfig::new();
+        config.now_playing_dir = Some(PathBuf::from("/run/lofi"));
+        assert_eq!(config.effective_now_playing_dir(), PathBuf::from("/run/lofi"));
+        config.library_path = Some(PathBuf::from("/home/me/Music/lofi"));
+        assert_eq!(config.effective_library_path(), PathBuf::from("/home/me/Music/lofi"));
+
+        // Config files written before the rename still set the library
+        let config: DaemonConfig =
+            serde_json::from_str(r#"{ "output_path": "/home/me/Music/lofi" }"#).unwrap();
+        assert_eq!(config.library_path, Some(PathBuf::from("/home/me/Music/lofi")));
     }
 
     #[test]
@@ -362,6 +1585,64 @@ mod tests {
         assert!(config.threads.is_none());
     }
 
+    #[test]
+    fn env_vars_override_only_what_they_set() {
+        let mut config = DaemonConfig::new();
+        config.cache_path = Some(PathBuf::from("/from/file"));
+        config.threads = Some(2);
+        config.apply_env_vars(|name| match name {
+            "LOFI_THREADS" => Some("8".to_string()),
+            "LOFI_DEVICE" => Some("not-a-device".to_string()),
+            _ => None,
+        });
+        assert_eq!(config.threads, Some(8));
+        assert_eq!(config.cache_path, Some(PathBuf::from("/from/file")));
+        assert_eq!(config.device, Device::Auto);
+    }
+
+    #[test]
+    fn env_exports_round_trip() {
+        assert!(DaemonConfig::default().to_env_exports().is_empty());
+
+        let mut config = DaemonConfig::new();
+        config.model_path = Some(PathBuf::from("/models/musicgen"));
+        config.tokenizer_path = Some(PathBuf::from("/models/shared/t5-tokenizer.json"));
+        config.ace_step_tokenizer_path = Some(PathBuf::from("/models/shared/umt5-tokenizer.json"));
+        config.cache_path = Some(PathBuf::from("/tmp/lofi tracks"));
+        config.library_path = Some(PathBuf::from("/home/me/Music/lofi"));
+        config.restrict_output_root = Some(PathBuf::from("/srv/lofi"));
+        config.cache_filename = "%Y%m%d-{prompt}-{seed}.wav".to_string();
+        config.device = Device::Cpu;
+        config.default_backend = Backend::AceStep;
+        config.threads = Some(4);
+        config.dcae_parallelism = Some(2);
+        config.model_load_timeout_sec = 600;
+        config.warm_up_on_load = false;
+        config.onnx_disable_spinning = true;
+        config.onnx_arena_extend_strategy = OnnxArenaStrategy::SameAsRequested;
+        config.ace_step.inference_steps = 80;
+        config.ace_step.scheduler = "heun".to_string();
+        config.ace_step.guidance_scale = 10.5;
+        config.prompt_sanitization = PromptSanitization::Reject;
+        config.param_strictness = ParamStrictness::Clamp;
+        config.redact_prompts = true;
+        config.now_playing = false;
+        config.now_playing_dir = Some(PathBuf::from("/run/lofi"));
+        config.on_complete_command = Some("/usr/local/bin/lofi-done".to_string());
+        config.on_error_command = Some("/usr/local/bin/lofi-failed".to_string());
+
+        let exports = config.to_env_exports();
+        assert!(exports.iter().all(|(name, _)| name.starts_with("LOFI_")));
+        assert!(!exports.iter().any(|(name, _)| name == "LOFI_ACE_STEP_MODEL_PATH"));
+
+        let vars: std::collections::HashMap<String, String> = exports.into_iter().collect();
+        let restored = DaemonConfig::from_env_vars(|name| vars.get(name).cloned());
+        assert_eq!(
+            serde_json::to_value(&restored).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+    }
+
     #[test]
     fn ace_step_config_defaults() {
         let config = AceStepConfig::default();
@@ -370,6 +1651,22 @@ mod tests {
         assert_eq!(config.guidance_scale, 7.0);
     }
 
+    #[test]
+    fn ace_step_per_request_values_take_precedence() {
+        let config = AceStepConfig {
+            inference_steps: 80,
+            scheduler: "heun".to_string(),
+            guidance_scale: 10.0,
+            ..AceStepConfig::default()
+        };
+        assert_eq!(config.effective_inference_steps(None), 80);
+        assert_eq!(config.effective_inference_steps(Some(30)), 30);
+        assert_eq!(config.effective_scheduler(None), "heun");
+        assert_eq!(config.effective_scheduler(Some("pingpong")), "pingpong");
+        assert_eq!(config.effective_guidance_scale(None), 10.0);
+        assert_eq!(config.effective_guidance_scale(Some(4.5)), 4.5);
+    }
+
     #[test]
     fn daemon_config_has_ace_step_config() {
         let config = DaemonConfig::new();
@@ -377,4 +1674,25 @@ mod tests {
         assert_eq!(config.ace_step.scheduler, "euler");
         assert_eq!(config.ace_step.guidance_scale, 7.0);
     }
+
+    #[test]
+    fn load_fills_missing_fields_with_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.json");
+        std::fs::write(&path, r#"{ "default_backend": "ace_step", "ace_step": { "inference_steps": 30 } }"#)
+            .unwrap();
+
+        let config = DaemonConfig::load(&path).unwrap();
+        assert_eq!(config.default_backend, Backend::AceStep);
+        assert_eq!(config.ace_step.inference_steps, 30);
+        assert_eq!(config.ace_step.guidance_scale, 7.0);
+        assert_eq!(config.max_queue_size, MAX_QUEUE_SIZE);
+
+        config.save(&path).unwrap();
+        let reloaded = DaemonConfig::load(&path).unwrap();
+        assert_eq!(reloaded.ace_step.inference_steps, 30);
+
+        std::fs::write(&path, r#"{ "max_queue_size": 0 }"#).unwrap();
+        assert!(DaemonConfig::load(&path).is_err());
+    }
 }
@@ -0,0 +1,360 @@
+//! Failure diagnostics for bug reports.
+//!
+//! When the MusicGen decoder or audio codec fails, the inputs that triggered the
+//! failure are otherwise lost. If `DaemonConfig::debug_dump_dir` is set, they are
+//! written to a timestamped folder so they can be attached to an upstream issue.
+//! Total dump size is capped; the oldest dumps are removed first.
+//!
+//! For debugging generation quality, the MusicGen token stream of every
+//! generation can also be written to a CSV file with [`write_token_dump`].
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::config::DaemonConfig;
+use crate::error::{DaemonError, ErrorCode, Result};
+
+/// Pipeline stage where a failure occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureStage {
+    /// Autoregressive token generation.
+    Decoder,
+    /// EnCodec token-to-audio decoding.
+    AudioCodec,
+}
+
+impl FailureStage {
+    /// Returns the string representation of the stage.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureStage::Decoder => "decoder",
+            FailureStage::AudioCodec => "audio_codec",
+        }
+    }
+}
+
+/// Inputs and environment of a failed generation.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailureContext {
+    /// Stage that failed.
+    pub stage: FailureStage,
+    /// Error message reported by the stage.
+    pub error: String,
+    /// Prompt used for generation, redacted when prompt redaction is enabled.
+    pub prompt: String,
+    /// Seed used for generation.
+    pub seed: u64,
+    /// Number of token frames requested.
+    pub target_frames: usize,
+    /// Model identifier.
+    pub model_version: String,
+    /// Execution provider used for inference.
+    pub provider: String,
+    /// Token frames passed to the failing stage (empty for decoder failures).
+    #[serde(skip)]
+    pub tokens: Vec<[i64; 4]>,
+}
+
+/// Writes failure dumps to a directory with a total size cap.
+#[derive(Debug, Clone)]
+pub struct FailureDumper {
+    dir: PathBuf,
+    max_total_bytes: u64,
+}
+
+impl FailureDumper {
+    /// Creates a dumper writing to `dir`, keeping at most `max_total_bytes` of dumps.
+    pub fn new(dir: impl Into<PathBuf>, max_total_bytes: u64) -> Self {
+        Self {
+            dir: dir.into(),
+            max_total_bytes,
+        }
+    }
+
+    /// Creates a dumper from the configuration.
+    ///
+    /// Returns None if `debug_dump_dir` is not set.
+    pub fn from_config(config: &DaemonConfig) -> Option<Self> {
+        config
+            .debug_dump_dir
+            .as_ref()
+            .map(|dir| Self::new(dir, config.debug_dump_max_bytes))
+    }
+
+    /// Returns the dump directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes a failure dump and applies the retention cap.
+    ///
+    /// Each dump is a folder named `<unix_millis>-<stage>` containing
+    /// `context.json` and `tokens.json`. Returns the folder path.
+    pub fn dump_failure(&self, context: &FailureContext) -> Result<PathBuf> {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+
+        let mut folder = self
+            .dir
+            .join(format!("{}-{}", millis, context.stage.as_str()));
+        let mut suffix = 1;
+        while folder.exists() {
+            folder = self
+                .dir
+                .join(format!("{}-{}-{}", millis, context.stage.as_str(), suffix));
+            suffix += 1;
+        }
+
+        std::fs::create_dir_all(&folder).map_err(|e| dump_error(&folder, e))?;
+
+        let context_json =
+            serde_json::to_vec_pretty(context).map_err(|e| dump_error(&folder, e))?;
+        std::fs::write(folder.join("context.json"), context_json)
+            .map_err(|e| dump_error(&folder, e))?;
+
+        let tokens_json =
+            serde_json::to_vec(&context.tokens).map_err(|e| dump_error(&folder, e))?;
+        std::fs::write(folder.join("tokens.json"), tokens_json)
+            .map_err(|e| dump_error(&folder, e))?;
+
+        self.enforce_retention(&folder);
+        Ok(folder)
+    }
+
+    /// Removes the oldest dumps until the total size fits the cap.
+    ///
+    /// The dump at `keep` is never removed.
+    fn enforce_retention(&self, keep: &Path) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+
+        // Folder names start with a millisecond timestamp, so name order is age order
+        let mut dumps: Vec<(PathBuf, u64)> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .map(|path| {
+                let size = dir_size(&path);
+                (path, size)
+            })
+            .collect();
+        dumps.sort_by(|a, b| a.0.file_name().cmp(&b.0.file_name()));
+
+        let mut total: u64 = dumps.iter().map(|(_, size)| size).sum();
+        for (path, size) in dumps {
+            if total <= self.max_total_bytes {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+            if std::fs::remove_dir_all(&path).is_ok() {
+                total = total.saturating_sub(size);
+            }
+        }
+    }
+}
+
+/// Dumps a failure if diagnostics are enabled and attaches the dump path to the error.
+///
+/// Only `ModelInferenceFailed` errors are dumped. Failures while writing the
+/// dump are logged and the original error is returned unchanged.
+pub fn report_failure(
+    dumper: Option<&FailureDumper>,
+    error: DaemonError,
+    context: FailureContext,
+) -> DaemonError {
+    let Some(dumper) = dumper else {
+        return error;
+    };
+    if error.code != ErrorCode::ModelInferenceFailed {
+        return error;
+    }
+
+    match dumper.dump_failure(&context) {
+        Ok(path) => {
+            eprintln!("Saved failure diagnostics to {}", path.display());
+            error.with_dump_path(path)
+        }
+        Err(e) => {
+            eprintln!("Failed to save failure diagnostics: {}", e);
+            error
+        }
+    }
+}
+
+/// Writes MusicGen token frames to `path` as CSV.
+///
+/// One line per frame, `frame,codebook_0,codebook_1,codebook_2,codebook_3`,
+/// so the streams of two runs can be compared with `diff`.
+pub fn write_token_dump(path: &Path, tokens: &[[i64; 4]]) -> Result<()> {
+    let mut csv = String::from("frame,codebook_0,codebook_1,codebook_2,codebook_3\n");
+    for (frame, codes) in tokens.iter().enumerate() {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            frame, codes[0], codes[1], codes[2], codes[3]
+        ));
+    }
+    std::fs::write(path, csv).map_err(|e| {
+        DaemonError::with_source(
+            ErrorCode::ModelInferenceFailed,
+            format!("Failed to write token dump to {}", path.display()),
+            e,
+        )
+    })
+}
+
+/// Returns the combined size of all files directly inside `dir`.
+fn dir_size(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.metadata().ok())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn dump_error(folder: &Path, e: impl std::error::Error + Send + Sync + 'static) -> DaemonError {
+    DaemonError::with_source(
+        ErrorCode::ModelInferenceFailed,
+        format!("Failed to write failure dump to {}", folder.display()),
+        e,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_context(stage: FailureStage, frames: usize) -> FailureContext {
+        FailureContext {
+            stage,
+            error: "index out of range".to_string(),
+            prompt: "lofi beats".to_string(),
+            seed: 42,
+            target_frames: frames,
+            model_version: "musicgen-small-fp16-v1".to_string(),
+            provider: "CPU".to_string(),
+            tokens: vec![[1, 2, 3, 2048]; frames],
+        }
+    }
+
+    #[test]
+    fn dump_writes_context_and_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let dumper = FailureDumper::new(dir.path(), u64::MAX);
+
+        let path = dumper
+            .dump_failure(&make_context(FailureStage::AudioCodec, 3))
+            .unwrap();
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .ends_with("-audio_codec"));
+
+        let context: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(path.join("context.json")).unwrap()).unwrap();
+        assert_eq!(context["stage"], "audio_codec");
+        assert_eq!(context["seed"], 42);
+        assert_eq!(context["provider"], "CPU");
+
+        let tokens: Vec<[i64; 4]> =
+            serde_json::from_slice(&std::fs::read(path.join("tokens.json")).unwrap()).unwrap();
+        assert_eq!(tokens, vec![[1, 2, 3, 2048]; 3]);
+    }
+
+    #[test]
+    fn retention_removes_oldest_dumps() {
+        let dir = tempfile::tempdir().unwrap();
+        // Each dump is a few hundred bytes; allow roughly two of them
+        let single = {
+            let probe = FailureDumper::new(dir.path().join("probe"), u64::MAX);
+            dir_size(
+                &probe
+                    .dump_failure(&make_context(FailureStage::Decoder, 10))
+                    .unwrap(),
+            )
+        };
+        let dumper = FailureDumper::new(dir.path().join("dumps"), single * 2 + single / 2);
+
+        let first = dumper
+            .dump_failure(&make_context(FailureStage::Decoder, 10))
+            .unwrap();
+        let second = dumper
+            .dump_failure(&make_context(FailureStage::Decoder, 10))
+            .unwrap();
+        let third = dumper
+            .dump_failure(&make_context(FailureStage::Decoder, 10))
+            .unwrap();
+
+        assert!(!first.exists());
+        assert!(second.exists());
+        assert!(third.exists());
+    }
+
+    #[test]
+    fn no_dump_without_config() {
+        let config = DaemonConfig::default();
+        let dumper = FailureDumper::from_config(&config);
+        assert!(dumper.is_none());
+
+        let error = DaemonError::model_inference_failed("codec failed");
+        let error = report_failure(
+            dumper.as_ref(),
+            error,
+            make_context(FailureStage::AudioCodec, 1),
+        );
+        assert!(error.dump_path.is_none());
+    }
+
+    #[test]
+    fn report_failure_attaches_dump_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DaemonConfig {
+            debug_dump_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let dumper = FailureDumper::from_config(&config).unwrap();
+
+        let error = report_failure(
+            Some(&dumper),
+            DaemonError::model_inference_failed("codec failed"),
+            make_context(FailureStage::AudioCodec, 2),
+        );
+        let path = error.dump_path.clone().unwrap();
+        assert!(path.join("tokens.json").exists());
+        assert!(error.to_string().contains(&path.display().to_string()));
+
+        // Non-inference errors are not dumped
+        let error = report_failure(
+            Some(&dumper),
+            DaemonError::empty_prompt(),
+            make_context(FailureStage::Decoder, 0),
+        );
+        assert!(error.dump_path.is_none());
+    }
+
+    #[test]
+    fn token_dump_writes_one_line_per_frame() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tokens.csv");
+        write_token_dump(&path, &[[1, 2, 3, 4], [5, 6, 7, 2048]]).unwrap();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "frame,codebook_0,codebook_1,codebook_2,codebook_3");
+        assert_eq!(&lines[1..], ["0,1,2,3,4", "1,5,6,7,2048"]);
+
+        assert!(write_token_dump(&dir.path().join("missing/tokens.csv"), &[]).is_err());
+    }
+}
@@ -4,6 +4,7 @@
 //! consistent error handling and reporting.
 
 use std::fmt;
+use std::path::{Path, PathBuf};
 
 /// Error codes returned by the daemon in error responses.
 ///
@@ -58,6 +59,31 @@ pub enum ErrorCode {
     /// Generation was cancelled.
     /// Trigger: User requested cancellation via cancel RPC.
     GenerationCancelled,
+
+    /// Not enough free system memory for the requested generation.
+    /// Trigger: Pre-flight estimate exceeds available memory minus the safety margin.
+    InsufficientMemory,
+
+    /// Tokenizer file not found.
+    /// Trigger: `tokenizer.json` missing from the model directory and no override path set.
+    TokenizerMissing,
+
+    /// An operation did not finish within its time limit.
+    /// Trigger: Model loading takes longer than `model_load_timeout_sec`.
+    TimedOut,
+
+    /// The track cache is read-only for this daemon instance.
+    /// Trigger: A cache write while another instance holds the cache lock (`--allow-multiple`).
+    CacheReadOnly,
+
+    /// A requested output path cannot be written.
+    /// Trigger: `generate` with an `output_path` whose directory is missing or
+    /// unwritable, or outside `restrict_output_root`.
+    OutputPathInvalid,
+
+    /// Reading or writing a daemon file failed.
+    /// Trigger: Disk full or a permission error on track metadata in the cache directory.
+    StorageFailed,
 }
 
 impl ErrorCode {
@@ -76,6 +102,12 @@ impl ErrorCode {
             ErrorCode::InvalidGuidanceScale => "INVALID_GUIDANCE_SCALE",
             ErrorCode::InvalidScheduler => "INVALID_SCHEDULER",
             ErrorCode::GenerationCancelled => "GENERATION_CANCELLED",
+            ErrorCode::InsufficientMemory => "INSUFFICIENT_MEMORY",
+            ErrorCode::TokenizerMissing => "TOKENIZER_MISSING",
+            ErrorCode::TimedOut => "TIMED_OUT",
+            ErrorCode::CacheReadOnly => "CACHE_READ_ONLY",
+            ErrorCode::OutputPathInvalid => "OUTPUT_PATH_INVALID",
+            ErrorCode::StorageFailed => "STORAGE_FAILED",
         }
     }
 
@@ -94,6 +126,12 @@ impl ErrorCode {
             ErrorCode::InvalidGuidanceScale => "Guidance scale must be between 1.0 and 20.0",
             ErrorCode::InvalidScheduler => "Unknown scheduler type specified",
             ErrorCode::GenerationCancelled => "Generation was cancelled by user request",
+            ErrorCode::InsufficientMemory => "Not enough free memory for the requested generation",
+            ErrorCode::TokenizerMissing => "Tokenizer file not found",
+            ErrorCode::TimedOut => "Operation did not finish within its time limit",
+            ErrorCode::CacheReadOnly => "Track cache is read-only for this daemon instance",
+            ErrorCode::OutputPathInvalid => "Requested output path cannot be written",
+            ErrorCode::StorageFailed => "Reading or writing a daemon file failed",
         }
     }
 
@@ -143,6 +181,62 @@ impl ErrorCode {
             ErrorCode::GenerationCancelled => {
                 "Generation was stopped as requested. Start a new generation to continue"
             }
+            ErrorCode::InsufficientMemory => {
+                "Request a shorter duration, close other applications to free memory, \
+                 or lower memory_safety_margin_bytes if the estimate is too conservative"
+            }
+            ErrorCode::TokenizerMissing => {
+                "Download tokenizer.json from the URL in the error message into the model directory, \
+                 or set LOFI_TOKENIZER_PATH (MusicGen) or LOFI_ACE_STEP_TOKENIZER_PATH (ACE-Step) \
+                 to a shared copy"
+            }
+            ErrorCode::TimedOut => {
+                "Model loading can take minutes on slow or network storage. Retry once the \
+                 background load finishes, raise model_load_timeout_sec, or move the models \
+                 to faster storage"
+            }
+            ErrorCode::CacheReadOnly => {
+                "Another lofi-daemon instance owns the cache. Send the request to that instance, \
+                 or stop it and restart this one without --allow-multiple"
+            }
+            ErrorCode::OutputPathInvalid => {
+                "Choose an output_path in an existing directory the daemon can write to. \
+                 If restrict_output_root is set, the path must be inside that directory"
+            }
+            ErrorCode::StorageFailed => {
+                "Check free disk space and the permissions of the cache directory \
+                 (LOFI_CACHE_PATH), then retry"
+            }
+        }
+    }
+}
+
+impl ErrorCode {
+    /// Returns true if the same request may succeed when retried unchanged.
+    ///
+    /// Inference, loading and download failures are often transient, and a full
+    /// queue drains over time. Validation errors and missing installs need the
+    /// request or the setup to change first.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            ErrorCode::ModelLoadFailed
+            | ErrorCode::ModelDownloadFailed
+            | ErrorCode::ModelInferenceFailed
+            | ErrorCode::QueueFull
+            | ErrorCode::TimedOut
+            | ErrorCode::StorageFailed => true,
+            ErrorCode::ModelNotFound
+            | ErrorCode::InvalidDuration
+            | ErrorCode::InvalidPrompt
+            | ErrorCode::BackendNotInstalled
+            | ErrorCode::InvalidInferenceSteps
+            | ErrorCode::InvalidGuidanceScale
+            | ErrorCode::InvalidScheduler
+            | ErrorCode::GenerationCancelled
+            | ErrorCode::InsufficientMemory
+            | ErrorCode::TokenizerMissing
+            | ErrorCode::CacheReadOnly
+            | ErrorCode::OutputPathInvalid => false,
         }
     }
 }
@@ -153,6 +247,43 @@ impl fmt::Display for ErrorCode {
     }
 }
 
+/// Generation pipeline phase in which an error occurred.
+///
+/// MusicGen has no diffusion phase; its autoregressive token decoder reports
+/// `Decode` and its EnCodec audio codec reports `Vocode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerationPhase {
+    /// Prompt encoding (including ACE-Step transformer context).
+    TextEncode,
+    /// ACE-Step diffusion loop.
+    Diffusion,
+    /// Latent-to-mel decoding (ACE-Step) or token generation (MusicGen).
+    Decode,
+    /// Waveform synthesis from mel-spectrogram or audio tokens.
+    Vocode,
+    /// Writing the generated audio to disk.
+    Write,
+}
+
+impl GenerationPhase {
+    /// Returns the string representation of the phase.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GenerationPhase::TextEncode => "text_encode",
+            GenerationPhase::Diffusion => "diffusion",
+            GenerationPhase::Decode => "decode",
+            GenerationPhase::Vocode => "vocode",
+            GenerationPhase::Write => "write",
+        }
+    }
+}
+
+impl fmt::Display for GenerationPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 /// Main error type for daemon operations.
 #[derive(Debug)]
 pub struct DaemonError {
@@ -162,6 +293,10 @@ pub struct DaemonError {
     pub message: String,
     /// Optional underlying cause of the error.
     pub source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    /// Folder containing failure diagnostics, if they were saved.
+    pub dump_path: Option<PathBuf>,
+    /// Generation phase that failed, if the error came from the pipeline.
+    pub phase: Option<GenerationPhase>,
 }
 
 impl DaemonError {
@@ -171,6 +306,8 @@ impl DaemonError {
             code,
             message: message.into(),
             source: None,
+            dump_path: None,
+            phase: None,
         }
     }
 
@@ -184,9 +321,50 @@ impl DaemonError {
             code,
             message: message.into(),
             source: Some(Box::new(source)),
+            dump_path: None,
+            phase: None,
         }
     }
 
+    /// Records where failure diagnostics were saved and mentions it in the message.
+    pub fn with_dump_path(mut self, path: PathBuf) -> Self {
+        self.message = format!("{} (diagnostics saved to {})", self.message, path.display());
+        self.dump_path = Some(path);
+        self
+    }
+
+    /// Records the generation phase that failed.
+    ///
+    /// An already recorded phase is kept, so the innermost phase wins.
+    pub fn with_phase(mut self, phase: GenerationPhase) -> Self {
+        self.phase.get_or_insert(phase);
+        self
+    }
+
+    /// Returns the chain of underlying causes joined with `: `, if any.
+    pub fn details(&self) -> Option<String> {
+        let mut causes = Vec::new();
+        let mut current = std::error::Error::source(self);
+        while let Some(cause) = current {
+            causes.push(cause.to_string());
+            current = cause.source();
+        }
+        if causes.is_empty() {
+            None
+        } else {
+            Some(causes.join(": "))
+        }
+    }
+
+    /// Returns the deepest error in the source chain, or `self` if there is none.
+    pub fn root_cause(&self) -> &(dyn std::error::Error + 'static) {
+        let mut root: &(dyn std::error::Error + 'static) = self;
+        while let Some(cause) = root.source() {
+            root = cause;
+        }
+        root
+    }
+
     /// Creates a MODEL_NOT_FOUND error.
     pub fn model_not_found(path: impl Into<String>) -> Self {
         Self::new(
@@ -195,6 +373,18 @@ impl DaemonError {
         )
     }
 
+    /// Creates a TOKENIZER_MISSING error naming the expected file and where to get it.
+    pub fn tokenizer_missing(path: &Path, download_url: &str) -> Self {
+        Self::new(
+            ErrorCode::TokenizerMissing,
+            format!(
+                "Tokenizer not found at {} (download it from {})",
+                path.display(),
+                download_url
+            ),
+        )
+    }
+
     /// Creates a MODEL_LOAD_FAILED error.
     pub fn model_load_failed(reason: impl Into<String>) -> Self {
         Self::new(
@@ -219,6 +409,21 @@ impl DaemonError {
         )
     }
 
+    /// Creates a MODEL_INFERENCE_FAILED error caused by `source`.
+    ///
+    /// The runtime error stays available through [`DaemonError::details`]
+    /// instead of being folded into the message.
+    pub fn model_inference_failed_with_source(
+        reason: impl Into<String>,
+        source: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        Self::with_source(
+            ErrorCode::ModelInferenceFailed,
+            format!("Inference failed: {}", reason.into()),
+            source,
+        )
+    }
+
     /// Creates a QUEUE_FULL error.
     pub fn queue_full() -> Self {
         Self::new(
@@ -292,6 +497,46 @@ impl DaemonError {
         )
     }
 
+    /// Creates a TIMED_OUT error for an operation stopped after `elapsed_sec`
+    /// against a limit of `timeout_sec`.
+    pub fn timed_out(elapsed_sec: f32, timeout_sec: f32) -> Self {
+        Self::new(
+            ErrorCode::TimedOut,
+            format!(
+                "Timed out after {:.1}s (limit {:.1}s)",
+                elapsed_sec, timeout_sec
+            ),
+        )
+    }
+
+    /// Creates a CACHE_READ_ONLY error for a refused cache write.
+    ///
+    /// `action` describes what was refused, e.g. "generate new tracks".
+    pub fn cache_read_only(action: &str) -> Self {
+        Self::new(
+            ErrorCode::CacheReadOnly,
+            format!(
+                "Cannot {}: the track cache is read-only because another daemon instance holds its lock",
+                action
+            ),
+        )
+    }
+
+    /// Creates an OUTPUT_PATH_INVALID error for `path`, explaining why it
+    /// cannot be written.
+    pub fn output_path_invalid(path: &Path, reason: impl fmt::Display) -> Self {
+        Self::new(
+            ErrorCode::OutputPathInvalid,
+            format!("Cannot write to {}: {}", path.display(), reason),
+        )
+    }
+
+    /// Creates a STORAGE_FAILED error for a daemon file that could not be
+    /// read or written.
+    pub fn storage_failed(reason: impl Into<String>) -> Self {
+        Self::new(ErrorCode::StorageFailed, reason)
+    }
+
     /// Creates a GENERATION_CANCELLED error.
     pub fn generation_cancelled() -> Self {
         Self::new(
@@ -299,17 +544,41 @@ impl DaemonError {
             "Generation was cancelled by user request",
         )
     }
+
+    /// Creates an INSUFFICIENT_MEMORY error.
+    pub fn insufficient_memory(
+        required_bytes: u64,
+        available_bytes: u64,
+        max_feasible_sec: f32,
+    ) -> Self {
+        Self::new(
+            ErrorCode::InsufficientMemory,
+            format!(
+                "Insufficient memory: generation needs about {} MB but only {} MB is available \
+                 (about {:.0}s would fit)",
+                required_bytes / (1024 * 1024),
+                available_bytes / (1024 * 1024),
+                max_feasible_sec
+            ),
+        )
+    }
 }
 
 impl fmt::Display for DaemonError {
+    /// Formats as `[CODE] message (caused by: cause)... Recovery: hint`, with
+    /// one `caused by` per error in the source chain.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "[{}] {}. Recovery: {}",
-            self.code,
-            self.message,
-            self.code.recovery_hint()
-        )
+        write!(f, "[{}] {}", self.code, self.message)?;
+        let mut current = std::error::Error::source(self);
+        while let Some(cause) = current {
+            // A nested DaemonError would repeat its own chain and recovery hint
+            match cause.downcast_ref::<DaemonError>() {
+                Some(inner) => write!(f, " (caused by: [{}] {})", inner.code, inner.message)?,
+                None => write!(f, " (caused by: {})", cause)?,
+            }
+            current = cause.source();
+        }
+        write!(f, ". Recovery: {}", self.code.recovery_hint())
     }
 }
 
@@ -360,6 +629,15 @@ mod tests {
             ErrorCode::GenerationCancelled.as_str(),
             "GENERATION_CANCELLED"
         );
+        assert_eq!(
+            ErrorCode::InsufficientMemory.as_str(),
+            "INSUFFICIENT_MEMORY"
+        );
+        assert_eq!(ErrorCode::TokenizerMissing.as_str(), "TOKENIZER_MISSING");
+        assert_eq!(ErrorCode::TimedOut.as_str(), "TIMED_OUT");
+        assert_eq!(ErrorCode::CacheReadOnly.as_str(), "CACHE_READ_ONLY");
+        assert_eq!(ErrorCode::OutputPathInvalid.as_str(), "OUTPUT_PATH_INVALID");
+        assert_eq!(ErrorCode::StorageFailed.as_str(), "STORAGE_FAILED");
     }
 
     #[test]
@@ -377,6 +655,52 @@ mod tests {
         assert!(!ErrorCode::InvalidGuidanceScale.recovery_hint().is_empty());
         assert!(!ErrorCode::InvalidScheduler.recovery_hint().is_empty());
         assert!(!ErrorCode::GenerationCancelled.recovery_hint().is_empty());
+        assert!(!ErrorCode::InsufficientMemory.recovery_hint().is_empty());
+        assert!(!ErrorCode::TokenizerMissing.recovery_hint().is_empty());
+        assert!(!ErrorCode::TimedOut.recovery_hint().is_empty());
+        assert!(!ErrorCode::CacheReadOnly.recovery_hint().is_empty());
+        assert!(!ErrorCode::OutputPathInvalid.recovery_hint().is_empty());
+        assert!(!ErrorCode::StorageFailed.recovery_hint().is_empty());
+    }
+
+    #[test]
+    fn error_code_retriability() {
+        let cases = [
+            (ErrorCode::ModelNotFound, false),
+            (ErrorCode::ModelLoadFailed, true),
+            (ErrorCode::ModelDownloadFailed, true),
+            (ErrorCode::ModelInferenceFailed, true),
+            (ErrorCode::QueueFull, true),
+            (ErrorCode::InvalidDuration, false),
+            (ErrorCode::InvalidPrompt, false),
+            (ErrorCode::BackendNotInstalled, false),
+            (ErrorCode::InvalidInferenceSteps, false),
+            (ErrorCode::InvalidGuidanceScale, false),
+            (ErrorCode::InvalidScheduler, false),
+            (ErrorCode::GenerationCancelled, false),
+            (ErrorCode::InsufficientMemory, false),
+            (ErrorCode::TokenizerMissing, false),
+            (ErrorCode::TimedOut, true),
+            (ErrorCode::CacheReadOnly, false),
+            (ErrorCode::OutputPathInvalid, false),
+            (ErrorCode::StorageFailed, true),
+        ];
+        for (code, retriable) in cases {
+            assert_eq!(code.is_retriable(), retriable, "{}", code);
+        }
+    }
+
+    #[test]
+    fn phase_and_details() {
+        let io = std::io::Error::other("disk full");
+        let err = DaemonError::with_source(ErrorCode::ModelInferenceFailed, "Failed to write", io)
+            .with_phase(GenerationPhase::Write)
+            .with_phase(GenerationPhase::Vocode);
+        assert_eq!(err.phase, Some(GenerationPhase::Write));
+        assert_eq!(err.details().as_deref(), Some("disk full"));
+
+        assert!(DaemonError::empty_prompt().details().is_none());
+        assert!(DaemonError::empty_prompt().phase.is_none());
     }
 
     #[test]
@@ -385,6 +709,32 @@ mod tests {
         assert!(err.to_string().contains("INVALID_DURATION"));
         assert!(err.to_string().contains("200"));
         assert!(err.to_string().contains("Recovery:"));
+        assert!(!err.to_string().contains("caused by"));
+
+        let root = std::io::Error::other("ort: invalid protobuf");
+        let inner = DaemonError::with_source(ErrorCode::ModelLoadFailed, "Failed to read decoder", root);
+        let err = DaemonError::with_source(ErrorCode::ModelLoadFailed, "Failed to load MusicGen", inner);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "[MODEL_LOAD_FAILED] Failed to load MusicGen \
+                 (caused by: [MODEL_LOAD_FAILED] Failed to read decoder) \
+                 (caused by: ort: invalid protobuf). Recovery: {}",
+                ErrorCode::ModelLoadFailed.recovery_hint()
+            )
+        );
+    }
+
+    #[test]
+    fn root_cause_is_deepest_error() {
+        let err = DaemonError::empty_prompt();
+        assert_eq!(err.root_cause().to_string(), err.to_string());
+
+        let root = std::io::Error::other("disk full");
+        let inner = DaemonError::with_source(ErrorCode::ModelDownloadFailed, "write failed", root);
+        let err = DaemonError::with_source(ErrorCode::ModelDownloadFailed, "download failed", inner);
+        assert_eq!(err.root_cause().to_string(), "disk full");
+        assert!(err.root_cause().downcast_ref::<std::io::Error>().is_some());
     }
 
     #[test]
@@ -407,5 +757,10 @@ mod tests {
 
         let err = DaemonError::generation_cancelled();
         assert_eq!(err.code, ErrorCode::GenerationCancelled);
+
+        let err = DaemonError::tokenizer_missing(Path::new("/models/tokenizer.json"), "https://example.com/tokenizer.json");
+        assert_eq!(err.code, ErrorCode::TokenizerMissing);
+        assert!(err.message.contains("/models/tokenizer.json"));
+        assert!(err.message.contains("https://example.com/tokenizer.json"));
     }
 }
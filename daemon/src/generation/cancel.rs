@@ -0,0 +1,62 @@
+//! Cooperative cancellation for running generations.
+//!
+//! A [`CancelToken`] is shared between the code driving a generation and
+//! whatever requests the stop (e.g. a Ctrl+C handler). Backends check it
+//! between decoder or diffusion steps. By default a cancelled generation
+//! fails with `GENERATION_CANCELLED`; with `keep_partial` set, the work done
+//! so far is decoded and returned instead.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared flag for stopping a generation early.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    keep_partial: bool,
+}
+
+impl CancelToken {
+    /// Creates a token that has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether partial output is kept when the generation is cancelled.
+    pub fn with_keep_partial(mut self, keep_partial: bool) -> Self {
+        self.keep_partial = keep_partial;
+        self
+    }
+
+    /// Requests cancellation. Every clone of the token observes it.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if cancellation has been requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if partial output should be returned on cancellation.
+    pub fn keep_partial(&self) -> bool {
+        self.keep_partial
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_cancellation() {
+        let token = CancelToken::new().with_keep_partial(true);
+        let handle = token.clone();
+        assert!(!token.is_cancelled());
+
+        handle.cancel();
+        assert!(token.is_cancelled());
+        assert!(token.keep_partial());
+        assert!(!CancelToken::new().keep_partial());
+    }
+}
@@ -0,0 +1,333 @@
+//! Pre-flight memory checks for generation requests.
+//!
+//! Long ACE-Step renders allocate a large latent and sample buffer. On machines
+//! with little free RAM this can get the daemon killed by the OS, so requests
+//! are checked against available memory before anything is allocated.
+
+use sysinfo::{MemoryRefreshKind, RefreshKind, System};
+
+use crate::error::{DaemonError, Result};
+use crate::models::ace_step::decoder::{HOP_LENGTH as MEL_HOP_LENGTH, MEL_BINS};
+use crate::models::ace_step::transformer::{LATENT_CHANNELS, LATENT_HEIGHT};
+use crate::models::ace_step::vocoder::VOCODER_SAMPLE_RATE;
+use crate::models::Backend;
+
+/// Approximate resident size of the loaded MusicGen models (1.5 GB).
+const MUSICGEN_MODEL_BYTES: u64 = 1536 * 1024 * 1024;
+
+/// Approximate resident size of the loaded ACE-Step models with a single
+/// DCAE session (8 GB).
+const ACE_STEP_MODEL_BYTES: u64 = 8 * 1024 * 1024 * 1024;
+
+// Per-second buffer costs are derived from tensor shapes, multiplied by the
+// number of copies alive at peak. Calibration: run 30s and 240s generations
+// under `/usr/bin/time -v`, take the slope of peak RSS between them, and adjust
+// the copy counts below until the per-second total matches the slope (the
+// intercept is the model footprint above).
+
+/// Approximate resident size of one extra DCAE decoder session (600 MB),
+/// counted for each session beyond the first. The ignored
+/// `dcae_parallel_benchmark` test in `models::ace_step::decoder` prints the
+/// RSS growth between one and two sessions to check it against.
+pub const DCAE_SESSION_BYTES: u64 = 600 * 1024 * 1024;
+
+/// Total memory below which DCAE chunks are decoded on a single session (16 GB).
+const PARALLEL_DCAE_MIN_MEMORY: u64 = 16 * 1024 * 1024 * 1024;
+
+/// Upper bound for the default DCAE parallelism.
+const DEFAULT_DCAE_PARALLELISM_MAX: usize = 2;
+
+/// Latent-sized tensors alive at once during a diffusion step
+/// (latent, conditional/unconditional noise, sanitized copies, guided noise, scheduler state).
+const LATENT_WORKING_COPIES: f64 = 8.0;
+
+/// Mel-spectrogram buffers alive at once (decoded chunks plus the concatenated result).
+const MEL_BUFFER_COPIES: f64 = 2.0;
+
+/// Sample buffers alive at once (decoded audio plus its resampled or normalized copy).
+const SAMPLE_BUFFER_COPIES: f64 = 2.0;
+
+/// Latent frames per second of ACE-Step audio (one frame per 8 mel frames).
+const LATENT_FRAMES_PER_SEC: f64 = VOCODER_SAMPLE_RATE as f64 / (MEL_HOP_LENGTH * 8) as f64;
+
+/// Share of available memory a generation should use to be recommended.
+/// The hard check allows everything above the safety margin; recommendations
+/// leave room for other applications.
+const RECOMMENDED_MEMORY_FRACTION: f64 = 0.75;
+
+/// Estimated peak memory needed by a generation, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    /// Latent tensors used during diffusion (zero for MusicGen).
+    pub latent_bytes: u64,
+    /// Mel-spectrogram buffers produced by the decoder (zero for MusicGen).
+    pub mel_bytes: u64,
+    /// Output sample buffers (`f32` samples).
+    pub sample_bytes: u64,
+    /// Model weights that still have to be loaded (zero if already resident).
+    pub model_bytes: u64,
+}
+
+impl MemoryEstimate {
+    /// Returns the total estimated memory in bytes.
+    pub fn total(&self) -> u64 {
+        self.latent_bytes + self.mel_bytes + self.sample_bytes + self.model_bytes
+    }
+}
+
+/// Bytes of latent tensors per second of audio.
+fn latent_bytes_per_sec(backend: Backend) -> f64 {
+    match backend {
+        Backend::MusicGen => 0.0,
+        Backend::AceStep => {
+            let bytes_per_frame = (LATENT_CHANNELS * LATENT_HEIGHT * 4) as f64;
+            LATENT_FRAMES_PER_SEC * bytes_per_frame * LATENT_WORKING_COPIES
+        }
+    }
+}
+
+/// Bytes of mel-spectrogram buffers per second of audio.
+fn mel_bytes_per_sec(backend: Backend) -> f64 {
+    match backend {
+        Backend::MusicGen => 0.0,
+        Backend::AceStep => {
+            let mel_frames_per_sec = VOCODER_SAMPLE_RATE as f64 / MEL_HOP_LENGTH as f64;
+            mel_frames_per_sec * (MEL_BINS * 4) as f64 * MEL_BUFFER_COPIES
+        }
+    }
+}
+
+/// Bytes of `f32` sample buffers per second of audio.
+fn sample_bytes_per_sec(backend: Backend) -> f64 {
+    backend.sample_rate() as f64 * 4.0 * SAMPLE_BUFFER_COPIES
+}
+
+/// Returns the memory that still has to be allocated for the backend's models,
+/// loading ACE-Step with `dcae_sessions` DCAE sessions.
+fn model_bytes(backend: Backend, model_loaded: bool, dcae_sessions: usize) -> u64 {
+    if model_loaded {
+        return 0;
+    }
+    match backend {
+        Backend::MusicGen => MUSICGEN_MODEL_BYTES,
+        Backend::AceStep => {
+            let extra_sessions = dcae_sessions.saturating_sub(1) as u64;
+            ACE_STEP_MODEL_BYTES + extra_sessions * DCAE_SESSION_BYTES
+        }
+    }
+}
+
+/// Estimates the peak memory needed to generate `duration_sec` of audio.
+///
+/// If `model_loaded` is true, the backend's models are already resident and
+/// are not counted again. Otherwise ACE-Step is counted with `dcae_sessions`
+/// DCAE sessions (see `DaemonConfig::effective_dcae_parallelism`).
+pub fn estimate_generation_memory(
+    backend: Backend,
+    duration_sec: u32,
+    model_loaded: bool,
+    dcae_sessions: usize,
+) -> MemoryEstimate {
+    let duration = duration_sec as f64;
+    MemoryEstimate {
+        latent_bytes: (latent_bytes_per_sec(backend) * duration).ceil() as u64,
+        mel_bytes: (mel_bytes_per_sec(backend) * duration).ceil() as u64,
+        sample_bytes: (sample_bytes_per_sec(backend) * duration).ceil() as u64,
+        model_bytes: model_bytes(backend, model_loaded, dcae_sessions),
+    }
+}
+
+/// Returns the longest duration whose buffers fit into `available_bytes`.
+///
+/// `available_bytes` is memory left for per-second buffers, i.e. after the
+/// model footprint and safety margin. The result is capped at the backend's
+/// maximum duration.
+pub fn max_feasible_duration(backend: Backend, available_bytes: u64) -> f32 {
+    let per_sec =
+        latent_bytes_per_sec(backend) + mel_bytes_per_sec(backend) + sample_bytes_per_sec(backend);
+    let duration = available_bytes as f64 / per_sec;
+    duration.min(backend.max_duration_sec() as f64) as f32
+}
+
+/// Returns the recommended maximum duration for this machine, if memory
+/// information is available.
+///
+/// Only part of the available memory is budgeted so other applications keep
+/// some headroom. Unloaded models are counted as in
+/// [`estimate_generation_memory`].
+pub fn recommended_max_duration(
+    backend: Backend,
+    model_loaded: bool,
+    dcae_sessions: usize,
+    margin_bytes: u64,
+) -> Option<f32> {
+    let available = available_memory()?;
+    let budget = (available as f64 * RECOMMENDED_MEMORY_FRACTION) as u64;
+    let usable = budget
+        .saturating_sub(margin_bytes)
+        .saturating_sub(model_bytes(backend, model_loaded, dcae_sessions));
+    Some(max_feasible_duration(backend, usable))
+}
+
+/// Returns the memory currently available to the daemon in bytes.
+///
+/// Respects cgroup limits when running in a container. Returns None if the
+/// platform does not report memory information.
+pub fn available_memory() -> Option<u64> {
+    let system = System::new_with_specifics(
+        RefreshKind::nothing().with_memory(MemoryRefreshKind::nothing().with_ram()),
+    );
+
+    let mut available = system.available_memory();
+    if let Some(limits) = system.cgroup_limits() {
+        available = available.min(limits.free_memory);
+    }
+
+    if available == 0 {
+        None
+    } else {
+        Some(available)
+    }
+}
+
+/// Returns the total memory of the machine in bytes.
+///
+/// Respects cgroup limits when running in a container. Returns None if the
+/// platform does not report memory information.
+pub fn total_memory() -> Option<u64> {
+    let system = System::new_with_specifics(
+        RefreshKind::nothing().with_memory(MemoryRefreshKind::nothing().with_ram()),
+    );
+
+    let mut total = system.total_memory();
+    if let Some(limits) = system.cgroup_limits() {
+        total = total.min(limits.total_memory);
+    }
+
+    if total == 0 {
+        None
+    } else {
+        Some(total)
+    }
+}
+
+/// Returns how many DCAE sessions to load when `dcae_parallelism` is unset.
+///
+/// One session per four cores, at most two, and only on machines with at
+/// least 16 GB of `total_bytes`; each extra session costs about
+/// [`DCAE_SESSION_BYTES`].
+pub fn default_dcae_parallelism(total_bytes: Option<u64>, cores: usize) -> usize {
+    match total_bytes {
+        Some(total) if total >= PARALLEL_DCAE_MIN_MEMORY => {
+            (cores / 4).clamp(1, DEFAULT_DCAE_PARALLELISM_MAX)
+        }
+        _ => 1,
+    }
+}
+
+/// Checks that a generation fits into available memory with `margin_bytes` to spare.
+///
+/// Returns INSUFFICIENT_MEMORY if the estimate plus margin exceeds `available_bytes`.
+/// The error names the longest duration that would fit.
+pub fn check_memory(
+    backend: Backend,
+    estimate: &MemoryEstimate,
+    available_bytes: u64,
+    margin_bytes: u64,
+) -> Result<()> {
+    let required = estimate.total().saturating_add(margin_bytes);
+    if required > available_bytes {
+        let usable = available_bytes
+            .saturating_sub(margin_bytes)
+            .saturating_sub(estimate.model_bytes);
+        return Err(DaemonError::insufficient_memory(
+            required,
+            available_bytes,
+            max_feasible_duration(backend, usable),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn estimate_scales_with_duration() {
+        let short = estimate_generation_memory(Backend::AceStep, 30, true, 1);
+        let long = estimate_generation_memory(Backend::AceStep, 240, true, 1);
+        assert!(long.total() > short.total() * 7);
+        assert_eq!(long.model_bytes, 0);
+
+        // 240s at 48 kHz: two f32 buffers of 11.52M samples
+        assert_eq!(long.sample_bytes, 240 * 48000 * 4 * 2);
+    }
+
+    #[test]
+    fn estimate_counts_unloaded_models() {
+        let musicgen = estimate_generation_memory(Backend::MusicGen, 30, false, 1);
+        assert_eq!(musicgen.latent_bytes, 0);
+        assert_eq!(musicgen.model_bytes, MUSICGEN_MODEL_BYTES);
+
+        let ace_step = estimate_generation_memory(Backend::AceStep, 30, false, 1);
+        assert_eq!(ace_step.model_bytes, ACE_STEP_MODEL_BYTES);
+        assert!(ace_step.latent_bytes > 0);
+        assert!(ace_step.mel_bytes > 0);
+
+        // Each DCAE session beyond the first adds its footprint
+        let parallel = estimate_generation_memory(Backend::AceStep, 30, false, 2);
+        assert_eq!(parallel.model_bytes, ACE_STEP_MODEL_BYTES + DCAE_SESSION_BYTES);
+        assert_eq!(parallel.total() - ace_step.total(), DCAE_SESSION_BYTES);
+        assert_eq!(estimate_generation_memory(Backend::AceStep, 30, true, 2).model_bytes, 0);
+        assert_eq!(
+            estimate_generation_memory(Backend::MusicGen, 30, false, 2).model_bytes,
+            MUSICGEN_MODEL_BYTES
+        );
+    }
+
+    #[test]
+    fn max_feasible_duration_inverts_estimate() {
+        for duration in [10, 60, 120] {
+            let estimate = estimate_generation_memory(Backend::AceStep, duration, true, 1);
+            let feasible = max_feasible_duration(Backend::AceStep, estimate.total());
+            assert!((feasible - duration as f32).abs() < 0.01, "{} -> {}", duration, feasible);
+        }
+
+        // Plenty of memory is capped at the backend maximum
+        assert_eq!(max_feasible_duration(Backend::MusicGen, u64::MAX), 120.0);
+        assert_eq!(max_feasible_duration(Backend::AceStep, 0), 0.0);
+    }
+
+    #[test]
+    fn check_memory_applies_margin() {
+        let estimate = MemoryEstimate {
+            latent_bytes: 100 * MB,
+            mel_bytes: 0,
+            sample_bytes: 100 * MB,
+            model_bytes: 0,
+        };
+
+        assert!(check_memory(Backend::AceStep, &estimate, 1024 * MB, 512 * MB).is_ok());
+
+        let err = check_memory(Backend::AceStep, &estimate, 600 * MB, 512 * MB).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InsufficientMemory);
+        assert!(err.message.contains("712 MB"));
+        assert!(err.message.contains("600 MB"));
+    }
+
+    #[test]
+    fn dcae_parallelism_defaults_by_memory_and_cores() {
+        const GB: u64 = 1024 * 1024 * 1024;
+        assert_eq!(default_dcae_parallelism(Some(32 * GB), 16), 2);
+        assert_eq!(default_dcae_parallelism(Some(16 * GB), 8), 2);
+        assert_eq!(default_dcae_parallelism(Some(16 * GB), 4), 1);
+        assert_eq!(default_dcae_parallelism(Some(64 * GB), 2), 1);
+        // Off below 16 GB or when memory is unknown
+        assert_eq!(default_dcae_parallelism(Some(8 * GB), 16), 1);
+        assert_eq!(default_dcae_parallelism(None, 16), 1);
+    }
+}
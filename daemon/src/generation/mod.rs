@@ -2,14 +2,36 @@
 //!
 //! Provides the generation pipeline for MusicGen and ACE-Step backends.
 
+pub mod cancel;
+pub mod memory;
 pub mod pipeline;
 pub mod progress;
+pub mod prompt;
 pub mod queue;
+pub mod timings;
 
 // Re-export commonly used items
+pub use cancel::CancelToken;
+pub use memory::{
+    available_memory, check_memory, default_dcae_parallelism, estimate_generation_memory,
+    max_feasible_duration, recommended_max_duration, total_memory, MemoryEstimate,
+};
+#[allow(deprecated)]
+pub use pipeline::generate_ace_step;
 pub use pipeline::{
-    estimate_generation_time, estimate_samples, generate, generate_ace_step, generate_with_models,
-    generate_with_progress,
+    estimate_generation_time, estimate_samples, generate, generate_ace_step_params,
+    generate_ace_step_params_with_progress, generate_with_models, generate_with_models_diagnosed,
+    generate_with_progress, generate_with_seed_retry, generate_with_seed_retry_diagnosed,
+    DEFAULT_SEED_RETRIES, SAMPLES_PER_TOKEN,
+};
+pub use crate::models::ace_step::GenerationParams;
+pub use progress::{
+    AudioProgress, DownloadProgressThrottle, ProgressMode, ProgressReporter, ProgressTracker,
+    ACE_STEP_PHASES, DEFAULT_RATE_WINDOW,
+};
+pub use prompt::{PromptTags, GENRES, MAX_BPM, MIN_BPM, MOODS};
+pub use queue::{
+    GenerationQueue, JobResult, QueueFullError, QueuePolicy, QueueProcessor,
+    DEFAULT_MAX_STARVATION_SEC, MAX_QUEUE_SIZE,
 };
-pub use progress::{ProgressMode, ProgressTracker};
-pub use queue::{GenerationQueue, JobResult, QueueFullError, QueueProcessor, MAX_QUEUE_SIZE};
+pub use timings::{StageTiming, Timings};
@@ -3,12 +3,18 @@
 //! Orchestrates the generation process for both MusicGen and ACE-Step backends.
 
 use std::path::Path;
+use std::time::Instant;
 
-use crate::audio::resample_44100_to_48000;
+use crate::audio::{resample_44100_to_48000, SAMPLE_RATE_MUSICGEN};
 use crate::cli::TOKENS_PER_SECOND;
-use crate::error::Result;
+use crate::diagnostics::{
+    report_failure, write_token_dump, FailureContext, FailureDumper, FailureStage,
+};
+use crate::error::{DaemonError, ErrorCode, GenerationPhase, Result};
+use crate::generation::{CancelToken, Timings};
 use crate::models::ace_step::{self, GenerationParams as AceStepParams, SchedulerType};
 use crate::models::{load_sessions, AceStepModels, MusicGenModels};
+use crate::types::DisplayPrompt;
 
 /// Generates audio from a text prompt.
 ///
@@ -16,7 +22,7 @@ use crate::models::{load_sessions, AceStepModels, MusicGenModels};
 ///
 /// * `prompt` - Text description of the music to generate
 /// * `duration_sec` - Duration of audio to generate in seconds
-/// * `seed` - Random seed for reproducible generation (not yet implemented)
+/// * `seed` - Random seed for reproducible generation
 /// * `model_dir` - Path to directory containing ONNX model files
 ///
 /// # Returns
@@ -38,10 +44,10 @@ use crate::models::{load_sessions, AceStepModels, MusicGenModels};
 pub fn generate(
     prompt: &str,
     duration_sec: u32,
-    _seed: Option<u64>,
+    seed: Option<u64>,
     model_dir: &Path,
 ) -> Result<Vec<f32>> {
-    generate_with_progress(prompt, duration_sec, _seed, model_dir, |_, _| {})
+    generate_with_progress(prompt, duration_sec, seed, model_dir, |_, _| {})
 }
 
 /// Generates audio with progress callback.
@@ -60,7 +66,7 @@ pub fn generate(
 pub fn generate_with_progress<F>(
     prompt: &str,
     duration_sec: u32,
-    _seed: Option<u64>,
+    seed: Option<u64>,
     model_dir: &Path,
     on_progress: F,
 ) -> Result<Vec<f32>>
@@ -70,66 +76,312 @@ where
     // Load models
     let mut models = load_sessions(model_dir)?;
 
-    // Calculate target tokens
-    let max_tokens = duration_sec as usize * TOKENS_PER_SECOND;
+    // Calculate target frames
+    let target_frames = duration_sec as usize * TOKENS_PER_SECOND;
 
     // Generate audio using the models
-    generate_with_models(&mut models, prompt, max_tokens, on_progress)
+    let seed = seed.unwrap_or_else(rand::random);
+    generate_with_models_diagnosed(
+        &mut models,
+        prompt,
+        target_frames,
+        seed,
+        None,
+        None,
+        None,
+        None,
+        on_progress,
+    )
 }
 
 /// Generates audio using pre-loaded models.
 ///
 /// This is useful for batch generation where models should be loaded once.
-/// The callback receives (tokens_generated, tokens_total) on every token.
+/// Exactly `target_frames` token frames are generated, so the output length is
+/// `estimate_samples(target_frames)`. The callback receives
+/// (frames_generated, target_frames) on every decoder step.
 pub fn generate_with_models<F>(
     models: &mut MusicGenModels,
     prompt: &str,
-    max_tokens: usize,
+    target_frames: usize,
     on_progress: F,
 ) -> Result<Vec<f32>>
 where
     F: Fn(usize, usize),
 {
-    eprintln!("Encoding prompt: \"{}\"", prompt);
+    generate_with_models_diagnosed(
+        models,
+        prompt,
+        target_frames,
+        rand::random(),
+        None,
+        None,
+        None,
+        None,
+        on_progress,
+    )
+}
+
+/// Generates audio using pre-loaded models, saving failure diagnostics.
+///
+/// Behaves like [`generate_with_models`], but token sampling is seeded with
+/// `seed`, so the same prompt and seed reproduce the same audio. If the decoder
+/// or audio codec fails and `dumper` is set, the failing inputs are written to
+/// disk and the dump path is attached to the returned error.
+///
+/// If `cancel` is triggered during token generation the call fails with
+/// `GENERATION_CANCELLED`, or, when the token keeps partial output, the frames
+/// generated so far are decoded and the shorter audio is returned.
+///
+/// If `token_dump` is set, the generated token frames are written there as CSV
+/// before decoding. A failed write is logged and generation continues.
+///
+/// If `timings` is set, it receives the time spent encoding the prompt,
+/// generating tokens (`decode`, one iteration per token frame), and decoding
+/// them to audio (`vocode`).
+#[allow(clippy::too_many_arguments)]
+pub fn generate_with_models_diagnosed<F>(
+    models: &mut MusicGenModels,
+    prompt: &str,
+    target_frames: usize,
+    seed: u64,
+    dumper: Option<&FailureDumper>,
+    token_dump: Option<&Path>,
+    cancel: Option<&CancelToken>,
+    timings: Option<&Timings>,
+    on_progress: F,
+) -> Result<Vec<f32>>
+where
+    F: Fn(usize, usize),
+{
+    let record = |phase, start: Instant, count| {
+        if let Some(timings) = timings {
+            timings.record_many(phase, start.elapsed(), count);
+        }
+    };
+    let failure_context = |models: &MusicGenModels, stage, error: &DaemonError, tokens| {
+        FailureContext {
+            stage,
+            // Display includes the source chain
+            error: error.to_string(),
+            prompt: DisplayPrompt::new(prompt).to_string(),
+            seed,
+            target_frames,
+            model_version: models.version().to_string(),
+            provider: models.device_name().to_string(),
+            tokens,
+        }
+    };
+
+    eprintln!("Encoding prompt: \"{}\"", DisplayPrompt::new(prompt));
 
     // Step 1: Encode the text prompt
-    let (encoder_hidden_states, encoder_attention_mask) = models.text_encoder.encode(prompt)?;
+    let start = Instant::now();
+    let (encoder_hidden_states, encoder_attention_mask) = models
+        .text_encoder
+        .encode(prompt)
+        .map_err(|e| e.with_phase(GenerationPhase::TextEncode))?;
+    record(GenerationPhase::TextEncode, start, 1);
 
-    eprintln!("Generating {} tokens...", max_tokens);
+    eprintln!("Generating {} tokens...", target_frames);
 
     // Step 2: Generate tokens autoregressively with progress
     // The on_progress callback is called for every token, allowing the caller
-    // to filter by 5% increments using ProgressTracker
-    let tokens = models.decoder.generate_tokens_with_progress(
+    // to filter by 5% increments using ProgressReporter
+    let start = Instant::now();
+    let tokens = match models.decoder.generate_tokens_seeded(
         encoder_hidden_states,
         encoder_attention_mask,
-        max_tokens,
+        target_frames,
+        seed,
+        cancel,
         &on_progress,
-    )?;
+    ) {
+        Ok(tokens) => tokens,
+        Err(e) if e.code == ErrorCode::GenerationCancelled => {
+            return Err(e.with_phase(GenerationPhase::Decode));
+        }
+        Err(e) => {
+            let context = failure_context(models, FailureStage::Decoder, &e, Vec::new());
+            return Err(report_failure(dumper, e, context).with_phase(GenerationPhase::Decode));
+        }
+    };
 
     let token_count = tokens.len();
+    record(GenerationPhase::Decode, start, token_count);
+
+    if let Some(path) = token_dump {
+        let frames: Vec<[i64; 4]> = tokens.iter().copied().collect();
+        match write_token_dump(path, &frames) {
+            Ok(()) => eprintln!("Saved {} token frames to {}", token_count, path.display()),
+            Err(e) => eprintln!("Warning: {}", e),
+        }
+    }
 
     eprintln!("Generated {} tokens, decoding audio...", token_count);
 
     // Step 3: Decode tokens to audio
-    let audio_samples = models.audio_codec.decode(tokens)?;
+    let start = Instant::now();
+    let audio_samples = match models.audio_codec.decode(tokens.iter().copied()) {
+        Ok((samples, stats)) => {
+            if stats.recovered_errors > 0 {
+                eprintln!(
+                    "Warning: replaced {} undecodable frames with silence",
+                    stats.recovered_errors
+                );
+            }
+            samples
+        }
+        Err(e) => {
+            let tokens = tokens.into_iter().collect();
+            let context = failure_context(models, FailureStage::AudioCodec, &e, tokens);
+            return Err(report_failure(dumper, e, context).with_phase(GenerationPhase::Vocode));
+        }
+    };
+    record(GenerationPhase::Vocode, start, 1);
 
     eprintln!(
         "Generated {} audio samples ({:.2}s at 32kHz)",
         audio_samples.len(),
-        audio_samples.len() as f32 / 32000.0
+        audio_samples.len() as f32 / SAMPLE_RATE_MUSICGEN as f32
     );
 
-    Ok(audio_samples.into())
+    let expected_samples = estimate_samples(token_count);
+    if audio_samples.len() != expected_samples {
+        eprintln!(
+            "Warning: audio codec returned {} samples, expected {} for {} tokens",
+            audio_samples.len(),
+            expected_samples,
+            token_count
+        );
+    }
+
+    Ok(audio_samples)
+}
+
+/// Number of times [`generate_with_seed_retry`] retries by default.
+pub const DEFAULT_SEED_RETRIES: u32 = 3;
+
+/// Generates audio using pre-loaded models, retrying with the next seed if the
+/// output is invalid.
+///
+/// Some seeds make MusicGen produce NaN audio. When the output contains
+/// non-finite samples, or is entirely silent because the audio codec replaced
+/// every non-finite frame, generation is retried with `seed + 1`, up to
+/// `max_retries` times. Returns the audio and the seed that produced it.
+pub fn generate_with_seed_retry<F>(
+    models: &mut MusicGenModels,
+    prompt: &str,
+    target_frames: usize,
+    seed: u64,
+    max_retries: u32,
+    on_progress: F,
+) -> Result<(Vec<f32>, u64)>
+where
+    F: Fn(usize, usize),
+{
+    generate_with_seed_retry_diagnosed(
+        models,
+        prompt,
+        target_frames,
+        seed,
+        max_retries,
+        None,
+        None,
+        None,
+        None,
+        on_progress,
+    )
+}
+
+/// Like [`generate_with_seed_retry`], with the diagnostics, cancellation and
+/// timing options of [`generate_with_models_diagnosed`].
+///
+/// A cancelled generation is never retried.
+#[allow(clippy::too_many_arguments)]
+pub fn generate_with_seed_retry_diagnosed<F>(
+    models: &mut MusicGenModels,
+    prompt: &str,
+    target_frames: usize,
+    seed: u64,
+    max_retries: u32,
+    dumper: Option<&FailureDumper>,
+    token_dump: Option<&Path>,
+    cancel: Option<&CancelToken>,
+    timings: Option<&Timings>,
+    on_progress: F,
+) -> Result<(Vec<f32>, u64)>
+where
+    F: Fn(usize, usize),
+{
+    retry_invalid_output(seed, max_retries, cancel, |seed| {
+        generate_with_models_diagnosed(
+            models,
+            prompt,
+            target_frames,
+            seed,
+            dumper,
+            token_dump,
+            cancel,
+            timings,
+            &on_progress,
+        )
+    })
+}
+
+/// Calls `generate` with `seed`, then with each following seed while its
+/// output is invalid, at most `max_retries` more times.
+fn retry_invalid_output<G>(
+    seed: u64,
+    max_retries: u32,
+    cancel: Option<&CancelToken>,
+    mut generate: G,
+) -> Result<(Vec<f32>, u64)>
+where
+    G: FnMut(u64) -> Result<Vec<f32>>,
+{
+    let mut current = seed;
+    for attempt in 0..=max_retries {
+        let samples = generate(current)?;
+        if !is_invalid_output(&samples) || cancel.is_some_and(CancelToken::is_cancelled) {
+            return Ok((samples, current));
+        }
+        if attempt == max_retries {
+            break;
+        }
+        let next = current.wrapping_add(1);
+        eprintln!(
+            "Warning: seed {} produced invalid audio, retrying with seed {} (original seed {})",
+            current, next, seed
+        );
+        current = next;
+    }
+
+    Err(DaemonError::model_inference_failed(format!(
+        "seeds {} to {} all produced invalid audio",
+        seed, current
+    ))
+    .with_phase(GenerationPhase::Vocode))
 }
 
-/// Estimates the number of audio samples for a given token count.
+/// Returns true if `samples` contain NaN or infinite values, or are entirely
+/// silent.
+fn is_invalid_output(samples: &[f32]) -> bool {
+    samples.iter().any(|s| s.is_nan() || s.is_infinite())
+        || (!samples.is_empty() && samples.iter().all(|&s| s == 0.0))
+}
+
+/// Number of audio samples the EnCodec decoder produces per token frame.
 ///
-/// MusicGen generates approximately 640 samples per token at 32kHz.
+/// (32000 samples/sec) / (50 tokens/sec) = 640 samples/token
+pub const SAMPLES_PER_TOKEN: usize = SAMPLE_RATE_MUSICGEN as usize / TOKENS_PER_SECOND;
+
+/// Returns the number of audio samples for a given token count.
+///
+/// The decoder emits exactly the requested number of frames, so this matches
+/// the audio codec's output length.
 pub fn estimate_samples(token_count: usize) -> usize {
-    // Each token represents approximately 640 samples at 32kHz
-    // (32000 samples/sec) / (50 tokens/sec) = 640 samples/token
-    token_count * 640
+    token_count * SAMPLES_PER_TOKEN
 }
 
 /// Estimates generation time based on token count.
@@ -157,6 +409,7 @@ pub fn estimate_generation_time(token_count: usize) -> f32 {
 /// # Returns
 ///
 /// Audio samples at 48kHz sample rate (resampled from 44.1kHz vocoder output).
+#[deprecated(note = "use generate_ace_step_params or generate_ace_step_params_with_progress")]
 pub fn generate_ace_step<F>(
     models: &mut AceStepModels,
     prompt: &str,
@@ -170,24 +423,56 @@ pub fn generate_ace_step<F>(
 where
     F: Fn(usize, usize),
 {
-    // Parse scheduler type
-    let scheduler_type = SchedulerType::parse(scheduler).unwrap_or(SchedulerType::Euler);
-
-    // Create generation parameters
     let params = AceStepParams {
         prompt: prompt.to_string(),
+        prompt_blend: None,
         duration_sec,
         seed,
         inference_steps,
-        scheduler: scheduler_type,
+        scheduler: SchedulerType::parse(scheduler).unwrap_or(SchedulerType::Euler),
         guidance_scale,
+        cancel: None,
+        timings: None,
+        on_audio_progress: None,
     };
+    generate_ace_step_params_with_progress(models, params, on_progress)
+}
 
+/// Generates audio using pre-loaded ACE-Step models.
+///
+/// Returns audio samples at 48kHz sample rate.
+pub fn generate_ace_step_params(
+    models: &mut AceStepModels,
+    params: AceStepParams,
+) -> Result<Vec<f32>> {
+    generate_ace_step_params_with_progress(models, params, |_, _| {})
+}
+
+/// Generates audio using pre-loaded ACE-Step models with a progress callback.
+///
+/// # Arguments
+///
+/// * `models` - Loaded ACE-Step models
+/// * `params` - Generation parameters
+/// * `on_progress` - Callback receiving (current_step, total_steps)
+///
+/// # Returns
+///
+/// Audio samples at 48kHz sample rate (resampled from 44.1kHz vocoder output).
+pub fn generate_ace_step_params_with_progress<F>(
+    models: &mut AceStepModels,
+    params: AceStepParams,
+    on_progress: F,
+) -> Result<Vec<f32>>
+where
+    F: Fn(usize, usize),
+{
     // Generate audio at 44.1kHz
     let samples_44100 = ace_step::generate_with_progress(models, params, on_progress)?;
 
     // Resample to 48kHz for consistency with lofi.nvim output format
-    let samples_48000 = resample_44100_to_48000(&samples_44100)?;
+    let samples_48000 = resample_44100_to_48000(&samples_44100)
+        .map_err(|e| e.with_phase(GenerationPhase::Vocode))?;
 
     Ok(samples_48000)
 }
@@ -208,8 +493,54 @@ mod tests {
         assert_eq!(estimate_generation_time(500), 50.0);
     }
 
+    #[test]
+    fn invalid_output_is_retried_with_the_next_seed() {
+        let mut seeds = Vec::new();
+        let (samples, seed) = retry_invalid_output(41, 3, None, |seed| {
+            seeds.push(seed);
+            Ok(match seed {
+                41 => vec![f32::NAN; 4],
+                42 => vec![0.0; 4],
+                _ => vec![0.1; 4],
+            })
+        })
+        .unwrap();
+        assert_eq!(seed, 43);
+        assert_eq!(samples, vec![0.1; 4]);
+        assert_eq!(seeds, vec![41, 42, 43]);
+    }
+
+    #[test]
+    fn retries_are_limited() {
+        let mut attempts = 0;
+        let error = retry_invalid_output(u64::MAX, 2, None, |_| {
+            attempts += 1;
+            Ok(vec![0.1, f32::INFINITY])
+        })
+        .unwrap_err();
+        assert_eq!(error.code, ErrorCode::ModelInferenceFailed);
+        assert_eq!(attempts, 3);
+
+        // Cancelled generations keep their partial output
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let (samples, seed) =
+            retry_invalid_output(5, 2, Some(&cancel), |_| Ok(vec![0.0; 4])).unwrap();
+        assert_eq!((samples.len(), seed), (4, 5));
+    }
+
     #[test]
     fn tokens_per_second_matches_cli() {
         assert_eq!(TOKENS_PER_SECOND, 50);
     }
+
+    #[test]
+    fn samples_per_token_matches_sample_rate() {
+        assert_eq!(SAMPLES_PER_TOKEN, 640);
+        // 10 seconds of tokens decode to exactly 10 seconds of audio
+        assert_eq!(
+            estimate_samples(10 * TOKENS_PER_SECOND),
+            10 * SAMPLE_RATE_MUSICGEN as usize
+        );
+    }
 }
@@ -2,9 +2,18 @@
 //!
 //! Provides utilities for calculating generation progress, percentages,
 //! and estimated time remaining. Supports both token-based progress
-//! (MusicGen) and step-based progress (ACE-Step diffusion).
+//! (MusicGen) and step-based progress (ACE-Step diffusion), with the rate
+//! measured over a sliding window so estimates follow changes of pace.
+//!
+//! [`ProgressReporter`] and [`DownloadProgressThrottle`] decide which progress
+//! callbacks become notifications, bounding how often clients are woken up.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use std::time::Instant;
+use crate::error::GenerationPhase;
 
 /// Token generation rate (tokens per second of audio).
 const TOKENS_PER_SECOND: usize = 50;
@@ -20,24 +29,58 @@ pub enum ProgressMode {
     Steps,
 }
 
+/// Default length of the window over which [`ProgressTracker`] measures the
+/// generation rate.
+pub const DEFAULT_RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Fewest updates the rate is measured over, however long they took, so
+/// that one slow update cannot dominate the rate.
+const MIN_RATE_SAMPLES: usize = 8;
+
+/// Phases of an ACE-Step generation after the prompt is encoded, with their
+/// rough share of the time on CPU: the diffusion steps, then decoding the
+/// latent to audio.
+pub const ACE_STEP_PHASES: &[(GenerationPhase, f32)] =
+    &[(GenerationPhase::Diffusion, 0.85), (GenerationPhase::Decode, 0.15)];
+
 /// Tracks progress during generation.
 ///
 /// Computes percentage and ETA based on tokens/steps generated vs estimated.
 /// Supports both token-based (MusicGen) and step-based (ACE-Step) tracking.
+///
+/// The rate is measured over a sliding window of recent updates, starting at
+/// the first update, so model setup before the first token or step does not
+/// inflate early estimates and a change of pace shows up within the window.
+/// The window always spans a few updates, so a single stalled update only
+/// raises the estimate for a while.
+/// Repeated updates with the same count, as when a Heun step takes two model
+/// evaluations, are ignored, and the count never goes backwards.
+///
+/// A generation can be split into weighted phases with
+/// [`with_phases`](Self::with_phases), so that percent and ETA cover the
+/// whole generation rather than the current phase.
 #[derive(Debug)]
 pub struct ProgressTracker {
     /// Target duration in seconds.
     duration_sec: u32,
-    /// Estimated total units (tokens or steps).
+    /// Estimated total units (tokens or steps) of the current phase.
     units_estimated: usize,
-    /// Current units completed (tokens or steps).
+    /// Current units completed (tokens or steps) in the current phase.
     units_completed: usize,
-    /// Time when generation started.
-    start_time: Instant,
     /// Last reported percentage (for 5% increment tracking).
     last_reported_percent: u8,
     /// Progress tracking mode.
     mode: ProgressMode,
+    /// `(time, units_completed)` of the updates that changed the count,
+    /// oldest first. Of those before the rate window, only the newest is kept,
+    /// unless fewer than [`MIN_RATE_SAMPLES`] would remain.
+    samples: VecDeque<(Instant, usize)>,
+    /// Length of the window the rate is measured over.
+    window: Duration,
+    /// Phases with their relative share of the time, empty for a single phase.
+    phases: Vec<(GenerationPhase, f32)>,
+    /// Index of the current phase in `phases`.
+    phase: usize,
 }
 
 impl ProgressTracker {
@@ -57,14 +100,11 @@ impl ProgressTracker {
     /// assert_eq!(tracker.units_estimated(), 1500); // 30 * 50
     /// ```
     pub fn new(duration_sec: u32) -> Self {
-        Self {
+        Self::with_mode(
             duration_sec,
-            units_estimated: duration_sec as usize * TOKENS_PER_SECOND,
-            units_completed: 0,
-            start_time: Instant::now(),
-            last_reported_percent: 0,
-            mode: ProgressMode::Tokens,
-        }
+            duration_sec as usize * TOKENS_PER_SECOND,
+            ProgressMode::Tokens,
+        )
     }
 
     /// Creates a new step-based progress tracker for diffusion models.
@@ -84,57 +124,166 @@ impl ProgressTracker {
     /// assert_eq!(tracker.units_estimated(), 60);
     /// ```
     pub fn for_steps(duration_sec: u32, total_steps: usize) -> Self {
+        Self::with_mode(duration_sec, total_steps, ProgressMode::Steps)
+    }
+
+    fn with_mode(duration_sec: u32, units_estimated: usize, mode: ProgressMode) -> Self {
         Self {
             duration_sec,
-            units_estimated: total_steps,
+            units_estimated,
             units_completed: 0,
-            start_time: Instant::now(),
             last_reported_percent: 0,
-            mode: ProgressMode::Steps,
+            mode,
+            samples: VecDeque::new(),
+            window: DEFAULT_RATE_WINDOW,
+            phases: Vec::new(),
+            phase: 0,
         }
     }
 
+    /// Sets the length of the window the rate is measured over.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Splits the generation into phases with relative shares of its time.
+    ///
+    /// The tracker starts in the first phase; see
+    /// [`begin_phase`](Self::begin_phase).
+    pub fn with_phases(mut self, phases: &[(GenerationPhase, f32)]) -> Self {
+        self.phases = phases.to_vec();
+        self.phase = 0;
+        self
+    }
+
+    /// Moves on to `phase`, which has `units_estimated` units of its own, at
+    /// time `now`.
+    ///
+    /// Phases that are not configured, or come before the current one, are
+    /// ignored.
+    pub fn begin_phase(&mut self, phase: GenerationPhase, units_estimated: usize, now: Instant) {
+        let Some(index) = self.phases.iter().position(|(p, _)| *p == phase) else {
+            return;
+        };
+        if index <= self.phase {
+            return;
+        }
+        self.phase = index;
+        self.units_estimated = units_estimated;
+        self.units_completed = 0;
+        self.samples.clear();
+        self.samples.push_back((now, 0));
+    }
+
+    /// Returns the current phase, if the tracker has phases.
+    pub fn phase(&self) -> Option<GenerationPhase> {
+        self.phases.get(self.phase).map(|(phase, _)| *phase)
+    }
+
     /// Updates the progress with the current number of units completed.
     ///
     /// # Arguments
     ///
     /// * `units_completed` - Current number of tokens/steps completed
     pub fn update(&mut self, units_completed: usize) {
+        self.record(units_completed, Instant::now());
+    }
+
+    /// Records that `units_completed` units were done at time `now`.
+    ///
+    /// Times are passed in by the caller, so tests can use a fake clock.
+    pub fn record(&mut self, units_completed: usize, now: Instant) {
+        if !self.samples.is_empty() && units_completed <= self.units_completed {
+            return;
+        }
         self.units_completed = units_completed;
+        self.samples.push_back((now, units_completed));
+
+        let window_start = now.checked_sub(self.window).unwrap_or(now);
+        while self.samples.len() > MIN_RATE_SAMPLES && self.samples[1].0 <= window_start {
+            self.samples.pop_front();
+        }
     }
 
-    /// Returns the current progress percentage (0-99).
+    /// Returns units completed per second over the rate window, or None until
+    /// two different counts have been recorded.
+    pub fn rate_per_sec(&self) -> Option<f32> {
+        let (first_time, first_units) = *self.samples.front()?;
+        let (last_time, last_units) = *self.samples.back()?;
+        let elapsed = last_time.saturating_duration_since(first_time).as_secs_f32();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        Some((last_units - first_units) as f32 / elapsed)
+    }
+
+    /// Returns the current progress percentage (0-99) of the whole
+    /// generation.
     ///
     /// Progress is capped at 99 until generation is complete.
     /// The completion notification signals 100%.
-    pub fn get_percent(&self) -> u8 {
+    pub fn percent(&self) -> u8 {
         if self.units_estimated == 0 {
             return 0;
         }
-        let percent = (self.units_completed * 100) / self.units_estimated;
+        let percent = if self.phases.is_empty() {
+            (self.units_completed * 100) / self.units_estimated
+        } else {
+            let fraction = (self.units_completed as f32 / self.units_estimated as f32).min(1.0);
+            let done: f32 = self.phases[..self.phase].iter().map(|(_, w)| w).sum();
+            let total: f32 = self.phases.iter().map(|(_, w)| w).sum();
+            ((done + self.phase_weight() * fraction) / total * 100.0) as usize
+        };
         // Cap at 99 until complete
         std::cmp::min(percent, 99) as u8
     }
 
-    /// Returns the estimated time remaining in seconds.
+    /// Returns the estimated time remaining in seconds, including later
+    /// phases.
     ///
-    /// Based on current generation rate extrapolated to remaining units.
-    pub fn get_eta(&self) -> f32 {
-        let elapsed = self.start_time.elapsed().as_secs_f32();
-
-        if self.units_completed == 0 || elapsed == 0.0 {
+    /// Based on the rate over the window, or a rough per-unit estimate until
+    /// the rate is known. Later phases are assumed to take their share of
+    /// the time relative to the current phase.
+    pub fn eta_seconds(&self) -> f32 {
+        let units_remaining = self.units_estimated.saturating_sub(self.units_completed);
+        let (remaining, phase_total) = match self.rate_per_sec() {
+            Some(rate) if rate > 0.0 => (
+                units_remaining as f32 / rate,
+                self.units_estimated as f32 / rate,
+            ),
             // Can't estimate without data, use rough estimate
-            return estimate_generation_time(self.units_estimated, self.mode);
+            _ => (
+                estimate_generation_time(units_remaining, self.mode),
+                estimate_generation_time(self.units_estimated, self.mode),
+            ),
+        };
+
+        let later: f32 = self.phases.iter().skip(self.phase + 1).map(|(_, w)| w).sum();
+        if later > 0.0 && self.phase_weight() > 0.0 {
+            remaining + phase_total * later / self.phase_weight()
+        } else {
+            remaining
         }
+    }
 
-        let units_per_sec = self.units_completed as f32 / elapsed;
-        let units_remaining = self.units_estimated.saturating_sub(self.units_completed);
+    /// Returns the relative share of the current phase, 1 without phases.
+    fn phase_weight(&self) -> f32 {
+        self.phases.get(self.phase).map_or(1.0, |(_, weight)| *weight)
+    }
 
-        if units_per_sec > 0.0 {
-            units_remaining as f32 / units_per_sec
-        } else {
-            estimate_generation_time(units_remaining, self.mode)
-        }
+    /// Returns the current progress percentage (0-99).
+    ///
+    /// Same as [`percent`](Self::percent).
+    pub fn get_percent(&self) -> u8 {
+        self.percent()
+    }
+
+    /// Returns the estimated time remaining in seconds.
+    ///
+    /// Same as [`eta_seconds`](Self::eta_seconds).
+    pub fn get_eta(&self) -> f32 {
+        self.eta_seconds()
     }
 
     /// Returns the number of units (tokens/steps) completed so far.
@@ -236,6 +385,151 @@ impl ProgressTracker {
     }
 }
 
+/// Decides which progress callbacks of one generation are sent as
+/// `generation_progress` notifications.
+///
+/// A notification is sent when progress crosses the next multiple of the
+/// percent interval, but not sooner than `min_interval` after the previous
+/// one. The first notification and the final one (current == total) are
+/// always sent. Times are passed in by the caller, so tests can use a fake
+/// clock.
+#[derive(Debug)]
+pub struct ProgressReporter {
+    /// Minimum progress increment in percent.
+    interval_percent: u8,
+    /// Minimum time between notifications.
+    min_interval: Duration,
+    /// Last reported percentage, rounded down to the interval.
+    last_percent: u8,
+    /// When the last notification was sent.
+    last_sent: Option<Instant>,
+}
+
+impl ProgressReporter {
+    /// Creates a reporter for a new generation.
+    pub fn new(interval_percent: u8, min_interval: Duration) -> Self {
+        Self {
+            interval_percent: interval_percent.max(1),
+            min_interval,
+            last_percent: 0,
+            last_sent: None,
+        }
+    }
+
+    /// Handles a progress callback at time `now`.
+    ///
+    /// Returns the percentage to report (100 when complete), or None if no
+    /// notification should be sent.
+    pub fn report(&mut self, current: usize, total: usize, now: Instant) -> Option<u8> {
+        if total == 0 {
+            return None;
+        }
+        if current >= total {
+            self.last_sent = Some(now);
+            return Some(100);
+        }
+
+        let percent = std::cmp::min(current * 100 / total, 99) as u8;
+        let interval = self.interval_percent;
+        let next_threshold = (self.last_percent / interval + 1) * interval;
+        if percent < next_threshold {
+            return None;
+        }
+        if let Some(last_sent) = self.last_sent {
+            if now.saturating_duration_since(last_sent) < self.min_interval {
+                return None;
+            }
+        }
+
+        self.last_percent = (percent / interval) * interval;
+        self.last_sent = Some(now);
+        Some(percent)
+    }
+}
+
+/// Callback receiving `(decoded_sec, total_sec)` whenever a chunk of audio
+/// has been decoded.
+///
+/// Reports progress in seconds of audio rather than model steps. Cloning is
+/// cheap; clones call the same callback.
+#[derive(Clone)]
+pub struct AudioProgress {
+    callback: Arc<dyn Fn(f32, f32) + Send + Sync>,
+}
+
+impl AudioProgress {
+    /// Wraps a callback receiving `(decoded_sec, total_sec)`.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(f32, f32) + Send + Sync + 'static,
+    {
+        Self {
+            callback: Arc::new(callback),
+        }
+    }
+
+    /// Reports that `decoded_sec` of `total_sec` seconds have been decoded.
+    pub fn report(&self, decoded_sec: f32, total_sec: f32) {
+        (self.callback)(decoded_sec, total_sec);
+    }
+
+    /// Returns the decoded share as a percentage (0-100).
+    pub fn percent(decoded_sec: f32, total_sec: f32) -> u8 {
+        if total_sec <= 0.0 {
+            return 100;
+        }
+        (decoded_sec / total_sec * 100.0).clamp(0.0, 100.0) as u8
+    }
+}
+
+impl fmt::Debug for AudioProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AudioProgress").finish_non_exhaustive()
+    }
+}
+
+/// Rate limits `download_progress` notifications per file.
+///
+/// The first and final notification for each file are always sent; the
+/// others only if `min_interval` has passed since the previous one for the
+/// same file.
+#[derive(Debug)]
+pub struct DownloadProgressThrottle {
+    /// Minimum time between notifications for one file.
+    min_interval: Duration,
+    /// When the last notification was sent, by file name.
+    last_sent: HashMap<String, Instant>,
+}
+
+impl DownloadProgressThrottle {
+    /// Creates a throttle with no notifications sent yet.
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            last_sent: HashMap::new(),
+        }
+    }
+
+    /// Returns true if a notification for `file_name` at time `now` should be sent.
+    pub fn should_send(
+        &mut self,
+        file_name: &str,
+        bytes_downloaded: u64,
+        bytes_total: u64,
+        now: Instant,
+    ) -> bool {
+        let finished = bytes_total > 0 && bytes_downloaded >= bytes_total;
+        let due = match self.last_sent.get(file_name) {
+            Some(&last_sent) => now.saturating_duration_since(last_sent) >= self.min_interval,
+            None => true,
+        };
+        if finished || due {
+            self.last_sent.insert(file_name.to_string(), now);
+        }
+        finished || due
+    }
+}
+
 /// Estimates generation time based on unit count and mode.
 ///
 /// Returns an estimate in seconds. Actual time depends on hardware.
@@ -380,6 +674,194 @@ mod tests {
         assert_eq!(tracker.total_steps(), None);
     }
 
+    /// Feeds `tracker` one update per unit, `secs(unit)` seconds after
+    /// `start`, returning the ETA after each update.
+    fn feed(
+        tracker: &mut ProgressTracker,
+        start: Instant,
+        units: std::ops::Range<usize>,
+        secs: impl Fn(usize) -> f32,
+    ) -> Vec<f32> {
+        units
+            .map(|unit| {
+                tracker.record(unit, start + Duration::from_secs_f32(secs(unit)));
+                tracker.eta_seconds()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn eta_at_constant_rate_ignores_startup() {
+        // 100 steps at 2/s, starting after 20s of model setup
+        let start = Instant::now();
+        let mut tracker = ProgressTracker::for_steps(30, 100);
+        let etas = feed(&mut tracker, start, 0..100, |step| 20.0 + step as f32 * 0.5);
+
+        assert_eq!(tracker.rate_per_sec(), Some(2.0));
+        for (step, eta) in etas.iter().enumerate().skip(1) {
+            let actual = (100 - step) as f32 * 0.5;
+            assert!((eta - actual).abs() < 0.01, "step {}: {} vs {}", step, eta, actual);
+        }
+    }
+
+    #[test]
+    fn eta_follows_acceleration_within_the_window() {
+        // 1 step/s for 50 steps, then 4 steps/s
+        let start = Instant::now();
+        let secs = |step: usize| {
+            if step <= 50 {
+                step as f32
+            } else {
+                50.0 + (step - 50) as f32 * 0.25
+            }
+        };
+        let mut tracker = ProgressTracker::for_steps(30, 200).with_window(Duration::from_secs(5));
+        let etas = feed(&mut tracker, start, 0..200, secs);
+
+        // Once the window holds only fast steps, the ETA is the fast rate's
+        for (step, eta) in etas.iter().enumerate().skip(71) {
+            let actual = (200 - step) as f32 * 0.25;
+            assert!((eta - actual).abs() < 0.01, "step {}: {} vs {}", step, eta, actual);
+        }
+        // A whole-run average would still overestimate by 40% at step 100
+        let naive = 100.0 / (100.0 / secs(100));
+        assert!(naive > etas[100] * 1.4);
+    }
+
+    #[test]
+    fn eta_grows_after_a_stall_and_recovers() {
+        // 1 step/s, except step 30 takes 10s
+        let start = Instant::now();
+        let secs = |step: usize| if step < 30 { step as f32 } else { step as f32 + 9.0 };
+        let mut tracker = ProgressTracker::for_steps(30, 100).with_window(Duration::from_secs(10));
+        let etas = feed(&mut tracker, start, 0..100, secs);
+
+        assert!((etas[29] - 71.0).abs() < 0.01);
+        // Right after the stall the estimate is pessimistic, but bounded
+        assert!(etas[30] > 70.0 * 1.5 && etas[30] < 70.0 * 2.5);
+        // Once the stall leaves the window the estimate is exact again
+        for (step, eta) in etas.iter().enumerate().skip(40) {
+            assert!((eta - (100 - step) as f32).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn heun_half_steps_advance_percent_monotonically() {
+        // Heun reports each user step twice, once per model evaluation
+        let start = Instant::now();
+        let mut tracker = ProgressTracker::for_steps(30, 20);
+        let mut last_percent = 0;
+        for eval in 0..40 {
+            tracker.record(eval / 2, start + Duration::from_millis(eval as u64 * 250));
+            let percent = tracker.percent();
+            assert!(percent >= last_percent);
+            last_percent = percent;
+        }
+        assert_eq!(last_percent, 95);
+        // Steps take two evaluations: 2 steps/s, not a mix of 4 and infinite
+        assert_eq!(tracker.rate_per_sec(), Some(2.0));
+
+        // Going backwards is ignored
+        tracker.record(3, start + Duration::from_secs(20));
+        assert_eq!(tracker.units_completed(), 19);
+    }
+
+    #[test]
+    fn phases_weight_percent_and_eta() {
+        let start = Instant::now();
+        let mut tracker = ProgressTracker::for_steps(30, 10).with_phases(&[
+            (GenerationPhase::Diffusion, 3.0),
+            (GenerationPhase::Decode, 1.0),
+        ]);
+        assert_eq!(tracker.phase(), Some(GenerationPhase::Diffusion));
+        feed(&mut tracker, start, 0..6, |step| step as f32);
+
+        // Half of the diffusion is 37.5% of the run; 5s of steps and 10s / 3
+        // of decoding remain
+        assert_eq!(tracker.percent(), 37);
+        assert!((tracker.eta_seconds() - (5.0 + 10.0 / 3.0)).abs() < 0.01);
+
+        tracker.begin_phase(GenerationPhase::Decode, 1000, start + Duration::from_secs(10));
+        tracker.record(500, start + Duration::from_secs(11));
+        assert_eq!(tracker.percent(), 87);
+        assert!((tracker.eta_seconds() - 1.0).abs() < 0.01);
+
+        // Earlier or unknown phases are ignored
+        tracker.begin_phase(GenerationPhase::Diffusion, 10, start);
+        tracker.begin_phase(GenerationPhase::Vocode, 10, start);
+        assert_eq!(tracker.phase(), Some(GenerationPhase::Decode));
+        assert_eq!(tracker.units_estimated(), 1000);
+    }
+
+    #[test]
+    fn reporter_suppresses_notifications_within_interval() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut reporter = ProgressReporter::new(5, Duration::from_millis(250));
+
+        // Below the first threshold nothing is sent
+        assert_eq!(reporter.report(0, 100, at(0)), None);
+        assert_eq!(reporter.report(4, 100, at(10)), None);
+
+        // The first notification goes out immediately
+        assert_eq!(reporter.report(5, 100, at(20)), Some(5));
+
+        // Later thresholds wait for the interval
+        assert_eq!(reporter.report(10, 100, at(100)), None);
+        assert_eq!(reporter.report(15, 100, at(200)), None);
+        assert_eq!(reporter.report(16, 100, at(270)), Some(16));
+        assert_eq!(reporter.report(19, 100, at(600)), None);
+        assert_eq!(reporter.report(20, 100, at(600)), Some(20));
+    }
+
+    #[test]
+    fn reporter_always_sends_completion() {
+        let start = Instant::now();
+        let mut reporter = ProgressReporter::new(5, Duration::from_millis(250));
+        assert_eq!(reporter.report(50, 100, start), Some(50));
+        assert_eq!(reporter.report(99, 100, start), None);
+        assert_eq!(reporter.report(100, 100, start), Some(100));
+        assert_eq!(reporter.report(0, 0, start), None);
+    }
+
+    #[test]
+    fn reporter_without_interval_sends_every_increment() {
+        let now = Instant::now();
+        let mut reporter = ProgressReporter::new(5, Duration::ZERO);
+        let sent: Vec<u8> = (0..=100).filter_map(|i| reporter.report(i, 100, now)).collect();
+        assert_eq!(sent.len(), 20);
+        assert_eq!(sent.last(), Some(&100));
+    }
+
+    #[test]
+    fn download_throttle_is_per_file() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut throttle = DownloadProgressThrottle::new(Duration::from_millis(250));
+
+        assert!(throttle.should_send("a.onnx", 10, 100, at(0)));
+        assert!(!throttle.should_send("a.onnx", 20, 100, at(100)));
+        // A new file starts its own interval
+        assert!(throttle.should_send("b.onnx", 10, 100, at(100)));
+        assert!(throttle.should_send("a.onnx", 30, 100, at(250)));
+        // The final notification for a file always goes out
+        assert!(throttle.should_send("a.onnx", 100, 100, at(260)));
+        assert!(!throttle.should_send("b.onnx", 50, 100, at(300)));
+    }
+
+    #[test]
+    fn audio_progress_reports_decoded_seconds() {
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let progress = AudioProgress::new(move |decoded, total| {
+            sink.lock().unwrap().push(AudioProgress::percent(decoded, total));
+        });
+        progress.clone().report(15.0, 60.0);
+        progress.report(61.0, 60.0);
+        assert_eq!(*reported.lock().unwrap(), vec![25, 100]);
+        assert_eq!(AudioProgress::percent(0.0, 0.0), 100);
+    }
+
     #[test]
     fn estimate_generation_time_tokens() {
         // 500 tokens at 0.05s each = 25s
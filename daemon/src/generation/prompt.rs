@@ -0,0 +1,209 @@
+//! Composition of tag-style prompts from structured tags.
+//!
+//! ACE-Step was trained on comma-separated tag prompts such as
+//! `jazz, lofi, piano, 80 bpm, chill`. [`PromptTags`] lets clients pick tags
+//! from lists instead of writing free text, and [`PromptTags::compose`]
+//! serializes them in a fixed order so the same tags always produce the same
+//! prompt, and therefore the same track ID.
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// Genres offered to clients. Others are passed through with a warning.
+pub const GENRES: &[&str] = &[
+    "acoustic",
+    "ambient",
+    "boom bap",
+    "bossa nova",
+    "chillhop",
+    "cinematic",
+    "classical",
+    "downtempo",
+    "electronic",
+    "folk",
+    "funk",
+    "hip hop",
+    "house",
+    "jazz",
+    "lofi",
+    "r&b",
+    "soul",
+    "synthwave",
+    "trip hop",
+];
+
+/// Moods offered to clients. Others are passed through with a warning.
+pub const MOODS: &[&str] = &[
+    "calm",
+    "chill",
+    "cozy",
+    "dark",
+    "dreamy",
+    "energetic",
+    "focused",
+    "happy",
+    "melancholic",
+    "mellow",
+    "nostalgic",
+    "peaceful",
+    "relaxed",
+    "romantic",
+    "sad",
+    "upbeat",
+    "warm",
+];
+
+/// Slowest accepted tempo in beats per minute.
+pub const MIN_BPM: u16 = 40;
+
+/// Fastest accepted tempo in beats per minute.
+pub const MAX_BPM: u16 = 200;
+
+/// Structured description of a track, composed into a tag-style prompt.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, JsonSchema)]
+pub struct PromptTags {
+    /// Genres, e.g. `["lofi", "jazz"]`.
+    #[serde(default)]
+    pub genre: Vec<String>,
+
+    /// Instruments, e.g. `["piano", "vinyl crackle"]`.
+    #[serde(default)]
+    pub instruments: Vec<String>,
+
+    /// Tempo in beats per minute (40-200).
+    pub bpm: Option<u16>,
+
+    /// Moods, e.g. `["chill", "nostalgic"]`.
+    #[serde(default)]
+    pub mood: Vec<String>,
+
+    /// Free text appended after the tags.
+    pub extra: Option<String>,
+}
+
+impl PromptTags {
+    /// Composes the tag-style prompt, returning it with warnings for genres
+    /// and moods outside [`GENRES`] and [`MOODS`].
+    ///
+    /// Tags are trimmed, lowercased, sorted and deduplicated within each
+    /// group, and the groups always appear as genres, instruments, tempo,
+    /// moods, then `extra`. Fails on a tag containing a comma, a tempo
+    /// outside 40-200 bpm, or when no tags are given.
+    pub fn compose(&self) -> Result<(String, Vec<String>), String> {
+        let mut warnings = Vec::new();
+        let genres = normalize_group(&self.genre, "genre")?;
+        let instruments = normalize_group(&self.instruments, "instruments")?;
+        let moods = normalize_group(&self.mood, "mood")?;
+        warn_unknown(&genres, GENRES, "genre", &mut warnings);
+        warn_unknown(&moods, MOODS, "mood", &mut warnings);
+
+        let mut parts = genres;
+        parts.extend(instruments);
+        if let Some(bpm) = self.bpm {
+            if !(MIN_BPM..=MAX_BPM).contains(&bpm) {
+                return Err(format!(
+                    "bpm must be between {} and {}, got {}",
+                    MIN_BPM, MAX_BPM, bpm
+                ));
+            }
+            parts.push(format!("{} bpm", bpm));
+        }
+        parts.extend(moods);
+        if let Some(extra) = self.extra.as_deref().map(str::trim).filter(|e| !e.is_empty()) {
+            parts.push(extra.to_string());
+        }
+
+        if parts.is_empty() {
+            return Err("prompt_tags must contain at least one tag".to_string());
+        }
+        Ok((parts.join(", "), warnings))
+    }
+}
+
+/// Trims, lowercases, sorts and deduplicates a group of tags, dropping empty
+/// ones.
+fn normalize_group(tags: &[String], group: &str) -> Result<Vec<String>, String> {
+    let mut normalized = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.contains(',') {
+            return Err(format!("{} tag '{}' cannot contain a comma", group, tag));
+        }
+        if !tag.is_empty() {
+            normalized.push(tag);
+        }
+    }
+    normalized.sort();
+    normalized.dedup();
+    Ok(normalized)
+}
+
+/// Adds a warning for each tag missing from `known`.
+fn warn_unknown(tags: &[String], known: &[&str], group: &str, warnings: &mut Vec<String>) {
+    for tag in tags.iter().filter(|tag| !known.contains(&tag.as_str())) {
+        warnings.push(format!("Unknown {} '{}' passed through to the prompt", group, tag));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(genre: &[&str], instruments: &[&str], bpm: Option<u16>, mood: &[&str]) -> PromptTags {
+        let owned = |list: &[&str]| list.iter().map(|s| s.to_string()).collect();
+        PromptTags {
+            genre: owned(genre),
+            instruments: owned(instruments),
+            bpm,
+            mood: owned(mood),
+            extra: None,
+        }
+    }
+
+    #[test]
+    fn composes_groups_in_fixed_order() {
+        let mut tags = tags(&["lofi", "jazz"], &["rhodes", "piano"], Some(80), &["chill"]);
+        tags.extra = Some("  rain on the window ".to_string());
+        let (prompt, warnings) = tags.compose().unwrap();
+        assert_eq!(prompt, "jazz, lofi, piano, rhodes, 80 bpm, chill, rain on the window");
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn composition_is_stable_across_order_case_and_repeats() {
+        let a = tags(&["lofi", "jazz"], &["piano"], Some(72), &["warm", "chill"]);
+        let b = tags(&[" Jazz", "LOFI", "jazz"], &["piano ", ""], Some(72), &["chill", "Warm"]);
+        assert_eq!(a.compose().unwrap().0, b.compose().unwrap().0);
+
+        // Only the order within a group is normalized, not across groups
+        let c = tags(&["piano"], &["jazz", "lofi"], Some(72), &["chill", "warm"]);
+        assert_ne!(a.compose().unwrap().0, c.compose().unwrap().0);
+    }
+
+    #[test]
+    fn unknown_vocabulary_is_passed_through_with_warnings() {
+        let (prompt, warnings) = tags(&["vaporwave"], &["kalimba"], None, &["sleepy"])
+            .compose()
+            .unwrap();
+        assert_eq!(prompt, "vaporwave, kalimba, sleepy");
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("genre 'vaporwave'"));
+        assert!(warnings[1].contains("mood 'sleepy'"));
+    }
+
+    #[test]
+    fn rejects_invalid_tags() {
+        for bpm in [MIN_BPM - 1, MAX_BPM + 1] {
+            let err = tags(&["lofi"], &[], Some(bpm), &[]).compose().unwrap_err();
+            assert!(err.contains("bpm"), "{}", err);
+        }
+        assert!(tags(&[], &[], Some(MIN_BPM), &[]).compose().is_ok());
+        assert!(tags(&[], &[], Some(MAX_BPM), &[]).compose().is_ok());
+
+        let err = tags(&["lofi, jazz"], &[], None, &[]).compose().unwrap_err();
+        assert!(err.contains("comma"));
+
+        let err = tags(&[" "], &[], None, &[]).compose().unwrap_err();
+        assert!(err.contains("at least one tag"));
+    }
+}
@@ -1,25 +1,76 @@
 //! Generation queue for managing pending jobs.
 //!
-//! Implements a priority queue for generation jobs with a maximum capacity of 10.
-//! High-priority jobs are inserted at the front of the queue.
+//! Implements a priority queue for generation jobs with a configurable maximum
+//! capacity (10 by default).
+//! Jobs are ordered high priority first, then by submission order within
+//! each priority (see [`GenerationQueue::add`]).
+//! Under the [`QueuePolicy::BackendAffinity`] policy, jobs for the loaded
+//! backend may be processed ahead of older jobs for the other backend to
+//! avoid reloading models.
 
 use std::collections::VecDeque;
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
 
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::models::Backend;
 use crate::types::{GenerationJob, JobPriority};
 
 /// Maximum number of jobs allowed in the queue.
 pub const MAX_QUEUE_SIZE: usize = 10;
 
+/// Default time a job may wait while jobs for the loaded backend are
+/// preferred under [`QueuePolicy::BackendAffinity`] (5 minutes).
+pub const DEFAULT_MAX_STARVATION_SEC: u64 = 300;
+
+/// Order in which queued jobs are processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum QueuePolicy {
+    /// Strict priority then submission order.
+    #[default]
+    Fifo,
+
+    /// Prefer jobs for the loaded backend to minimize model switches, within
+    /// the same priority and until another job has waited too long.
+    BackendAffinity,
+}
+
+impl QueuePolicy {
+    /// Returns the string representation of the policy.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueuePolicy::Fifo => "fifo",
+            QueuePolicy::BackendAffinity => "backend_affinity",
+        }
+    }
+
+    /// Parses a queue policy from a string.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "fifo" => Some(QueuePolicy::Fifo),
+            "backend_affinity" => Some(QueuePolicy::BackendAffinity),
+            _ => None,
+        }
+    }
+}
+
 /// A priority queue for generation jobs.
 ///
-/// The queue has a maximum capacity of 10 jobs. High-priority jobs
-/// are inserted at the front, normal priority at the back.
+/// The queue has a maximum capacity of 10 jobs by default. High-priority jobs
+/// are inserted ahead of normal priority ones, and jobs of equal priority keep
+/// their submission order.
 #[derive(Debug)]
 pub struct GenerationQueue {
     jobs: VecDeque<GenerationJob>,
+    max_size: usize,
+    next_seq: u64,
+    policy: QueuePolicy,
+    max_starvation: Duration,
 }
 
 impl Default for GenerationQueue {
@@ -31,49 +82,102 @@ impl Default for GenerationQueue {
 impl GenerationQueue {
     /// Creates a new empty generation queue.
     pub fn new() -> Self {
+        Self::with_max_size(MAX_QUEUE_SIZE)
+    }
+
+    /// Creates a new empty generation queue with the given capacity.
+    pub fn with_max_size(max_size: usize) -> Self {
         Self {
-            jobs: VecDeque::with_capacity(MAX_QUEUE_SIZE),
+            jobs: VecDeque::with_capacity(max_size),
+            max_size,
+            next_seq: 0,
+            policy: QueuePolicy::default(),
+            max_starvation: Duration::from_secs(DEFAULT_MAX_STARVATION_SEC),
         }
     }
 
+    /// Returns the maximum number of jobs the queue accepts.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Changes the queue capacity.
+    ///
+    /// Shrinking below the current length never drops queued jobs; new jobs
+    /// are rejected until the queue drains below the new limit.
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
+    /// Returns the scheduling policy used by [`pop_next_with_policy`](Self::pop_next_with_policy).
+    pub fn policy(&self) -> QueuePolicy {
+        self.policy
+    }
+
+    /// Changes the scheduling policy and how long a job may be passed over
+    /// under [`QueuePolicy::BackendAffinity`].
+    pub fn set_policy(&mut self, policy: QueuePolicy, max_starvation_sec: u64) {
+        self.policy = policy;
+        self.max_starvation = Duration::from_secs(max_starvation_sec);
+    }
+
     /// Adds a job to the queue with the given priority.
     ///
-    /// High-priority jobs are inserted at the front of the queue,
-    /// normal priority jobs at the back.
+    /// The job is stamped with the next submission sequence number and
+    /// placed by [`order_key`](Self::order_key): after every high-priority
+    /// job if it is high priority, at the back otherwise. Jobs of equal
+    /// priority are therefore processed in submission order.
     ///
     /// Returns `Err` if the queue is full.
     pub fn add(&mut self, mut job: GenerationJob) -> Result<usize, QueueFullError> {
         if self.is_full() {
             return Err(QueueFullError {
                 current_size: self.jobs.len(),
+                max_size: self.max_size,
             });
         }
 
-        let position = match job.priority {
-            JobPriority::High => {
-                // Insert at front, after any other high-priority jobs
-                let insert_pos = self
-                    .jobs
-                    .iter()
-                    .position(|j| j.priority != JobPriority::High)
-                    .unwrap_or(self.jobs.len());
-                job.set_queued(insert_pos as u8);
-                self.jobs.insert(insert_pos, job);
-                // Update positions for jobs after the insertion point
-                self.update_positions();
-                insert_pos
-            }
-            JobPriority::Normal => {
-                let pos = self.jobs.len();
-                job.set_queued(pos as u8);
-                self.jobs.push_back(job);
-                pos
-            }
-        };
+        job.submission_seq = self.next_seq;
+        self.next_seq += 1;
+
+        let key = Self::order_key(&job);
+        let position = self.jobs.partition_point(|j| Self::order_key(j) < key);
+        job.set_queued(position as u8);
+        self.jobs.insert(position, job);
+        self.update_positions();
 
         Ok(position)
     }
 
+    /// Returns the key jobs are ordered by: high priority before normal, then
+    /// submission sequence within each priority.
+    pub fn order_key(job: &GenerationJob) -> (u8, u64) {
+        let class = match job.priority {
+            JobPriority::High => 0,
+            JobPriority::Normal => 1,
+        };
+        (class, job.submission_seq)
+    }
+
+    /// Returns the queued jobs in priority order, front to back.
+    pub fn iter(&self) -> impl Iterator<Item = &GenerationJob> {
+        self.jobs.iter()
+    }
+
+    /// Returns the queued jobs in priority order for in-place changes.
+    ///
+    /// Jobs are not reordered afterwards, so a job whose priority is changed
+    /// keeps its place in the queue.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut GenerationJob> {
+        self.jobs.iter_mut()
+    }
+
+    /// Returns the job [`pop_next`](Self::pop_next) would remove, without
+    /// removing it.
+    pub fn peek_next(&self) -> Option<&GenerationJob> {
+        self.jobs.front()
+    }
+
     /// Removes and returns the next job to process.
     ///
     /// Returns `None` if the queue is empty.
@@ -85,6 +189,118 @@ impl GenerationQueue {
         job
     }
 
+    /// Removes and returns the next job to process under the queue's policy.
+    ///
+    /// With [`QueuePolicy::Fifo`] this is [`pop_next`](Self::pop_next). With
+    /// [`QueuePolicy::BackendAffinity`] the first job for `current_backend`
+    /// among the highest-priority jobs is taken, unless a job for another
+    /// backend has been waiting longer than the starvation limit at `now`.
+    pub fn pop_next_with_policy(
+        &mut self,
+        current_backend: Option<Backend>,
+        now: SystemTime,
+    ) -> Option<GenerationJob> {
+        if self.jobs.is_empty() {
+            return None;
+        }
+        let candidates: Vec<&GenerationJob> = self.iter().collect();
+        let index = self.select_next(&candidates, current_backend, now);
+        let job = self.jobs.remove(index);
+        self.update_positions();
+        job
+    }
+
+    /// Returns the job [`pop_next_for_backend`](Self::pop_next_for_backend)
+    /// would remove, without removing it.
+    pub fn peek_next_for_backend(&self, backend: Backend) -> Option<&GenerationJob> {
+        self.next_for_backend(backend).map(|index| &self.jobs[index])
+    }
+
+    /// Removes and returns the next job for `backend`, if the policy lets it
+    /// start now.
+    ///
+    /// Used to start a job on a second backend alongside the one chosen by
+    /// [`pop_next_with_policy`](Self::pop_next_with_policy). Only jobs sharing
+    /// the front job's priority are candidates, so the job never starts ahead
+    /// of higher-priority work. With [`QueuePolicy::Fifo`] jobs start in queue
+    /// order, so only the front job is taken; with
+    /// [`QueuePolicy::BackendAffinity`] the first candidate for `backend` is.
+    pub fn pop_next_for_backend(&mut self, backend: Backend) -> Option<GenerationJob> {
+        let index = self.next_for_backend(backend)?;
+        let job = self.jobs.remove(index);
+        self.update_positions();
+        job
+    }
+
+    /// Returns the index of the job [`pop_next_for_backend`](Self::pop_next_for_backend)
+    /// would remove.
+    fn next_for_backend(&self, backend: Backend) -> Option<usize> {
+        let priority = self.jobs.front()?.priority;
+        let mut candidates = self.jobs.iter().take_while(|job| job.priority == priority);
+        match self.policy {
+            QueuePolicy::Fifo => candidates.next().filter(|job| job.backend == backend).map(|_| 0),
+            QueuePolicy::BackendAffinity => candidates.position(|job| job.backend == backend),
+        }
+    }
+
+    /// Returns the queued jobs in the order they will be processed, assuming
+    /// `current_backend` is loaded and no other jobs arrive.
+    pub fn processing_order(
+        &self,
+        current_backend: Option<Backend>,
+        now: SystemTime,
+    ) -> Vec<&GenerationJob> {
+        let mut remaining: Vec<&GenerationJob> = self.iter().collect();
+        let mut order = Vec::with_capacity(remaining.len());
+        let mut backend = current_backend;
+        while !remaining.is_empty() {
+            let job = remaining.remove(self.select_next(&remaining, backend, now));
+            backend = Some(job.backend);
+            order.push(job);
+        }
+        order
+    }
+
+    /// Returns the index in `jobs` (in queue order) of the job to run next.
+    fn select_next(
+        &self,
+        jobs: &[&GenerationJob],
+        current_backend: Option<Backend>,
+        now: SystemTime,
+    ) -> usize {
+        let Some(current_backend) = current_backend else {
+            return 0;
+        };
+        if self.policy == QueuePolicy::Fifo {
+            return 0;
+        }
+
+        // Only jobs sharing the front job's priority are candidates, so a
+        // high-priority job for the other backend still runs first
+        let priority = jobs[0].priority;
+        let candidates = jobs.iter().take_while(|job| job.priority == priority);
+
+        let mut preferred = None;
+        for (index, job) in candidates.enumerate() {
+            if job.backend == current_backend {
+                preferred.get_or_insert(index);
+            } else if self.is_starving(job, now) {
+                return index;
+            }
+        }
+        preferred.unwrap_or(0)
+    }
+
+    /// Returns true if `job` has waited longer than the starvation limit.
+    fn is_starving(&self, job: &GenerationJob, now: SystemTime) -> bool {
+        now.duration_since(job.created_at).unwrap_or_default() > self.max_starvation
+    }
+
+    /// Returns the number of queued jobs for `backend`.
+    pub fn count_for_backend(&self, backend: Backend) -> usize {
+        self.jobs.iter().filter(|job| job.backend == backend).count()
+    }
+
     /// Returns the number of jobs in the queue.
     pub fn len(&self) -> usize {
         self.jobs.len()
@@ -95,9 +311,9 @@ impl GenerationQueue {
         self.jobs.is_empty()
     }
 
-    /// Returns true if the queue is at maximum capacity (10 jobs).
+    /// Returns true if the queue is at maximum capacity.
     pub fn is_full(&self) -> bool {
-        self.jobs.len() >= MAX_QUEUE_SIZE
+        self.jobs.len() >= self.max_size
     }
 
     /// Returns the position of a job in the queue by job_id.
@@ -130,6 +346,8 @@ impl GenerationQueue {
 pub struct QueueFullError {
     /// Current number of jobs in the queue.
     pub current_size: usize,
+    /// Maximum capacity of the queue.
+    pub max_size: usize,
 }
 
 impl std::fmt::Display for QueueFullError {
@@ -137,7 +355,7 @@ impl std::fmt::Display for QueueFullError {
         write!(
             f,
             "Queue is full ({} jobs). Maximum capacity is {}.",
-            self.current_size, MAX_QUEUE_SIZE
+            self.current_size, self.max_size
         )
     }
 }
@@ -311,6 +529,40 @@ mod tests {
         )
     }
 
+    fn create_backend_job(priority: JobPriority, backend: Backend) -> GenerationJob {
+        GenerationJob::with_backend(
+            "test prompt".to_string(),
+            30,
+            Some(42),
+            priority,
+            "v1",
+            backend,
+        )
+    }
+
+    /// Queues MusicGen, ACE-Step, MusicGen, ACE-Step normal-priority jobs.
+    fn alternating_queue(policy: QueuePolicy) -> (GenerationQueue, Vec<String>) {
+        let mut queue = GenerationQueue::new();
+        queue.set_policy(policy, DEFAULT_MAX_STARVATION_SEC);
+        let mut ids = Vec::new();
+        for backend in [Backend::MusicGen, Backend::AceStep].repeat(2) {
+            let job = create_backend_job(JobPriority::Normal, backend);
+            ids.push(job.job_id.clone());
+            queue.add(job).unwrap();
+        }
+        (queue, ids)
+    }
+
+    fn drain(queue: &mut GenerationQueue, mut backend: Option<Backend>) -> Vec<String> {
+        let now = SystemTime::now();
+        let mut order = Vec::new();
+        while let Some(job) = queue.pop_next_with_policy(backend, now) {
+            backend = Some(job.backend);
+            order.push(job.job_id);
+        }
+        order
+    }
+
     #[test]
     fn queue_new_is_empty() {
         let queue = GenerationQueue::new();
@@ -331,6 +583,34 @@ mod tests {
         assert_eq!(queue.get_position(&job_id), Some(0));
     }
 
+    #[test]
+    fn queue_iter_follows_priority_order() {
+        let mut queue = GenerationQueue::new();
+        let mut ids = Vec::new();
+        for priority in [JobPriority::Normal, JobPriority::Normal, JobPriority::High] {
+            let job = create_test_job(priority);
+            ids.push(job.job_id.clone());
+            queue.add(job).unwrap();
+        }
+
+        let order: Vec<&str> = queue.iter().map(|job| job.job_id.as_str()).collect();
+        assert_eq!(order, [&ids[2], &ids[0], &ids[1]]);
+        assert_eq!(queue.peek_next().map(|job| job.job_id.as_str()), Some(ids[2].as_str()));
+        assert_eq!(queue.len(), 3);
+
+        for job in queue.iter_mut() {
+            job.priority = JobPriority::Normal;
+        }
+        assert!(queue.iter().all(|job| job.priority == JobPriority::Normal));
+        assert_eq!(queue.pop_next().unwrap().job_id, ids[2]);
+        assert_eq!(queue.peek_next().unwrap().job_id, ids[0]);
+    }
+
+    #[test]
+    fn queue_peek_next_empty() {
+        assert!(GenerationQueue::new().peek_next().is_none());
+    }
+
     #[test]
     fn queue_add_high_priority_front() {
         let mut queue = GenerationQueue::new();
@@ -381,6 +661,24 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn queue_set_max_size_rechecks_fullness() {
+        let mut queue = GenerationQueue::with_max_size(3);
+        for _ in 0..3 {
+            queue.add(create_test_job(JobPriority::Normal)).unwrap();
+        }
+        assert!(queue.is_full());
+
+        queue.set_max_size(5);
+        assert!(!queue.is_full());
+
+        // Shrinking keeps existing jobs but rejects new ones
+        queue.set_max_size(2);
+        assert_eq!(queue.len(), 3);
+        let err = queue.add(create_test_job(JobPriority::Normal)).unwrap_err();
+        assert_eq!(err.max_size, 2);
+    }
+
     #[test]
     fn queue_priority_ordering() {
         let mut queue = GenerationQueue::new();
@@ -414,6 +712,41 @@ mod tests {
         assert_eq!(queue.get_position(&n2_id), Some(3));
     }
 
+    #[test]
+    fn queue_orders_interleaved_submissions_stably() {
+        let mut queue = GenerationQueue::new();
+        let submissions = [
+            ("n1", JobPriority::Normal),
+            ("h1", JobPriority::High),
+            ("n2", JobPriority::Normal),
+            ("h2", JobPriority::High),
+            ("n3", JobPriority::Normal),
+            ("h3", JobPriority::High),
+        ];
+        let mut names = std::collections::HashMap::new();
+        for (name, priority) in submissions {
+            let job = create_test_job(priority);
+            names.insert(job.job_id.clone(), name);
+            queue.add(job).unwrap();
+        }
+
+        let seqs: Vec<u64> = queue.iter().map(|job| job.submission_seq).collect();
+        assert_eq!(seqs, [1, 3, 5, 0, 2, 4]);
+
+        let keys: Vec<(u8, u64)> = queue.iter().map(GenerationQueue::order_key).collect();
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let mut order = Vec::new();
+        while let Some(job) = queue.pop_next() {
+            order.push(names[&job.job_id]);
+        }
+        assert_eq!(order, ["h1", "h2", "h3", "n1", "n2", "n3"]);
+
+        // Sequence numbers keep increasing after the queue drains
+        queue.add(create_test_job(JobPriority::Normal)).unwrap();
+        assert_eq!(queue.peek_next().unwrap().submission_seq, 6);
+    }
+
     #[test]
     fn queue_positions_update_after_pop() {
         let mut queue = GenerationQueue::new();
@@ -441,6 +774,118 @@ mod tests {
         assert_eq!(queue.get_position(&j3_id), Some(1));
     }
 
+    #[test]
+    fn fifo_policy_matches_pop_next() {
+        let (mut fifo, _) = alternating_queue(QueuePolicy::Fifo);
+        fifo.add(create_backend_job(JobPriority::High, Backend::AceStep))
+            .unwrap();
+        let mut plain = GenerationQueue::new();
+        for job in fifo.jobs.iter() {
+            plain.jobs.push_back(job.clone());
+        }
+
+        let expected: Vec<String> = std::iter::from_fn(|| plain.pop_next())
+            .map(|job| job.job_id)
+            .collect();
+        assert_eq!(drain(&mut fifo, Some(Backend::AceStep)), expected);
+    }
+
+    #[test]
+    fn backend_affinity_batches_loaded_backend() {
+        let (mut queue, ids) = alternating_queue(QueuePolicy::BackendAffinity);
+        assert_eq!(queue.count_for_backend(Backend::AceStep), 2);
+
+        let planned: Vec<String> = queue
+            .processing_order(Some(Backend::AceStep), SystemTime::now())
+            .into_iter()
+            .map(|job| job.job_id.clone())
+            .collect();
+        // One switch instead of three: both ACE-Step jobs, then both MusicGen jobs
+        let expected: Vec<String> = [1, 3, 0, 2].iter().map(|&i| ids[i].clone()).collect();
+        assert_eq!(planned, expected);
+        assert_eq!(drain(&mut queue, Some(Backend::AceStep)), expected);
+    }
+
+    #[test]
+    fn backend_affinity_yields_to_starving_job() {
+        let (mut queue, ids) = alternating_queue(QueuePolicy::BackendAffinity);
+        let waited = Duration::from_secs(DEFAULT_MAX_STARVATION_SEC + 1);
+        queue.get_job_mut(&ids[0]).unwrap().created_at = SystemTime::now() - waited;
+
+        // The MusicGen job has waited too long, so it runs despite ACE-Step being loaded
+        let job = queue
+            .pop_next_with_policy(Some(Backend::AceStep), SystemTime::now())
+            .unwrap();
+        assert_eq!(job.job_id, ids[0]);
+        assert_eq!(queue.get_position(&ids[1]), Some(0));
+    }
+
+    #[test]
+    fn backend_affinity_respects_priority() {
+        let (mut queue, ids) = alternating_queue(QueuePolicy::BackendAffinity);
+        let urgent = create_backend_job(JobPriority::High, Backend::MusicGen);
+        let urgent_id = urgent.job_id.clone();
+        queue.add(urgent).unwrap();
+
+        let order = drain(&mut queue, Some(Backend::AceStep));
+        assert_eq!(order[0], urgent_id);
+        // After switching to MusicGen for the urgent job, its batch continues
+        let expected: Vec<String> = [0, 2, 1, 3].iter().map(|&i| ids[i].clone()).collect();
+        assert_eq!(&order[1..], expected.as_slice());
+    }
+
+    #[test]
+    fn pop_next_for_backend_keeps_fifo_order() {
+        let (mut queue, ids) = alternating_queue(QueuePolicy::Fifo);
+
+        // A MusicGen job is at the front, so no ACE-Step job may start yet
+        assert!(queue.peek_next_for_backend(Backend::AceStep).is_none());
+        assert!(queue.pop_next_for_backend(Backend::AceStep).is_none());
+
+        queue.pop_next().unwrap();
+        assert_eq!(queue.peek_next_for_backend(Backend::AceStep).unwrap().job_id, ids[1]);
+        let job = queue.pop_next_for_backend(Backend::AceStep).unwrap();
+        assert_eq!(job.job_id, ids[1]);
+        assert_eq!(queue.get_position(&ids[2]), Some(0));
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn pop_next_for_backend_takes_first_job_of_front_priority() {
+        let (mut queue, ids) = alternating_queue(QueuePolicy::BackendAffinity);
+
+        let job = queue.pop_next_for_backend(Backend::AceStep).unwrap();
+        assert_eq!(job.job_id, ids[1]);
+        assert_eq!(queue.get_position(&ids[2]), Some(1));
+
+        // A high-priority MusicGen job holds back the normal ACE-Step job
+        let urgent = create_backend_job(JobPriority::High, Backend::MusicGen);
+        let urgent_id = urgent.job_id.clone();
+        queue.add(urgent).unwrap();
+        assert!(queue.pop_next_for_backend(Backend::AceStep).is_none());
+        let job = queue.pop_next_for_backend(Backend::MusicGen).unwrap();
+        assert_eq!(job.job_id, urgent_id);
+
+        let job = queue.pop_next_for_backend(Backend::AceStep).unwrap();
+        assert_eq!(job.job_id, ids[3]);
+        assert!(queue.pop_next_for_backend(Backend::AceStep).is_none());
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn queue_policy_parsing() {
+        assert_eq!(QueuePolicy::parse("FIFO"), Some(QueuePolicy::Fifo));
+        assert_eq!(
+            QueuePolicy::parse("backend_affinity"),
+            Some(QueuePolicy::BackendAffinity)
+        );
+        assert_eq!(QueuePolicy::parse("lifo"), None);
+        assert_eq!(
+            serde_json::to_value(QueuePolicy::BackendAffinity).unwrap(),
+            "backend_affinity"
+        );
+    }
+
     #[test]
     fn queue_job_status_updates() {
         let mut queue = GenerationQueue::new();
@@ -0,0 +1,175 @@
+//! Per-stage timing breakdown for `--profile`.
+//!
+//! A [`Timings`] accumulator is passed to a pipeline, which records how long
+//! each [`GenerationPhase`] took. Like [`CancelToken`](super::CancelToken),
+//! clones share the same measurements, so the caller keeps a handle and prints
+//! the table once generation returns.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::GenerationPhase;
+
+/// Time spent in one phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageTiming {
+    /// Phase of the pipeline.
+    pub phase: GenerationPhase,
+    /// Total time spent in the phase.
+    pub total: Duration,
+    /// Number of iterations (diffusion steps, generated tokens, or calls).
+    pub count: usize,
+}
+
+impl StageTiming {
+    /// Returns the average time per iteration.
+    pub fn per_iteration(&self) -> Duration {
+        self.total / self.count.max(1) as u32
+    }
+}
+
+/// Shared accumulator of per-phase timings.
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    stages: Arc<Mutex<Vec<StageTiming>>>,
+}
+
+impl Timings {
+    /// Creates an empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds one iteration of `phase` that took `elapsed`.
+    pub fn record(&self, phase: GenerationPhase, elapsed: Duration) {
+        self.record_many(phase, elapsed, 1);
+    }
+
+    /// Adds `count` iterations of `phase` that took `elapsed` in total.
+    pub fn record_many(&self, phase: GenerationPhase, elapsed: Duration, count: usize) {
+        let mut stages = self.stages.lock().unwrap_or_else(|e| e.into_inner());
+        match stages.iter_mut().find(|s| s.phase == phase) {
+            Some(stage) => {
+                stage.total += elapsed;
+                stage.count += count;
+            }
+            None => stages.push(StageTiming {
+                phase,
+                total: elapsed,
+                count,
+            }),
+        }
+    }
+
+    /// Runs `f`, recording its duration as one iteration of `phase`.
+    pub fn time<T>(&self, phase: GenerationPhase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(phase, start.elapsed());
+        result
+    }
+
+    /// Returns the recorded phases in the order they were first recorded.
+    pub fn stages(&self) -> Vec<StageTiming> {
+        self.stages
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Formats the timings as a table.
+    ///
+    /// `wall` is the end-to-end time; whatever the phases do not account for
+    /// (model bookkeeping, resampling) is shown as `other`.
+    pub fn format_table(&self, wall: Duration) -> String {
+        let stages = self.stages();
+        let measured: Duration = stages.iter().map(|s| s.total).sum();
+        let percent = |d: Duration| {
+            if wall.is_zero() {
+                0.0
+            } else {
+                d.as_secs_f64() / wall.as_secs_f64() * 100.0
+            }
+        };
+
+        let mut table = format!(
+            "{:<12} {:>10} {:>6} {:>7} {:>12}\n",
+            "phase", "time", "%", "count", "per item"
+        );
+        for stage in &stages {
+            table.push_str(&format!(
+                "{:<12} {:>9.3}s {:>5.1}% {:>7} {:>10.2}ms\n",
+                stage.phase.as_str(),
+                stage.total.as_secs_f64(),
+                percent(stage.total),
+                stage.count,
+                stage.per_iteration().as_secs_f64() * 1000.0
+            ));
+        }
+        let other = wall.saturating_sub(measured);
+        table.push_str(&format!(
+            "{:<12} {:>9.3}s {:>5.1}%\n",
+            "other",
+            other.as_secs_f64(),
+            percent(other)
+        ));
+        table.push_str(&format!("{:<12} {:>9.3}s", "total", wall.as_secs_f64()));
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_accumulate_per_phase() {
+        let timings = Timings::new();
+        let handle = timings.clone();
+        handle.record(GenerationPhase::TextEncode, Duration::from_millis(50));
+        for _ in 0..4 {
+            handle.record(GenerationPhase::Diffusion, Duration::from_millis(100));
+        }
+        handle.record_many(GenerationPhase::Decode, Duration::from_millis(300), 150);
+
+        let stages = timings.stages();
+        let phases: Vec<_> = stages.iter().map(|s| s.phase).collect();
+        assert_eq!(
+            phases,
+            vec![
+                GenerationPhase::TextEncode,
+                GenerationPhase::Diffusion,
+                GenerationPhase::Decode
+            ]
+        );
+        assert_eq!(stages[1].total, Duration::from_millis(400));
+        assert_eq!(stages[1].count, 4);
+        assert_eq!(stages[1].per_iteration(), Duration::from_millis(100));
+        assert_eq!(stages[2].per_iteration(), Duration::from_millis(2));
+    }
+
+    #[test]
+    fn time_returns_the_result() {
+        let timings = Timings::new();
+        assert_eq!(timings.time(GenerationPhase::Write, || 7), 7);
+        assert_eq!(timings.stages()[0].count, 1);
+    }
+
+    #[test]
+    fn table_lists_phases_and_remainder() {
+        let timings = Timings::new();
+        timings.record(GenerationPhase::Diffusion, Duration::from_millis(750));
+        timings.record(GenerationPhase::Vocode, Duration::from_millis(200));
+
+        let table = timings.format_table(Duration::from_secs(1));
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("phase"));
+        assert!(lines[1].starts_with("diffusion") && lines[1].contains("75.0%"));
+        assert!(lines[3].starts_with("other") && lines[3].contains("0.050s"));
+        assert!(lines[4].starts_with("total") && lines[4].contains("1.000s"));
+
+        // Zero wall time does not divide by zero
+        assert!(Timings::new().format_table(Duration::ZERO).contains("0.0%"));
+    }
+}
@@ -0,0 +1,185 @@
+//! Commands run when generations finish.
+//!
+//! `on_complete_command` and `on_error_command` name a program the daemon
+//! runs after every completed or failed generation, so a headless daemon can
+//! raise desktop notifications or kick off scripts without being polled.
+//!
+//! The program is run directly, without a shell and without arguments. The
+//! generation's metadata is passed only in `LOFI_*` environment variables, so
+//! a prompt can never be interpreted as shell syntax; users who want shell
+//! features point the setting at a script. Hooks run on a background thread,
+//! their stdout is discarded (it would corrupt the JSON-RPC stream), and they
+//! are killed if still running after [`HOOK_TIMEOUT`]. A failing hook is
+//! logged and otherwise ignored.
+
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::error::DaemonError;
+use crate::types::{DisplayPrompt, GenerationJob, Track};
+
+/// How long a hook may run before it is killed.
+pub const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a running hook is checked for exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Environment variables describing a hook's generation, as name/value pairs.
+pub type HookEnv = Vec<(&'static str, String)>;
+
+/// Returns the environment for `on_complete_command`.
+///
+/// Sets `LOFI_TRACK_ID`, `LOFI_PATH`, `LOFI_PROMPT`, `LOFI_DURATION`,
+/// `LOFI_SEED` and `LOFI_BACKEND`. The prompt is redacted if `redact_prompts`
+/// is set.
+pub fn completion_env(track: &Track, redact_prompts: bool) -> HookEnv {
+    vec![
+        ("LOFI_TRACK_ID", track.track_id.clone()),
+        ("LOFI_PATH", track.path.to_string_lossy().to_string()),
+        ("LOFI_PROMPT", DisplayPrompt::with_redaction(&track.prompt, redact_prompts).to_string()),
+        ("LOFI_DURATION", track.duration_sec.to_string()),
+        ("LOFI_SEED", track.seed.to_string()),
+        ("LOFI_BACKEND", track.backend.as_str().to_string()),
+    ]
+}
+
+/// Returns the environment for `on_error_command`.
+///
+/// Sets `LOFI_TRACK_ID`, `LOFI_PROMPT`, `LOFI_DURATION` (the requested
+/// duration), `LOFI_SEED` (if known), `LOFI_BACKEND`, `LOFI_ERROR_CODE` and
+/// `LOFI_ERROR`.
+pub fn error_env(job: &GenerationJob, error: &DaemonError, redact_prompts: bool) -> HookEnv {
+    let mut env = vec![
+        ("LOFI_TRACK_ID", job.track_id.clone()),
+        ("LOFI_PROMPT", DisplayPrompt::with_redaction(&job.prompt, redact_prompts).to_string()),
+        ("LOFI_DURATION", job.duration_sec.to_string()),
+        ("LOFI_BACKEND", job.backend.as_str().to_string()),
+        ("LOFI_ERROR_CODE", error.code.as_str().to_string()),
+        ("LOFI_ERROR", error.message.clone()),
+    ];
+    if let Some(seed) = job.seed {
+        env.push(("LOFI_SEED", seed.to_string()));
+    }
+    env
+}
+
+/// Runs `program` in the background with `env` added to its environment.
+///
+/// Returns the thread waiting for the hook; dropping it leaves the hook
+/// running detached.
+pub fn spawn_hook(program: &str, env: HookEnv) -> JoinHandle<()> {
+    spawn_hook_with_timeout(program, env, HOOK_TIMEOUT)
+}
+
+fn spawn_hook_with_timeout(program: &str, env: HookEnv, timeout: Duration) -> JoinHandle<()> {
+    let program = program.to_string();
+    thread::spawn(move || run_hook(&program, env, timeout))
+}
+
+/// Runs `program` to completion, killing it after `timeout`.
+fn run_hook(program: &str, env: HookEnv, timeout: Duration) {
+    let spawned = Command::new(program)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Failed to run hook {}: {}", program, e);
+            return;
+        }
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                if !status.success() {
+                    eprintln!("Hook {} exited with {}", program, status);
+                }
+                return;
+            }
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                eprintln!("Hook {} killed after {}s", program, timeout.as_secs_f32());
+                return;
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                eprintln!("Failed to wait for hook {}: {}", program, e);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::models::Backend;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+
+    /// Writes an executable shell script running `body` into `dir`.
+    fn script(dir: &Path, body: &str) -> PathBuf {
+        let path = dir.join("hook.sh");
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn hook_receives_metadata_in_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("env.txt");
+        let hook = script(dir.path(), &format!("env | grep '^LOFI_' > '{}'", output.display()));
+
+        let track = Track::new(
+            PathBuf::from("/tmp/lofi/abc123.wav"),
+            "rain; rm -rf ~ $(whoami)".to_string(),
+            12.5,
+            42,
+            "musicgen-small-fp16-v1".to_string(),
+            Backend::MusicGen,
+            3.0,
+        );
+        spawn_hook(hook.to_str().unwrap(), completion_env(&track, false)).join().unwrap();
+
+        let recorded = std::fs::read_to_string(&output).unwrap();
+        for line in [
+            format!("LOFI_TRACK_ID={}", track.track_id),
+            "LOFI_PATH=/tmp/lofi/abc123.wav".to_string(),
+            "LOFI_PROMPT=rain; rm -rf ~ $(whoami)".to_string(),
+            "LOFI_DURATION=12.5".to_string(),
+            "LOFI_SEED=42".to_string(),
+            "LOFI_BACKEND=musicgen".to_string(),
+        ] {
+            assert!(recorded.lines().any(|l| l == line), "missing {}", line);
+        }
+    }
+
+    #[test]
+    fn failing_and_missing_hooks_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let hook = script(dir.path(), "exit 3");
+        spawn_hook(hook.to_str().unwrap(), Vec::new()).join().unwrap();
+
+        let missing = dir.path().join("missing.sh");
+        spawn_hook(missing.to_str().unwrap(), Vec::new()).join().unwrap();
+    }
+
+    #[test]
+    fn hung_hook_is_killed() {
+        let dir = tempfile::tempdir().unwrap();
+        let hook = script(dir.path(), "sleep 30");
+
+        let start = Instant::now();
+        spawn_hook_with_timeout(hook.to_str().unwrap(), Vec::new(), Duration::from_millis(200))
+            .join()
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -8,12 +8,16 @@
 //! - [`types`]: Core data types (Track, GenerationJob, ModelConfig)
 //! - [`config`]: Runtime configuration (DaemonConfig, Device)
 //! - [`error`]: Error types and codes (DaemonError, ErrorCode)
+//! - [`diagnostics`]: Failure dumps for bug reports
 //! - [`models`]: ONNX model wrappers (TextEncoder, Decoder, AudioCodec)
 //! - [`audio`]: Audio output (WAV writer)
 //! - [`generation`]: Generation pipeline
 //! - [`cli`]: CLI argument parsing
 //! - [`cache`]: Track caching with LRU eviction
+//! - [`hooks`]: Commands run when generations complete or fail
+//! - [`metrics`]: Generation and queue metrics in the Prometheus text format
 //! - [`rpc`]: JSON-RPC server for daemon mode
+//! - [`watch`]: Prompt file watching for `--watch` mode
 //!
 //! # Example
 //!
@@ -53,13 +57,18 @@ pub mod audio;
 pub mod cache;
 pub mod cli;
 pub mod config;
+pub mod diagnostics;
 pub mod error;
 pub mod generation;
+pub mod hooks;
+pub mod metrics;
 pub mod models;
+pub mod output;
 pub mod rpc;
 pub mod types;
+pub mod watch;
 
 // Re-export commonly used types at crate root for convenience
 pub use config::{DaemonConfig, Device};
-pub use error::{DaemonError, ErrorCode, Result};
+pub use error::{DaemonError, ErrorCode, GenerationPhase, Result};
 pub use types::{compute_track_id, GenerationJob, JobPriority, JobStatus, ModelConfig, Track};
@@ -2,84 +2,346 @@
 //!
 //! This binary can run in two modes:
 //! - CLI mode: Standalone music generation for testing
+//! - Watch mode: Regenerates whenever a prompt file changes
 //! - Daemon mode: JSON-RPC server for Neovim integration
 
-use std::time::Instant;
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 
-use lofi_daemon::audio::write_wav;
-use lofi_daemon::cli::{BackendArg, Cli, SchedulerArg};
-use lofi_daemon::config::DaemonConfig;
-use lofi_daemon::error::Result;
-use lofi_daemon::generation::{generate_ace_step, generate_with_progress};
-use lofi_daemon::models::ace_step::AceStepModels;
-use lofi_daemon::models::{ensure_ace_step_models, ensure_models};
-use lofi_daemon::rpc::{run_server, ServerState};
+use lofi_daemon::audio::{write_wav, write_wav_to_buffer};
+use lofi_daemon::cache::{acquire_instance_lock, LockAttempt, Ratings};
+use lofi_daemon::cli::{
+    AceStepCapabilities, BackendArg, BackendVersionInfo, Cli, CliErrorReport, CliResultReport,
+    VersionInfo,
+};
+use lofi_daemon::config::{config_file_path, redact_prompts_from_env, DaemonConfig};
+use lofi_daemon::error::{DaemonError, ErrorCode, GenerationPhase, Result};
+use lofi_daemon::generation::{
+    generate_ace_step_params_with_progress, generate_with_seed_retry_diagnosed, CancelToken,
+    GenerationParams, ProgressTracker, Timings, ACE_STEP_PHASES, DEFAULT_SEED_RETRIES,
+};
+use lofi_daemon::models::ace_step::{AceStepModels, SchedulerType};
+use lofi_daemon::models::{
+    check_backend_available, detect_available_providers, ensure_ace_step_models, ensure_models,
+    get_backend_fingerprint, get_backend_version, load_backend, load_sessions_with_tuning, Backend,
+    GenerateDispatchParams, LoadedModels, SessionTuning,
+};
+use lofi_daemon::output::TemplateValues;
+use lofi_daemon::rpc::{run_server, schema_document, BackendStatus, ServerState};
+use lofi_daemon::types::{
+    compute_track_id, read_manifest, set_redact_prompts, DisplayPrompt, StepsParam, TrackVariant,
+};
+use lofi_daemon::watch::{open_with_default_player, read_prompt_file, watch_prompt_file};
+use serde::Serialize;
+
+/// Exit status when the reader of `--output -` closes the pipe early,
+/// matching what shells report for a process killed by SIGPIPE.
+const BROKEN_PIPE_EXIT_CODE: i32 = 141;
+
+/// Exit status for a second Ctrl+C with `--keep-partial`, matching SIGINT.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {}", e);
+    let cli = Cli::parse_args();
+    if let Err(e) = run(&cli) {
+        if cli.json {
+            print_json_report(&cli, &CliErrorReport::from_error(&e));
+        } else {
+            print_error(&e);
+        }
         std::process::exit(1);
     }
 }
 
-fn run() -> Result<()> {
-    let cli = Cli::parse_args();
+/// Prints an error with the same fields as the `generation_error` notification.
+fn print_error(e: &DaemonError) {
+    eprintln!("Error: [{}] {}", e.code, e.message);
+    if let Some(phase) = e.phase {
+        eprintln!("  Phase: {}", phase);
+    }
+    if let Some(details) = e.details() {
+        eprintln!("  Details: {}", details);
+    }
+    eprintln!("  Retriable: {}", if e.code.is_retriable() { "yes" } else { "no" });
+    eprintln!("  Recovery: {}", e.code.recovery_hint());
+}
 
-    if cli.is_daemon_mode() {
-        run_daemon_mode()
+fn run(cli: &Cli) -> Result<()> {
+    if let Some(redact) = redact_prompts_from_env() {
+        set_redact_prompts(redact);
+    }
+
+    // Command-line flags win over LOFI_* variables, which win over the config
+    // file (read in daemon mode) and the defaults
+    let (base_config, config_path) = if cli.is_daemon_mode() {
+        load_daemon_config()
+    } else {
+        (DaemonConfig::from_env(), None)
+    };
+    let (cli, config) = cli.resolve(base_config);
+    let cli = &cli;
+
+    if let Some(path) = cli.dump_schema.as_deref() {
+        dump_schema(path);
+        Ok(())
+    } else if cli.prune_orphans {
+        run_prune_orphans(cli.dry_run)
+    } else if cli.version_info {
+        run_version_info(cli.json)
+    } else if cli.is_daemon_mode() {
+        run_daemon_mode(cli, config, config_path)
+    } else if let Some(prompt_file) = cli.watch.as_deref() {
+        run_watch_mode(cli, &config, prompt_file)
+    } else if let Some(manifest_path) = cli.manifest.as_deref() {
+        run_manifest_mode(cli, &config, manifest_path)
     } else if cli.is_cli_mode() {
-        run_cli_mode(&cli)
+        run_cli_mode(cli, &config)
     } else {
         print_usage();
         Ok(())
     }
 }
 
+/// Writes the JSON Schema of the JSON-RPC protocol to `path`.
+fn dump_schema(path: &Path) {
+    let schema = serde_json::to_string_pretty(&schema_document()).unwrap();
+    if let Err(e) = std::fs::write(path, schema + "\n") {
+        Cli::exit_with_error(format!("Failed to write schema to {}: {}", path.display(), e));
+    }
+    eprintln!("Wrote JSON-RPC schema to {}", path.display());
+}
+
 /// Runs the CLI mode for music generation.
-fn run_cli_mode(cli: &Cli) -> Result<()> {
+///
+/// `config` holds the settings resolved from the environment and the flags.
+fn run_cli_mode(cli: &Cli, config: &DaemonConfig) -> Result<()> {
+    let cli = &clamped(cli);
+    let config = &cli.apply_to_config(config.clone());
+    let validation = cli.validate();
+    if cli.dry_run {
+        match validation {
+            Ok(()) => println!("Arguments are valid"),
+            Err(e) => Cli::exit_with_error(e),
+        }
+        return Ok(());
+    }
+    if let Err(e) = validation {
+        Cli::exit_with_error(e);
+    }
+
     let prompt = cli.prompt.as_ref().expect("Prompt required in CLI mode");
-    let output_path = cli.output_path();
+    let (backend, seed) = match cli.backend {
+        BackendArg::Musicgen => (Backend::MusicGen, cli.seed.unwrap_or_else(rand::random)),
+        BackendArg::AceStep => (Backend::AceStep, cli.seed.unwrap_or(42)),
+    };
+    let output_path = render_output_path(cli, config, prompt, backend, seed, cli.overwrite)?;
+
+    let outcome = match backend {
+        Backend::MusicGen => run_musicgen_cli(cli, config, prompt, seed, &output_path)?,
+        Backend::AceStep => run_ace_step_cli(cli, config, prompt, seed, &output_path)?,
+    };
+
+    print_repro(config);
+    if cli.json {
+        let report = CliResultReport {
+            track_id: cli_track_id(cli, config, prompt, backend, seed),
+            path: output_path.to_string_lossy().to_string(),
+            duration_sec: outcome.duration_sec,
+            sample_rate: backend.sample_rate(),
+            prompt: DisplayPrompt::new(prompt).to_string(),
+            seed: outcome.seed,
+            generation_time_sec: outcome.generation_time_sec,
+            backend: backend.as_str().to_string(),
+            cancelled: outcome.cancelled,
+            config_env: config.to_env_exports().into_iter().collect(),
+        };
+        print_json_report(cli, &report);
+    }
+    Ok(())
+}
+
+/// Prints a `--json` report as one line on stdout, or on stderr when stdout
+/// carries the WAV.
+fn print_json_report<T: Serialize>(cli: &Cli, report: &T) {
+    let line = serde_json::to_string(report).unwrap();
+    if cli.output_is_stdout() {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// What a CLI generation produced, for the `--json` report.
+struct CliOutcome {
+    /// Seed used, after any MusicGen seed retries.
+    seed: u64,
+    /// Duration of the written audio in seconds.
+    duration_sec: f32,
+    /// Wall-clock time for generation.
+    generation_time_sec: f32,
+    /// Whether generation was stopped early with `--keep-partial`.
+    cancelled: bool,
+}
+
+/// Regenerates the track described by a manifest file in CLI mode.
+///
+/// Warns about unknown manifest fields and about differences from the
+/// exporter's models, which mean the result will not be bit-identical.
+fn run_manifest_mode(cli: &Cli, config: &DaemonConfig, manifest_path: &Path) -> Result<()> {
+    let (manifest, mut warnings) =
+        read_manifest(manifest_path).unwrap_or_else(|e| Cli::exit_with_error(e));
+    let cli = cli.with_manifest(&manifest).unwrap_or_else(|e| Cli::exit_with_error(e));
+
+    let config = cli.apply_to_config(config.clone());
+    warnings.extend(manifest.compatibility_warnings(
+        get_backend_version(manifest.backend, &config).as_deref(),
+        get_backend_fingerprint(manifest.backend, &config).as_deref(),
+    ));
+    if let Some(rate) = manifest.output_sample_rate {
+        warnings.push(format!(
+            "output_sample_rate {} is ignored; CLI mode writes the backend's native rate",
+            rate
+        ));
+    }
+    for warning in &warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    run_cli_mode(&cli, &config)
+}
+
+/// Expands `--output` for a generation of `prompt` with `seed`.
+///
+/// The track ID matches the one the daemon would assign to the same request.
+fn render_output_path(
+    cli: &Cli,
+    config: &DaemonConfig,
+    prompt: &str,
+    backend: Backend,
+    seed: u64,
+    overwrite: bool,
+) -> Result<PathBuf> {
+    let track_id = cli_track_id(cli, config, prompt, backend, seed);
+    let values = TemplateValues {
+        prompt,
+        redact_prompt: config.redact_prompts,
+        seed,
+        duration_sec: cli.duration,
+        backend,
+        track_id: &track_id,
+        time: SystemTime::now(),
+    };
+    cli.render_output_path(&values, overwrite)
+}
+
+/// Returns the track ID the daemon would assign to the same request.
+///
+/// Covers the ACE-Step steps, reverb and silence trimming the same way as
+/// `GenerateParams::track_id`; CLI mode always writes the native sample rate.
+fn cli_track_id(cli: &Cli, config: &DaemonConfig, prompt: &str, backend: Backend, seed: u64) -> String {
+    let model_version = get_backend_version(backend, config).unwrap_or_default();
+    let track_id = compute_track_id(backend, prompt, seed, cli.duration as f32, &model_version);
+    let reverb = cli.reverb_settings();
+    let trim = cli.trim_silence.then(|| config.silence_trim());
+    let variant = TrackVariant {
+        inference_steps: cli.is_ace_step().then(|| cli.inference_steps()),
+        output_sample_rate: None,
+        reverb: reverb.as_ref(),
+        trim: trim.as_ref(),
+    };
+    variant.track_id(&track_id, backend)
+}
+
+/// Prints the environment variables that reproduce the configuration used.
+///
+/// Settings at their defaults are omitted.
+fn print_repro(config: &DaemonConfig) {
+    let exports = config.to_env_exports();
+    eprintln!();
+    if exports.is_empty() {
+        eprintln!("# Repro: default configuration");
+        return;
+    }
+    eprintln!("# Repro:");
+    for (name, value) in exports {
+        eprintln!("export {}={}", name, shell_quote(&value));
+    }
+}
 
-    match cli.backend {
-        BackendArg::Musicgen => run_musicgen_cli(cli, prompt, &output_path),
-        BackendArg::AceStep => run_ace_step_cli(cli, prompt, &output_path),
+/// Quotes a value for a POSIX shell if it contains anything but safe characters.
+fn shell_quote(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=,+@".contains(c);
+    if !value.is_empty() && value.chars().all(safe) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
     }
 }
 
+/// Returns the arguments with `--param-strictness clamp` adjustments applied,
+/// printing each adjustment.
+fn clamped(cli: &Cli) -> Cli {
+    let mut cli = cli.clone();
+    for adjustment in cli.clamp_params() {
+        eprintln!("Adjusted {}", adjustment);
+    }
+    cli
+}
+
 /// Runs MusicGen generation in CLI mode.
-fn run_musicgen_cli(cli: &Cli, prompt: &str, output_path: &std::path::Path) -> Result<()> {
-    let model_dir = cli.model_directory();
+fn run_musicgen_cli(
+    cli: &Cli,
+    config: &DaemonConfig,
+    prompt: &str,
+    seed: u64,
+    output_path: &std::path::Path,
+) -> Result<CliOutcome> {
+    let model_dir = config.effective_model_path();
 
     eprintln!("=== lofi-daemon MusicGen CLI ===");
     eprintln!("Backend: MusicGen (32kHz, 5-30s)");
-    eprintln!("Prompt: \"{}\"", prompt);
+    eprintln!("Prompt: \"{}\"", DisplayPrompt::new(prompt));
     eprintln!("Duration: {}s", cli.duration);
     eprintln!("Output: {}", output_path.display());
     eprintln!("Model directory: {}", model_dir.display());
-    if let Some(seed) = cli.seed {
-        eprintln!("Seed: {}", seed);
-    }
+    eprintln!("Device: {}", config.device.as_str());
+    eprintln!("Seed: {}", seed);
     eprintln!();
 
-    // Validate duration for MusicGen
-    if cli.duration > 30 {
-        eprintln!("Warning: MusicGen supports up to 30s. Consider using --backend ace_step for longer audio.");
-    }
-
     // Ensure models are downloaded
     eprintln!("Checking model files...");
     ensure_models(&model_dir)?;
     eprintln!();
 
+    // Load models
+    let mut models = load_sessions_with_tuning(
+        &model_dir,
+        config.tokenizer_override(Backend::MusicGen),
+        config.device,
+        config.threads,
+        &SessionTuning::from_config(config),
+    )?;
+    let cancel = install_cancel_handler(cli);
+    let timings = cli.profile.then(Timings::new);
+
     // Start timing
     let start_time = Instant::now();
 
     // Generate audio with progress callback
-    let samples = generate_with_progress(
+    let (mut samples, generated_seed) = generate_with_seed_retry_diagnosed(
+        &mut models,
         prompt,
-        cli.duration,
-        cli.seed,
-        &model_dir,
+        cli.tokens_to_generate(),
+        seed,
+        DEFAULT_SEED_RETRIES,
+        None,
+        cli.token_dump_path().as_deref(),
+        cancel.as_ref(),
+        timings.as_ref(),
         |current, total| {
             let _ = (current, total);
         },
@@ -90,8 +352,16 @@ fn run_musicgen_cli(cli: &Cli, prompt: &str, output_path: &std::path::Path) -> R
     let generation_time_sec = generation_time.as_secs_f32();
 
     eprintln!();
-    eprintln!("Generation complete!");
+    let cancelled = cancel.as_ref().is_some_and(CancelToken::is_cancelled);
+    if cancelled {
+        eprintln!("Generation cancelled, saving partial result");
+    } else {
+        eprintln!("Generation complete!");
+    }
     eprintln!("  Time: {:.2}s", generation_time_sec);
+    if generated_seed != seed {
+        eprintln!("  Seed: {}", generated_seed);
+    }
     eprintln!("  Samples: {}", samples.len());
     eprintln!(
         "  Audio duration: {:.2}s",
@@ -100,30 +370,41 @@ fn run_musicgen_cli(cli: &Cli, prompt: &str, output_path: &std::path::Path) -> R
     eprintln!();
 
     // Write to WAV file (32kHz for MusicGen)
+    apply_cli_trim(cli, config, &mut samples, 32000);
+    apply_cli_reverb(cli, &mut samples, 32000);
     eprintln!("Writing WAV file...");
-    write_wav(&samples, output_path, 32000)?;
-    eprintln!("Saved to: {}", output_path.display());
+    write_timed(cli, &samples, output_path, 32000, timings.as_ref())?;
+    print_profile(timings.as_ref(), start_time.elapsed());
 
-    Ok(())
+    Ok(CliOutcome {
+        seed: generated_seed,
+        duration_sec: samples.len() as f32 / 32000.0,
+        generation_time_sec,
+        cancelled,
+    })
 }
 
 /// Runs ACE-Step generation in CLI mode.
-fn run_ace_step_cli(cli: &Cli, prompt: &str, output_path: &std::path::Path) -> Result<()> {
-    let model_dir = cli.ace_step_model_directory();
-    let seed = cli.seed.unwrap_or(42);
+fn run_ace_step_cli(
+    cli: &Cli,
+    config: &DaemonConfig,
+    prompt: &str,
+    seed: u64,
+    output_path: &std::path::Path,
+) -> Result<CliOutcome> {
+    let model_dir = config.effective_ace_step_model_path();
 
     // Convert scheduler arg to string
-    let scheduler_str = match cli.scheduler {
-        SchedulerArg::Euler => "euler",
-        SchedulerArg::Heun => "heun",
-        SchedulerArg::Pingpong => "pingpong",
-    };
+    let scheduler_str = scheduler_name(cli);
 
     eprintln!("=== lofi-daemon ACE-Step CLI ===");
     eprintln!("Backend: ACE-Step (48kHz, 5-240s)");
-    eprintln!("Prompt: \"{}\"", prompt);
+    eprintln!("Prompt: \"{}\"", DisplayPrompt::new(prompt));
     eprintln!("Duration: {}s", cli.duration);
-    eprintln!("Steps: {}", cli.steps);
+    match cli.steps {
+        StepsParam::Auto => eprintln!("Steps: {} (auto)", cli.inference_steps()),
+        StepsParam::Fixed(steps) => eprintln!("Steps: {}", steps),
+    }
     eprintln!("Scheduler: {}", scheduler_str);
     eprintln!("Guidance: {:.1}", cli.guidance);
     eprintln!("Seed: {}", seed);
@@ -137,24 +418,46 @@ fn run_ace_step_cli(cli: &Cli, prompt: &str, output_path: &std::path::Path) -> R
     eprintln!();
 
     // Load models
-    let config = DaemonConfig::default();
-    let mut models = AceStepModels::load(&model_dir, &config)?;
+    let mut models = AceStepModels::load(&model_dir, config)?;
+    let cancel = install_cancel_handler(cli);
+    let timings = cli.profile.then(Timings::new);
 
     // Start timing
     let start_time = Instant::now();
 
     // Generate audio
-    let samples = generate_ace_step(
-        &mut models,
-        prompt,
-        cli.duration as f32,
+    let params = GenerationParams {
+        prompt: prompt.to_string(),
+        prompt_blend: None,
+        duration_sec: cli.duration as f32,
         seed,
-        cli.steps,
-        scheduler_str,
-        cli.guidance,
+        inference_steps: cli.inference_steps(),
+        scheduler: SchedulerType::parse(scheduler_str).unwrap_or(SchedulerType::Euler),
+        guidance_scale: cli.guidance,
+        cancel: cancel.clone(),
+        timings: timings.clone(),
+        on_audio_progress: None,
+    };
+    // The estimate includes decoding, which follows the last step
+    let tracker = RefCell::new(
+        ProgressTracker::for_steps(cli.duration, cli.inference_steps() as usize)
+            .with_phases(ACE_STEP_PHASES),
+    );
+    let mut samples = generate_ace_step_params_with_progress(
+        &mut models,
+        params,
         |step, total| {
-            if step % 5 == 0 || step == total {
+            let mut tracker = tracker.borrow_mut();
+            tracker.record(step, Instant::now());
+            if step == total {
                 eprintln!("Progress: {}/{} steps", step, total);
+            } else if step % 5 == 0 {
+                eprintln!(
+                    "Progress: {}/{} steps, ~{:.0}s left",
+                    step,
+                    total,
+                    tracker.eta_seconds()
+                );
             }
         },
     )?;
@@ -164,7 +467,12 @@ fn run_ace_step_cli(cli: &Cli, prompt: &str, output_path: &std::path::Path) -> R
     let generation_time_sec = generation_time.as_secs_f32();
 
     eprintln!();
-    eprintln!("Generation complete!");
+    let cancelled = cancel.as_ref().is_some_and(CancelToken::is_cancelled);
+    if cancelled {
+        eprintln!("Generation cancelled, saving partial result");
+    } else {
+        eprintln!("Generation complete!");
+    }
     eprintln!("  Time: {:.2}s", generation_time_sec);
     eprintln!("  Samples: {}", samples.len());
     eprintln!(
@@ -174,46 +482,428 @@ fn run_ace_step_cli(cli: &Cli, prompt: &str, output_path: &std::path::Path) -> R
     eprintln!();
 
     // Write to WAV file (48kHz for ACE-Step)
+    apply_cli_trim(cli, config, &mut samples, 48000);
+    apply_cli_reverb(cli, &mut samples, 48000);
     eprintln!("Writing WAV file...");
-    write_wav(&samples, output_path, 48000)?;
+    write_timed(cli, &samples, output_path, 48000, timings.as_ref())?;
+    print_profile(timings.as_ref(), start_time.elapsed());
+
+    Ok(CliOutcome {
+        seed,
+        duration_sec: samples.len() as f32 / 48000.0,
+        generation_time_sec,
+        cancelled,
+    })
+}
+
+/// Returns a cancel token triggered by Ctrl+C if `--keep-partial` was given.
+///
+/// The first Ctrl+C stops generation so the audio produced so far is written;
+/// a second one exits immediately.
+fn install_cancel_handler(cli: &Cli) -> Option<CancelToken> {
+    if !cli.keep_partial {
+        return None;
+    }
+
+    let cancel = CancelToken::new().with_keep_partial(true);
+    let handler = cancel.clone();
+    let result = ctrlc::set_handler(move || {
+        if handler.is_cancelled() {
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+        eprintln!();
+        eprintln!("Stopping after the current step (Ctrl+C again to abort)...");
+        handler.cancel();
+    });
+    if let Err(e) = result {
+        eprintln!("Warning: failed to install Ctrl+C handler: {}", e);
+    }
+    Some(cancel)
+}
+
+/// Trims near-silent padding with the configured settings if `--trim-silence`
+/// was given.
+fn apply_cli_trim(cli: &Cli, config: &DaemonConfig, samples: &mut Vec<f32>, sample_rate: u32) {
+    if cli.trim_silence {
+        let removed = config.silence_trim().apply(samples, sample_rate);
+        eprintln!(
+            "Trimmed {:.2}s of silence",
+            removed as f32 / sample_rate as f32
+        );
+    }
+}
+
+/// Applies the `--reverb` preset, if given.
+fn apply_cli_reverb(cli: &Cli, samples: &mut [f32], sample_rate: u32) {
+    if let Some(reverb) = cli.reverb_settings() {
+        eprintln!("Applying {}", reverb.label());
+        reverb.apply(samples, sample_rate);
+    }
+}
+
+/// Writes the output like [`write_output`], recording the time taken as the
+/// `write` phase if profiling.
+fn write_timed(
+    cli: &Cli,
+    samples: &[f32],
+    output_path: &Path,
+    sample_rate: u32,
+    timings: Option<&Timings>,
+) -> Result<()> {
+    match timings {
+        Some(timings) => timings.time(GenerationPhase::Write, || {
+            write_output(cli, samples, output_path, sample_rate)
+        }),
+        None => write_output(cli, samples, output_path, sample_rate),
+    }
+}
+
+/// Prints the `--profile` timing table, if profiling.
+fn print_profile(timings: Option<&Timings>, wall: std::time::Duration) {
+    if let Some(timings) = timings {
+        eprintln!();
+        eprintln!("Profile:");
+        eprintln!("{}", timings.format_table(wall));
+    }
+}
+
+/// Writes the generated WAV to `output_path`, or to stdout for `--output -`.
+///
+/// Stdout receives the complete WAV in a single write; progress output stays
+/// on stderr. If the reader exits early the process exits without an error
+/// report.
+fn write_output(cli: &Cli, samples: &[f32], output_path: &Path, sample_rate: u32) -> Result<()> {
+    if !cli.output_is_stdout() {
+        write_wav(samples, output_path, sample_rate)?;
+        eprintln!("Saved to: {}", output_path.display());
+        return Ok(());
+    }
+
+    let wav = write_wav_to_buffer(samples, sample_rate)?;
+    let mut stdout = std::io::stdout().lock();
+    match stdout.write_all(&wav).and_then(|_| stdout.flush()) {
+        Ok(()) => {
+            eprintln!("Wrote {} bytes to stdout", wav.len());
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {
+            eprintln!("Output pipe closed before the WAV was fully written");
+            std::process::exit(BROKEN_PIPE_EXIT_CODE);
+        }
+        Err(e) => Err(DaemonError::with_source(
+            ErrorCode::ModelInferenceFailed,
+            "Failed to write WAV to stdout",
+            e,
+        )
+        .with_phase(GenerationPhase::Write)),
+    }
+}
+
+/// Returns the ACE-Step scheduler name selected on the command line.
+fn scheduler_name(cli: &Cli) -> &'static str {
+    cli.scheduler.as_str()
+}
+
+/// Runs watch mode: generates from a prompt file and regenerates on every change.
+///
+/// Models are loaded once and reused across regenerations. Each regeneration
+/// uses the seed after the one the previous track was generated with, so a
+/// retried seed is not repeated. Exits on Ctrl+C.
+fn run_watch_mode(cli: &Cli, config: &DaemonConfig, prompt_file: &Path) -> Result<()> {
+    let cli = &clamped(cli);
+    if let Err(e) = cli.validate() {
+        Cli::exit_with_error(e);
+    }
+    let config = &cli.apply_to_config(config.clone());
+
+    let (backend, model_dir) = match cli.backend {
+        BackendArg::Musicgen => (Backend::MusicGen, config.effective_model_path()),
+        BackendArg::AceStep => (Backend::AceStep, config.effective_ace_step_model_path()),
+    };
+    let output_template = cli.output_path();
+    let seed = cli.seed.unwrap_or(42);
+
+    eprintln!("=== lofi-daemon watch mode ===");
+    eprintln!("Backend: {}", backend);
+    eprintln!("Prompt file: {}", prompt_file.display());
+    eprintln!("Duration: {}s", cli.duration);
+    eprintln!("Output: {}", output_template.display());
+    eprintln!("Model directory: {}", model_dir.display());
+    eprintln!("Press Ctrl+C to stop.");
+    eprintln!();
+
+    eprintln!("Checking model files...");
+    match backend {
+        Backend::MusicGen => ensure_models(&model_dir)?,
+        Backend::AceStep => ensure_ace_step_models(&model_dir)?,
+    }
+
+    eprintln!("Loading models...");
+    let mut models = load_backend(backend, &model_dir, config)?;
+    eprintln!();
+
+    let mut seed = generate_from_prompt_file(cli, config, &mut models, prompt_file, seed)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_handler = Arc::clone(&stop);
+    if let Err(e) = ctrlc::set_handler(move || stop_handler.store(true, Ordering::SeqCst)) {
+        eprintln!("Warning: failed to install Ctrl+C handler: {}", e);
+    }
+
+    watch_prompt_file(prompt_file, stop, || {
+        let next = seed.wrapping_add(1);
+        seed = generate_from_prompt_file(cli, config, &mut models, prompt_file, next)?;
+        Ok(())
+    })?;
+
+    eprintln!("Stopped watching {}", prompt_file.display());
+    Ok(())
+}
+
+/// Generates a track from the current contents of a prompt file, writes it,
+/// and opens it with the default player.
+///
+/// An `--output` template is expanded per regeneration; a path that stays the
+/// same is overwritten each time. Like CLI mode, the path and track ID come
+/// from the requested seed, and a seed MusicGen retried is reported. Returns
+/// the seed used.
+fn generate_from_prompt_file(
+    cli: &Cli,
+    config: &DaemonConfig,
+    models: &mut LoadedModels,
+    prompt_file: &Path,
+    seed: u64,
+) -> Result<u64> {
+    let prompt = read_prompt_file(prompt_file)?;
+    let backend = models.backend().unwrap_or_default();
+
+    eprintln!("Prompt: \"{}\"", DisplayPrompt::new(&prompt));
+    eprintln!("Seed: {}", seed);
+
+    let params = GenerateDispatchParams::new(prompt, cli.duration, seed, backend).with_ace_step_params(
+        Some(cli.inference_steps()),
+        Some(scheduler_name(cli).to_string()),
+        Some(cli.guidance),
+    )
+    .with_token_dump(cli.token_dump_path());
+
+    let start_time = Instant::now();
+    let (mut samples, generated_seed) = models.generate(&params, |_, _| {})?;
+    eprintln!("Generated in {:.2}s", start_time.elapsed().as_secs_f32());
+    if generated_seed != seed {
+        eprintln!("Seed: {} (retried from {})", generated_seed, seed);
+    }
+    apply_cli_trim(cli, config, &mut samples, backend.sample_rate());
+    apply_cli_reverb(cli, &mut samples, backend.sample_rate());
+
+    let output_path = render_output_path(cli, config, &params.prompt, backend, seed, true)?;
+    write_wav(&samples, &output_path, backend.sample_rate())?;
     eprintln!("Saved to: {}", output_path.display());
 
+    if let Err(e) = open_with_default_player(&output_path) {
+        eprintln!("Warning: failed to open player: {}", e);
+    }
+    eprintln!();
+
+    Ok(generated_seed)
+}
+
+/// Loads the daemon configuration and the config file path to persist to.
+///
+/// A missing config file is created on the first persistent change; an
+/// unreadable one is left untouched so it is never overwritten with defaults.
+fn load_daemon_config() -> (DaemonConfig, Option<PathBuf>) {
+    let (config, config_path) = match config_file_path() {
+        Some(path) if path.exists() => match DaemonConfig::load(&path) {
+            Ok(config) => (config, Some(path)),
+            Err(e) => {
+                eprintln!("{}; using defaults", e);
+                (DaemonConfig::default(), None)
+            }
+        },
+        path => (DaemonConfig::default(), path),
+    };
+    (config.with_env(), config_path)
+}
+
+/// Deletes WAV files in the daemon's cache directory that are no longer cached.
+///
+/// The cache is restored from its sidecars the same way the daemon does at
+/// startup, so the files removed are those a restarted daemon would not know.
+fn run_prune_orphans(dry_run: bool) -> Result<()> {
+    let (config, _) = load_daemon_config();
+    let cache_dir = config.effective_cache_path();
+    let mut state = ServerState::new(config);
+    state.cache.load_sidecars(&cache_dir)?;
+
+    let report = state.prune_orphans(dry_run)?;
+    for path in &report.files {
+        println!("{}", path.display());
+    }
+    eprintln!(
+        "{} {} orphaned tracks ({:.1} MB) in {}",
+        if dry_run { "Found" } else { "Deleted" },
+        report.files.len(),
+        report.bytes_freed as f64 / (1024.0 * 1024.0),
+        cache_dir.display()
+    );
     Ok(())
 }
 
-/// Runs the daemon mode (JSON-RPC server).
-fn run_daemon_mode() -> Result<()> {
-    use lofi_daemon::models::{check_backend_available, Backend};
+/// Prints build, runtime and backend details for bug reports.
+///
+/// Paths come from the same configuration the daemon loads, so the output
+/// describes what `--daemon` would use.
+fn run_version_info(json: bool) -> Result<()> {
+    let (config, _) = load_daemon_config();
+    let backends = [Backend::MusicGen, Backend::AceStep]
+        .into_iter()
+        .map(|backend| {
+            let model_path = match backend {
+                Backend::MusicGen => config.effective_model_path(),
+                Backend::AceStep => config.effective_ace_step_model_path(),
+            };
+            let installed =
+                check_backend_available(backend, &model_path, config.tokenizer_override(backend));
+            BackendVersionInfo {
+                backend: backend.as_str().to_string(),
+                installed,
+                model_version: installed.then(|| get_backend_version(backend, &config)).flatten(),
+                model_path,
+                min_duration_sec: backend.min_duration_sec(),
+                max_duration_sec: backend.max_duration_sec(),
+                sample_rate: backend.sample_rate(),
+            }
+        })
+        .collect();
 
+    let info = VersionInfo {
+        daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+        rustc_version: env!("LOFI_RUSTC_VERSION").to_string(),
+        ort_api_version: format!("1.{}", ort::MINOR_VERSION),
+        ort_build_info: ort::info().to_string(),
+        execution_providers: detect_available_providers()
+            .iter()
+            .map(|p| p.name.to_string())
+            .collect(),
+        cache_path: config.effective_cache_path(),
+        library_path: config.effective_library_path(),
+        backends,
+        ace_step: AceStepCapabilities::current(),
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info).unwrap());
+    } else {
+        println!("{}", info.to_table());
+    }
+    Ok(())
+}
+
+/// Runs the daemon mode (JSON-RPC server).
+///
+/// With `--metrics-port`, `GET /health` is served on that port on all
+/// interfaces so container probes can reach it. With `--metrics-addr` and
+/// the `metrics-http` feature, `GET /metrics` is served on that address.
+///
+/// The daemon holds the instance lock in the cache directory while it runs.
+/// If another instance holds it, this exits with an error naming that
+/// instance, or with `--allow-multiple` runs with the track cache read-only.
+fn run_daemon_mode(
+    cli: &Cli,
+    config: DaemonConfig,
+    config_path: Option<PathBuf>,
+) -> Result<()> {
     eprintln!("=== lofi-daemon JSON-RPC Server ===");
     eprintln!("Reading from stdin, writing to stdout.");
     eprintln!("Send JSON-RPC requests to control the daemon.");
     eprintln!();
 
-    let config = DaemonConfig::default();
-    let state = ServerState::new(config.clone());
+    let cache_dir = config.effective_cache_path();
+    let instance_lock = match acquire_instance_lock(&cache_dir)? {
+        LockAttempt::Acquired(lock) => Some(lock),
+        LockAttempt::Held(owner) => {
+            let holder = match owner {
+                Some(owner) => format!(
+                    "PID {}, started {}s ago",
+                    owner.pid,
+                    owner.age_sec()
+                ),
+                None => "PID unknown".to_string(),
+            };
+            if !cli.allow_multiple {
+                eprintln!(
+                    "Error: another lofi-daemon instance ({}) is using the cache at {}",
+                    holder,
+                    cache_dir.display()
+                );
+                eprintln!(
+                    "Stop it first, or pass --allow-multiple to start with a read-only cache."
+                );
+                std::process::exit(1);
+            }
+            eprintln!(
+                "Another lofi-daemon instance ({}) holds the cache lock; the track cache is read-only",
+                holder
+            );
+            None
+        }
+    };
+
+    let mut state = ServerState::new(config.clone());
+    if let Some(path) = config_path {
+        state = state.with_config_path(path);
+    }
+    if instance_lock.is_none() {
+        state = state.with_read_only_cache();
+    }
 
-    // Detect available backends at startup
-    // Note: BackendStatus starts as NotInstalled by default
-    // We check if model files exist and update status accordingly
-    let musicgen_available = check_backend_available(Backend::MusicGen, &config.effective_model_path());
-    let ace_step_available = check_backend_available(Backend::AceStep, &config.effective_ace_step_model_path());
+    // Restore cached tracks and remove WAVs left behind by interrupted generations
+    match state.cache.load_sidecars(&cache_dir) {
+        Ok(loaded) if loaded > 0 => eprintln!("Loaded {} cached tracks", loaded),
+        Ok(_) => {}
+        Err(e) => eprintln!("{}", e),
+    }
+    match Ratings::load(&cache_dir) {
+        Ok(ratings) => state.ratings = ratings,
+        Err(e) => eprintln!("{}", e),
+    }
+    if !state.is_cache_read_only() {
+        match state.cache.save_and_evict_orphaned(&cache_dir) {
+            Ok(deleted) if deleted > 0 => eprintln!("Deleted {} orphaned tracks", deleted),
+            Ok(_) => {}
+            Err(e) => eprintln!("{}", e),
+        }
+    }
 
-    // If models are available (downloaded), status becomes "ready to load"
-    // which we represent as NotInstalled until they're actually loaded
-    if musicgen_available {
+    // Backends whose model files are on disk start as ready (loaded on demand)
+    if state.get_backend_status(Backend::MusicGen) == BackendStatus::Ready {
         eprintln!("MusicGen backend: available (models found, not loaded)");
     } else {
         eprintln!("MusicGen backend: not installed (download models first)");
     }
 
-    if ace_step_available {
+    if state.get_backend_status(Backend::AceStep) == BackendStatus::Ready {
         eprintln!("ACE-Step backend: available (models found, not loaded)");
     } else {
         eprintln!("ACE-Step backend: not installed (download models first)");
     }
 
     eprintln!("Default backend: {}", config.default_backend.as_str());
+
+    if let Some(port) = cli.metrics_port {
+        match state.serve_health(("0.0.0.0", port)) {
+            Ok(addr) => eprintln!("Health endpoint: http://{}/health", addr),
+            Err(e) => eprintln!("Failed to start health endpoint on port {}: {}", port, e),
+        }
+    }
+    #[cfg(feature = "metrics-http")]
+    if let Some(addr) = cli.metrics_addr {
+        match state.serve_metrics(addr) {
+            Ok(addr) => eprintln!("Metrics endpoint: http://{}/metrics", addr),
+            Err(e) => eprintln!("Failed to start metrics endpoint on {}: {}", addr, e),
+        }
+    }
     eprintln!();
 
     run_server(state)
@@ -230,9 +920,51 @@ fn print_usage() {
     eprintln!("  ACE-Step (5-240s at 48kHz):");
     eprintln!("    lofi-daemon --backend ace-step --prompt \"lofi beats\" --duration 60 --output long.wav");
     eprintln!();
+    eprintln!("  Pipe to another tool (WAV on stdout):");
+    eprintln!("    lofi-daemon --prompt \"lofi beats\" --duration 10 --output - | ffplay -");
+    eprintln!();
+    eprintln!("  Keep the audio generated so far when interrupted with Ctrl+C:");
+    eprintln!("    lofi-daemon --backend ace-step --prompt \"lofi beats\" --duration 240 --keep-partial");
+    eprintln!();
+    eprintln!("  Save the MusicGen token stream to compare runs:");
+    eprintln!("    lofi-daemon --prompt \"lofi beats\" --seed 42 --dump-tokens tokens.csv");
+    eprintln!();
+    eprintln!("  Check the arguments without generating:");
+    eprintln!("    lofi-daemon --prompt \"lofi beats\" --duration 60 --dry-run");
+    eprintln!();
+    eprintln!("  Add a touch of reverb (room, hall or lofi-tape; --reverb-wet sets the mix):");
+    eprintln!("    lofi-daemon --prompt \"lofi beats\" --reverb lofi-tape --reverb-wet 0.2");
+    eprintln!();
+    eprintln!("  Cut near-silent padding from the start and end:");
+    eprintln!("    lofi-daemon --prompt \"lofi beats\" --trim-silence");
+    eprintln!();
+    eprintln!("  Report the result or failure as one JSON line on stdout for scripts:");
+    eprintln!("    lofi-daemon --prompt \"lofi beats\" --json");
+    eprintln!();
+    eprintln!("  Use the nearest valid --steps/--guidance instead of failing:");
+    eprintln!("    lofi-daemon --backend ace-step --prompt \"lofi beats\" --guidance 35 --param-strictness clamp");
+    eprintln!();
+    eprintln!("  Regenerate a track from a manifest exported by the daemon:");
+    eprintln!("    lofi-daemon --manifest recipe.json --output recipe.wav");
+    eprintln!();
+    eprintln!("  Watch mode (regenerate when the prompt file changes):");
+    eprintln!("    lofi-daemon --watch prompt.txt --duration 10 --output watch.wav");
+    eprintln!();
+    eprintln!("  Name the output from the prompt, seed and date (directories are created):");
+    eprintln!("    lofi-daemon --prompt \"rain lofi\" --output \"lofi/%Y-%m-%d/{{prompt}}-{{seed}}.wav\"");
+    eprintln!();
+    eprintln!("  Delete cached WAV files the daemon no longer tracks (--dry-run to list them):");
+    eprintln!("    lofi-daemon --prune-orphans");
+    eprintln!();
+    eprintln!("  Print versions and backend status for a bug report:");
+    eprintln!("    lofi-daemon --version-info");
+    eprintln!();
     eprintln!("  Daemon mode (JSON-RPC server):");
     eprintln!("    lofi-daemon --daemon");
     eprintln!();
+    eprintln!("  Write the JSON Schema of the JSON-RPC protocol:");
+    eprintln!("    lofi-daemon --dump-schema schema.json");
+    eprintln!();
     eprintln!("Run 'lofi-daemon --help' for full options.");
 }
 
@@ -244,4 +976,58 @@ mod tests {
     fn print_usage_doesnt_panic() {
         print_usage();
     }
+
+    #[test]
+    fn daemon_flags_win_over_env_which_wins_over_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.json");
+        let file = serde_json::json!({
+            "model_path": "/file/musicgen",
+            "cache_path": "/file/cache",
+            "max_queue_size": 3,
+        });
+        std::fs::write(&path, file.to_string()).unwrap();
+
+        let mut config = DaemonConfig::load(&path).unwrap();
+        config.apply_env_vars(|name| (name == "LOFI_CACHE_PATH").then(|| "/env/cache".to_string()));
+        let args = ["lofi-daemon", "--daemon", "--model-dir", "/flag/musicgen"];
+        let (_, config) = Cli::try_parse_args_from(args).unwrap().resolve(config);
+
+        assert_eq!(config.model_path, Some(PathBuf::from("/flag/musicgen")));
+        assert_eq!(config.cache_path, Some(PathBuf::from("/env/cache")));
+        assert_eq!(config.max_queue_size, 3);
+    }
+
+    #[test]
+    fn cli_track_id_matches_the_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let model_dir = dir.path().to_str().unwrap();
+        let args = [
+            "lofi-daemon", "--prompt", "rain", "--backend", "ace-step", "--duration", "30",
+            "--steps", "40", "--reverb", "hall", "--trim-silence", "--model-dir", model_dir,
+        ];
+        let (cli, config) = Cli::try_parse_args_from(args).unwrap().resolve(DaemonConfig::default());
+        let track_id = cli_track_id(&cli, &config, "rain", Backend::AceStep, 7);
+
+        let mut params: lofi_daemon::rpc::GenerateParams = serde_json::from_value(serde_json::json!({
+            "prompt": "rain",
+            "duration_sec": 30,
+            "inference_steps": 40,
+            "reverb": "hall",
+            "trim_silence": true,
+        }))
+        .unwrap();
+        params.resolve_inference_steps(Backend::AceStep, &config.ace_step);
+        let model_version = get_backend_version(Backend::AceStep, &config).unwrap();
+        let trim = config.silence_trim();
+        assert_eq!(track_id, params.track_id(Backend::AceStep, 7, &model_version, &trim));
+        assert_ne!(track_id, compute_track_id(Backend::AceStep, "rain", 7, 30.0, &model_version));
+    }
+
+    #[test]
+    fn shell_quote_only_quotes_when_needed() {
+        assert_eq!(shell_quote("/tmp/models"), "/tmp/models");
+        assert_eq!(shell_quote("/tmp/my models"), "'/tmp/my models'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
 }
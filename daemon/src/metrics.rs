@@ -0,0 +1,456 @@
+//! Generation and queue metrics in the Prometheus text format.
+//!
+//! A small hand-rolled registry: counters of generations per backend and
+//! outcome, cache lookups and generated bytes, histograms of generation wall
+//! time (whose `_sum` is the cumulative generation time) and realtime factor,
+//! and gauges for the queue depth, cache size and loaded backends. Labels are
+//! limited to `backend` and `status` so the number of series stays fixed.
+//!
+//! [`Metrics`] is a cheap handle; clones share the same registry, so the
+//! HTTP endpoint can render it while the request loop is generating.
+
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use crate::models::Backend;
+
+/// Backends, in the order their series are written.
+const BACKENDS: [Backend; 2] = [Backend::MusicGen, Backend::AceStep];
+
+/// Upper bounds of the generation wall time buckets, in seconds.
+const DURATION_BUCKETS: &[f64] = &[1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+/// Upper bounds of the realtime factor buckets.
+const REALTIME_FACTOR_BUCKETS: &[f64] = &[0.1, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+/// How a generation ended, or that it started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerationOutcome {
+    /// The backend started generating.
+    Started,
+    /// The track was generated and cached.
+    Completed,
+    /// Loading, generation or writing the track failed.
+    Failed,
+}
+
+impl GenerationOutcome {
+    const ALL: [GenerationOutcome; 3] = [
+        GenerationOutcome::Started,
+        GenerationOutcome::Completed,
+        GenerationOutcome::Failed,
+    ];
+
+    /// Returns the `status` label value.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GenerationOutcome::Started => "started",
+            GenerationOutcome::Completed => "completed",
+            GenerationOutcome::Failed => "failed",
+        }
+    }
+}
+
+/// Shared handle to the metrics registry.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    /// Generations per backend and outcome, indexed like `BACKENDS` and
+    /// `GenerationOutcome::ALL`.
+    generations: [[u64; 3]; 2],
+    /// Cache lookups per backend, as `[hits, misses]`.
+    cache_lookups: [[u64; 2]; 2],
+    /// Bytes of WAV files written by completed generations, per backend.
+    generated_bytes: [u64; 2],
+    generation_seconds: [Histogram; 2],
+    realtime_factor: [Histogram; 2],
+    queue_depth: usize,
+    cache_bytes: u64,
+    loaded: [bool; 2],
+}
+
+/// Cumulative histogram with fixed bucket bounds.
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations per bucket (not cumulative), one per bound.
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, bounds: &[f64], value: f64) {
+        self.counts.resize(bounds.len(), 0);
+        if let Some(bucket) = bounds.iter().position(|&bound| value <= bound) {
+            self.counts[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+fn backend_index(backend: Backend) -> usize {
+    match backend {
+        Backend::MusicGen => 0,
+        Backend::AceStep => 1,
+    }
+}
+
+fn outcome_index(outcome: GenerationOutcome) -> usize {
+    match outcome {
+        GenerationOutcome::Started => 0,
+        GenerationOutcome::Completed => 1,
+        GenerationOutcome::Failed => 2,
+    }
+}
+
+impl Metrics {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_registry<T>(&self, f: impl FnOnce(&mut Registry) -> T) -> T {
+        f(&mut self.registry.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Counts a generation outcome for `backend`.
+    pub fn record(&self, backend: Backend, outcome: GenerationOutcome) {
+        self.with_registry(|r| {
+            r.generations[backend_index(backend)][outcome_index(outcome)] += 1;
+        });
+    }
+
+    /// Counts a completed generation, observing its wall time and realtime
+    /// factor (seconds of audio per second of generation) and adding the
+    /// size of the written track to the generated bytes.
+    pub fn record_completed(
+        &self,
+        backend: Backend,
+        generation_sec: f32,
+        audio_sec: f32,
+        file_size_bytes: u64,
+    ) {
+        self.record(backend, GenerationOutcome::Completed);
+        self.with_registry(|r| {
+            let index = backend_index(backend);
+            r.generated_bytes[index] += file_size_bytes;
+            r.generation_seconds[index].observe(DURATION_BUCKETS, generation_sec as f64);
+            if generation_sec > 0.0 {
+                r.realtime_factor[index]
+                    .observe(REALTIME_FACTOR_BUCKETS, (audio_sec / generation_sec) as f64);
+            }
+        });
+    }
+
+    /// Counts a lookup of a requested track in the cache.
+    pub fn record_cache_lookup(&self, backend: Backend, hit: bool) {
+        self.with_registry(|r| {
+            r.cache_lookups[backend_index(backend)][usize::from(!hit)] += 1;
+        });
+    }
+
+    /// Returns how many cache lookups for `backend` were hits, or misses if
+    /// `hit` is false.
+    pub fn cache_lookups(&self, backend: Backend, hit: bool) -> u64 {
+        self.with_registry(|r| r.cache_lookups[backend_index(backend)][usize::from(!hit)])
+    }
+
+    /// Returns the bytes written by completed generations of `backend`.
+    pub fn generated_bytes(&self, backend: Backend) -> u64 {
+        self.with_registry(|r| r.generated_bytes[backend_index(backend)])
+    }
+
+    /// Returns how many generations of `backend` had `outcome`.
+    pub fn count(&self, backend: Backend, outcome: GenerationOutcome) -> u64 {
+        self.with_registry(|r| r.generations[backend_index(backend)][outcome_index(outcome)])
+    }
+
+    /// Sets the gauges describing the server's current state.
+    pub fn set_gauges(&self, queue_depth: usize, cache_bytes: u64, loaded: &[Backend]) {
+        self.with_registry(|r| {
+            r.queue_depth = queue_depth;
+            r.cache_bytes = cache_bytes;
+            r.loaded = BACKENDS.map(|backend| loaded.contains(&backend));
+        });
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        self.with_registry(|r| r.render())
+    }
+}
+
+impl Registry {
+    fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(&mut out, "lofi_generations_total", "counter", "Generations by backend and outcome.");
+        for (b, backend) in BACKENDS.iter().enumerate() {
+            for (o, outcome) in GenerationOutcome::ALL.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "lofi_generations_total{{backend=\"{}\",status=\"{}\"}} {}",
+                    backend.as_str(),
+                    outcome.as_str(),
+                    self.generations[b][o]
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "lofi_cache_lookups_total",
+            "counter",
+            "Lookups of requested tracks in the cache by backend and result.",
+        );
+        for (b, backend) in BACKENDS.iter().enumerate() {
+            for (status, count) in ["hit", "miss"].iter().zip(self.cache_lookups[b]) {
+                let _ = writeln!(
+                    out,
+                    "lofi_cache_lookups_total{{backend=\"{}\",status=\"{}\"}} {}",
+                    backend.as_str(),
+                    status,
+                    count
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "lofi_generated_bytes_total",
+            "counter",
+            "Bytes of WAV files written by completed generations.",
+        );
+        for (b, backend) in BACKENDS.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "lofi_generated_bytes_total{{backend=\"{}\"}} {}",
+                backend.as_str(),
+                self.generated_bytes[b]
+            );
+        }
+
+        header(
+            &mut out,
+            "lofi_generation_duration_seconds",
+            "histogram",
+            "Wall time of completed generations.",
+        );
+        for (b, backend) in BACKENDS.iter().enumerate() {
+            write_histogram(
+                &mut out,
+                "lofi_generation_duration_seconds",
+                backend,
+                DURATION_BUCKETS,
+                &self.generation_seconds[b],
+            );
+        }
+
+        header(
+            &mut out,
+            "lofi_generation_realtime_factor",
+            "histogram",
+            "Seconds of audio generated per second of wall time.",
+        );
+        for (b, backend) in BACKENDS.iter().enumerate() {
+            write_histogram(
+                &mut out,
+                "lofi_generation_realtime_factor",
+                backend,
+                REALTIME_FACTOR_BUCKETS,
+                &self.realtime_factor[b],
+            );
+        }
+
+        header(&mut out, "lofi_queue_depth", "gauge", "Jobs waiting in the queue.");
+        let _ = writeln!(out, "lofi_queue_depth {}", self.queue_depth);
+
+        header(&mut out, "lofi_cache_bytes", "gauge", "Size of the cached tracks in bytes.");
+        let _ = writeln!(out, "lofi_cache_bytes {}", self.cache_bytes);
+
+        header(&mut out, "lofi_backend_loaded", "gauge", "1 if the backend's models are loaded.");
+        for (b, backend) in BACKENDS.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "lofi_backend_loaded{{backend=\"{}\"}} {}",
+                backend.as_str(),
+                u8::from(self.loaded[b])
+            );
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn write_histogram(
+    out: &mut String,
+    name: &str,
+    backend: &Backend,
+    bounds: &[f64],
+    histogram: &Histogram,
+) {
+    let backend = backend.as_str();
+    let mut cumulative = 0;
+    for (i, bound) in bounds.iter().enumerate() {
+        cumulative += histogram.counts.get(i).copied().unwrap_or(0);
+        let _ = writeln!(
+            out,
+            "{}_bucket{{backend=\"{}\",le=\"{}\"}} {}",
+            name, backend, bound, cumulative
+        );
+    }
+    let _ = writeln!(out, "{}_bucket{{backend=\"{}\",le=\"+Inf\"}} {}", name, backend, histogram.count);
+    let _ = writeln!(out, "{}_sum{{backend=\"{}\"}} {}", name, backend, histogram.sum);
+    let _ = writeln!(out, "{}_count{{backend=\"{}\"}} {}", name, backend, histogram.count);
+}
+
+/// Checks that `text` is valid Prometheus exposition output.
+///
+/// Every line must be a `# HELP`/`# TYPE` comment or a sample
+/// `name{label="value",...} number`. Returns the first offending line.
+#[cfg(test)]
+pub(crate) fn check_exposition(text: &str) -> std::result::Result<(), String> {
+    fn is_name(s: &str) -> bool {
+        !s.is_empty()
+            && !s.starts_with(|c: char| c.is_ascii_digit())
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+    }
+    fn is_labels(s: &str) -> bool {
+        s.split(',').all(|pair| {
+            pair.split_once('=').is_some_and(|(name, value)| {
+                is_name(name) && value.len() >= 2 && value.starts_with('"') && value.ends_with('"')
+            })
+        })
+    }
+
+    for line in text.lines() {
+        let valid = if let Some(comment) = line.strip_prefix("# ") {
+            let mut parts = comment.splitn(3, ' ');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("HELP"), Some(name), Some(_)) => is_name(name),
+                (Some("TYPE"), Some(name), Some(kind)) => {
+                    is_name(name) && ["counter", "gauge", "histogram"].contains(&kind)
+                }
+                _ => false,
+            }
+        } else {
+            line.rsplit_once(' ').is_some_and(|(series, value)| {
+                let value_ok = value.parse::<f64>().is_ok() || value == "+Inf";
+                let series_ok = match series.split_once('{') {
+                    Some((name, labels)) => {
+                        is_name(name) && labels.strip_suffix('}').is_some_and(is_labels)
+                    }
+                    None => is_name(series),
+                };
+                value_ok && series_ok
+            })
+        };
+        if !valid {
+            return Err(line.to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_outcomes_per_backend() {
+        let metrics = Metrics::new();
+        metrics.record(Backend::MusicGen, GenerationOutcome::Started);
+        metrics.record_completed(Backend::MusicGen, 4.0, 10.0, 1024);
+        metrics.record(Backend::AceStep, GenerationOutcome::Failed);
+
+        // Clones share the registry
+        let shared = metrics.clone();
+        assert_eq!(shared.count(Backend::MusicGen, GenerationOutcome::Started), 1);
+        assert_eq!(shared.count(Backend::MusicGen, GenerationOutcome::Completed), 1);
+        assert_eq!(shared.count(Backend::AceStep, GenerationOutcome::Failed), 1);
+        assert_eq!(shared.count(Backend::AceStep, GenerationOutcome::Completed), 0);
+
+        let text = metrics.render();
+        assert!(text.contains("lofi_generations_total{backend=\"musicgen\",status=\"completed\"} 1\n"));
+        assert!(text.contains("lofi_generations_total{backend=\"ace_step\",status=\"failed\"} 1\n"));
+    }
+
+    #[test]
+    fn counts_cache_lookups_and_generated_bytes() {
+        let metrics = Metrics::new();
+        metrics.record_cache_lookup(Backend::MusicGen, true);
+        metrics.record_cache_lookup(Backend::MusicGen, false);
+        metrics.record_cache_lookup(Backend::MusicGen, false);
+        metrics.record_completed(Backend::AceStep, 30.0, 60.0, 5_000);
+        metrics.record_completed(Backend::AceStep, 30.0, 60.0, 7_000);
+
+        assert_eq!(metrics.cache_lookups(Backend::MusicGen, true), 1);
+        assert_eq!(metrics.cache_lookups(Backend::MusicGen, false), 2);
+        assert_eq!(metrics.cache_lookups(Backend::AceStep, true), 0);
+        assert_eq!(metrics.generated_bytes(Backend::AceStep), 12_000);
+        assert_eq!(metrics.generated_bytes(Backend::MusicGen), 0);
+
+        let text = metrics.render();
+        assert!(text.contains("lofi_cache_lookups_total{backend=\"musicgen\",status=\"hit\"} 1\n"));
+        assert!(text.contains("lofi_cache_lookups_total{backend=\"musicgen\",status=\"miss\"} 2\n"));
+        assert!(text.contains("lofi_generated_bytes_total{backend=\"ace_step\"} 12000\n"));
+        assert!(text.contains("lofi_generation_duration_seconds_sum{backend=\"ace_step\"} 60\n"));
+    }
+
+    #[test]
+    fn histograms_are_cumulative() {
+        let metrics = Metrics::new();
+        metrics.record_completed(Backend::MusicGen, 4.0, 10.0, 0);
+        metrics.record_completed(Backend::MusicGen, 40.0, 10.0, 0);
+
+        let text = metrics.render();
+        let name = "lofi_generation_duration_seconds";
+        assert!(text.contains(&format!("{}_bucket{{backend=\"musicgen\",le=\"2.5\"}} 0\n", name)));
+        assert!(text.contains(&format!("{}_bucket{{backend=\"musicgen\",le=\"5\"}} 1\n", name)));
+        assert!(text.contains(&format!("{}_bucket{{backend=\"musicgen\",le=\"60\"}} 2\n", name)));
+        assert!(text.contains(&format!("{}_bucket{{backend=\"musicgen\",le=\"+Inf\"}} 2\n", name)));
+        assert!(text.contains(&format!("{}_sum{{backend=\"musicgen\"}} 44\n", name)));
+        assert!(text.contains(&format!("{}_count{{backend=\"ace_step\"}} 0\n", name)));
+
+        // 10 s of audio in 4 s is 2.5x realtime
+        let name = "lofi_generation_realtime_factor";
+        assert!(text.contains(&format!("{}_bucket{{backend=\"musicgen\",le=\"2\"}} 1\n", name)));
+        assert!(text.contains(&format!("{}_bucket{{backend=\"musicgen\",le=\"4\"}} 2\n", name)));
+    }
+
+    #[test]
+    fn gauges_reflect_last_update() {
+        let metrics = Metrics::new();
+        metrics.set_gauges(3, 2048, &[Backend::AceStep]);
+
+        let text = metrics.render();
+        assert!(text.contains("lofi_queue_depth 3\n"));
+        assert!(text.contains("lofi_cache_bytes 2048\n"));
+        assert!(text.contains("lofi_backend_loaded{backend=\"musicgen\"} 0\n"));
+        assert!(text.contains("lofi_backend_loaded{backend=\"ace_step\"} 1\n"));
+    }
+
+    #[test]
+    fn exposition_format_is_valid() {
+        let metrics = Metrics::new();
+        metrics.record(Backend::MusicGen, GenerationOutcome::Started);
+        metrics.record_completed(Backend::MusicGen, 1.5, 5.0, 512);
+        metrics.record_cache_lookup(Backend::MusicGen, true);
+        metrics.set_gauges(1, 10, &[Backend::MusicGen]);
+        assert_eq!(check_exposition(&metrics.render()), Ok(()));
+
+        assert!(check_exposition("lofi_queue_depth three").is_err());
+        assert!(check_exposition("lofi_queue_depth{backend=musicgen} 1").is_err());
+        assert!(check_exposition("# TYPE lofi_queue_depth summary").is_err());
+    }
+}
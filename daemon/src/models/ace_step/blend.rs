@@ -0,0 +1,168 @@
+//! Weighted prompt blending.
+//!
+//! ACE-Step conditions on UMT5 text embeddings, so several prompts can be
+//! mixed by averaging their hidden states before `encode_context`, e.g.
+//! 70% "jazzy piano" and 30% "rain ambience".
+
+use ndarray::{s, Array2, Array3};
+
+/// Maximum number of prompts in a blend.
+pub const MAX_BLEND_PROMPTS: usize = 8;
+
+/// Validates a prompt blend and scales its weights to sum to 1.
+///
+/// Every prompt must be non-empty and every weight finite and non-negative,
+/// with a positive total. Returns an error message if validation fails.
+pub fn normalize_blend(blend: &[(String, f32)]) -> Result<Vec<(String, f32)>, String> {
+    if blend.is_empty() {
+        return Err("Prompt blend cannot be empty".to_string());
+    }
+    if blend.len() > MAX_BLEND_PROMPTS {
+        return Err(format!(
+            "Prompt blend has {} prompts (maximum {})",
+            blend.len(),
+            MAX_BLEND_PROMPTS
+        ));
+    }
+    if let Some((prompt, weight)) = blend
+        .iter()
+        .find(|(_, weight)| !weight.is_finite() || *weight < 0.0)
+    {
+        return Err(format!(
+            "Invalid weight {} for blend prompt \"{}\" (must be non-negative)",
+            weight, prompt
+        ));
+    }
+    if blend.iter().any(|(prompt, _)| prompt.trim().is_empty()) {
+        return Err("Blend prompts cannot be empty".to_string());
+    }
+
+    let total: f32 = blend.iter().map(|(_, weight)| weight).sum();
+    if total <= 0.0 {
+        return Err("Prompt blend weights must not all be zero".to_string());
+    }
+
+    Ok(blend
+        .iter()
+        .map(|(prompt, weight)| (prompt.clone(), weight / total))
+        .collect())
+}
+
+/// Describes a normalized blend, e.g. `70% jazzy piano + 30% rain ambience`.
+///
+/// Used as the track prompt, so blends that differ by less than 0.05% share
+/// a track ID.
+pub fn blend_label(blend: &[(String, f32)]) -> String {
+    blend
+        .iter()
+        .map(|(prompt, weight)| {
+            let percent = format!("{:.1}", weight * 100.0);
+            let percent = percent.strip_suffix(".0").unwrap_or(&percent);
+            format!("{}% {}", percent, prompt)
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+/// Combines encoded prompts into one weighted average.
+///
+/// `encoded` holds the text encoder output for each prompt: hidden states of
+/// shape (1, seq_len, dim) and an attention mask of shape (1, seq_len).
+/// Shorter sequences are padded to the longest one. At each position only the
+/// prompts that have a token there contribute, with their weights rescaled,
+/// so a short prompt does not dilute the tail of a longer one. The result
+/// attends to every position any prompt attends to.
+///
+/// # Panics
+///
+/// Panics if `encoded` is empty, its length differs from `weights`, or the
+/// hidden dimensions differ.
+pub fn blend_hidden_states(
+    encoded: &[(Array3<f32>, Array2<i64>)],
+    weights: &[f32],
+) -> (Array3<f32>, Array2<i64>) {
+    assert_eq!(encoded.len(), weights.len(), "one weight per encoded prompt");
+    let seq_len = encoded
+        .iter()
+        .map(|(hidden, _)| hidden.shape()[1])
+        .max()
+        .expect("at least one encoded prompt");
+    let dim = encoded[0].0.shape()[2];
+
+    let mut hidden = Array3::<f32>::zeros((1, seq_len, dim));
+    let mut position_weight = vec![0.0f32; seq_len];
+
+    for ((states, mask), &weight) in encoded.iter().zip(weights) {
+        assert_eq!(states.shape()[2], dim, "hidden dimensions must match");
+        for t in 0..states.shape()[1] {
+            if mask[[0, t]] == 0 {
+                continue;
+            }
+            let mut row = hidden.slice_mut(s![0, t, ..]);
+            row.scaled_add(weight, &states.slice(s![0, t, ..]));
+            position_weight[t] += weight;
+        }
+    }
+
+    let mut mask = Array2::<i64>::zeros((1, seq_len));
+    for (t, &total) in position_weight.iter().enumerate() {
+        if total > 0.0 {
+            hidden.slice_mut(s![0, t, ..]).mapv_inplace(|v| v / total);
+            mask[[0, t]] = 1;
+        }
+    }
+
+    (hidden, mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blend(entries: &[(&str, f32)]) -> Vec<(String, f32)> {
+        entries.iter().map(|(p, w)| (p.to_string(), *w)).collect()
+    }
+
+    #[test]
+    fn weights_are_normalized() {
+        let normalized = normalize_blend(&blend(&[("jazzy piano", 7.0), ("rain", 3.0)])).unwrap();
+        assert!((normalized[0].1 - 0.7).abs() < 1e-6);
+        assert!((normalized[1].1 - 0.3).abs() < 1e-6);
+        assert_eq!(blend_label(&normalized), "70% jazzy piano + 30% rain");
+
+        let thirds = normalize_blend(&blend(&[("a", 1.0), ("b", 2.0)])).unwrap();
+        assert_eq!(blend_label(&thirds), "33.3% a + 66.7% b");
+    }
+
+    #[test]
+    fn invalid_blends_are_rejected() {
+        assert!(normalize_blend(&[]).is_err());
+        assert!(normalize_blend(&blend(&[("a", 0.0), ("b", 0.0)])).is_err());
+        assert!(normalize_blend(&blend(&[("a", -1.0), ("b", 2.0)])).is_err());
+        assert!(normalize_blend(&blend(&[("a", f32::NAN)])).is_err());
+        assert!(normalize_blend(&blend(&[("  ", 1.0)])).is_err());
+        let many: Vec<(String, f32)> = (0..=MAX_BLEND_PROMPTS).map(|i| (i.to_string(), 1.0)).collect();
+        assert!(normalize_blend(&many).is_err());
+    }
+
+    #[test]
+    fn hidden_states_are_averaged_per_position() {
+        // Two positions for the first prompt, one for the second
+        let a = (
+            Array3::from_shape_vec((1, 2, 2), vec![1.0, 1.0, 2.0, 2.0]).unwrap(),
+            Array2::from_shape_vec((1, 2), vec![1, 1]).unwrap(),
+        );
+        let b = (
+            Array3::from_shape_vec((1, 1, 2), vec![3.0, 5.0]).unwrap(),
+            Array2::from_shape_vec((1, 1), vec![1]).unwrap(),
+        );
+
+        let (hidden, mask) = blend_hidden_states(&[a, b], &[0.75, 0.25]);
+        assert_eq!(hidden.shape(), &[1, 2, 2]);
+        assert_eq!(hidden[[0, 0, 0]], 0.75 * 1.0 + 0.25 * 3.0);
+        assert_eq!(hidden[[0, 0, 1]], 0.75 * 1.0 + 0.25 * 5.0);
+        // Only the first prompt covers the second position
+        assert_eq!(hidden[[0, 1, 0]], 2.0);
+        assert_eq!(mask, Array2::from_shape_vec((1, 2), vec![1, 1]).unwrap());
+    }
+}
@@ -0,0 +1,186 @@
+//! Transformer context cache for repeated prompts.
+//!
+//! Text encoding and `encode_context` run for both the conditional and the
+//! unconditional branch on every generation. Sweeps and batches often reuse
+//! the same prompt, so the resulting tensors are kept in a small LRU cache for
+//! the lifetime of the loaded models.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use ndarray::{Array2, Array3};
+use unicode_normalization::UnicodeNormalization;
+
+/// Default number of prompts whose context is kept.
+pub const DEFAULT_CONTEXT_CACHE_SIZE: usize = 8;
+
+/// Encoded transformer context for one prompt pair.
+#[derive(Debug, Clone)]
+pub struct PromptContext {
+    /// Conditional context, shape (1, seq_len, 2560).
+    pub cond_context: Array3<f32>,
+    /// Conditional context mask, shape (1, seq_len).
+    pub cond_mask: Array2<f32>,
+    /// Unconditional context, shape (1, seq_len, 2560).
+    pub uncond_context: Array3<f32>,
+    /// Unconditional context mask, shape (1, seq_len).
+    pub uncond_mask: Array2<f32>,
+}
+
+/// Cache key: canonicalized (prompt, negative_prompt).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ContextKey {
+    prompt: String,
+    negative_prompt: String,
+}
+
+impl ContextKey {
+    fn new(prompt: &str, negative_prompt: &str) -> Self {
+        Self {
+            prompt: canonicalize(prompt),
+            negative_prompt: canonicalize(negative_prompt),
+        }
+    }
+}
+
+/// Canonicalizes prompt text for use as a cache key.
+///
+/// NFC-normalizes and trims, so visually identical prompts share an entry.
+/// Prompts must be encoded in this form too, or a cached context could come
+/// from a differently encoded spelling of the prompt.
+pub fn canonicalize(prompt: &str) -> String {
+    prompt.nfc().collect::<String>().trim().to_string()
+}
+
+/// Bounded least-recently-used cache of encoded prompt contexts.
+///
+/// A capacity of 0 disables caching.
+#[derive(Debug)]
+pub struct ContextCache {
+    capacity: usize,
+    /// Entries ordered from least to most recently used.
+    entries: VecDeque<(ContextKey, Arc<PromptContext>)>,
+}
+
+impl ContextCache {
+    /// Creates an empty cache holding at most `capacity` prompts.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the maximum number of cached prompts.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of cached prompts.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Looks up the context for a prompt pair and marks it most recently used.
+    pub fn get(&mut self, prompt: &str, negative_prompt: &str) -> Option<Arc<PromptContext>> {
+        let key = ContextKey::new(prompt, negative_prompt);
+        let index = self.entries.iter().position(|(k, _)| *k == key)?;
+        let entry = self.entries.remove(index)?;
+        let context = Arc::clone(&entry.1);
+        self.entries.push_back(entry);
+        Some(context)
+    }
+
+    /// Stores the context for a prompt pair, evicting the least recently used
+    /// entry if the cache is full. Returns the shared context.
+    pub fn insert(
+        &mut self,
+        prompt: &str,
+        negative_prompt: &str,
+        context: PromptContext,
+    ) -> Arc<PromptContext> {
+        let context = Arc::new(context);
+        if self.capacity == 0 {
+            return context;
+        }
+
+        let key = ContextKey::new(prompt, negative_prompt);
+        self.entries.retain(|(k, _)| *k != key);
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, Arc::clone(&context)));
+        context
+    }
+
+    /// Removes all cached contexts.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Default for ContextCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CONTEXT_CACHE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(value: f32) -> PromptContext {
+        PromptContext {
+            cond_context: Array3::from_elem((1, 2, 4), value),
+            cond_mask: Array2::ones((1, 2)),
+            uncond_context: Array3::zeros((1, 2, 4)),
+            uncond_mask: Array2::ones((1, 2)),
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = ContextCache::new(2);
+        cache.insert("rain", "", context(1.0));
+        cache.insert("piano", "", context(2.0));
+
+        // Touch "rain" so "piano" becomes the eviction candidate
+        assert!(cache.get("rain", "").is_some());
+        cache.insert("jazz", "", context(3.0));
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("piano", "").is_none());
+        assert_eq!(cache.get("rain", "").unwrap().cond_context[[0, 0, 0]], 1.0);
+        assert_eq!(cache.get("jazz", "").unwrap().cond_context[[0, 0, 0]], 3.0);
+    }
+
+    #[test]
+    fn keys_are_canonicalized() {
+        let mut cache = ContextCache::new(4);
+        cache.insert("  caf\u{e9} jazz ", "", context(1.0));
+
+        assert!(cache.get("cafe\u{301} jazz", "").is_some());
+        assert!(cache.get("caf\u{e9} jazz", "muddy").is_none());
+
+        // Re-inserting an existing key replaces it instead of duplicating
+        cache.insert("caf\u{e9} jazz", "", context(2.0));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(
+            cache.get("caf\u{e9} jazz", "").unwrap().cond_context[[0, 0, 0]],
+            2.0
+        );
+    }
+
+    #[test]
+    fn zero_capacity_disables_cache() {
+        let mut cache = ContextCache::new(0);
+        cache.insert("rain", "", context(1.0));
+        assert!(cache.is_empty());
+        assert!(cache.get("rain", "").is_none());
+    }
+}
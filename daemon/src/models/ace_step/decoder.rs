@@ -4,9 +4,14 @@
 //! into mel-spectrograms.
 //!
 //! Note: The ONNX model has a fixed input size of 128 frames.
-//! For longer audio, we decode in chunks and concatenate.
+//! For longer audio, we decode in chunks and concatenate. Chunks are
+//! independent, so with more than one session loaded (see
+//! `DaemonConfig::dcae_parallelism`) they are decoded on parallel threads
+//! and reassembled in order.
 
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 use ndarray::{s, Array3, Array4, Axis};
 use ort::execution_providers::ExecutionProviderDispatch;
@@ -14,6 +19,7 @@ use ort::session::Session;
 use ort::value::Tensor;
 
 use crate::error::{DaemonError, Result};
+use crate::models::device::SessionTuning;
 
 use super::models::load_session;
 
@@ -31,13 +37,16 @@ pub const MAX_DECODE_FRAMES: usize = 128;
 /// Converts latent representations from the diffusion process into
 /// mel-spectrograms that can be vocoded into audio.
 pub struct DcaeDecoder {
-    /// The ONNX session for the DCAE decoder.
-    session: Session,
+    /// ONNX sessions for the DCAE decoder, all loaded from the same file.
+    /// Running a session needs exclusive access, so each chunk decoded in
+    /// parallel uses its own session.
+    sessions: Vec<Session>,
 }
 
 impl std::fmt::Debug for DcaeDecoder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("DcaeDecoder")
+            .field("sessions", &self.sessions.len())
             .finish_non_exhaustive()
     }
 }
@@ -49,10 +58,25 @@ impl DcaeDecoder {
     ///
     /// * `model_dir` - Directory containing `dcae_decoder.onnx`
     /// * `providers` - Execution providers for ONNX Runtime
-    pub fn load(model_dir: &Path, providers: &[ExecutionProviderDispatch]) -> Result<Self> {
+    /// * `tuning` - ONNX Runtime session settings
+    /// * `parallelism` - Number of sessions to load (at least 1). Each extra
+    ///   session holds another copy of the model in memory.
+    pub fn load(
+        model_dir: &Path,
+        providers: &[ExecutionProviderDispatch],
+        tuning: &SessionTuning,
+        parallelism: usize,
+    ) -> Result<Self> {
         let decoder_path = model_dir.join("dcae_decoder.onnx");
-        let session = load_session(&decoder_path, providers)?;
-        Ok(Self { session })
+        let sessions = (0..parallelism.max(1))
+            .map(|_| load_session(&decoder_path, providers, tuning))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { sessions })
+    }
+
+    /// Returns the number of chunks decoded at once.
+    pub fn parallelism(&self) -> usize {
+        self.sessions.len()
     }
 
     /// Decodes latent representation to mel-spectrogram.
@@ -68,120 +92,7 @@ impl DcaeDecoder {
     ///
     /// Mel-spectrogram with shape (1, mel_bins, time_frames).
     pub fn decode(&mut self, latent: &Array4<f32>) -> Result<Array3<f32>> {
-        let frame_length = latent.shape()[3];
-
-        if frame_length == MAX_DECODE_FRAMES {
-            // Exact size - decode directly
-            self.decode_chunk(latent)
-        } else if frame_length < MAX_DECODE_FRAMES {
-            // Pad to 128 frames, decode, then trim output
-            let mut padded = Array4::<f32>::zeros((1, 8, 16, MAX_DECODE_FRAMES));
-            padded.slice_mut(s![.., .., .., ..frame_length])
-                .assign(latent);
-
-            let mel = self.decode_chunk(&padded)?;
-
-            // Trim mel output proportionally
-            let mel_frames = mel.shape()[2];
-            let expected_frames = (mel_frames * frame_length) / MAX_DECODE_FRAMES;
-            let trimmed = mel.slice(s![.., .., ..expected_frames]).to_owned();
-            Ok(trimmed)
-        } else {
-            // Multiple chunks needed
-            let num_chunks = (frame_length + MAX_DECODE_FRAMES - 1) / MAX_DECODE_FRAMES;
-            eprintln!("Decoding in {} chunks of {} frames...", num_chunks, MAX_DECODE_FRAMES);
-
-            let mut mel_chunks: Vec<Array3<f32>> = Vec::new();
-
-            for i in 0..num_chunks {
-                let start = i * MAX_DECODE_FRAMES;
-                let end = ((i + 1) * MAX_DECODE_FRAMES).min(frame_length);
-                let chunk_len = end - start;
-
-                // Extract chunk - need to pad to 128 if smaller
-                let chunk = if chunk_len < MAX_DECODE_FRAMES {
-                    // Pad the last chunk with zeros
-                    let mut padded = Array4::<f32>::zeros((1, 8, 16, MAX_DECODE_FRAMES));
-                    padded.slice_mut(s![.., .., .., ..chunk_len])
-                        .assign(&latent.slice(s![.., .., .., start..end]));
-                    padded
-                } else {
-                    latent.slice(s![.., .., .., start..end]).to_owned()
-                };
-
-                let mel_chunk = self.decode_chunk(&chunk)?;
-
-                // If padded, trim the mel output proportionally
-                if chunk_len < MAX_DECODE_FRAMES {
-                    let mel_frames = mel_chunk.shape()[2];
-                    let expected_frames = (mel_frames * chunk_len) / MAX_DECODE_FRAMES;
-                    let trimmed = mel_chunk.slice(s![.., .., ..expected_frames]).to_owned();
-                    mel_chunks.push(trimmed);
-                } else {
-                    mel_chunks.push(mel_chunk);
-                }
-            }
-
-            // Concatenate along time axis
-            let views: Vec<_> = mel_chunks.iter().map(|c| c.view()).collect();
-            let concatenated = ndarray::concatenate(Axis(2), &views)
-                .map_err(|e| DaemonError::model_inference_failed(format!("Failed to concatenate mel chunks: {}", e)))?;
-
-            Ok(concatenated)
-        }
-    }
-
-    /// Decodes a single chunk (must be exactly 128 frames or less with padding).
-    fn decode_chunk(&mut self, latent: &Array4<f32>) -> Result<Array3<f32>> {
-        let shape = latent.shape();
-        let data: Vec<f32> = latent.iter().copied().collect();
-        let latent_tensor = Tensor::from_array(([shape[0], shape[1], shape[2], shape[3]], data))
-            .map_err(|e| DaemonError::model_inference_failed(format!("Failed to create latent tensor: {}", e)))?;
-
-        let mut outputs = self
-            .session
-            .run(ort::inputs!["latents" => latent_tensor])
-            .map_err(|e| DaemonError::model_inference_failed(format!("DCAE decoder failed: {}", e)))?;
-
-        // Get mel_spectrogram output
-        let mel = outputs.remove("mel_spectrogram").ok_or_else(|| {
-            DaemonError::model_inference_failed("Missing mel_spectrogram output".to_string())
-        })?;
-
-        let (mel_shape, mel_data) = mel
-            .try_extract_tensor::<f32>()
-            .map_err(|e| DaemonError::model_inference_failed(format!("Failed to extract mel spectrogram: {}", e)))?;
-
-        let dims: Vec<usize> = mel_shape.iter().map(|&d| d as usize).collect();
-
-        // Handle 4D output (1, 2, mel_bins, time) or 3D output (1, mel_bins, time)
-        // Take first channel if 4D with 2 channels
-        let output = if dims.len() == 4 {
-            // Shape is (1, 2, mel_bins, time) - take first channel
-            let channel_size = dims[2] * dims[3];
-            let first_channel: Vec<f32> = mel_data.iter()
-                .take(channel_size)
-                .copied()
-                .collect();
-            Array3::from_shape_vec(
-                (dims[0], dims[2], dims[3]),
-                first_channel,
-            )
-            .map_err(|e| DaemonError::model_inference_failed(format!("Failed to reshape mel: {}", e)))?
-        } else if dims.len() == 3 {
-            Array3::from_shape_vec(
-                (dims[0], dims[1], dims[2]),
-                mel_data.to_vec(),
-            )
-            .map_err(|e| DaemonError::model_inference_failed(format!("Failed to reshape mel: {}", e)))?
-        } else {
-            return Err(DaemonError::model_inference_failed(format!(
-                "Unexpected DCAE output shape: {:?}",
-                dims
-            )));
-        };
-
-        Ok(output)
+        decode_in_chunks(&mut self.sessions, latent, decode_chunk)
     }
 
     /// Estimates the output time frames from latent frame length.
@@ -199,6 +110,166 @@ impl DcaeDecoder {
     }
 }
 
+
+/// Splits `latent` into chunks of [`MAX_DECODE_FRAMES`], decodes them with
+/// `decode` and concatenates the mel-spectrograms along the time axis.
+///
+/// The last chunk is zero-padded to the full size and its mel output trimmed
+/// proportionally. Chunks are decoded on one thread per worker, each taking
+/// the next undecoded chunk, and reassembled in chunk order, so the result
+/// does not depend on the number of workers.
+fn decode_in_chunks<W, F>(
+    workers: &mut [W],
+    latent: &Array4<f32>,
+    decode: F,
+) -> Result<Array3<f32>>
+where
+    W: Send,
+    F: Fn(&mut W, &Array4<f32>) -> Result<Array3<f32>> + Sync,
+{
+    let frame_length = latent.shape()[3];
+    let num_chunks = frame_length.div_ceil(MAX_DECODE_FRAMES);
+    if num_chunks > 1 {
+        eprintln!(
+            "Decoding in {} chunks of {} frames on {} session(s)...",
+            num_chunks,
+            MAX_DECODE_FRAMES,
+            workers.len().min(num_chunks)
+        );
+    }
+
+    let decode_at = |worker: &mut W, index: usize| {
+        let start = index * MAX_DECODE_FRAMES;
+        let end = (start + MAX_DECODE_FRAMES).min(frame_length);
+        let chunk_len = end - start;
+
+        // Extract chunk - need to pad to 128 if smaller
+        let chunk = if chunk_len < MAX_DECODE_FRAMES {
+            let (batch, channels, height, _) = latent.dim();
+            let mut padded = Array4::<f32>::zeros((batch, channels, height, MAX_DECODE_FRAMES));
+            padded
+                .slice_mut(s![.., .., .., ..chunk_len])
+                .assign(&latent.slice(s![.., .., .., start..end]));
+            padded
+        } else {
+            latent.slice(s![.., .., .., start..end]).to_owned()
+        };
+
+        let mel_chunk = decode(worker, &chunk)?;
+        if num_chunks > 1 {
+            eprintln!("Decoded chunk {}/{}", index + 1, num_chunks);
+        }
+
+        // If padded, trim the mel output proportionally
+        if chunk_len < MAX_DECODE_FRAMES {
+            let mel_frames = mel_chunk.shape()[2];
+            let expected_frames = (mel_frames * chunk_len) / MAX_DECODE_FRAMES;
+            Ok(mel_chunk.slice(s![.., .., ..expected_frames]).to_owned())
+        } else {
+            Ok(mel_chunk)
+        }
+    };
+
+    let mel_chunks: Vec<Array3<f32>> = if workers.len() <= 1 || num_chunks <= 1 {
+        let worker = workers
+            .first_mut()
+            .ok_or_else(|| DaemonError::model_inference_failed("No DCAE session loaded"))?;
+        (0..num_chunks)
+            .map(|index| decode_at(worker, index))
+            .collect::<Result<_>>()?
+    } else {
+        let next_chunk = &AtomicUsize::new(0);
+        let slots: Mutex<Vec<Option<Result<Array3<f32>>>>> =
+            Mutex::new((0..num_chunks).map(|_| None).collect());
+        let (results, decode_at) = (&slots, &decode_at);
+        std::thread::scope(|scope| {
+            for worker in workers.iter_mut().take(num_chunks) {
+                scope.spawn(move || loop {
+                    let index = next_chunk.fetch_add(1, Ordering::SeqCst);
+                    if index >= num_chunks {
+                        break;
+                    }
+                    let result = decode_at(worker, index);
+                    let failed = result.is_err();
+                    results.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(result);
+                    if failed {
+                        // Stop handing out chunks; the first error is returned
+                        next_chunk.store(num_chunks, Ordering::SeqCst);
+                        break;
+                    }
+                });
+            }
+        });
+
+        // Chunks are handed out in order and a worker finishes the chunk it
+        // took, so every chunk before the first failure was decoded
+        slots
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .into_iter()
+            .map_while(|result| result)
+            .collect::<Result<_>>()?
+    };
+
+    // Concatenate along time axis
+    let views: Vec<_> = mel_chunks.iter().map(|c| c.view()).collect();
+    ndarray::concatenate(Axis(2), &views).map_err(|e| {
+        DaemonError::model_inference_failed(format!("Failed to concatenate mel chunks: {}", e))
+    })
+}
+
+/// Decodes a single chunk of exactly 128 frames with `session`.
+fn decode_chunk(session: &mut Session, latent: &Array4<f32>) -> Result<Array3<f32>> {
+    let shape = latent.shape();
+    let data: Vec<f32> = latent.iter().copied().collect();
+    let latent_tensor = Tensor::from_array(([shape[0], shape[1], shape[2], shape[3]], data))
+        .map_err(|e| DaemonError::model_inference_failed(format!("Failed to create latent tensor: {}", e)))?;
+
+    let mut outputs = session
+        .run(ort::inputs!["latents" => latent_tensor])
+        .map_err(|e| DaemonError::model_inference_failed_with_source("DCAE decoder failed", e))?;
+
+    // Get mel_spectrogram output
+    let mel = outputs.remove("mel_spectrogram").ok_or_else(|| {
+        DaemonError::model_inference_failed("Missing mel_spectrogram output".to_string())
+    })?;
+
+    let (mel_shape, mel_data) = mel
+        .try_extract_tensor::<f32>()
+        .map_err(|e| DaemonError::model_inference_failed(format!("Failed to extract mel spectrogram: {}", e)))?;
+
+    let dims: Vec<usize> = mel_shape.iter().map(|&d| d as usize).collect();
+
+    // Handle 4D output (1, 2, mel_bins, time) or 3D output (1, mel_bins, time)
+    // Take first channel if 4D with 2 channels
+    let output = if dims.len() == 4 {
+        // Shape is (1, 2, mel_bins, time) - take first channel
+        let channel_size = dims[2] * dims[3];
+        let first_channel: Vec<f32> = mel_data.iter()
+            .take(channel_size)
+            .copied()
+            .collect();
+        Array3::from_shape_vec(
+            (dims[0], dims[2], dims[3]),
+            first_channel,
+        )
+        .map_err(|e| DaemonError::model_inference_failed(format!("Failed to reshape mel: {}", e)))?
+    } else if dims.len() == 3 {
+        Array3::from_shape_vec(
+            (dims[0], dims[1], dims[2]),
+            mel_data.to_vec(),
+        )
+        .map_err(|e| DaemonError::model_inference_failed(format!("Failed to reshape mel: {}", e)))?
+    } else {
+        return Err(DaemonError::model_inference_failed(format!(
+            "Unexpected DCAE output shape: {:?}",
+            dims
+        )));
+    };
+
+    Ok(output)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -220,4 +291,112 @@ mod tests {
         // 800 frames * 512 hop = 409600 samples
         assert_eq!(DcaeDecoder::estimate_samples(800), 409600);
     }
+
+    /// Stands in for a DCAE session: 8 mel frames per latent frame, each
+    /// derived from the latent value so chunk order shows in the output.
+    fn stub_decode(calls: &mut usize, chunk: &Array4<f32>) -> Result<Array3<f32>> {
+        assert_eq!(chunk.shape()[3], MAX_DECODE_FRAMES);
+        *calls += 1;
+        let frames = chunk.shape()[3] * 8;
+        Ok(Array3::from_shape_fn((1, MEL_BINS, frames), |(_, bin, t)| {
+            chunk[[0, 0, 0, t / 8]] + bin as f32 * 0.001
+        }))
+    }
+
+    fn ramp_latent(frames: usize) -> Array4<f32> {
+        Array4::from_shape_fn((1, 8, 16, frames), |(_, _, _, t)| t as f32)
+    }
+
+    #[test]
+    fn parallel_decode_matches_sequential() {
+        // Five full chunks and a partial one
+        let latent = ramp_latent(5 * MAX_DECODE_FRAMES + 64);
+
+        let mut sequential = [0];
+        let expected = decode_in_chunks(&mut sequential, &latent, stub_decode).unwrap();
+        let mut parallel = [0, 0, 0];
+        let mel = decode_in_chunks(&mut parallel, &latent, stub_decode).unwrap();
+
+        assert_eq!(mel, expected);
+        assert_eq!(mel.shape(), &[1, MEL_BINS, DcaeDecoder::estimate_output_frames(704)]);
+        assert_eq!(sequential[0], 6);
+        assert_eq!(parallel.iter().sum::<usize>(), 6);
+        // Reassembled in chunk order
+        assert_eq!(mel[[0, 0, 8 * 300]], 300.0);
+        assert_eq!(mel[[0, 0, mel.shape()[2] - 1]], 703.0);
+    }
+
+    #[test]
+    fn short_latents_are_padded_and_trimmed() {
+        for frames in [1, 100, MAX_DECODE_FRAMES] {
+            let mut workers = [0, 0];
+            let mel = decode_in_chunks(&mut workers, &ramp_latent(frames), stub_decode).unwrap();
+            assert_eq!(mel.shape()[2], frames * 8);
+            assert_eq!(workers.iter().sum::<usize>(), 1);
+        }
+    }
+
+    #[test]
+    fn chunk_errors_are_returned() {
+        let mut workers = [0, 0];
+        let result = decode_in_chunks(&mut workers, &ramp_latent(4 * MAX_DECODE_FRAMES), |_, chunk| {
+            if chunk[[0, 0, 0, 0]] == (2 * MAX_DECODE_FRAMES) as f32 {
+                Err(DaemonError::model_inference_failed("chunk failed"))
+            } else {
+                stub_decode(&mut 0, chunk)
+            }
+        });
+        assert!(result.unwrap_err().to_string().contains("chunk failed"));
+    }
+
+    /// Returns the resident set size of this process in bytes.
+    fn resident_bytes() -> u64 {
+        use sysinfo::{ProcessesToUpdate, System};
+
+        let pid = sysinfo::get_current_pid().unwrap();
+        let mut system = System::new();
+        system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+        system.process(pid).map_or(0, |process| process.memory())
+    }
+
+    /// Times a 120 second decode with one and two sessions on the real model,
+    /// and measures the resident memory each extra session adds, to check
+    /// `DCAE_SESSION_BYTES`:
+    /// `LOFI_ACE_STEP_MODEL_PATH=... cargo test --release dcae_parallel_benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn dcae_parallel_benchmark() {
+        use crate::generation::memory::DCAE_SESSION_BYTES;
+        use crate::models::ace_step::calculate_frame_length;
+
+        const MB: u64 = 1024 * 1024;
+        let model_dir = std::path::PathBuf::from(
+            std::env::var("LOFI_ACE_STEP_MODEL_PATH").expect("LOFI_ACE_STEP_MODEL_PATH not set"),
+        );
+        let latent = Array4::<f32>::zeros((1, 8, 16, calculate_frame_length(120.0)));
+
+        let mut loaded_bytes = Vec::new();
+        for parallelism in [1, 2] {
+            let tuning = SessionTuning::default();
+            let before = resident_bytes();
+            let mut decoder = DcaeDecoder::load(&model_dir, &[], &tuning, parallelism).unwrap();
+            let start = std::time::Instant::now();
+            let mel = decoder.decode(&latent).unwrap();
+            // Sessions allocate their arenas on the first run, so measure after decoding
+            let loaded = resident_bytes().saturating_sub(before);
+            println!(
+                "parallelism {}: {:?} for {} mel frames, {} MB resident",
+                parallelism,
+                start.elapsed(),
+                mel.shape()[2],
+                loaded / MB
+            );
+            loaded_bytes.push(loaded);
+        }
+        println!(
+            "extra session: {} MB measured, DCAE_SESSION_BYTES is {} MB",
+            loaded_bytes[1].saturating_sub(loaded_bytes[0]) / MB,
+            DCAE_SESSION_BYTES / MB
+        );
+    }
 }
@@ -3,18 +3,31 @@
 //! Implements the complete diffusion-based audio generation loop using
 //! all ACE-Step model components.
 
-use crate::error::Result;
+use std::sync::Arc;
+use std::time::Instant;
 
-use super::guidance::{apply_cfg, DEFAULT_GUIDANCE_SCALE};
+use ndarray::{Array2, Array3, Array4, Zip};
+
+use crate::config::AceStepConfig;
+use crate::error::{DaemonError, ErrorCode, GenerationPhase, Result};
+use crate::generation::{AudioProgress, CancelToken, Timings};
+use crate::types::DisplayPrompt;
+
+use super::blend::{blend_hidden_states, blend_label, normalize_blend};
+use super::context_cache::{canonicalize, PromptContext};
+use super::guidance::{is_unguided, predict_guided_noise_into};
 use super::latent::{calculate_frame_length, initialize_latent};
 use super::models::AceStepModels;
-use super::scheduler::{create_scheduler, SchedulerType};
+use super::scheduler::{reuse_scheduler, SchedulerType};
 
 /// Generation parameters for ACE-Step.
 #[derive(Debug, Clone)]
 pub struct GenerationParams {
     /// Text description of the music to generate.
     pub prompt: String,
+    /// Prompts to blend in embedding space with their weights, used instead
+    /// of `prompt` if set. Weights are normalized to sum to 1.
+    pub prompt_blend: Option<Vec<(String, f32)>>,
     /// Target duration in seconds (5-240).
     pub duration_sec: f32,
     /// Random seed for reproducibility.
@@ -25,17 +38,30 @@ pub struct GenerationParams {
     pub scheduler: SchedulerType,
     /// Classifier-free guidance scale (1.0-20.0, default 7.0).
     pub guidance_scale: f32,
+    /// Checked before every diffusion step. With `keep_partial` set, a
+    /// cancelled generation decodes its current estimate of the clean latent.
+    pub cancel: Option<CancelToken>,
+    /// Receives the time spent in each phase, if set.
+    pub timings: Option<Timings>,
+    /// Called with `(decoded_sec, total_sec)` once audio has been synthesized.
+    pub on_audio_progress: Option<AudioProgress>,
 }
 
 impl Default for GenerationParams {
+    /// Uses the [`AceStepConfig`] defaults for steps, scheduler and guidance.
     fn default() -> Self {
+        let defaults = AceStepConfig::default();
         Self {
             prompt: String::new(),
+            prompt_blend: None,
             duration_sec: 30.0,
             seed: 42,
-            inference_steps: 60,
-            scheduler: SchedulerType::Euler,
-            guidance_scale: DEFAULT_GUIDANCE_SCALE,
+            inference_steps: defaults.inference_steps,
+            scheduler: SchedulerType::parse(&defaults.scheduler).unwrap_or(SchedulerType::Euler),
+            guidance_scale: defaults.guidance_scale,
+            cancel: None,
+            timings: None,
+            on_audio_progress: None,
         }
     }
 }
@@ -53,6 +79,9 @@ pub fn generate(models: &mut AceStepModels, params: GenerationParams) -> Result<
 /// * `params` - Generation parameters
 /// * `on_progress` - Callback receiving (current_step, total_steps)
 ///
+/// The latent is decoded in one piece, so `params.on_audio_progress` is called
+/// once, after synthesis, with the full requested duration.
+///
 /// # Returns
 ///
 /// Audio samples at 44.1 kHz sample rate.
@@ -69,23 +98,22 @@ where
         params.duration_sec, params.inference_steps, params.guidance_scale
     );
 
-    // Step 1: Encode the text prompt
-    eprintln!("Encoding prompt: \"{}\"", params.prompt);
-    let (text_hidden_states, text_attention_mask) = models.text_encoder.encode(&params.prompt)?;
-
-    // Step 2: Encode empty prompt for classifier-free guidance
-    let (uncond_text_hidden_states, uncond_text_attention_mask) = models.text_encoder.encode("")?;
-
-    // Step 3: Get transformer context for conditional and unconditional
-    eprintln!("Encoding transformer context...");
-    let (cond_context, cond_mask) = models.transformer.encode_context(
-        &text_hidden_states,
-        &text_attention_mask,
-    )?;
-    let (uncond_context, uncond_mask) = models.transformer.encode_context(
-        &uncond_text_hidden_states,
-        &uncond_text_attention_mask,
-    )?;
+    // Steps 1-3: Encode the prompt (or blend) and the empty prompt for
+    // classifier-free guidance, then get the transformer context for both
+    // (cached per prompt)
+    let start = Instant::now();
+    let context = match &params.prompt_blend {
+        Some(blend) => blended_prompt_context(models, blend),
+        None => prompt_context(models, &params.prompt),
+    }
+    .map_err(|e| e.with_phase(GenerationPhase::TextEncode))?;
+    record(&params, GenerationPhase::TextEncode, start);
+    let PromptContext {
+        cond_context,
+        cond_mask,
+        uncond_context,
+        uncond_mask,
+    } = &*context;
 
     eprintln!(
         "Context shape: {:?} (dim=2560)",
@@ -99,8 +127,14 @@ where
         frame_length, params.duration_sec
     );
 
-    // Step 5: Create scheduler (pass seed for PingPong's stochastic noise)
-    let mut scheduler = create_scheduler(params.scheduler, params.inference_steps, params.seed);
+    // Step 5: Create scheduler (pass seed for PingPong's stochastic noise),
+    // resetting the previous generation's one if it has the same type
+    let mut scheduler = reuse_scheduler(
+        models.scheduler.take().map(|scheduler| *scheduler),
+        params.scheduler,
+        params.inference_steps,
+        params.seed,
+    );
 
     // Step 6: Initialize latent with random noise
     let initial_sigma = scheduler.sigma();
@@ -115,11 +149,39 @@ where
         user_total_steps,
         params.scheduler.as_str()
     );
+    if is_unguided(params.guidance_scale) {
+        eprintln!("Guidance disabled, skipping unconditional noise predictions");
+    }
 
     // Step 7: Diffusion loop
-    // Loop over internal steps (which may be 2x user steps for Heun)
+    // Loop over internal steps (which may be 2x user steps for Heun). The
+    // latent is updated in place and the guided noise and denoised estimate
+    // reuse their buffers, so steps do not allocate latent-sized arrays
     let mut last_user_step = 0;
+    let mut guided_noise = Array4::zeros(latent.raw_dim());
+    let mut denoised_estimate: Option<Array4<f32>> = None;
+    let mut cancelled = false;
     while !scheduler.is_done() {
+        if let Some(cancel) = params.cancel.as_ref().filter(|c| c.is_cancelled()) {
+            match denoised_estimate.take() {
+                Some(estimate) if cancel.keep_partial() => {
+                    eprintln!(
+                        "Generation cancelled at step {}/{}, decoding partial result",
+                        scheduler.user_step(),
+                        user_total_steps
+                    );
+                    latent = estimate;
+                    cancelled = true;
+                    break;
+                }
+                _ => {
+                    return Err(
+                        DaemonError::generation_cancelled().with_phase(GenerationPhase::Diffusion)
+                    );
+                }
+            }
+        }
+
         let current_user_step = scheduler.user_step();
 
         // Report progress at user-step granularity
@@ -129,28 +191,39 @@ where
         }
 
         let timestep = scheduler.timestep();
-
-        // Get conditional noise prediction
-        let cond_noise = models.transformer.predict_noise(
-            &latent,
-            timestep,
-            &cond_context,
-            &cond_mask,
-        )?;
-
-        // Get unconditional noise prediction
-        let uncond_noise = models.transformer.predict_noise(
-            &latent,
-            timestep,
-            &uncond_context,
-            &uncond_mask,
-        )?;
-
-        // Apply classifier-free guidance
-        let guided_noise = apply_cfg(&cond_noise, &uncond_noise, params.guidance_scale);
+        let step_start = Instant::now();
+
+        // Predict the conditional and unconditional noise and apply
+        // classifier-free guidance (conditional only at guidance 1.0)
+        predict_guided_noise_into(
+            params.guidance_scale,
+            |conditional| {
+                let (context, mask) = if conditional {
+                    (cond_context, cond_mask)
+                } else {
+                    (uncond_context, uncond_mask)
+                };
+                models.transformer.predict_noise(&latent, timestep, context, mask)
+            },
+            &mut guided_noise,
+        )
+        .map_err(|e| e.with_phase(GenerationPhase::Diffusion))?;
+
+        // Flow matching predicts the velocity, so the clean latent is estimated
+        // as latent - sigma * velocity; only needed if the run may be cut short
+        if params.cancel.is_some() {
+            let sigma = scheduler.sigma();
+            let estimate =
+                denoised_estimate.get_or_insert_with(|| Array4::zeros(latent.raw_dim()));
+            Zip::from(estimate)
+                .and(&latent)
+                .and(&guided_noise)
+                .for_each(|e, &x, &v| *e = x - v * sigma);
+        }
 
         // Update latent with scheduler step
-        latent = scheduler.step(&latent, &guided_noise);
+        scheduler.step_in_place(&mut latent, &guided_noise);
+        record(&params, GenerationPhase::Diffusion, step_start);
 
         // Log progress at regular intervals (based on user steps)
         let user_step = scheduler.user_step();
@@ -160,12 +233,20 @@ where
     }
 
     // Final progress callback
-    on_progress(user_total_steps, user_total_steps);
+    if !cancelled {
+        on_progress(user_total_steps, user_total_steps);
+    }
+    models.scheduler = Some(Box::new(scheduler));
 
     eprintln!("Decoding latent to mel-spectrogram...");
 
     // Step 8: Decode latent to mel-spectrogram
-    let mel = models.decoder.decode(&latent)?;
+    let start = Instant::now();
+    let mel = models
+        .decoder
+        .decode(&latent)
+        .map_err(|e| e.with_phase(GenerationPhase::Decode))?;
+    record(&params, GenerationPhase::Decode, start);
 
     eprintln!(
         "Mel shape: {:?}, synthesizing audio...",
@@ -173,7 +254,15 @@ where
     );
 
     // Step 9: Synthesize audio from mel-spectrogram
-    let audio = models.vocoder.synthesize(&mel)?;
+    let start = Instant::now();
+    let audio = models
+        .vocoder
+        .synthesize(&mel)
+        .map_err(|e| e.with_phase(GenerationPhase::Vocode))?;
+    record(&params, GenerationPhase::Vocode, start);
+    if let Some(audio_progress) = &params.on_audio_progress {
+        audio_progress.report(params.duration_sec, params.duration_sec);
+    }
 
     eprintln!(
         "Generated {} samples ({:.2}s at 44.1kHz)",
@@ -184,6 +273,108 @@ where
     Ok(audio.to_vec())
 }
 
+/// Records the time since `start` as one iteration of `phase`, if timings
+/// were requested.
+fn record(params: &GenerationParams, phase: GenerationPhase, start: Instant) {
+    if let Some(timings) = &params.timings {
+        timings.record(phase, start.elapsed());
+    }
+}
+
+/// Prompt used for the unconditional branch of classifier-free guidance.
+pub const UNCONDITIONAL_PROMPT: &str = "";
+
+/// Prefix of context cache keys for prompt blends. Sanitized prompts never
+/// contain NUL, so blend keys cannot collide with plain prompts.
+const BLEND_CACHE_KEY_PREFIX: &str = "\u{0}blend:";
+
+/// Returns the transformer context for `prompt`, encoding it on a cache miss.
+///
+/// The prompt is canonicalized first, so the encoded text matches the cache
+/// key.
+fn prompt_context(models: &mut AceStepModels, prompt: &str) -> Result<Arc<PromptContext>> {
+    let prompt = &canonicalize(prompt);
+    if let Some(context) = models.context_cache.get(prompt, UNCONDITIONAL_PROMPT) {
+        eprintln!("Reusing cached context for prompt: \"{}\"", DisplayPrompt::new(prompt));
+        return Ok(context);
+    }
+
+    eprintln!("Encoding prompt: \"{}\"", DisplayPrompt::new(prompt));
+    let (text_hidden_states, text_attention_mask) = models.text_encoder.encode(prompt)?;
+    encode_prompt_context(models, prompt, &text_hidden_states, &text_attention_mask)
+}
+
+/// Returns the transformer context for a weighted blend of prompts.
+///
+/// Each prompt is encoded separately and the text hidden states are averaged
+/// by weight before the transformer context is computed.
+fn blended_prompt_context(
+    models: &mut AceStepModels,
+    blend: &[(String, f32)],
+) -> Result<Arc<PromptContext>> {
+    let blend =
+        normalize_blend(blend).map_err(|e| DaemonError::new(ErrorCode::InvalidPrompt, e))?;
+    let label = blend_label(&blend);
+    let cache_key = format!("{}{}", BLEND_CACHE_KEY_PREFIX, label);
+    if let Some(context) = models.context_cache.get(&cache_key, UNCONDITIONAL_PROMPT) {
+        eprintln!("Reusing cached context for blend: \"{}\"", DisplayPrompt::new(&label));
+        return Ok(context);
+    }
+
+    eprintln!("Encoding prompt blend: \"{}\"", DisplayPrompt::new(&label));
+    let encoded = blend
+        .iter()
+        .map(|(prompt, _)| models.text_encoder.encode(&canonicalize(prompt)))
+        .collect::<Result<Vec<_>>>()?;
+    let weights: Vec<f32> = blend.iter().map(|(_, weight)| *weight).collect();
+    let (text_hidden_states, text_attention_mask) = blend_hidden_states(&encoded, &weights);
+    encode_prompt_context(models, &cache_key, &text_hidden_states, &text_attention_mask)
+}
+
+/// Computes the transformer context for encoded text and the unconditional
+/// prompt, and caches it under `cache_key`.
+fn encode_prompt_context(
+    models: &mut AceStepModels,
+    cache_key: &str,
+    text_hidden_states: &Array3<f32>,
+    text_attention_mask: &Array2<i64>,
+) -> Result<Arc<PromptContext>> {
+    let (uncond_text_hidden_states, uncond_text_attention_mask) =
+        models.text_encoder.encode(UNCONDITIONAL_PROMPT)?;
+
+    eprintln!("Encoding transformer context...");
+    let (cond_context, cond_mask) = models
+        .transformer
+        .encode_context(text_hidden_states, text_attention_mask)?;
+    let (uncond_context, uncond_mask) = models
+        .transformer
+        .encode_context(&uncond_text_hidden_states, &uncond_text_attention_mask)?;
+
+    let context = PromptContext {
+        cond_context,
+        cond_mask,
+        uncond_context,
+        uncond_mask,
+    };
+    Ok(models
+        .context_cache
+        .insert(cache_key, UNCONDITIONAL_PROMPT, context))
+}
+
+/// Returns the diffusion steps `inference_steps: "auto"` picks for a clip.
+///
+/// Starts at `auto_steps_min` for the shortest clips and adds
+/// `auto_steps_per_sec` for every second of audio, up to `auto_steps_max`.
+/// Short clips sound fine with few steps, while long ones need more to stay
+/// coherent.
+pub fn auto_inference_steps(duration_sec: f32, config: &AceStepConfig) -> u32 {
+    let extra = (duration_sec.max(0.0) * config.auto_steps_per_sec).floor() as u32;
+    config
+        .auto_steps_min
+        .saturating_add(extra)
+        .clamp(config.auto_steps_min, config.auto_steps_max)
+}
+
 /// Estimates the generation time based on parameters.
 pub fn estimate_generation_time(_duration_sec: f32, inference_steps: u32) -> f32 {
     let step_time = 0.2;
@@ -199,10 +390,33 @@ mod tests {
     fn default_params() {
         let params = GenerationParams::default();
         assert_eq!(params.inference_steps, 60);
-        assert_eq!(params.guidance_scale, DEFAULT_GUIDANCE_SCALE);
+        assert_eq!(params.guidance_scale, AceStepConfig::default().guidance_scale);
         assert_eq!(params.scheduler, SchedulerType::Euler);
     }
 
+    #[test]
+    fn auto_steps_follow_duration() {
+        let config = AceStepConfig::default();
+        assert_eq!(auto_inference_steps(0.0, &config), 27);
+        assert_eq!(auto_inference_steps(5.0, &config), 28);
+        assert_eq!(auto_inference_steps(30.0, &config), 34);
+        assert_eq!(auto_inference_steps(120.0, &config), 57);
+        assert_eq!(auto_inference_steps(212.0, &config), 80);
+        assert_eq!(auto_inference_steps(240.0, &config), 80);
+    }
+
+    #[test]
+    fn auto_steps_use_configured_curve() {
+        let config = AceStepConfig {
+            auto_steps_min: 10,
+            auto_steps_per_sec: 1.0,
+            auto_steps_max: 40,
+            ..AceStepConfig::default()
+        };
+        assert_eq!(auto_inference_steps(5.0, &config), 15);
+        assert_eq!(auto_inference_steps(60.0, &config), 40);
+    }
+
     #[test]
     fn estimate_generation_reasonable() {
         let estimate = estimate_generation_time(30.0, 60);
@@ -5,9 +5,7 @@
 
 use ndarray::{Array4, Zip};
 
-/// Default guidance scale for ACE-Step.
-/// Higher values = stronger prompt adherence.
-pub const DEFAULT_GUIDANCE_SCALE: f32 = 7.0;
+use crate::error::{DaemonError, Result};
 
 /// Minimum guidance scale (essentially no guidance).
 pub const MIN_GUIDANCE_SCALE: f32 = 1.0;
@@ -15,6 +13,32 @@ pub const MIN_GUIDANCE_SCALE: f32 = 1.0;
 /// Maximum guidance scale (very strong guidance).
 pub const MAX_GUIDANCE_SCALE: f32 = 20.0;
 
+/// Guidance scales closer than this to 1.0 are treated as no guidance.
+pub const UNGUIDED_TOLERANCE: f32 = 1e-3;
+
+/// Bound applied to noise predictions before guidance is computed.
+const INPUT_CLAMP: f32 = 50.0;
+
+/// Bound applied to the guided noise prediction.
+const OUTPUT_CLAMP: f32 = 20.0;
+
+/// Replaces non-finite values in a noise prediction.
+///
+/// NaN becomes 0.0 and all values (including infinities) are clamped to
+/// [-50.0, 50.0], so a single bad element cannot poison the guided output.
+pub fn sanitize_tensor(arr: &Array4<f32>) -> Array4<f32> {
+    arr.mapv(sanitize)
+}
+
+/// Sanitizes a single noise prediction value, see [`sanitize_tensor`].
+fn sanitize(v: f32) -> f32 {
+    if v.is_nan() {
+        0.0
+    } else {
+        v.clamp(-INPUT_CLAMP, INPUT_CLAMP)
+    }
+}
+
 /// Applies classifier-free guidance to noise predictions.
 ///
 /// CFG formula: output = uncond + scale * (cond - uncond)
@@ -27,54 +51,137 @@ pub const MAX_GUIDANCE_SCALE: f32 = 20.0;
 ///
 /// # Returns
 ///
-/// Guided noise prediction combining both conditional and unconditional paths.
+/// Guided noise prediction combining both conditional and unconditional paths,
+/// clamped to [-20.0, 20.0]. Inputs are sanitized with [`sanitize_tensor`] first.
+///
+/// # Errors
+///
+/// Returns `INVALID_GUIDANCE_SCALE` if the scale is outside
+/// [`MIN_GUIDANCE_SCALE`]..=[`MAX_GUIDANCE_SCALE`] or not finite.
 ///
 /// # Example
 ///
 /// ```ignore
 /// use lofi_daemon::models::ace_step::guidance::apply_cfg;
 ///
-/// let guided = apply_cfg(&cond_noise, &uncond_noise, 7.0);
+/// let guided = apply_cfg(&cond_noise, &uncond_noise, 7.0)?;
 /// ```
 pub fn apply_cfg(
     cond_noise: &Array4<f32>,
     uncond_noise: &Array4<f32>,
     guidance_scale: f32,
-) -> Array4<f32> {
+) -> Result<Array4<f32>> {
+    let mut result = Array4::zeros(cond_noise.raw_dim());
+    apply_cfg_into(cond_noise, uncond_noise, guidance_scale, &mut result)?;
+    Ok(result)
+}
+
+/// Applies classifier-free guidance like [`apply_cfg`], writing the guided
+/// noise prediction into `out`.
+///
+/// Sanitizing, guidance and clamping happen in a single pass over the
+/// inputs. `out` is reshaped to the inputs' shape if needed, so a buffer
+/// reused across diffusion steps is only allocated once.
+///
+/// # Errors
+///
+/// Returns `INVALID_GUIDANCE_SCALE` for an out-of-range scale; `out` is
+/// left unchanged.
+pub fn apply_cfg_into(
+    cond_noise: &Array4<f32>,
+    uncond_noise: &Array4<f32>,
+    guidance_scale: f32,
+    out: &mut Array4<f32>,
+) -> Result<()> {
+    validate_guidance_scale(guidance_scale)?;
+    ensure_shape(out, cond_noise);
+
     // CFG: output = uncond + scale * (cond - uncond)
     // Which simplifies to: output = (1 - scale) * uncond + scale * cond
     // But the first form is more numerically stable
-
-    let mut result = Array4::zeros(cond_noise.raw_dim());
-
-    Zip::from(&mut result)
+    Zip::from(out)
         .and(cond_noise)
         .and(uncond_noise)
         .for_each(|r, &c, &u| {
-            *r = u + guidance_scale * (c - u);
+            let (c, u) = (sanitize(c), sanitize(u));
+            *r = (u + guidance_scale * (c - u)).clamp(-OUTPUT_CLAMP, OUTPUT_CLAMP);
         });
 
-    result
+    Ok(())
+}
+
+/// Reallocates `out` with the shape of `like` if the shapes differ.
+fn ensure_shape(out: &mut Array4<f32>, like: &Array4<f32>) {
+    if out.raw_dim() != like.raw_dim() {
+        *out = Array4::zeros(like.raw_dim());
+    }
+}
+
+/// Returns true if `guidance_scale` is effectively 1.0.
+///
+/// At 1.0 the CFG formula reduces to the conditional prediction, so the
+/// unconditional one contributes nothing.
+pub fn is_unguided(guidance_scale: f32) -> bool {
+    (guidance_scale - 1.0).abs() < UNGUIDED_TOLERANCE
+}
+
+/// Predicts the guided noise for one diffusion step.
+///
+/// `predict` is called with `true` for the conditional prediction and with
+/// `false` for the unconditional one. When [`is_unguided`] holds, only the
+/// conditional prediction is made, sanitized and clamped the way
+/// [`apply_cfg`] would, which halves the transformer calls per step.
+///
+/// # Errors
+///
+/// Returns `INVALID_GUIDANCE_SCALE` for an out-of-range scale, or the error
+/// of `predict`.
+pub fn predict_guided_noise<F>(guidance_scale: f32, predict: F) -> Result<Array4<f32>>
+where
+    F: FnMut(bool) -> Result<Array4<f32>>,
+{
+    let mut guided = Array4::zeros((0, 0, 0, 0));
+    predict_guided_noise_into(guidance_scale, predict, &mut guided)?;
+    Ok(guided)
+}
+
+/// Predicts the guided noise for one diffusion step like
+/// [`predict_guided_noise`], writing it into `out`.
+///
+/// The diffusion loop passes the same buffer every step, so guidance does
+/// not allocate beyond the predictions themselves.
+pub fn predict_guided_noise_into<F>(
+    guidance_scale: f32,
+    mut predict: F,
+    out: &mut Array4<f32>,
+) -> Result<()>
+where
+    F: FnMut(bool) -> Result<Array4<f32>>,
+{
+    validate_guidance_scale(guidance_scale)?;
+
+    let cond_noise = predict(true)?;
+    if is_unguided(guidance_scale) {
+        ensure_shape(out, &cond_noise);
+        Zip::from(out)
+            .and(&cond_noise)
+            .for_each(|r, &c| *r = sanitize(c).clamp(-OUTPUT_CLAMP, OUTPUT_CLAMP));
+        return Ok(());
+    }
+    let uncond_noise = predict(false)?;
+    apply_cfg_into(&cond_noise, &uncond_noise, guidance_scale, out)
 }
 
 /// Validates a guidance scale value.
 ///
-/// Returns an error message if the scale is outside the valid range.
-pub fn validate_guidance_scale(scale: f32) -> Option<String> {
-    if scale < MIN_GUIDANCE_SCALE {
-        Some(format!(
-            "Guidance scale {} is below minimum {}",
-            scale, MIN_GUIDANCE_SCALE
-        ))
-    } else if scale > MAX_GUIDANCE_SCALE {
-        Some(format!(
-            "Guidance scale {} exceeds maximum {}",
-            scale, MAX_GUIDANCE_SCALE
-        ))
-    } else if scale.is_nan() || scale.is_infinite() {
-        Some("Guidance scale must be a finite number".to_string())
+/// Returns `INVALID_GUIDANCE_SCALE` if the scale is outside the valid range
+/// or not a finite number.
+pub fn validate_guidance_scale(scale: f32) -> Result<()> {
+    if (MIN_GUIDANCE_SCALE..=MAX_GUIDANCE_SCALE).contains(&scale) {
+        Ok(())
     } else {
-        None
+        // NaN fails the range check as well
+        Err(DaemonError::invalid_guidance_scale(scale))
     }
 }
 
@@ -89,22 +196,20 @@ mod tests {
         let cond = Array4::from_elem((1, 2, 2, 2), 1.0f32);
         let uncond = Array4::from_elem((1, 2, 2, 2), 0.0f32);
 
-        let result = apply_cfg(&cond, &uncond, 1.0);
+        let result = apply_cfg(&cond, &uncond, 1.0).unwrap();
 
         // uncond + 1.0 * (cond - uncond) = uncond + cond - uncond = cond
         assert!((result[[0, 0, 0, 0]] - 1.0).abs() < 1e-6);
     }
 
     #[test]
-    fn cfg_with_scale_0() {
-        // With scale=0, should return unconditional output
+    fn cfg_rejects_scale_below_minimum() {
+        // Scales below 1.0 would weaken the prompt and are rejected
         let cond = Array4::from_elem((1, 2, 2, 2), 1.0f32);
         let uncond = Array4::from_elem((1, 2, 2, 2), 0.5f32);
 
-        let result = apply_cfg(&cond, &uncond, 0.0);
-
-        // uncond + 0.0 * (cond - uncond) = uncond
-        assert!((result[[0, 0, 0, 0]] - 0.5).abs() < 1e-6);
+        let err = apply_cfg(&cond, &uncond, 0.0).unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::InvalidGuidanceScale);
     }
 
     #[test]
@@ -112,24 +217,133 @@ mod tests {
         let cond = Array4::from_elem((1, 2, 2, 2), 1.0f32);
         let uncond = Array4::from_elem((1, 2, 2, 2), 0.0f32);
 
-        let result = apply_cfg(&cond, &uncond, DEFAULT_GUIDANCE_SCALE);
+        let result = apply_cfg(&cond, &uncond, 7.0).unwrap();
 
         // uncond + 7.0 * (cond - uncond) = 0 + 7.0 * 1 = 7.0
         assert!((result[[0, 0, 0, 0]] - 7.0).abs() < 1e-6);
     }
 
+    #[test]
+    fn sanitize_replaces_nan_and_clamps() {
+        let mut arr = Array4::from_elem((1, 1, 1, 4), 1.0f32);
+        arr[[0, 0, 0, 0]] = f32::NAN;
+        arr[[0, 0, 0, 1]] = f32::INFINITY;
+        arr[[0, 0, 0, 2]] = -1000.0;
+
+        let clean = sanitize_tensor(&arr);
+        assert_eq!(clean[[0, 0, 0, 0]], 0.0);
+        assert_eq!(clean[[0, 0, 0, 1]], 50.0);
+        assert_eq!(clean[[0, 0, 0, 2]], -50.0);
+        assert_eq!(clean[[0, 0, 0, 3]], 1.0);
+    }
+
+    #[test]
+    fn cfg_with_corrupt_inputs_stays_finite_and_bounded() {
+        let mut cond = Array4::from_elem((1, 2, 2, 2), 1.0f32);
+        let mut uncond = Array4::from_elem((1, 2, 2, 2), 0.0f32);
+        cond[[0, 0, 0, 0]] = f32::NAN;
+        cond[[0, 1, 1, 1]] = f32::INFINITY;
+        uncond[[0, 1, 0, 0]] = f32::NEG_INFINITY;
+
+        let result = apply_cfg(&cond, &uncond, MAX_GUIDANCE_SCALE).unwrap();
+
+        assert!(result.iter().all(|v| v.is_finite()));
+        assert!(result.iter().all(|v| v.abs() <= 20.0));
+        // NaN in cond became 0.0, matching uncond, so guidance has no effect there
+        assert_eq!(result[[0, 0, 0, 0]], 0.0);
+    }
+
+    #[test]
+    fn cfg_into_matches_sanitized_cfg() {
+        let cond = Array4::from_shape_fn((1, 2, 3, 4), |(_, c, h, w)| {
+            (c as f32 - 0.5) * 40.0 + h as f32 * 0.7 - w as f32 * 1.3
+        });
+        let mut uncond = Array4::from_shape_fn((1, 2, 3, 4), |(_, c, h, w)| {
+            c as f32 * 2.0 - h as f32 + w as f32 * 0.1
+        });
+        uncond[[0, 1, 2, 3]] = f32::NAN;
+
+        // Sanitizing whole tensors first, as guidance used to
+        let expected = Zip::from(&sanitize_tensor(&cond))
+            .and(&sanitize_tensor(&uncond))
+            .map_collect(|&c, &u| (u + 7.0 * (c - u)).clamp(-OUTPUT_CLAMP, OUTPUT_CLAMP));
+
+        // A buffer of another shape, as on the first step, is replaced
+        let mut out = Array4::zeros((1, 1, 1, 1));
+        apply_cfg_into(&cond, &uncond, 7.0, &mut out).unwrap();
+        assert_eq!(out, expected);
+
+        let before = out.clone();
+        assert!(apply_cfg_into(&cond, &uncond, 0.5, &mut out).is_err());
+        assert_eq!(out, before);
+    }
+
     #[test]
     fn validate_valid_scales() {
-        assert!(validate_guidance_scale(1.0).is_none());
-        assert!(validate_guidance_scale(7.0).is_none());
-        assert!(validate_guidance_scale(20.0).is_none());
+        assert!(validate_guidance_scale(1.0).is_ok());
+        assert!(validate_guidance_scale(7.0).is_ok());
+        assert!(validate_guidance_scale(20.0).is_ok());
     }
 
     #[test]
     fn validate_invalid_scales() {
-        assert!(validate_guidance_scale(0.5).is_some());
-        assert!(validate_guidance_scale(25.0).is_some());
-        assert!(validate_guidance_scale(f32::NAN).is_some());
-        assert!(validate_guidance_scale(f32::INFINITY).is_some());
+        assert!(validate_guidance_scale(0.5).is_err());
+        assert!(validate_guidance_scale(25.0).is_err());
+        assert!(validate_guidance_scale(f32::NAN).is_err());
+        assert!(validate_guidance_scale(f32::INFINITY).is_err());
+    }
+
+    #[test]
+    fn unguided_prediction_matches_cfg() {
+        let cond = Array4::from_shape_fn((1, 2, 3, 4), |(_, c, h, w)| {
+            (c as f32 - 0.5) * 30.0 + h as f32 * 0.7 - w as f32 * 1.3
+        });
+        let uncond = Array4::from_shape_fn((1, 2, 3, 4), |(_, c, h, w)| {
+            c as f32 * 2.0 - h as f32 + w as f32 * 0.1
+        });
+
+        let mut calls = Vec::new();
+        let skipped = predict_guided_noise(1.0, |conditional| {
+            calls.push(conditional);
+            Ok(if conditional { cond.clone() } else { uncond.clone() })
+        })
+        .unwrap();
+        assert_eq!(calls, vec![true]);
+
+        let full = apply_cfg(&cond, &uncond, 1.0).unwrap();
+        for (a, b) in skipped.iter().zip(full.iter()) {
+            assert!((a - b).abs() < 1e-5, "{} vs {}", a, b);
+        }
+
+        calls.clear();
+        let guided = predict_guided_noise(7.0, |conditional| {
+            calls.push(conditional);
+            Ok(if conditional { cond.clone() } else { uncond.clone() })
+        })
+        .unwrap();
+        assert_eq!(calls, vec![true, false]);
+        assert_eq!(guided, apply_cfg(&cond, &uncond, 7.0).unwrap());
+        assert!(predict_guided_noise(0.5, |_| Ok(cond.clone())).is_err());
+    }
+
+    #[test]
+    fn unguided_prediction_skips_unconditional_cost() {
+        use std::time::{Duration, Instant};
+
+        let predict = |_| {
+            std::thread::sleep(Duration::from_millis(20));
+            Ok(Array4::zeros((1, 1, 1, 1)))
+        };
+
+        let start = Instant::now();
+        predict_guided_noise(1.0, predict).unwrap();
+        let unguided = start.elapsed();
+
+        let start = Instant::now();
+        predict_guided_noise(7.0, predict).unwrap();
+        let guided = start.elapsed();
+
+        assert!(unguided < guided, "{:?} vs {:?}", unguided, guided);
+        assert!(guided >= Duration::from_millis(40));
     }
 }
@@ -4,10 +4,10 @@
 //! used in the diffusion process.
 
 use ndarray::Array4;
-use rand::Rng;
 use rand::SeedableRng;
 use rand_chacha::ChaCha8Rng;
 
+use super::noise::fill_standard_normal;
 use super::transformer::{LATENT_CHANNELS, LATENT_HEIGHT};
 
 /// Sample rate used for frame length calculation (vocoder native rate).
@@ -16,11 +16,19 @@ const SAMPLE_RATE: f32 = 44100.0;
 /// Hop length for the DCAE (samples per latent frame, after 8x compression).
 const HOP_LENGTH: f32 = 512.0 * 8.0; // 4096
 
+/// Fraction of a frame ignored when rounding a duration up to whole frames,
+/// so that float error in a duration computed from a frame count does not
+/// add a frame (about 0.1 ms).
+const FRAME_ROUNDING_TOLERANCE: f64 = 1e-3;
+
 /// Initializes a latent tensor with random Gaussian noise.
 ///
 /// For Flow Matching, the initial latent is pure standard normal noise
 /// (NOT scaled by sigma - that's for Karras/EDM diffusion).
 ///
+/// The noise depends only on the seed and shape, never on the sigma schedule,
+/// so the same seed starts from the same latent at any `inference_steps`.
+///
 /// # Arguments
 ///
 /// * `batch_size` - Number of samples to generate (typically 1)
@@ -44,23 +52,9 @@ pub fn initialize_latent(
     // Use ChaCha8 for reproducible random generation
     let mut rng = ChaCha8Rng::seed_from_u64(seed);
 
-    // Generate standard normal samples using Box-Muller transform
-    let mut samples = Vec::with_capacity(total_elements);
-    while samples.len() < total_elements {
-        // Box-Muller transform for Gaussian samples
-        let u1: f32 = rng.gen_range(1e-10..1.0); // Avoid log(0)
-        let u2: f32 = rng.gen_range(0.0..1.0);
-
-        let mag = (-2.0 * u1.ln()).sqrt();
-        let z0 = mag * (2.0 * std::f32::consts::PI * u2).cos();
-        let z1 = mag * (2.0 * std::f32::consts::PI * u2).sin();
-
-        // Flow Matching uses unscaled standard normal noise
-        samples.push(z0);
-        if samples.len() < total_elements {
-            samples.push(z1);
-        }
-    }
+    // Flow Matching uses unscaled standard normal noise
+    let mut samples = vec![0.0; total_elements];
+    fill_standard_normal(&mut samples, &mut rng);
 
     Array4::from_shape_vec(shape, samples)
         .expect("Shape calculation should be correct")
@@ -69,7 +63,9 @@ pub fn initialize_latent(
 /// Calculates the latent frame length from audio duration.
 ///
 /// The frame length determines the temporal resolution of the latent.
-/// Formula: frame_length = duration_sec * sample_rate / hop_length
+/// Formula: frame_length = duration_sec * sample_rate / hop_length, rounded
+/// up to whole frames. Durations less than [`FRAME_ROUNDING_TOLERANCE`] of a
+/// frame past a whole frame round down instead.
 ///
 /// # Arguments
 ///
@@ -90,12 +86,17 @@ pub fn calculate_frame_length(duration_sec: f32) -> usize {
     // frame_length = duration_sec * sample_rate / hop_length
     // = duration_sec * 44100 / 4096
     // ≈ duration_sec * 10.77
-    ((duration_sec * SAMPLE_RATE / HOP_LENGTH).ceil() as usize).max(1)
+    let frames = duration_sec as f64 * SAMPLE_RATE as f64 / HOP_LENGTH as f64;
+    ((frames - FRAME_ROUNDING_TOLERANCE).ceil() as usize).max(1)
 }
 
 /// Estimates the output audio duration from frame length.
 ///
-/// This is the inverse of `calculate_frame_length`.
+/// This is the inverse of `calculate_frame_length`: frame lengths round
+/// trip exactly, and since durations round up to whole frames,
+/// `estimate_duration(calculate_frame_length(d))` exceeds `d` by less than
+/// one frame (about 0.093 s), and falls short by at most the rounding
+/// tolerance.
 ///
 /// # Arguments
 ///
@@ -206,6 +207,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn initialize_latent_independent_of_step_count() {
+        use crate::models::ace_step::scheduler::{create_scheduler, SchedulerType};
+
+        let sigma_30 = create_scheduler(SchedulerType::Euler, 30, 42).sigma();
+        let sigma_60 = create_scheduler(SchedulerType::Euler, 60, 42).sigma();
+        // The shifted flow matching schedule always starts at sigma 1.0
+        assert_eq!(sigma_30, 1.0);
+        assert_eq!(sigma_60, 1.0);
+
+        let latent_30 = initialize_latent(1, 50, sigma_30, 42);
+        let latent_60 = initialize_latent(1, 50, sigma_60, 42);
+        assert_eq!(latent_30, latent_60);
+    }
+
+    /// Sweeps every supported duration in 0.1s steps instead of sampling
+    /// with proptest, which is not a dependency: the whole range is small
+    /// enough to check exhaustively.
+    #[test]
+    fn estimate_duration_within_one_frame() {
+        let frame_sec = estimate_duration(1);
+        // Every 0.1s from 5s to 240s
+        for tenths in 50..=2400 {
+            let duration = tenths as f32 / 10.0;
+            let estimated = estimate_duration(calculate_frame_length(duration));
+            // Frame length rounds up, so the estimate never falls short
+            assert!(
+                estimated >= duration - 1e-3 && estimated - duration < frame_sec,
+                "Duration {} -> {:.4}s (frame is {:.4}s)",
+                duration,
+                estimated,
+                frame_sec
+            );
+        }
+    }
+
+    /// Checks every frame count from 40 to 1920 exhaustively, like
+    /// `estimate_duration_within_one_frame`, instead of sampling with proptest.
+    #[test]
+    fn frame_length_round_trips_through_duration() {
+        for frames in 40..=1920 {
+            assert_eq!(
+                calculate_frame_length(estimate_duration(frames)),
+                frames,
+                "{} frames -> {}s",
+                frames,
+                estimate_duration(frames)
+            );
+        }
+    }
+
     #[test]
     fn estimate_duration_inverse() {
         for duration in [5.0, 30.0, 60.0, 120.0, 240.0] {
@@ -7,6 +7,8 @@
 //! ## Components
 //!
 //! - [`models`]: Model loader for all ACE-Step ONNX components
+//! - [`blend`]: Weighted blending of several prompts in embedding space
+//! - [`context_cache`]: LRU cache of encoded prompt contexts
 //! - [`text_encoder`]: UMT5 text encoder for prompt conditioning
 //! - [`transformer`]: Diffusion transformer for noise prediction
 //! - [`decoder`]: DCAE latent decoder for mel-spectrogram generation
@@ -14,24 +16,40 @@
 //! - [`scheduler`]: Diffusion schedulers (Euler, Heun, PingPong)
 //! - [`guidance`]: Classifier-free guidance implementation
 //! - [`latent`]: Latent space initialization and utilities
+//! - [`noise`]: Bulk standard normal noise generation
 //! - [`generate`]: Complete generation pipeline
 
+pub mod blend;
+pub mod context_cache;
 pub mod decoder;
 pub mod generate;
 pub mod guidance;
 pub mod latent;
 pub mod models;
+pub mod noise;
 pub mod scheduler;
 pub mod text_encoder;
 pub mod transformer;
 pub mod vocoder;
 
 // Re-export commonly used types
-pub use generate::{generate, generate_with_progress, GenerationParams};
-pub use guidance::{apply_cfg, DEFAULT_GUIDANCE_SCALE, MAX_GUIDANCE_SCALE, MIN_GUIDANCE_SCALE};
+pub use blend::{blend_hidden_states, blend_label, normalize_blend, MAX_BLEND_PROMPTS};
+pub use context_cache::{ContextCache, PromptContext, DEFAULT_CONTEXT_CACHE_SIZE};
+pub use generate::{
+    auto_inference_steps, generate, generate_with_progress, GenerationParams, UNCONDITIONAL_PROMPT,
+};
+pub use guidance::{
+    apply_cfg, apply_cfg_into, is_unguided, predict_guided_noise, predict_guided_noise_into,
+    sanitize_tensor, validate_guidance_scale, MAX_GUIDANCE_SCALE, MIN_GUIDANCE_SCALE, UNGUIDED_TOLERANCE,
+};
 pub use latent::{calculate_frame_length, estimate_duration, initialize_latent};
-pub use models::{check_models, load_session, AceStepModels, MODEL_URLS, REQUIRED_FILES};
+pub use models::{
+    check_models, check_models_with_tokenizer, load_session, missing_files, model_url,
+    model_version, AceStepModels, MODEL_URLS, REQUIRED_FILES, TOKENIZER_URL,
+};
+pub use noise::{fill_standard_normal, NOISE_GENERATOR_VERSION};
 pub use scheduler::{
-    create_scheduler, DynScheduler, EulerScheduler, HeunScheduler, PingPongScheduler, Scheduler,
-    SchedulerType,
+    create_scheduler, reuse_scheduler, DynScheduler, EulerScheduler, HeunScheduler, PingPongScheduler, Scheduler,
+    SchedulerType, DEFAULT_INFERENCE_STEPS, DEFAULT_OMEGA, DEFAULT_SHIFT, MAX_INFERENCE_STEPS,
+    MIN_INFERENCE_STEPS,
 };
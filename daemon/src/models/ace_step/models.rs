@@ -11,9 +11,13 @@ use ort::session::Session;
 
 use crate::config::DaemonConfig;
 use crate::error::{DaemonError, Result};
-use crate::models::device::{get_device_name, get_providers};
+use crate::models::device::{get_device_name, get_providers, SessionTuning};
+use crate::models::tokenizer::{check_tokenizer, tokenizer_path, TOKENIZER_FILE};
 
+use super::context_cache::ContextCache;
 use super::decoder::DcaeDecoder;
+use super::noise::NOISE_GENERATOR_VERSION;
+use super::scheduler::DynScheduler;
 use super::text_encoder::Umt5TextEncoder;
 use super::transformer::DiffusionTransformer;
 use super::vocoder::Vocoder;
@@ -28,6 +32,11 @@ pub struct AceStepModels {
     pub decoder: DcaeDecoder,
     /// Vocoder for mel-spectrogram to waveform conversion.
     pub vocoder: Vocoder,
+    /// Encoded prompt contexts reused across generations.
+    pub context_cache: ContextCache,
+    /// Scheduler of the last generation, reset and reused by the next one
+    /// with the same scheduler type.
+    pub scheduler: Option<Box<DynScheduler>>,
     /// Model version string.
     version: String,
     /// Device name used for inference.
@@ -72,13 +81,22 @@ impl AceStepModels {
     /// - `tokenizer.json` - UMT5 tokenizer (~16.8 MB)
     pub fn load(model_dir: &Path, config: &DaemonConfig) -> Result<Self> {
         // Get execution providers based on device config
-        let providers = get_providers(config.device, config.threads);
+        let tuning = SessionTuning::from_config(config);
+        let providers = get_providers(config.device, config.threads, &tuning);
         let device_name = get_device_name(config.device).to_string();
 
         // On macOS, we force fp32 for numerical stability
         let force_fp32 = cfg!(target_os = "macos");
 
-        Self::load_with_providers(model_dir, &providers, &device_name, force_fp32)
+        Self::load_with_providers(
+            model_dir,
+            config.ace_step_tokenizer_path.as_deref(),
+            &providers,
+            &tuning,
+            &device_name,
+            force_fp32,
+            config.effective_dcae_parallelism(),
+        )
     }
 
     /// Loads all ACE-Step models with specific execution providers.
@@ -86,33 +104,39 @@ impl AceStepModels {
     /// # Arguments
     ///
     /// * `model_dir` - Directory containing the ONNX model files
+    /// * `tokenizer_override` - Tokenizer to use instead of the one in `model_dir`
     /// * `providers` - Execution providers for ONNX Runtime
+    /// * `tuning` - ONNX Runtime session settings
     /// * `device_name` - Name of the device for logging
     /// * `force_fp32` - Force fp32 precision (required on macOS)
+    /// * `dcae_parallelism` - Number of DCAE sessions for parallel chunk decoding
     pub fn load_with_providers(
         model_dir: &Path,
+        tokenizer_override: Option<&Path>,
         providers: &[ExecutionProviderDispatch],
+        tuning: &SessionTuning,
         device_name: &str,
         force_fp32: bool,
+        dcae_parallelism: usize,
     ) -> Result<Self> {
         eprintln!("Loading ACE-Step models from {}...", model_dir.display());
         eprintln!("Using device: {} (fp32 forced: {})", device_name, force_fp32);
 
         // Load text encoder
         eprintln!("Loading UMT5 text encoder...");
-        let text_encoder = Umt5TextEncoder::load(model_dir, providers)?;
+        let text_encoder = Umt5TextEncoder::load(model_dir, tokenizer_override, providers, tuning)?;
 
         // Load diffusion transformer (encoder + decoder)
         eprintln!("Loading diffusion transformer...");
-        let transformer = DiffusionTransformer::load(model_dir, providers)?;
+        let transformer = DiffusionTransformer::load(model_dir, providers, tuning)?;
 
         // Load DCAE decoder
-        eprintln!("Loading DCAE decoder...");
-        let decoder = DcaeDecoder::load(model_dir, providers)?;
+        eprintln!("Loading DCAE decoder ({} session(s))...", dcae_parallelism);
+        let decoder = DcaeDecoder::load(model_dir, providers, tuning, dcae_parallelism)?;
 
         // Load vocoder
         eprintln!("Loading vocoder...");
-        let vocoder = Vocoder::load(model_dir, providers)?;
+        let vocoder = Vocoder::load(model_dir, providers, tuning)?;
 
         eprintln!("All ACE-Step models loaded successfully.");
 
@@ -121,12 +145,22 @@ impl AceStepModels {
             transformer,
             decoder,
             vocoder,
-            version: "ace-step-v1".to_string(),
+            context_cache: ContextCache::default(),
+            scheduler: None,
+            version: model_version(),
             device_name: device_name.to_string(),
         })
     }
 }
 
+/// Returns the ACE-Step model version recorded in tracks and track IDs.
+///
+/// Includes the noise generator version, since a seed produces different
+/// audio when the noise changes.
+pub fn model_version() -> String {
+    format!("ace-step-v1-noise{}", NOISE_GENERATOR_VERSION)
+}
+
 /// Required model files for ACE-Step.
 pub const REQUIRED_FILES: &[&str] = &[
     "text_encoder.onnx",
@@ -138,13 +172,14 @@ pub const REQUIRED_FILES: &[&str] = &[
     "tokenizer.json",
 ];
 
+/// Download URL of the ACE-Step UMT5 tokenizer.
+pub const TOKENIZER_URL: &str =
+    "https://huggingface.co/willibrandon/lofi-models/resolve/main/ace-step/tokenizer.json";
+
 /// Download URLs for ACE-Step model files.
 /// Hosted at https://huggingface.co/willibrandon/lofi-models/tree/main/ace-step/
 pub const MODEL_URLS: &[(&str, &str)] = &[
-    (
-        "tokenizer.json",
-        "https://huggingface.co/willibrandon/lofi-models/resolve/main/ace-step/tokenizer.json",
-    ),
+    ("tokenizer.json", TOKENIZER_URL),
     (
         "text_encoder.onnx",
         "https://huggingface.co/willibrandon/lofi-models/resolve/main/ace-step/text_encoder.onnx",
@@ -171,32 +206,72 @@ pub const MODEL_URLS: &[(&str, &str)] = &[
     ),
 ];
 
+/// Returns the download URL for an ACE-Step model file.
+pub fn model_url(file: &str) -> Option<&'static str> {
+    MODEL_URLS
+        .iter()
+        .find(|(name, _)| *name == file)
+        .map(|(_, url)| *url)
+}
+
+/// Returns the required ACE-Step model files that are not present in `model_dir`.
+pub fn missing_files(model_dir: &Path) -> Vec<&'static str> {
+    REQUIRED_FILES
+        .iter()
+        .copied()
+        .filter(|file| !model_dir.join(file).exists())
+        .collect()
+}
+
 /// Checks if all required ACE-Step model files exist.
+///
+/// The error lists each missing file with its download URL, so a partial
+/// install can be completed by fetching only those files.
 pub fn check_models(model_dir: &Path) -> Result<()> {
-    let mut missing = Vec::new();
+    check_models_with_tokenizer(model_dir, None)
+}
 
-    for file in REQUIRED_FILES {
-        let path = model_dir.join(file);
-        if !path.exists() {
-            missing.push(*file);
-        }
+/// Checks the required ACE-Step model files, reading the tokenizer from
+/// `tokenizer_override` instead of the model directory if set.
+///
+/// If only the tokenizer is missing, returns TOKENIZER_MISSING with its
+/// download URL.
+pub fn check_models_with_tokenizer(model_dir: &Path, tokenizer_override: Option<&Path>) -> Result<()> {
+    let tokenizer = tokenizer_path(model_dir, tokenizer_override);
+    let mut missing = missing_files(model_dir);
+    if tokenizer.exists() {
+        missing.retain(|file| *file != TOKENIZER_FILE);
+    } else if !missing.contains(&TOKENIZER_FILE) {
+        missing.push(TOKENIZER_FILE);
     }
 
     if missing.is_empty() {
         Ok(())
+    } else if missing == [TOKENIZER_FILE] {
+        check_tokenizer(&tokenizer, TOKENIZER_URL)
     } else {
+        let files: Vec<String> = missing
+            .iter()
+            .map(|file| match model_url(file) {
+                Some(url) => format!("{} ({})", file, url),
+                None => file.to_string(),
+            })
+            .collect();
         Err(DaemonError::model_not_found(format!(
-            "Missing ACE-Step model files in {}: {}",
+            "Missing {} of {} ACE-Step model files in {}: {}",
+            missing.len(),
+            REQUIRED_FILES.len(),
             model_dir.display(),
-            missing.join(", ")
+            files.join(", ")
         )))
     }
 }
 
-/// Loads an ONNX session from a file with the given providers.
+/// Loads an ONNX session from a file with the given providers and tuning.
 pub fn load_session(
     model_path: &Path,
     providers: &[ExecutionProviderDispatch],
+    tuning: &SessionTuning,
 ) -> Result<Session> {
     if !model_path.exists() {
         return Err(DaemonError::model_not_found(format!(
@@ -205,7 +280,7 @@ pub fn load_session(
         )));
     }
 
-    let builder = Session::builder().map_err(|e| {
+    let builder = tuning.session_builder().map_err(|e| {
         DaemonError::model_load_failed(format!("Failed to create session builder: {}", e))
     })?;
 
@@ -248,4 +323,49 @@ mod tests {
         let result = check_models(path);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn every_required_file_has_a_url() {
+        for file in REQUIRED_FILES {
+            assert!(model_url(file).is_some(), "no URL for {}", file);
+        }
+    }
+
+    #[test]
+    fn check_lists_only_missing_files_with_urls() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in REQUIRED_FILES.iter().filter(|f| **f != "vocoder.onnx") {
+            std::fs::write(dir.path().join(file), b"").unwrap();
+        }
+
+        assert_eq!(missing_files(dir.path()), vec!["vocoder.onnx"]);
+
+        let message = check_models(dir.path()).unwrap_err().to_string();
+        assert!(message.contains("Missing 1 of 7"));
+        assert!(message.contains(&format!("vocoder.onnx ({})", model_url("vocoder.onnx").unwrap())));
+        assert!(!message.contains("tokenizer.json"));
+
+        std::fs::write(dir.path().join("vocoder.onnx"), b"").unwrap();
+        assert!(missing_files(dir.path()).is_empty());
+        assert!(check_models(dir.path()).is_ok());
+    }
+
+    #[test]
+    fn tokenizer_can_come_from_override() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in REQUIRED_FILES.iter().filter(|f| **f != TOKENIZER_FILE) {
+            std::fs::write(dir.path().join(file), b"").unwrap();
+        }
+
+        let err = check_models(dir.path()).unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::TokenizerMissing);
+        assert!(err.message.contains(TOKENIZER_URL));
+
+        let shared = tempfile::NamedTempFile::new().unwrap();
+        assert!(check_models_with_tokenizer(dir.path(), Some(shared.path())).is_ok());
+
+        let missing = dir.path().join("missing.json");
+        let err = check_models_with_tokenizer(dir.path(), Some(&missing)).unwrap_err();
+        assert!(err.message.contains("missing.json"));
+    }
 }
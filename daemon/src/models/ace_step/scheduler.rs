@@ -4,10 +4,26 @@
 //! and FlowMatchPingPongScheduler from the ACE-Step codebase.
 //! These are NOT Karras diffusion schedulers - they use flow matching formulation.
 
-use ndarray::{Array4, Dimension};
+use ndarray::{Array4, ArrayView4, Zip};
 use rand::SeedableRng;
 use rand_chacha::ChaCha8Rng;
-use rand_distr::{Distribution, StandardNormal};
+
+use super::noise::fill_standard_normal;
+
+/// Minimum number of diffusion steps.
+pub const MIN_INFERENCE_STEPS: u32 = 1;
+
+/// Maximum number of diffusion steps.
+pub const MAX_INFERENCE_STEPS: u32 = 200;
+
+/// Number of diffusion steps used unless configured or requested otherwise.
+pub const DEFAULT_INFERENCE_STEPS: u32 = 60;
+
+/// Shift of the flow matching sigma schedule used by ACE-Step.
+pub const DEFAULT_SHIFT: f32 = 3.0;
+
+/// Omega scale for mean shifting used by ACE-Step.
+pub const DEFAULT_OMEGA: f32 = 10.0;
 
 /// Scheduler type for diffusion process.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -22,6 +38,10 @@ pub enum SchedulerType {
 }
 
 impl SchedulerType {
+    /// All scheduler types.
+    pub const ALL: [SchedulerType; 3] =
+        [SchedulerType::Euler, SchedulerType::Heun, SchedulerType::PingPong];
+
     /// Parses a scheduler type from a string.
     pub fn parse(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
@@ -50,8 +70,18 @@ pub trait Scheduler {
     /// Returns the current sigma (noise level, 0.0 to ~1.0).
     fn sigma(&self) -> f32;
 
-    /// Performs one scheduler step to update the latent.
-    fn step(&mut self, latent: &Array4<f32>, model_output: &Array4<f32>) -> Array4<f32>;
+    /// Performs one scheduler step, returning the updated latent.
+    fn step(&mut self, latent: &Array4<f32>, model_output: &Array4<f32>) -> Array4<f32> {
+        let mut next = latent.clone();
+        self.step_in_place(&mut next, model_output);
+        next
+    }
+
+    /// Performs one scheduler step, updating `latent` in place.
+    ///
+    /// Each step makes a single fused pass over the latent after any
+    /// reductions it needs, and reuses the scheduler's buffers across steps.
+    fn step_in_place(&mut self, latent: &mut Array4<f32>, model_output: &Array4<f32>);
 
     /// Returns whether the scheduler has completed all steps.
     fn is_done(&self) -> bool;
@@ -62,9 +92,21 @@ pub trait Scheduler {
     /// Returns the total number of steps.
     fn num_steps(&self) -> u32;
 
-    /// Resets the scheduler to the initial state.
+    /// Resets the scheduler to the state of a freshly constructed one.
     fn reset(&mut self);
 
+    /// Resets the scheduler for a generation with `seed`.
+    ///
+    /// Only stochastic schedulers use the seed.
+    fn reset_with_seed(&mut self, _seed: u64) {
+        self.reset();
+    }
+
+    /// Changes the number of user-visible steps and resets the scheduler.
+    ///
+    /// The schedule is recomputed into the existing buffers.
+    fn set_num_steps(&mut self, num_steps: u32);
+
     /// Returns all sigmas for the schedule.
     fn sigmas(&self) -> &[f32];
 
@@ -85,6 +127,28 @@ pub trait Scheduler {
     fn user_num_steps(&self) -> u32 {
         self.num_steps()
     }
+
+    /// Noises a clean `latent` to noise level `sigma` (0.0 = clean, 1.0 = pure noise).
+    ///
+    /// Flow matching interpolates linearly between data and noise:
+    /// `(1 - sigma) * latent + sigma * noise`. Used to start diffusion partway
+    /// down the schedule from existing audio.
+    fn add_noise(&self, latent: &Array4<f32>, noise: &Array4<f32>, sigma: f32) -> Array4<f32> {
+        let mut noised = latent * (1.0 - sigma);
+        noised.scaled_add(sigma, noise);
+        noised
+    }
+
+    /// Returns the user step at which to start for a denoising `strength`.
+    ///
+    /// A strength of 1.0 starts at step 0 (the whole schedule, from pure
+    /// noise); 0.0 starts at the end, leaving the input unchanged. Strengths
+    /// in between skip the first `(1 - strength)` of the steps.
+    fn start_step_for_strength(&self, strength: f32) -> usize {
+        let num_steps = self.user_num_steps() as usize;
+        let denoised = (strength.clamp(0.0, 1.0) * num_steps as f32).round() as usize;
+        num_steps - denoised.min(num_steps)
+    }
 }
 
 /// Flow Matching Euler scheduler.
@@ -95,6 +159,8 @@ pub trait Scheduler {
 pub struct EulerScheduler {
     /// Total number of inference steps.
     num_steps: u32,
+    /// Shift of the sigma schedule (default 3.0).
+    shift: f32,
     /// Omega scale for mean shifting (default 10.0).
     omega: f32,
     /// Sigma values for each timestep (from ~1.0 to 0.0).
@@ -118,6 +184,7 @@ impl EulerScheduler {
 
         Self {
             num_steps,
+            shift,
             omega,
             sigmas,
             timesteps,
@@ -127,7 +194,7 @@ impl EulerScheduler {
 
     /// Creates a scheduler with default ACE-Step parameters.
     pub fn default_ace_step(num_steps: u32) -> Self {
-        Self::new(num_steps, 3.0, 10.0)
+        Self::new(num_steps, DEFAULT_SHIFT, DEFAULT_OMEGA)
     }
 
     /// Returns the next sigma (noise level for next step).
@@ -145,26 +212,21 @@ impl Scheduler for EulerScheduler {
         self.sigmas[self.current_step]
     }
 
-    fn step(&mut self, latent: &Array4<f32>, model_output: &Array4<f32>) -> Array4<f32> {
+    fn step_in_place(&mut self, latent: &mut Array4<f32>, model_output: &Array4<f32>) {
         let sigma = self.sigma();
         let sigma_next = self.next_sigma();
         let dt = sigma_next - sigma; // This is negative (going from high sigma to low)
 
-        // Compute dx = dt * model_output
-        let dx = model_output.mapv(|v| v * dt);
-
-        // Apply omega mean shifting for stability
+        // dx = dt * model_output, mean-shifted by omega for stability:
+        // x_next = x + (dx - mean(dx)) * omega_scaled + mean(dx)
         let omega_scaled = logistic(self.omega, 0.9, 1.1, 0.0, 0.1);
-        let mean = dx.mean().unwrap_or(0.0);
-        let dx_shifted = dx.mapv(|v| (v - mean) * omega_scaled + mean);
-
-        // Update latent: x_next = x + dx_shifted
-        let next_latent = latent + &dx_shifted;
+        let mean = scaled_mean(model_output, dt);
+        Zip::from(latent)
+            .and(model_output)
+            .for_each(|x, &v| *x += (v * dt - mean) * omega_scaled + mean);
 
         // Advance to next step
         self.current_step += 1;
-
-        next_latent
     }
 
     fn is_done(&self) -> bool {
@@ -183,6 +245,14 @@ impl Scheduler for EulerScheduler {
         self.current_step = 0;
     }
 
+    fn set_num_steps(&mut self, num_steps: u32) {
+        if num_steps != self.num_steps {
+            fill_flow_matching_schedule(num_steps, self.shift, &mut self.sigmas, &mut self.timesteps);
+            self.num_steps = num_steps;
+        }
+        self.reset();
+    }
+
     fn sigmas(&self) -> &[f32] {
         &self.sigmas
     }
@@ -205,6 +275,8 @@ impl Scheduler for EulerScheduler {
 pub struct HeunScheduler {
     /// Total number of user-visible inference steps.
     num_steps: u32,
+    /// Shift of the sigma schedule (default 3.0).
+    shift: f32,
     /// Omega scale for mean shifting (default 10.0).
     omega: f32,
     /// Sigma values for each internal timestep (interleaved for Heun).
@@ -213,57 +285,66 @@ pub struct HeunScheduler {
     timesteps: Vec<f32>,
     /// Current internal step index (0 to 2*num_steps-1).
     current_step: usize,
-    /// Stored derivative from first-order prediction.
-    prev_derivative: Option<Array4<f32>>,
+    /// Derivative from the first-order prediction; a buffer reused across
+    /// steps, valid while `dt` is set.
+    prev_derivative: Array4<f32>,
     /// Stored delta-t from first-order prediction.
     dt: Option<f32>,
-    /// Stored sample from first-order prediction.
-    prev_sample: Option<Array4<f32>>,
+    /// Sample the first-order prediction started from; a buffer reused
+    /// across steps, valid while `dt` is set.
+    prev_sample: Array4<f32>,
 }
 
 impl HeunScheduler {
     /// Creates a new Flow Matching Heun scheduler.
     pub fn new(num_steps: u32, shift: f32, omega: f32) -> Self {
-        let (base_sigmas, _) = compute_flow_matching_schedule(num_steps, shift);
-
-        // Heun scheduler needs interleaved sigmas and timesteps
-        // timesteps[1:].repeat_interleave(2) with timesteps[:1] prepended
-        // sigmas: sigmas[:1], sigmas[1:-1].repeat_interleave(2), sigmas[-1:]
-        let num_train_timesteps = 1000.0_f32;
-
-        // Build interleaved timesteps for Heun
         let mut timesteps = Vec::with_capacity(2 * num_steps as usize - 1);
-        timesteps.push(base_sigmas[0] * num_train_timesteps);
-        for i in 1..num_steps as usize {
-            let t = base_sigmas[i] * num_train_timesteps;
-            timesteps.push(t);
-            timesteps.push(t);
-        }
-
-        // Build interleaved sigmas for Heun
         let mut sigmas = Vec::with_capacity(2 * num_steps as usize);
-        sigmas.push(base_sigmas[0]);
-        for i in 1..base_sigmas.len() - 1 {
-            sigmas.push(base_sigmas[i]);
-            sigmas.push(base_sigmas[i]);
-        }
-        sigmas.push(0.0); // Final sigma
+        Self::fill_schedule(num_steps, shift, &mut sigmas, &mut timesteps);
 
         Self {
             num_steps,
+            shift,
             omega,
             sigmas,
             timesteps,
             current_step: 0,
-            prev_derivative: None,
+            prev_derivative: Array4::zeros((0, 0, 0, 0)),
             dt: None,
-            prev_sample: None,
+            prev_sample: Array4::zeros((0, 0, 0, 0)),
         }
     }
 
     /// Creates a scheduler with default ACE-Step parameters.
     pub fn default_ace_step(num_steps: u32) -> Self {
-        Self::new(num_steps, 3.0, 10.0)
+        Self::new(num_steps, DEFAULT_SHIFT, DEFAULT_OMEGA)
+    }
+
+    /// Writes the interleaved Heun schedule into existing buffers.
+    fn fill_schedule(num_steps: u32, shift: f32, sigmas: &mut Vec<f32>, timesteps: &mut Vec<f32>) {
+        // Heun scheduler needs interleaved sigmas and timesteps
+        // timesteps[1:].repeat_interleave(2) with timesteps[:1] prepended
+        // sigmas: sigmas[:1], sigmas[1:-1].repeat_interleave(2), sigmas[-1:]
+        let num_train_timesteps = 1000.0_f32;
+        let base_sigma = |i: u32| shifted_sigma(i, num_steps, shift);
+
+        // Build interleaved timesteps for Heun
+        timesteps.clear();
+        timesteps.push(base_sigma(0) * num_train_timesteps);
+        for i in 1..num_steps {
+            let t = base_sigma(i) * num_train_timesteps;
+            timesteps.push(t);
+            timesteps.push(t);
+        }
+
+        // Build interleaved sigmas for Heun
+        sigmas.clear();
+        sigmas.push(base_sigma(0));
+        for i in 1..num_steps {
+            sigmas.push(base_sigma(i));
+            sigmas.push(base_sigma(i));
+        }
+        sigmas.push(0.0); // Final sigma
     }
 
     /// Returns true if in first-order (prediction) state.
@@ -281,7 +362,7 @@ impl Scheduler for HeunScheduler {
         self.sigmas[self.current_step]
     }
 
-    fn step(&mut self, latent: &Array4<f32>, model_output: &Array4<f32>) -> Array4<f32> {
+    fn step_in_place(&mut self, latent: &mut Array4<f32>, model_output: &Array4<f32>) {
         let omega_scaled = logistic(self.omega, 0.9, 1.1, 0.0, 0.1);
 
         if self.state_in_first_order() {
@@ -289,61 +370,60 @@ impl Scheduler for HeunScheduler {
             let sigma = self.sigmas[self.current_step];
             let sigma_next = self.sigmas[self.current_step + 1];
             let sigma_hat = sigma;
-
-            // 1. Compute denoised prediction
-            let denoised = latent - &model_output.mapv(|v| v * sigma);
-
-            // 2. Compute derivative
-            let derivative = (latent - &denoised).mapv(|v| v / sigma_hat);
-
-            // 3. Delta timestep
             let dt = sigma_next - sigma_hat;
 
-            // Store for 2nd order step
-            self.prev_derivative = Some(derivative);
+            // Store the sample and the derivative of the denoised prediction
+            // (x - (x - sigma * v)) / sigma_hat for the 2nd order step
+            ensure_shape(&mut self.prev_derivative, latent);
+            ensure_shape(&mut self.prev_sample, latent);
+            Zip::from(&mut self.prev_derivative)
+                .and(&mut self.prev_sample)
+                .and(&*latent)
+                .and(model_output)
+                .for_each(|d, s, &x, &v| {
+                    let denoised = x - v * sigma;
+                    *d = (x - denoised) / sigma_hat;
+                    *s = x;
+                });
             self.dt = Some(dt);
-            self.prev_sample = Some(latent.clone());
 
             // Advance step
             self.current_step += 1;
 
             // For first order, return predicted next sample for model evaluation
-            let dx = self.prev_derivative.as_ref().unwrap().mapv(|v| v * dt);
-            let mean = dx.mean().unwrap_or(0.0);
-            let dx_shifted = dx.mapv(|v| (v - mean) * omega_scaled + mean);
-            latent + &dx_shifted
+            let mean = scaled_mean(&self.prev_derivative, dt);
+            Zip::from(latent)
+                .and(&self.prev_derivative)
+                .for_each(|x, &d| *x += (d * dt - mean) * omega_scaled + mean);
         } else {
             // Second order: correction step
             let sigma_next = self.sigmas[self.current_step];
-
-            // 1. Compute denoised prediction at predicted point
-            let denoised = latent - &model_output.mapv(|v| v * sigma_next);
-
-            // 2. Compute new derivative
-            let derivative = if sigma_next > 0.0 {
-                (latent - &denoised).mapv(|v| v / sigma_next)
-            } else {
-                Array4::zeros(latent.raw_dim())
-            };
-
-            // 3. Average with previous derivative (Heun's method)
-            let prev_deriv = self.prev_derivative.take().unwrap();
-            let avg_derivative = (&prev_deriv + &derivative).mapv(|v| v * 0.5);
-
-            // 4. Get stored values
             let dt = self.dt.take().unwrap();
-            let sample = self.prev_sample.take().unwrap();
 
-            // 5. Apply update with omega mean shifting
-            let dx = avg_derivative.mapv(|v| v * dt);
-            let mean = dx.mean().unwrap_or(0.0);
-            let dx_shifted = dx.mapv(|v| (v - mean) * omega_scaled + mean);
-            let prev_sample = &sample + &dx_shifted;
+            // Average the derivative at the predicted point with the
+            // previous one (Heun's method)
+            Zip::from(&mut self.prev_derivative)
+                .and(&*latent)
+                .and(model_output)
+                .for_each(|d, &x, &v| {
+                    let derivative = if sigma_next > 0.0 {
+                        let denoised = x - v * sigma_next;
+                        (x - denoised) / sigma_next
+                    } else {
+                        0.0
+                    };
+                    *d = (*d + derivative) * 0.5;
+                });
+
+            // Apply update from the stored sample with omega mean shifting
+            let mean = scaled_mean(&self.prev_derivative, dt);
+            Zip::from(latent)
+                .and(&self.prev_sample)
+                .and(&self.prev_derivative)
+                .for_each(|x, &s, &d| *x = s + ((d * dt - mean) * omega_scaled + mean));
 
             // Advance step
             self.current_step += 1;
-
-            prev_sample
         }
     }
 
@@ -360,11 +440,18 @@ impl Scheduler for HeunScheduler {
         (self.sigmas.len() - 1) as u32
     }
 
+    /// The derivative and sample buffers keep their allocations.
     fn reset(&mut self) {
         self.current_step = 0;
-        self.prev_derivative = None;
         self.dt = None;
-        self.prev_sample = None;
+    }
+
+    fn set_num_steps(&mut self, num_steps: u32) {
+        if num_steps != self.num_steps {
+            Self::fill_schedule(num_steps, self.shift, &mut self.sigmas, &mut self.timesteps);
+            self.num_steps = num_steps;
+        }
+        self.reset();
     }
 
     fn sigmas(&self) -> &[f32] {
@@ -401,6 +488,8 @@ impl Scheduler for HeunScheduler {
 pub struct PingPongScheduler {
     /// Total number of inference steps.
     num_steps: u32,
+    /// Shift of the sigma schedule (default 3.0).
+    shift: f32,
     /// Omega scale for mean shifting (reserved for future use).
     #[allow(dead_code)]
     omega: f32,
@@ -410,8 +499,12 @@ pub struct PingPongScheduler {
     timesteps: Vec<f32>,
     /// Current step index.
     current_step: usize,
+    /// Seed the noise generator starts from, restored on reset.
+    seed: u64,
     /// Random number generator for stochastic noise.
     rng: ChaCha8Rng,
+    /// Noise buffer reused across steps, sized to the latent.
+    noise: Vec<f32>,
 }
 
 impl PingPongScheduler {
@@ -421,17 +514,20 @@ impl PingPongScheduler {
 
         Self {
             num_steps,
+            shift,
             omega,
             sigmas,
             timesteps,
             current_step: 0,
+            seed,
             rng: ChaCha8Rng::seed_from_u64(seed),
+            noise: Vec::new(),
         }
     }
 
     /// Creates a scheduler with default ACE-Step parameters.
     pub fn default_ace_step(num_steps: u32, seed: u64) -> Self {
-        Self::new(num_steps, 3.0, 10.0, seed)
+        Self::new(num_steps, DEFAULT_SHIFT, DEFAULT_OMEGA, seed)
     }
 
     /// Returns the next sigma (noise level for next step).
@@ -449,26 +545,30 @@ impl Scheduler for PingPongScheduler {
         self.sigmas[self.current_step]
     }
 
-    fn step(&mut self, latent: &Array4<f32>, model_output: &Array4<f32>) -> Array4<f32> {
+    fn step_in_place(&mut self, latent: &mut Array4<f32>, model_output: &Array4<f32>) {
         let sigma = self.sigma();
         let sigma_next = self.next_sigma();
 
         // PingPong step (SDE formulation):
-        // 1. Compute denoised sample: denoised = sample - sigma * model_output
-        let denoised = latent - &model_output.mapv(|v| v * sigma);
-
-        // 2. Generate fresh noise for stochastic exploration
-        let noise = generate_noise_like(latent, &mut self.rng);
-
-        // 3. Mix denoised with fresh noise: prev_sample = (1 - sigma_next) * denoised + sigma_next * noise
+        // 1. Generate fresh noise for stochastic exploration
+        self.noise.resize(latent.len(), 0.0);
+        fill_standard_normal(&mut self.noise, &mut self.rng);
+        let noise = ArrayView4::from_shape(latent.raw_dim(), &self.noise)
+            .expect("noise buffer is sized to the latent");
+
+        // 2. Compute the denoised sample, sample - sigma * model_output, and
+        // mix it with the noise: (1 - sigma_next) * denoised + sigma_next * noise
         let one_minus_sigma_next = 1.0 - sigma_next;
-        let prev_sample = denoised.mapv(|v| v * one_minus_sigma_next)
-            + noise.mapv(|v| v * sigma_next);
+        Zip::from(latent)
+            .and(model_output)
+            .and(&noise)
+            .for_each(|x, &v, &n| {
+                let denoised = *x - v * sigma;
+                *x = denoised * one_minus_sigma_next + n * sigma_next;
+            });
 
         // Advance to next step
         self.current_step += 1;
-
-        prev_sample
     }
 
     fn is_done(&self) -> bool {
@@ -483,8 +583,24 @@ impl Scheduler for PingPongScheduler {
         self.num_steps
     }
 
+    /// Also restarts the noise generator from the seed. The noise buffer
+    /// keeps its allocation.
     fn reset(&mut self) {
         self.current_step = 0;
+        self.rng = ChaCha8Rng::seed_from_u64(self.seed);
+    }
+
+    fn reset_with_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.reset();
+    }
+
+    fn set_num_steps(&mut self, num_steps: u32) {
+        if num_steps != self.num_steps {
+            fill_flow_matching_schedule(num_steps, self.shift, &mut self.sigmas, &mut self.timesteps);
+            self.num_steps = num_steps;
+        }
+        self.reset();
     }
 
     fn sigmas(&self) -> &[f32] {
@@ -504,32 +620,56 @@ impl Scheduler for PingPongScheduler {
 ///
 /// Returns (sigmas, timesteps) where sigmas has num_steps + 1 elements (final is 0.0).
 fn compute_flow_matching_schedule(num_steps: u32, shift: f32) -> (Vec<f32>, Vec<f32>) {
+    let mut sigmas = Vec::with_capacity(num_steps as usize + 1);
+    let mut timesteps = Vec::with_capacity(num_steps as usize);
+    fill_flow_matching_schedule(num_steps, shift, &mut sigmas, &mut timesteps);
+    (sigmas, timesteps)
+}
+
+/// Writes the flow matching schedule into existing buffers, reusing their
+/// allocations.
+fn fill_flow_matching_schedule(
+    num_steps: u32,
+    shift: f32,
+    sigmas: &mut Vec<f32>,
+    timesteps: &mut Vec<f32>,
+) {
     let num_train_timesteps = 1000.0_f32;
-    let sigma_max = 1.0_f32;
 
-    // Linear interpolation from max to small positive value with shift applied
-    // Use num_steps as denominator so last sigma is small but non-zero
-    // (prevents division by zero in Heun scheduler)
-    let mut sigmas: Vec<f32> = (0..num_steps)
-        .map(|i| {
-            // t goes from 1.0 to small positive (not 0)
-            let t = sigma_max - (i as f32 / num_steps as f32) * sigma_max;
-            // Apply shift: shift * t / (1 + (shift - 1) * t)
-            shift * t / (1.0 + (shift - 1.0) * t)
-        })
-        .collect();
+    sigmas.clear();
+    sigmas.extend((0..num_steps).map(|i| shifted_sigma(i, num_steps, shift)));
 
     // Append final sigma of 0 (only used as terminal condition)
     sigmas.push(0.0);
 
     // Timesteps are sigmas * num_train_timesteps
-    let timesteps: Vec<f32> = sigmas
-        .iter()
-        .take(num_steps as usize)
-        .map(|s| s * num_train_timesteps)
-        .collect();
+    timesteps.clear();
+    timesteps.extend(sigmas[..num_steps as usize].iter().map(|s| s * num_train_timesteps));
+}
 
-    (sigmas, timesteps)
+/// Returns the shifted sigma of step `i` out of `num_steps`.
+fn shifted_sigma(i: u32, num_steps: u32, shift: f32) -> f32 {
+    let sigma_max = 1.0_f32;
+
+    // Linear interpolation from max to small positive value with shift applied
+    // Use num_steps as denominator so last sigma is small but non-zero
+    // (prevents division by zero in Heun scheduler)
+    let t = sigma_max - (i as f32 / num_steps as f32) * sigma_max;
+    // Apply shift: shift * t / (1 + (shift - 1) * t)
+    shift * t / (1.0 + (shift - 1.0) * t)
+}
+
+/// Returns the mean of `values` scaled by `scale`, without allocating the
+/// scaled array.
+fn scaled_mean(values: &Array4<f32>, scale: f32) -> f32 {
+    values.mean().unwrap_or(0.0) * scale
+}
+
+/// Reallocates `buffer` with the shape of `like` if the shapes differ.
+fn ensure_shape(buffer: &mut Array4<f32>, like: &Array4<f32>) {
+    if buffer.raw_dim() != like.raw_dim() {
+        *buffer = Array4::zeros(like.raw_dim());
+    }
 }
 
 /// Logistic function for omega scaling.
@@ -538,17 +678,6 @@ fn logistic(x: f32, lower: f32, upper: f32, x0: f32, k: f32) -> f32 {
     lower + (upper - lower) / (1.0 + (-k * (x - x0)).exp())
 }
 
-/// Generates random noise with the same shape as the input array.
-fn generate_noise_like(arr: &Array4<f32>, rng: &mut ChaCha8Rng) -> Array4<f32> {
-    let shape = arr.raw_dim();
-    let size = shape.size();
-    let noise: Vec<f32> = (0..size)
-        .map(|_| StandardNormal.sample(rng))
-        .collect();
-
-    Array4::from_shape_vec(shape, noise).unwrap()
-}
-
 // ============================================================================
 // Scheduler enum for dynamic dispatch
 // ============================================================================
@@ -561,6 +690,15 @@ pub enum DynScheduler {
 }
 
 impl DynScheduler {
+    /// Returns the type of the wrapped scheduler.
+    pub fn scheduler_type(&self) -> SchedulerType {
+        match self {
+            DynScheduler::Euler(_) => SchedulerType::Euler,
+            DynScheduler::Heun(_) => SchedulerType::Heun,
+            DynScheduler::PingPong(_) => SchedulerType::PingPong,
+        }
+    }
+
     /// Returns the current timestep value (sigma * 1000).
     pub fn timestep(&self) -> f32 {
         match self {
@@ -588,6 +726,15 @@ impl DynScheduler {
         }
     }
 
+    /// Performs one scheduler step, updating `latent` in place.
+    pub fn step_in_place(&mut self, latent: &mut Array4<f32>, model_output: &Array4<f32>) {
+        match self {
+            DynScheduler::Euler(s) => s.step_in_place(latent, model_output),
+            DynScheduler::Heun(s) => s.step_in_place(latent, model_output),
+            DynScheduler::PingPong(s) => s.step_in_place(latent, model_output),
+        }
+    }
+
     /// Returns whether the scheduler has completed all steps.
     pub fn is_done(&self) -> bool {
         match self {
@@ -615,7 +762,7 @@ impl DynScheduler {
         }
     }
 
-    /// Resets the scheduler.
+    /// Resets the scheduler to the state of a freshly constructed one.
     pub fn reset(&mut self) {
         match self {
             DynScheduler::Euler(s) => s.reset(),
@@ -624,6 +771,25 @@ impl DynScheduler {
         }
     }
 
+    /// Resets the scheduler for a generation with `seed` (PingPong only).
+    pub fn reset_with_seed(&mut self, seed: u64) {
+        match self {
+            DynScheduler::Euler(s) => s.reset_with_seed(seed),
+            DynScheduler::Heun(s) => s.reset_with_seed(seed),
+            DynScheduler::PingPong(s) => s.reset_with_seed(seed),
+        }
+    }
+
+    /// Changes the number of user-visible steps without reconstructing the
+    /// scheduler. The schedule is recomputed in place and the scheduler reset.
+    pub fn with_num_steps(&mut self, num_steps: u32) {
+        match self {
+            DynScheduler::Euler(s) => s.set_num_steps(num_steps),
+            DynScheduler::Heun(s) => s.set_num_steps(num_steps),
+            DynScheduler::PingPong(s) => s.set_num_steps(num_steps),
+        }
+    }
+
     /// Returns all sigmas.
     pub fn sigmas(&self) -> &[f32] {
         match self {
@@ -668,6 +834,24 @@ impl DynScheduler {
             DynScheduler::PingPong(s) => s.user_num_steps(),
         }
     }
+
+    /// Noises a clean latent to noise level `sigma`.
+    pub fn add_noise(&self, latent: &Array4<f32>, noise: &Array4<f32>, sigma: f32) -> Array4<f32> {
+        match self {
+            DynScheduler::Euler(s) => s.add_noise(latent, noise, sigma),
+            DynScheduler::Heun(s) => s.add_noise(latent, noise, sigma),
+            DynScheduler::PingPong(s) => s.add_noise(latent, noise, sigma),
+        }
+    }
+
+    /// Returns the user step at which to start for a denoising `strength`.
+    pub fn start_step_for_strength(&self, strength: f32) -> usize {
+        match self {
+            DynScheduler::Euler(s) => s.start_step_for_strength(strength),
+            DynScheduler::Heun(s) => s.start_step_for_strength(strength),
+            DynScheduler::PingPong(s) => s.start_step_for_strength(strength),
+        }
+    }
 }
 
 /// Creates a scheduler of the specified type.
@@ -684,6 +868,27 @@ pub fn create_scheduler(scheduler_type: SchedulerType, num_steps: u32, seed: u64
     }
 }
 
+/// Returns a scheduler of the specified type for a new generation, reusing
+/// `previous` if it has the same type.
+///
+/// A reused scheduler keeps its buffers (including PingPong's latent-sized
+/// noise buffer) and steps exactly like one from [`create_scheduler`].
+pub fn reuse_scheduler(
+    previous: Option<DynScheduler>,
+    scheduler_type: SchedulerType,
+    num_steps: u32,
+    seed: u64,
+) -> DynScheduler {
+    match previous {
+        Some(mut scheduler) if scheduler.scheduler_type() == scheduler_type => {
+            scheduler.with_num_steps(num_steps);
+            scheduler.reset_with_seed(seed);
+            scheduler
+        }
+        _ => create_scheduler(scheduler_type, num_steps, seed),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -705,6 +910,31 @@ mod tests {
         assert_eq!(SchedulerType::PingPong.as_str(), "pingpong");
     }
 
+    #[test]
+    fn add_noise_interpolates_to_sigma() {
+        let scheduler = create_scheduler(SchedulerType::Euler, 60, 0);
+        let latent = Array4::from_elem((1, 2, 2, 2), 2.0f32);
+        let noise = Array4::from_elem((1, 2, 2, 2), -1.0f32);
+
+        assert_eq!(scheduler.add_noise(&latent, &noise, 0.0), latent);
+        assert_eq!(scheduler.add_noise(&latent, &noise, 1.0), noise);
+        let quarter = scheduler.add_noise(&latent, &noise, 0.25);
+        assert!(quarter.iter().all(|&v| (v - 1.25).abs() < 1e-6));
+    }
+
+    #[test]
+    fn start_step_follows_strength() {
+        for scheduler_type in [SchedulerType::Euler, SchedulerType::Heun, SchedulerType::PingPong] {
+            let scheduler = create_scheduler(scheduler_type, 60, 0);
+            assert_eq!(scheduler.start_step_for_strength(1.0), 0);
+            assert_eq!(scheduler.start_step_for_strength(0.5), 30);
+            assert_eq!(scheduler.start_step_for_strength(0.25), 45);
+            assert_eq!(scheduler.start_step_for_strength(0.0), 60);
+            assert_eq!(scheduler.start_step_for_strength(1.5), 0);
+            assert_eq!(scheduler.start_step_for_strength(-1.0), 60);
+        }
+    }
+
     // ========== Euler Scheduler Tests ==========
 
     #[test]
@@ -899,6 +1129,318 @@ mod tests {
         assert_eq!(scheduler.num_steps(), 60);
     }
 
+    // ========== Reset and Reuse Tests ==========
+
+    /// Runs `scheduler` to completion on a fixed toy model and returns every
+    /// step's output.
+    fn run_to_done(scheduler: &mut DynScheduler) -> Vec<Array4<f32>> {
+        let mut latent = Array4::from_shape_fn((1, 2, 2, 4), |(_, c, h, w)| {
+            (c * 8 + h * 4 + w) as f32 / 16.0 - 0.5
+        });
+        let mut outputs = Vec::new();
+        while !scheduler.is_done() {
+            let model_output = latent.mapv(|v| 0.5 * v + 0.1);
+            latent = scheduler.step(&latent, &model_output);
+            outputs.push(latent.clone());
+        }
+        outputs
+    }
+
+    #[test]
+    fn reset_matches_fresh_scheduler() {
+        for scheduler_type in SchedulerType::ALL {
+            let mut scheduler = create_scheduler(scheduler_type, 8, 42);
+            let first = run_to_done(&mut scheduler);
+            scheduler.reset();
+            let second = run_to_done(&mut scheduler);
+
+            let fresh = run_to_done(&mut create_scheduler(scheduler_type, 8, 42));
+            assert_eq!(first, fresh, "{:?}", scheduler_type);
+            assert_eq!(second, fresh, "{:?} after reset", scheduler_type);
+        }
+    }
+
+    #[test]
+    fn reuse_matches_fresh_scheduler() {
+        for scheduler_type in SchedulerType::ALL {
+            let mut scheduler = create_scheduler(scheduler_type, 8, 42);
+            run_to_done(&mut scheduler);
+
+            // New step count and seed, as for the next track of a batch
+            let mut reused = reuse_scheduler(Some(scheduler), scheduler_type, 5, 7);
+            let mut fresh = create_scheduler(scheduler_type, 5, 7);
+            assert_eq!(reused.sigmas(), fresh.sigmas(), "{:?}", scheduler_type);
+            assert_eq!(reused.timesteps(), fresh.timesteps(), "{:?}", scheduler_type);
+            assert_eq!(reused.user_num_steps(), 5);
+            assert_eq!(run_to_done(&mut reused), run_to_done(&mut fresh), "{:?}", scheduler_type);
+        }
+
+        // A scheduler of another type is replaced
+        let euler = create_scheduler(SchedulerType::Euler, 8, 42);
+        let heun = reuse_scheduler(Some(euler), SchedulerType::Heun, 8, 42);
+        assert_eq!(heun.scheduler_type(), SchedulerType::Heun);
+    }
+
+    #[test]
+    fn pingpong_reset_with_seed_changes_noise() {
+        let mut scheduler = create_scheduler(SchedulerType::PingPong, 4, 42);
+        let seed_42 = run_to_done(&mut scheduler);
+        scheduler.reset_with_seed(123);
+        let seed_123 = run_to_done(&mut scheduler);
+        assert_ne!(seed_42, seed_123);
+        assert_eq!(seed_123, run_to_done(&mut create_scheduler(SchedulerType::PingPong, 4, 123)));
+    }
+
+    /// Compares a 10-track batch that creates a scheduler per track with one
+    /// that reuses a single scheduler:
+    /// `cargo test --release scheduler_reuse_benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn scheduler_reuse_benchmark() {
+        // A 30 second latent: 8 channels x 16 rows x 323 frames
+        let latent = Array4::from_elem((1, 8, 16, 323), 0.5f32);
+        let model_output = Array4::from_elem((1, 8, 16, 323), 0.1f32);
+        let run = |scheduler: &mut DynScheduler| {
+            while !scheduler.is_done() {
+                std::hint::black_box(scheduler.step(&latent, &model_output));
+            }
+        };
+
+        for scheduler_type in SchedulerType::ALL {
+            let start = std::time::Instant::now();
+            for seed in 0..10 {
+                run(&mut create_scheduler(scheduler_type, 60, seed));
+            }
+            let fresh = start.elapsed();
+
+            let start = std::time::Instant::now();
+            let mut scheduler = None;
+            for seed in 0..10 {
+                let mut reused = reuse_scheduler(scheduler.take(), scheduler_type, 60, seed);
+                run(&mut reused);
+                scheduler = Some(reused);
+            }
+            let reused = start.elapsed();
+
+            // Fresh PingPong schedulers each grow a latent-sized noise buffer
+            let buffers_saved = match scheduler_type {
+                SchedulerType::PingPong => 9 * latent.len() * std::mem::size_of::<f32>(),
+                _ => 0,
+            };
+            println!(
+                "{}: 10 tracks x 60 steps, fresh schedulers {:?}, reused scheduler {:?}, \
+                 {} noise buffer bytes not reallocated",
+                scheduler_type.as_str(),
+                fresh,
+                reused,
+                buffers_saved
+            );
+        }
+    }
+
+    // ========== Fused Step Tests ==========
+
+    /// The allocating step math the fused in-place steps replaced.
+    mod reference {
+        use super::*;
+
+        fn shifted(dx: Array4<f32>) -> Array4<f32> {
+            let omega_scaled = logistic(DEFAULT_OMEGA, 0.9, 1.1, 0.0, 0.1);
+            let mean = dx.mean().unwrap_or(0.0);
+            dx.mapv(|v| (v - mean) * omega_scaled + mean)
+        }
+
+        pub fn euler(latent: &Array4<f32>, output: &Array4<f32>, sigma: f32, sigma_next: f32) -> Array4<f32> {
+            let dt = sigma_next - sigma;
+            latent + &shifted(output.mapv(|v| v * dt))
+        }
+
+        /// Returns the predicted sample and the derivative and dt to keep.
+        pub fn heun_first(
+            latent: &Array4<f32>,
+            output: &Array4<f32>,
+            sigma: f32,
+            sigma_next: f32,
+        ) -> (Array4<f32>, Array4<f32>, f32) {
+            let denoised = latent - &output.mapv(|v| v * sigma);
+            let derivative = (latent - &denoised).mapv(|v| v / sigma);
+            let dt = sigma_next - sigma;
+            let next = latent + &shifted(derivative.mapv(|v| v * dt));
+            (next, derivative, dt)
+        }
+
+        pub fn heun_second(
+            latent: &Array4<f32>,
+            output: &Array4<f32>,
+            sigma_next: f32,
+            prev_derivative: &Array4<f32>,
+            dt: f32,
+            sample: &Array4<f32>,
+        ) -> Array4<f32> {
+            let denoised = latent - &output.mapv(|v| v * sigma_next);
+            let derivative = if sigma_next > 0.0 {
+                (latent - &denoised).mapv(|v| v / sigma_next)
+            } else {
+                Array4::zeros(latent.raw_dim())
+            };
+            let avg_derivative = (prev_derivative + &derivative).mapv(|v| v * 0.5);
+            sample + &shifted(avg_derivative.mapv(|v| v * dt))
+        }
+
+        pub fn pingpong(
+            latent: &Array4<f32>,
+            output: &Array4<f32>,
+            sigma: f32,
+            sigma_next: f32,
+            noise: &Array4<f32>,
+        ) -> Array4<f32> {
+            let denoised = latent - &output.mapv(|v| v * sigma);
+            Zip::from(&denoised)
+                .and(noise)
+                .map_collect(|&d, &n| d * (1.0 - sigma_next) + n * sigma_next)
+        }
+    }
+
+    /// Steps latents with the reference math, keeping Heun's first-order
+    /// state and PingPong's noise stream like the schedulers do.
+    struct ReferenceStepper {
+        scheduler_type: SchedulerType,
+        sigmas: Vec<f32>,
+        noise_rng: ChaCha8Rng,
+        noise: Array4<f32>,
+        heun_state: Option<(Array4<f32>, f32, Array4<f32>)>,
+    }
+
+    impl ReferenceStepper {
+        fn new(scheduler: &DynScheduler, seed: u64, shape: (usize, usize, usize, usize)) -> Self {
+            Self {
+                scheduler_type: scheduler.scheduler_type(),
+                sigmas: scheduler.sigmas().to_vec(),
+                noise_rng: ChaCha8Rng::seed_from_u64(seed),
+                noise: Array4::zeros(shape),
+                heun_state: None,
+            }
+        }
+
+        /// Returns the latent after internal step `i`.
+        fn step(&mut self, i: usize, latent: &Array4<f32>, output: &Array4<f32>) -> Array4<f32> {
+            let (sigma, sigma_next) = (self.sigmas[i], self.sigmas[i + 1]);
+            match self.scheduler_type {
+                SchedulerType::Euler => reference::euler(latent, output, sigma, sigma_next),
+                SchedulerType::PingPong => {
+                    fill_standard_normal(self.noise.as_slice_mut().unwrap(), &mut self.noise_rng);
+                    reference::pingpong(latent, output, sigma, sigma_next, &self.noise)
+                }
+                SchedulerType::Heun => match self.heun_state.take() {
+                    None => {
+                        let (next, derivative, dt) =
+                            reference::heun_first(latent, output, sigma, sigma_next);
+                        self.heun_state = Some((derivative, dt, latent.clone()));
+                        next
+                    }
+                    Some((derivative, dt, sample)) => {
+                        reference::heun_second(latent, output, sigma, &derivative, dt, &sample)
+                    }
+                },
+            }
+        }
+    }
+
+    fn random_tensor(shape: (usize, usize, usize, usize), scale: f32, rng: &mut ChaCha8Rng) -> Array4<f32> {
+        let mut values = Array4::zeros(shape);
+        fill_standard_normal(values.as_slice_mut().unwrap(), rng);
+        values * scale
+    }
+
+    fn assert_close(actual: &Array4<f32>, expected: &Array4<f32>, context: &str) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() <= 1e-6 * e.abs().max(1.0), "{}: {} vs {}", context, a, e);
+        }
+    }
+
+    /// Steps each scheduler through a whole schedule in place and with the
+    /// reference math, on random latents and model outputs.
+    #[test]
+    fn fused_steps_match_reference() {
+        let shape = (1, 8, 16, 57);
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        for scheduler_type in SchedulerType::ALL {
+            let mut scheduler = create_scheduler(scheduler_type, 12, 42);
+            let mut stepper = ReferenceStepper::new(&scheduler, 42, shape);
+
+            let mut latent = random_tensor(shape, 1.0, &mut rng);
+            let mut expected = latent.clone();
+            while !scheduler.is_done() {
+                let i = scheduler.current_step();
+                let output = random_tensor(shape, 2.0, &mut rng);
+                expected = stepper.step(i, &expected, &output);
+                scheduler.step_in_place(&mut latent, &output);
+                assert_close(&latent, &expected, &format!("{:?} step {}", scheduler_type, i));
+            }
+        }
+    }
+
+    /// Compares per-step time of the allocating reference steps and guidance
+    /// with the fused in-place ones on a 240 second latent:
+    /// `cargo test --release fused_step_benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn fused_step_benchmark() {
+        use super::super::guidance::{apply_cfg_into, sanitize_tensor};
+        use super::super::latent::calculate_frame_length;
+        use std::time::Instant;
+
+        let shape = (1, 8, 16, calculate_frame_length(240.0));
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let cond = random_tensor(shape, 2.0, &mut rng);
+        let uncond = random_tensor(shape, 2.0, &mut rng);
+        let initial = random_tensor(shape, 1.0, &mut rng);
+
+        for scheduler_type in SchedulerType::ALL {
+            let mut scheduler = create_scheduler(scheduler_type, 60, 42);
+            let mut stepper = ReferenceStepper::new(&scheduler, 42, shape);
+            let steps = scheduler.num_steps();
+
+            let mut latent = initial.clone();
+            let start = Instant::now();
+            for i in 0..steps as usize {
+                latent = std::hint::black_box(stepper.step(i, &latent, &cond));
+            }
+            let allocating = start.elapsed() / steps;
+
+            let mut latent = initial.clone();
+            let start = Instant::now();
+            while !scheduler.is_done() {
+                scheduler.step_in_place(&mut latent, &cond);
+                std::hint::black_box(&latent);
+            }
+            let fused = start.elapsed() / steps;
+            println!(
+                "{}: {:?} per step allocating, {:?} per step fused",
+                scheduler_type.as_str(),
+                allocating,
+                fused
+            );
+        }
+
+        let start = Instant::now();
+        for _ in 0..60 {
+            let (c, u) = (sanitize_tensor(&cond), sanitize_tensor(&uncond));
+            let guided = Zip::from(&c).and(&u).map_collect(|&c, &u| (u + 7.0 * (c - u)).clamp(-20.0, 20.0));
+            std::hint::black_box(guided);
+        }
+        let allocating = start.elapsed() / 60;
+
+        let mut guided = Array4::zeros(shape);
+        let start = Instant::now();
+        for _ in 0..60 {
+            apply_cfg_into(&cond, &uncond, 7.0, &mut guided).unwrap();
+            std::hint::black_box(&guided);
+        }
+        let fused = start.elapsed() / 60;
+        println!("cfg: {:?} per step allocating, {:?} per step fused", allocating, fused);
+    }
+
     // ========== Helper Function Tests ==========
 
     #[test]
@@ -932,11 +1474,19 @@ mod tests {
     }
 
     #[test]
-    fn generate_noise_shape() {
-        let arr = Array4::zeros((1, 8, 16, 100));
-        let mut rng = ChaCha8Rng::seed_from_u64(42);
-        let noise = generate_noise_like(&arr, &mut rng);
-
-        assert_eq!(noise.shape(), arr.shape());
+    fn pingpong_noise_shape_and_reproducibility() {
+        let latent = Array4::zeros((1, 8, 16, 100));
+        let model_output = Array4::zeros((1, 8, 16, 100));
+
+        let mut a = PingPongScheduler::default_ace_step(10, 42);
+        let mut b = PingPongScheduler::default_ace_step(10, 42);
+        let first = a.step(&latent, &model_output);
+        assert_eq!(first.shape(), latent.shape());
+        assert_eq!(first, b.step(&latent, &model_output));
+
+        // Each step draws fresh noise into the reused buffer
+        let second = a.step(&latent, &model_output);
+        assert_ne!(first, second);
+        assert_eq!(a.noise.len(), latent.len());
     }
 }
@@ -8,14 +8,19 @@ use std::path::Path;
 use ndarray::{Array2, Array3, Axis};
 use ort::execution_providers::ExecutionProviderDispatch;
 use ort::session::Session;
-use ort::value::Tensor;
+use ort::value::{Tensor, ValueType};
 use tokenizers::Tokenizer;
 
 use crate::error::{DaemonError, Result};
 
 use super::models::load_session;
+use crate::models::device::SessionTuning;
+use crate::models::tokenizer::{load_tokenizer, tokenizer_path};
 
-/// Maximum sequence length for text encoding.
+use super::models::TOKENIZER_URL;
+
+/// Maximum sequence length for text encoding, used when the model's input
+/// shape leaves the sequence dimension dynamic.
 pub const MAX_SEQ_LENGTH: usize = 512;
 
 /// UMT5 text encoder for ACE-Step prompt conditioning.
@@ -42,24 +47,57 @@ impl Umt5TextEncoder {
     /// # Arguments
     ///
     /// * `model_dir` - Directory containing `text_encoder.onnx` and `tokenizer.json`
+    /// * `tokenizer_override` - Tokenizer to use instead of the one in `model_dir`
     /// * `providers` - Execution providers for ONNX Runtime
-    pub fn load(model_dir: &Path, providers: &[ExecutionProviderDispatch]) -> Result<Self> {
+    pub fn load(
+        model_dir: &Path,
+        tokenizer_override: Option<&Path>,
+        providers: &[ExecutionProviderDispatch],
+        tuning: &SessionTuning,
+    ) -> Result<Self> {
         let encoder_path = model_dir.join("text_encoder.onnx");
-        let tokenizer_path = model_dir.join("tokenizer.json");
+        let tokenizer_path = tokenizer_path(model_dir, tokenizer_override);
 
         // Load the ONNX session
-        let session = load_session(&encoder_path, providers)?;
+        let session = load_session(&encoder_path, providers, tuning)?;
 
         // Load the tokenizer
-        let tokenizer = Tokenizer::from_file(&tokenizer_path).map_err(|e| {
-            DaemonError::model_load_failed(format!("Failed to load tokenizer: {}", e))
-        })?;
+        let tokenizer = load_tokenizer(&tokenizer_path, TOKENIZER_URL)?;
 
         Ok(Self { session, tokenizer })
     }
 
+    /// Returns the longest token sequence the encoder accepts.
+    ///
+    /// Read from the sequence dimension of the first model input; falls back
+    /// to [`MAX_SEQ_LENGTH`] when that dimension is dynamic.
+    pub fn max_sequence_length(&self) -> usize {
+        self.session
+            .inputs
+            .first()
+            .and_then(|input| match &input.input_type {
+                ValueType::Tensor { shape, .. } => shape.get(1).copied(),
+                _ => None,
+            })
+            .filter(|&len| len > 0)
+            .map_or(MAX_SEQ_LENGTH, |len| len as usize)
+    }
+
+    /// Checks that `prompt` tokenizes to no more than
+    /// [`Self::max_sequence_length`] tokens.
+    pub fn validate_prompt_token_length(&self, prompt: &str) -> Result<()> {
+        let encoding = self
+            .tokenizer
+            .encode(prompt, true)
+            .map_err(|e| DaemonError::model_inference_failed(format!("Tokenization failed: {}", e)))?;
+        check_token_length(encoding.len(), self.max_sequence_length())
+    }
+
     /// Encodes a text prompt into hidden states.
     ///
+    /// Fails without running the model if the prompt is longer than
+    /// [`Self::max_sequence_length`] tokens.
+    ///
     /// # Arguments
     ///
     /// * `prompt` - The text prompt to encode
@@ -79,10 +117,9 @@ impl Umt5TextEncoder {
         let token_ids: Vec<i64> = encoding.get_ids().iter().map(|&id| id as i64).collect();
         let attention_mask: Vec<i64> = encoding.get_attention_mask().iter().map(|&m| m as i64).collect();
 
-        // Truncate if needed
-        let seq_len = token_ids.len().min(MAX_SEQ_LENGTH);
-        let token_ids = token_ids[..seq_len].to_vec();
-        let attention_mask = attention_mask[..seq_len].to_vec();
+        // Reject prompts the model can't take rather than failing on a shape mismatch
+        let seq_len = token_ids.len();
+        check_token_length(seq_len, self.max_sequence_length())?;
 
         // Create ONNX tensors using the shape-data tuple pattern
         let input_ids_tensor = Tensor::from_array(([1, seq_len], token_ids.clone()))
@@ -94,7 +131,7 @@ impl Umt5TextEncoder {
         let mut outputs = self
             .session
             .run(ort::inputs![input_ids_tensor, attention_mask_tensor])
-            .map_err(|e| DaemonError::model_inference_failed(format!("Encoder inference failed: {}", e)))?;
+            .map_err(|e| DaemonError::model_inference_failed_with_source("Encoder inference failed", e))?;
 
         // Extract encoder hidden states - shape (1, seq_len, 768)
         let output_key = outputs.keys().next().map(|s| s.to_string()).ok_or_else(|| {
@@ -164,6 +201,17 @@ impl Umt5TextEncoder {
     }
 }
 
+/// Returns an error if `token_count` exceeds `max_tokens`.
+fn check_token_length(token_count: usize, max_tokens: usize) -> Result<()> {
+    if token_count > max_tokens {
+        return Err(DaemonError::model_inference_failed(format!(
+            "Prompt exceeds maximum token length: {} > {}",
+            token_count, max_tokens
+        )));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -173,4 +221,11 @@ mod tests {
         assert!(MAX_SEQ_LENGTH >= 64);
         assert!(MAX_SEQ_LENGTH <= 1024);
     }
+
+    #[test]
+    fn token_length_is_checked() {
+        assert!(check_token_length(512, 512).is_ok());
+        let error = check_token_length(513, 512).unwrap_err();
+        assert!(error.message.contains("Prompt exceeds maximum token length: 513 > 512"));
+    }
 }
@@ -28,12 +28,13 @@
 
 use std::path::Path;
 
-use ndarray::{Array2, Array3, Array4};
+use ndarray::{Array2, Array3, Array4, Axis};
 use ort::execution_providers::ExecutionProviderDispatch;
 use ort::session::Session;
 use ort::value::Tensor;
 
 use crate::error::{DaemonError, Result};
+use crate::models::device::SessionTuning;
 
 use super::models::load_session;
 
@@ -49,6 +50,31 @@ pub const SPEAKER_EMBED_DIM: usize = 512;
 /// Dimension of transformer encoder output.
 pub const ENCODER_HIDDEN_DIM: usize = 2560;
 
+/// Checks that an encoder attention mask attends to at least one position.
+///
+/// An all-zero mask makes the transformer ignore the context entirely and
+/// the generation decays to silence.
+pub fn validate_attention_mask(mask: &Array2<f32>) -> Result<()> {
+    if mask.sum() == 0.0 {
+        return Err(DaemonError::model_inference_failed(
+            "All-zero attention mask: prompt may be empty or tokenizer failed",
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the latent channels whose values are all zero.
+///
+/// Zero channels can be legitimate for some prompts, so callers only warn.
+pub fn zero_latent_channels(latent: &Array4<f32>) -> Vec<usize> {
+    latent
+        .axis_iter(Axis(1))
+        .enumerate()
+        .filter(|(_, channel)| channel.iter().all(|&v| v == 0.0))
+        .map(|(index, _)| index)
+        .collect()
+}
+
 /// Diffusion transformer for ACE-Step noise prediction.
 pub struct DiffusionTransformer {
     encoder: Session,
@@ -64,12 +90,16 @@ impl std::fmt::Debug for DiffusionTransformer {
 
 impl DiffusionTransformer {
     /// Loads the diffusion transformer from the model directory.
-    pub fn load(model_dir: &Path, providers: &[ExecutionProviderDispatch]) -> Result<Self> {
+    pub fn load(
+        model_dir: &Path,
+        providers: &[ExecutionProviderDispatch],
+        tuning: &SessionTuning,
+    ) -> Result<Self> {
         let encoder_path = model_dir.join("transformer_encoder.onnx");
         let decoder_path = model_dir.join("transformer_decoder.onnx");
 
-        let encoder = load_session(&encoder_path, providers)?;
-        let decoder = load_session(&decoder_path, providers)?;
+        let encoder = load_session(&encoder_path, providers, tuning)?;
+        let decoder = load_session(&decoder_path, providers, tuning)?;
 
         Ok(Self { encoder, decoder })
     }
@@ -143,7 +173,7 @@ impl DiffusionTransformer {
                 "lyric_token_idx" => lyric_tensor,
                 "lyric_mask" => lyric_mask_tensor,
             ])
-            .map_err(|e| DaemonError::model_inference_failed(format!("Transformer encoder failed: {}", e)))?;
+            .map_err(|e| DaemonError::model_inference_failed_with_source("Transformer encoder failed", e))?;
 
         // Extract encoder_hidden_states
         let hidden_states = outputs.remove("encoder_hidden_states").ok_or_else(|| {
@@ -195,6 +225,16 @@ impl DiffusionTransformer {
         encoder_hidden_states: &Array3<f32>,
         encoder_hidden_mask: &Array2<f32>,
     ) -> Result<Array4<f32>> {
+        validate_attention_mask(encoder_hidden_mask)?;
+
+        let zero_channels = zero_latent_channels(latent);
+        if !zero_channels.is_empty() {
+            eprintln!(
+                "Warning: latent channels {:?} are all zero at timestep {}",
+                zero_channels, timestep
+            );
+        }
+
         let batch_size = latent.shape()[0];
         let frame_length = latent.shape()[3];
         let encoder_seq_len = encoder_hidden_states.shape()[1];
@@ -245,7 +285,7 @@ impl DiffusionTransformer {
                 "encoder_hidden_mask" => enc_mask_tensor,
                 "timestep" => timestep_tensor,
             ])
-            .map_err(|e| DaemonError::model_inference_failed(format!("Transformer decoder failed: {}", e)))?;
+            .map_err(|e| DaemonError::model_inference_failed_with_source("Transformer decoder failed", e))?;
 
         // Extract sample output
         let sample = outputs.remove("sample").ok_or_else(|| {
@@ -284,4 +324,22 @@ mod tests {
     fn speaker_embed_dim() {
         assert_eq!(SPEAKER_EMBED_DIM, 512);
     }
+
+    #[test]
+    fn attention_mask_must_not_be_all_zero() {
+        let mask = Array2::from_shape_vec((1, 4), vec![1.0, 1.0, 0.0, 0.0]).unwrap();
+        assert!(validate_attention_mask(&mask).is_ok());
+
+        let err = validate_attention_mask(&Array2::zeros((1, 4))).unwrap_err();
+        assert!(err.message.contains("All-zero attention mask"));
+    }
+
+    #[test]
+    fn zero_latent_channels_found() {
+        let mut latent = Array4::<f32>::ones((1, LATENT_CHANNELS, LATENT_HEIGHT, 4));
+        assert!(zero_latent_channels(&latent).is_empty());
+
+        latent.index_axis_mut(Axis(1), 3).fill(0.0);
+        assert_eq!(zero_latent_channels(&latent), vec![3]);
+    }
 }
@@ -11,6 +11,7 @@ use ort::session::Session;
 use ort::value::Tensor;
 
 use crate::error::{DaemonError, Result};
+use crate::models::device::SessionTuning;
 
 use super::models::load_session;
 
@@ -20,6 +21,12 @@ pub const VOCODER_SAMPLE_RATE: u32 = 44100;
 /// Target sample rate for lofi.nvim output (48 kHz).
 pub const TARGET_SAMPLE_RATE: u32 = 48000;
 
+/// Magnitude above which mel values are considered out of range.
+///
+/// The DCAE decoder produces log-mel values in roughly [-11.5, 2.0]; values
+/// far outside that range make the vocoder output noise or non-finite samples.
+pub const MEL_WARN_THRESHOLD: f32 = 20.0;
+
 /// ADaMoSHiFiGAN vocoder for ACE-Step.
 ///
 /// Converts mel-spectrograms from the DCAE decoder into audio waveforms
@@ -43,9 +50,13 @@ impl Vocoder {
     ///
     /// * `model_dir` - Directory containing `vocoder.onnx`
     /// * `providers` - Execution providers for ONNX Runtime
-    pub fn load(model_dir: &Path, providers: &[ExecutionProviderDispatch]) -> Result<Self> {
+    pub fn load(
+        model_dir: &Path,
+        providers: &[ExecutionProviderDispatch],
+        tuning: &SessionTuning,
+    ) -> Result<Self> {
         let vocoder_path = model_dir.join("vocoder.onnx");
-        let session = load_session(&vocoder_path, providers)?;
+        let session = load_session(&vocoder_path, providers, tuning)?;
         Ok(Self { session })
     }
 
@@ -58,7 +69,12 @@ impl Vocoder {
     /// # Returns
     ///
     /// Audio waveform as a 1D array of f32 samples at 44.1 kHz.
+    ///
+    /// The mel-spectrogram is validated first (see [`validate_mel_input`]) and
+    /// the output is clamped to [-1.0, 1.0] (see [`clamp_audio_output`]).
     pub fn synthesize(&mut self, mel: &Array3<f32>) -> Result<Array1<f32>> {
+        validate_mel_input(mel)?;
+
         // Create input tensor from flat data
         let shape = mel.shape();
         let data: Vec<f32> = mel.iter().copied().collect();
@@ -68,7 +84,7 @@ impl Vocoder {
         let mut outputs = self
             .session
             .run(ort::inputs![mel_tensor])
-            .map_err(|e| DaemonError::model_inference_failed(format!("Vocoder inference failed: {}", e)))?;
+            .map_err(|e| DaemonError::model_inference_failed_with_source("Vocoder inference failed", e))?;
 
         // Get first output
         let output_key = outputs.keys().next().map(|s| s.to_string()).ok_or_else(|| {
@@ -83,7 +99,12 @@ impl Vocoder {
             .map_err(|e| DaemonError::model_inference_failed(format!("Failed to extract audio: {}", e)))?;
 
         // Flatten to 1D - output may be (1, samples) or (1, 1, samples) or (samples,)
-        let samples: Vec<f32> = audio_data.to_vec();
+        let mut samples: Vec<f32> = audio_data.to_vec();
+        let stats = clamp_audio_output(&mut samples);
+        eprintln!(
+            "vocoder_output_stats: min={:.4} max={:.4} mean={:.6} non_finite={}",
+            stats.min, stats.max, stats.mean, stats.non_finite
+        );
 
         Ok(Array1::from_vec(samples))
     }
@@ -94,6 +115,89 @@ impl Vocoder {
     }
 }
 
+/// Checks that a mel-spectrogram can be passed to the vocoder.
+///
+/// Returns an error if any value is NaN or infinite. Logs a warning if any
+/// value exceeds [`MEL_WARN_THRESHOLD`] in magnitude, since the output is
+/// then likely to be distorted.
+pub fn validate_mel_input(mel: &Array3<f32>) -> Result<()> {
+    let non_finite = mel.iter().filter(|v| !v.is_finite()).count();
+    if non_finite > 0 {
+        return Err(DaemonError::model_inference_failed(format!(
+            "Mel-spectrogram contains {} NaN or infinite values out of {}",
+            non_finite,
+            mel.len()
+        )));
+    }
+
+    let (min, max) = mel
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| (min.min(v), max.max(v)));
+    if min < -MEL_WARN_THRESHOLD || max > MEL_WARN_THRESHOLD {
+        eprintln!(
+            "Warning: mel-spectrogram values out of expected range: min={:.2} max={:.2} (expected about [-11.5, 2.0])",
+            min, max
+        );
+    }
+    Ok(())
+}
+
+/// Summary of vocoder output computed by [`clamp_audio_output`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VocoderOutputStats {
+    /// Smallest finite sample before clamping.
+    pub min: f32,
+    /// Largest finite sample before clamping.
+    pub max: f32,
+    /// Mean of the finite samples before clamping.
+    pub mean: f32,
+    /// Number of NaN or infinite samples (NaN is replaced with silence).
+    pub non_finite: usize,
+}
+
+/// Replaces NaN samples with 0.0 and clamps all samples to [-1.0, 1.0].
+///
+/// Infinite samples are clamped to full scale. Returns the statistics of the
+/// finite samples before clamping; min, max and mean are 0.0 if there are none.
+pub fn clamp_audio_output(samples: &mut [f32]) -> VocoderOutputStats {
+    let mut min = f32::INFINITY;
+    let mut max = f32::NEG_INFINITY;
+    let mut sum = 0.0f64;
+    let mut finite = 0usize;
+    let mut non_finite = 0usize;
+
+    for sample in samples.iter_mut() {
+        if sample.is_finite() {
+            min = min.min(*sample);
+            max = max.max(*sample);
+            sum += *sample as f64;
+            finite += 1;
+        } else {
+            non_finite += 1;
+        }
+        *sample = if sample.is_nan() {
+            0.0
+        } else {
+            sample.clamp(-1.0, 1.0)
+        };
+    }
+
+    if finite == 0 {
+        return VocoderOutputStats {
+            min: 0.0,
+            max: 0.0,
+            mean: 0.0,
+            non_finite,
+        };
+    }
+    VocoderOutputStats {
+        min,
+        max,
+        mean: (sum / finite as f64) as f32,
+        non_finite,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -103,4 +207,35 @@ mod tests {
         assert_eq!(VOCODER_SAMPLE_RATE, 44100);
         assert_eq!(TARGET_SAMPLE_RATE, 48000);
     }
+
+    #[test]
+    fn mel_validation_rejects_non_finite_values() {
+        let mut mel = Array3::from_elem((1, 4, 8), -5.0f32);
+        assert!(validate_mel_input(&mel).is_ok());
+
+        // Out-of-range values only warn
+        mel[[0, 1, 2]] = 35.0;
+        assert!(validate_mel_input(&mel).is_ok());
+
+        mel[[0, 0, 0]] = f32::NAN;
+        mel[[0, 3, 7]] = f32::NEG_INFINITY;
+        let err = validate_mel_input(&mel).unwrap_err();
+        assert!(err.to_string().contains("2 NaN or infinite values"));
+    }
+
+    #[test]
+    fn output_is_clamped_and_nan_silenced() {
+        let mut samples = vec![0.5, -1.5, f32::NAN, 2.0, f32::INFINITY, f32::NEG_INFINITY, -0.5];
+        let stats = clamp_audio_output(&mut samples);
+        assert_eq!(samples, vec![0.5, -1.0, 0.0, 1.0, 1.0, -1.0, -0.5]);
+        assert_eq!(stats.min, -1.5);
+        assert_eq!(stats.max, 2.0);
+        assert!((stats.mean - 0.125).abs() < 1e-6);
+        assert_eq!(stats.non_finite, 3);
+
+        let mut silent = vec![f32::NAN; 4];
+        let stats = clamp_audio_output(&mut silent);
+        assert_eq!(silent, vec![0.0; 4]);
+        assert_eq!((stats.min, stats.max, stats.mean), (0.0, 0.0, 0.0));
+    }
 }
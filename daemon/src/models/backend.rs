@@ -3,11 +3,16 @@
 //! This module provides a unified interface for MusicGen and ACE-Step backends,
 //! allowing seamless switching between generation models.
 
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::diagnostics::FailureDumper;
 use crate::error::{DaemonError, Result};
+use crate::generation::{AudioProgress, CancelToken};
 
-use super::ace_step::AceStepModels;
+use super::ace_step::{AceStepModels, GenerationParams, SchedulerType};
 use super::musicgen::MusicGenModels;
 
 /// Available music generation backends.
@@ -15,7 +20,7 @@ use super::musicgen::MusicGenModels;
 /// Each backend has different capabilities and characteristics:
 /// - **MusicGen**: Fast, ~30s max duration, 32kHz output
 /// - **AceStep**: Slower, up to 240s duration, 48kHz output, diffusion-based
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum Backend {
     /// MusicGen model - Meta's autoregressive audio generation.
@@ -171,34 +176,45 @@ impl LoadedModels {
     ///
     /// # Returns
     ///
-    /// Audio samples at the appropriate sample rate for the backend:
-    /// - MusicGen: 32kHz
+    /// Audio samples at the appropriate sample rate for the backend, and the
+    /// seed that produced them:
+    /// - MusicGen: 32kHz, retrying with the next seed if the output is invalid
     /// - ACE-Step: 48kHz
-    pub fn generate<F>(&mut self, params: &GenerateDispatchParams, on_progress: F) -> Result<Vec<f32>>
+    pub fn generate<F>(
+        &mut self,
+        params: &GenerateDispatchParams,
+        on_progress: F,
+    ) -> Result<(Vec<f32>, u64)>
     where
         F: Fn(usize, usize),
     {
         use crate::cli::TOKENS_PER_SECOND;
-        use crate::generation::{generate_ace_step, generate_with_models};
+        use crate::generation::{
+            generate_ace_step_params_with_progress, generate_with_seed_retry_diagnosed,
+            DEFAULT_SEED_RETRIES,
+        };
 
         match self {
             LoadedModels::None => Err(DaemonError::model_load_failed("No models loaded")),
             LoadedModels::MusicGen(models) => {
-                let max_tokens = params.duration_sec as usize * TOKENS_PER_SECOND;
-                generate_with_models(models, &params.prompt, max_tokens, on_progress)
-            }
-            LoadedModels::AceStep(models) => {
-                generate_ace_step(
+                let target_frames = params.duration_sec as usize * TOKENS_PER_SECOND;
+                generate_with_seed_retry_diagnosed(
                     models,
                     &params.prompt,
-                    params.duration_sec as f32,
+                    target_frames,
                     params.seed,
-                    params.inference_steps.unwrap_or(60),
-                    &params.scheduler.clone().unwrap_or_else(|| "euler".to_string()),
-                    params.guidance_scale.unwrap_or(15.0),
+                    DEFAULT_SEED_RETRIES,
+                    params.failure_dumper.as_ref(),
+                    params.token_dump.as_deref(),
+                    params.cancel.as_ref(),
+                    None,
                     on_progress,
                 )
             }
+            LoadedModels::AceStep(models) => {
+                generate_ace_step_params_with_progress(models, params.ace_step_params(), on_progress)
+                    .map(|samples| (samples, params.seed))
+            }
         }
     }
 }
@@ -208,6 +224,8 @@ impl LoadedModels {
 pub struct GenerateDispatchParams {
     /// Text prompt describing the music to generate.
     pub prompt: String,
+    /// ACE-Step: Weighted prompts to blend instead of `prompt`.
+    pub prompt_blend: Option<Vec<(String, f32)>>,
     /// Duration in seconds.
     pub duration_sec: u32,
     /// Random seed for reproducibility.
@@ -220,6 +238,14 @@ pub struct GenerateDispatchParams {
     pub scheduler: Option<String>,
     /// ACE-Step: Classifier-free guidance scale.
     pub guidance_scale: Option<f32>,
+    /// Where to save diagnostics if MusicGen decoding fails.
+    pub failure_dumper: Option<FailureDumper>,
+    /// MusicGen: file to write the generated token frames to.
+    pub token_dump: Option<PathBuf>,
+    /// Token for stopping the generation early.
+    pub cancel: Option<CancelToken>,
+    /// ACE-Step: Called with `(decoded_sec, total_sec)` as audio is decoded.
+    pub audio_progress: Option<AudioProgress>,
 }
 
 impl GenerateDispatchParams {
@@ -227,12 +253,41 @@ impl GenerateDispatchParams {
     pub fn new(prompt: String, duration_sec: u32, seed: u64, backend: Backend) -> Self {
         Self {
             prompt,
+            prompt_blend: None,
             duration_sec,
             seed,
             backend,
             inference_steps: None,
             scheduler: None,
             guidance_scale: None,
+            failure_dumper: None,
+            token_dump: None,
+            cancel: None,
+            audio_progress: None,
+        }
+    }
+
+    /// Returns the ACE-Step generation parameters.
+    ///
+    /// Unset ACE-Step options take the defaults of [`AceStepConfig`], like
+    /// [`GenerationParams::default`].
+    pub fn ace_step_params(&self) -> GenerationParams {
+        let defaults = GenerationParams::default();
+        GenerationParams {
+            prompt: self.prompt.clone(),
+            prompt_blend: self.prompt_blend.clone(),
+            duration_sec: self.duration_sec as f32,
+            seed: self.seed,
+            inference_steps: self.inference_steps.unwrap_or(defaults.inference_steps),
+            scheduler: self
+                .scheduler
+                .as_deref()
+                .and_then(SchedulerType::parse)
+                .unwrap_or(defaults.scheduler),
+            guidance_scale: self.guidance_scale.unwrap_or(defaults.guidance_scale),
+            cancel: self.cancel.clone(),
+            timings: None,
+            on_audio_progress: self.audio_progress.clone(),
         }
     }
 
@@ -248,6 +303,36 @@ impl GenerateDispatchParams {
         self.guidance_scale = guidance_scale;
         self
     }
+
+    /// Sets the weighted prompts to blend (ACE-Step only).
+    pub fn with_prompt_blend(mut self, prompt_blend: Option<Vec<(String, f32)>>) -> Self {
+        self.prompt_blend = prompt_blend;
+        self
+    }
+
+    /// Sets where failure diagnostics are saved.
+    pub fn with_failure_dumper(mut self, dumper: Option<FailureDumper>) -> Self {
+        self.failure_dumper = dumper;
+        self
+    }
+
+    /// Sets where MusicGen token frames are written (see `write_token_dump`).
+    pub fn with_token_dump(mut self, path: Option<PathBuf>) -> Self {
+        self.token_dump = path;
+        self
+    }
+
+    /// Sets the token that can stop the generation early.
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Sets the callback receiving decoded audio progress (ACE-Step only).
+    pub fn with_audio_progress(mut self, audio_progress: AudioProgress) -> Self {
+        self.audio_progress = Some(audio_progress);
+        self
+    }
 }
 
 // AceStepModels is now defined in ace_step::models and re-exported here
@@ -266,6 +351,23 @@ mod tests {
         assert_eq!(Backend::parse("invalid"), None);
     }
 
+    #[test]
+    fn dispatch_defaults_match_config() {
+        let defaults = crate::config::AceStepConfig::default();
+        let params = GenerateDispatchParams::new("rain".to_string(), 30, 1, Backend::AceStep)
+            .ace_step_params();
+        assert_eq!(params.guidance_scale, defaults.guidance_scale);
+        assert_eq!(params.inference_steps, defaults.inference_steps);
+        assert_eq!(params.scheduler.as_str(), defaults.scheduler);
+
+        let params = GenerateDispatchParams::new("rain".to_string(), 30, 1, Backend::AceStep)
+            .with_ace_step_params(Some(80), Some("heun".to_string()), Some(12.0))
+            .ace_step_params();
+        assert_eq!(params.guidance_scale, 12.0);
+        assert_eq!(params.inference_steps, 80);
+        assert_eq!(params.scheduler, SchedulerType::Heun);
+    }
+
     #[test]
     fn backend_display() {
         assert_eq!(Backend::MusicGen.to_string(), "musicgen");
@@ -4,12 +4,45 @@
 //! and returns appropriate execution providers based on device configuration.
 
 use ort::execution_providers::{
-    CPUExecutionProvider, CoreMLExecutionProvider, CUDAExecutionProvider, ExecutionProvider,
-    ExecutionProviderDispatch,
+    ArenaExtendStrategy, CPUExecutionProvider, CoreMLExecutionProvider, CUDAExecutionProvider,
+    ExecutionProvider, ExecutionProviderDispatch,
 };
+use ort::session::builder::SessionBuilder;
 use ort::session::Session;
 
-use crate::config::Device;
+use crate::config::{DaemonConfig, Device, OnnxArenaStrategy};
+
+/// ONNX Runtime settings applied to every session a backend loads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionTuning {
+    /// Whether worker threads block instead of spinning while idle.
+    pub disable_spinning: bool,
+    /// How the execution provider's memory arena grows.
+    pub arena_extend_strategy: OnnxArenaStrategy,
+}
+
+impl SessionTuning {
+    /// Returns the tuning configured by `onnx_disable_spinning` and
+    /// `onnx_arena_extend_strategy`.
+    pub fn from_config(config: &DaemonConfig) -> Self {
+        Self {
+            disable_spinning: config.onnx_disable_spinning,
+            arena_extend_strategy: config.onnx_arena_extend_strategy,
+        }
+    }
+
+    /// Creates a session builder with the session-level settings applied.
+    ///
+    /// The arena strategy belongs to the execution provider and is applied
+    /// by [`get_providers`].
+    pub fn session_builder(&self) -> ort::Result<SessionBuilder> {
+        let builder = Session::builder()?;
+        if !self.disable_spinning {
+            return Ok(builder);
+        }
+        builder.with_intra_op_spinning(false)?.with_inter_op_spinning(false)
+    }
+}
 
 /// Represents an available execution provider with its name.
 #[derive(Debug, Clone)]
@@ -79,6 +112,7 @@ pub fn detect_available_providers() -> Vec<AvailableProvider> {
 ///
 /// * `device` - The device selection from configuration
 /// * `threads` - Optional number of threads for CPU execution
+/// * `tuning` - Arena settings for the providers
 ///
 /// # Returns
 ///
@@ -89,28 +123,32 @@ pub fn detect_available_providers() -> Vec<AvailableProvider> {
 ///
 /// ```no_run
 /// use lofi_daemon::config::Device;
-/// use lofi_daemon::models::device::get_providers;
+/// use lofi_daemon::models::device::{get_providers, SessionTuning};
 ///
-/// let providers = get_providers(Device::Auto, None);
+/// let providers = get_providers(Device::Auto, None, &SessionTuning::default());
 /// println!("Using {} provider(s)", providers.len());
 /// ```
-pub fn get_providers(device: Device, threads: Option<u32>) -> Vec<ExecutionProviderDispatch> {
+pub fn get_providers(
+    device: Device,
+    threads: Option<u32>,
+    tuning: &SessionTuning,
+) -> Vec<ExecutionProviderDispatch> {
     match device {
         Device::Auto => {
-            // Detect and return best available
+            // Detect and return best available, rebuilt with the arena settings
             let available = detect_available_providers();
-            if let Some(first) = available.into_iter().next() {
-                vec![first.provider]
-            } else {
+            match available.into_iter().next() {
+                Some(first) if first.name == "CUDA" => vec![build_cuda_provider(tuning)],
+                Some(first) if first.name != "CPU" => vec![first.provider],
                 // Fallback to default CPU
-                vec![build_cpu_provider(threads)]
+                _ => vec![build_cpu_provider(threads, tuning)],
             }
         }
         Device::Cpu => {
-            vec![build_cpu_provider(threads)]
+            vec![build_cpu_provider(threads, tuning)]
         }
         Device::Cuda => {
-            vec![CUDAExecutionProvider::default().build()]
+            vec![build_cuda_provider(tuning)]
         }
         Device::Metal => {
             vec![CoreMLExecutionProvider::default().build()]
@@ -122,8 +160,23 @@ pub fn get_providers(device: Device, threads: Option<u32>) -> Vec<ExecutionProvi
 ///
 /// Note: Thread configuration is handled at the session level via
 /// `SessionBuilder::with_intra_threads()`, not at the provider level.
-fn build_cpu_provider(_threads: Option<u32>) -> ExecutionProviderDispatch {
-    CPUExecutionProvider::default().build()
+/// ONNX Runtime has no CPU arena extend strategy, so `same_as_requested`
+/// turns the CPU arena off instead.
+fn build_cpu_provider(_threads: Option<u32>, tuning: &SessionTuning) -> ExecutionProviderDispatch {
+    let provider = CPUExecutionProvider::default();
+    match tuning.arena_extend_strategy {
+        OnnxArenaStrategy::NextPowerOfTwo => provider.build(),
+        OnnxArenaStrategy::SameAsRequested => provider.with_arena_allocator(false).build(),
+    }
+}
+
+/// Builds a CUDA execution provider with the configured arena strategy.
+fn build_cuda_provider(tuning: &SessionTuning) -> ExecutionProviderDispatch {
+    let strategy = match tuning.arena_extend_strategy {
+        OnnxArenaStrategy::NextPowerOfTwo => ArenaExtendStrategy::NextPowerOfTwo,
+        OnnxArenaStrategy::SameAsRequested => ArenaExtendStrategy::SameAsRequested,
+    };
+    CUDAExecutionProvider::default().with_arena_extend_strategy(strategy).build()
 }
 
 /// Gets a human-readable name for the device configuration.
@@ -157,22 +210,36 @@ mod tests {
 
     #[test]
     fn get_providers_auto_returns_something() {
-        let providers = get_providers(Device::Auto, None);
+        let providers = get_providers(Device::Auto, None, &SessionTuning::default());
         assert!(!providers.is_empty(), "Auto should return at least one provider");
     }
 
     #[test]
     fn get_providers_cpu_explicit() {
-        let providers = get_providers(Device::Cpu, None);
+        let providers = get_providers(Device::Cpu, None, &SessionTuning::default());
         assert_eq!(providers.len(), 1, "CPU should return exactly one provider");
     }
 
     #[test]
     fn get_providers_cpu_with_threads() {
-        let providers = get_providers(Device::Cpu, Some(4));
+        let providers = get_providers(Device::Cpu, Some(4), &SessionTuning::default());
         assert_eq!(providers.len(), 1, "Should return one provider with threads config");
     }
 
+    #[test]
+    fn session_tuning_follows_config() {
+        let mut config = DaemonConfig::default();
+        assert_eq!(SessionTuning::from_config(&config), SessionTuning::default());
+
+        config.onnx_disable_spinning = true;
+        config.onnx_arena_extend_strategy = OnnxArenaStrategy::SameAsRequested;
+        let tuning = SessionTuning::from_config(&config);
+        assert!(tuning.disable_spinning);
+        assert_eq!(tuning.arena_extend_strategy, OnnxArenaStrategy::SameAsRequested);
+        let providers = get_providers(Device::Cpu, None, &tuning);
+        assert_eq!(providers.len(), 1);
+    }
+
     #[test]
     fn get_device_name_explicit() {
         assert_eq!(get_device_name(Device::Cpu), "CPU");
@@ -3,15 +3,21 @@
 //! Downloads model files from HuggingFace if not present locally.
 //! Supports both MusicGen and ACE-Step backends with progress tracking
 //! and partial download resume.
+//!
+//! Each file is downloaded while holding a `<file>.partial.lock` lock file, so
+//! daemon instances sharing a model directory never write the same file.
+//! Files another instance is downloading are skipped and reported as an error
+//! once the remaining files are done.
 
 use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use crate::cache::{FileLock, LockAttempt};
 use crate::error::{DaemonError, Result};
 use crate::models::Backend;
 
-use super::ace_step::{MODEL_URLS as ACE_STEP_URLS, REQUIRED_FILES as ACE_STEP_FILES};
+use super::ace_step::{self, REQUIRED_FILES as ACE_STEP_FILES};
 use super::musicgen::{MODEL_URLS, REQUIRED_MODEL_FILES};
 
 /// Progress callback for download operations.
@@ -24,6 +30,23 @@ use super::musicgen::{MODEL_URLS, REQUIRED_MODEL_FILES};
 /// - `files_total`: Total number of files to download
 pub type DownloadProgressCallback = Box<dyn Fn(&str, u64, u64, usize, usize) + Send>;
 
+/// Files fetched by a download, not counting those already present.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DownloadSummary {
+    /// Number of files downloaded, including the optional `config.json`.
+    pub files_downloaded: usize,
+
+    /// Combined size of the downloaded files in bytes.
+    pub bytes_downloaded: u64,
+}
+
+impl DownloadSummary {
+    fn add(&mut self, bytes: u64) {
+        self.files_downloaded += 1;
+        self.bytes_downloaded += bytes;
+    }
+}
+
 /// Downloads all required model files if not present.
 ///
 /// Returns Ok(()) if all files exist or were successfully downloaded.
@@ -58,6 +81,7 @@ pub fn ensure_models(model_dir: &Path) -> Result<()> {
     eprintln!();
 
     // Download missing files
+    let mut busy = Vec::new();
     for file in &missing {
         // Find the URL for this file
         let url = MODEL_URLS
@@ -66,7 +90,13 @@ pub fn ensure_models(model_dir: &Path) -> Result<()> {
             .map(|(_, url)| *url);
 
         if let Some(url) = url {
-            download_file_streaming(url, &model_dir.join(file))?;
+            let dest = model_dir.join(file);
+            let Some(_lock) = lock_download(&dest, &mut busy)? else {
+                continue;
+            };
+            if !dest.exists() {
+                download_file_streaming(url, &dest)?;
+            }
         } else {
             return Err(DaemonError::model_download_failed(format!(
                 "No download URL for {}",
@@ -75,11 +105,15 @@ pub fn ensure_models(model_dir: &Path) -> Result<()> {
         }
     }
 
+    check_busy_downloads(&busy)?;
+
     // Also download config.json if missing (optional but useful)
     let config_path = model_dir.join("config.json");
     if !config_path.exists() {
         if let Some((_, url)) = MODEL_URLS.iter().find(|(name, _)| *name == "config.json") {
-            let _ = download_file_streaming(url, &config_path); // Ignore error, config is optional
+            if let Ok(Some(_lock)) = lock_download(&config_path, &mut Vec::new()) {
+                let _ = download_file_streaming(url, &config_path); // Ignore error, config is optional
+            }
         }
     }
 
@@ -93,7 +127,7 @@ pub fn ensure_models(model_dir: &Path) -> Result<()> {
 /// Returns Ok(()) if all files exist or were successfully downloaded.
 /// Note: ACE-Step models are larger (~11.5GB total).
 pub fn ensure_ace_step_models(model_dir: &Path) -> Result<()> {
-    download_ace_step_models_with_progress(model_dir, None)
+    download_ace_step_models_with_progress(model_dir, None).map(|_| ())
 }
 
 /// Downloads all required ACE-Step model files with progress tracking.
@@ -103,12 +137,12 @@ pub fn ensure_ace_step_models(model_dir: &Path) -> Result<()> {
 /// * `model_dir` - Directory to download models to
 /// * `on_progress` - Optional callback for progress updates
 ///
-/// Returns Ok(()) if all files exist or were successfully downloaded.
+/// Returns what was downloaded once all files exist.
 /// Note: ACE-Step models are larger (~11.5GB total).
 pub fn download_ace_step_models_with_progress(
     model_dir: &Path,
     on_progress: Option<DownloadProgressCallback>,
-) -> Result<()> {
+) -> Result<DownloadSummary> {
     // Create model directory if it doesn't exist
     if !model_dir.exists() {
         fs::create_dir_all(model_dir).map_err(|e| {
@@ -122,14 +156,10 @@ pub fn download_ace_step_models_with_progress(
 
     // Check which files are missing or incomplete
     let mut to_download: Vec<(&str, bool)> = Vec::new(); // (file, is_resume)
-    for file in ACE_STEP_FILES {
-        let path = model_dir.join(file);
+    for file in ace_step::missing_files(model_dir) {
         let partial_path = model_dir.join(format!("{}.partial", file));
 
-        if path.exists() {
-            // File exists, skip
-            continue;
-        } else if partial_path.exists() {
+        if partial_path.exists() {
             // Partial file exists, resume
             to_download.push((file, true));
         } else {
@@ -140,30 +170,35 @@ pub fn download_ace_step_models_with_progress(
 
     if to_download.is_empty() {
         eprintln!("All ACE-Step model files present.");
-        return Ok(());
+        return Ok(DownloadSummary::default());
     }
 
     let files_total = ACE_STEP_FILES.len();
     let mut files_completed = files_total - to_download.len();
 
     eprintln!("Downloading {} missing ACE-Step model files...", to_download.len());
+    for (file, _) in &to_download {
+        eprintln!("  - {}", file);
+    }
     eprintln!("(This may take a while - total ~11.5GB)");
     eprintln!();
 
     // Download missing files
+    let mut summary = DownloadSummary::default();
+    let mut busy = Vec::new();
     for (file, is_resume) in &to_download {
         // Find the URL for this file
-        let url = ACE_STEP_URLS
-            .iter()
-            .find(|(name, _)| name == file)
-            .map(|(_, url)| *url);
-
-        if let Some(url) = url {
+        if let Some(url) = ace_step::model_url(file) {
             let dest = model_dir.join(file);
-            if *is_resume {
-                download_file_with_resume(url, &dest, files_completed, files_total, &on_progress)?;
+            let Some(_lock) = lock_download(&dest, &mut busy)? else {
+                continue;
+            };
+            if dest.exists() {
+                // Finished by another instance since we checked
+            } else if *is_resume {
+                summary.add(download_file_with_resume(url, &dest, files_completed, files_total, &on_progress)?);
             } else {
-                download_file_with_progress(url, &dest, files_completed, files_total, &on_progress)?;
+                summary.add(download_file_with_progress(url, &dest, files_completed, files_total, &on_progress)?);
             }
             files_completed += 1;
         } else {
@@ -173,10 +208,11 @@ pub fn download_ace_step_models_with_progress(
             )));
         }
     }
+    check_busy_downloads(&busy)?;
 
     eprintln!();
     eprintln!("All ACE-Step models downloaded successfully.");
-    Ok(())
+    Ok(summary)
 }
 
 /// Downloads backend models with progress tracking.
@@ -186,11 +222,13 @@ pub fn download_ace_step_models_with_progress(
 /// * `backend` - Which backend to download models for
 /// * `model_dir` - Directory to download models to
 /// * `on_progress` - Callback for progress updates
+///
+/// Returns what was downloaded once all files exist.
 pub fn download_backend_with_progress(
     backend: Backend,
     model_dir: &Path,
     on_progress: Option<DownloadProgressCallback>,
-) -> Result<()> {
+) -> Result<DownloadSummary> {
     match backend {
         Backend::MusicGen => download_musicgen_models_with_progress(model_dir, on_progress),
         Backend::AceStep => download_ace_step_models_with_progress(model_dir, on_progress),
@@ -201,7 +239,7 @@ pub fn download_backend_with_progress(
 fn download_musicgen_models_with_progress(
     model_dir: &Path,
     on_progress: Option<DownloadProgressCallback>,
-) -> Result<()> {
+) -> Result<DownloadSummary> {
     // Create model directory if it doesn't exist
     if !model_dir.exists() {
         fs::create_dir_all(model_dir).map_err(|e| {
@@ -230,7 +268,7 @@ fn download_musicgen_models_with_progress(
 
     if to_download.is_empty() {
         eprintln!("All MusicGen model files present.");
-        return Ok(());
+        return Ok(DownloadSummary::default());
     }
 
     let files_total = REQUIRED_MODEL_FILES.len();
@@ -239,6 +277,8 @@ fn download_musicgen_models_with_progress(
     eprintln!("Downloading {} missing MusicGen model files...", to_download.len());
     eprintln!();
 
+    let mut summary = DownloadSummary::default();
+    let mut busy = Vec::new();
     for (file, is_resume) in &to_download {
         let url = MODEL_URLS
             .iter()
@@ -247,10 +287,15 @@ fn download_musicgen_models_with_progress(
 
         if let Some(url) = url {
             let dest = model_dir.join(file);
-            if *is_resume {
-                download_file_with_resume(url, &dest, files_completed, files_total, &on_progress)?;
+            let Some(_lock) = lock_download(&dest, &mut busy)? else {
+                continue;
+            };
+            if dest.exists() {
+                // Finished by another instance since we checked
+            } else if *is_resume {
+                summary.add(download_file_with_resume(url, &dest, files_completed, files_total, &on_progress)?);
             } else {
-                download_file_with_progress(url, &dest, files_completed, files_total, &on_progress)?;
+                summary.add(download_file_with_progress(url, &dest, files_completed, files_total, &on_progress)?);
             }
             files_completed += 1;
         } else {
@@ -261,21 +306,68 @@ fn download_musicgen_models_with_progress(
         }
     }
 
+    check_busy_downloads(&busy)?;
+
     // Download config.json if missing
     let config_path = model_dir.join("config.json");
     if !config_path.exists() {
         if let Some((_, url)) = MODEL_URLS.iter().find(|(name, _)| *name == "config.json") {
-            let _ = download_file_with_progress(url, &config_path, files_completed, files_total, &on_progress);
+            if let Ok(Some(_lock)) = lock_download(&config_path, &mut Vec::new()) {
+                // Ignore errors, config is optional
+                if let Ok(bytes) = download_file_with_progress(url, &config_path, files_completed, files_total, &on_progress) {
+                    summary.add(bytes);
+                }
+            }
         }
     }
 
     eprintln!();
     eprintln!("All MusicGen models downloaded successfully.");
-    Ok(())
+    Ok(summary)
+}
+
+/// Returns the lock file held while `dest` is downloaded.
+fn download_lock_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial.lock");
+    dest.with_file_name(name)
+}
+
+/// Takes the download lock of `dest`.
+///
+/// Returns None if another daemon instance holds it, after adding the file
+/// and that instance's PID to `busy`.
+fn lock_download(dest: &Path, busy: &mut Vec<String>) -> Result<Option<FileLock>> {
+    let filename = dest.file_name().unwrap_or_default().to_string_lossy();
+    match FileLock::try_acquire(&download_lock_path(dest))? {
+        LockAttempt::Acquired(lock) => Ok(Some(lock)),
+        LockAttempt::Held(owner) => {
+            let entry = match owner {
+                Some(owner) => format!("{} (PID {})", filename, owner.pid),
+                None => filename.to_string(),
+            };
+            eprintln!("  Skipping {}: another daemon instance is downloading it", entry);
+            busy.push(entry);
+            Ok(None)
+        }
+    }
+}
+
+/// Fails if any files were skipped because another instance is downloading them.
+fn check_busy_downloads(busy: &[String]) -> Result<()> {
+    if busy.is_empty() {
+        return Ok(());
+    }
+    Err(DaemonError::model_download_failed(format!(
+        "Another daemon instance is downloading {}; retry once it finishes",
+        busy.join(", ")
+    )))
 }
 
 /// Downloads a file using streaming to handle large files.
-fn download_file_streaming(url: &str, dest: &Path) -> Result<()> {
+///
+/// Returns the size of the downloaded file in bytes.
+fn download_file_streaming(url: &str, dest: &Path) -> Result<u64> {
     download_file_with_progress(url, dest, 0, 1, &None)
 }
 
@@ -288,13 +380,15 @@ fn download_file_streaming(url: &str, dest: &Path) -> Result<()> {
 /// * `files_completed` - Number of files already completed
 /// * `files_total` - Total number of files to download
 /// * `on_progress` - Optional progress callback
+///
+/// Returns the size of the downloaded file in bytes.
 fn download_file_with_progress(
     url: &str,
     dest: &Path,
     files_completed: usize,
     files_total: usize,
     on_progress: &Option<DownloadProgressCallback>,
-) -> Result<()> {
+) -> Result<u64> {
     let filename = dest.file_name().unwrap_or_default().to_string_lossy();
     let partial_path = dest.with_extension(
         dest.extension()
@@ -400,7 +494,7 @@ fn download_file_with_progress(
         callback(&filename, downloaded, downloaded, files_completed + 1, files_total);
     }
 
-    Ok(())
+    Ok(downloaded)
 }
 
 /// Downloads a file with resume support for partial downloads.
@@ -412,13 +506,16 @@ fn download_file_with_progress(
 /// * `files_completed` - Number of files already completed
 /// * `files_total` - Total number of files to download
 /// * `on_progress` - Optional progress callback
+///
+/// Returns the size of the downloaded file in bytes, including the part
+/// downloaded before.
 fn download_file_with_resume(
     url: &str,
     dest: &Path,
     files_completed: usize,
     files_total: usize,
     on_progress: &Option<DownloadProgressCallback>,
-) -> Result<()> {
+) -> Result<u64> {
     let filename = dest.file_name().unwrap_or_default().to_string_lossy();
     let partial_path = dest.with_extension(
         dest.extension()
@@ -538,7 +635,7 @@ fn download_file_with_resume(
             callback(&filename, downloaded, downloaded, files_completed + 1, files_total);
         }
 
-        Ok(())
+        Ok(downloaded)
     } else if status.is_success() {
         // Server doesn't support resume (returned 200 OK instead of 206 Partial Content)
         // Delete partial and do full download
@@ -589,5 +686,158 @@ mod tests {
             assert!(has_url, "Missing URL for required file: {}", file);
         }
     }
+    /// Serves `body` to each of `requests` connections on a local port,
+    /// honoring `Range: bytes=N-` with a 206 response.
+    ///
+    /// Returns the URL and a handle yielding the request heads received.
+    fn serve(body: &'static [u8], requests: usize) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/model.onnx", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut heads = Vec::new();
+            for _ in 0..requests {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut head = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let range_start = head
+                    .lines()
+                    .find_map(|l| l.to_ascii_lowercase().strip_prefix("range: bytes=").map(str::to_string))
+                    .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok());
+                let (status, part) = match range_start {
+                    Some(start) => ("206 Partial Content", &body[start..]),
+                    None => ("200 OK", body),
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    part.len()
+                )
+                .unwrap();
+                stream.write_all(part).unwrap();
+                heads.push(head);
+            }
+            heads
+        });
+        (url, handle)
+    }
+
+    type ProgressCalls = std::sync::Arc<std::sync::Mutex<Vec<(String, u64, u64, usize, usize)>>>;
+
+    fn recording_callback() -> (ProgressCalls, Option<DownloadProgressCallback>) {
+        let calls = ProgressCalls::default();
+        let recorded = calls.clone();
+        let callback: DownloadProgressCallback = Box::new(move |name, done, total, completed, files| {
+            recorded.lock().unwrap().push((name.to_string(), done, total, completed, files));
+        });
+        (calls, Some(callback))
+    }
+
+    #[test]
+    fn download_reports_progress_and_size() {
+        static BODY: [u8; 200_000] = [7; 200_000];
+        let (url, server) = serve(&BODY, 1);
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("model.onnx");
+
+        let (calls, on_progress) = recording_callback();
+        let bytes = download_file_with_progress(&url, &dest, 2, 5, &on_progress).unwrap();
+        server.join().unwrap();
+
+        assert_eq!(bytes, BODY.len() as u64);
+        assert_eq!(fs::read(&dest).unwrap(), BODY);
+        assert!(!dir.path().join("model.onnx.partial").exists());
+        let calls = calls.lock().unwrap();
+        assert!(calls.len() >= 2, "{:?}", calls.len());
+        assert!(calls.iter().all(|call| call.0 == "model.onnx" && call.2 == BODY.len() as u64));
+        assert_eq!(calls[0].3, 2);
+        let last = calls.last().unwrap();
+        assert_eq!((last.1, last.3, last.4), (BODY.len() as u64, 3, 5));
+    }
+
+    #[test]
+    fn download_resumes_from_partial_file() {
+        static BODY: &[u8] = b"0123456789abcdefghij";
+        let (url, server) = serve(BODY, 1);
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("model.onnx");
+        fs::write(dir.path().join("model.onnx.partial"), &BODY[..8]).unwrap();
+
+        let (calls, on_progress) = recording_callback();
+        let bytes = download_file_with_resume(&url, &dest, 0, 1, &on_progress).unwrap();
+        let heads = server.join().unwrap();
+
+        assert!(heads[0].to_ascii_lowercase().contains("range: bytes=8-"), "{}", heads[0]);
+        assert_eq!(bytes, BODY.len() as u64);
+        assert_eq!(fs::read(&dest).unwrap(), BODY);
+        let last = calls.lock().unwrap().last().cloned().unwrap();
+        assert_eq!((last.1, last.2, last.3), (20, 20, 1));
+    }
+
+    #[test]
+    fn failed_download_keeps_no_file() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/model.onnx", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0u8; 1024];
+            let _ = stream.read(&mut buffer).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("model.onnx");
+
+        let err = download_file_with_progress(&url, &dest, 0, 1, &None).unwrap_err();
+        server.join().unwrap();
+        assert_eq!(err.code, crate::error::ErrorCode::ModelDownloadFailed);
+        assert!(err.message.contains("404"), "{}", err.message);
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn downloads_in_progress_elsewhere_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("decoder_model_merged.onnx");
+        let lock_path = dir.path().join("decoder_model_merged.onnx.partial.lock");
+        assert_eq!(download_lock_path(&dest), lock_path);
+
+        // Another instance downloading the file holds its lock
+        let other = match FileLock::try_acquire(&lock_path).unwrap() {
+            LockAttempt::Acquired(lock) => lock,
+            LockAttempt::Held(owner) => panic!("lock held by {:?}", owner),
+        };
+        let mut busy = Vec::new();
+        assert!(lock_download(&dest, &mut busy).unwrap().is_none());
+        assert_eq!(
+            busy,
+            vec![format!("decoder_model_merged.onnx (PID {})", std::process::id())]
+        );
+        let err = check_busy_downloads(&busy).unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::ModelDownloadFailed);
+        assert!(err.message.contains("decoder_model_merged.onnx"), "{}", err.message);
+
+        // Once it finishes, the file can be locked here
+        drop(other);
+        let mut busy = Vec::new();
+        let lock = lock_download(&dest, &mut busy).unwrap();
+        assert!(lock.is_some());
+        assert!(busy.is_empty());
+        assert!(check_busy_downloads(&busy).is_ok());
+    }
 }
 
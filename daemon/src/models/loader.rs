@@ -4,11 +4,17 @@
 //! returning a LoadedModels enum that can be used for generation.
 
 use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
 
 use crate::config::DaemonConfig;
-use crate::error::Result;
+use crate::error::{DaemonError, Result};
 use crate::models::ace_step;
 use crate::models::backend::{Backend, LoadedModels};
+use crate::models::device::SessionTuning;
 use crate::models::musicgen;
 
 /// Loads models for the specified backend.
@@ -30,9 +36,116 @@ pub fn load_backend(backend: Backend, model_path: &Path, config: &DaemonConfig)
     }
 }
 
+/// Loads models like [`load_backend`], waiting at most `timeout`.
+///
+/// Loading runs on its own thread. If it has not finished when `timeout`
+/// elapses, TIMED_OUT is returned and the thread is left to finish in the
+/// background (it cannot be stopped); whatever it loads is then dropped.
+/// Keep a [`PendingLoad`] instead to wait on the same load again.
+pub fn load_backend_with_timeout(
+    backend: Backend,
+    model_path: &Path,
+    config: &DaemonConfig,
+    timeout: Duration,
+) -> Result<LoadedModels> {
+    PendingLoad::start(backend, model_path, config).wait(timeout)
+}
+
+/// A model load running on its own thread.
+///
+/// Loading cannot be stopped once started. When waiting for it times out,
+/// the caller keeps the `PendingLoad` and waits on it again on retry, so a
+/// second copy of the models is never loaded alongside the first.
+#[derive(Debug)]
+pub struct PendingLoad {
+    backend: Backend,
+    receiver: mpsc::Receiver<Result<LoadedModels>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl PendingLoad {
+    /// Starts loading the models for `backend` like [`load_backend`].
+    pub fn start(backend: Backend, model_path: &Path, config: &DaemonConfig) -> Self {
+        let model_path = model_path.to_path_buf();
+        let config = config.clone();
+        Self::spawn(backend, move || load_backend(backend, &model_path, &config))
+    }
+
+    /// Runs `load` on a new thread as the load of `backend`.
+    pub fn spawn<F>(backend: Backend, load: F) -> Self
+    where
+        F: FnOnce() -> Result<LoadedModels> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            // The receiver is gone if the caller dropped the load
+            let _ = sender.send(load());
+        });
+        Self {
+            backend,
+            receiver,
+            handle: Some(handle),
+        }
+    }
+
+    /// Returns the backend being loaded.
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Waits at most `timeout` for the load to finish.
+    ///
+    /// Returns TIMED_OUT if it is still running; the load carries on and can
+    /// be waited on again. Any other result is final.
+    pub fn wait(&mut self, timeout: Duration) -> Result<LoadedModels> {
+        let start = Instant::now();
+        match self.receiver.recv_timeout(timeout) {
+            Ok(result) => {
+                self.join();
+                result
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                eprintln!(
+                    "Warning: loading {} models timed out; the load is still running in the background",
+                    self.backend
+                );
+                Err(DaemonError::timed_out(
+                    start.elapsed().as_secs_f32(),
+                    timeout.as_secs_f32(),
+                ))
+            }
+            // The thread ended without sending, i.e. it panicked
+            Err(mpsc::RecvTimeoutError::Disconnected) => match self.handle.take().map(|h| h.join()) {
+                Some(Err(panic)) => std::panic::resume_unwind(panic),
+                _ => Err(DaemonError::model_load_failed("Model loading thread exited early")),
+            },
+        }
+    }
+
+    fn join(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
 /// Loads MusicGen models from the specified path.
 fn load_musicgen(model_path: &Path, config: &DaemonConfig) -> Result<LoadedModels> {
-    let models = musicgen::load_sessions_with_device(model_path, config.device, config.threads)?;
+    let tuning = SessionTuning::from_config(config);
+    let mut models =
+        musicgen::load_sessions_with_tuning(
+            model_path,
+            config.tokenizer_override(Backend::MusicGen),
+            config.device,
+            config.threads,
+            &tuning,
+        )?;
+    // Warmup only saves time on the first request, so a failure is not fatal
+    if config.warm_up_on_load {
+        if let Err(e) = models.warmup() {
+            eprintln!("Warning: MusicGen warmup failed, continuing without it: {}", e);
+        }
+    }
     Ok(LoadedModels::MusicGen(models))
 }
 
@@ -44,53 +157,26 @@ fn load_ace_step(model_path: &Path, config: &DaemonConfig) -> Result<LoadedModel
     }
 
     // Check for required model files
-    check_ace_step_models(model_path)?;
+    ace_step::check_models_with_tokenizer(model_path, config.tokenizer_override(Backend::AceStep))?;
 
     // Load ACE-Step models
     let models = ace_step::AceStepModels::load(model_path, config)?;
     Ok(LoadedModels::AceStep(models))
 }
 
-/// Required model files for ACE-Step.
-const ACE_STEP_REQUIRED_FILES: &[&str] = &[
-    "text_encoder.onnx",
-    "transformer_encoder.onnx",
-    "transformer_decoder.onnx",
-    "dcae_decoder.onnx",
-    "vocoder.onnx",
-    "tokenizer.json",
-];
-
-/// Checks if all required ACE-Step model files exist.
-fn check_ace_step_models(model_dir: &Path) -> Result<()> {
-    let mut missing = Vec::new();
-
-    for file in ACE_STEP_REQUIRED_FILES {
-        let path = model_dir.join(file);
-        if !path.exists() {
-            missing.push(*file);
-        }
-    }
-
-    if missing.is_empty() {
-        Ok(())
-    } else {
-        Err(crate::error::DaemonError::model_not_found(format!(
-            "Missing ACE-Step model files in {}: {}",
-            model_dir.display(),
-            missing.join(", ")
-        )))
-    }
-}
-
 /// Checks if a backend's models are available without loading them.
 ///
 /// This is useful for quickly checking backend availability without
-/// the overhead of loading large models into memory.
-pub fn check_backend_available(backend: Backend, model_path: &Path) -> bool {
+/// the overhead of loading large models into memory. The tokenizer is looked
+/// for at `tokenizer_override` instead of the model directory if set.
+pub fn check_backend_available(
+    backend: Backend,
+    model_path: &Path,
+    tokenizer_override: Option<&Path>,
+) -> bool {
     match backend {
-        Backend::MusicGen => musicgen::check_models(model_path).is_ok(),
-        Backend::AceStep => check_ace_step_models(model_path).is_ok(),
+        Backend::MusicGen => musicgen::check_models_with_tokenizer(model_path, tokenizer_override).is_ok(),
+        Backend::AceStep => ace_step::check_models_with_tokenizer(model_path, tokenizer_override).is_ok(),
     }
 }
 
@@ -104,7 +190,7 @@ pub fn get_backend_version(backend: Backend, config: &DaemonConfig) -> Option<St
         Backend::AceStep => {
             let path = config.effective_ace_step_model_path();
             if path.exists() {
-                Some("ace-step-v1".to_string())
+                Some(ace_step::model_version())
             } else {
                 None
             }
@@ -112,17 +198,54 @@ pub fn get_backend_version(backend: Backend, config: &DaemonConfig) -> Option<St
     }
 }
 
+/// Returns a fingerprint of a backend's installed model files.
+///
+/// Two installs with different fingerprints hold different model files (for
+/// example another export or quantization), so the same parameters will not
+/// produce bit-identical audio. Returns None if any required file is missing.
+pub fn get_backend_fingerprint(backend: Backend, config: &DaemonConfig) -> Option<String> {
+    match backend {
+        Backend::MusicGen => {
+            model_fingerprint(&config.effective_model_path(), musicgen::REQUIRED_MODEL_FILES)
+        }
+        Backend::AceStep => {
+            model_fingerprint(&config.effective_ace_step_model_path(), ace_step::REQUIRED_FILES)
+        }
+    }
+}
+
+/// Hashes the names and sizes of `files` in `model_path`.
+///
+/// Sizes stand in for contents so that fingerprinting does not read
+/// gigabytes of weights.
+fn model_fingerprint(model_path: &Path, files: &[&str]) -> Option<String> {
+    let mut hasher = Sha256::new();
+    for file in files {
+        let size = std::fs::metadata(model_path.join(file)).ok()?.len();
+        hasher.update(format!("{}:{}\n", file, size).as_bytes());
+    }
+    Some(hex::encode(&hasher.finalize()[..8]))
+}
+
 /// Detects which backends are available.
 ///
 /// Returns a list of backends that have all required model files present.
 pub fn detect_available_backends(config: &DaemonConfig) -> Vec<Backend> {
     let mut available = Vec::new();
 
-    if check_backend_available(Backend::MusicGen, &config.effective_model_path()) {
+    if check_backend_available(
+        Backend::MusicGen,
+        &config.effective_model_path(),
+        config.tokenizer_override(Backend::MusicGen),
+    ) {
         available.push(Backend::MusicGen);
     }
 
-    if check_backend_available(Backend::AceStep, &config.effective_ace_step_model_path()) {
+    if check_backend_available(
+        Backend::AceStep,
+        &config.effective_ace_step_model_path(),
+        config.tokenizer_override(Backend::AceStep),
+    ) {
         available.push(Backend::AceStep);
     }
 
@@ -136,15 +259,62 @@ mod tests {
     #[test]
     fn ace_step_required_files() {
         // Verify all required files are listed
-        assert!(ACE_STEP_REQUIRED_FILES.contains(&"text_encoder.onnx"));
-        assert!(ACE_STEP_REQUIRED_FILES.contains(&"vocoder.onnx"));
-        assert!(ACE_STEP_REQUIRED_FILES.contains(&"tokenizer.json"));
+        assert!(ace_step::REQUIRED_FILES.contains(&"text_encoder.onnx"));
+        assert!(ace_step::REQUIRED_FILES.contains(&"vocoder.onnx"));
+        assert!(ace_step::REQUIRED_FILES.contains(&"tokenizer.json"));
     }
 
     #[test]
     fn check_nonexistent_dir_fails() {
         let path = std::path::Path::new("/nonexistent/path");
-        let result = check_ace_step_models(path);
+        let result = ace_step::check_models(path);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn pending_load_reports_errors_and_timeouts() {
+        let config = DaemonConfig::default();
+        let err = load_backend_with_timeout(
+            Backend::AceStep,
+            Path::new("/nonexistent/path"),
+            &config,
+            Duration::from_secs(5),
+        )
+        .unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::BackendNotInstalled);
+
+        let mut load = PendingLoad::spawn(Backend::MusicGen, || Ok(LoadedModels::None));
+        assert_eq!(load.backend(), Backend::MusicGen);
+        assert!(load.wait(Duration::from_secs(5)).unwrap().is_none());
+
+        let start = Instant::now();
+        let mut load = PendingLoad::spawn(Backend::MusicGen, || {
+            thread::sleep(Duration::from_millis(500));
+            Err(DaemonError::model_load_failed("slow load"))
+        });
+        let err = load.wait(Duration::from_millis(50)).unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::TimedOut);
+        assert!(start.elapsed() < Duration::from_millis(400));
+        assert!(err.message.starts_with("Timed out after"), "{}", err.message);
+
+        // Waiting again gets the same load's result
+        let err = load.wait(Duration::from_secs(5)).unwrap_err();
+        assert!(err.message.contains("slow load"), "{}", err.message);
+    }
+
+    #[test]
+    fn fingerprint_tracks_file_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = ["a.onnx", "b.onnx"];
+        assert_eq!(model_fingerprint(dir.path(), &files), None);
+
+        std::fs::write(dir.path().join("a.onnx"), b"weights").unwrap();
+        std::fs::write(dir.path().join("b.onnx"), b"graph").unwrap();
+        let fingerprint = model_fingerprint(dir.path(), &files).unwrap();
+        assert_eq!(fingerprint.len(), 16);
+        assert_eq!(model_fingerprint(dir.path(), &files), Some(fingerprint.clone()));
+
+        std::fs::write(dir.path().join("b.onnx"), b"other graph").unwrap();
+        assert_ne!(model_fingerprint(dir.path(), &files), Some(fingerprint));
+    }
 }
@@ -7,6 +7,7 @@
 //! - [`loader`]: Unified model loading for all backends
 //! - [`device`]: Device detection and execution provider selection
 //! - [`downloader`]: Model download and management
+//! - [`tokenizer`]: Tokenizer loading with corruption checks
 
 pub mod ace_step;
 pub mod backend;
@@ -14,18 +15,26 @@ pub mod device;
 pub mod downloader;
 pub mod loader;
 pub mod musicgen;
+pub mod tokenizer;
 
 // Re-export commonly used types from submodules
 pub use ace_step::AceStepModels;
 pub use backend::{Backend, GenerateDispatchParams, LoadedModels};
-pub use device::{detect_available_providers, get_device_name, get_providers, AvailableProvider};
+pub use device::{
+    detect_available_providers, get_device_name, get_providers, AvailableProvider, SessionTuning,
+};
 pub use downloader::{
     download_backend_with_progress, ensure_ace_step_models, ensure_models, DownloadProgressCallback,
+    DownloadSummary,
+};
+pub use loader::{
+    check_backend_available, detect_available_backends, get_backend_fingerprint,
+    get_backend_version, load_backend, load_backend_with_timeout, PendingLoad,
 };
-pub use loader::{check_backend_available, detect_available_backends, load_backend};
 pub use musicgen::{
     check_models, detect_model_version, generate_model_version, load_sessions,
-    load_sessions_with_device, DelayPatternMaskIds, Logits, MusicGenAudioCodec, MusicGenDecoder,
+    load_sessions_with_device, load_sessions_with_tuning, DelayPatternMaskIds, Logits, MusicGenAudioCodec, MusicGenDecoder,
     MusicGenModels, MusicGenTextEncoder, DEFAULT_GUIDANCE_SCALE, DEFAULT_TOP_K, MODEL_URLS,
     REQUIRED_MODEL_FILES,
 };
+pub use tokenizer::load_tokenizer;
@@ -2,7 +2,6 @@
 //!
 //! Decodes token sequences into audio samples using EnCodec.
 
-use std::collections::VecDeque;
 use std::path::Path;
 
 use half::f16;
@@ -11,30 +10,60 @@ use ort::session::Session;
 use ort::value::{DynValue, Tensor};
 
 use crate::error::{DaemonError, Result};
+use crate::generation::SAMPLES_PER_TOKEN;
+use crate::models::device::SessionTuning;
+use crate::types::ModelConfig;
+
+/// Token groups decoded on each side of a window in [`MusicGenAudioCodec::decode_window`].
+///
+/// The codec's convolutions see neighbouring frames, so a window decoded on its
+/// own differs from the full decode near its edges, which is audible as a click
+/// where windows are joined. Decoding a few extra groups of context on each
+/// side and discarding their audio keeps the edges close to the full decode.
+pub const DECODE_WINDOW_CONTEXT: usize = 4;
+
+/// Statistics from decoding a token stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    /// Token groups replaced with silence because they could not be decoded.
+    pub recovered_errors: usize,
+}
 
 /// MusicGen audio codec (EnCodec decoder).
 pub struct MusicGenAudioCodec {
     audio_codec: Session,
+    codebook_size: i64,
 }
 
 impl MusicGenAudioCodec {
     /// Loads the audio codec from a directory.
     ///
-    /// Expects `encodec_decode.onnx` in the directory.
+    /// Expects `encodec_decode.onnx` in the directory. Token ids are checked
+    /// against the musicgen-small codebook size.
     pub fn load(model_dir: &Path) -> Result<Self> {
-        Self::load_with_providers(model_dir, &[])
+        Self::load_with_providers(
+            model_dir,
+            &ModelConfig::musicgen_small(),
+            &[],
+            &SessionTuning::default(),
+        )
     }
 
     /// Loads the audio codec from a directory with specific execution providers.
     ///
-    /// Expects `encodec_decode.onnx` in the directory.
+    /// Expects `encodec_decode.onnx` in the directory. Valid token ids are
+    /// `0..config.vocab_size`, the size of each codebook the decoder samples
+    /// from.
     pub fn load_with_providers(
         model_dir: &Path,
+        config: &ModelConfig,
         providers: &[ExecutionProviderDispatch],
+        tuning: &SessionTuning,
     ) -> Result<Self> {
         let codec_path = model_dir.join("encodec_decode.onnx");
 
-        let mut builder = Session::builder()
+        let mut builder = tuning
+            .session_builder()
             .map_err(|e| DaemonError::model_load_failed(format!("Failed to create session: {}", e)))?;
 
         if !providers.is_empty() {
@@ -47,23 +76,63 @@ impl MusicGenAudioCodec {
             DaemonError::model_load_failed(format!("Failed to load encodec_decode.onnx: {}", e))
         })?;
 
-        Ok(Self { audio_codec })
+        Ok(Self {
+            audio_codec,
+            codebook_size: i64::from(config.vocab_size),
+        })
+    }
+
+    /// Returns the number of entries in each codebook.
+    pub fn codebook_size(&self) -> i64 {
+        self.codebook_size
     }
 
     /// Decodes tokens into audio samples.
     ///
     /// Takes an iterator of `[i64; 4]` token arrays (one per timestep, 4 codebooks)
-    /// and returns a deque of f32 audio samples.
-    pub fn decode(&mut self, tokens: impl IntoIterator<Item = [i64; 4]>) -> Result<VecDeque<f32>> {
+    /// and returns f32 audio samples. Token groups with an id outside the
+    /// codebook, and frames the codec decodes to non-finite samples, are
+    /// replaced with silence of the same length; the count is returned in
+    /// [`DecodeStats`]. Codec inference errors are still returned as errors.
+    pub fn decode(
+        &mut self,
+        tokens: impl IntoIterator<Item = [i64; 4]>,
+    ) -> Result<(Vec<f32>, DecodeStats)> {
+        let frames: Vec<[i64; 4]> = tokens.into_iter().collect();
+        let codebook_size = self.codebook_size;
+        decode_with_recovery(&frames, codebook_size, |run| self.decode_frames(run))
+    }
+
+    /// Decodes the `len` token groups starting at `start` into audio samples.
+    ///
+    /// Returns the samples for exactly those groups ([`SAMPLES_PER_TOKEN`] per
+    /// group), so consecutive windows can be concatenated for progressive
+    /// playback without holding the audio of the whole clip. Up to
+    /// [`DECODE_WINDOW_CONTEXT`] groups on each side are decoded as context and
+    /// discarded. Recovery works as in [`Self::decode`]; the returned stats
+    /// include recoveries within the context groups.
+    pub fn decode_window(
+        &mut self,
+        tokens: &[[i64; 4]],
+        start: usize,
+        len: usize,
+    ) -> Result<(Vec<f32>, DecodeStats)> {
+        decode_window_with(tokens, start, len, DECODE_WINDOW_CONTEXT, |frames| {
+            self.decode(frames.iter().copied())
+        })
+    }
+
+    /// Runs the codec on a sequence of token groups.
+    fn decode_frames(&mut self, frames: &[[i64; 4]]) -> Result<Vec<f32>> {
         let mut data = vec![];
-        for ids in tokens {
-            for id in ids {
+        for ids in frames {
+            for &id in ids {
                 data.push(id);
             }
         }
 
         if data.is_empty() {
-            return Ok(VecDeque::new());
+            return Ok(Vec::new());
         }
 
         let seq_len = data.len() / 4;
@@ -86,7 +155,7 @@ impl MusicGenAudioCodec {
             .audio_codec
             .run(ort::inputs![input_tensor])
             .map_err(|e| {
-                DaemonError::model_inference_failed(format!("Audio codec inference failed: {}", e))
+                DaemonError::model_inference_failed_with_source("Audio codec inference failed", e)
             })?;
 
         let audio_values: DynValue = outputs.remove("audio_values").ok_or_else(|| {
@@ -95,7 +164,7 @@ impl MusicGenAudioCodec {
 
         // Try f32 first, then f16
         if let Ok((_shape, data)) = audio_values.try_extract_tensor::<f32>() {
-            return Ok(data.iter().copied().collect());
+            return Ok(data.to_vec());
         }
         if let Ok((_shape, data)) = audio_values.try_extract_tensor::<f16>() {
             return Ok(data.iter().map(|e| f32::from(*e)).collect());
@@ -107,8 +176,276 @@ impl MusicGenAudioCodec {
     }
 }
 
+/// Decodes `frames` run by run, replacing undecodable token groups with silence.
+///
+/// Consecutive valid token groups are passed to `decode_run` together so the
+/// codec keeps its context; a stream without bad groups is decoded in a single
+/// call. Each invalid group becomes [`SAMPLES_PER_TOKEN`] zero samples, and each
+/// decoded frame containing NaN or infinity is zeroed.
+fn decode_with_recovery<F>(
+    frames: &[[i64; 4]],
+    codebook_size: i64,
+    mut decode_run: F,
+) -> Result<(Vec<f32>, DecodeStats)>
+where
+    F: FnMut(&[[i64; 4]]) -> Result<Vec<f32>>,
+{
+    let mut audio = Vec::with_capacity(frames.len() * SAMPLES_PER_TOKEN);
+    let mut stats = DecodeStats::default();
+    let mut run_start = 0;
+
+    for index in 0..=frames.len() {
+        let invalid = frames
+            .get(index)
+            .map(|group| check_token_group(group, codebook_size, index));
+        if let Some(Ok(())) = invalid {
+            continue;
+        }
+
+        // Decode the valid run that ends here
+        let run = &frames[run_start..index];
+        if !run.is_empty() {
+            let mut samples = decode_run(run)?;
+            let samples_per_frame = (samples.len() / run.len()).max(1);
+            for (frame, block) in samples.chunks_mut(samples_per_frame).enumerate() {
+                if block.iter().any(|s| !s.is_finite()) {
+                    eprintln!(
+                        "Warning: audio codec produced non-finite samples for frame {}, replacing with silence",
+                        run_start + frame
+                    );
+                    block.fill(0.0);
+                    stats.recovered_errors += 1;
+                }
+            }
+            audio.extend(samples);
+        }
+
+        if let Some(Err(e)) = invalid {
+            eprintln!("Warning: {}, replacing with silence", e);
+            audio.resize(audio.len() + SAMPLES_PER_TOKEN, 0.0);
+            stats.recovered_errors += 1;
+        }
+        run_start = index + 1;
+    }
+
+    Ok((audio, stats))
+}
+
+/// Decodes `tokens[start..start + len]` with up to `context` extra groups on
+/// each side, returning only the samples of the window itself.
+fn decode_window_with<F>(
+    tokens: &[[i64; 4]],
+    start: usize,
+    len: usize,
+    context: usize,
+    decode: F,
+) -> Result<(Vec<f32>, DecodeStats)>
+where
+    F: FnOnce(&[[i64; 4]]) -> Result<(Vec<f32>, DecodeStats)>,
+{
+    let end = start
+        .checked_add(len)
+        .filter(|&end| end <= tokens.len())
+        .ok_or_else(|| {
+            DaemonError::model_inference_failed(format!(
+                "Decode window {}..{} is outside the {} token groups",
+                start,
+                start.saturating_add(len),
+                tokens.len()
+            ))
+        })?;
+    if len == 0 {
+        return Ok((Vec::new(), DecodeStats::default()));
+    }
+
+    let context_start = start.saturating_sub(context);
+    let context_end = (end + context).min(tokens.len());
+    let (samples, stats) = decode(&tokens[context_start..context_end])?;
+
+    let groups = context_end - context_start;
+    let samples_per_frame = samples.len() / groups;
+    let offset = (start - context_start) * samples_per_frame;
+    let window = samples
+        .get(offset..offset + len * samples_per_frame)
+        .filter(|window| !window.is_empty())
+        .ok_or_else(|| {
+            DaemonError::model_inference_failed(format!(
+                "Audio codec returned {} samples for {} token groups",
+                samples.len(),
+                groups
+            ))
+        })?
+        .to_vec();
+    Ok((window, stats))
+}
+
+/// Checks that every token in a group is a valid codebook index.
+fn check_token_group(group: &[i64; 4], codebook_size: i64, frame: usize) -> Result<()> {
+    match group.iter().position(|id| !(0..codebook_size).contains(id)) {
+        Some(codebook) => Err(DaemonError::model_inference_failed(format!(
+            "Token {} in codebook {} at frame {} is outside the codebook (0-{})",
+            group[codebook],
+            codebook,
+            frame,
+            codebook_size - 1
+        ))),
+        None => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    const CODEBOOK_SIZE: i64 = 2048;
+
+    /// Stands in for the codec: one block of 0.5 per frame.
+    fn fake_codec(calls: &mut Vec<usize>) -> impl FnMut(&[[i64; 4]]) -> Result<Vec<f32>> + '_ {
+        move |run| {
+            calls.push(run.len());
+            Ok(vec![0.5; run.len() * SAMPLES_PER_TOKEN])
+        }
+    }
+
+    #[test]
+    fn valid_stream_is_decoded_in_one_call() {
+        let frames = vec![[1i64, 2, 3, 4]; 5];
+        let mut calls = Vec::new();
+        let (audio, stats) =
+            decode_with_recovery(&frames, CODEBOOK_SIZE, fake_codec(&mut calls)).unwrap();
+
+        assert_eq!(calls, [5]);
+        assert_eq!(audio.len(), 5 * SAMPLES_PER_TOKEN);
+        assert_eq!(stats.recovered_errors, 0);
+    }
+
+    #[test]
+    fn invalid_token_group_becomes_silence() {
+        let mut frames = vec![[1i64, 2, 3, 4]; 5];
+        frames[2][1] = CODEBOOK_SIZE + 1;
+        let mut calls = Vec::new();
+        let (audio, stats) =
+            decode_with_recovery(&frames, CODEBOOK_SIZE, fake_codec(&mut calls)).unwrap();
+
+        // The runs on either side of the bad group are decoded separately
+        assert_eq!(calls, [2, 2]);
+        assert_eq!(stats.recovered_errors, 1);
+        assert_eq!(audio.len(), 5 * SAMPLES_PER_TOKEN);
+
+        let blocks: Vec<&[f32]> = audio.chunks(SAMPLES_PER_TOKEN).collect();
+        assert!(blocks[2].iter().all(|&s| s == 0.0));
+        for i in [0, 1, 3, 4] {
+            assert!(blocks[i].iter().all(|&s| s == 0.5));
+        }
+    }
+
+    #[test]
+    fn tokens_are_checked_against_the_configured_codebook() {
+        // The tiny test model declares a 16-entry vocabulary
+        let mut config = ModelConfig::musicgen_small();
+        config.vocab_size = 16;
+        let codebook_size = i64::from(config.vocab_size);
+
+        let frames = vec![[1i64, 2, 3, 4], [1, 2, 3, 16], [1, 2, 3, 15]];
+        let mut calls = Vec::new();
+        let (_, stats) =
+            decode_with_recovery(&frames, codebook_size, fake_codec(&mut calls)).unwrap();
+        assert_eq!(calls, [1, 1]);
+        assert_eq!(stats.recovered_errors, 1);
+
+        let err = check_token_group(&frames[1], codebook_size, 1).unwrap_err();
+        assert!(err.message.contains("(0-15)"), "{}", err.message);
+    }
+
+    #[test]
+    fn non_finite_frames_are_zeroed() {
+        let frames = vec![[1i64, 2, 3, 4]; 3];
+        let (audio, stats) = decode_with_recovery(&frames, CODEBOOK_SIZE, |run| {
+            let mut samples = vec![0.5; run.len() * 4];
+            samples[5] = f32::NAN;
+            Ok(samples)
+        })
+        .unwrap();
+
+        assert_eq!(stats.recovered_errors, 1);
+        assert_eq!(audio, [0.5, 0.5, 0.5, 0.5, 0.0, 0.0, 0.0, 0.0, 0.5, 0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn codec_errors_are_returned() {
+        let frames = vec![[1i64, 2, 3, 4]; 2];
+        let result = decode_with_recovery(&frames, CODEBOOK_SIZE, |_| {
+            Err(DaemonError::model_inference_failed("boom"))
+        });
+        assert!(result.is_err());
+    }
+
+    /// Stands in for the codec: each frame decodes to its first token, plus
+    /// 0.5 if it is the first or last frame of the call (an edge artifact).
+    fn edge_sensitive_codec(frames: &[[i64; 4]]) -> Result<(Vec<f32>, DecodeStats)> {
+        let last = frames.len() - 1;
+        let samples = frames
+            .iter()
+            .enumerate()
+            .flat_map(|(i, frame)| {
+                let edge = if i == 0 || i == last { 0.5 } else { 0.0 };
+                vec![frame[0] as f32 + edge; SAMPLES_PER_TOKEN]
+            })
+            .collect();
+        Ok((samples, DecodeStats::default()))
+    }
+
+    #[test]
+    fn decode_window_returns_only_the_window() {
+        let tokens: Vec<[i64; 4]> = (0..20).map(|i| [i, 0, 0, 0]).collect();
+        let mut decoded = Vec::new();
+        let (audio, _) = decode_window_with(&tokens, 8, 4, 2, |frames| {
+            decoded.push((frames[0][0], frames.len()));
+            edge_sensitive_codec(frames)
+        })
+        .unwrap();
+
+        assert_eq!(decoded, [(6, 8)]);
+        assert_eq!(audio.len(), 4 * SAMPLES_PER_TOKEN);
+        let blocks: Vec<f32> = audio.chunks(SAMPLES_PER_TOKEN).map(|b| b[0]).collect();
+        assert_eq!(blocks, [8.0, 9.0, 10.0, 11.0]);
+    }
+
+    #[test]
+    fn decode_windows_join_like_the_full_decode() {
+        let tokens: Vec<[i64; 4]> = (0..10).map(|i| [i, 0, 0, 0]).collect();
+        let (full, _) = edge_sensitive_codec(&tokens).unwrap();
+
+        let mut joined = Vec::new();
+        for start in (0..10).step_by(3) {
+            let len = 3.min(10 - start);
+            let (audio, _) =
+                decode_window_with(&tokens, start, len, 1, edge_sensitive_codec).unwrap();
+            joined.extend(audio);
+        }
+        assert_eq!(joined, full);
+
+        // Without context every window edge differs from the full decode
+        let (audio, _) = decode_window_with(&tokens, 3, 3, 0, edge_sensitive_codec).unwrap();
+        assert_eq!(audio[0], 3.5);
+    }
+
+    #[test]
+    fn decode_window_rejects_out_of_range() {
+        let tokens = vec![[1i64, 2, 3, 4]; 5];
+        let codec = |_: &[[i64; 4]]| -> Result<(Vec<f32>, DecodeStats)> { unreachable!() };
+        assert!(decode_window_with(&tokens, 3, 3, 2, codec).is_err());
+        assert!(decode_window_with(&tokens, usize::MAX, 2, 2, codec).is_err());
+
+        let (audio, _) = decode_window_with(&tokens, 5, 0, 2, codec).unwrap();
+        assert!(audio.is_empty());
+
+        // A codec output too short to cover every group is an error, not silence
+        let short_codec = |_: &[[i64; 4]]| Ok((vec![0.5; 2], DecodeStats::default()));
+        let err = decode_window_with(&tokens, 1, 2, 1, short_codec).unwrap_err();
+        assert!(err.message.contains("2 samples for 4 token groups"), "{}", err);
+    }
+
     #[test]
     fn empty_tokens_returns_empty_audio() {
         let tokens: Vec<[i64; 4]> = vec![];
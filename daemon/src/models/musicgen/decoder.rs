@@ -1,7 +1,10 @@
 //! MusicGen decoder wrapper with KV cache support.
 //!
 //! Implements autoregressive token generation using split decoder architecture
-//! with KV cache optimization for efficient inference.
+//! with KV cache optimization for efficient inference. A generation can be
+//! resumed from its saved [`DecoderState`], so chunked long-form generation
+//! only pays for the full decoder pass once, and long generations attend to
+//! a bounded [`ContextWindow`] of recent positions.
 
 use std::borrow::Cow;
 use std::collections::VecDeque;
@@ -10,20 +13,32 @@ use std::path::Path;
 use half::f16;
 use ort::execution_providers::ExecutionProviderDispatch;
 use ort::session::{Session, SessionInputValue};
+use ort::tensor::TensorElementType;
 use ort::value::{DynValue, Tensor};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 
-use crate::error::{DaemonError, Result};
-use crate::types::ModelConfig;
+use crate::error::{DaemonError, ErrorCode, Result};
+use crate::generation::CancelToken;
+use crate::models::device::SessionTuning;
+use crate::types::{ModelConfig, Precision};
 
 use super::delay_pattern::DelayPatternMaskIds;
 use super::logits::{Logits, DEFAULT_GUIDANCE_SCALE, DEFAULT_TOP_K};
 
+/// Number of EnCodec codebooks generated in parallel.
+const CODEBOOKS: usize = 4;
+
+/// Decoder batch size: one conditional and one unconditional row per codebook.
+const GUIDANCE_BATCH_SIZE: usize = 2 * CODEBOOKS;
+
 /// MusicGen decoder using split architecture with KV cache.
 pub struct MusicGenDecoder {
     decoder_model: Session,
     decoder_with_past: Session,
     config: ModelConfig,
-    use_fp16: bool,
+    precision: Precision,
+    context_window: ContextWindow,
 }
 
 impl MusicGenDecoder {
@@ -31,7 +46,7 @@ impl MusicGenDecoder {
     ///
     /// Expects `decoder_model.onnx` and `decoder_with_past_model.onnx` in the directory.
     pub fn load(model_dir: &Path, config: ModelConfig) -> Result<Self> {
-        Self::load_with_providers(model_dir, config, &[])
+        Self::load_with_providers(model_dir, config, &[], &SessionTuning::default())
     }
 
     /// Loads the decoder models from a directory with specific execution providers.
@@ -41,11 +56,13 @@ impl MusicGenDecoder {
         model_dir: &Path,
         config: ModelConfig,
         providers: &[ExecutionProviderDispatch],
+        tuning: &SessionTuning,
     ) -> Result<Self> {
         let decoder_path = model_dir.join("decoder_model.onnx");
         let decoder_with_past_path = model_dir.join("decoder_with_past_model.onnx");
 
-        let mut decoder_builder = Session::builder()
+        let mut decoder_builder = tuning
+            .session_builder()
             .map_err(|e| DaemonError::model_load_failed(format!("Failed to create session: {}", e)))?;
 
         if !providers.is_empty() {
@@ -60,7 +77,8 @@ impl MusicGenDecoder {
             DaemonError::model_load_failed(format!("Failed to load decoder_model.onnx: {}", e))
         })?;
 
-        let mut decoder_with_past_builder = Session::builder()
+        let mut decoder_with_past_builder = tuning
+            .session_builder()
             .map_err(|e| DaemonError::model_load_failed(format!("Failed to create session: {}", e)))?;
 
         if !providers.is_empty() {
@@ -81,102 +99,346 @@ impl MusicGenDecoder {
                     ))
                 })?;
 
-        // Detect if using fp16 by checking model path
-        let use_fp16 = model_dir
-            .to_str()
-            .map(|s| s.contains("fp16"))
-            .unwrap_or(false);
+        let graph_precision = decoder_model
+            .inputs
+            .iter()
+            .find(|input| input.name == "encoder_hidden_states")
+            .and_then(|input| input.input_type.tensor_type())
+            .and_then(precision_of);
+        let (precision, warning) = resolve_precision(config.precision, graph_precision);
+        if let Some(warning) = warning {
+            eprintln!("Warning: {}", warning);
+        }
 
         Ok(Self {
             decoder_model,
             decoder_with_past,
             config,
-            use_fp16,
+            precision,
+            context_window: ContextWindow::default(),
         })
     }
 
+    /// Returns the precision of the encoder hidden states the decoder takes.
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
     /// Generates tokens autoregressively from the encoder hidden states.
     ///
-    /// Returns a VecDeque of `[i64; 4]` token arrays.
-    /// Note: max_len is the desired number of output tokens. We generate extra
-    /// tokens to compensate for the delay pattern masking (which loses N-1 tokens
-    /// at the start, where N=4 codebooks).
+    /// Returns exactly `target_frames` de-delayed `[i64; 4]` frames. The decoder
+    /// runs `target_frames + 3` steps internally to let the delay pattern fill
+    /// (see [`super::delay_pattern`]).
     pub fn generate_tokens(
         &mut self,
         encoder_hidden_states: DynValue,
         encoder_attention_mask: DynValue,
-        max_len: usize,
+        target_frames: usize,
     ) -> Result<VecDeque<[i64; 4]>> {
-        self.generate_tokens_with_progress(encoder_hidden_states, encoder_attention_mask, max_len, |_, _| {})
+        self.generate_tokens_with_progress(encoder_hidden_states, encoder_attention_mask, target_frames, |_, _| {})
     }
 
     /// Generates tokens autoregressively with a progress callback.
     ///
+    /// Sampling uses a random seed; see [`Self::generate_tokens_seeded`].
+    pub fn generate_tokens_with_progress<F>(
+        &mut self,
+        encoder_hidden_states: DynValue,
+        encoder_attention_mask: DynValue,
+        target_frames: usize,
+        on_progress: F,
+    ) -> Result<VecDeque<[i64; 4]>>
+    where
+        F: Fn(usize, usize),
+    {
+        self.generate_tokens_seeded(
+            encoder_hidden_states,
+            encoder_attention_mask,
+            target_frames,
+            rand::random(),
+            None,
+            on_progress,
+        )
+    }
+
+    /// Generates tokens autoregressively, sampling with a seeded RNG.
+    ///
+    /// The same inputs and seed always produce the same tokens. Layer count,
+    /// vocabulary size and pad token come from the model config.
+    ///
     /// # Arguments
     ///
     /// * `encoder_hidden_states` - Encoded text embeddings
     /// * `encoder_attention_mask` - Attention mask for encoder
-    /// * `max_len` - Number of output tokens desired
-    /// * `on_progress` - Callback receiving (tokens_generated, total_tokens)
-    pub fn generate_tokens_with_progress<F>(
+    /// * `target_frames` - Number of de-delayed frames to emit
+    /// * `seed` - Seed for top-k sampling
+    /// * `cancel` - Checked before every decoder step; with `keep_partial` set,
+    ///   the frames emitted so far are returned
+    /// * `on_progress` - Callback receiving (frames_emitted, target_frames)
+    pub fn generate_tokens_seeded<F>(
         &mut self,
         encoder_hidden_states: DynValue,
         encoder_attention_mask: DynValue,
-        max_len: usize,
+        target_frames: usize,
+        seed: u64,
+        cancel: Option<&CancelToken>,
         on_progress: F,
     ) -> Result<VecDeque<[i64; 4]>>
     where
         F: Fn(usize, usize),
     {
-        // Compensate for delay pattern: we need N-1 extra tokens (where N=4 codebooks)
-        // to get the desired number of output tokens
-        let generation_len = max_len + 3;
-        // Get model parameters
-        let num_hidden_layers = self.config.num_hidden_layers as usize;
+        self.generate_tokens_resumable(
+            encoder_hidden_states,
+            encoder_attention_mask,
+            target_frames,
+            seed,
+            cancel,
+            on_progress,
+        )
+        .map(|(frames, _)| frames)
+    }
+
+    /// Generates tokens like [`Self::generate_tokens_seeded`], also returning
+    /// the decoder state so that [`Self::continue_tokens`] can extend the
+    /// generation later.
+    pub fn generate_tokens_resumable<F>(
+        &mut self,
+        encoder_hidden_states: DynValue,
+        encoder_attention_mask: DynValue,
+        target_frames: usize,
+        seed: u64,
+        cancel: Option<&CancelToken>,
+        on_progress: F,
+    ) -> Result<(VecDeque<[i64; 4]>, DecoderState)>
+    where
+        F: Fn(usize, usize),
+    {
+        let mut state = self.first_pass(encoder_hidden_states, encoder_attention_mask, seed)?;
+        let frames = self.continue_tokens(&mut state, target_frames, cancel, on_progress)?;
+        Ok((frames, state))
+    }
+
+    /// Emits `target_frames` more frames from a saved decoder state.
+    ///
+    /// Only `decoder_with_past` runs: the KV cache primes it, so the full
+    /// `decoder_model` pass that starts every fresh generation is skipped.
+    /// Whenever the cache fills the decoder's [`ContextWindow`] it is cut back
+    /// with [`Self::truncate_kv_cache`]. The frames are exactly those a single
+    /// longer generation with the same seed would have emitted next. `state`
+    /// is updated to continue again afterwards.
+    pub fn continue_tokens<F>(
+        &mut self,
+        state: &mut DecoderState,
+        target_frames: usize,
+        cancel: Option<&CancelToken>,
+        on_progress: F,
+    ) -> Result<VecDeque<[i64; 4]>>
+    where
+        F: Fn(usize, usize),
+    {
+        let window = self.context_window;
+        let mut frames = VecDeque::with_capacity(target_frames);
+        loop {
+            if state.context_len() >= window.max_len {
+                self.truncate_kv_cache(state, window.keep_len)?;
+            }
+            let done = frames.len();
+            let chunk = (target_frames - done).min(state.frames_until_full(window.max_len));
+            let emitted = self.run_steps(state, chunk, cancel, |current, _| {
+                on_progress(done + current, target_frames)
+            });
+            let emitted = match emitted {
+                Err(e)
+                    if e.code == ErrorCode::GenerationCancelled
+                        && done > 0
+                        && cancel.is_some_and(|c| c.keep_partial()) =>
+                {
+                    eprintln!("Generation cancelled, keeping {} of {} frames", done, target_frames);
+                    break;
+                }
+                emitted => emitted?,
+            };
+            let cancelled = emitted.len() < chunk;
+            frames.extend(emitted);
+            if cancelled || frames.len() >= target_frames {
+                break;
+            }
+        }
+        Ok(frames)
+    }
+
+    /// Cuts the KV cache in `state` back to its last `max_len` positions.
+    ///
+    /// The decoder places each step right after the positions in the cache,
+    /// so slicing the old positions off would leave the kept keys and values
+    /// at their original positions while new steps land at much earlier ones.
+    /// Instead the cache is rebuilt the way a fresh generation builds it: the
+    /// pad token at position zero, then the inputs of the last `max_len - 1`
+    /// steps replayed through `decoder_with_past`. Nothing is sampled, so the
+    /// RNG, the emitted frames and [`DecoderState::steps`] are unchanged.
+    ///
+    /// Costs `max_len` decoder steps. Does nothing if the cache already holds
+    /// at most `max_len` positions.
+    pub fn truncate_kv_cache(&mut self, state: &mut DecoderState, max_len: usize) -> Result<()> {
+        if state.context_len() <= max_len {
+            return Ok(());
+        }
+        if max_len == 0 {
+            return Err(DaemonError::model_inference_failed(
+                "The KV cache must keep at least one position",
+            ));
+        }
+
         let pad_token_id = self.config.pad_token_id;
+        let (_, mut kv_cache) = self.run_full_pass(
+            &state.encoder_hidden_states,
+            &state.encoder_attention_mask,
+            [pad_token_id; CODEBOOKS],
+        )?;
+        let steps = state.steps();
+        for position in steps + 1 - max_len..steps {
+            let input_ids = state.delay_pattern_mask_ids.delayed_masked(position, pad_token_id);
+            run_with_past(
+                &mut self.decoder_with_past,
+                &mut kv_cache,
+                &state.encoder_attention_mask,
+                input_ids,
+            )?;
+        }
+        state.kv_cache = kv_cache;
+        Ok(())
+    }
+
+    /// Sets the context window of later generations.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `keep_len` is zero or not below `max_len`.
+    pub fn set_context_window(&mut self, window: ContextWindow) {
+        assert!(
+            window.keep_len > 0 && window.keep_len < window.max_len,
+            "Context window must keep between 1 and {} positions",
+            window.max_len.saturating_sub(1)
+        );
+        self.context_window = window;
+    }
+
+    /// Returns the context window generations are bounded to.
+    pub fn context_window(&self) -> ContextWindow {
+        self.context_window
+    }
+
+    /// Runs `decoder_with_past` until `target_frames` more frames are emitted,
+    /// without truncating the KV cache.
+    fn run_steps<F>(
+        &mut self,
+        state: &mut DecoderState,
+        target_frames: usize,
+        cancel: Option<&CancelToken>,
+        on_progress: F,
+    ) -> Result<VecDeque<[i64; 4]>>
+    where
+        F: Fn(usize, usize),
+    {
+        let vocab_size = self.config.vocab_size as usize;
+        let pad_token_id = self.config.pad_token_id;
+        let decoder_with_past = &mut self.decoder_with_past;
+        let DecoderState {
+            kv_cache,
+            encoder_attention_mask,
+            delay_pattern_mask_ids,
+            rng,
+            ..
+        } = state;
+
+        // Run autoregressive generation until exactly target_frames frames are emitted
+        emit_frames(
+            delay_pattern_mask_ids,
+            pad_token_id,
+            target_frames,
+            |input_ids| {
+                let logits =
+                    run_with_past(decoder_with_past, kv_cache, encoder_attention_mask, input_ids)?;
+                check_vocab_size(&logits, vocab_size)?;
+                Ok(logits
+                    .apply_free_guidance(DEFAULT_GUIDANCE_SCALE)
+                    .sample_top_k_with_rng(DEFAULT_TOP_K, rng)
+                    .iter()
+                    .map(|e| e.0)
+                    .collect())
+            },
+            cancel,
+            on_progress,
+        )
+    }
+
+    /// Runs the full decoder on the pad token, sampling the first token and
+    /// building the KV cache for `decoder_with_past`.
+    fn first_pass(
+        &mut self,
+        encoder_hidden_states: DynValue,
+        encoder_attention_mask: DynValue,
+        seed: u64,
+    ) -> Result<DecoderState> {
+        let vocab_size = self.config.vocab_size as usize;
+        let pad_token_id = self.config.pad_token_id;
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
 
         // Duplicate encoder states for classifier-free guidance (conditional + unconditional)
-        let encoder_hidden_states = duplicate_with_zeros(&encoder_hidden_states, self.use_fp16)?;
+        let encoder_hidden_states = duplicate_with_zeros(&encoder_hidden_states, self.precision)?;
         let encoder_attention_mask = duplicate_with_zeros_i64(&encoder_attention_mask)?;
 
-        // Build initial inputs map
-        let mut inputs: Vec<(String, DynValue)> = Vec::new();
+        let (logits, kv_cache) = self.run_full_pass(
+            &encoder_hidden_states,
+            &encoder_attention_mask,
+            [pad_token_id; CODEBOOKS],
+        )?;
+        check_vocab_size(&logits, vocab_size)?;
 
-        // Add encoder inputs
-        inputs.push(("encoder_attention_mask".to_string(), encoder_attention_mask));
-        inputs.push(("encoder_hidden_states".to_string(), encoder_hidden_states));
+        let mut delay_pattern_mask_ids = DelayPatternMaskIds::<4>::new();
+        delay_pattern_mask_ids.push(
+            logits
+                .apply_free_guidance(DEFAULT_GUIDANCE_SCALE)
+                .sample_top_k_with_rng(DEFAULT_TOP_K, &mut rng)
+                .iter()
+                .map(|e| e.0),
+        );
 
-        // Add initial input_ids (batch of 8 with pad tokens)
-        let initial_input_ids = Tensor::from_array(([8usize, 1], vec![pad_token_id; 8]))
-            .map_err(|e| DaemonError::model_inference_failed(format!("Failed to create input_ids: {}", e)))?;
-        inputs.push(("input_ids".to_string(), initial_input_ids.into_dyn()));
+        Ok(DecoderState {
+            kv_cache,
+            encoder_hidden_states,
+            encoder_attention_mask,
+            delay_pattern_mask_ids,
+            rng,
+        })
+    }
 
-        // Run first pass with full decoder
-        let session_inputs: Vec<(Cow<str>, SessionInputValue)> = inputs
-            .iter()
-            .map(|(k, v)| (Cow::from(k.as_str()), SessionInputValue::from(v.view())))
-            .collect();
+    /// Runs the full decoder for a single step, returning the logits and the
+    /// KV cache for `decoder_with_past`.
+    fn run_full_pass(
+        &mut self,
+        encoder_hidden_states: &DynValue,
+        encoder_attention_mask: &DynValue,
+        input_ids: [i64; CODEBOOKS],
+    ) -> Result<(Logits, Vec<(String, DynValue)>)> {
+        let num_hidden_layers = self.config.num_hidden_layers as usize;
+        let input_ids = guidance_input_ids(input_ids)?;
+        let session_inputs: Vec<(Cow<str>, SessionInputValue)> = vec![
+            (Cow::from("encoder_attention_mask"), SessionInputValue::from(encoder_attention_mask.view())),
+            (Cow::from("encoder_hidden_states"), SessionInputValue::from(encoder_hidden_states.view())),
+            (Cow::from("input_ids"), SessionInputValue::from(input_ids.view())),
+        ];
 
         let mut outputs = self.decoder_model.run(session_inputs).map_err(|e| {
-            DaemonError::model_inference_failed(format!("Initial decoder inference failed: {}", e))
+            DaemonError::model_inference_failed_with_source("Initial decoder inference failed", e)
         })?;
 
-        let mut delay_pattern_mask_ids = DelayPatternMaskIds::<4>::new();
-
-        // Process first iteration logits
         let logits_value = outputs.remove("logits").ok_or_else(|| {
             DaemonError::model_inference_failed("logits not found in output")
         })?;
         let logits = Logits::from_3d_dyn_value(&logits_value)?;
-        delay_pattern_mask_ids.push(
-            logits
-                .apply_free_guidance(DEFAULT_GUIDANCE_SCALE)
-                .sample_top_k(DEFAULT_TOP_K)
-                .iter()
-                .map(|e| e.0),
-        );
 
-        // Extract KV cache from first pass
+        // Extract KV cache from the full pass
         let mut kv_cache: Vec<(String, DynValue)> = Vec::new();
         for j in 0..num_hidden_layers {
             let dk = outputs.remove(format!("present.{j}.decoder.key")).ok_or_else(|| {
@@ -198,104 +460,278 @@ impl MusicGenDecoder {
             kv_cache.push((format!("past_key_values.{j}.encoder.value"), ev));
         }
 
-        // Store encoder attention mask for subsequent passes
-        let encoder_attention_mask = inputs
-            .into_iter()
-            .find(|(k, _)| k == "encoder_attention_mask")
-            .map(|(_, v)| v)
-            .ok_or_else(|| {
-                DaemonError::model_inference_failed("encoder_attention_mask not found")
-            })?;
+        Ok((logits, kv_cache))
+    }
+}
 
-        // Collect results
-        let mut results = VecDeque::new();
+/// Builds the `input_ids` of one decoder step, repeating the codebook tokens
+/// for the conditional and unconditional rows.
+fn guidance_input_ids([a, b, c, d]: [i64; CODEBOOKS]) -> Result<Tensor<i64>> {
+    Tensor::from_array(([GUIDANCE_BATCH_SIZE, 1], vec![a, b, c, d, a, b, c, d]))
+        .map_err(|e| DaemonError::model_inference_failed(format!("Failed to create input_ids: {}", e)))
+}
 
-        // Run autoregressive generation
-        for i in 0..generation_len {
-            // Call progress callback with current token count
-            on_progress(i, generation_len);
-            let [a, b, c, d] = delay_pattern_mask_ids.last_delayed_masked(pad_token_id);
+/// Runs one `decoder_with_past` step and returns its logits.
+///
+/// The decoder keys and values in `kv_cache` are replaced with the ones
+/// grown by this step; encoder keys and values never change.
+fn run_with_past(
+    decoder_with_past: &mut Session,
+    kv_cache: &mut [(String, DynValue)],
+    encoder_attention_mask: &DynValue,
+    input_ids: [i64; CODEBOOKS],
+) -> Result<Logits> {
+    let input_ids = guidance_input_ids(input_ids)?;
 
-            // Create new input_ids
-            let input_ids = Tensor::from_array(([8usize, 1], vec![a, b, c, d, a, b, c, d]))
-                .map_err(|e| DaemonError::model_inference_failed(format!("Failed to create input_ids: {}", e)))?;
+    // Build inputs for decoder_with_past
+    let mut session_inputs: Vec<(Cow<str>, SessionInputValue)> = vec![
+        (Cow::from("input_ids"), SessionInputValue::from(input_ids.view())),
+        (Cow::from("encoder_attention_mask"), SessionInputValue::from(encoder_attention_mask.view())),
+    ];
+    for (k, v) in kv_cache.iter() {
+        session_inputs.push((Cow::from(k.as_str()), SessionInputValue::from(v.view())));
+    }
 
-            // Build inputs for decoder_with_past
-            let mut session_inputs: Vec<(Cow<str>, SessionInputValue)> = vec![
-                (Cow::from("input_ids"), SessionInputValue::from(input_ids.view())),
-                (Cow::from("encoder_attention_mask"), SessionInputValue::from(encoder_attention_mask.view())),
-            ];
+    let mut outputs = decoder_with_past.run(session_inputs).map_err(|e| {
+        DaemonError::model_inference_failed_with_source("Decoder with past inference failed", e)
+    })?;
 
-            for (k, v) in &kv_cache {
-                session_inputs.push((Cow::from(k.as_str()), SessionInputValue::from(v.view())));
-            }
+    let logits_value = outputs.remove("logits").ok_or_else(|| {
+        DaemonError::model_inference_failed("logits not found")
+    })?;
+    let logits = Logits::from_3d_dyn_value(&logits_value)?;
 
-            let mut outputs = self.decoder_with_past.run(session_inputs).map_err(|e| {
-                DaemonError::model_inference_failed(format!(
-                    "Decoder with past inference failed: {}",
-                    e
-                ))
-            })?;
+    // Update KV cache (only decoder keys/values change)
+    let num_layers = kv_cache.len() / 4;
+    for j in 0..num_layers {
+        let dk = outputs.remove(format!("present.{j}.decoder.key")).ok_or_else(|| {
+            DaemonError::model_inference_failed(format!("present.{j}.decoder.key not found"))
+        })?;
+        let dv = outputs.remove(format!("present.{j}.decoder.value")).ok_or_else(|| {
+            DaemonError::model_inference_failed(format!("present.{j}.decoder.value not found"))
+        })?;
 
-            let logits_value = outputs.remove("logits").ok_or_else(|| {
-                DaemonError::model_inference_failed("logits not found")
-            })?;
-            let logits = Logits::from_3d_dyn_value(&logits_value)?;
-            delay_pattern_mask_ids.push(
-                logits
-                    .apply_free_guidance(DEFAULT_GUIDANCE_SCALE)
-                    .sample_top_k(DEFAULT_TOP_K)
-                    .iter()
-                    .map(|e| e.0),
-            );
+        kv_cache[j * 4] = (format!("past_key_values.{j}.decoder.key"), dk);
+        kv_cache[j * 4 + 1] = (format!("past_key_values.{j}.decoder.value"), dv);
+    }
 
-            if let Some(last_de_delayed) = delay_pattern_mask_ids.last_de_delayed() {
-                results.push_back(last_de_delayed);
-            }
+    Ok(logits)
+}
 
-            // Update KV cache (only decoder keys/values change)
-            let num_layers = kv_cache.len() / 4;
-            for j in 0..num_layers {
-                let dk = outputs.remove(format!("present.{j}.decoder.key")).ok_or_else(|| {
-                    DaemonError::model_inference_failed(format!("present.{j}.decoder.key not found"))
-                })?;
-                let dv = outputs.remove(format!("present.{j}.decoder.value")).ok_or_else(|| {
-                    DaemonError::model_inference_failed(format!("present.{j}.decoder.value not found"))
-                })?;
+/// Bounds the decoder positions a generation attends to.
+///
+/// Once the KV cache holds `max_len` positions it is cut back to the last
+/// `keep_len` (see [`MusicGenDecoder::truncate_kv_cache`]), so generations
+/// longer than MusicGen's training clips keep a context of the length the
+/// model was trained on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextWindow {
+    /// Positions the cache may hold before it is truncated.
+    pub max_len: usize,
+    /// Positions kept by each truncation; below `max_len`.
+    pub keep_len: usize,
+}
+
+impl Default for ContextWindow {
+    /// 30 seconds of frames at 50 per second, MusicGen's training clip
+    /// length, cut back to the last 10 seconds.
+    fn default() -> Self {
+        Self {
+            max_len: 1500,
+            keep_len: 500,
+        }
+    }
+}
+
+/// Decoder state at the end of a generation, for continuing it without
+/// rerunning the full `decoder_model` pass.
+///
+/// Holds the `past_key_values.*` inputs of `decoder_with_past`, the encoder
+/// hidden states and attention mask duplicated for guidance, the delay
+/// pattern (so codebook offsets carry over between chunks) and the sampling
+/// RNG. Encoder keys and values depend only on the prompt and are reused
+/// as-is; decoder keys and values grow by one position per step, see
+/// [`MusicGenDecoder::truncate_kv_cache`].
+pub struct DecoderState {
+    kv_cache: Vec<(String, DynValue)>,
+    encoder_hidden_states: DynValue,
+    encoder_attention_mask: DynValue,
+    delay_pattern_mask_ids: DelayPatternMaskIds<4>,
+    rng: ChaCha8Rng,
+}
+
+impl DecoderState {
+    /// Returns the number of decoder positions held in the KV cache.
+    pub fn context_len(&self) -> usize {
+        self.kv_cache
+            .first()
+            .and_then(|(_, value)| value.shape().get(KV_SEQ_AXIS).copied())
+            .map_or(0, |len| len as usize)
+    }
+
+    /// Returns the decoder steps run so far, including the first pass and
+    /// steps whose positions were truncated from the KV cache.
+    pub fn steps(&self) -> usize {
+        self.delay_pattern_mask_ids.len()
+    }
+
+    /// Returns the frames emitted so far, including earlier chunks.
+    pub fn frames_emitted(&self) -> usize {
+        self.delay_pattern_mask_ids.de_delayed_len()
+    }
+
+    /// Returns how many more frames can be emitted before the KV cache holds
+    /// `max_len` positions, counting steps that fill the delay pattern. At
+    /// least one, so generation always progresses.
+    fn frames_until_full(&self, max_len: usize) -> usize {
+        let room = max_len.saturating_sub(self.context_len());
+        let fill = DelayPatternMaskIds::<CODEBOOKS>::pushes_for_frames(self.frames_emitted())
+            .saturating_sub(self.steps());
+        room.saturating_sub(fill).max(1)
+    }
+}
+
+impl std::fmt::Debug for DecoderState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecoderState")
+            .field("context_len", &self.context_len())
+            .field("steps", &self.steps())
+            .field("frames_emitted", &self.frames_emitted())
+            .finish_non_exhaustive()
+    }
+}
 
-                kv_cache[j * 4] = (format!("past_key_values.{j}.decoder.key"), dk);
-                kv_cache[j * 4 + 1] = (format!("past_key_values.{j}.decoder.value"), dv);
+/// Sequence axis of KV-cache tensors (`[batch, heads, seq, head_dim]`).
+const KV_SEQ_AXIS: usize = 2;
+
+/// Checks that the decoder produced logits for the configured vocabulary.
+fn check_vocab_size(logits: &Logits, vocab_size: usize) -> Result<()> {
+    if logits.vocab_size() != vocab_size {
+        return Err(DaemonError::model_inference_failed(format!(
+            "Decoder produced logits for {} tokens but config.json declares vocab_size {}",
+            logits.vocab_size(),
+            vocab_size
+        )));
+    }
+    Ok(())
+}
+
+/// Drives the autoregressive loop until exactly `target_frames` de-delayed frames are emitted.
+///
+/// `step` receives the delay-masked input ids for the next decoder pass and returns the
+/// sampled token ids for every codebook. Progress is reported as (frames_emitted, target_frames).
+/// If `cancel` is triggered the loop stops early: the frames emitted so far are returned when
+/// the token keeps partial output and at least one frame exists, otherwise generation fails.
+fn emit_frames<const N: usize, S, F>(
+    delay_pattern_mask_ids: &mut DelayPatternMaskIds<N>,
+    pad_token_id: i64,
+    target_frames: usize,
+    mut step: S,
+    cancel: Option<&CancelToken>,
+    on_progress: F,
+) -> Result<VecDeque<[i64; N]>>
+where
+    S: FnMut([i64; N]) -> Result<Vec<i64>>,
+    F: Fn(usize, usize),
+{
+    let mut results = VecDeque::with_capacity(target_frames);
+
+    while results.len() < target_frames {
+        if let Some(cancel) = cancel.filter(|c| c.is_cancelled()) {
+            if cancel.keep_partial() && !results.is_empty() {
+                eprintln!(
+                    "Generation cancelled, keeping {} of {} frames",
+                    results.len(),
+                    target_frames
+                );
+                return Ok(results);
             }
+            return Err(DaemonError::generation_cancelled());
+        }
+
+        on_progress(results.len(), target_frames);
+
+        let input_ids = delay_pattern_mask_ids.last_delayed_masked(pad_token_id);
+        delay_pattern_mask_ids.push(step(input_ids)?);
+
+        if let Some(last_de_delayed) = delay_pattern_mask_ids.last_de_delayed() {
+            results.push_back(last_de_delayed);
         }
+    }
 
-        // Final progress callback
-        on_progress(generation_len, generation_len);
+    // Final progress callback
+    on_progress(target_frames, target_frames);
+
+    Ok(results)
+}
+
+/// Returns the precision of a floating-point tensor element type.
+pub(super) fn precision_of(ty: TensorElementType) -> Option<Precision> {
+    match ty {
+        TensorElementType::Float16 => Some(Precision::Fp16),
+        TensorElementType::Float32 => Some(Precision::Fp32),
+        _ => None,
+    }
+}
 
-        Ok(results)
+/// Picks the precision of the decoder's encoder hidden states.
+///
+/// The type the ONNX graph declares wins, since the session rejects any
+/// other; a disagreeing `declared` precision from config.json is reported
+/// in the returned warning. Without either, assumes the fp16 weights the
+/// daemon downloads.
+fn resolve_precision(
+    declared: Option<Precision>,
+    graph: Option<Precision>,
+) -> (Precision, Option<String>) {
+    match (declared, graph) {
+        (Some(declared), Some(graph)) if declared != graph => (
+            graph,
+            Some(format!(
+                "config.json declares {} weights but decoder_model.onnx takes {} inputs; using {}",
+                declared, graph, graph
+            )),
+        ),
+        (_, Some(graph)) => (graph, None),
+        (Some(declared), None) => (declared, None),
+        (None, None) => (Precision::Fp16, None),
     }
 }
 
+/// Describes a mismatch between the hidden states the text encoder produces
+/// and the ones the decoder takes, which are converted on every generation.
+pub(super) fn encoder_precision_warning(
+    encoder: Option<Precision>,
+    decoder: Precision,
+) -> Option<String> {
+    encoder.filter(|&encoder| encoder != decoder).map(|encoder| {
+        format!(
+            "text encoder produces {} hidden states but the decoder takes {}; converting",
+            encoder, decoder
+        )
+    })
+}
+
 /// Duplicates a tensor along the first dimension, filling new entries with zeros.
 /// Used for classifier-free guidance where we need both conditional and unconditional embeddings.
-/// Automatically detects f16 vs f32 tensor type.
-fn duplicate_with_zeros(tensor: &DynValue, _use_fp16: bool) -> Result<DynValue> {
-    // Try f16 first (common for fp16 models), then f32
-    if let Ok(result) = duplicate_with_zeros_typed::<f16>(tensor) {
-        return Ok(result);
+/// The result has the element type of `precision`, converting f16 or f32 input if needed.
+fn duplicate_with_zeros(tensor: &DynValue, precision: Precision) -> Result<DynValue> {
+    match precision {
+        Precision::Fp16 => duplicate_with_zeros_typed(tensor, f16::from_f32),
+        Precision::Fp32 => duplicate_with_zeros_typed(tensor, |x| x),
     }
-    duplicate_with_zeros_typed::<f32>(tensor)
 }
 
-fn duplicate_with_zeros_typed<T>(tensor: &DynValue) -> Result<DynValue>
+fn duplicate_with_zeros_typed<T>(tensor: &DynValue, from_f32: fn(f32) -> T) -> Result<DynValue>
 where
     T: ort::tensor::PrimitiveTensorElementType + Clone + Default + std::fmt::Debug + 'static,
 {
-    let (shape, data_slice) = tensor.try_extract_tensor::<T>().map_err(|e| {
-        DaemonError::model_inference_failed(format!("Failed to extract tensor: {}", e))
-    })?;
-
+    let (shape, data) = extract_float_tensor(tensor)?;
     let shape_vec: Vec<usize> = shape.iter().map(|&x| x as usize).collect();
-    let data: Vec<T> = data_slice.to_vec();
+    let data: Vec<T> = match data {
+        FloatData::F16(values) => values.iter().map(|v| from_f32(v.to_f32())).collect(),
+        FloatData::F32(values) => values.iter().copied().map(from_f32).collect(),
+    };
 
     let mut new_shape = shape_vec;
     new_shape[0] *= 2;
@@ -310,6 +746,22 @@ where
     Ok(result.into_dyn())
 }
 
+/// Elements of an f16 or f32 tensor.
+enum FloatData<'a> {
+    F16(&'a [f16]),
+    F32(&'a [f32]),
+}
+
+fn extract_float_tensor(tensor: &DynValue) -> Result<(&ort::tensor::Shape, FloatData<'_>)> {
+    if let Ok((shape, data)) = tensor.try_extract_tensor::<f16>() {
+        return Ok((shape, FloatData::F16(data)));
+    }
+    let (shape, data) = tensor.try_extract_tensor::<f32>().map_err(|e| {
+        DaemonError::model_inference_failed(format!("Failed to extract tensor: {}", e))
+    })?;
+    Ok((shape, FloatData::F32(data)))
+}
+
 fn duplicate_with_zeros_i64(tensor: &DynValue) -> Result<DynValue> {
     let (shape, data_slice) = tensor.try_extract_tensor::<i64>().map_err(|e| {
         DaemonError::model_inference_failed(format!("Failed to extract i64 tensor: {}", e))
@@ -346,6 +798,220 @@ mod tests {
         }
     }
 
+    #[test]
+    fn graph_precision_wins_over_config() {
+        use Precision::{Fp16, Fp32};
+
+        assert_eq!(resolve_precision(None, None), (Fp16, None));
+        assert_eq!(resolve_precision(Some(Fp32), None), (Fp32, None));
+        assert_eq!(resolve_precision(None, Some(Fp32)), (Fp32, None));
+        assert_eq!(resolve_precision(Some(Fp16), Some(Fp16)), (Fp16, None));
+
+        let (precision, warning) = resolve_precision(Some(Fp16), Some(Fp32));
+        assert_eq!(precision, Fp32);
+        assert!(warning.unwrap().contains("declares fp16"));
+
+        assert_eq!(precision_of(TensorElementType::Float16), Some(Fp16));
+        assert_eq!(precision_of(TensorElementType::Int64), None);
+
+        assert_eq!(encoder_precision_warning(None, Fp16), None);
+        assert_eq!(encoder_precision_warning(Some(Fp16), Fp16), None);
+        let warning = encoder_precision_warning(Some(Fp32), Fp16).unwrap();
+        assert!(warning.contains("fp32 hidden states"), "{}", warning);
+    }
+
+    #[test]
+    fn emit_frames_returns_exact_frame_count() {
+        for target in [0, 1, 2, 3, 4, 17, 500] {
+            let mut pattern = DelayPatternMaskIds::<4>::new();
+            pattern.push([0, 0, 0, 0]);
+
+            let mut steps = 0;
+            let frames = emit_frames(
+                &mut pattern,
+                2048,
+                target,
+                |_| {
+                    steps += 1;
+                    Ok(vec![1, 2, 3, 4])
+                },
+                None,
+                |current, total| assert!(current <= total),
+            )
+            .unwrap();
+
+            assert_eq!(frames.len(), target);
+            assert_eq!(pattern.len(), steps + 1);
+            if target > 0 {
+                assert_eq!(pattern.len(), DelayPatternMaskIds::<4>::pushes_for_frames(target));
+            } else {
+                assert_eq!(steps, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn emit_frames_propagates_step_errors() {
+        let mut pattern = DelayPatternMaskIds::<4>::new();
+        let result = emit_frames(
+            &mut pattern,
+            2048,
+            10,
+            |_| Err(DaemonError::model_inference_failed("boom")),
+            None,
+            |_, _| {},
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn emit_frames_stops_when_cancelled() {
+        let run = |cancel: &CancelToken| {
+            let mut pattern = DelayPatternMaskIds::<4>::new();
+            pattern.push([0, 0, 0, 0]);
+            let mut steps = 0;
+            emit_frames(
+                &mut pattern,
+                2048,
+                100,
+                |_| {
+                    steps += 1;
+                    if steps == 10 {
+                        cancel.cancel();
+                    }
+                    Ok(vec![1, 2, 3, 4])
+                },
+                Some(cancel),
+                |_, _| {},
+            )
+        };
+
+        // 11 pushed frames de-delay into 8 once the 3-step delay pattern has filled
+        let frames = run(&CancelToken::new().with_keep_partial(true)).unwrap();
+        assert_eq!(frames.len(), 8);
+
+        let error = run(&CancelToken::new()).unwrap_err();
+        assert_eq!(error.code, crate::error::ErrorCode::GenerationCancelled);
+    }
+
+    /// Loads the decoder of the tiny fixture model.
+    fn tiny_decoder() -> MusicGenDecoder {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/musicgen-tiny");
+        let mut config = ModelConfig::musicgen_small();
+        config.vocab_size = 16;
+        config.num_hidden_layers = 2;
+        config.pad_token_id = 16;
+        MusicGenDecoder::load(&dir, config).unwrap()
+    }
+
+    /// Returns the input of every position in the first layer's decoder keys.
+    ///
+    /// The fixture decoder caches each step's input ids as its keys.
+    fn cached_inputs(state: &DecoderState) -> Vec<[i64; 4]> {
+        let (shape, data) = state.kv_cache[0].1.try_extract_tensor::<f32>().unwrap();
+        let positions = shape[KV_SEQ_AXIS] as usize;
+        (0..positions)
+            .map(|p| std::array::from_fn(|codebook| data[codebook * positions + p] as i64))
+            .collect()
+    }
+
+    #[test]
+    fn truncation_rebuilds_cache_from_position_zero() {
+        let mut decoder = tiny_decoder();
+        let hidden = Tensor::from_array(([1usize, 3, 1], vec![1.0f32; 3])).unwrap().into_dyn();
+        let mask = Tensor::from_array(([1usize, 3], vec![1i64; 3])).unwrap().into_dyn();
+        let (_, mut state) =
+            decoder.generate_tokens_resumable(hidden, mask, 8, 3, None, |_, _| {}).unwrap();
+
+        let steps = state.steps();
+        let inputs: Vec<[i64; 4]> = (0..steps)
+            .map(|p| state.delay_pattern_mask_ids.delayed_masked(p, 16))
+            .collect();
+        assert_eq!(cached_inputs(&state), inputs);
+
+        // The pad token starts the window again, followed by the latest inputs
+        decoder.truncate_kv_cache(&mut state, 4).unwrap();
+        let mut expected = vec![[16; 4]];
+        expected.extend_from_slice(&inputs[steps - 3..]);
+        assert_eq!(cached_inputs(&state), expected);
+        assert_eq!(state.steps(), steps);
+        assert_eq!(state.frames_emitted(), 8);
+
+        decoder.continue_tokens(&mut state, 2, None, |_, _| {}).unwrap();
+        assert_eq!(state.context_len(), 6);
+        assert_eq!(state.steps(), steps + 2);
+        let next = state.delay_pattern_mask_ids.delayed_masked(steps + 1, 16);
+        assert_eq!(cached_inputs(&state).last(), Some(&next));
+    }
+
+    /// Returns the mean time of `runs` calls to `run` in milliseconds.
+    fn mean_ms(runs: u32, mut run: impl FnMut()) -> f64 {
+        let start = std::time::Instant::now();
+        for _ in 0..runs {
+            run();
+        }
+        start.elapsed().as_secs_f64() * 1000.0 / f64::from(runs)
+    }
+
+    /// Compares per-chunk startup cost with and without a primed KV cache,
+    /// and measures truncating a full context window.
+    ///
+    /// Run with `cargo test --release -- --ignored resume_benchmark --nocapture`.
+    #[test]
+    #[ignore]
+    fn resume_benchmark() {
+        const RUNS: u32 = 5;
+
+        let Some(model_dir) = get_model_dir() else {
+            eprintln!("Skipping test: models not found");
+            return;
+        };
+        let config = ModelConfig::musicgen_small();
+        let hidden_size = config.d_model as usize;
+        let mut decoder = MusicGenDecoder::load(&model_dir, config).unwrap();
+        let inputs = || {
+            let hidden = Tensor::from_array(([1, 8, hidden_size], vec![f16::ZERO; 8 * hidden_size]))
+                .unwrap()
+                .into_dyn();
+            let mask = Tensor::from_array(([1, 8], vec![1i64; 8])).unwrap().into_dyn();
+            (hidden, mask)
+        };
+
+        let (hidden, mask) = inputs();
+        let (_, mut state) =
+            decoder.generate_tokens_resumable(hidden, mask, 250, 0, None, |_, _| {}).unwrap();
+
+        let fresh = mean_ms(RUNS, || {
+            let (hidden, mask) = inputs();
+            decoder.generate_tokens_seeded(hidden, mask, 1, 0, None, |_, _| {}).unwrap();
+        });
+        let primed = mean_ms(RUNS, || {
+            decoder.continue_tokens(&mut state, 1, None, |_, _| {}).unwrap();
+        });
+        println!("fresh chunk, first frame:  {:.1} ms (mean of {})", fresh, RUNS);
+        println!(
+            "primed chunk, first frame: {:.1} ms (mean of {}, {:.1}x faster)",
+            primed,
+            RUNS,
+            fresh / primed
+        );
+
+        let window = ContextWindow::default();
+        let (hidden, mask) = inputs();
+        let (_, mut state) = decoder
+            .generate_tokens_resumable(hidden, mask, window.max_len - 4, 0, None, |_, _| {})
+            .unwrap();
+        let context_len = state.context_len();
+        let truncate = mean_ms(1, || decoder.truncate_kv_cache(&mut state, window.keep_len).unwrap());
+        println!(
+            "truncating {} positions to {}: {:.1} ms ({:.1} ms per replayed step)",
+            context_len,
+            window.keep_len,
+            truncate,
+            truncate / window.keep_len as f64
+        );
+    }
+
     #[test]
     fn decoder_loads_successfully() {
         let Some(model_dir) = get_model_dir() else {
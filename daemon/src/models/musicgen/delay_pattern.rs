@@ -6,6 +6,23 @@
 //! - Codebook 1: 1 token delay
 //! - Codebook 2: 2 token delay
 //! - Codebook 3: 3 token delay
+//!
+//! ## Frame accounting
+//!
+//! Each decoder step pushes one token per codebook. A complete audio frame
+//! (one token from every codebook for the same timestep) only becomes
+//! available once the most-delayed codebook has caught up, so with N codebooks
+//! the first N - 1 pushes yield no frames and every push after that yields
+//! exactly one:
+//!
+//! ```text
+//! frames(pushes)  = pushes.saturating_sub(N - 1)
+//! pushes(frames)  = frames + (N - 1)
+//! ```
+//!
+//! For MusicGen (N = 4, 50 frames/sec) a 10 second clip needs 500 frames and
+//! therefore 503 decoder steps. Callers should count emitted frames via
+//! [`DelayPatternMaskIds::de_delayed_len`] rather than decoder steps.
 
 /// Delay pattern mask for N codebooks.
 ///
@@ -23,9 +40,22 @@ impl<const N: usize> Default for DelayPatternMaskIds<N> {
 }
 
 impl<const N: usize> DelayPatternMaskIds<N> {
+    /// Compile-time check that at least one codebook is configured.
+    ///
+    /// Referenced from [`Self::new`] so that `DelayPatternMaskIds::<0>` fails to build.
+    const ASSERT_NON_ZERO: () = assert!(N > 0, "N needs to be greater than 0");
+
+    /// Number of pushes that produce no de-delayed frame (the largest codebook delay).
+    pub const DELAY: usize = N - 1;
+
+    /// Returns the number of pushes required to emit `target_frames` de-delayed frames.
+    pub fn pushes_for_frames(target_frames: usize) -> usize {
+        target_frames + Self::DELAY
+    }
+
     /// Creates a new empty delay pattern mask.
     pub fn new() -> Self {
-        assert!(N > 0, "N needs to be greater than 0");
+        let () = Self::ASSERT_NON_ZERO;
         Self {
             batches: [(); N].map(|()| vec![]),
         }
@@ -58,13 +88,24 @@ impl<const N: usize> DelayPatternMaskIds<N> {
     /// 3 P P P x x x x x x x ...
     /// ```
     pub fn last_delayed_masked(&self, pad_token_id: i64) -> [i64; N] {
-        let seq_len = self.batches[0].len();
+        self.delayed_masked(self.len(), pad_token_id)
+    }
+
+    /// Returns the tokens [`Self::last_delayed_masked`] returned when only the
+    /// first `seq_len` pushes had been made, i.e. the decoder input at
+    /// position `seq_len`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `seq_len` is greater than [`Self::len`].
+    pub fn delayed_masked(&self, seq_len: usize, pad_token_id: i64) -> [i64; N] {
+        assert!(seq_len <= self.len(), "Only {} tokens were pushed", self.len());
         let mut result = [0; N];
         for (i, item) in result.iter_mut().enumerate() {
-            if (seq_len as i64 - i as i64) <= 0 {
+            if seq_len <= i {
                 *item = pad_token_id
             } else {
-                *item = *self.batches[i].last().expect("There are no input_ids");
+                *item = self.batches[i][seq_len - 1];
             }
         }
         result
@@ -92,17 +133,49 @@ impl<const N: usize> DelayPatternMaskIds<N> {
         Some(result)
     }
 
+    /// Returns the pushed tokens as a row-major matrix.
+    ///
+    /// Each row holds the tokens pushed in one step, one column per codebook,
+    /// without the delay pattern applied.
+    pub fn to_token_matrix(&self) -> Vec<[i64; N]> {
+        (0..self.len())
+            .map(|row| {
+                let mut tokens = [0; N];
+                for (codebook, token) in tokens.iter_mut().enumerate() {
+                    *token = self.batches[codebook][row];
+                }
+                tokens
+            })
+            .collect()
+    }
+
     /// Returns the number of tokens in the first codebook.
     pub fn len(&self) -> usize {
         self.batches[0].len()
     }
 
+    /// Returns the number of complete de-delayed frames available so far.
+    pub fn de_delayed_len(&self) -> usize {
+        self.len().saturating_sub(Self::DELAY)
+    }
+
     /// Returns true if no tokens have been added yet.
     pub fn is_empty(&self) -> bool {
         self.batches[0].is_empty()
     }
 }
 
+impl<const N: usize, const M: usize> From<[[i64; N]; M]> for DelayPatternMaskIds<N> {
+    /// Builds a pattern by pushing each row of `rows` in order.
+    fn from(rows: [[i64; N]; M]) -> Self {
+        let mut pattern = Self::new();
+        for row in rows {
+            pattern.push(row);
+        }
+        pattern
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,6 +203,19 @@ mod tests {
         assert_eq!(input_ids.last_delayed_masked(0), [17, 18, 19, 20]);
     }
 
+    #[test]
+    fn delayed_masked_replays_earlier_inputs() {
+        let mut input_ids = DelayPatternMaskIds::<4>::new();
+        let mut inputs = vec![input_ids.last_delayed_masked(0)];
+        for step in 0..6 {
+            input_ids.push([step * 4 + 1, step * 4 + 2, step * 4 + 3, step * 4 + 4]);
+            inputs.push(input_ids.last_delayed_masked(0));
+        }
+        for (seq_len, expected) in inputs.iter().enumerate() {
+            assert_eq!(&input_ids.delayed_masked(seq_len, 0), expected);
+        }
+    }
+
     #[test]
     fn last_de_delayed() {
         let mut input_ids = DelayPatternMaskIds::<4>::new();
@@ -155,4 +241,83 @@ mod tests {
         pattern.push([5, 6, 7, 8]);
         assert_eq!(pattern.len(), 2);
     }
+
+    #[test]
+    fn pushes_yield_pushes_minus_delay_frames() {
+        let mut pattern = DelayPatternMaskIds::<4>::new();
+        let mut emitted = 0;
+        for n in 1..=20i64 {
+            pattern.push([n, n, n, n]);
+            if pattern.last_de_delayed().is_some() {
+                emitted += 1;
+            }
+            let expected = (n as usize).saturating_sub(3);
+            assert_eq!(emitted, expected, "after {} pushes", n);
+            assert_eq!(pattern.de_delayed_len(), expected);
+        }
+    }
+
+    #[test]
+    fn pushes_for_frames_round_trips() {
+        assert_eq!(DelayPatternMaskIds::<4>::DELAY, 3);
+        for frames in [0, 1, 50, 500] {
+            let mut pattern = DelayPatternMaskIds::<4>::new();
+            for _ in 0..DelayPatternMaskIds::<4>::pushes_for_frames(frames) {
+                pattern.push([0, 0, 0, 0]);
+            }
+            assert_eq!(pattern.de_delayed_len(), frames);
+        }
+    }
+
+    #[test]
+    fn from_rows_round_trips_token_matrix() {
+        let rows = [[1, 2, 3, 4], [5, 6, 7, 8], [9, 10, 11, 12]];
+        let pattern = DelayPatternMaskIds::from(rows);
+        assert_eq!(pattern.len(), 3);
+        assert_eq!(pattern.to_token_matrix(), rows.to_vec());
+    }
+
+    #[test]
+    fn single_codebook_has_no_delay() {
+        assert_eq!(DelayPatternMaskIds::<1>::DELAY, 0);
+
+        let mut pattern = DelayPatternMaskIds::<1>::new();
+        assert_eq!(pattern.last_delayed_masked(-1), [-1]);
+        pattern.push([7]);
+        assert_eq!(pattern.last_delayed_masked(-1), [7]);
+        assert_eq!(pattern.last_de_delayed(), Some([7]));
+        pattern.push([8]);
+        assert_eq!(pattern.last_de_delayed(), Some([8]));
+        assert_eq!(pattern.de_delayed_len(), 2);
+        assert_eq!(pattern.to_token_matrix(), vec![[7], [8]]);
+    }
+
+    #[test]
+    fn eight_codebooks_delay_by_seven() {
+        assert_eq!(DelayPatternMaskIds::<8>::DELAY, 7);
+
+        let rows: [[i64; 8]; 9] = std::array::from_fn(|step| {
+            std::array::from_fn(|codebook| (step * 10 + codebook) as i64)
+        });
+        let mut pattern = DelayPatternMaskIds::<8>::new();
+        for (step, row) in rows.iter().enumerate() {
+            pattern.push(*row);
+            let masked = pattern.last_delayed_masked(-1);
+            // Codebooks beyond the current step are still padded
+            for (codebook, token) in masked.iter().enumerate() {
+                if codebook > step {
+                    assert_eq!(*token, -1);
+                } else {
+                    assert_eq!(*token, row[codebook]);
+                }
+            }
+        }
+
+        // 9 pushes with a delay of 7 yield 2 frames; the last one is the diagonal
+        // starting at step 1
+        assert_eq!(pattern.de_delayed_len(), 2);
+        let expected: [i64; 8] = std::array::from_fn(|codebook| ((1 + codebook) * 10 + codebook) as i64);
+        assert_eq!(pattern.last_de_delayed(), Some(expected));
+        assert_eq!(pattern.to_token_matrix(), rows.to_vec());
+    }
 }
@@ -11,7 +11,7 @@ use ort::tensor::ArrayExtensions;
 use ort::value::DynValue;
 use rand::distributions::WeightedIndex;
 use rand::prelude::Distribution;
-use rand::thread_rng;
+use rand::{thread_rng, Rng};
 
 use crate::error::{DaemonError, Result};
 
@@ -66,13 +66,18 @@ impl Logits {
             .into_dimensionality::<Ix3>()
             .map_err(|e| DaemonError::model_inference_failed(format!("Expected 3D logits: {}", e)))?;
 
-        // logits come in the following shape float32[batch_size,decoder_sequence_length,2048]
+        // logits come in the following shape float32[batch_size,decoder_sequence_length,vocab_size]
         // based on transformers.js we can assume that decoder_sequence_length is going
         // to be 1, so we can just remove it.
         let arr = arr.remove_axis(Axis(1));
         Ok(Self(arr))
     }
 
+    /// Returns the number of token logits per batch entry.
+    pub fn vocab_size(&self) -> usize {
+        self.0.dim().1
+    }
+
     /// Applies classifier-free guidance to the logits.
     ///
     /// The batch is expected to have conditional logits in the first half
@@ -104,6 +109,13 @@ impl Logits {
     ///
     /// * `k` - Take into account only top k logits in each batch
     pub fn sample_top_k(&self, k: usize) -> Vec<(i64, f32)> {
+        self.sample_top_k_with_rng(k, &mut thread_rng())
+    }
+
+    /// Samples from the logits using top-k sampling with the given random source.
+    ///
+    /// Produces the same tokens for the same logits and RNG state.
+    pub fn sample_top_k_with_rng<R: Rng>(&self, k: usize, rng: &mut R) -> Vec<(i64, f32)> {
         let mut result = vec![];
         let softmax_logits = self.0.softmax(Axis(1));
 
@@ -131,7 +143,7 @@ impl Logits {
                 .expect("Could not create WeightedIndex distribution");
 
             // Sample a random index based on the softmax probabilities.
-            let (idx, softmax_prob) = softmax_logits_batch[distribution.sample(&mut thread_rng())];
+            let (idx, softmax_prob) = softmax_logits_batch[distribution.sample(rng)];
 
             // Use natural log for log probability
             result.push((idx, softmax_prob.ln()));
@@ -169,4 +181,24 @@ mod tests {
             assert!(*idx >= 0 && *idx < 3);
         }
     }
+
+    #[test]
+    fn seeded_sampling_is_reproducible() {
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha8Rng;
+
+        let arr = Array::from_shape_vec((4, 16), vec![0.5; 64]).unwrap();
+        let logits = Logits(arr);
+        assert_eq!(logits.vocab_size(), 16);
+
+        let sample = |seed| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            (0..8)
+                .flat_map(|_| logits.sample_top_k_with_rng(DEFAULT_TOP_K, &mut rng))
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sample(42), sample(42));
+        assert_ne!(sample(42), sample(43));
+    }
 }
@@ -15,12 +15,13 @@ pub mod models;
 pub mod text_encoder;
 
 // Re-export commonly used types
-pub use audio_codec::MusicGenAudioCodec;
-pub use decoder::MusicGenDecoder;
+pub use audio_codec::{DecodeStats, MusicGenAudioCodec, DECODE_WINDOW_CONTEXT};
+pub use decoder::{ContextWindow, DecoderState, MusicGenDecoder};
 pub use delay_pattern::DelayPatternMaskIds;
 pub use logits::{Logits, DEFAULT_GUIDANCE_SCALE, DEFAULT_TOP_K};
 pub use models::{
-    check_models, detect_model_version, generate_model_version, load_sessions,
-    load_sessions_with_device, MusicGenModels, MODEL_URLS, REQUIRED_MODEL_FILES,
+    check_models, check_models_with_tokenizer, detect_model_version, generate_model_version,
+    load_sessions, load_sessions_with_device, load_sessions_with_tuning, MusicGenModels,
+    MODEL_URLS, REQUIRED_MODEL_FILES, TOKENIZER_URL,
 };
-pub use text_encoder::MusicGenTextEncoder;
+pub use text_encoder::{tokenize_prompts, MusicGenTextEncoder, TokenizedPrompts};
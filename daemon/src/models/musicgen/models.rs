@@ -3,15 +3,20 @@
 //! Handles loading all required model components and configuration.
 
 use std::path::Path;
+use std::time::Instant;
+
+use half::f16;
+use ort::value::Tensor;
 
 use crate::config::Device;
 use crate::error::{DaemonError, Result};
-use crate::types::ModelConfig;
+use crate::types::{ModelConfig, Precision};
 
 use super::audio_codec::MusicGenAudioCodec;
-use super::decoder::MusicGenDecoder;
+use super::decoder::{encoder_precision_warning, MusicGenDecoder};
 use super::text_encoder::MusicGenTextEncoder;
-use crate::models::device::{get_device_name, get_providers};
+use crate::models::device::{get_device_name, get_providers, SessionTuning};
+use crate::models::tokenizer::{check_tokenizer, tokenizer_path, TOKENIZER_FILE};
 
 /// Complete set of loaded MusicGen models.
 pub struct MusicGenModels {
@@ -49,6 +54,39 @@ impl MusicGenModels {
     pub fn device_name(&self) -> &str {
         &self.device_name
     }
+
+    /// Primes the decoder sessions so the first request does not pay for
+    /// graph compilation.
+    ///
+    /// Generates a single frame from a zero encoder output of shape
+    /// `[1, 1, d_model]`, which runs both `decoder_model` and
+    /// `decoder_with_past`. The generated tokens are discarded.
+    pub fn warmup(&mut self) -> Result<()> {
+        let start = Instant::now();
+        let hidden_size = self.config.d_model as usize;
+
+        let hidden_states = if self.decoder.precision() == Precision::Fp16 {
+            Tensor::from_array(([1, 1, hidden_size], vec![f16::ZERO; hidden_size]))
+                .map(|t| t.into_dyn())
+        } else {
+            Tensor::from_array(([1, 1, hidden_size], vec![0.0f32; hidden_size]))
+                .map(|t| t.into_dyn())
+        }
+        .map_err(|e| {
+            DaemonError::model_inference_failed(format!("Failed to create warmup input: {}", e))
+        })?;
+        let attention_mask = Tensor::from_array(([1, 1], vec![1i64]))
+            .map_err(|e| {
+                DaemonError::model_inference_failed(format!("Failed to create warmup mask: {}", e))
+            })?
+            .into_dyn();
+
+        self.decoder
+            .generate_tokens_seeded(hidden_states, attention_mask, 1, 0, None, |_, _| {})?;
+
+        eprintln!("MusicGen warmup took {:.2}s", start.elapsed().as_secs_f32());
+        Ok(())
+    }
 }
 
 /// Required model files for MusicGen.
@@ -64,16 +102,32 @@ pub const REQUIRED_MODEL_FILES: &[&str] = &[
 ///
 /// Returns Ok(()) if all files exist, or an error listing missing files.
 pub fn check_models(model_dir: &Path) -> Result<()> {
+    check_models_with_tokenizer(model_dir, None)
+}
+
+/// Checks the required model files, reading the tokenizer from
+/// `tokenizer_override` instead of the model directory if set.
+///
+/// If only the tokenizer is missing, returns TOKENIZER_MISSING with its
+/// download URL; otherwise missing files are reported as MODEL_NOT_FOUND.
+pub fn check_models_with_tokenizer(model_dir: &Path, tokenizer_override: Option<&Path>) -> Result<()> {
+    let tokenizer = tokenizer_path(model_dir, tokenizer_override);
     let mut missing = Vec::new();
 
     for file in REQUIRED_MODEL_FILES {
-        let path = model_dir.join(file);
+        let path = if *file == TOKENIZER_FILE {
+            tokenizer.clone()
+        } else {
+            model_dir.join(file)
+        };
         if !path.exists() {
             missing.push(*file);
         }
     }
 
-    if missing.is_empty() {
+    if missing == [TOKENIZER_FILE] {
+        check_tokenizer(&tokenizer, TOKENIZER_URL)
+    } else if missing.is_empty() {
         Ok(())
     } else {
         Err(DaemonError::model_not_found(format!(
@@ -120,27 +174,48 @@ pub fn load_sessions_with_device(
     model_dir: &Path,
     device: Device,
     threads: Option<u32>,
+) -> Result<MusicGenModels> {
+    load_sessions_with_tuning(model_dir, None, device, threads, &SessionTuning::default())
+}
+
+/// Loads all MusicGen model sessions with the given device and ONNX Runtime
+/// tuning.
+///
+/// Same as [`load_sessions_with_device`], with `tuning` applied to every
+/// session and the tokenizer read from `tokenizer_override` if set.
+pub fn load_sessions_with_tuning(
+    model_dir: &Path,
+    tokenizer_override: Option<&Path>,
+    device: Device,
+    threads: Option<u32>,
+    tuning: &SessionTuning,
 ) -> Result<MusicGenModels> {
     // Check all required files exist first
-    check_models(model_dir)?;
+    check_models_with_tokenizer(model_dir, tokenizer_override)?;
 
     // Get execution providers for the device
-    let providers = get_providers(device, threads);
+    let providers = get_providers(device, threads, tuning);
     let device_name = get_device_name(device).to_string();
 
     eprintln!("Using device: {}", device_name);
 
     eprintln!("Loading text encoder...");
-    let text_encoder = MusicGenTextEncoder::load_with_providers(model_dir, &providers)?;
+    let text_encoder =
+        MusicGenTextEncoder::load_with_providers(model_dir, tokenizer_override, &providers, tuning)?;
 
     // Load or create config
     let config = load_or_default_config(model_dir)?;
 
     eprintln!("Loading decoder models...");
-    let decoder = MusicGenDecoder::load_with_providers(model_dir, config.clone(), &providers)?;
+    let decoder = MusicGenDecoder::load_with_providers(model_dir, config.clone(), &providers, tuning)?;
+    if let Some(warning) =
+        encoder_precision_warning(text_encoder.output_precision(), decoder.precision())
+    {
+        eprintln!("Warning: {}", warning);
+    }
 
     eprintln!("Loading audio codec...");
-    let audio_codec = MusicGenAudioCodec::load_with_providers(model_dir, &providers)?;
+    let audio_codec = MusicGenAudioCodec::load_with_providers(model_dir, &config, &providers, tuning)?;
 
     // Determine version from directory name or default
     let version = detect_model_version(model_dir);
@@ -191,10 +266,11 @@ fn load_or_default_config(model_dir: &Path) -> Result<ModelConfig> {
             .and_then(|v| v.as_u64())
             .unwrap_or(2048) as u32;
 
+        // MusicGen pads with the first id past the vocabulary
         let pad_token_id = decoder
             .get("pad_token_id")
             .and_then(|v| v.as_i64())
-            .unwrap_or(2048);
+            .unwrap_or(vocab_size as i64);
 
         let text_encoder = json.get("text_encoder");
         let d_kv = text_encoder
@@ -207,6 +283,18 @@ fn load_or_default_config(model_dir: &Path) -> Result<ModelConfig> {
             .and_then(|v| v.as_u64())
             .unwrap_or(1024) as u32;
 
+        // Not `torch_dtype`: that describes the original PyTorch weights,
+        // which can differ from the ONNX export next to it
+        let precision = match json.get("precision").and_then(|v| v.as_str()) {
+            Some(name) => Some(Precision::parse(name).ok_or_else(|| {
+                DaemonError::model_load_failed(format!(
+                    "config.json has unknown precision '{}'; expected fp16 or fp32",
+                    name
+                ))
+            })?),
+            None => None,
+        };
+
         Ok(ModelConfig {
             vocab_size,
             num_hidden_layers,
@@ -217,6 +305,7 @@ fn load_or_default_config(model_dir: &Path) -> Result<ModelConfig> {
             sample_rate: 32000,
             codebooks: 4,
             pad_token_id,
+            precision,
         })
     } else {
         // Use default musicgen-small config
@@ -272,16 +361,17 @@ pub fn detect_model_version(model_dir: &Path) -> String {
     generate_model_version("small", "fp16", 1)
 }
 
+/// Download URL of the MusicGen T5 tokenizer.
+pub const TOKENIZER_URL: &str =
+    "https://huggingface.co/gabotechs/music_gen/resolve/main/small/tokenizer.json";
+
 /// HuggingFace model URLs for musicgen-small-fp16.
 pub const MODEL_URLS: &[(&str, &str)] = &[
     (
         "config.json",
         "https://huggingface.co/gabotechs/music_gen/resolve/main/small/config.json",
     ),
-    (
-        "tokenizer.json",
-        "https://huggingface.co/gabotechs/music_gen/resolve/main/small/tokenizer.json",
-    ),
+    ("tokenizer.json", TOKENIZER_URL),
     (
         "text_encoder.onnx",
         "https://huggingface.co/gabotechs/music_gen/resolve/main/small_fp16/text_encoder.onnx",
@@ -329,10 +419,51 @@ mod tests {
         assert_eq!(detect_model_version(&path), "musicgen-medium-fp32-v1");
     }
 
+    #[test]
+    fn config_json_declares_precision() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        assert_eq!(load_or_default_config(dir.path()).unwrap().precision, None);
+
+        std::fs::write(&config_path, r#"{ "decoder": {}, "torch_dtype": "float32" }"#).unwrap();
+        assert_eq!(load_or_default_config(dir.path()).unwrap().precision, None);
+
+        std::fs::write(&config_path, r#"{ "decoder": {}, "precision": "fp16" }"#).unwrap();
+        assert_eq!(
+            load_or_default_config(dir.path()).unwrap().precision,
+            Some(Precision::Fp16)
+        );
+
+        std::fs::write(&config_path, r#"{ "decoder": {}, "precision": "int8" }"#).unwrap();
+        let err = load_or_default_config(dir.path()).unwrap_err();
+        assert!(err.message.contains("int8"), "{}", err.message);
+    }
+
     #[test]
     fn required_files_list() {
         assert_eq!(REQUIRED_MODEL_FILES.len(), 5);
         assert!(REQUIRED_MODEL_FILES.contains(&"tokenizer.json"));
         assert!(REQUIRED_MODEL_FILES.contains(&"encodec_decode.onnx"));
     }
+
+    #[test]
+    fn missing_tokenizer_is_reported_separately() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in REQUIRED_MODEL_FILES.iter().filter(|f| **f != TOKENIZER_FILE) {
+            std::fs::write(dir.path().join(file), b"").unwrap();
+        }
+
+        let err = check_models(dir.path()).unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::TokenizerMissing);
+        assert!(err.message.contains(TOKENIZER_URL));
+
+        let shared = dir.path().join("shared.json");
+        std::fs::write(&shared, b"{}").unwrap();
+        assert!(check_models_with_tokenizer(dir.path(), Some(&shared)).is_ok());
+
+        // An otherwise empty install is still just missing models
+        let empty = tempfile::tempdir().unwrap();
+        let err = check_models(empty.path()).unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::ModelNotFound);
+    }
 }
@@ -7,9 +7,15 @@ use std::path::Path;
 use ort::execution_providers::ExecutionProviderDispatch;
 use ort::session::Session;
 use ort::value::{DynValue, Tensor};
-use tokenizers::Tokenizer;
+use tokenizers::{PaddingDirection, Tokenizer};
 
 use crate::error::{DaemonError, Result};
+use crate::models::device::SessionTuning;
+use crate::models::tokenizer::{load_tokenizer, tokenizer_path};
+use crate::types::Precision;
+
+use super::decoder::precision_of;
+use super::models::TOKENIZER_URL;
 
 /// MusicGen text encoder combining tokenizer and T5 encoder.
 pub struct MusicGenTextEncoder {
@@ -22,23 +28,24 @@ impl MusicGenTextEncoder {
     ///
     /// Loads `tokenizer.json` and `text_encoder.onnx` from the given directory.
     pub fn load(model_dir: &Path) -> Result<Self> {
-        Self::load_with_providers(model_dir, &[])
+        Self::load_with_providers(model_dir, None, &[], &SessionTuning::default())
     }
 
     /// Creates a new text encoder from model directory with specific execution providers.
     ///
     /// Loads `tokenizer.json` and `text_encoder.onnx` from the given directory,
-    /// using the provided execution providers for the ONNX session.
+    /// using the provided execution providers and tuning for the ONNX session.
+    /// The tokenizer is read from `tokenizer_override` instead if set.
     pub fn load_with_providers(
         model_dir: &Path,
+        tokenizer_override: Option<&Path>,
         providers: &[ExecutionProviderDispatch],
+        tuning: &SessionTuning,
     ) -> Result<Self> {
-        let tokenizer_path = model_dir.join("tokenizer.json");
+        let tokenizer_path = tokenizer_path(model_dir, tokenizer_override);
         let encoder_path = model_dir.join("text_encoder.onnx");
 
-        let mut tokenizer = Tokenizer::from_file(&tokenizer_path).map_err(|e| {
-            DaemonError::model_load_failed(format!("Failed to load tokenizer: {}", e))
-        })?;
+        let mut tokenizer = load_tokenizer(&tokenizer_path, TOKENIZER_URL)?;
 
         tokenizer
             .with_padding(None)
@@ -47,7 +54,8 @@ impl MusicGenTextEncoder {
                 DaemonError::model_load_failed(format!("Failed to configure tokenizer: {}", e))
             })?;
 
-        let mut builder = Session::builder()
+        let mut builder = tuning
+            .session_builder()
             .map_err(|e| DaemonError::model_load_failed(format!("Failed to create session: {}", e)))?;
 
         if !providers.is_empty() {
@@ -69,36 +77,45 @@ impl MusicGenTextEncoder {
     /// Encodes text into embeddings and attention mask.
     ///
     /// Returns a tuple of (last_hidden_state, attention_mask) as DynValue tensors.
+    /// The attention mask is the tokenizer's, and is also what the decoder
+    /// uses for cross-attention over the hidden states.
     pub fn encode(&mut self, text: &str) -> Result<(DynValue, DynValue)> {
-        let tokens = self
-            .tokenizer
-            .encode(text, true)
-            .map_err(|e| {
-                DaemonError::model_inference_failed(format!("Tokenization failed: {}", e))
-            })?
-            .get_ids()
+        self.encode_batch(&[text])
+    }
+
+    /// Returns the precision of the hidden states the ONNX graph declares,
+    /// if it declares a floating-point `last_hidden_state` output.
+    pub fn output_precision(&self) -> Option<Precision> {
+        self.text_encoder
+            .outputs
             .iter()
-            .map(|e| *e as i64)
-            .collect::<Vec<_>>();
+            .find(|output| output.name == "last_hidden_state")
+            .and_then(|output| output.output_type.tensor_type())
+            .and_then(precision_of)
+    }
 
-        let tokens_len = tokens.len();
+    /// Encodes several prompts at once, padded to the longest one.
+    ///
+    /// Returns (last_hidden_state, attention_mask) with a leading batch
+    /// dimension of `prompts.len()`; padding positions have a mask of 0.
+    pub fn encode_batch(&mut self, prompts: &[&str]) -> Result<(DynValue, DynValue)> {
+        let tokenized = tokenize_prompts(&self.tokenizer, prompts)?;
+        let shape = [tokenized.batch_size, tokenized.seq_len];
 
         // Create input tensors
-        let input_ids = Tensor::from_array(([1, tokens_len], tokens)).map_err(|e| {
+        let input_ids = Tensor::from_array((shape, tokenized.input_ids)).map_err(|e| {
             DaemonError::model_inference_failed(format!("Failed to create input tensor: {}", e))
         })?;
-
-        let attention_mask_data: Vec<i64> = vec![1; tokens_len];
-        let attention_mask = Tensor::from_array(([1, tokens_len], attention_mask_data)).map_err(|e| {
+        let attention_mask = Tensor::from_array((shape, tokenized.attention_mask)).map_err(|e| {
             DaemonError::model_inference_failed(format!("Failed to create attention mask: {}", e))
         })?;
 
         // Run the text encoder
         let mut output = self
             .text_encoder
-            .run(ort::inputs![input_ids, attention_mask])
+            .run(ort::inputs![input_ids, &attention_mask])
             .map_err(|e| {
-                DaemonError::model_inference_failed(format!("Text encoder inference failed: {}", e))
+                DaemonError::model_inference_failed_with_source("Text encoder inference failed", e)
             })?;
 
         let last_hidden_state = output
@@ -109,15 +126,55 @@ impl MusicGenTextEncoder {
                 )
             })?;
 
-        // Create attention mask for decoder
-        let decoder_attention_mask_data: Vec<i64> = vec![1; tokens_len];
-        let decoder_attention_mask = Tensor::from_array(([1, tokens_len], decoder_attention_mask_data))
-            .map_err(|e| {
-                DaemonError::model_inference_failed(format!("Failed to create decoder attention mask: {}", e))
-            })?;
+        Ok((last_hidden_state, attention_mask.into_dyn()))
+    }
+}
+
+/// Token used to pad prompts in a batch to the same length.
+const PAD_TOKEN: &str = "<pad>";
+
+/// Token IDs and attention mask of a batch of prompts, in row-major
+/// `[batch_size, seq_len]` order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenizedPrompts {
+    /// Token IDs, padded with the `<pad>` token.
+    pub input_ids: Vec<i64>,
+    /// 1 for prompt tokens, 0 for padding.
+    pub attention_mask: Vec<i64>,
+    /// Number of prompts.
+    pub batch_size: usize,
+    /// Length of the longest prompt in tokens.
+    pub seq_len: usize,
+}
+
+/// Tokenizes `prompts`, padding each to the longest with `<pad>`.
+///
+/// The attention mask comes from the tokenizer, so it also covers special
+/// tokens the tokenizer adds and masks out the padding.
+pub fn tokenize_prompts(tokenizer: &Tokenizer, prompts: &[&str]) -> Result<TokenizedPrompts> {
+    if prompts.is_empty() {
+        return Err(DaemonError::model_inference_failed("No prompts to encode"));
+    }
+    let mut encodings = tokenizer
+        .encode_batch(prompts.to_vec(), true)
+        .map_err(|e| DaemonError::model_inference_failed(format!("Tokenization failed: {}", e)))?;
 
-        Ok((last_hidden_state, decoder_attention_mask.into_dyn()))
+    let seq_len = encodings.iter().map(|e| e.len()).max().unwrap_or(0);
+    let pad_id = tokenizer.token_to_id(PAD_TOKEN).unwrap_or(0);
+    let mut tokenized = TokenizedPrompts {
+        input_ids: Vec::with_capacity(prompts.len() * seq_len),
+        attention_mask: Vec::with_capacity(prompts.len() * seq_len),
+        batch_size: prompts.len(),
+        seq_len,
+    };
+    for encoding in &mut encodings {
+        encoding.pad(seq_len, pad_id, 0, PAD_TOKEN, PaddingDirection::Right);
+        tokenized.input_ids.extend(encoding.get_ids().iter().map(|&id| id as i64));
+        tokenized
+            .attention_mask
+            .extend(encoding.get_attention_mask().iter().map(|&m| m as i64));
     }
+    Ok(tokenized)
 }
 
 #[cfg(test)]
@@ -163,4 +220,62 @@ mod tests {
                 hidden_state.try_extract_tensor::<half::f16>().is_ok());
         assert!(attention_mask.try_extract_tensor::<i64>().is_ok());
     }
+
+    fn fixture_tokenizer() -> Tokenizer {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/musicgen-tiny/tokenizer.json");
+        load_tokenizer(&path, TOKENIZER_URL).unwrap()
+    }
+
+    #[test]
+    fn attention_mask_comes_from_tokenizer() {
+        let tokenizer = fixture_tokenizer();
+        // Punctuation splits words into separate ids
+        let tokenized = tokenize_prompts(&tokenizer, &["lofi-beats, rain"]).unwrap();
+        assert_eq!(tokenized.batch_size, 1);
+        assert_eq!(tokenized.seq_len, 5);
+        assert_eq!(tokenized.input_ids, vec![3, 2, 4, 2, 7]);
+        assert_eq!(tokenized.attention_mask, vec![1; 5]);
+    }
+
+    #[test]
+    fn batch_is_padded_to_longest_prompt() {
+        let tokenizer = fixture_tokenizer();
+        let tokenized = tokenize_prompts(&tokenizer, &["chill", "lofi piano, rain"]).unwrap();
+        assert_eq!(tokenized.batch_size, 2);
+        assert_eq!(tokenized.seq_len, 4);
+        assert_eq!(tokenized.input_ids, vec![5, 0, 0, 0, 3, 6, 2, 7]);
+        assert_eq!(tokenized.attention_mask, vec![1, 0, 0, 0, 1, 1, 1, 1]);
+
+        assert!(tokenize_prompts(&tokenizer, &[]).is_err());
+    }
+
+    #[test]
+    fn tokenizer_padding_is_masked() {
+        use tokenizers::{PaddingParams, PaddingStrategy};
+
+        // The fixture tokenizer set to pad every prompt to 8 tokens itself
+        let mut tokenizer = fixture_tokenizer();
+        tokenizer.with_padding(Some(PaddingParams {
+            strategy: PaddingStrategy::Fixed(8),
+            pad_id: 0,
+            pad_token: PAD_TOKEN.to_string(),
+            ..PaddingParams::default()
+        }));
+
+        let tokenized = tokenize_prompts(&tokenizer, &["lofi-beats, rain", "chill"]).unwrap();
+        assert_eq!(tokenized.seq_len, 8);
+        assert_eq!(
+            tokenized.input_ids,
+            vec![3, 2, 4, 2, 7, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            tokenized.attention_mask,
+            vec![1, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]
+        );
+        // Every padded position is masked out, and only those
+        for (id, mask) in tokenized.input_ids.iter().zip(&tokenized.attention_mask) {
+            assert_eq!(*mask == 0, *id == 0, "id {} has mask {}", id, mask);
+        }
+    }
 }
@@ -0,0 +1,181 @@
+//! Tokenizer loading with corruption checks.
+//!
+//! A partially downloaded `tokenizer.json`, or an HTML error page served with
+//! status 200, otherwise fails deep inside the tokenizers crate with a cryptic
+//! message. The file is checked first so users are told to re-download it.
+//!
+//! A model directory assembled by hand often lacks `tokenizer.json`
+//! altogether; that is reported as TOKENIZER_MISSING with the download URL.
+//! Each backend's tokenizer can also be read from a shared location set in
+//! the config instead of the model directory.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use tokenizers::Tokenizer;
+
+use crate::error::{DaemonError, Result};
+
+/// Name of the tokenizer file in a model directory.
+pub const TOKENIZER_FILE: &str = "tokenizer.json";
+
+/// Top-level keys every HuggingFace `tokenizer.json` contains.
+const REQUIRED_KEYS: &[&str] = &["model", "added_tokens"];
+
+/// Returns where a backend's tokenizer is read from: `override_path` if set,
+/// otherwise `tokenizer.json` in `model_dir`.
+pub fn tokenizer_path(model_dir: &Path, override_path: Option<&Path>) -> PathBuf {
+    override_path.map_or_else(|| model_dir.join(TOKENIZER_FILE), Path::to_path_buf)
+}
+
+/// Returns TOKENIZER_MISSING if there is no file at `path`.
+///
+/// `download_url` is where the user can fetch the tokenizer from.
+pub fn check_tokenizer(path: &Path, download_url: &str) -> Result<()> {
+    if path.is_file() {
+        Ok(())
+    } else {
+        Err(DaemonError::tokenizer_missing(path, download_url))
+    }
+}
+
+/// Loads a tokenizer after checking that the file exists and looks like a
+/// tokenizer.json.
+///
+/// A missing file is reported as TOKENIZER_MISSING with `download_url`.
+pub fn load_tokenizer(path: &Path, download_url: &str) -> Result<Tokenizer> {
+    check_tokenizer(path, download_url)?;
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        DaemonError::model_load_failed(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+
+    if let Some(problem) = validate_tokenizer_json(&contents) {
+        return Err(DaemonError::model_load_failed(format!(
+            "{} appears corrupt ({}), delete it and re-download the models",
+            path.display(),
+            problem
+        )));
+    }
+
+    Tokenizer::from_str(&contents)
+        .map_err(|e| DaemonError::model_load_failed(format!("Failed to load tokenizer: {}", e)))
+}
+
+/// Checks that `contents` is a JSON object with the expected top-level keys.
+///
+/// Returns a short description of the problem otherwise.
+pub fn validate_tokenizer_json(contents: &str) -> Option<String> {
+    let trimmed = contents.trim_start();
+    if trimmed.is_empty() {
+        return Some("file is empty".to_string());
+    }
+    if trimmed.starts_with('<') {
+        return Some("file is an HTML page, not JSON".to_string());
+    }
+
+    let value: serde_json::Value = match serde_json::from_str(contents) {
+        Ok(value) => value,
+        Err(e) if e.is_eof() => {
+            return Some(format!("file is truncated ({} bytes)", contents.len()))
+        }
+        Err(e) => return Some(format!("invalid JSON: {}", e)),
+    };
+
+    let Some(object) = value.as_object() else {
+        return Some("top-level value is not an object".to_string());
+    };
+    let missing: Vec<&str> = REQUIRED_KEYS
+        .iter()
+        .copied()
+        .filter(|key| !object.contains_key(*key))
+        .collect();
+    if missing.is_empty() {
+        None
+    } else {
+        Some(format!("missing keys: {}", missing.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+
+    const MINIMAL_TOKENIZER: &str = r#"{
+        "version": "1.0",
+        "truncation": null,
+        "padding": null,
+        "added_tokens": [],
+        "normalizer": null,
+        "pre_tokenizer": null,
+        "post_processor": null,
+        "decoder": null,
+        "model": { "type": "WordLevel", "vocab": { "lofi": 0, "[UNK]": 1 }, "unk_token": "[UNK]" }
+    }"#;
+
+    #[test]
+    fn validate_detects_common_corruption() {
+        assert!(validate_tokenizer_json(MINIMAL_TOKENIZER).is_none());
+
+        assert!(validate_tokenizer_json("").unwrap().contains("empty"));
+        assert!(
+            validate_tokenizer_json("<!DOCTYPE html><html>Bad Gateway</html>")
+                .unwrap()
+                .contains("HTML")
+        );
+        let truncated = &MINIMAL_TOKENIZER[..MINIMAL_TOKENIZER.len() / 2];
+        assert!(validate_tokenizer_json(truncated)
+            .unwrap()
+            .contains("truncated"));
+        assert!(validate_tokenizer_json(r#"{"error": "not found"}"#)
+            .unwrap()
+            .contains("model"));
+        assert!(validate_tokenizer_json("[1, 2]")
+            .unwrap()
+            .contains("object"));
+    }
+
+    const URL: &str = "https://example.com/tokenizer.json";
+
+    #[test]
+    fn load_tokenizer_reports_corrupt_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tokenizer.json");
+
+        std::fs::write(&path, MINIMAL_TOKENIZER).unwrap();
+        assert!(load_tokenizer(&path, URL).is_ok());
+
+        std::fs::write(&path, "<html>Service Unavailable</html>").unwrap();
+        let err = load_tokenizer(&path, URL).unwrap_err();
+        assert_eq!(err.code, ErrorCode::ModelLoadFailed);
+        assert!(err.message.contains("appears corrupt"));
+        assert!(err.message.contains("re-download"));
+    }
+
+    #[test]
+    fn missing_tokenizer_names_file_and_url() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = tokenizer_path(dir.path(), None);
+        assert_eq!(path, dir.path().join("tokenizer.json"));
+
+        let err = load_tokenizer(&path, URL).unwrap_err();
+        assert_eq!(err.code, ErrorCode::TokenizerMissing);
+        assert!(err.message.contains(&path.display().to_string()));
+        assert!(err.message.contains(URL));
+
+        // A directory is not a tokenizer either
+        let err = check_tokenizer(dir.path(), URL).unwrap_err();
+        assert_eq!(err.code, ErrorCode::TokenizerMissing);
+    }
+
+    #[test]
+    fn override_path_replaces_model_dir_tokenizer() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared-tokenizer.json");
+        std::fs::write(&shared, MINIMAL_TOKENIZER).unwrap();
+
+        let path = tokenizer_path(Path::new("/models/musicgen"), Some(&shared));
+        assert_eq!(path, shared);
+        assert!(load_tokenizer(&path, URL).is_ok());
+    }
+}
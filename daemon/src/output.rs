@@ -0,0 +1,548 @@
+//! Output path templating for `--output` and cached track filenames.
+//!
+//! Templates expand `{prompt}`, `{seed}`, `{duration}`, `{backend}` and
+//! `{track_id}` placeholders and strftime-style date specifiers (`%Y`, `%m`,
+//! `%d`, `%H`, `%M`, `%S`, `%F`, `%T`, `%%`), e.g.
+//! `~/music/lofi/%Y-%m-%d/{prompt}-{seed}.wav`. Dates are in UTC, and `%T`
+//! is `%H-%M-%S` because `:` is not allowed in Windows filenames.
+//!
+//! Used by the CLI, the daemon's cache and its config. Also resolves the
+//! `output_path` that `generate` requests may name instead of the cache
+//! directory.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{DaemonError, ErrorCode, GenerationPhase, Result};
+use crate::models::Backend;
+use crate::types::DisplayPrompt;
+
+/// Maximum length of the `{prompt}` slug in characters.
+pub const MAX_PROMPT_SLUG_LEN: usize = 40;
+
+/// Default template for cached track filenames.
+pub const DEFAULT_CACHE_FILENAME: &str = "{track_id}.wav";
+
+/// Slug used for prompts without any alphanumeric characters.
+const EMPTY_SLUG: &str = "untitled";
+
+/// Values substituted into an [`OutputTemplate`].
+#[derive(Debug, Clone)]
+pub struct TemplateValues<'a> {
+    /// Prompt, slugified by [`slugify`] for `{prompt}`.
+    pub prompt: &'a str,
+    /// Whether `{prompt}` shows only the redacted prompt of
+    /// [`DisplayPrompt`], so filenames do not reveal it.
+    pub redact_prompt: bool,
+    /// Seed for `{seed}`.
+    pub seed: u64,
+    /// Duration in seconds for `{duration}`.
+    pub duration_sec: u32,
+    /// Backend for `{backend}`.
+    pub backend: Backend,
+    /// Track ID for `{track_id}`.
+    pub track_id: &'a str,
+    /// Time the date specifiers are formatted from.
+    pub time: SystemTime,
+}
+
+/// A placeholder in an [`OutputTemplate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Prompt,
+    Seed,
+    Duration,
+    Backend,
+    TrackId,
+}
+
+impl Placeholder {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "prompt" => Some(Placeholder::Prompt),
+            "seed" => Some(Placeholder::Seed),
+            "duration" => Some(Placeholder::Duration),
+            "backend" => Some(Placeholder::Backend),
+            "track_id" => Some(Placeholder::TrackId),
+            _ => None,
+        }
+    }
+}
+
+/// A piece of a parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+    Date(char),
+}
+
+/// A parsed output path template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTemplate {
+    segments: Vec<Segment>,
+}
+
+impl OutputTemplate {
+    /// Parses a template.
+    ///
+    /// Returns an error message for an unknown or unclosed placeholder, or an
+    /// unsupported date specifier.
+    pub fn parse(template: &str) -> std::result::Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            let segment = match c {
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    let placeholder = Placeholder::parse(&name).ok_or_else(|| {
+                        format!(
+                            "unknown placeholder {{{}}} in '{}' (expected prompt, seed, duration, backend or track_id)",
+                            name, template
+                        )
+                    })?;
+                    Segment::Placeholder(placeholder)
+                }
+                '%' => match chars.next() {
+                    Some('%') => {
+                        literal.push('%');
+                        continue;
+                    }
+                    Some(spec) if "YmdHMSFT".contains(spec) => Segment::Date(spec),
+                    Some(spec) => {
+                        return Err(format!("unsupported date specifier %{} in '{}'", spec, template))
+                    }
+                    None => return Err(format!("trailing % in '{}'", template)),
+                },
+                '}' => return Err(format!("unmatched }} in '{}'", template)),
+                c => {
+                    literal.push(c);
+                    continue;
+                }
+            };
+            if !literal.is_empty() {
+                segments.push(Segment::Literal(std::mem::take(&mut literal)));
+            }
+            segments.push(segment);
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Self { segments })
+    }
+
+    /// Returns true if the template contains `{track_id}`.
+    pub fn uses_track_id(&self) -> bool {
+        self.segments
+            .contains(&Segment::Placeholder(Placeholder::TrackId))
+    }
+
+    /// Returns true if the template's own text contains a path separator.
+    pub fn has_separator(&self) -> bool {
+        self.segments.iter().any(|segment| match segment {
+            Segment::Literal(text) => text.contains(['/', '\\']),
+            _ => false,
+        })
+    }
+
+    /// Expands the template.
+    ///
+    /// Substituted values never contain path separators, so they cannot move
+    /// the result out of the directories the template names.
+    pub fn expand(&self, values: &TemplateValues) -> String {
+        let date = Date::from_system_time(values.time);
+        let mut expanded = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => expanded.push_str(text),
+                Segment::Placeholder(Placeholder::Prompt) => {
+                    let prompt = DisplayPrompt::with_redaction(values.prompt, values.redact_prompt);
+                    expanded.push_str(&slugify(&prompt.to_string()))
+                }
+                Segment::Placeholder(Placeholder::Seed) => {
+                    expanded.push_str(&values.seed.to_string())
+                }
+                Segment::Placeholder(Placeholder::Duration) => {
+                    expanded.push_str(&values.duration_sec.to_string())
+                }
+                Segment::Placeholder(Placeholder::Backend) => {
+                    expanded.push_str(values.backend.as_str())
+                }
+                Segment::Placeholder(Placeholder::TrackId) => {
+                    expanded.push_str(&slugify(values.track_id))
+                }
+                Segment::Date(spec) => expanded.push_str(&date.format(*spec)),
+            }
+        }
+        expanded
+    }
+}
+
+/// Turns a prompt into a filename-safe slug.
+///
+/// Letters and digits are lowercased and kept, every run of other characters
+/// (including path separators and dots) becomes a single `-`, and the result
+/// is truncated to [`MAX_PROMPT_SLUG_LEN`] characters. Prompts without
+/// letters or digits become `untitled`.
+pub fn slugify(prompt: &str) -> String {
+    let mut slug = String::new();
+    for c in prompt.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.chars().take(MAX_PROMPT_SLUG_LEN).collect();
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        EMPTY_SLUG.to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Creates the parent directories of `path` and returns the path to write.
+///
+/// If `path` already exists and `overwrite` is false, `-1`, `-2`, ... is
+/// appended to the file stem until the name is free.
+pub fn prepare_output_path(path: &Path, overwrite: bool) -> Result<PathBuf> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            DaemonError::with_source(
+                ErrorCode::ModelInferenceFailed,
+                format!("Failed to create output directory {}", parent.display()),
+                e,
+            )
+            .with_phase(GenerationPhase::Write)
+        })?;
+    }
+    if overwrite || !path.exists() {
+        return Ok(path.to_path_buf());
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| e.to_string_lossy());
+    (1u32..)
+        .map(|n| {
+            let name = match &extension {
+                Some(extension) => format!("{}-{}.{}", stem, n, extension),
+                None => format!("{}-{}", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .ok_or_else(|| DaemonError::model_inference_failed("No free output filename"))
+}
+
+/// Resolves the `output_path` of a `generate` request to the file to write.
+///
+/// Relative paths are taken relative to `root` if given, else the working
+/// directory. The parent directory must already exist and pass
+/// [`probe_writable`]. With `root`, the path must stay inside it once `..`
+/// and symlinks are resolved. Fails with OUTPUT_PATH_INVALID.
+pub fn resolve_requested_output(path: &str, root: Option<&Path>) -> Result<PathBuf> {
+    let requested = Path::new(path);
+    let joined = match root {
+        Some(root) => root.join(requested),
+        None => requested.to_path_buf(),
+    };
+    let Some(file_name) = joined.file_name() else {
+        return Err(DaemonError::output_path_invalid(requested, "not a file path"));
+    };
+
+    let parent = joined
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let parent = parent.canonicalize().map_err(|e| {
+        DaemonError::output_path_invalid(
+            requested,
+            format!("directory {} does not exist ({})", parent.display(), e),
+        )
+    })?;
+    if !parent.is_dir() {
+        return Err(DaemonError::output_path_invalid(
+            requested,
+            format!("{} is not a directory", parent.display()),
+        ));
+    }
+    let resolved = parent.join(file_name);
+    if resolved.is_dir() {
+        return Err(DaemonError::output_path_invalid(requested, "is a directory"));
+    }
+
+    if let Some(root) = root {
+        let root = root.canonicalize().map_err(|e| {
+            DaemonError::output_path_invalid(
+                requested,
+                format!("restrict_output_root {} does not exist ({})", root.display(), e),
+            )
+        })?;
+        // An existing file may be a symlink pointing out of the root
+        let target = resolved.canonicalize().unwrap_or_else(|_| resolved.clone());
+        if !target.starts_with(&root) {
+            return Err(DaemonError::output_path_invalid(
+                requested,
+                format!("outside restrict_output_root {}", root.display()),
+            ));
+        }
+    }
+
+    probe_writable(&parent).map_err(|e| {
+        DaemonError::output_path_invalid(
+            requested,
+            format!("directory {} is not writable ({})", parent.display(), e),
+        )
+    })?;
+    Ok(resolved)
+}
+
+/// Checks that files can be created in `dir` by creating and removing a
+/// probe file.
+pub fn probe_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(format!(".lofi-write-probe-{}", std::process::id()));
+    std::fs::File::create(&probe)?;
+    std::fs::remove_file(&probe)
+}
+
+/// A UTC calendar date and time of day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Date {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+impl Date {
+    fn from_system_time(time: SystemTime) -> Self {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        };
+        let (days, secs_of_day) = (secs.div_euclid(86_400), secs.rem_euclid(86_400) as u32);
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: secs_of_day / 60 % 60,
+            second: secs_of_day % 60,
+        }
+    }
+
+    fn format(&self, spec: char) -> String {
+        match spec {
+            'Y' => format!("{:04}", self.year),
+            'm' => format!("{:02}", self.month),
+            'd' => format!("{:02}", self.day),
+            'H' => format!("{:02}", self.hour),
+            'M' => format!("{:02}", self.minute),
+            'S' => format!("{:02}", self.second),
+            'F' => format!("{:04}-{:02}-{:02}", self.year, self.month, self.day),
+            'T' => format!("{:02}-{:02}-{:02}", self.hour, self.minute, self.second),
+            _ => unreachable!("date specifiers are checked by OutputTemplate::parse"),
+        }
+    }
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian (year, month, day).
+///
+/// Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// 2024-03-05 07:08:09 UTC.
+    fn fixed_clock() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_709_622_489)
+    }
+
+    fn values(prompt: &str) -> TemplateValues<'_> {
+        TemplateValues {
+            prompt,
+            redact_prompt: false,
+            seed: 42,
+            duration_sec: 30,
+            backend: Backend::AceStep,
+            track_id: "0123456789abcdef",
+            time: fixed_clock(),
+        }
+    }
+
+    fn expand(template: &str, prompt: &str) -> String {
+        OutputTemplate::parse(template).unwrap().expand(&values(prompt))
+    }
+
+    #[test]
+    fn expands_placeholders() {
+        assert_eq!(expand("{prompt}.wav", "Rain Lofi"), "rain-lofi.wav");
+        assert_eq!(expand("{seed}.wav", ""), "42.wav");
+        assert_eq!(expand("{duration}s.wav", ""), "30s.wav");
+        assert_eq!(expand("{backend}.wav", ""), "ace_step.wav");
+        assert_eq!(expand("{track_id}.wav", ""), "0123456789abcdef.wav");
+        assert_eq!(
+            expand("out/{prompt}-{seed}.wav", "rain lofi"),
+            "out/rain-lofi-42.wav"
+        );
+        assert_eq!(expand("plain.wav", "rain"), "plain.wav");
+    }
+
+    #[test]
+    fn redacted_prompt_is_not_in_the_name() {
+        let prompt = "call mom about the surgery";
+        let values = TemplateValues {
+            redact_prompt: true,
+            ..values(prompt)
+        };
+        let name = OutputTemplate::parse("{prompt}-{seed}.wav").unwrap().expand(&values);
+        assert_eq!(name, format!("call-mom-{}-42.wav", crate::types::prompt_hash(prompt)));
+        assert!(!name.contains("surgery"));
+    }
+
+    #[test]
+    fn formats_dates_from_clock() {
+        assert_eq!(expand("%Y-%m-%d/%H%M%S.wav", ""), "2024-03-05/070809.wav");
+        // No ':' from %T, which Windows does not allow in filenames
+        assert_eq!(expand("%F_%T", ""), "2024-03-05_07-08-09");
+        assert_eq!(expand("100%%.wav", ""), "100%.wav");
+
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(OutputTemplate::parse("{title}.wav").is_err());
+        assert!(OutputTemplate::parse("{prompt.wav").is_err());
+        assert!(OutputTemplate::parse("prompt}.wav").is_err());
+        assert!(OutputTemplate::parse("%Q.wav").is_err());
+        assert!(OutputTemplate::parse("out%").is_err());
+    }
+
+    #[test]
+    fn slug_cannot_escape_directory() {
+        assert_eq!(slugify("../../etc/x"), "etc-x");
+        assert_eq!(slugify("..\\..\\windows"), "windows");
+        assert_eq!(slugify("a/b\0c:d*e?"), "a-b-c-d-e");
+        assert_eq!(slugify("..."), "untitled");
+        assert_eq!(expand("lofi/{prompt}.wav", "../../etc/x"), "lofi/etc-x.wav");
+
+        let long = "word ".repeat(20);
+        let slug = slugify(&long);
+        assert!(slug.chars().count() <= MAX_PROMPT_SLUG_LEN);
+        assert!(!slug.ends_with('-'));
+    }
+
+    #[test]
+    fn reports_track_id_and_separators() {
+        assert!(OutputTemplate::parse(DEFAULT_CACHE_FILENAME).unwrap().uses_track_id());
+        assert!(!OutputTemplate::parse("{prompt}.wav").unwrap().uses_track_id());
+        assert!(OutputTemplate::parse("%Y/{track_id}.wav").unwrap().has_separator());
+        assert!(!OutputTemplate::parse("{prompt}.wav").unwrap().has_separator());
+    }
+
+    #[test]
+    fn collisions_get_suffixes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/day/track.wav");
+
+        assert_eq!(prepare_output_path(&path, false).unwrap(), path);
+        assert!(path.parent().unwrap().is_dir());
+
+        std::fs::write(&path, b"first").unwrap();
+        let second = prepare_output_path(&path, false).unwrap();
+        assert_eq!(second, dir.path().join("nested/day/track-1.wav"));
+
+        std::fs::write(&second, b"second").unwrap();
+        assert_eq!(
+            prepare_output_path(&path, false).unwrap(),
+            dir.path().join("nested/day/track-2.wav")
+        );
+        assert_eq!(prepare_output_path(&path, true).unwrap(), path);
+    }
+
+    #[test]
+    fn requested_output_resolves_into_existing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let path = root.join("render.wav");
+
+        let resolved = resolve_requested_output(path.to_str().unwrap(), None).unwrap();
+        assert_eq!(resolved, path);
+        // The probe leaves nothing behind
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+
+        std::fs::create_dir(root.join("renders")).unwrap();
+        let resolved = resolve_requested_output("renders/a.wav", Some(&root)).unwrap();
+        assert_eq!(resolved, root.join("renders/a.wav"));
+        let resolved = resolve_requested_output("renders/../b.wav", Some(&root)).unwrap();
+        assert_eq!(resolved, root.join("b.wav"));
+    }
+
+    #[test]
+    fn requested_output_rejects_unwritable_destinations() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, b"").unwrap();
+
+        let cases = [
+            dir.path().join("missing/render.wav"),
+            file.join("render.wav"),
+            dir.path().to_path_buf(),
+        ];
+        for path in cases {
+            let err = resolve_requested_output(path.to_str().unwrap(), None).unwrap_err();
+            assert_eq!(err.code, ErrorCode::OutputPathInvalid, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn requested_output_stays_inside_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("a")).unwrap();
+        let outside = dir.path().join("outside.wav");
+
+        let cases = [
+            "../outside.wav".to_string(),
+            "a/../../outside.wav".to_string(),
+            outside.to_string_lossy().to_string(),
+        ];
+        for path in &cases {
+            let err = resolve_requested_output(path, Some(&root)).unwrap_err();
+            assert_eq!(err.code, ErrorCode::OutputPathInvalid, "{}", path);
+            assert!(err.message.contains("outside restrict_output_root"), "{}", err);
+        }
+        // Without the restriction the same file is fine
+        assert!(resolve_requested_output(outside.to_str().unwrap(), None).is_ok());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&outside, root.join("link.wav")).unwrap();
+            std::fs::write(&outside, b"").unwrap();
+            let err = resolve_requested_output("link.wav", Some(&root)).unwrap_err();
+            assert!(err.message.contains("outside restrict_output_root"));
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! HTTP liveness endpoint for container orchestration.
+//!
+//! With `--metrics-port` the daemon answers `GET /health` with the same JSON
+//! as the `health` method: HTTP 200 while healthy, 503 otherwise. Generation
+//! blocks the request loop, so the endpoint serves the last health report the
+//! loop published (see [`ServerState::publish_health`]) with a live uptime.
+//!
+//! Metrics are served separately, with the `metrics-http` feature.
+//!
+//! [`ServerState::publish_health`]: super::ServerState::publish_health
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::types::HealthResult;
+
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Health report shared between the request loop and the HTTP thread.
+pub type SharedHealth = Arc<Mutex<HealthResult>>;
+
+/// Serves `GET /health` on `listener` from a background thread.
+///
+/// `started_at` is the daemon start time used to report a live uptime.
+pub fn spawn_health_server(listener: TcpListener, health: SharedHealth, started_at: Instant) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if let Err(e) = handle_connection(stream, &health, started_at) {
+                eprintln!("Health endpoint error: {}", e);
+            }
+        }
+    });
+}
+
+/// Answers a single HTTP request.
+fn handle_connection(
+    mut stream: TcpStream,
+    health: &SharedHealth,
+    started_at: Instant,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers; the endpoint ignores them
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut report = health.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    report.uptime_sec = started_at.elapsed().as_secs_f32();
+
+    let (status, content_type, body) = route(&request_line, &report);
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Returns the HTTP status line, content type and body for a request line.
+fn route(request_line: &str, health: &HealthResult) -> (&'static str, &'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+
+    if (method, path) != ("GET", "/health") {
+        return (
+            "404 Not Found",
+            "application/json",
+            r#"{"error":"not found"}"#.to_string(),
+        );
+    }
+
+    let status = if health.is_healthy() {
+        "200 OK"
+    } else {
+        "503 Service Unavailable"
+    };
+    (status, "application/json", serde_json::to_string(health).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn report(status: &str) -> HealthResult {
+        HealthResult {
+            status: status.to_string(),
+            uptime_sec: 0.0,
+            backend_loaded: false,
+            queue_depth: 0,
+            cache_size: 3,
+        }
+    }
+
+    fn get(port: u16, path: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn routes_health_requests() {
+        let (status, _, body) = route("GET /health HTTP/1.1\r\n", &report("healthy"));
+        assert_eq!(status, "200 OK");
+        assert!(body.contains("\"cache_size\":3"));
+
+        let (status, _, _) = route("GET /health HTTP/1.1\r\n", &report("unhealthy"));
+        assert_eq!(status, "503 Service Unavailable");
+
+        // Metrics are not served next to the health check
+        assert_eq!(route("GET /metrics HTTP/1.1", &report("healthy")).0, "404 Not Found");
+        assert_eq!(route("GET / HTTP/1.1", &report("healthy")).0, "404 Not Found");
+        assert_eq!(route("POST /health HTTP/1.1", &report("healthy")).0, "404 Not Found");
+    }
+
+    #[test]
+    fn serves_health_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let health: SharedHealth = Arc::new(Mutex::new(report("healthy")));
+        spawn_health_server(listener, health.clone(), Instant::now());
+
+        let response = get(port, "/health");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["status"], "healthy");
+        assert_eq!(json["cache_size"], 3);
+
+        *health.lock().unwrap() = report("unhealthy");
+        assert!(get(port, "/health").starts_with("HTTP/1.1 503 "));
+        assert!(get(port, "/missing").starts_with("HTTP/1.1 404 "));
+    }
+}
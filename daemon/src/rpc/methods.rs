@@ -3,22 +3,87 @@
 //! Implements the handlers for all supported JSON-RPC methods.
 
 use std::cell::RefCell;
-use std::time::Instant;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::audio::write_wav;
+use serde::de::DeserializeOwned;
+
+use crate::audio::{
+    append_info_comment, normalize_loudness, read_wav, resample,
+    write_spectrogram_png_with_options, write_wav, AudioStats, Reverb, SilenceTrim, WavValidator,
+};
+use crate::cache::{
+    avoid_reserved_names, read_history, read_now_playing, record_now_playing, save_sidecar,
+    NowPlaying, Ratings, TrackCache, MAX_RATING, MIN_RATING,
+};
+use crate::config::{token_dump_path, DaemonConfig, ParamStrictness};
+use crate::diagnostics::FailureDumper;
+use crate::error::{DaemonError, ErrorCode, GenerationPhase};
+use crate::generation::{
+    available_memory, check_memory, estimate_generation_memory, recommended_max_duration,
+    AudioProgress, DownloadProgressThrottle, ProgressReporter, ProgressTracker, ACE_STEP_PHASES,
+};
+use crate::hooks::{completion_env, error_env, spawn_hook};
+use crate::metrics::GenerationOutcome;
+use crate::models::ace_step::SchedulerType;
+use crate::models::musicgen;
 use crate::models::{
-    check_backend_available, download_backend_with_progress, ensure_ace_step_models, ensure_models,
-    load_backend, Backend, GenerateDispatchParams,
+    check_backend_available, download_backend_with_progress, get_backend_fingerprint,
+    get_backend_version, Backend, DownloadProgressCallback, PendingLoad,
+    DownloadSummary, GenerateDispatchParams, LoadedModels,
+};
+use crate::output::{prepare_output_path, resolve_requested_output, TemplateValues};
+use crate::types::{
+    diff_provenance, normalize_tags, prompt_hash, ConnectionId, DisplayPrompt, GenerationJob,
+    JobPriority, Manifest, ParamAdjustment, Provenance, SamplingParams, StepsParam, Track,
 };
-use crate::types::{compute_track_id, GenerationJob, JobPriority, Track};
 
-use super::server::{send_notification, ServerState};
+use super::notifications::{validate_notification_methods, NotificationFilter, NotificationRouter};
+use super::schema::schema_document;
+use super::server::ServerState;
 use super::types::{
-    BackendInfo, BackendStatus, DownloadBackendParams, DownloadBackendResult, DownloadProgressParams,
-    GenerateParams, GenerateResult, GenerationCompleteParams, GenerationErrorParams,
-    GenerationProgressParams, GenerationStatus, GetBackendsResult, JsonRpcError, Priority,
+    BackendCacheStats, BackendInfo, BackendStatus, CacheDiskUsage, CacheStatsParams, CacheStatsResult, CompareTracksParams, CompareTracksResult, DownloadBackendParams, DownloadBackendResult, DownloadCompleteParams, DownloadProgressParams,
+    ExportManifestParams, ExportManifestResult, GenerateFromManifestParams, GenerateParams, GenerateResult, GenerationCompleteParams, GenerationErrorParams,
+    GenerationProgressParams, GenerationStatus, GetBackendsResult, GetHistoryParams,
+    GetHistoryResult, GetNowPlayingResult, GetQueueResult, GetTrackParams,
+    JsonRpcError, ListTracksParams, ListTracksResult, Priority, PruneOrphansParams,
+    PruneOrphansResult, PurgeBackendParams, PurgeBackendResult, QueuedJobInfo, RateTrackParams,
+    RateTrackResult, SetDefaultBackendParams, SetDefaultBackendResult, StatusResult, SubscribeParams,
+    SubscribeResult, DEFAULT_HISTORY_LIMIT,
 };
 
+/// Methods handled by [`handle_request`].
+pub const METHODS: &[&str] = &[
+    "generate",
+    "get_backends",
+    "download_backend",
+    "purge_backend",
+    "prune_orphans",
+    "get_cache_stats",
+    "get_queue",
+    "list_tracks",
+    "get_track",
+    "rate_track",
+    "compare_tracks",
+    "export_manifest",
+    "generate_from_manifest",
+    "get_now_playing",
+    "get_history",
+    "get_config",
+    "set_config",
+    "set_default_backend",
+    "subscribe",
+    "unsubscribe",
+    "get_schema",
+    "health",
+    "get_metrics",
+    "ping",
+    "shutdown",
+];
+
 /// Handles a JSON-RPC method call.
 pub fn handle_request(
     method: &str,
@@ -29,21 +94,72 @@ pub fn handle_request(
         "generate" => handle_generate(params, state),
         "get_backends" => handle_get_backends(state),
         "download_backend" => handle_download_backend(params, state),
+        "purge_backend" => handle_purge_backend(params, state),
+        "prune_orphans" => handle_prune_orphans(params, state),
+        "get_cache_stats" => handle_get_cache_stats(params, state),
+        "get_queue" => handle_get_queue(state),
+        "list_tracks" => handle_list_tracks(params, state),
+        "get_track" => handle_get_track(params, state),
+        "rate_track" => handle_rate_track(params, state),
+        "compare_tracks" => handle_compare_tracks(params, state),
+        "export_manifest" => handle_export_manifest(params, state),
+        "generate_from_manifest" => handle_generate_from_manifest(params, state),
+        "get_now_playing" => handle_get_now_playing(state),
+        "get_history" => handle_get_history(params, state),
+        "get_config" => handle_get_config(state),
+        "set_config" => handle_set_config(params, state),
+        "set_default_backend" => handle_set_default_backend(params, state),
+        "subscribe" => handle_subscribe(params, state),
+        "unsubscribe" => handle_unsubscribe(state),
+        "get_schema" => Ok(schema_document()),
+        "health" => handle_health_check(state),
+        "get_metrics" => handle_get_metrics(state),
         "ping" => handle_ping(),
         "shutdown" => handle_shutdown(state),
         _ => Err(JsonRpcError::method_not_found(method)),
     }
 }
 
+/// Deserializes request params, naming the offending field on failure.
+///
+/// Errors read like `Invalid params: duration_sec: invalid type: string "thirty",
+/// expected u32`.
+fn parse_params<T: DeserializeOwned>(params: serde_json::Value) -> Result<T, JsonRpcError> {
+    serde_path_to_error::deserialize(params).map_err(|e| {
+        let path = e.path().to_string();
+        if path == "." {
+            JsonRpcError::invalid_params(format!("Invalid params: {}", e.inner()))
+        } else {
+            JsonRpcError::invalid_params(format!("Invalid params: {}: {}", path, e.inner()))
+        }
+    })
+}
+
+/// Handles the health method for liveness probes.
+///
+/// Only reads server state; never loads models or touches the disk.
+fn handle_health_check(state: &ServerState) -> Result<serde_json::Value, JsonRpcError> {
+    Ok(serde_json::to_value(state.health()).unwrap())
+}
+
+/// Handles the get_metrics method.
+///
+/// Returns the metrics in the Prometheus text exposition format, as served
+/// on `/metrics` by the HTTP endpoint.
+fn handle_get_metrics(state: &ServerState) -> Result<serde_json::Value, JsonRpcError> {
+    state.refresh_metrics();
+    Ok(serde_json::Value::String(state.metrics.render()))
+}
+
 /// Handles the ping method for health checks.
 fn handle_ping() -> Result<serde_json::Value, JsonRpcError> {
-    Ok(serde_json::json!({ "status": "ok" }))
+    Ok(serde_json::to_value(StatusResult { status: "ok".to_string() }).unwrap())
 }
 
 /// Handles the shutdown method.
 fn handle_shutdown(state: &mut ServerState) -> Result<serde_json::Value, JsonRpcError> {
     state.shutdown();
-    Ok(serde_json::json!({ "status": "shutting_down" }))
+    Ok(serde_json::to_value(StatusResult { status: "shutting_down".to_string() }).unwrap())
 }
 
 /// Handles the generate method.
@@ -51,113 +167,172 @@ fn handle_generate(
     params: serde_json::Value,
     state: &mut ServerState,
 ) -> Result<serde_json::Value, JsonRpcError> {
-    // Parse parameters
-    let params: GenerateParams = serde_json::from_value(params)
-        .map_err(|e| JsonRpcError::invalid_params(format!("Invalid params: {}", e)))?;
+    // prompt may be omitted only in favor of prompt_tags
+    if params.get("prompt").is_none() && params.get("prompt_tags").is_none() {
+        return Err(JsonRpcError::invalid_params("one of prompt or prompt_tags is required"));
+    }
+    let params: GenerateParams = parse_params(params)?;
+    start_generation(params, Vec::new(), state)
+}
+
+/// Validates a generate request, then returns the cached track or starts or
+/// queues its generation.
+///
+/// `notices` are returned as warnings ahead of those the request raises.
+fn start_generation(
+    mut params: GenerateParams,
+    mut notices: Vec<String>,
+    state: &mut ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    // Compose structured tags and normalize the prompt before validation
+    // and track ID computation
+    notices.extend(params.compose_prompt_tags()?);
+    params.sanitize(state.config.prompt_sanitization)?;
 
     // Resolve which backend to use
     let backend = params.resolve_backend(state.config.default_backend)?;
 
+    // In clamp mode, replace out-of-range values instead of rejecting them
+    let adjusted_params = match state.config.param_strictness {
+        ParamStrictness::Strict => Vec::new(),
+        ParamStrictness::Clamp => params.clamp(backend, &state.config.ace_step),
+    };
+    for adjustment in &adjusted_params {
+        eprintln!("Adjusted {}", adjustment);
+    }
+
     // Validate parameters for the selected backend
     params.validate(backend)?;
+    let output_path = params
+        .output_path
+        .as_deref()
+        .map(|path| resolve_requested_output(path, state.config.restrict_output_root.as_deref()))
+        .transpose()
+        .map_err(JsonRpcError::from)?;
+    let requested_path = output_path
+        .as_ref()
+        .map(|path| path.to_string_lossy().to_string());
+
+    // Settle "auto" and default steps, which are part of the track ID, and
+    // the scheduler and guidance defaults so the response echoes what is used
+    params.resolve_inference_steps(backend, &state.config.ace_step);
+    params.resolve_sampling(backend, &state.config.ace_step);
 
     // Check if queue is full before proceeding
     if state.queue.is_full() {
-        return Err(JsonRpcError::queue_full(state.queue.len()));
+        return Err(JsonRpcError::queue_full(state.queue.len(), state.queue.max_size()));
     }
 
-    // Generate seed if not provided
-    let seed = params.seed.unwrap_or_else(rand::random);
+    // Refuse requests that would exhaust system memory before anything is allocated
+    let model_loaded = state.loaded_models(backend).is_some();
+    let dcae_sessions = state.config.effective_dcae_parallelism();
+    let estimate =
+        estimate_generation_memory(backend, params.duration_sec, model_loaded, dcae_sessions);
+    if let Some(available) = available_memory() {
+        check_memory(backend, &estimate, available, state.config.memory_safety_margin_bytes)
+            .map_err(JsonRpcError::from)?;
+    }
 
-    // Ensure models are downloaded for the selected backend
-    match backend {
-        Backend::MusicGen => {
-            let model_dir = state.config.effective_model_path();
-            if let Err(e) = ensure_models(&model_dir) {
-                return Err(JsonRpcError::model_download_failed(e.to_string()));
-            }
-        }
-        Backend::AceStep => {
-            let model_dir = state.config.effective_ace_step_model_path();
-            if let Err(e) = ensure_ace_step_models(&model_dir) {
-                return Err(JsonRpcError::model_download_failed(e.to_string()));
-            }
+    // Durations that fit but leave little headroom are accepted with a warning
+    let margin = state.config.memory_safety_margin_bytes;
+    let recommended = recommended_max_duration(backend, model_loaded, dcae_sessions, margin);
+
+    // Generate seed if not provided, preferring well-rated seeds if asked
+    let (seed, derived_from_seed) = match params.seed {
+        Some(seed) => (seed, None),
+        None if params.prefer_rated && params.seeds.is_none() => {
+            let (seed, derived_from_seed, notice) = preferred_seed(
+                &state.ratings,
+                &state.cache,
+                &params.prompt,
+                params.duration_sec,
+                backend,
+            );
+            notices.push(notice);
+            (seed, derived_from_seed)
         }
+        None => (rand::random(), None),
+    };
+    let mut warnings = notices.clone();
+    warnings.extend(params.duration_warning(recommended));
+
+    // Ensure models are downloaded for the selected backend
+    let model_dir = match backend {
+        Backend::MusicGen => state.config.effective_model_path(),
+        Backend::AceStep => state.config.effective_ace_step_model_path(),
+    };
+    let summary = download_models(state, backend, &model_dir).map_err(JsonRpcError::from)?;
+    if summary.files_downloaded > 0 {
+        state.mark_models_updated(backend);
     }
 
     // Check if the loaded models match the requested backend
-    let current_backend = state.models.backend();
-    if current_backend != Some(backend) {
-        // Need to load the correct backend
-        let model_dir = match backend {
-            Backend::MusicGen => state.config.effective_model_path(),
-            Backend::AceStep => state.config.effective_ace_step_model_path(),
-        };
-        match load_backend(backend, &model_dir, &state.config) {
-            Ok(models) => state.set_models(models),
-            Err(e) => return Err(JsonRpcError::model_load_failed(e.to_string())),
-        }
+    ensure_backend_loaded(state, backend).map_err(JsonRpcError::from)?;
+
+    // Reject prompts longer than the text encoder takes before queueing them
+    if let Some(models) = state.models.as_ace_step() {
+        models
+            .text_encoder
+            .validate_prompt_token_length(&params.prompt)
+            .map_err(JsonRpcError::from)?;
     }
 
     let model_version = state.models.version().unwrap_or("unknown").to_string();
 
-    // Compute track ID (includes backend for uniqueness)
-    let track_id = compute_track_id(
-        backend,
-        &params.prompt,
-        seed,
-        params.duration_sec as f32,
-        &model_version,
-    );
+    // Batches queue one job per seed
+    if let Some(seeds) = params.batch_seeds(seed) {
+        warnings.extend(params.batch_warnings());
+        let batch = BatchRequest {
+            params: &params,
+            backend,
+            model_version: &model_version,
+            adjusted_params,
+        };
+        return start_batch(batch, &seeds, warnings, state);
+    }
 
-    // Check cache for existing track
-    if let Some(track) = state.cache.get(&track_id) {
-        // Return cached track immediately
-        send_notification(
-            "generation_complete",
-            GenerationCompleteParams {
-                track_id: track.track_id.clone(),
-                path: track.path.to_string_lossy().to_string(),
-                duration_sec: track.duration_sec,
-                sample_rate: track.sample_rate,
-                prompt: track.prompt.clone(),
-                seed: track.seed,
-                generation_time_sec: 0.0, // Cached, no generation time
-                model_version: track.model_version.clone(),
-                backend: track.backend.as_str().to_string(),
-            },
-        );
+    // Compute track ID (includes backend for uniqueness)
+    let track_id = params.track_id(backend, seed, &model_version, &state.config.silence_trim());
 
+    // Return cached track immediately, unless it must be written elsewhere
+    let cached = output_path.is_none()
+        && notify_cached_track(state, &track_id, &adjusted_params, params.save_to_output);
+    if output_path.is_none() {
+        state.metrics.record_cache_lookup(backend, cached);
+    }
+    if cached {
+        let status = (GenerationStatus::Complete, 0);
         return Ok(serde_json::to_value(GenerateResult {
-            track_id: track.track_id.clone(),
-            status: GenerationStatus::Complete,
-            position: 0,
-            seed,
-            backend: backend.as_str().to_string(),
+            warnings: notices,
+            adjusted_params,
+            derived_from_seed,
+            ..generate_result(&params, backend, track_id, seed, status, state)
         })
         .unwrap());
     }
 
-    // Convert RPC priority to job priority
-    let job_priority = match params.priority {
-        Priority::High => JobPriority::High,
-        Priority::Normal => JobPriority::Normal,
-    };
+    // A read-only cache can serve cached tracks but not store new ones
+    if output_path.is_none() || params.also_cache {
+        state.ensure_cache_writable("generate new tracks")?;
+    }
 
     // Create a generation job
-    let job = GenerationJob::new(
-        params.prompt.clone(),
-        params.duration_sec,
-        Some(seed),
-        job_priority,
+    let job = generation_job(
+        &params,
+        seed,
+        backend,
         &model_version,
-    );
+        adjusted_params.clone(),
+        state,
+    )
+    .with_output_path(output_path, params.also_cache)
+    .with_derived_from_seed(derived_from_seed);
 
     // Add job to queue and get position
     let position = state
         .queue
         .add(job)
-        .map_err(|e| JsonRpcError::queue_full(e.current_size))?;
+        .map_err(|e| JsonRpcError::queue_full(e.current_size, e.max_size))?;
 
     // Check if this job should start immediately (position 0 and nothing generating)
     let should_generate_now = position == 0;
@@ -168,210 +343,542 @@ fn handle_generate(
         job.set_generating();
 
         // Return response indicating generation is starting
+        let status = (GenerationStatus::Generating, 0);
         let result = GenerateResult {
-            track_id: track_id.clone(),
-            status: GenerationStatus::Generating,
-            position: 0,
-            seed,
-            backend: backend.as_str().to_string(),
+            warnings,
+            adjusted_params,
+            derived_from_seed,
+            path: requested_path,
+            ..generate_result(&params, backend, track_id, seed, status, state)
         };
 
-        // Build dispatch params
-        let dispatch_params = GenerateDispatchParams::new(
-            params.prompt.clone(),
-            params.duration_sec,
-            seed,
-            backend,
-        )
-        .with_ace_step_params(
-            params.inference_steps,
-            params.scheduler.clone(),
-            params.guidance_scale,
-        );
+        // Generate through the same steps as queued jobs, then work through
+        // whatever was queued meanwhile, even after a failure
+        let mut generation = QueuedGeneration::start(state, job);
+        let generated = generation.run(&mut state.models);
+        let finished = generation.finish(state, generated);
+        process_next_job(state);
+        finished.map_err(JsonRpcError::from)?;
 
-        // Perform generation
-        let start_time = Instant::now();
-        let sample_rate = backend.sample_rate();
+        Ok(serde_json::to_value(result).unwrap())
+    } else {
+        // Job is queued, return immediately with queue position
+        let status = (GenerationStatus::Queued, position);
+        Ok(serde_json::to_value(GenerateResult {
+            warnings,
+            adjusted_params,
+            derived_from_seed,
+            path: requested_path,
+            ..generate_result(&params, backend, track_id, seed, status, state)
+        })
+        .unwrap())
+    }
+}
 
-        // Track progress - use RefCell for interior mutability in closure
-        let last_percent = RefCell::new(0u8);
-        let track_id_for_progress = track_id.clone();
+/// Builds the `generate` response for a track, with no warnings, adjusted
+/// parameters or path.
+///
+/// `params` must already have its ACE-Step steps and sampling resolved, and
+/// `status` is the initial status with the queue position.
+fn generate_result(
+    params: &GenerateParams,
+    backend: Backend,
+    track_id: String,
+    seed: u64,
+    (status, position): (GenerationStatus, usize),
+    state: &ServerState,
+) -> GenerateResult {
+    GenerateResult {
+        track_id,
+        status,
+        position,
+        seed,
+        backend: backend.as_str().to_string(),
+        prompt: DisplayPrompt::with_redaction(&params.prompt, state.config.redact_prompts)
+            .to_string(),
+        duration_sec: params.duration_sec,
+        inference_steps: params.inference_steps.and_then(StepsParam::fixed),
+        scheduler: params.scheduler.clone(),
+        guidance_scale: params.guidance_scale,
+        warnings: Vec::new(),
+        adjusted_params: Vec::new(),
+        derived_from_seed: None,
+        track_ids_by_seed: None,
+        path: None,
+    }
+}
 
-        // Track if this is step-based (ACE-Step) or token-based (MusicGen)
-        let is_step_based = backend == Backend::AceStep;
+/// A validated batch generate request.
+struct BatchRequest<'a> {
+    params: &'a GenerateParams,
+    backend: Backend,
+    model_version: &'a str,
+    adjusted_params: Vec<ParamAdjustment>,
+}
 
-        match state.models.generate(&dispatch_params, |current, total| {
-            if total == 0 {
-                return;
-            }
+/// Starts a batch: cached tracks are returned right away and one job is
+/// queued per remaining seed, then the queue is processed.
+///
+/// Fails without queueing anything if the queue cannot take every job.
+fn start_batch(
+    batch: BatchRequest<'_>,
+    seeds: &[u64],
+    warnings: Vec<String>,
+    state: &mut ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let BatchRequest {
+        params,
+        backend,
+        model_version,
+        adjusted_params,
+    } = batch;
+    let trim = state.config.silence_trim();
+    let track_ids: BTreeMap<u64, String> = seeds
+        .iter()
+        .map(|&seed| (seed, params.track_id(backend, seed, model_version, &trim)))
+        .collect();
+    let uncached: Vec<u64> = seeds
+        .iter()
+        .copied()
+        .filter(|seed| !state.cache.contains(&track_ids[seed]))
+        .collect();
+    if !uncached.is_empty() {
+        state.ensure_cache_writable("generate new tracks")?;
+    }
 
-            // Calculate percent directly from callback values
-            let percent = std::cmp::min((current * 100 / total) as u8, 99);
-            let mut last = last_percent.borrow_mut();
+    let queued_before = state.queue.len();
+    if queued_before + uncached.len() > state.queue.max_size() {
+        return Err(JsonRpcError::queue_full(queued_before, state.queue.max_size()));
+    }
 
-            // Report every 5% increment
-            let next_threshold = (*last / 5 + 1) * 5;
-            if percent >= next_threshold || current == total {
-                *last = (percent / 5) * 5;
+    for seed in seeds {
+        let cached =
+            notify_cached_track(state, &track_ids[seed], &adjusted_params, params.save_to_output);
+        state.metrics.record_cache_lookup(backend, cached);
+    }
+    for &seed in &uncached {
+        let job = generation_job(
+            params,
+            seed,
+            backend,
+            model_version,
+            adjusted_params.clone(),
+            state,
+        );
+        state
+            .queue
+            .add(job)
+            .map_err(|e| JsonRpcError::queue_full(e.current_size, e.max_size))?;
+    }
 
-                let elapsed = start_time.elapsed().as_secs_f32();
-                let eta_sec = if current > 0 && elapsed > 0.0 {
-                    let remaining = total.saturating_sub(current);
-                    (remaining as f32 / current as f32) * elapsed
-                } else {
-                    0.0
-                };
+    let status = if uncached.is_empty() {
+        (GenerationStatus::Complete, 0)
+    } else if queued_before == 0 {
+        (GenerationStatus::Generating, 0)
+    } else {
+        (GenerationStatus::Queued, queued_before)
+    };
+    let first_seed = seeds[0];
+    let first_track_id = track_ids[&first_seed].clone();
+    let result = GenerateResult {
+        warnings,
+        adjusted_params,
+        track_ids_by_seed: Some(track_ids),
+        ..generate_result(params, backend, first_track_id, first_seed, status, state)
+    };
 
-                // Include step info for ACE-Step, None for MusicGen
-                let (current_step, total_steps) = if is_step_based {
-                    (Some(current), Some(total))
-                } else {
-                    (None, None)
-                };
+    // Nothing was generating before, so start working through the batch
+    if queued_before == 0 {
+        process_next_job(state);
+    }
 
-                send_notification(
-                    "generation_progress",
-                    GenerationProgressParams {
-                        track_id: track_id_for_progress.clone(),
-                        percent: if current == total { 100 } else { percent },
-                        tokens_generated: current,
-                        tokens_estimated: total,
-                        eta_sec,
-                        current_step,
-                        total_steps,
-                    },
-                );
-            }
-        }) {
-            Ok(samples) => {
-                let generation_time = start_time.elapsed().as_secs_f32();
-                let actual_duration = samples.len() as f32 / sample_rate as f32;
-
-                // Write to cache directory
-                let cache_dir = state.config.effective_cache_path();
-                std::fs::create_dir_all(&cache_dir).ok();
-                let output_path = cache_dir.join(format!("{}.wav", track_id));
-
-                if let Err(e) = write_wav(&samples, &output_path, sample_rate) {
-                    send_notification(
-                        "generation_error",
-                        GenerationErrorParams {
-                            track_id: track_id.clone(),
-                            code: "MODEL_INFERENCE_FAILED".to_string(),
-                            message: format!("Failed to write audio file: {}", e),
-                        },
-                    );
-                    return Err(JsonRpcError::model_inference_failed(format!(
-                        "Failed to write audio file: {}",
-                        e
-                    )));
-                }
+    Ok(serde_json::to_value(result).unwrap())
+}
 
-                // Create track and cache it
-                let track = Track::new(
-                    output_path.clone(),
-                    params.prompt.clone(),
-                    actual_duration,
-                    seed,
-                    model_version.clone(),
-                    backend,
-                    generation_time,
-                );
-                state.cache.put(track);
+/// Creates the generation job for `params` with `seed`, for the connection
+/// handling the request and with the configured silence trim.
+fn generation_job(
+    params: &GenerateParams,
+    seed: u64,
+    backend: Backend,
+    model_version: &str,
+    adjusted_params: Vec<ParamAdjustment>,
+    state: &ServerState,
+) -> GenerationJob {
+    // Convert RPC priority to job priority
+    let job_priority = match params.priority {
+        Priority::High => JobPriority::High,
+        Priority::Normal => JobPriority::Normal,
+    };
 
-                // Send completion notification
-                send_notification(
-                    "generation_complete",
-                    GenerationCompleteParams {
-                        track_id: track_id.clone(),
-                        path: output_path.to_string_lossy().to_string(),
-                        duration_sec: actual_duration,
-                        sample_rate,
-                        prompt: params.prompt,
-                        seed,
-                        generation_time_sec: generation_time,
-                        model_version,
-                        backend: backend.as_str().to_string(),
-                    },
-                );
+    GenerationJob::with_backend(
+        params.prompt.clone(),
+        params.duration_sec,
+        Some(seed),
+        job_priority,
+        model_version,
+        backend,
+    )
+    .with_tags(normalize_tags(&params.tags))
+    .with_prompt_blend(params.prompt_blend.clone())
+    .with_ace_step_params(
+        params.inference_steps.and_then(StepsParam::fixed),
+        params.scheduler.clone(),
+        params.guidance_scale,
+    )
+    .with_output_sample_rate(params.output_sample_rate)
+    .with_reverb(params.reverb_settings())
+    .with_trim_silence(params.trim_silence.then(|| state.config.silence_trim()).as_ref())
+    .with_notify_interval_ms(params.notify_interval_ms)
+    .with_adjusted_params(adjusted_params)
+    .with_save_to_output(params.save_to_output)
+    .with_connection_id(state.connection_id)
+}
 
-                // Process next job in queue if any
-                process_next_job(state, backend);
-            }
-            Err(e) => {
-                send_notification(
-                    "generation_error",
-                    GenerationErrorParams {
-                        track_id: track_id.clone(),
-                        code: "MODEL_INFERENCE_FAILED".to_string(),
-                        message: e.to_string(),
-                    },
-                );
+/// Sends `generation_complete` for `track_id` if it is cached, and makes it
+/// the now-playing track. With `save_to_output` the cached WAV is copied into
+/// the library directory as well.
+///
+/// Returns true if the track was cached.
+fn notify_cached_track(
+    state: &mut ServerState,
+    track_id: &str,
+    adjusted_params: &[ParamAdjustment],
+    save_to_output: bool,
+) -> bool {
+    let Some(track) = state.cache.get(track_id).cloned() else {
+        return false;
+    };
+    let track = &track;
+    write_now_playing(state, track, || {
+        read_wav(&track.path)
+            .map(|(samples, sample_rate)| AudioStats::from_samples(&samples, sample_rate))
+    });
+
+    state.notifications.notify_job(
+        "generation_complete",
+        GenerationCompleteParams {
+            track_id: track.track_id.clone(),
+            path: track.path.to_string_lossy().to_string(),
+            duration_sec: track.duration_sec,
+            sample_rate: track.sample_rate,
+            prompt: DisplayPrompt::with_redaction(&track.prompt, state.config.redact_prompts)
+                .to_string(),
+            seed: track.seed,
+            generation_time_sec: 0.0, // Cached, no generation time
+            model_version: track.model_version.clone(),
+            backend: track.backend.as_str().to_string(),
+            inference_steps: track
+                .provenance
+                .as_ref()
+                .and_then(|provenance| provenance.sampling.inference_steps),
+            file_size_bytes: track.file_size_bytes,
+            tags: track.tags.clone(),
+            thumbnail_path: existing_thumbnail(&track.path),
+            library_path: copy_to_library(state, track, save_to_output),
+            adjusted_params: adjusted_params.to_vec(),
+        },
+        &track.track_id,
+        state.connection_id,
+    );
+    true
+}
+
+/// Loads the models for `backend` unless they are already loaded.
+///
+/// Standby models for `backend` are made active instead of loading again.
+pub(super) fn ensure_backend_loaded(state: &mut ServerState, backend: Backend) -> crate::error::Result<()> {
+    if state.models.backend() == Some(backend) || state.activate_standby(backend) {
+        return Ok(());
+    }
+    let models = load_models(state, backend)?;
+    state.set_models(models);
+    Ok(())
+}
 
-                // Process next job in queue even after failure
-                process_next_job(state, backend);
+/// Loads the standby models for `backend` unless they are already loaded.
+///
+/// Fails with INSUFFICIENT_MEMORY, before loading anything, if the models
+/// and a `duration_sec` generation would not fit into `available_bytes`.
+fn ensure_standby_loaded(
+    state: &mut ServerState,
+    backend: Backend,
+    duration_sec: u32,
+    available_bytes: Option<u64>,
+) -> crate::error::Result<()> {
+    let loaded = state.standby_models.backend() == Some(backend);
+    let dcae_sessions = state.config.effective_dcae_parallelism();
+    let estimate = estimate_generation_memory(backend, duration_sec, loaded, dcae_sessions);
+    if let Some(available) = available_bytes {
+        check_memory(backend, &estimate, available, state.config.memory_safety_margin_bytes)?;
+    }
+    if loaded {
+        return Ok(());
+    }
+    let models = load_models(state, backend)?;
+    state.set_standby_models(models);
+    Ok(())
+}
 
-                return Err(JsonRpcError::model_inference_failed(e.to_string()));
+/// Loads the models for `backend`, tracking its status while loading.
+///
+/// A load that timed out keeps running, so it is waited on again rather than
+/// started twice. One left running for the other backend is waited out and
+/// dropped first, so two loads never hold memory at once. A load that times
+/// out again stays pending for the next attempt.
+fn load_models(state: &mut ServerState, backend: Backend) -> crate::error::Result<LoadedModels> {
+    let timeout = Duration::from_secs(state.config.model_load_timeout_sec);
+    match state.pending_load.take() {
+        Some(mut other) if other.backend() != backend => {
+            eprintln!("Waiting for the {} load to finish before loading {}", other.backend(), backend);
+            if let Err(e) = other.wait(timeout) {
+                if e.code == ErrorCode::TimedOut {
+                    state.pending_load = Some(other);
+                    return Err(e);
+                }
             }
         }
+        pending => state.pending_load = pending,
+    }
 
-        Ok(serde_json::to_value(result).unwrap())
-    } else {
-        // Job is queued, return immediately with queue position
-        Ok(serde_json::to_value(GenerateResult {
-            track_id,
-            status: GenerationStatus::Queued,
-            position,
-            seed,
-            backend: backend.as_str().to_string(),
-        })
-        .unwrap())
+    let mut load = match state.pending_load.take() {
+        Some(load) => {
+            eprintln!("Waiting for the {} models still loading from an earlier request", backend);
+            load
+        }
+        None => {
+            let model_dir = match backend {
+                Backend::MusicGen => state.config.effective_model_path(),
+                Backend::AceStep => state.config.effective_ace_step_model_path(),
+            };
+            PendingLoad::start(backend, &model_dir, &state.config)
+        }
+    };
+
+    state.begin_loading(backend);
+    let loaded = load.wait(timeout);
+    if let Err(e) = &loaded {
+        if e.code == ErrorCode::TimedOut {
+            state.pending_load = Some(load);
+        }
+        state.loading_failed(backend, e);
     }
+    loaded
 }
 
 /// Process the next job in the queue if any.
-fn process_next_job(state: &mut ServerState, backend: Backend) {
-    if let Some(mut job) = state.queue.pop_next() {
-        job.set_generating();
+///
+/// The job is chosen by the configured queue policy, and its backend is
+/// loaded first if another one is active. With `parallel_backends` set, the
+/// next job for the other backend is generated at the same time on the
+/// standby models.
+fn process_next_job(state: &mut ServerState) {
+    let next = state
+        .queue
+        .pop_next_with_policy(state.models.backend(), SystemTime::now());
+    let Some(mut job) = next else {
+        return;
+    };
+    job.set_generating();
 
-        let track_id = job.track_id.clone();
-        let prompt = job.prompt.clone();
-        let duration_sec = job.duration_sec;
-        let seed = job.seed.unwrap_or_else(rand::random);
+    if let Err(e) = ensure_backend_loaded(state, job.backend) {
+        notify_job_error(state, &job, &e);
+        process_next_job(state);
+        return;
+    }
 
-        let model_version = state.models.version().unwrap_or("unknown").to_string();
-        let sample_rate = backend.sample_rate();
+    let mut first = QueuedGeneration::start(state, job);
+    match pop_parallel_job(state, &first.job, available_memory()) {
+        Some(job) => {
+            let second = QueuedGeneration::start(state, job);
+            generate_in_parallel(state, first, second);
+        }
+        None => {
+            let result = first.run(&mut state.models);
+            first.finish(state, result).ok();
+        }
+    }
 
-        // Build dispatch params for queued job (uses defaults for ACE-Step params)
-        let dispatch_params = GenerateDispatchParams::new(prompt.clone(), duration_sec, seed, backend);
+    // Continue processing queue, even after a failure
+    process_next_job(state);
+}
 
-        let start_time = Instant::now();
+/// Generates `first` on the active models and `second` on the standby
+/// models at the same time, then finishes both.
+fn generate_in_parallel(
+    state: &mut ServerState,
+    mut first: QueuedGeneration,
+    mut second: QueuedGeneration,
+) {
+    // The two backends use separate sessions, so each thread gets
+    // exclusive access to its own models
+    let models = &mut state.models;
+    let standby_models = &mut state.standby_models;
+    let (first_result, second_result) = thread::scope(|scope| {
+        let handle = scope.spawn(|| second.run(standby_models));
+        let first_result = first.run(models);
+        let second_result = handle
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        (first_result, second_result)
+    });
+    first.finish(state, first_result).ok();
+    second.finish(state, second_result).ok();
+}
 
-        // Track progress
-        let last_percent = RefCell::new(0u8);
-        let track_id_for_progress = track_id.clone();
-        let is_step_based = backend == Backend::AceStep;
+/// Removes the next job for the backend other than `active`'s and loads its
+/// standby models, if `parallel_backends` is set.
+///
+/// The job is chosen by `GenerationQueue::pop_next_for_backend`, so it
+/// follows the queue's priority order and policy. It stays queued if it
+/// would not fit into `available_bytes` next to `active`, and runs once its
+/// backend is active instead. If the models fail to load, the job fails and
+/// None is returned.
+fn pop_parallel_job(
+    state: &mut ServerState,
+    active: &GenerationJob,
+    available_bytes: Option<u64>,
+) -> Option<GenerationJob> {
+    if !state.config.parallel_backends {
+        return None;
+    }
+    let other = match active.backend {
+        Backend::MusicGen => Backend::AceStep,
+        Backend::AceStep => Backend::MusicGen,
+    };
+    let duration_sec = state.queue.peek_next_for_backend(other)?.duration_sec;
+    let reserved = estimate_generation_memory(active.backend, active.duration_sec, true, 1).total();
+    let available = available_bytes.map(|available| available.saturating_sub(reserved));
+    let loaded = ensure_standby_loaded(state, other, duration_sec, available);
+    if let Err(e) = &loaded {
+        if e.code == ErrorCode::InsufficientMemory {
+            eprintln!("Not starting a {} job in parallel: {}", other, e.message);
+            return None;
+        }
+    }
 
-        match state.models.generate(&dispatch_params, |current, total| {
-            if total == 0 {
-                return;
-            }
+    let mut job = state.queue.pop_next_for_backend(other)?;
+    job.set_generating();
+    match loaded {
+        Ok(()) => Some(job),
+        Err(e) => {
+            notify_job_error(state, &job, &e);
+            None
+        }
+    }
+}
 
-            let percent = std::cmp::min((current * 100 / total) as u8, 99);
-            let mut last = last_percent.borrow_mut();
+/// Sends a `generation_error` notification for `job` and counts the failure.
+fn notify_job_error(state: &ServerState, job: &GenerationJob, error: &DaemonError) {
+    state.notifications.notify_job(
+        "generation_error",
+        GenerationErrorParams::from_error(&job.track_id, error),
+        &job.track_id,
+        job.connection_id,
+    );
+    state.metrics.record(job.backend, GenerationOutcome::Failed);
+    run_error_hook(&state.config, job, error);
+}
 
-            let next_threshold = (*last / 5 + 1) * 5;
-            if percent >= next_threshold || current == total {
-                *last = (percent / 5) * 5;
+/// Runs `on_complete_command` for a newly generated track, if configured.
+fn run_complete_hook(config: &DaemonConfig, track: &Track) {
+    if let Some(command) = &config.on_complete_command {
+        spawn_hook(command, completion_env(track, config.redact_prompts));
+    }
+}
 
-                let elapsed = start_time.elapsed().as_secs_f32();
-                let eta_sec = if current > 0 && elapsed > 0.0 {
-                    let remaining = total.saturating_sub(current);
-                    (remaining as f32 / current as f32) * elapsed
-                } else {
-                    0.0
-                };
+/// Runs `on_error_command` for a failed job, if configured.
+fn run_error_hook(config: &DaemonConfig, job: &GenerationJob, error: &DaemonError) {
+    if let Some(command) = &config.on_error_command {
+        spawn_hook(command, error_env(job, error, config.redact_prompts));
+    }
+}
+
+/// A queued job whose generation has started.
+///
+/// Split into [`start`](Self::start), [`run`](Self::run) and
+/// [`finish`](Self::finish) so that only `run`, which needs nothing but the
+/// backend's models, can happen on another thread.
+struct QueuedGeneration {
+    job: GenerationJob,
+    seed: u64,
+    model_version: String,
+    dispatch_params: GenerateDispatchParams,
+    reporter: ProgressReporter,
+    tracker: Arc<Mutex<ProgressTracker>>,
+    notifications: NotificationRouter,
+    start_time: Instant,
+}
+
+impl QueuedGeneration {
+    /// Builds the dispatch parameters for `job`, whose backend must be
+    /// loaded, and marks it as generating.
+    fn start(state: &mut ServerState, job: GenerationJob) -> Self {
+        let backend = job.backend;
+        let seed = job.seed.unwrap_or_else(rand::random);
+        let model_version = state
+            .loaded_models(backend)
+            .and_then(LoadedModels::version)
+            .unwrap_or("unknown")
+            .to_string();
+
+        // Build dispatch params, falling back to the configured ACE-Step defaults
+        let ace_step = &state.config.ace_step;
+        let dispatch_params =
+            GenerateDispatchParams::new(job.prompt.clone(), job.duration_sec, seed, backend)
+                .with_ace_step_params(
+                    Some(ace_step.effective_inference_steps(job.inference_steps)),
+                    Some(ace_step.effective_scheduler(job.scheduler.as_deref()).to_string()),
+                    Some(ace_step.effective_guidance_scale(job.guidance_scale)),
+                )
+                .with_prompt_blend(job.prompt_blend.clone())
+                .with_failure_dumper(FailureDumper::from_config(&state.config))
+                .with_token_dump(token_dump_path());
 
+        let start_time = Instant::now();
+        let notifications = state.notifications.clone();
+        let total_steps = dispatch_params.inference_steps.unwrap_or_default() as usize;
+        let tracker = progress_tracker(&job, total_steps);
+        let dispatch_params = dispatch_params.with_audio_progress(audio_progress_notifier(
+            &notifications,
+            &job.track_id,
+            job.connection_id,
+            total_steps,
+            &tracker,
+        ));
+
+        state.mark_models_used();
+        state.begin_backend_job(backend);
+        state.metrics.record(backend, GenerationOutcome::Started);
+        Self {
+            reporter: progress_reporter(&state.config, &job),
+            tracker,
+            job,
+            seed,
+            model_version,
+            dispatch_params,
+            notifications,
+            start_time,
+        }
+    }
+
+    /// Generates the audio with `models`, sending progress notifications.
+    fn run(&mut self, models: &mut LoadedModels) -> crate::error::Result<(Vec<f32>, u64)> {
+        let reporter = RefCell::new(&mut self.reporter);
+        let job = &self.job;
+        let track_id = &job.track_id;
+        let owner = job.connection_id;
+        let is_step_based = job.backend == Backend::AceStep;
+        let tracker = &self.tracker;
+        let notifications = &self.notifications;
+
+        models.generate(&self.dispatch_params, |current, total| {
+            let now = Instant::now();
+            let eta_sec = track_progress(tracker, job, current, total, now);
+            if is_step_based && current >= total {
+                return;
+            }
+            if let Some(percent) = reporter.borrow_mut().report(current, total, now) {
                 // Include step info for ACE-Step, None for MusicGen
                 let (current_step, total_steps) = if is_step_based {
                     (Some(current), Some(total))
@@ -379,120 +886,953 @@ fn process_next_job(state: &mut ServerState, backend: Backend) {
                     (None, None)
                 };
 
-                send_notification(
+                notifications.notify_job(
                     "generation_progress",
                     GenerationProgressParams {
-                        track_id: track_id_for_progress.clone(),
-                        percent: if current == total { 100 } else { percent },
+                        track_id: track_id.clone(),
+                        percent,
                         tokens_generated: current,
                         tokens_estimated: total,
                         eta_sec,
                         current_step,
                         total_steps,
+                        decoded_sec: None,
+                        total_sec: None,
                     },
+                    track_id,
+                    owner,
                 );
             }
-        }) {
-            Ok(samples) => {
-                let generation_time = start_time.elapsed().as_secs_f32();
-                let actual_duration = samples.len() as f32 / sample_rate as f32;
-
-                let cache_dir = state.config.effective_cache_path();
-                std::fs::create_dir_all(&cache_dir).ok();
-                let output_path = cache_dir.join(format!("{}.wav", track_id));
-
-                if let Err(e) = write_wav(&samples, &output_path, sample_rate) {
-                    send_notification(
-                        "generation_error",
-                        GenerationErrorParams {
-                            track_id: track_id.clone(),
-                            code: "MODEL_INFERENCE_FAILED".to_string(),
-                            message: format!("Failed to write audio file: {}", e),
-                        },
-                    );
-                } else {
-                    let track = Track::new(
-                        output_path.clone(),
-                        prompt.clone(),
-                        actual_duration,
-                        seed,
-                        model_version.clone(),
-                        backend,
-                        generation_time,
-                    );
-                    state.cache.put(track);
-
-                    send_notification(
-                        "generation_complete",
-                        GenerationCompleteParams {
-                            track_id: track_id.clone(),
-                            path: output_path.to_string_lossy().to_string(),
-                            duration_sec: actual_duration,
-                            sample_rate,
-                            prompt,
-                            seed,
-                            generation_time_sec: generation_time,
-                            model_version,
-                            backend: backend.as_str().to_string(),
-                        },
-                    );
-                }
+        })
+    }
 
-                // Continue processing queue
-                process_next_job(state, backend);
-            }
-            Err(e) => {
-                send_notification(
-                    "generation_error",
-                    GenerationErrorParams {
-                        track_id: track_id.clone(),
-                        code: "MODEL_INFERENCE_FAILED".to_string(),
-                        message: e.to_string(),
-                    },
-                );
+    /// Post-processes and caches the generated audio, sending the completion
+    /// or error notification.
+    ///
+    /// Returns the error that failed the job, after it has been notified.
+    fn finish(
+        self,
+        state: &mut ServerState,
+        generated: crate::error::Result<(Vec<f32>, u64)>,
+    ) -> crate::error::Result<()> {
+        let Self {
+            job,
+            seed,
+            model_version,
+            dispatch_params,
+            notifications,
+            start_time,
+            ..
+        } = self;
+        let backend = job.backend;
+        let track_id = job.track_id.clone();
+        let owner = job.connection_id;
+        let reverb = job.reverb;
+        let sample_rate = backend.sample_rate();
+        state.end_backend_job(backend);
 
-                // Continue processing queue even after failure
-                process_next_job(state, backend);
+        let (mut samples, generated_seed) = match generated {
+            Ok(generated) => generated,
+            Err(e) => {
+                notify_job_error(state, &job, &e);
+                return Err(e);
             }
-        }
-    }
-}
+        };
 
-/// Handles the get_backends method.
-fn handle_get_backends(state: &ServerState) -> Result<serde_json::Value, JsonRpcError> {
-    // Check installation status for each backend
-    // "Ready" means models are downloaded and can be loaded on-demand
-    let musicgen_status = if check_backend_available(Backend::MusicGen, &state.config.effective_model_path()) {
-        // Models exist on disk - report as Ready (loadable on-demand)
-        BackendStatus::Ready
-    } else {
-        BackendStatus::NotInstalled
-    };
+        let generation_time = start_time.elapsed().as_secs_f32();
+        let trim = job.trim_silence.then(|| state.config.silence_trim());
+        let provenance =
+            generation_provenance(state, &dispatch_params, trim.as_ref(), reverb.as_ref())
+                .with_timing("generate", generation_time);
+        let generated_duration = samples.len() as f32 / sample_rate as f32;
+        let mut provenance = post_process(
+            &state.config,
+            &mut samples,
+            sample_rate,
+            trim.as_ref(),
+            reverb.as_ref(),
+            provenance,
+        );
+        let actual_duration = samples.len() as f32 / sample_rate as f32;
 
-    let ace_step_status = if check_backend_available(Backend::AceStep, &state.config.effective_ace_step_model_path()) {
-        // Models exist on disk - report as Ready (loadable on-demand)
-        BackendStatus::Ready
-    } else {
-        BackendStatus::NotInstalled
-    };
+        let output_path = job_wav_path(&state.config, &job, seed);
 
-    // Get model versions if loaded
-    let musicgen_version = if state.models.backend() == Some(Backend::MusicGen) {
-        state.models.version().map(|s| s.to_string())
+        let write_start = Instant::now();
+        let written = write_output_wav(samples, sample_rate, job.output_sample_rate, &output_path);
+        let (samples, sample_rate) = match written {
+            Ok(written) => written,
+            Err(e) => {
+                notify_job_error(state, &job, &e);
+                return Err(e);
+            }
+        };
+        if sample_rate != backend.sample_rate() {
+            provenance = provenance.with_post_processing(format!(
+                "resample:{}->{}",
+                backend.sample_rate(),
+                sample_rate
+            ));
+        }
+        provenance = provenance.with_timing("write", write_start.elapsed().as_secs_f32());
+        write_provenance_comment(&output_path, &provenance);
+        let thumbnail_path = write_thumbnail(&state.config, &samples, sample_rate, &output_path);
+        let track = Track::new(
+            output_path.clone(),
+            job.prompt.clone(),
+            generated_duration,
+            seed,
+            model_version.clone(),
+            backend,
+            generation_time,
+        )
+        .with_tags(job.tags.clone())
+        .with_prompt_blend(job.prompt_blend.clone())
+        .with_inference_steps(job.inference_steps)
+        .with_output_sample_rate(sample_rate)
+        .with_reverb(reverb.as_ref())
+        .with_trimmed_duration(trim.as_ref(), actual_duration)
+        .with_generated_seed(generated_seed)
+        .with_derived_from_seed(job.derived_from_seed)
+        .with_provenance(provenance);
+        let tags = track.tags.clone();
+        let file_size_bytes = track.file_size_bytes;
+        let library_path = copy_to_library(state, &track, job.save_to_output);
+        state.metrics
+            .record_completed(backend, generation_time, actual_duration, file_size_bytes);
+        run_complete_hook(&state.config, &track);
+        if let Some(track) = track_to_cache(&state.config, &job, seed, track) {
+            if let Err(e) = save_sidecar(&track) {
+                eprintln!("{}", e);
+            }
+            write_now_playing(state, &track, || {
+                Ok(AudioStats::from_samples(&samples, sample_rate))
+            });
+            state.cache.put(track);
+        }
+
+        notifications.notify_job(
+            "generation_complete",
+            GenerationCompleteParams {
+                track_id: track_id.clone(),
+                path: output_path.to_string_lossy().to_string(),
+                duration_sec: actual_duration,
+                sample_rate,
+                prompt: DisplayPrompt::with_redaction(&job.prompt, state.config.redact_prompts)
+                    .to_string(),
+                seed: generated_seed,
+                generation_time_sec: generation_time,
+                model_version,
+                backend: backend.as_str().to_string(),
+                inference_steps: job.inference_steps,
+                file_size_bytes,
+                tags,
+                thumbnail_path,
+                library_path,
+                adjusted_params: job.adjusted_params.clone(),
+            },
+            &track_id,
+            owner,
+        );
+        Ok(())
+    }
+}
+
+/// Builds the provenance block for a generation from its dispatch parameters,
+/// requested trimming and reverb, and the current configuration. Timings are
+/// added by the caller.
+fn generation_provenance(
+    state: &ServerState,
+    params: &GenerateDispatchParams,
+    trim: Option<&SilenceTrim>,
+    reverb: Option<&Reverb>,
+) -> Provenance {
+    let execution_provider = state
+        .loaded_models(params.backend)
+        .and_then(LoadedModels::device_name)
+        .unwrap_or("unknown");
+    let sampling = match params.backend {
+        Backend::MusicGen => SamplingParams {
+            guidance_scale: Some(musicgen::DEFAULT_GUIDANCE_SCALE as f32),
+            top_k: Some(musicgen::DEFAULT_TOP_K),
+            ..SamplingParams::default()
+        },
+        Backend::AceStep => SamplingParams {
+            scheduler: params
+                .scheduler
+                .as_deref()
+                .and_then(SchedulerType::parse)
+                .map(|s| s.as_str().to_string()),
+            inference_steps: params.inference_steps,
+            guidance_scale: params.guidance_scale,
+            top_k: None,
+        },
+    };
+
+    let mut provenance = Provenance::new(execution_provider)
+        .with_sampling(sampling)
+        .with_prompt_enrichment(format!(
+            "sanitize:{}",
+            state.config.prompt_sanitization.as_str()
+        ));
+    if params.backend == Backend::AceStep {
+        provenance = provenance.with_post_processing("resample:44100->48000");
+    }
+    if let Some(trim) = trim {
+        provenance = provenance.with_post_processing(trim.label());
+    }
+    if let Some(reverb) = reverb {
+        provenance = provenance.with_post_processing(reverb.label());
+    }
+    if state.config.normalize_audio {
+        provenance = provenance
+            .with_post_processing(format!("loudness:{:.1} LUFS", state.config.target_lufs));
+    }
+    provenance
+}
+
+/// Trims silence from generated samples, then applies reverb and loudness
+/// normalization, recording how long each took in the provenance.
+fn post_process(
+    config: &DaemonConfig,
+    samples: &mut Vec<f32>,
+    sample_rate: u32,
+    trim: Option<&SilenceTrim>,
+    reverb: Option<&Reverb>,
+    mut provenance: Provenance,
+) -> Provenance {
+    if let Some(trim) = trim {
+        let trim_start = Instant::now();
+        trim.apply(samples, sample_rate);
+        provenance = provenance.with_timing("trim", trim_start.elapsed().as_secs_f32());
+    }
+    if let Some(reverb) = reverb {
+        let reverb_start = Instant::now();
+        reverb.apply(samples, sample_rate);
+        provenance = provenance.with_timing("reverb", reverb_start.elapsed().as_secs_f32());
+    }
+    if config.normalize_audio {
+        let normalize_start = Instant::now();
+        normalize_loudness(samples, config.target_lufs);
+        provenance = provenance.with_timing("normalize", normalize_start.elapsed().as_secs_f32());
+    }
+    provenance
+}
+
+/// Embeds the provenance as JSON in the WAV's INFO comment.
+///
+/// Failures are logged but never fail the generation.
+fn write_provenance_comment(output_path: &Path, provenance: &Provenance) {
+    let result = serde_json::to_string(provenance)
+        .map_err(|e| e.to_string())
+        .and_then(|json| append_info_comment(output_path, &json).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        eprintln!("Failed to embed provenance: {}", e);
+    }
+}
+
+/// Resamples generated audio to the requested output rate and writes the WAV.
+///
+/// The written file is checked with [`WavValidator`] and deleted if it is
+/// empty or silent, so a failed generation never reaches the cache.
+/// Returns the written samples and their rate, which is `native_rate` when no
+/// output rate was requested.
+fn write_output_wav(
+    samples: Vec<f32>,
+    native_rate: u32,
+    output_rate: Option<u32>,
+    path: &Path,
+) -> crate::error::Result<(Vec<f32>, u32)> {
+    let (samples, rate) = match output_rate {
+        Some(rate) if rate != native_rate => (resample(&samples, native_rate, rate)?, rate),
+        _ => (samples, native_rate),
+    };
+    write_wav(&samples, path, rate)?;
+    if let Err(e) = WavValidator::validate(path) {
+        std::fs::remove_file(path).ok();
+        return Err(e);
+    }
+    Ok((samples, rate))
+}
+
+/// Writes a spectrogram PNG next to the WAV if thumbnails are enabled.
+///
+/// Thumbnail failures are logged but never fail the generation.
+/// Returns the PNG path on success.
+fn write_thumbnail(
+    config: &DaemonConfig,
+    samples: &[f32],
+    sample_rate: u32,
+    wav_path: &Path,
+) -> Option<String> {
+    if !config.generate_thumbnails {
+        return None;
+    }
+
+    let png_path = wav_path.with_extension("png");
+    match write_spectrogram_png_with_options(
+        samples,
+        sample_rate,
+        &png_path,
+        config.spectrogram_options(),
+    ) {
+        Ok(()) => Some(png_path.to_string_lossy().to_string()),
+        Err(e) => {
+            eprintln!("Failed to write spectrogram thumbnail: {}", e);
+            None
+        }
+    }
+}
+
+/// Copies a track's WAV into the library directory if `save_to_output` is set.
+///
+/// The library keeps the cache's file name. A read-only instance skips the
+/// copy, since the instance owning the cache manages the library. Copy
+/// failures are logged but never fail the generation. Returns the copy's path
+/// on success.
+fn copy_to_library(state: &ServerState, track: &Track, save_to_output: bool) -> Option<String> {
+    if !save_to_output {
+        return None;
+    }
+    if let Err(e) = state.ensure_cache_writable("copy tracks into the library") {
+        eprintln!("{}", e);
+        return None;
+    }
+
+    let config = &state.config;
+    let library = config.effective_library_path();
+    let library_path = library.join(track.path.file_name()?);
+    match std::fs::create_dir_all(&library).and_then(|_| std::fs::copy(&track.path, &library_path))
+    {
+        Ok(_) => Some(library_path.to_string_lossy().to_string()),
+        Err(e) => {
+            eprintln!("Failed to copy {} to {}: {}", track.path.display(), library.display(), e);
+            None
+        }
+    }
+}
+
+/// Returns the path in the cache directory to write `job`'s track to, named
+/// by the `cache_filename` template.
+///
+/// Names containing the track ID are unique per track, so an existing file is
+/// a stale copy of the same track and is replaced; other names get a numeric
+/// suffix instead of overwriting another track. Names whose sidecar would
+/// replace the ratings or now-playing file are changed too.
+fn cache_wav_path(config: &DaemonConfig, job: &GenerationJob, seed: u64) -> PathBuf {
+    let template = config.cache_filename_template();
+    let values = TemplateValues {
+        prompt: &job.prompt,
+        redact_prompt: config.redact_prompts,
+        seed,
+        duration_sec: job.duration_sec,
+        backend: job.backend,
+        track_id: &job.track_id,
+        time: SystemTime::now(),
+    };
+    let path = avoid_reserved_names(&config.effective_cache_path().join(template.expand(&values)));
+    prepare_output_path(&path, template.uses_track_id()).unwrap_or(path)
+}
+
+/// Returns the path to write `job`'s track to: the `output_path` of its
+/// request if given, else a path in the cache directory.
+fn job_wav_path(config: &DaemonConfig, job: &GenerationJob, seed: u64) -> PathBuf {
+    match &job.output_path {
+        Some(path) => path.clone(),
+        None => cache_wav_path(config, job, seed),
+    }
+}
+
+/// Returns the track to put in the cache for `job`, if any.
+///
+/// Tracks written to the cache are returned as they are. A track written to
+/// an `output_path` is only cached with `also_cache`, as a copy in the cache
+/// directory so that cache pruning never deletes the caller's file. Copy
+/// failures are logged and leave the track uncached.
+fn track_to_cache(
+    config: &DaemonConfig,
+    job: &GenerationJob,
+    seed: u64,
+    mut track: Track,
+) -> Option<Track> {
+    if job.output_path.is_none() {
+        return Some(track);
+    }
+    if !job.also_cache {
+        return None;
+    }
+    let cache_path = cache_wav_path(config, job, seed);
+    match std::fs::copy(&track.path, &cache_path) {
+        Ok(_) => {
+            track.path = cache_path;
+            Some(track)
+        }
+        Err(e) => {
+            eprintln!("Failed to copy {} into the cache: {}", track.path.display(), e);
+            None
+        }
+    }
+}
+
+/// Returns the path of a previously written thumbnail for a cached track, if any.
+fn existing_thumbnail(wav_path: &Path) -> Option<String> {
+    let png_path = wav_path.with_extension("png");
+    png_path
+        .exists()
+        .then(|| png_path.to_string_lossy().to_string())
+}
+
+/// Returns the progress reporter for `job`.
+///
+/// The job's `notify_interval_ms` overrides the configured
+/// `min_notify_interval_ms`.
+fn progress_reporter(config: &DaemonConfig, job: &GenerationJob) -> ProgressReporter {
+    let interval_ms = job.notify_interval_ms.unwrap_or(config.min_notify_interval_ms);
+    ProgressReporter::new(
+        config.progress_interval_percent,
+        Duration::from_millis(interval_ms),
+    )
+}
+
+/// Returns the tracker estimating a job's remaining time from its progress
+/// callbacks.
+///
+/// ACE-Step jobs decode audio after the diffusion steps, which the estimate
+/// accounts for while the steps run.
+fn progress_tracker(job: &GenerationJob, total_steps: usize) -> Arc<Mutex<ProgressTracker>> {
+    let tracker = match job.backend {
+        Backend::MusicGen => ProgressTracker::new(job.duration_sec),
+        Backend::AceStep => {
+            ProgressTracker::for_steps(job.duration_sec, total_steps).with_phases(ACE_STEP_PHASES)
+        }
+    };
+    Arc::new(Mutex::new(tracker))
+}
+
+/// Records a token or step progress callback with `tracker` and returns the
+/// estimated seconds remaining.
+///
+/// The last diffusion step starts the decoding phase, whose progress the
+/// audio progress notifier records in milliseconds of audio.
+fn track_progress(
+    tracker: &Mutex<ProgressTracker>,
+    job: &GenerationJob,
+    current: usize,
+    total: usize,
+    now: Instant,
+) -> f32 {
+    let mut tracker = tracker.lock().unwrap();
+    tracker.record(current, now);
+    if job.backend == Backend::AceStep && current >= total {
+        tracker.begin_phase(GenerationPhase::Decode, job.duration_sec as usize * 1000, now);
+    }
+    tracker.eta_seconds()
+}
+
+/// Returns the callback sending ACE-Step `generation_progress` notifications
+/// by decoded audio, with `percent` being the decoded share of the duration.
+///
+/// Decoding follows the last diffusion step, so these notifications report
+/// all steps as done. They are not rate limited: decoding produces at most
+/// a few chunks.
+fn audio_progress_notifier(
+    notifications: &NotificationRouter,
+    track_id: &str,
+    owner: ConnectionId,
+    total_steps: usize,
+    tracker: &Arc<Mutex<ProgressTracker>>,
+) -> AudioProgress {
+    let notifications = notifications.clone();
+    let track_id = track_id.to_string();
+    let tracker = tracker.clone();
+    AudioProgress::new(move |decoded_sec, total_sec| {
+        let percent = AudioProgress::percent(decoded_sec, total_sec);
+        let eta_sec = {
+            let mut tracker = tracker.lock().unwrap();
+            tracker.record((decoded_sec * 1000.0) as usize, Instant::now());
+            tracker.eta_seconds()
+        };
+        notifications.notify_job(
+            "generation_progress",
+            GenerationProgressParams {
+                track_id: track_id.clone(),
+                percent,
+                tokens_generated: total_steps,
+                tokens_estimated: total_steps,
+                eta_sec,
+                current_step: Some(total_steps),
+                total_steps: Some(total_steps),
+                decoded_sec: Some(decoded_sec),
+                total_sec: Some(total_sec),
+            },
+            &track_id,
+            owner,
+        );
+    })
+}
+
+/// Writes now-playing metadata and a history entry for a completed track
+/// if enabled.
+///
+/// `audio` computes the track's statistics and is only called when the
+/// metadata is written. A read-only instance leaves both files to the
+/// instance owning the cache. Failures are logged but never fail the
+/// generation.
+fn write_now_playing(
+    state: &ServerState,
+    track: &Track,
+    audio: impl FnOnce() -> crate::error::Result<AudioStats>,
+) {
+    let config = &state.config;
+    if !config.now_playing || state.is_cache_read_only() {
+        return;
+    }
+
+    let result = audio().and_then(|audio| {
+        let entry = NowPlaying::new(track, audio, config.redact_prompts);
+        record_now_playing(&config.effective_now_playing_dir(), &entry, config.history_max_entries)
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to write now playing metadata: {}", e);
+    }
+}
+
+/// Handles the list_tracks method.
+///
+/// Returns cached tracks, newest first, along with their combined size on disk.
+/// An optional `tag` parameter restricts the result to tracks with that tag.
+fn handle_list_tracks(
+    params: serde_json::Value,
+    state: &ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: ListTracksParams = if params.is_null() {
+        ListTracksParams::default()
     } else {
-        None
+        parse_params(params)?
+    };
+
+    let mut tracks: Vec<Track> = match &params.tag {
+        Some(tag) => state.cache.find_by_tag(tag).into_iter().cloned().collect(),
+        None => state.cache.iter().cloned().collect(),
+    };
+    if let Some(min_rating) = params.min_rating {
+        tracks.retain(|track| track.rating.unwrap_or(0) >= min_rating);
+    }
+    tracks.sort_by_key(|track| std::cmp::Reverse(track.created_at));
+
+    let result = ListTracksResult {
+        count: tracks.len(),
+        total_size_bytes: state.cache.total_size_bytes(),
+        tracks: tracks
+            .iter()
+            .map(|track| track_to_value(track, state.config.redact_prompts))
+            .collect(),
+    };
+
+    Ok(serde_json::to_value(result).unwrap())
+}
+
+/// Serializes a track for a response.
+///
+/// With prompt redaction enabled the `prompt` field is replaced by
+/// `prompt_hash`, and any prompt blend is omitted.
+fn track_to_value(track: &Track, redact_prompts: bool) -> serde_json::Value {
+    let mut value = serde_json::to_value(track).unwrap();
+    if redact_prompts {
+        if let Some(object) = value.as_object_mut() {
+            object.remove("prompt");
+            object.remove("prompt_blend");
+            object.insert("prompt_hash".to_string(), prompt_hash(&track.prompt).into());
+        }
+    }
+    value
+}
+
+/// Handles the get_track method.
+fn handle_get_track(
+    params: serde_json::Value,
+    state: &mut ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: GetTrackParams = parse_params(params)?;
+
+    match state.cache.get(&params.track_id) {
+        Some(track) => Ok(track_to_value(track, state.config.redact_prompts)),
+        None => Err(JsonRpcError::invalid_params(format!(
+            "Track not found: {}",
+            params.track_id
+        ))),
+    }
+}
+
+/// Handles the rate_track method.
+///
+/// Stores the rating in the track's sidecar, where it protects the track from
+/// eviction, and under the track's prompt and seed in `ratings.json`.
+fn handle_rate_track(
+    params: serde_json::Value,
+    state: &mut ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: RateTrackParams = parse_params(params)?;
+    state.ensure_cache_writable("rate tracks")?;
+    if !(MIN_RATING..=MAX_RATING).contains(&params.rating) {
+        return Err(JsonRpcError::invalid_params(format!(
+            "rating must be between {} and {}, got {}",
+            MIN_RATING, MAX_RATING, params.rating
+        )));
+    }
+
+    let track = state
+        .cache
+        .set_rating(&params.track_id, params.rating)
+        .ok_or_else(|| {
+            JsonRpcError::invalid_params(format!("Track not found: {}", params.track_id))
+        })?
+        .clone();
+    if let Err(e) = save_sidecar(&track) {
+        eprintln!("{}", e);
+    }
+
+    state.ratings.record(&track.prompt, track.backend, track.seed, params.rating);
+    state
+        .ratings
+        .save(&state.config.effective_cache_path())
+        .map_err(|e| JsonRpcError::internal_error(e.to_string()))?;
+
+    Ok(serde_json::to_value(RateTrackResult {
+        track_id: track.track_id,
+        rating: params.rating,
+        seed: track.seed,
+    })
+    .unwrap())
+}
+
+/// Chooses the seed of a `prefer_rated` request that names no seed.
+///
+/// Reusing a rated seed would only replay its cached track, so this always
+/// picks a new random seed that was not rated down. The prompt's best-rated
+/// seed is returned as the new track's lineage: among equally rated seeds,
+/// the one whose cached track is nearest to `duration_sec`, then the most
+/// recently rated. A notice says which seed was derived from which.
+fn preferred_seed(
+    ratings: &Ratings,
+    cache: &TrackCache,
+    prompt: &str,
+    duration_sec: u32,
+    backend: Backend,
+) -> (u64, Option<u64>, String) {
+    let duration_gap = |seed: u64| {
+        cache
+            .iter()
+            .filter(|track| track.backend == backend && track.seed == seed)
+            .filter(|track| track.prompt == prompt)
+            .map(|track| (track.duration_sec - duration_sec as f32).abs())
+            .fold(f32::INFINITY, f32::min)
     };
+    // min_by keeps the first of equal minima, and best seeds are newest first
+    let parent = ratings
+        .best_seeds(prompt, backend)
+        .into_iter()
+        .min_by(|&a, &b| duration_gap(a).total_cmp(&duration_gap(b)));
+
+    let seed = std::iter::repeat_with(rand::random)
+        .find(|&seed| Some(seed) != parent && !ratings.is_rated_down(prompt, backend, seed))
+        .unwrap();
+    let notice = match parent {
+        Some(parent) => format!(
+            "Using new seed {} derived from seed {}, the best rated for this prompt",
+            seed, parent
+        ),
+        None => "No well-rated seed for this prompt, using a random seed".to_string(),
+    };
+    (seed, parent, notice)
+}
 
-    let ace_step_version = if state.models.backend() == Some(Backend::AceStep) {
-        state.models.version().map(|s| s.to_string())
+/// Handles the get_now_playing method.
+///
+/// Reads the same `now_playing.json` that file watchers see.
+fn handle_get_now_playing(state: &ServerState) -> Result<serde_json::Value, JsonRpcError> {
+    let now_playing = read_now_playing(&state.config.effective_now_playing_dir())
+        .map_err(|e| JsonRpcError::internal_error(e.to_string()))?;
+    Ok(serde_json::to_value(GetNowPlayingResult { now_playing }).unwrap())
+}
+
+/// Handles the get_history method.
+///
+/// Returns up to `limit` entries of `history.jsonl`, newest first.
+fn handle_get_history(
+    params: serde_json::Value,
+    state: &ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: GetHistoryParams = if params.is_null() {
+        GetHistoryParams::default()
     } else {
-        None
+        parse_params(params)?
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    let entries = read_history(&state.config.effective_now_playing_dir(), limit)
+        .map_err(|e| JsonRpcError::internal_error(e.to_string()))?;
+    Ok(serde_json::to_value(GetHistoryResult { entries }).unwrap())
+}
+
+/// Handles the compare_tracks method.
+///
+/// Returns the provenance fields that differ between two cached tracks and
+/// statistics of both WAV files with their difference (b - a).
+fn handle_compare_tracks(
+    params: serde_json::Value,
+    state: &mut ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: CompareTracksParams = parse_params(params)?;
+
+    let find_track = |track_id: &str| {
+        state
+            .cache
+            .peek(track_id)
+            .ok_or_else(|| JsonRpcError::invalid_params(format!("Track not found: {}", track_id)))
+    };
+    let track_a = find_track(&params.track_id_a)?;
+    let track_b = find_track(&params.track_id_b)?;
+
+    let audio_stats = |track: &Track| {
+        read_wav(&track.path)
+            .map(|(samples, sample_rate)| AudioStats::from_samples(&samples, sample_rate))
+            .map_err(|e| JsonRpcError::internal_error(e.to_string()))
+    };
+    let audio_a = audio_stats(track_a)?;
+    let audio_b = audio_stats(track_b)?;
+
+    Ok(serde_json::to_value(CompareTracksResult {
+        track_id_a: params.track_id_a,
+        track_id_b: params.track_id_b,
+        provenance_diff: diff_provenance(track_a.provenance.as_ref(), track_b.provenance.as_ref()),
+        audio_a,
+        audio_b,
+        audio_delta: audio_a.delta(&audio_b),
+    })
+    .unwrap())
+}
+
+/// Handles the export_manifest method.
+///
+/// Returns the recipe for regenerating a cached track, and writes it to
+/// `path` if given. The fingerprint is of the models installed now.
+fn handle_export_manifest(
+    params: serde_json::Value,
+    state: &ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: ExportManifestParams = parse_params(params)?;
+
+    let track = state.cache.peek(&params.track_id).ok_or_else(|| {
+        JsonRpcError::invalid_params(format!("Track not found: {}", params.track_id))
+    })?;
+    let fingerprint = get_backend_fingerprint(track.backend, &state.config);
+    let manifest = Manifest::from_track(track, fingerprint);
+
+    // The file is checked like a generate output_path, root restriction included
+    let path = params
+        .path
+        .as_deref()
+        .map(|path| resolve_requested_output(path, state.config.restrict_output_root.as_deref()))
+        .transpose()
+        .map_err(JsonRpcError::from)?;
+    if let Some(path) = &path {
+        let json = serde_json::to_string_pretty(&manifest).unwrap();
+        std::fs::write(path, json + "\n").map_err(|e| {
+            JsonRpcError::internal_error(format!(
+                "Failed to write manifest to {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+    }
+    Ok(serde_json::to_value(ExportManifestResult {
+        manifest,
+        path: path.map(|path| path.to_string_lossy().to_string()),
+    })
+    .unwrap())
+}
+
+/// Handles the generate_from_manifest method.
+///
+/// Generates with the manifest's parameters exactly as `generate` would.
+/// Unknown manifest fields and differences from the exporter's models are
+/// returned as warnings; the track ID matches the exported one only when the
+/// model versions match.
+fn handle_generate_from_manifest(
+    params: serde_json::Value,
+    state: &mut ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: GenerateFromManifestParams = parse_params(params)?;
+    let (manifest, mut warnings) =
+        Manifest::from_value(params.manifest).map_err(JsonRpcError::invalid_params)?;
+    warnings.extend(manifest.compatibility_warnings(
+        get_backend_version(manifest.backend, &state.config).as_deref(),
+        get_backend_fingerprint(manifest.backend, &state.config).as_deref(),
+    ));
+    for warning in &warnings {
+        eprintln!("Manifest: {}", warning);
+    }
+
+    let mut generate = GenerateParams::from_manifest(&manifest);
+    generate.priority = params.priority;
+    start_generation(generate, warnings, state)
+}
+
+/// Handles the get_config method.
+fn handle_get_config(state: &ServerState) -> Result<serde_json::Value, JsonRpcError> {
+    Ok(config_to_value(&state.config))
+}
+
+/// Handles the set_config method.
+///
+/// Applies whitelisted settings immediately and returns the full effective config.
+/// Settings that require a model reload or restart are rejected.
+fn handle_set_config(
+    params: serde_json::Value,
+    state: &mut ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let updates = params
+        .as_object()
+        .ok_or_else(|| JsonRpcError::invalid_params("Invalid params: expected an object"))?;
+
+    let config = state
+        .config
+        .with_runtime_updates(updates)
+        .map_err(JsonRpcError::invalid_params)?;
+
+    let evicted = state.apply_config(config);
+    if !evicted.is_empty() {
+        eprintln!("Evicted {} cached tracks to fit the new cache budget", evicted.len());
+    }
+
+    Ok(config_to_value(&state.config))
+}
+
+/// Handles the set_default_backend method.
+///
+/// Changes the backend used when a generate request does not name one. The
+/// backend's models must be installed. If the daemon was started with a config
+/// file, the setting is written back to it so it survives a restart; nothing is
+/// applied if writing fails.
+fn handle_set_default_backend(
+    params: serde_json::Value,
+    state: &mut ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: SetDefaultBackendParams = parse_params(params)?;
+
+    let backend =
+        Backend::parse(&params.backend).ok_or_else(|| JsonRpcError::invalid_backend(&params.backend))?;
+
+    let model_dir = match backend {
+        Backend::MusicGen => state.config.effective_model_path(),
+        Backend::AceStep => state.config.effective_ace_step_model_path(),
+    };
+    if !check_backend_available(backend, &model_dir, state.config.tokenizer_override(backend)) {
+        return Err(JsonRpcError::backend_not_installed(&backend));
+    }
+
+    let persisted = state
+        .set_default_backend(backend)
+        .map_err(JsonRpcError::internal_error)?;
+
+    Ok(serde_json::to_value(SetDefaultBackendResult {
+        default_backend: backend.as_str().to_string(),
+        persisted,
+    })
+    .unwrap())
+}
+
+/// Handles the subscribe method.
+///
+/// Restricts the notifications delivered to the calling connection. Terminal
+/// events for the connection's own jobs are always delivered.
+fn handle_subscribe(
+    params: serde_json::Value,
+    state: &mut ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: SubscribeParams = parse_params(params)?;
+
+    if let Some(error) = validate_notification_methods(&params.methods) {
+        return Err(JsonRpcError::invalid_params(error));
+    }
+
+    let filter = NotificationFilter {
+        methods: Some(params.methods),
+        track_ids: params.track_ids,
+    };
+    state.notifications.subscribe(state.connection_id, filter.clone());
+
+    Ok(serde_json::to_value(SubscribeResult {
+        methods: filter.methods,
+        track_ids: filter.track_ids,
+    })
+    .unwrap())
+}
+
+/// Handles the unsubscribe method.
+///
+/// Resets the calling connection to receive all notifications.
+fn handle_unsubscribe(state: &mut ServerState) -> Result<serde_json::Value, JsonRpcError> {
+    state.notifications.unsubscribe(state.connection_id);
+
+    Ok(serde_json::to_value(SubscribeResult {
+        methods: None,
+        track_ids: None,
+    })
+    .unwrap())
+}
+
+/// Serializes the config, reporting the default backend the same way as get_backends.
+fn config_to_value(config: &DaemonConfig) -> serde_json::Value {
+    let mut value = serde_json::to_value(config).unwrap();
+    value["default_backend"] = serde_json::json!(config.default_backend.as_str());
+    value
+}
+
+/// Handles the get_backends method.
+///
+/// Reports the tracked lifecycle status of each backend. Model files are only
+/// checked at startup (see [`ServerState::detect_installed_backends`]); after
+/// that, downloads and loads keep the status current.
+fn handle_get_backends(state: &ServerState) -> Result<serde_json::Value, JsonRpcError> {
+    let musicgen_status = state.get_backend_status(Backend::MusicGen);
+    let ace_step_status = state.get_backend_status(Backend::AceStep);
+
+    // Get model versions if loaded
+    let loaded_version = |backend| {
+        state
+            .loaded_models(backend)
+            .and_then(LoadedModels::version)
+            .map(str::to_string)
     };
+    let musicgen_version = loaded_version(Backend::MusicGen);
+    let ace_step_version = loaded_version(Backend::AceStep);
 
+    let margin = state.config.memory_safety_margin_bytes;
+    let dcae_sessions = state.config.effective_dcae_parallelism();
     let result = GetBackendsResult {
         backends: vec![
-            BackendInfo::new(Backend::MusicGen, musicgen_status, musicgen_version),
-            BackendInfo::new(Backend::AceStep, ace_step_status, ace_step_version),
+            BackendInfo::new(Backend::MusicGen, musicgen_status, musicgen_version)
+                .with_recommended_max_duration(recommended_max_duration(
+                    Backend::MusicGen,
+                    state.loaded_models(Backend::MusicGen).is_some(),
+                    dcae_sessions,
+                    margin,
+                ))
+                .with_active_jobs(state.active_jobs(Backend::MusicGen))
+                .with_error_detail(
+                    state.backend_status.error_detail(Backend::MusicGen).map(str::to_string),
+                ),
+            BackendInfo::new(Backend::AceStep, ace_step_status, ace_step_version)
+                .with_recommended_max_duration(recommended_max_duration(
+                    Backend::AceStep,
+                    state.loaded_models(Backend::AceStep).is_some(),
+                    dcae_sessions,
+                    margin,
+                ))
+                .with_active_jobs(state.active_jobs(Backend::AceStep))
+                .with_error_detail(
+                    state.backend_status.error_detail(Backend::AceStep).map(str::to_string),
+                ),
         ],
         default_backend: state.config.default_backend.as_str().to_string(),
     };
@@ -509,8 +1849,7 @@ fn handle_download_backend(
     state: &mut ServerState,
 ) -> Result<serde_json::Value, JsonRpcError> {
     // Parse and validate parameters
-    let params: DownloadBackendParams = serde_json::from_value(params)
-        .map_err(|e| JsonRpcError::invalid_params(format!("Invalid params: {}", e)))?;
+    let params: DownloadBackendParams = parse_params(params)?;
 
     let backend = params.validate()?;
 
@@ -530,7 +1869,7 @@ fn handle_download_backend(
         Backend::AceStep => state.config.effective_ace_step_model_path(),
     };
 
-    if check_backend_available(backend, &model_dir) {
+    if check_backend_available(backend, &model_dir, state.config.tokenizer_override(backend)) {
         return Ok(serde_json::to_value(DownloadBackendResult {
             backend: backend.as_str().to_string(),
             status: "already_installed".to_string(),
@@ -540,64 +1879,252 @@ fn handle_download_backend(
     }
 
     // Update status to downloading
-    state.backend_status.set(backend, BackendStatus::Downloading);
-
-    // Create progress callback that sends notifications
-    let on_progress = Box::new(move |file_name: &str, bytes_downloaded: u64, bytes_total: u64, files_completed: usize, files_total: usize| {
-        send_notification(
-            "download_progress",
-            DownloadProgressParams {
-                file_name: file_name.to_string(),
-                bytes_downloaded,
-                bytes_total,
-                files_completed,
-                files_total,
-            },
-        );
-    });
+    state.set_backend_status(backend, BackendStatus::Downloading);
 
     // Perform download
-    match download_backend_with_progress(backend, &model_dir, Some(on_progress)) {
-        Ok(()) => {
-            state.backend_status.set(backend, BackendStatus::Ready);
+    match download_models(state, backend, &model_dir) {
+        Ok(summary) => {
+            state.set_backend_status(backend, BackendStatus::Ready);
+            // Only new files can make loaded models and cached tracks stale
+            if summary.files_downloaded > 0 {
+                state.mark_models_updated(backend);
+            }
             Ok(serde_json::to_value(DownloadBackendResult {
                 backend: backend.as_str().to_string(),
                 status: "complete".to_string(),
-                files_downloaded: match backend {
-                    Backend::MusicGen => 6, // Number of MusicGen files
-                    Backend::AceStep => 7,   // Number of ACE-Step files
-                },
+                files_downloaded: summary.files_downloaded,
             })
             .unwrap())
         }
         Err(e) => {
-            state.backend_status.set(backend, BackendStatus::Error);
-            Err(JsonRpcError::model_download_failed(e.to_string()))
+            state.set_backend_error(backend, e.message.clone());
+            Err(JsonRpcError::from(e))
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    fn test_config() -> crate::config::DaemonConfig {
-        crate::config::DaemonConfig::default()
-    }
-
-    #[test]
-    fn handle_ping() {
-        let result = super::handle_ping();
-        assert!(result.is_ok());
-        let value = result.unwrap();
-        assert_eq!(value["status"], "ok");
+/// Downloads the missing model files of `backend` into `model_dir`.
+///
+/// Sends rate-limited `download_progress` notifications while files download
+/// and a `download_complete` notification once any were downloaded.
+fn download_models(
+    state: &ServerState,
+    backend: Backend,
+    model_dir: &Path,
+) -> Result<DownloadSummary, DaemonError> {
+    let started = Instant::now();
+    let summary =
+        download_backend_with_progress(backend, model_dir, Some(download_progress_callback(state)))?;
+    if summary.files_downloaded > 0 {
+        state.notifications.notify(
+            "download_complete",
+            DownloadCompleteParams {
+                backend: backend.as_str().to_string(),
+                total_files: summary.files_downloaded,
+                total_bytes: summary.bytes_downloaded,
+                duration_sec: started.elapsed().as_secs_f32(),
+            },
+        );
     }
+    Ok(summary)
+}
 
-    #[test]
-    fn handle_unknown_method() {
-        let mut state = ServerState::new(test_config());
-        let result = handle_request("nonexistent", serde_json::Value::Null, &mut state);
-        assert!(result.is_err());
+/// Returns a download progress callback that sends rate-limited
+/// `download_progress` notifications.
+fn download_progress_callback(state: &ServerState) -> DownloadProgressCallback {
+    let notifications = state.notifications.clone();
+    let throttle = std::sync::Mutex::new(DownloadProgressThrottle::new(Duration::from_millis(
+        state.config.min_notify_interval_ms,
+    )));
+    Box::new(move |file_name: &str, bytes_downloaded: u64, bytes_total: u64, files_completed: usize, files_total: usize| {
+        let mut throttle = throttle.lock().unwrap_or_else(|e| e.into_inner());
+        if !throttle.should_send(file_name, bytes_downloaded, bytes_total, Instant::now()) {
+            return;
+        }
+        notifications.notify(
+            "download_progress",
+            DownloadProgressParams {
+                file_name: file_name.to_string(),
+                bytes_downloaded,
+                bytes_total,
+                files_completed,
+                files_total,
+            },
+        );
+    })
+}
+
+/// Handles the purge_backend method.
+///
+/// Removes every cached track generated by the backend and deletes its files.
+fn handle_purge_backend(
+    params: serde_json::Value,
+    state: &mut ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: PurgeBackendParams = parse_params(params)?;
+    let backend = params.validate()?;
+    state.ensure_cache_writable("purge cached tracks")?;
+
+    let removed = state.purge_backend(backend);
+    Ok(serde_json::to_value(PurgeBackendResult {
+        backend: backend.as_str().to_string(),
+        tracks_removed: removed.len(),
+        bytes_freed: removed.iter().map(|track| track.file_size_bytes).sum(),
+    })
+    .unwrap())
+}
+
+/// Handles the prune_orphans method.
+///
+/// Deletes WAV files in the cache directory that are no longer in the cache,
+/// such as those of evicted tracks.
+fn handle_prune_orphans(
+    params: serde_json::Value,
+    state: &ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: PruneOrphansParams = if params.is_null() {
+        PruneOrphansParams::default()
+    } else {
+        parse_params(params)?
+    };
+
+    let report = state.prune_orphans(params.dry_run).map_err(|e| {
+        if e.code == ErrorCode::CacheReadOnly {
+            JsonRpcError::from(e)
+        } else {
+            JsonRpcError::internal_error(e.to_string())
+        }
+    })?;
+    Ok(serde_json::to_value(PruneOrphansResult {
+        files_removed: report.files.len(),
+        bytes_freed: report.bytes_freed,
+        files: report
+            .files
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect(),
+        dry_run: params.dry_run,
+    })
+    .unwrap())
+}
+
+/// Handles the get_cache_stats method.
+///
+/// Reports the number, duration and size of the cached tracks, overall and
+/// per backend, and optionally the disk usage of the cache directory.
+fn handle_get_cache_stats(
+    params: serde_json::Value,
+    state: &mut ServerState,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let params: CacheStatsParams = if params.is_null() {
+        CacheStatsParams::default()
+    } else {
+        parse_params(params)?
+    };
+
+    let disk = if params.scan_disk || params.refresh {
+        let (usage, age) = state
+            .cache_disk_usage(params.refresh)
+            .map_err(|e| JsonRpcError::internal_error(e.to_string()))?;
+        Some(CacheDiskUsage {
+            file_count: usage.file_count,
+            total_bytes: usage.total_bytes,
+            orphan_count: usage.orphan_count,
+            orphan_bytes: usage.orphan_bytes,
+            age_sec: age.as_secs_f32(),
+        })
+    } else {
+        None
+    };
+
+    let cache = &state.cache;
+    let unix_secs = |time: SystemTime| {
+        time.duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs())
+    };
+    let created_at = cache.created_at_range();
+    let by_backend: Vec<BackendCacheStats> = [Backend::MusicGen, Backend::AceStep]
+        .into_iter()
+        .map(|backend| BackendCacheStats {
+            backend: backend.as_str().to_string(),
+            track_count: cache.count_by_backend(backend),
+            total_duration_sec: cache.total_duration_by_backend(backend),
+            total_size_bytes: cache.total_size_bytes_by_backend(backend),
+        })
+        .collect();
+
+    Ok(serde_json::to_value(CacheStatsResult {
+        track_count: cache.len(),
+        total_duration_sec: by_backend.iter().map(|stats| stats.total_duration_sec).sum(),
+        total_size_bytes: cache.total_size_bytes(),
+        max_bytes: cache.max_bytes(),
+        oldest_created_at: created_at.map(|(oldest, _)| unix_secs(oldest)),
+        newest_created_at: created_at.map(|(_, newest)| unix_secs(newest)),
+        cache_dir: state.config.effective_cache_path().display().to_string(),
+        by_backend,
+        disk,
+    })
+    .unwrap())
+}
+
+/// Handles the get_queue method.
+///
+/// Jobs are listed in their effective processing order under the configured
+/// queue policy, which may differ from submission order.
+fn handle_get_queue(state: &ServerState) -> Result<serde_json::Value, JsonRpcError> {
+    let now = SystemTime::now();
+    let current_backend = state.models.backend();
+    let jobs = state
+        .queue
+        .processing_order(current_backend, now)
+        .into_iter()
+        .map(|job| QueuedJobInfo {
+            job_id: job.job_id.clone(),
+            track_id: job.track_id.clone(),
+            prompt: DisplayPrompt::with_redaction(&job.prompt, state.config.redact_prompts)
+                .to_string(),
+            backend: job.backend.as_str().to_string(),
+            priority: job.priority,
+            duration_sec: job.duration_sec,
+            waiting_sec: now
+                .duration_since(job.created_at)
+                .unwrap_or_default()
+                .as_secs_f32(),
+        })
+        .collect();
+
+    Ok(serde_json::to_value(GetQueueResult {
+        policy: state.queue.policy().as_str().to_string(),
+        current_backend: current_backend.map(|backend| backend.as_str().to_string()),
+        jobs,
+    })
+    .unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::notifications::RecordingSink;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::types::compute_track_id;
+
+    fn test_config() -> crate::config::DaemonConfig {
+        crate::config::DaemonConfig::default()
+    }
+
+    #[test]
+    fn handle_ping() {
+        let result = super::handle_ping();
+        assert!(result.is_ok());
+        let value = result.unwrap();
+        assert_eq!(value["status"], "ok");
+    }
+
+    #[test]
+    fn handle_unknown_method() {
+        let mut state = ServerState::new(test_config());
+        let result = handle_request("nonexistent", serde_json::Value::Null, &mut state);
+        assert!(result.is_err());
         let err = result.unwrap_err();
         assert_eq!(err.code, -32601);
     }
@@ -610,6 +2137,121 @@ mod tests {
         assert!(result.is_err());
         let err = result.unwrap_err();
         assert_eq!(err.code, -32602); // Invalid params
+        assert!(err.message.contains("prompt_tags"), "{}", err.message);
+
+        // Both prompt and prompt_tags is a conflict, not a choice
+        let params = serde_json::json!({
+            "prompt": "lofi beats",
+            "prompt_tags": { "genre": ["lofi"] },
+        });
+        let err = handle_request("generate", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32602);
+        assert!(err.message.contains("prompt and prompt_tags"), "{}", err.message);
+    }
+
+    #[test]
+    fn invalid_params_name_the_field() {
+        let mut state = ServerState::new(test_config());
+        let params = serde_json::json!({ "prompt": "lofi beats", "duration_sec": "thirty" });
+        let err = handle_request("generate", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32602);
+        assert!(err.message.contains("duration_sec"), "{}", err.message);
+        assert!(err.message.contains("u32"), "{}", err.message);
+    }
+
+    #[test]
+    fn generate_rejects_output_paths_outside_the_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("renders");
+        std::fs::create_dir(&root).unwrap();
+        let mut config = test_config();
+        config.restrict_output_root = Some(root);
+        let mut state = ServerState::new(config);
+
+        let params = serde_json::json!({
+            "prompt": "lofi beats",
+            "duration_sec": 10,
+            "output_path": "../escaped.wav",
+        });
+        let err = handle_request("generate", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32017);
+        assert_eq!(err.data.unwrap().error_code, "OUTPUT_PATH_INVALID");
+        assert!(state.queue.is_empty());
+        assert!(!dir.path().join("escaped.wav").exists());
+    }
+
+    #[test]
+    fn track_to_cache_copies_output_path_tracks_with_also_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let mut config = test_config();
+        config.cache_path = Some(cache_dir.clone());
+        let output = dir.path().join("render.wav");
+        write_wav(&vec![0.1f32; 3200], &output, 32000).unwrap();
+        let track = Track::new(
+            output.clone(),
+            "lofi beats".to_string(),
+            0.1,
+            42,
+            "test".to_string(),
+            Backend::MusicGen,
+            1.0,
+        );
+        let job = GenerationJob::new("lofi beats".to_string(), 10, Some(42), JobPriority::Normal, "test");
+
+        let cached = track_to_cache(&config, &job, 42, track.clone()).unwrap();
+        assert_eq!(cached.path, track.path);
+
+        let job = job.with_output_path(Some(output.clone()), false);
+        assert!(track_to_cache(&config, &job, 42, track.clone()).is_none());
+        assert!(!cache_dir.exists());
+
+        let job = job.with_output_path(Some(output.clone()), true);
+        let cached = track_to_cache(&config, &job, 42, track).unwrap();
+        assert!(cached.path.starts_with(&cache_dir));
+        assert!(cached.path.exists() && output.exists());
+    }
+
+    #[test]
+    fn every_method_is_dispatched() {
+        // prune_orphans deletes files, so keep it out of the real cache directory
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.cache_path = Some(cache_dir.path().to_path_buf());
+        let mut state = ServerState::new(config);
+        for method in METHODS.iter().filter(|m| **m != "shutdown") {
+            if let Err(err) = handle_request(method, serde_json::Value::Null, &mut state) {
+                assert_ne!(err.code, -32601, "{} is not dispatched", method);
+            }
+        }
+    }
+
+    #[test]
+    fn get_schema_returns_the_schema_document() {
+        let mut state = ServerState::new(test_config());
+        let result = handle_request("get_schema", serde_json::Value::Null, &mut state).unwrap();
+        assert_eq!(result, schema_document());
+    }
+
+    #[test]
+    fn handle_generate_param_strictness() {
+        let params = serde_json::json!({
+            "prompt": "", "backend": "ace_step", "guidance_scale": 35.0, "duration_sec": 500
+        });
+
+        // Strict mode rejects the first out-of-range parameter as before
+        let mut state = ServerState::new(test_config());
+        let mut strict = params.clone();
+        strict["prompt"] = "rain".into();
+        let err = handle_request("generate", strict, &mut state).unwrap_err();
+        assert_eq!(err.code, -32005);
+
+        // Clamp mode fixes the numbers but never an empty prompt
+        let mut config = test_config();
+        config.param_strictness = ParamStrictness::Clamp;
+        let mut state = ServerState::new(config);
+        let err = handle_request("generate", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32006);
     }
 
     #[test]
@@ -622,6 +2264,1201 @@ mod tests {
         assert_eq!(err.code, -32006); // Invalid prompt
     }
 
+    #[test]
+    fn handle_generate_rejects_control_characters_when_configured() {
+        let mut config = test_config();
+        config.prompt_sanitization = crate::config::PromptSanitization::Reject;
+        let mut state = ServerState::new(config);
+        let params = serde_json::json!({ "prompt": "lofi\u{0}beats" });
+        let err = handle_request("generate", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32006);
+    }
+
+    #[test]
+    fn handle_list_tracks_empty() {
+        let mut state = ServerState::new(test_config());
+        let value = handle_request("list_tracks", serde_json::Value::Null, &mut state).unwrap();
+        assert_eq!(value["count"], 0);
+        assert_eq!(value["total_size_bytes"], 0);
+    }
+
+    #[test]
+    fn handle_list_tracks_filters_by_tag() {
+        let mut state = ServerState::new(test_config());
+        let track = Track::new(
+            std::path::PathBuf::from("/nonexistent/a.wav"),
+            "rainy night".to_string(),
+            30.0,
+            1,
+            "v1".to_string(),
+            Backend::MusicGen,
+            1.0,
+        )
+        .with_tags(vec!["LoFi".to_string()]);
+        state.cache.put(track);
+
+        let params = serde_json::json!({ "tag": "lofi" });
+        let value = handle_request("list_tracks", params, &mut state).unwrap();
+        assert_eq!(value["count"], 1);
+        assert_eq!(value["tracks"][0]["tags"][0], "lofi");
+
+        let params = serde_json::json!({ "tag": "jazz" });
+        let value = handle_request("list_tracks", params, &mut state).unwrap();
+        assert_eq!(value["count"], 0);
+    }
+
+    #[test]
+    fn handle_rate_track_persists_ratings() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.cache_path = Some(dir.path().to_path_buf());
+        let mut state = ServerState::new(config);
+        for seed in [1, 2] {
+            let mut track = Track::new(
+                dir.path().join(format!("{}.wav", seed)),
+                "rainy night".to_string(),
+                30.0,
+                seed,
+                "v1".to_string(),
+                Backend::MusicGen,
+                1.0,
+            );
+            track.track_id = format!("track{}", seed);
+            state.cache.put(track);
+        }
+
+        for rating in [2, -2] {
+            let params = serde_json::json!({ "track_id": "track1", "rating": rating });
+            let err = handle_request("rate_track", params, &mut state).unwrap_err();
+            assert_eq!(err.code, -32602);
+        }
+        let params = serde_json::json!({ "track_id": "missing", "rating": 1 });
+        let err = handle_request("rate_track", params, &mut state).unwrap_err();
+        assert!(err.message.contains("Track not found"));
+
+        let params = serde_json::json!({ "track_id": "track1", "rating": 1 });
+        let value = handle_request("rate_track", params, &mut state).unwrap();
+        assert_eq!(value["seed"], 1);
+        let params = serde_json::json!({ "track_id": "track2", "rating": -1 });
+        handle_request("rate_track", params, &mut state).unwrap();
+
+        let ratings = Ratings::load(dir.path()).unwrap();
+        assert_eq!(ratings, state.ratings);
+        assert_eq!(ratings.best_seed("rainy night", Backend::MusicGen), Some(1));
+        assert!(ratings.is_rated_down("rainy night", Backend::MusicGen, 2));
+        let (seed, parent, notice) =
+            preferred_seed(&ratings, &state.cache, "rainy night", 30, Backend::MusicGen);
+        assert!(seed != 1 && seed != 2);
+        assert_eq!(parent, Some(1));
+        assert!(notice.contains("derived from seed 1"), "{}", notice);
+        let (_, parent, _) =
+            preferred_seed(&ratings, &state.cache, "sunny day", 30, Backend::MusicGen);
+        assert_eq!(parent, None);
+
+        let params = serde_json::json!({ "min_rating": 0 });
+        let value = handle_request("list_tracks", params, &mut state).unwrap();
+        assert_eq!(value["count"], 1);
+        assert_eq!(value["tracks"][0]["rating"], 1);
+        let params = serde_json::json!({ "min_rating": -1 });
+        let value = handle_request("list_tracks", params, &mut state).unwrap();
+        assert_eq!(value["count"], 2);
+    }
+
+    #[test]
+    fn preferred_seed_derives_from_the_nearest_best_rated_track() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = TrackCache::new();
+        let mut ratings = Ratings::default();
+        for (seed, duration_sec) in [(1, 10.0), (2, 60.0)] {
+            cache.put(Track::new(
+                dir.path().join(format!("{}.wav", seed)),
+                "rainy night".to_string(),
+                duration_sec,
+                seed,
+                "v1".to_string(),
+                Backend::MusicGen,
+                1.0,
+            ));
+            ratings.record("rainy night", Backend::MusicGen, seed, 1);
+        }
+
+        // Seed 2 is rated more recently, but seed 1 is nearer a 15 second request
+        let (_, parent, _) =
+            preferred_seed(&ratings, &cache, "rainy night", 15, Backend::MusicGen);
+        assert_eq!(parent, Some(1));
+        let (_, parent, _) =
+            preferred_seed(&ratings, &cache, "rainy night", 50, Backend::MusicGen);
+        assert_eq!(parent, Some(2));
+
+        // Without cached tracks the most recent rating wins
+        let (seed, parent, _) =
+            preferred_seed(&ratings, &TrackCache::new(), "rainy night", 15, Backend::MusicGen);
+        assert_eq!(parent, Some(2));
+        assert!(seed != 1 && seed != 2);
+    }
+
+    #[test]
+    fn track_responses_redact_prompts() {
+        let mut config = test_config();
+        config.redact_prompts = true;
+        let mut state = ServerState::new(config);
+        let prompt = "music for writing my resignation letter";
+        let track = Track::new(
+            std::path::PathBuf::from("/nonexistent/a.wav"),
+            prompt.to_string(),
+            30.0,
+            1,
+            "v1".to_string(),
+            Backend::MusicGen,
+            1.0,
+        )
+        .with_prompt_blend(Some(vec![("my resignation letter".to_string(), 1.0)]));
+        // Track IDs still hash the full prompt, so cache lookups are unaffected
+        let track_id = compute_track_id(Backend::MusicGen, prompt, 1, 30.0, "v1");
+        assert_eq!(track.track_id, track_id);
+        state.cache.put(track);
+
+        let value = handle_request("list_tracks", serde_json::Value::Null, &mut state).unwrap();
+        let listed = &value["tracks"][0];
+        assert!(listed.get("prompt").is_none());
+        assert_eq!(listed["prompt_hash"], prompt_hash(prompt));
+        assert!(!value.to_string().contains("resignation"));
+
+        let params = serde_json::json!({ "track_id": track_id });
+        let value = handle_request("get_track", params.clone(), &mut state).unwrap();
+        assert_eq!(value["prompt_hash"], prompt_hash(prompt));
+
+        state.config.redact_prompts = false;
+        let value = handle_request("get_track", params, &mut state).unwrap();
+        assert_eq!(value["prompt"], prompt);
+        assert!(value.get("prompt_hash").is_none());
+    }
+
+    #[test]
+    fn request_notify_interval_overrides_config() {
+        let mut config = test_config();
+        config.min_notify_interval_ms = 10_000;
+        let job = GenerationJob::new("rain".to_string(), 30, Some(1), JobPriority::Normal, "v1");
+        let start = Instant::now();
+        let later = start + Duration::from_millis(500);
+
+        // The configured interval suppresses the second notification
+        let mut reporter = progress_reporter(&config, &job);
+        assert_eq!(reporter.report(10, 100, start), Some(10));
+        assert_eq!(reporter.report(20, 100, later), None);
+
+        // A per-request interval takes precedence
+        let job = job.with_notify_interval_ms(Some(100));
+        let mut reporter = progress_reporter(&config, &job);
+        assert_eq!(reporter.report(10, 100, start), Some(10));
+        assert_eq!(reporter.report(20, 100, later), Some(20));
+    }
+
+    #[test]
+    fn handle_get_queue_lists_processing_order() {
+        let mut config = test_config();
+        config.queue_policy = crate::generation::QueuePolicy::BackendAffinity;
+        config.redact_prompts = true;
+        let mut state = ServerState::new(config);
+        for backend in [Backend::AceStep, Backend::MusicGen] {
+            let job = GenerationJob::with_backend(
+                "music for my therapist".to_string(),
+                30,
+                Some(1),
+                JobPriority::Normal,
+                "v1",
+                backend,
+            );
+            state.queue.add(job).unwrap();
+        }
+
+        // No backend loaded, so submission order applies
+        let value = handle_request("get_queue", serde_json::Value::Null, &mut state).unwrap();
+        assert_eq!(value["policy"], "backend_affinity");
+        assert!(value["current_backend"].is_null());
+        assert_eq!(value["jobs"][0]["backend"], "ace_step");
+        assert_eq!(value["jobs"][1]["priority"], "normal");
+        assert!(!value.to_string().contains("therapist"));
+    }
+
+    #[test]
+    fn handle_purge_backend_removes_backend_tracks() {
+        let mut state = ServerState::new(test_config());
+        let tracks = [
+            ("a", Backend::MusicGen),
+            ("b", Backend::AceStep),
+            ("c", Backend::AceStep),
+        ];
+        for (name, backend) in tracks {
+            let mut track = Track::new(
+                std::path::PathBuf::from(format!("/nonexistent/{}.wav", name)),
+                name.to_string(),
+                10.0,
+                1,
+                "v1".to_string(),
+                backend,
+                1.0,
+            );
+            track.file_size_bytes = 100;
+            state.cache.put(track);
+        }
+
+        let params = serde_json::json!({ "backend": "ace_step" });
+        let value = handle_request("purge_backend", params, &mut state).unwrap();
+        assert_eq!(value["tracks_removed"], 2);
+        assert_eq!(value["bytes_freed"], 200);
+        assert_eq!(state.cache.len(), 1);
+
+        let params = serde_json::json!({ "backend": "jukebox" });
+        let err = handle_request("purge_backend", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32007);
+    }
+
+    #[test]
+    fn get_backends_follows_backend_lifecycle() {
+        let musicgen_dir = tempfile::tempdir().unwrap();
+        let ace_step_dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.model_path = Some(musicgen_dir.path().to_path_buf());
+        config.ace_step_model_path = Some(ace_step_dir.path().join("missing"));
+        let mut state = ServerState::new(config);
+        let sink = RecordingSink::default();
+        state.notifications = NotificationRouter::new();
+        state.notifications.add_connection(state.connection_id, Box::new(sink.clone()));
+
+        let musicgen = |state: &mut ServerState| {
+            let value = handle_request("get_backends", serde_json::Value::Null, state).unwrap();
+            value["backends"][0].clone()
+        };
+        let info = musicgen(&mut state);
+        assert_eq!(info["status"], "not_installed");
+        assert_eq!(info["loaded"], false);
+        assert_eq!(info["active_jobs"], 0);
+
+        state.set_backend_status(Backend::MusicGen, BackendStatus::Downloading);
+        assert_eq!(musicgen(&mut state)["status"], "downloading");
+
+        // Download finished: files on disk, loadable on demand
+        for file in musicgen::REQUIRED_MODEL_FILES {
+            std::fs::write(musicgen_dir.path().join(file), b"").unwrap();
+        }
+        state.set_backend_status(Backend::MusicGen, BackendStatus::Ready);
+        assert_eq!(musicgen(&mut state)["status"], "ready");
+
+        // A failed load is reported with its reason
+        std::fs::remove_file(musicgen_dir.path().join("decoder_model.onnx")).unwrap();
+        assert!(ensure_backend_loaded(&mut state, Backend::MusicGen).is_err());
+        let info = musicgen(&mut state);
+        assert_eq!(info["status"], "error");
+        assert!(info["error_detail"].as_str().unwrap().contains("decoder_model.onnx"));
+        assert_eq!(info["loaded"], false);
+
+        // Retrying clears the error while loading
+        state.begin_loading(Backend::MusicGen);
+        let info = musicgen(&mut state);
+        assert_eq!(info["status"], "loading");
+        assert!(info.get("error_detail").is_none());
+
+        // Queued and generating jobs count as active
+        let job = GenerationJob::new("rain".to_string(), 10, Some(1), JobPriority::Normal, "v1");
+        state.queue.add(job).unwrap();
+        state.begin_backend_job(Backend::MusicGen);
+        assert_eq!(musicgen(&mut state)["active_jobs"], 2);
+        state.end_backend_job(Backend::MusicGen);
+        assert_eq!(musicgen(&mut state)["active_jobs"], 1);
+
+        let value = handle_request("get_backends", serde_json::Value::Null, &mut state).unwrap();
+        assert_eq!(value["backends"][1]["status"], "not_installed");
+        assert_eq!(value["backends"][1]["active_jobs"], 0);
+
+        // Every transition was also sent while it happened
+        let sent: Vec<(String, u64)> = sink
+            .received()
+            .iter()
+            .filter(|n| n["method"] == "backend_status")
+            .map(|n| {
+                let params = &n["params"];
+                assert_eq!(params["backend"], "musicgen");
+                let status = params["status"].as_str().unwrap().to_string();
+                (status, params["active_jobs"].as_u64().unwrap())
+            })
+            .collect();
+        let expected = [
+            ("downloading", 0),
+            ("ready", 0),
+            ("loading", 0),
+            ("error", 0),
+            ("loading", 0),
+            ("loading", 2),
+            ("loading", 1),
+        ];
+        assert_eq!(sent, expected.map(|(status, jobs)| (status.to_string(), jobs)));
+    }
+
+    #[test]
+    fn load_retry_waits_for_the_timed_out_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.model_path = Some(dir.path().join("missing"));
+        config.model_load_timeout_sec = 1;
+        let mut state = ServerState::new(config);
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        // A load left running by an earlier request that timed out
+        let started = Arc::clone(&loads);
+        state.pending_load = Some(PendingLoad::spawn(Backend::MusicGen, move || {
+            started.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(1500));
+            Err(DaemonError::model_load_failed("first load"))
+        }));
+
+        // Still running after another timeout, so it stays pending
+        let err = ensure_backend_loaded(&mut state, Backend::MusicGen).unwrap_err();
+        assert_eq!(err.code, ErrorCode::TimedOut);
+        assert_eq!(state.get_backend_status(Backend::MusicGen), BackendStatus::Error);
+        assert_eq!(state.pending_load.as_ref().map(PendingLoad::backend), Some(Backend::MusicGen));
+
+        // The retry gets that load's result instead of loading from the model dir
+        let err = ensure_backend_loaded(&mut state, Backend::MusicGen).unwrap_err();
+        assert!(err.message.contains("first load"), "{}", err.message);
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert!(state.pending_load.is_none());
+
+        // With nothing pending, the next attempt starts a new load
+        let err = ensure_backend_loaded(&mut state, Backend::MusicGen).unwrap_err();
+        assert!(!err.message.contains("first load"), "{}", err.message);
+    }
+
+    fn backend_job(state: &ServerState, backend: Backend) -> GenerationJob {
+        GenerationJob::with_backend("rain".to_string(), 10, Some(1), JobPriority::Normal, "v1", backend)
+            .with_connection_id(state.connection_id)
+    }
+
+    fn recording_state(config: crate::config::DaemonConfig) -> (ServerState, RecordingSink) {
+        let mut state = ServerState::new(config);
+        let sink = RecordingSink::default();
+        state.notifications = NotificationRouter::new();
+        state.notifications.add_connection(state.connection_id, Box::new(sink.clone()));
+        (state, sink)
+    }
+
+    #[test]
+    fn parallel_job_waits_for_its_turn_and_memory() {
+        let mut config = test_config();
+        config.parallel_backends = true;
+        let (mut state, sink) = recording_state(config);
+        let available = Some(8 * 1024 * 1024 * 1024);
+        let active = backend_job(&state, Backend::MusicGen);
+        state.queue.add(backend_job(&state, Backend::MusicGen)).unwrap();
+        state.queue.add(backend_job(&state, Backend::AceStep)).unwrap();
+
+        // Under FIFO the ACE-Step job waits behind the queued MusicGen job
+        assert!(pop_parallel_job(&mut state, &active, available).is_none());
+        assert_eq!(state.queue.len(), 2);
+
+        // Next in line, but its 8 GB of models do not fit next to the active
+        // job, so it stays queued and nothing is loaded
+        state.queue.pop_next().unwrap();
+        assert!(pop_parallel_job(&mut state, &active, available).is_none());
+        assert_eq!(state.queue.len(), 1);
+        assert!(state.standby_models.is_none());
+        assert_eq!(state.get_backend_status(Backend::AceStep), BackendStatus::NotInstalled);
+        assert!(sink.received().is_empty());
+
+        // Nothing runs in parallel unless configured
+        state.config.parallel_backends = false;
+        assert!(pop_parallel_job(&mut state, &active, None).is_none());
+        assert_eq!(state.queue.len(), 1);
+    }
+
+    #[test]
+    fn failed_standby_load_fails_the_parallel_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.parallel_backends = true;
+        config.model_path = Some(dir.path().join("missing"));
+        let (mut state, sink) = recording_state(config);
+        let active = backend_job(&state, Backend::AceStep);
+        let job = backend_job(&state, Backend::MusicGen);
+        let track_id = job.track_id.clone();
+        state.queue.add(job).unwrap();
+
+        assert!(pop_parallel_job(&mut state, &active, None).is_none());
+        assert!(state.queue.is_empty());
+        assert!(state.standby_models.is_none());
+        assert!(!state.activate_standby(Backend::MusicGen));
+        assert_eq!(state.get_backend_status(Backend::MusicGen), BackendStatus::Error);
+
+        let errors: Vec<serde_json::Value> = sink
+            .received()
+            .into_iter()
+            .filter(|n| n["method"] == "generation_error")
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0]["params"]["track_id"], track_id.as_str());
+    }
+
+    #[test]
+    fn parallel_generations_finish_independently() {
+        let mut config = test_config();
+        config.parallel_backends = true;
+        let (mut state, sink) = recording_state(config);
+        let jobs = [Backend::MusicGen, Backend::AceStep].map(|backend| backend_job(&state, backend));
+        let track_ids: Vec<String> = jobs.iter().map(|job| job.track_id.clone()).collect();
+
+        let [first, second] = jobs.map(|job| QueuedGeneration::start(&mut state, job));
+        assert_eq!(state.active_jobs(Backend::AceStep), 1);
+        generate_in_parallel(&mut state, first, second);
+
+        // Each thread failed on its own empty model set and both were finished
+        let mut failed: Vec<String> = sink
+            .received()
+            .iter()
+            .filter(|n| n["method"] == "generation_error")
+            .map(|n| n["params"]["track_id"].as_str().unwrap().to_string())
+            .collect();
+        failed.sort();
+        let mut expected = track_ids;
+        expected.sort();
+        assert_eq!(failed, expected);
+        for backend in [Backend::MusicGen, Backend::AceStep] {
+            assert_eq!(state.active_jobs(backend), 0);
+        }
+    }
+
+    /// Waits up to ten seconds for a background hook to write `path`.
+    #[cfg(unix)]
+    fn read_hook_output(path: &std::path::Path) -> String {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            match std::fs::read_to_string(path) {
+                Ok(contents) if contents.ends_with('\n') => return contents,
+                _ if Instant::now() >= deadline => panic!("hook never wrote {}", path.display()),
+                _ => std::thread::sleep(Duration::from_millis(20)),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hooks_run_when_generations_finish() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.cache_path = Some(dir.path().join("cache"));
+        config.redact_prompts = true;
+        for (name, setting) in [
+            ("complete", &mut config.on_complete_command),
+            ("error", &mut config.on_error_command),
+        ] {
+            let hook = dir.path().join(format!("{}.sh", name));
+            let output = dir.path().join(format!("{}.env", name));
+            let body = format!("#!/bin/sh\nenv | grep '^LOFI_' | sort > '{}'\n", output.display());
+            std::fs::write(&hook, body).unwrap();
+            std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+            *setting = Some(hook.to_string_lossy().to_string());
+        }
+        let (mut state, _sink) = recording_state(config);
+        let redacted = DisplayPrompt::with_redaction("rain", true).to_string();
+
+        let completed = backend_job(&state, Backend::MusicGen);
+        QueuedGeneration::start(&mut state, completed)
+            .finish(&mut state, Ok((vec![0.1; Backend::MusicGen.sample_rate() as usize], 1)))
+            .unwrap();
+        let env = read_hook_output(&dir.path().join("complete.env"));
+        let path = env.lines().find_map(|l| l.strip_prefix("LOFI_PATH=")).unwrap();
+        assert!(std::path::Path::new(path).is_file(), "{}", env);
+        assert!(env.contains("LOFI_DURATION=1\n"), "{}", env);
+        assert!(env.contains("LOFI_BACKEND=musicgen\n"), "{}", env);
+        assert!(env.contains(&format!("LOFI_PROMPT={}\n", redacted)), "{}", env);
+
+        let failed = backend_job(&state, Backend::AceStep);
+        let failed_id = failed.track_id.clone();
+        let error = DaemonError::model_inference_failed("decoder exploded");
+        let err = QueuedGeneration::start(&mut state, failed)
+            .finish(&mut state, Err(error))
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::ModelInferenceFailed);
+        let env = read_hook_output(&dir.path().join("error.env"));
+        assert!(env.contains(&format!("LOFI_TRACK_ID={}\n", failed_id)), "{}", env);
+        assert!(env.contains("LOFI_ERROR=Inference failed: decoder exploded\n"), "{}", env);
+        assert!(env.contains("LOFI_ERROR_CODE=MODEL_INFERENCE_FAILED\n"), "{}", env);
+        assert!(env.contains(&format!("LOFI_PROMPT={}\n", redacted)), "{}", env);
+    }
+
+    #[test]
+    fn handle_prune_orphans_deletes_unindexed_wavs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.cache_path = Some(dir.path().to_path_buf());
+        let mut state = ServerState::new(config);
+
+        let old = std::time::SystemTime::now() - Duration::from_secs(600);
+        for name in ["cached", "evicted"] {
+            let path = dir.path().join(format!("{}.wav", name));
+            std::fs::write(&path, [0u8; 64]).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(old).unwrap();
+        }
+        let mut track = Track::new(
+            dir.path().join("cached.wav"),
+            "cached".to_string(),
+            10.0,
+            1,
+            "v1".to_string(),
+            Backend::MusicGen,
+            1.0,
+        );
+        track.file_size_bytes = 64;
+        state.cache.put(track);
+
+        let params = serde_json::json!({ "dry_run": true });
+        let value = handle_request("prune_orphans", params, &mut state).unwrap();
+        assert_eq!(value["files_removed"], 1);
+        assert_eq!(value["dry_run"], true);
+        assert!(dir.path().join("evicted.wav").exists());
+
+        // Without params nothing is deleted
+        let value = handle_request("prune_orphans", serde_json::Value::Null, &mut state).unwrap();
+        assert_eq!(value["dry_run"], true);
+        let value = handle_request("prune_orphans", serde_json::json!({}), &mut state).unwrap();
+        assert_eq!(value["dry_run"], true);
+        assert!(dir.path().join("evicted.wav").exists());
+
+        let params = serde_json::json!({ "dry_run": false });
+        let value = handle_request("prune_orphans", params, &mut state).unwrap();
+        assert_eq!(value["files_removed"], 1);
+        assert_eq!(value["bytes_freed"], 64);
+        assert!(value["files"][0].as_str().unwrap().ends_with("evicted.wav"));
+        assert!(!dir.path().join("evicted.wav").exists());
+        assert!(dir.path().join("cached.wav").exists());
+    }
+
+    #[test]
+    fn read_only_cache_refuses_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.cache_path = Some(dir.path().to_path_buf());
+        config.library_path = Some(dir.path().join("library"));
+        config.now_playing = true;
+        let mut state = ServerState::new(config).with_read_only_cache();
+        let path = dir.path().join("cached.wav");
+        crate::audio::write_wav(&[0.25; 480], &path, 48000).unwrap();
+        let track = Track::new(
+            path.clone(),
+            "cached".to_string(),
+            10.0,
+            1,
+            "v1".to_string(),
+            Backend::MusicGen,
+            1.0,
+        );
+        let track_id = track.track_id.clone();
+        state.cache.put(track);
+
+        let requests = [
+            ("rate_track", serde_json::json!({ "track_id": track_id, "rating": 1 })),
+            ("purge_backend", serde_json::json!({ "backend": "musicgen" })),
+            ("prune_orphans", serde_json::json!({ "dry_run": false })),
+        ];
+        for (method, params) in requests {
+            let err = handle_request(method, params, &mut state).unwrap_err();
+            assert_eq!(err.code, -32016, "{}", method);
+            assert_eq!(err.data.unwrap().error_code, "CACHE_READ_ONLY");
+        }
+        assert!(path.exists());
+        assert!(!dir.path().join(crate::cache::RATINGS_FILE).exists());
+
+        // Reading the cache still works
+        let params = serde_json::json!({ "dry_run": true });
+        assert!(handle_request("prune_orphans", params, &mut state).is_ok());
+        let params = serde_json::json!({ "track_id": track_id });
+        assert!(handle_request("get_track", params, &mut state).is_ok());
+
+        // Serving a cached track writes neither now playing, history nor a
+        // library copy
+        let listing = || {
+            let mut names: Vec<_> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect();
+            names.sort();
+            names
+        };
+        let before = listing();
+        assert!(notify_cached_track(&mut state, &track_id, &[], true));
+        assert_eq!(listing(), before);
+    }
+
+    #[test]
+    fn handle_get_cache_stats_breaks_down_by_backend() {
+        let mut state = ServerState::new(test_config());
+        for (i, backend) in [Backend::MusicGen, Backend::MusicGen, Backend::AceStep]
+            .into_iter()
+            .enumerate()
+        {
+            let mut track = Track::new(
+                PathBuf::from(format!("/tmp/{}.wav", i)),
+                format!("prompt {}", i),
+                30.0,
+                i as u64,
+                "v1".to_string(),
+                backend,
+                1.0,
+            );
+            track.file_size_bytes = 1000;
+            state.cache.put(track);
+        }
+
+        let value = handle_request("get_cache_stats", serde_json::Value::Null, &mut state).unwrap();
+        assert_eq!(value["track_count"], 3);
+        assert_eq!(value["total_duration_sec"], 90.0);
+        assert_eq!(value["total_size_bytes"], 3000);
+        assert_eq!(value["by_backend"][0]["backend"], "musicgen");
+        assert_eq!(value["by_backend"][0]["track_count"], 2);
+        assert_eq!(value["by_backend"][0]["total_duration_sec"], 60.0);
+        assert_eq!(value["by_backend"][1]["backend"], "ace_step");
+        assert_eq!(value["by_backend"][1]["track_count"], 1);
+        assert_eq!(value["by_backend"][1]["total_size_bytes"], 1000);
+    }
+
+    #[test]
+    fn handle_get_cache_stats_scans_the_cache_dir_on_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.cache_path = Some(dir.path().to_path_buf());
+        let mut state = ServerState::new(config);
+
+        let value = handle_request("get_cache_stats", serde_json::Value::Null, &mut state).unwrap();
+        assert!(value.get("disk").is_none());
+        assert!(value["oldest_created_at"].is_null());
+        assert_eq!(value["cache_dir"], dir.path().display().to_string());
+
+        let params = serde_json::json!({ "scan_disk": true });
+        let value = handle_request("get_cache_stats", params.clone(), &mut state).unwrap();
+        let file_count = value["disk"]["file_count"].as_u64().unwrap();
+
+        // The scan is reused until a refresh is requested
+        std::fs::write(dir.path().join("extra.bin"), [0u8; 10]).unwrap();
+        let value = handle_request("get_cache_stats", params, &mut state).unwrap();
+        assert_eq!(value["disk"]["file_count"], file_count);
+        let params = serde_json::json!({ "refresh": true });
+        let value = handle_request("get_cache_stats", params, &mut state).unwrap();
+        assert_eq!(value["disk"]["file_count"], file_count + 1);
+        assert_eq!(value["disk"]["age_sec"], 0.0);
+    }
+
+    #[test]
+    fn handle_get_track_missing() {
+        let mut state = ServerState::new(test_config());
+        let params = serde_json::json!({ "track_id": "0123456789abcdef" });
+        let err = handle_request("get_track", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32602);
+    }
+
+    #[test]
+    fn handle_set_config_rejects_reload_required_keys() {
+        let mut state = ServerState::new(test_config());
+        let params = serde_json::json!({ "device": "cpu", "normalize_audio": true });
+        let err = handle_request("set_config", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32602);
+        assert!(err.message.contains("device"));
+        // Nothing is applied when any key is rejected
+        assert!(!state.config.normalize_audio);
+    }
+
+    #[test]
+    fn handle_set_config_keeps_library_inside_output_root() {
+        let mut config = test_config();
+        config.restrict_output_root = Some(std::path::PathBuf::from("/srv/lofi"));
+        let mut state = ServerState::new(config);
+
+        let params = serde_json::json!({ "library_path": "/home/me/Music" });
+        let err = handle_request("set_config", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32602);
+        assert!(err.message.contains("restrict_output_root"));
+        assert!(state.config.library_path.is_none());
+
+        let params = serde_json::json!({ "library_path": "/srv/lofi/library" });
+        handle_request("set_config", params, &mut state).unwrap();
+        assert_eq!(state.config.library_path, Some("/srv/lofi/library".into()));
+    }
+
+    #[test]
+    fn write_output_wav_resamples_to_requested_rate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.wav");
+        let samples = vec![0.1f32; 48000];
+
+        let (written, rate) = write_output_wav(samples.clone(), 48000, None, &path).unwrap();
+        assert_eq!((written.len(), rate), (48000, 48000));
+
+        let (written, rate) = write_output_wav(samples, 48000, Some(44100), &path).unwrap();
+        assert_eq!(rate, 44100);
+        assert_eq!(written.len(), 44100);
+        let (read, read_rate) = read_wav(&path).unwrap();
+        assert_eq!((read.len(), read_rate), (44100, 44100));
+
+        // Silent output is rejected and not left behind
+        let err = write_output_wav(vec![0.0; 48000], 48000, None, &path).unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::ModelInferenceFailed);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn copy_to_library_keeps_cache_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.cache_path = Some(dir.path().join("cache"));
+        config.library_path = Some(dir.path().join("library"));
+        std::fs::create_dir_all(config.effective_cache_path()).unwrap();
+        let path = config.effective_cache_path().join("abc.wav");
+        crate::audio::write_wav(&[0.25; 4800], &path, 48000).unwrap();
+        let track = Track::new(
+            path.clone(),
+            "rain".to_string(),
+            0.1,
+            42,
+            "v1".to_string(),
+            Backend::MusicGen,
+            1.0,
+        );
+
+        let state = ServerState::new(config);
+        assert_eq!(copy_to_library(&state, &track, false), None);
+        assert!(!dir.path().join("library").exists());
+
+        let copied = copy_to_library(&state, &track, true).unwrap();
+        assert_eq!(Path::new(&copied), dir.path().join("library").join("abc.wav"));
+        assert_eq!(std::fs::read(&copied).unwrap(), std::fs::read(&path).unwrap());
+
+        // Deleting the cached track leaves the library copy alone
+        crate::cache::delete_track_files(&track);
+        assert!(!path.exists());
+        assert!(Path::new(&copied).exists());
+    }
+
+    #[test]
+    fn cache_wav_path_uses_filename_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.cache_path = Some(dir.path().join("cache"));
+        let job = GenerationJob::new("../rain lofi".to_string(), 10, Some(7), JobPriority::Normal, "v1");
+
+        let path = cache_wav_path(&config, &job, 7);
+        assert_eq!(path, dir.path().join("cache").join(format!("{}.wav", job.track_id)));
+        std::fs::write(&path, b"stale").unwrap();
+        assert_eq!(cache_wav_path(&config, &job, 7), path);
+
+        config.cache_filename = "{prompt}-{seed}.wav".to_string();
+        let path = cache_wav_path(&config, &job, 7);
+        assert_eq!(path, dir.path().join("cache").join("rain-lofi-7.wav"));
+        std::fs::write(&path, b"other track").unwrap();
+        assert_eq!(
+            cache_wav_path(&config, &job, 7),
+            dir.path().join("cache").join("rain-lofi-7-1.wav")
+        );
+
+        // A sidecar never replaces the ratings or now-playing file
+        for (template, prompt) in [("{prompt}.wav", "Ratings"), ("now_playing.wav", "rain")] {
+            config.cache_filename = template.to_string();
+            let job = GenerationJob::new(prompt.to_string(), 10, Some(7), JobPriority::Normal, "v1");
+            let path = cache_wav_path(&config, &job, 7);
+            let sidecar = crate::cache::sidecar_path(&path);
+            let name = sidecar.file_name().unwrap().to_str().unwrap();
+            assert!(!crate::cache::RESERVED_FILES.contains(&name), "{}", name);
+            assert!(name.ends_with("-track.json"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn handle_compare_tracks_diffs_provenance_and_audio() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = ServerState::new(test_config());
+
+        let provenance = Provenance::new("CPU").with_sampling(SamplingParams {
+            scheduler: Some("euler".to_string()),
+            inference_steps: Some(60),
+            guidance_scale: Some(7.0),
+            top_k: None,
+        });
+        let mut louder = provenance.clone();
+        louder.sampling.guidance_scale = Some(12.0);
+
+        for (id, amplitude, provenance) in [("a", 0.25, provenance), ("b", 0.5, louder)] {
+            let path = dir.path().join(format!("{}.wav", id));
+            let samples: Vec<f32> = (0..4800)
+                .map(|i| if i % 2 == 0 { amplitude } else { -amplitude })
+                .collect();
+            crate::audio::write_wav(&samples, &path, 48000).unwrap();
+            let mut track = Track::new(
+                path,
+                "rain".to_string(),
+                0.1,
+                42,
+                "ace-step-v1".to_string(),
+                Backend::AceStep,
+                1.0,
+            )
+            .with_provenance(provenance);
+            track.track_id = id.to_string();
+            state.cache.put(track);
+        }
+
+        let params = serde_json::json!({ "track_id_a": "a", "track_id_b": "b" });
+        let value = handle_request("compare_tracks", params, &mut state).unwrap();
+        let diff = value["provenance_diff"].as_array().unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0]["field"], "sampling.guidance_scale");
+        assert_eq!(diff[0]["a"], 7.0);
+        assert_eq!(diff[0]["b"], 12.0);
+        assert_eq!(value["audio_delta"]["peak"], 0.25);
+        assert_eq!(value["audio_delta"]["duration_sec"], 0.0);
+
+        let params = serde_json::json!({ "track_id_a": "a", "track_id_b": "missing" });
+        let err = handle_request("compare_tracks", params, &mut state).unwrap_err();
+        assert!(err.message.contains("missing"));
+    }
+
+    #[test]
+    fn handle_export_manifest_writes_the_recipe() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = ServerState::new(test_config());
+        let track = Track::new(
+            dir.path().join("track.wav"),
+            "rain".to_string(),
+            30.0,
+            42,
+            "ace-step-v1".to_string(),
+            Backend::AceStep,
+            1.0,
+        )
+        .with_provenance(Provenance::new("CPU").with_sampling(SamplingParams {
+            scheduler: Some("pingpong".to_string()),
+            ..SamplingParams::default()
+        }));
+        let track_id = track.track_id.clone();
+        state.cache.put(track);
+
+        let path = dir.path().join("recipe.json");
+        let params = serde_json::json!({ "track_id": track_id, "path": path });
+        let value = handle_request("export_manifest", params, &mut state).unwrap();
+        assert_eq!(value["manifest"]["seed"], 42);
+        assert_eq!(value["manifest"]["sampling"]["scheduler"], "pingpong");
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written, value["manifest"]);
+
+        let params = serde_json::json!({ "track_id": "missing" });
+        let err = handle_request("export_manifest", params, &mut state).unwrap_err();
+        assert!(err.message.contains("missing"));
+
+        // The file must lie inside restrict_output_root when it is set
+        let root = dir.path().join("renders");
+        std::fs::create_dir(&root).unwrap();
+        state.config.restrict_output_root = Some(root.clone());
+        let params = serde_json::json!({ "track_id": track_id, "path": "../escaped.json" });
+        let err = handle_request("export_manifest", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32017);
+        assert!(!dir.path().join("escaped.json").exists());
+        let params = serde_json::json!({ "track_id": track_id, "path": "recipe.json" });
+        let value = handle_request("export_manifest", params, &mut state).unwrap();
+        assert!(root.join("recipe.json").exists());
+        assert!(value["path"].as_str().unwrap().ends_with("recipe.json"));
+
+        // Invalid manifests are rejected before anything is loaded
+        let mut manifest = value["manifest"].clone();
+        manifest.as_object_mut().unwrap().remove("prompt");
+        let params = serde_json::json!({ "manifest": manifest });
+        let err = handle_request("generate_from_manifest", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32602);
+        assert!(err.message.contains("prompt"));
+    }
+
+    #[test]
+    fn handle_set_config_evicts_cache_immediately() {
+        let mut state = ServerState::new(test_config());
+        for id in ["a", "b", "c"] {
+            state.cache.put(Track {
+                track_id: id.to_string(),
+                path: std::path::PathBuf::from(format!("/tmp/{}.wav", id)),
+                prompt: "test".to_string(),
+                prompt_blend: None,
+                duration_sec: 10.0,
+                sample_rate: 32000,
+                reverb: None,
+                trimmed_sec: None,
+                seed: 1,
+                model_version: "test".to_string(),
+                backend: Backend::MusicGen,
+                generation_time_sec: 1.0,
+                file_size_bytes: 1000,
+                tags: Vec::new(),
+                rating: None,
+                derived_from_seed: None,
+                created_at: std::time::SystemTime::now(),
+                provenance: None,
+            });
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        let params = serde_json::json!({ "cache_max_bytes": 1500 });
+        let value = handle_request("set_config", params, &mut state).unwrap();
+        assert_eq!(value["cache_max_bytes"], 1500);
+        assert_eq!(state.cache.len(), 1);
+        assert!(state.cache.contains("c"));
+    }
+
+    /// Returns a config whose ACE-Step model directory holds placeholder files
+    /// and whose MusicGen model directory is empty.
+    fn config_with_ace_step_installed(dir: &std::path::Path) -> crate::config::DaemonConfig {
+        let ace_step_dir = dir.join("ace-step");
+        std::fs::create_dir_all(&ace_step_dir).unwrap();
+        for file in crate::models::ace_step::REQUIRED_FILES {
+            std::fs::write(ace_step_dir.join(file), b"").unwrap();
+        }
+
+        let mut config = test_config();
+        config.model_path = Some(dir.join("musicgen"));
+        config.ace_step_model_path = Some(ace_step_dir);
+        config
+    }
+
+    #[test]
+    fn handle_set_default_backend_persists_to_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.json");
+        let config = config_with_ace_step_installed(dir.path());
+        let mut state = ServerState::new(config).with_config_path(&path);
+
+        let params = serde_json::json!({ "backend": "ace-step" });
+        let value = handle_request("set_default_backend", params, &mut state).unwrap();
+        assert_eq!(value["default_backend"], "ace_step");
+        assert_eq!(value["persisted"], true);
+        assert_eq!(state.config.default_backend, Backend::AceStep);
+
+        // A restarted daemon picks the setting up from the file
+        let reloaded = DaemonConfig::load(&path).unwrap();
+        assert_eq!(reloaded.default_backend, Backend::AceStep);
+    }
+
+    #[test]
+    fn handle_set_default_backend_keeps_env_settings_out_of_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.json");
+        let file_config = DaemonConfig {
+            max_queue_size: 4,
+            ..DaemonConfig::default()
+        };
+        file_config.save(&path).unwrap();
+
+        // The running config also carries values from LOFI_* variables
+        let mut config = config_with_ace_step_installed(dir.path());
+        config.max_queue_size = 4;
+        config.threads = Some(3);
+        let mut state = ServerState::new(config).with_config_path(&path);
+
+        let params = serde_json::json!({ "backend": "ace-step" });
+        handle_request("set_default_backend", params, &mut state).unwrap();
+        assert_eq!(state.config.threads, Some(3));
+
+        let reloaded = DaemonConfig::load(&path).unwrap();
+        assert_eq!(reloaded.default_backend, Backend::AceStep);
+        assert_eq!(reloaded.max_queue_size, 4);
+        assert_eq!(reloaded.threads, None);
+        assert_eq!(reloaded.model_path, None);
+    }
+
+    #[test]
+    fn handle_set_default_backend_rejects_unknown_backend() {
+        let mut state = ServerState::new(test_config());
+        let params = serde_json::json!({ "backend": "jukebox" });
+        let err = handle_request("set_default_backend", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32007);
+        assert_eq!(state.config.default_backend, Backend::MusicGen);
+    }
+
+    #[test]
+    fn handle_set_default_backend_requires_installed_models() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = ServerState::new(config_with_ace_step_installed(dir.path()));
+
+        let params = serde_json::json!({ "backend": "ace_step" });
+        let value = handle_request("set_default_backend", params, &mut state).unwrap();
+        assert_eq!(value["default_backend"], "ace_step");
+
+        // MusicGen's model directory is empty
+        let params = serde_json::json!({ "backend": "musicgen" });
+        let err = handle_request("set_default_backend", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32008);
+        assert_eq!(state.config.default_backend, Backend::AceStep);
+
+        // Without a config file the change applies to this session only
+        let params = serde_json::json!({ "backend": "ace-step" });
+        let value = handle_request("set_default_backend", params, &mut state).unwrap();
+        assert_eq!(value["persisted"], false);
+    }
+
+    #[test]
+    fn handle_set_config_rejects_invalid_guidance() {
+        let mut state = ServerState::new(test_config());
+        let params = serde_json::json!({ "ace_step": { "guidance_scale": 0.5 } });
+        let err = handle_request("set_config", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32602);
+        assert_eq!(state.config.ace_step.guidance_scale, 7.0);
+    }
+
+    #[test]
+    fn handle_subscribe_rejects_unknown_methods() {
+        let mut state = ServerState::new(test_config());
+        let params = serde_json::json!({ "methods": ["generation_progress", "bogus"] });
+        let err = handle_request("subscribe", params, &mut state).unwrap_err();
+        assert_eq!(err.code, -32602);
+        assert!(err.message.contains("bogus"));
+        assert!(err.message.contains("download_progress"));
+    }
+
+    #[test]
+    fn handle_subscribe_and_unsubscribe() {
+        let mut state = ServerState::new(test_config());
+        let params = serde_json::json!({ "methods": ["generation_complete"], "track_ids": ["abc"] });
+        let value = handle_request("subscribe", params, &mut state).unwrap();
+        assert_eq!(value["methods"][0], "generation_complete");
+        assert_eq!(value["track_ids"][0], "abc");
+
+        let value = handle_request("unsubscribe", serde_json::Value::Null, &mut state).unwrap();
+        assert!(value["methods"].is_null());
+    }
+
+    #[test]
+    fn handle_get_config_reports_backend() {
+        let mut state = ServerState::new(test_config());
+        let params = serde_json::json!({ "default_backend": "ace_step" });
+        handle_request("set_config", params, &mut state).unwrap();
+
+        let value = handle_request("get_config", serde_json::Value::Null, &mut state).unwrap();
+        assert_eq!(value["default_backend"], "ace_step");
+        assert_eq!(value["progress_interval_percent"], 5);
+    }
+
+    #[test]
+    fn write_thumbnail_respects_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav_path = dir.path().join("abc.wav");
+        let samples: Vec<f32> = (0..8000).map(|i| (i as f32 * 0.05).sin()).collect();
+
+        let mut config = test_config();
+        assert!(write_thumbnail(&config, &samples, 32000, &wav_path).is_none());
+        assert!(existing_thumbnail(&wav_path).is_none());
+
+        config.generate_thumbnails = true;
+        let path = write_thumbnail(&config, &samples, 32000, &wav_path).unwrap();
+        assert!(path.ends_with("abc.png"));
+        assert_eq!(existing_thumbnail(&wav_path), Some(path));
+    }
+
+    #[test]
+    fn now_playing_is_written_and_served() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.now_playing_dir = Some(dir.path().to_path_buf());
+        config.history_max_entries = 2;
+        let mut state = ServerState::new(config);
+
+        let value = handle_request("get_now_playing", serde_json::Value::Null, &mut state).unwrap();
+        assert!(value["now_playing"].is_null());
+
+        let stats = || Ok(AudioStats::from_samples(&[0.5, -0.5], 2));
+        for seed in 1..=3 {
+            let track = Track::new(
+                dir.path().join(format!("{}.wav", seed)),
+                "rainy night".to_string(),
+                30.0,
+                seed,
+                "v1".to_string(),
+                Backend::MusicGen,
+                1.0,
+            );
+            write_now_playing(&state, &track, stats);
+        }
+
+        let value = handle_request("get_now_playing", serde_json::Value::Null, &mut state).unwrap();
+        assert_eq!(value["now_playing"]["track"]["seed"], 3);
+        assert_eq!(value["now_playing"]["audio"]["peak"], 0.5);
+
+        let value = handle_request("get_history", serde_json::Value::Null, &mut state).unwrap();
+        let seeds: Vec<u64> = value["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["track"]["seed"].as_u64().unwrap())
+            .collect();
+        assert_eq!(seeds, vec![3, 2]);
+
+        let value =
+            handle_request("get_history", serde_json::json!({ "limit": 1 }), &mut state).unwrap();
+        assert_eq!(value["entries"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn now_playing_can_be_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.now_playing_dir = Some(dir.path().to_path_buf());
+        config.now_playing = false;
+
+        let track = Track::new(
+            dir.path().join("a.wav"),
+            "rainy night".to_string(),
+            30.0,
+            1,
+            "v1".to_string(),
+            Backend::MusicGen,
+            1.0,
+        );
+        let state = ServerState::new(config);
+        write_now_playing(&state, &track, || panic!("stats computed while disabled"));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn handle_health_check() {
+        let mut state = ServerState::new(test_config());
+        let value = handle_request("health", serde_json::Value::Null, &mut state).unwrap();
+        assert_eq!(value["status"], "healthy");
+        assert!(value["uptime_sec"].as_f64().unwrap() >= 0.0);
+        assert_eq!(value["backend_loaded"], false);
+        assert_eq!(value["queue_depth"], 0);
+        assert_eq!(value["cache_size"], 0);
+        assert!(state.models.is_none());
+    }
+
+    #[test]
+    fn get_metrics_counts_failed_jobs() {
+        let model_dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.model_path = Some(model_dir.path().to_path_buf());
+        let mut state = ServerState::new(config);
+
+        // The models are missing, so both queued jobs fail to load
+        for seed in [1, 2] {
+            let job = GenerationJob::new("rain".to_string(), 10, Some(seed), JobPriority::Normal, "v1");
+            state.queue.add(job).unwrap();
+        }
+        let value = handle_request("get_metrics", serde_json::Value::Null, &mut state).unwrap();
+        assert!(value.as_str().unwrap().contains("lofi_queue_depth 2\n"));
+
+        process_next_job(&mut state);
+        let value = handle_request("get_metrics", serde_json::Value::Null, &mut state).unwrap();
+        let text = value.as_str().unwrap();
+        assert_eq!(crate::metrics::check_exposition(text), Ok(()));
+        assert!(text.contains("lofi_generations_total{backend=\"musicgen\",status=\"failed\"} 2\n"));
+        assert!(text.contains("lofi_generations_total{backend=\"musicgen\",status=\"started\"} 0\n"));
+        assert!(text.contains("lofi_queue_depth 0\n"));
+        assert!(text.contains("lofi_backend_loaded{backend=\"musicgen\"} 0\n"));
+    }
+
     #[test]
     fn handle_shutdown() {
         let mut state = ServerState::new(test_config());
@@ -0,0 +1,47 @@
+//! HTTP metrics endpoint for Prometheus scraping.
+//!
+//! Built with the `metrics-http` feature. With `--metrics-addr` the daemon
+//! answers `GET /metrics` with the same Prometheus text as the `get_metrics`
+//! method, on its own address (127.0.0.1:9184 by default) so metrics stay
+//! off the health endpoint, which may be exposed to the orchestrator.
+//! Generation counters are read live; the gauges are as of the last publish
+//! (see [`ServerState::publish_health`]).
+//!
+//! [`ServerState::publish_health`]: super::ServerState::publish_health
+
+use std::io;
+use std::net::SocketAddr;
+use std::thread;
+
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::metrics::Metrics;
+
+/// Address served when `--metrics-addr` is given without one.
+pub const DEFAULT_METRICS_ADDR: &str = "127.0.0.1:9184";
+
+/// Content type of the Prometheus text exposition format.
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Serves `GET /metrics` on `addr` from a background thread.
+///
+/// Returns the bound address.
+pub fn spawn_metrics_server(addr: SocketAddr, metrics: Metrics) -> io::Result<SocketAddr> {
+    let server = Server::http(addr).map_err(io::Error::other)?;
+    let local_addr = server.server_addr().to_ip().unwrap_or(addr);
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if *request.method() == Method::Get && request.url() == "/metrics" {
+                let content_type = Header::from_bytes("Content-Type", METRICS_CONTENT_TYPE)
+                    .expect("content type header is valid");
+                Response::from_string(metrics.render()).with_header(content_type)
+            } else {
+                Response::from_string("not found").with_status_code(404)
+            };
+            if let Err(e) = request.respond(response) {
+                eprintln!("Metrics endpoint error: {}", e);
+            }
+        }
+    });
+    Ok(local_addr)
+}
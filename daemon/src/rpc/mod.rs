@@ -2,6 +2,25 @@
 //!
 //! Provides the JSON-RPC 2.0 server implementation for:
 //! - `generate`: Start music generation
+//! - `list_tracks`: List cached tracks with their sizes, optionally filtered by tag or rating
+//! - `get_track`: Look up a single cached track, including its provenance
+//! - `rate_track`: Rate a track; ratings protect it from eviction and guide `prefer_rated` seeds
+//! - `compare_tracks`: Diff the provenance and audio statistics of two tracks
+//! - `export_manifest` / `generate_from_manifest`: Share and replay a track's generation recipe
+//! - `get_now_playing` / `get_history`: Read the now-playing metadata and track history
+//! - `purge_backend`: Remove all cached tracks generated by a backend
+//! - `prune_orphans`: Find WAV files in the cache directory that are no longer cached, deleting them with `dry_run: false`
+//! - `get_cache_stats`: Count, duration and size of the cached tracks, overall and per backend,
+//!   and optionally the disk usage of the cache directory
+//! - `get_queue`: List queued jobs in processing order
+//! - `get_config`: Return the effective daemon configuration
+//! - `set_config`: Change runtime settings without restarting
+//! - `set_default_backend`: Change the default backend and persist it to the config file
+//! - `subscribe` / `unsubscribe`: Filter which notifications a connection receives
+//! - `get_schema`: Return the JSON Schema of the protocol
+//! - `health`: Liveness check (also served over HTTP with `--metrics-port`)
+//! - `get_metrics`: Generation and queue metrics in the Prometheus text format (also served over
+//!   HTTP with `--metrics-addr` and the `metrics-http` feature)
 //! - `ping`: Health check
 //! - `shutdown`: Graceful shutdown
 //!
@@ -9,16 +28,29 @@
 //! - `generation_progress`: Progress updates during generation
 //! - `generation_complete`: Successful completion
 //! - `generation_error`: Generation failure
+//! - `download_progress`: Backend model download progress
+//! - `download_complete`: Backend model files finished downloading
 
+pub mod health;
+#[cfg(feature = "metrics-http")]
+pub mod metrics_http;
 pub mod methods;
+pub mod notifications;
+pub mod schema;
 pub mod server;
 pub mod types;
 
 // Re-export commonly used types
+pub use notifications::{
+    NotificationFilter, NotificationRouter, NotificationSink, StdoutSink, NOTIFICATION_METHODS,
+};
+pub use schema::schema_document;
 pub use server::{run_server, send_notification, BackendStatuses, ServerState};
 pub use types::{
     BackendInfo, BackendStatus, GenerateParams, GenerateResult, GenerationCompleteParams,
     GenerationErrorParams, GenerationProgressParams, GenerationStatus, GetBackendsResult,
-    JsonRpcError, JsonRpcErrorResponse, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
-    Priority, RequestId,
+    GetTrackParams, HealthResult, JsonRpcError, JsonRpcErrorResponse, JsonRpcNotification, JsonRpcRequest,
+    JsonRpcResponse, ListTracksParams, ListTracksResult, Priority, RateTrackParams,
+    RateTrackResult, RequestId, SubscribeParams,
+    SubscribeResult,
 };
@@ -0,0 +1,311 @@
+//! Notification routing with per-connection subscription filters.
+//!
+//! Every client connection owns a [`NotificationSink`] and an optional
+//! [`NotificationFilter`] set through the `subscribe` method. Terminal events
+//! (`generation_complete`, `generation_error`) for jobs a connection submitted
+//! are always delivered to it, so a narrow filter can never hide the outcome
+//! of the client's own requests. In stdio mode there is a single connection,
+//! so filters effectively apply globally.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::Serialize;
+
+use crate::types::{ConnectionId, STDIO_CONNECTION_ID};
+
+use super::types::JsonRpcNotification;
+
+/// Notification methods the daemon can emit.
+pub const NOTIFICATION_METHODS: &[&str] = &[
+    "generation_progress",
+    "generation_complete",
+    "generation_error",
+    "download_progress",
+    "download_complete",
+    "backend_status",
+];
+
+/// Notifications that end a job; always delivered to the submitting connection.
+const TERMINAL_METHODS: &[&str] = &["generation_complete", "generation_error"];
+
+/// Destination for serialized notifications of a single connection.
+pub trait NotificationSink: Send {
+    /// Writes one serialized JSON-RPC notification.
+    fn send(&self, line: &str);
+}
+
+/// Sink writing notifications to stdout, one JSON object per line.
+pub struct StdoutSink;
+
+impl NotificationSink for StdoutSink {
+    fn send(&self, line: &str) {
+        let mut stdout = io::stdout();
+        writeln!(stdout, "{}", line).ok();
+        stdout.flush().ok();
+    }
+}
+
+/// Events a connection has subscribed to.
+///
+/// `None` means no restriction for that dimension.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NotificationFilter {
+    /// Notification methods to deliver.
+    pub methods: Option<Vec<String>>,
+    /// Tracks whose job notifications are delivered. Notifications not tied
+    /// to a track (e.g. `download_progress`) are unaffected.
+    pub track_ids: Option<Vec<String>>,
+}
+
+impl NotificationFilter {
+    /// Returns true if a notification passes the filter.
+    pub fn allows(&self, method: &str, track_id: Option<&str>) -> bool {
+        if let Some(ref methods) = self.methods {
+            if !methods.iter().any(|m| m == method) {
+                return false;
+            }
+        }
+        match (&self.track_ids, track_id) {
+            (Some(track_ids), Some(track_id)) => track_ids.iter().any(|t| t == track_id),
+            _ => true,
+        }
+    }
+}
+
+/// Checks that every name is a known notification method.
+///
+/// Returns an error message listing the valid names otherwise.
+pub fn validate_notification_methods(methods: &[String]) -> Option<String> {
+    let unknown: Vec<&str> = methods
+        .iter()
+        .map(String::as_str)
+        .filter(|m| !NOTIFICATION_METHODS.contains(m))
+        .collect();
+    if unknown.is_empty() {
+        None
+    } else {
+        Some(format!(
+            "Unknown notification method(s): {}. Valid names: {}",
+            unknown.join(", "),
+            NOTIFICATION_METHODS.join(", ")
+        ))
+    }
+}
+
+/// A registered connection and its subscription.
+struct Connection {
+    id: ConnectionId,
+    sink: Box<dyn NotificationSink>,
+    filter: NotificationFilter,
+}
+
+/// Routes notifications to connections according to their filters.
+///
+/// Cloning is cheap; clones share the same connections, so a router can be
+/// moved into progress callbacks.
+#[derive(Clone, Default)]
+pub struct NotificationRouter {
+    connections: Arc<Mutex<Vec<Connection>>>,
+}
+
+impl NotificationRouter {
+    /// Creates a router without connections.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a router with the stdio connection writing to stdout.
+    pub fn stdio() -> Self {
+        let router = Self::new();
+        router.add_connection(STDIO_CONNECTION_ID, Box::new(StdoutSink));
+        router
+    }
+
+    /// Registers a connection, replacing any existing one with the same id.
+    pub fn add_connection(&self, id: ConnectionId, sink: Box<dyn NotificationSink>) {
+        let mut connections = self.lock();
+        connections.retain(|c| c.id != id);
+        connections.push(Connection {
+            id,
+            sink,
+            filter: NotificationFilter::default(),
+        });
+    }
+
+    /// Removes a connection.
+    pub fn remove_connection(&self, id: ConnectionId) {
+        self.lock().retain(|c| c.id != id);
+    }
+
+    /// Sets the filter of a connection. Returns false if it is not registered.
+    pub fn subscribe(&self, id: ConnectionId, filter: NotificationFilter) -> bool {
+        match self.lock().iter_mut().find(|c| c.id == id) {
+            Some(connection) => {
+                connection.filter = filter;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Resets a connection to receive all notifications.
+    pub fn unsubscribe(&self, id: ConnectionId) -> bool {
+        self.subscribe(id, NotificationFilter::default())
+    }
+
+    /// Sends a notification that is not tied to a job.
+    pub fn notify<T: Serialize>(&self, method: &'static str, params: T) {
+        self.route(method, params, None, None);
+    }
+
+    /// Sends a job notification for `track_id`, submitted by `owner`.
+    pub fn notify_job<T: Serialize>(
+        &self,
+        method: &'static str,
+        params: T,
+        track_id: &str,
+        owner: ConnectionId,
+    ) {
+        self.route(method, params, Some(track_id), Some(owner));
+    }
+
+    fn route<T: Serialize>(
+        &self,
+        method: &'static str,
+        params: T,
+        track_id: Option<&str>,
+        owner: Option<ConnectionId>,
+    ) {
+        let Ok(line) = serde_json::to_string(&JsonRpcNotification::new(method, params)) else {
+            return;
+        };
+        let terminal = TERMINAL_METHODS.contains(&method);
+
+        for connection in self.lock().iter() {
+            let own_terminal = terminal && owner == Some(connection.id);
+            if own_terminal || connection.filter.allows(method, track_id) {
+                connection.sink.send(&line);
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Connection>> {
+        // A panicking sink must not silence every other connection
+        self.connections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Sink recording every notification it receives, for tests.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct RecordingSink(Arc<Mutex<Vec<serde_json::Value>>>);
+
+#[cfg(test)]
+impl NotificationSink for RecordingSink {
+    fn send(&self, line: &str) {
+        self.0.lock().unwrap().push(serde_json::from_str(line).unwrap());
+    }
+}
+
+#[cfg(test)]
+impl RecordingSink {
+    /// Returns the notifications received so far.
+    pub(crate) fn received(&self) -> Vec<serde_json::Value> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sink recording the method of every notification it receives.
+    #[derive(Clone, Default)]
+    struct FakeSink(Arc<Mutex<Vec<String>>>);
+
+    impl NotificationSink for FakeSink {
+        fn send(&self, line: &str) {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            let method = value["method"].as_str().unwrap().to_string();
+            self.0.lock().unwrap().push(method);
+        }
+    }
+
+    impl FakeSink {
+        fn received(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    fn router_with_two_sinks() -> (NotificationRouter, FakeSink, FakeSink) {
+        let router = NotificationRouter::new();
+        let editor = FakeSink::default();
+        let dashboard = FakeSink::default();
+        router.add_connection(1, Box::new(editor.clone()));
+        router.add_connection(2, Box::new(dashboard.clone()));
+        (router, editor, dashboard)
+    }
+
+    fn filter(methods: &[&str], track_ids: Option<&[&str]>) -> NotificationFilter {
+        NotificationFilter {
+            methods: Some(methods.iter().map(|m| m.to_string()).collect()),
+            track_ids: track_ids.map(|ids| ids.iter().map(|t| t.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn filtered_delivery() {
+        let (router, editor, dashboard) = router_with_two_sinks();
+        assert!(router.subscribe(2, filter(&["download_progress"], None)));
+        assert!(router.subscribe(1, filter(&["generation_progress"], Some(&["aaa"]))));
+
+        router.notify("download_progress", serde_json::json!({}));
+        router.notify_job("generation_progress", serde_json::json!({}), "aaa", 2);
+        router.notify_job("generation_progress", serde_json::json!({}), "bbb", 2);
+
+        assert_eq!(editor.received(), vec!["generation_progress"]);
+        assert_eq!(dashboard.received(), vec!["download_progress"]);
+    }
+
+    #[test]
+    fn own_terminal_events_bypass_filter() {
+        let (router, editor, dashboard) = router_with_two_sinks();
+        router.subscribe(1, filter(&["download_progress"], None));
+        router.subscribe(2, filter(&["download_progress"], None));
+
+        router.notify_job("generation_complete", serde_json::json!({}), "aaa", 1);
+        router.notify_job("generation_progress", serde_json::json!({}), "aaa", 1);
+
+        // Progress is still filtered; only the terminal event gets through
+        assert_eq!(editor.received(), vec!["generation_complete"]);
+        assert!(dashboard.received().is_empty());
+    }
+
+    #[test]
+    fn unsubscribe_restores_defaults() {
+        let (router, editor, _) = router_with_two_sinks();
+        router.subscribe(1, filter(&[], None));
+        router.notify("download_progress", serde_json::json!({}));
+        assert!(editor.received().is_empty());
+
+        assert!(router.unsubscribe(1));
+        router.notify("download_progress", serde_json::json!({}));
+        assert_eq!(editor.received(), vec!["download_progress"]);
+
+        assert!(!router.unsubscribe(99));
+    }
+
+    #[test]
+    fn validate_rejects_unknown_methods() {
+        assert!(validate_notification_methods(&["generation_error".to_string()]).is_none());
+
+        let error = validate_notification_methods(&["bogus".to_string()]).unwrap();
+        assert!(error.contains("bogus"));
+        for method in NOTIFICATION_METHODS {
+            assert!(error.contains(method));
+        }
+    }
+}
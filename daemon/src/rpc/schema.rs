@@ -0,0 +1,276 @@
+//! JSON Schema of the JSON-RPC protocol.
+//!
+//! The schema is generated from the request, response and notification types
+//! in [`super::types`], so it cannot drift from what the daemon accepts. It is
+//! served by the `get_schema` method and written by `--dump-schema`.
+
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+use crate::config::DaemonConfig;
+use crate::types::Track;
+
+use super::notifications::NOTIFICATION_METHODS;
+use super::types::{
+    BackendStatusParams, CacheStatsParams, CacheStatsResult, CompareTracksParams, CompareTracksResult, DownloadBackendParams, DownloadBackendResult,
+    DownloadCompleteParams, DownloadProgressParams, ExportManifestParams, ExportManifestResult,
+    GenerateFromManifestParams, GenerateParams, GenerateResult, GenerationCompleteParams,
+    GenerationErrorParams, GenerationProgressParams, GetBackendsResult, GetHistoryParams,
+    GetHistoryResult, GetNowPlayingResult, GetQueueResult, GetTrackParams, HealthResult, JsonRpcError, ListTracksParams, ListTracksResult, PurgeBackendParams,
+    PruneOrphansParams, PruneOrphansResult, PurgeBackendResult, RateTrackParams, RateTrackResult,
+    SetDefaultBackendParams, SetDefaultBackendResult, StatusResult,
+    SubscribeParams, SubscribeResult,
+};
+
+/// Produces the schema of a type, registering its definitions with the generator.
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+/// Schemas of a method's params and result.
+struct MethodSchema {
+    name: &'static str,
+    /// None for methods that take no params.
+    params: Option<SchemaFn>,
+    result: SchemaFn,
+}
+
+fn schema<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<T>()
+}
+
+/// Params and result of every method in [`super::methods::METHODS`].
+const METHOD_SCHEMAS: &[MethodSchema] = &[
+    MethodSchema {
+        name: "generate",
+        params: Some(schema::<GenerateParams>),
+        result: schema::<GenerateResult>,
+    },
+    MethodSchema {
+        name: "get_backends",
+        params: None,
+        result: schema::<GetBackendsResult>,
+    },
+    MethodSchema {
+        name: "download_backend",
+        params: Some(schema::<DownloadBackendParams>),
+        result: schema::<DownloadBackendResult>,
+    },
+    MethodSchema {
+        name: "purge_backend",
+        params: Some(schema::<PurgeBackendParams>),
+        result: schema::<PurgeBackendResult>,
+    },
+    MethodSchema {
+        name: "prune_orphans",
+        params: Some(schema::<PruneOrphansParams>),
+        result: schema::<PruneOrphansResult>,
+    },
+    MethodSchema {
+        name: "get_cache_stats",
+        params: Some(schema::<CacheStatsParams>),
+        result: schema::<CacheStatsResult>,
+    },
+    MethodSchema {
+        name: "get_queue",
+        params: None,
+        result: schema::<GetQueueResult>,
+    },
+    MethodSchema {
+        name: "list_tracks",
+        params: Some(schema::<ListTracksParams>),
+        result: schema::<ListTracksResult>,
+    },
+    MethodSchema {
+        name: "get_track",
+        params: Some(schema::<GetTrackParams>),
+        result: schema::<Track>,
+    },
+    MethodSchema {
+        name: "rate_track",
+        params: Some(schema::<RateTrackParams>),
+        result: schema::<RateTrackResult>,
+    },
+    MethodSchema {
+        name: "compare_tracks",
+        params: Some(schema::<CompareTracksParams>),
+        result: schema::<CompareTracksResult>,
+    },
+    MethodSchema {
+        name: "export_manifest",
+        params: Some(schema::<ExportManifestParams>),
+        result: schema::<ExportManifestResult>,
+    },
+    MethodSchema {
+        name: "generate_from_manifest",
+        params: Some(schema::<GenerateFromManifestParams>),
+        result: schema::<GenerateResult>,
+    },
+    MethodSchema {
+        name: "get_now_playing",
+        params: None,
+        result: schema::<GetNowPlayingResult>,
+    },
+    MethodSchema {
+        name: "get_history",
+        params: Some(schema::<GetHistoryParams>),
+        result: schema::<GetHistoryResult>,
+    },
+    MethodSchema {
+        name: "get_config",
+        params: None,
+        result: schema::<DaemonConfig>,
+    },
+    MethodSchema {
+        // Only the runtime settings may be given; see DaemonConfig::with_runtime_updates
+        name: "set_config",
+        params: Some(schema::<DaemonConfig>),
+        result: schema::<DaemonConfig>,
+    },
+    MethodSchema {
+        name: "set_default_backend",
+        params: Some(schema::<SetDefaultBackendParams>),
+        result: schema::<SetDefaultBackendResult>,
+    },
+    MethodSchema {
+        name: "subscribe",
+        params: Some(schema::<SubscribeParams>),
+        result: schema::<SubscribeResult>,
+    },
+    MethodSchema {
+        name: "unsubscribe",
+        params: None,
+        result: schema::<SubscribeResult>,
+    },
+    MethodSchema {
+        name: "get_schema",
+        params: None,
+        result: schema::<Value>,
+    },
+    MethodSchema {
+        name: "health",
+        params: None,
+        result: schema::<HealthResult>,
+    },
+    MethodSchema {
+        // Prometheus text exposition format
+        name: "get_metrics",
+        params: None,
+        result: schema::<String>,
+    },
+    MethodSchema {
+        name: "ping",
+        params: None,
+        result: schema::<StatusResult>,
+    },
+    MethodSchema {
+        name: "shutdown",
+        params: None,
+        result: schema::<StatusResult>,
+    },
+];
+
+/// Returns the params schema of a notification method.
+fn notification_schema(method: &str, gen: &mut SchemaGenerator) -> Schema {
+    match method {
+        "generation_progress" => schema::<GenerationProgressParams>(gen),
+        "generation_complete" => schema::<GenerationCompleteParams>(gen),
+        "generation_error" => schema::<GenerationErrorParams>(gen),
+        "download_progress" => schema::<DownloadProgressParams>(gen),
+        "download_complete" => schema::<DownloadCompleteParams>(gen),
+        "backend_status" => schema::<BackendStatusParams>(gen),
+        _ => unreachable!("no schema for notification {}", method),
+    }
+}
+
+/// Builds the schema document of the whole protocol.
+///
+/// `methods` maps each method to its `params` and `result` schemas,
+/// `notifications` maps each notification to its `params` schema, and `error`
+/// describes the error object. Shared types live under `definitions`.
+pub fn schema_document() -> Value {
+    let mut gen = SchemaSettings::draft07().into_generator();
+
+    let mut methods = Map::new();
+    for method in METHOD_SCHEMAS {
+        let params = match method.params {
+            Some(params) => json!(params(&mut gen)),
+            None => json!({ "type": "null" }),
+        };
+        let result = (method.result)(&mut gen);
+        methods.insert(
+            method.name.to_string(),
+            json!({ "params": params, "result": result }),
+        );
+    }
+
+    let mut notifications = Map::new();
+    for method in NOTIFICATION_METHODS {
+        let params = notification_schema(method, &mut gen);
+        notifications.insert(method.to_string(), json!({ "params": params }));
+    }
+
+    let error = schema::<JsonRpcError>(&mut gen);
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "lofi-daemon JSON-RPC protocol",
+        "version": env!("CARGO_PKG_VERSION"),
+        "methods": methods,
+        "notifications": notifications,
+        "error": error,
+        "definitions": gen.take_definitions(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::methods::METHODS;
+
+    #[test]
+    fn schema_covers_every_method_and_notification() {
+        let document = schema_document();
+
+        let methods: Vec<&str> = document["methods"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let mut expected = METHODS.to_vec();
+        expected.sort_unstable();
+        assert_eq!(methods, expected);
+
+        for method in NOTIFICATION_METHODS {
+            assert!(document["notifications"][method]["params"].is_object());
+        }
+        assert_eq!(
+            document["notifications"].as_object().unwrap().len(),
+            NOTIFICATION_METHODS.len()
+        );
+    }
+
+    #[test]
+    fn schema_describes_generate_params() {
+        let document = schema_document();
+        let params = &document["methods"]["generate"]["params"];
+        let name = params["$ref"]
+            .as_str()
+            .unwrap()
+            .trim_start_matches("#/definitions/");
+        let definition = &document["definitions"][name];
+
+        assert_eq!(definition["properties"]["duration_sec"]["type"], "integer");
+        assert!(definition["properties"]["prompt"].is_object());
+        assert!(definition["properties"]["prompt_tags"].is_object());
+    }
+
+    #[test]
+    fn schema_round_trips_through_json() {
+        let document = schema_document();
+        let text = serde_json::to_string_pretty(&document).unwrap();
+        let parsed: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(parsed, document);
+    }
+}
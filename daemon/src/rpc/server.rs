@@ -2,88 +2,576 @@
 //!
 //! Implements the JSON-RPC 2.0 protocol for daemon communication.
 
+use std::collections::{HashMap, HashSet};
 use std::io::{self, BufRead, Write};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::cache::TrackCache;
+use crate::cache::{delete_track_files, DiskUsage, PruneReport, Ratings, TrackCache};
 use crate::config::DaemonConfig;
-use crate::error::Result;
+use crate::error::{DaemonError, Result};
 use crate::generation::GenerationQueue;
-use crate::models::{Backend, LoadedModels};
-use crate::rpc::types::BackendStatus;
+use crate::metrics::Metrics;
+use crate::models::{check_backend_available, Backend, LoadedModels, PendingLoad};
+use crate::rpc::types::{BackendStatus, BackendStatusParams};
+use crate::types::{set_redact_prompts, ConnectionId, Track, STDIO_CONNECTION_ID};
 
-use super::methods::handle_request;
-use super::types::{JsonRpcError, JsonRpcErrorResponse, JsonRpcNotification, JsonRpcRequest};
+use super::health::{spawn_health_server, SharedHealth};
+#[cfg(feature = "metrics-http")]
+use super::metrics_http::spawn_metrics_server;
+use super::methods::{handle_request, METHODS};
+use super::notifications::NotificationRouter;
+use super::types::{
+    HealthResult, JsonRpcError, JsonRpcErrorResponse, JsonRpcNotification, JsonRpcRequest,
+};
 
 /// State shared across all request handlers.
 pub struct ServerState {
     /// Loaded models for generation.
     pub models: LoadedModels,
+    /// Models of the other backend, kept loaded alongside `models` while
+    /// `parallel_backends` is set.
+    pub standby_models: LoadedModels,
+    /// Model load that timed out but is still running, waited on by the next
+    /// load instead of starting another.
+    pub pending_load: Option<PendingLoad>,
     /// Track cache.
     pub cache: TrackCache,
+    /// Per-seed user ratings, kept in the cache directory.
+    pub ratings: Ratings,
     /// Daemon configuration.
     pub config: DaemonConfig,
+    /// Config file that persistent settings are written back to, if any.
+    pub config_path: Option<PathBuf>,
     /// Generation queue for pending jobs.
     pub queue: GenerationQueue,
     /// Flag to signal server shutdown.
     shutdown: Arc<AtomicBool>,
     /// Status of each backend.
     pub backend_status: BackendStatuses,
+    /// When the loaded models were last used for generation.
+    models_last_used: Instant,
+    /// Routes notifications to connections according to their subscriptions.
+    pub notifications: NotificationRouter,
+    /// Connection whose request is currently being handled.
+    pub connection_id: ConnectionId,
+    /// Calls per method in the current rate limit window, with the window start.
+    rate_limits: HashMap<&'static str, (u32, Instant)>,
+    /// Backends downloaded since they were last loaded whose cached tracks
+    /// are removed on the next load.
+    updated_backends: HashSet<Backend>,
+    /// When the server state was created, for reporting uptime.
+    started_at: Instant,
+    /// Health report read by the HTTP health endpoint, if it is running.
+    published_health: Option<SharedHealth>,
+    /// Generation and queue metrics, also read by the HTTP metrics endpoint.
+    pub metrics: Metrics,
+    /// Whether the HTTP metrics endpoint is running.
+    metrics_served: bool,
+    /// Whether another daemon instance owns the cache, so this one must not
+    /// write to it.
+    cache_read_only: bool,
+    /// Last scan of the cache directory and when it was taken.
+    disk_usage: Option<(Instant, DiskUsage)>,
 }
 
+/// Length of the window over which calls are counted for rate limiting.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Methods that are never rate limited, so a client can always stop the
+/// daemon or check that it is alive.
+const RATE_LIMIT_EXEMPT: &[&str] = &["shutdown", "ping"];
+
+/// How long a scan of the cache directory is reused by
+/// [`ServerState::cache_disk_usage`].
+pub const DISK_USAGE_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// How often the server checks for idle models while waiting for requests.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Status tracking for each backend.
+///
+/// Updated by the downloader, the model loader and generation; see
+/// [`BackendStatus`] for the lifecycle.
+#[derive(Debug, Default)]
 pub struct BackendStatuses {
-    pub musicgen: BackendStatus,
-    pub ace_step: BackendStatus,
+    musicgen: BackendState,
+    ace_step: BackendState,
 }
 
-impl Default for BackendStatuses {
-    fn default() -> Self {
-        Self {
-            musicgen: BackendStatus::NotInstalled,
-            ace_step: BackendStatus::NotInstalled,
-        }
-    }
+/// Lifecycle state of one backend.
+#[derive(Debug, Default)]
+struct BackendState {
+    status: BackendStatus,
+    /// Why the last download or load failed, kept while in `Error`.
+    error_detail: Option<String>,
+    /// Jobs currently generating.
+    generating: usize,
 }
 
 impl BackendStatuses {
-    /// Gets the status for a specific backend.
-    pub fn get(&self, backend: Backend) -> BackendStatus {
+    fn state(&self, backend: Backend) -> &BackendState {
         match backend {
-            Backend::MusicGen => self.musicgen,
-            Backend::AceStep => self.ace_step,
+            Backend::MusicGen => &self.musicgen,
+            Backend::AceStep => &self.ace_step,
         }
     }
 
-    /// Sets the status for a specific backend.
-    pub fn set(&mut self, backend: Backend, status: BackendStatus) {
+    fn state_mut(&mut self, backend: Backend) -> &mut BackendState {
         match backend {
-            Backend::MusicGen => self.musicgen = status,
-            Backend::AceStep => self.ace_step = status,
+            Backend::MusicGen => &mut self.musicgen,
+            Backend::AceStep => &mut self.ace_step,
         }
     }
+
+    /// Gets the status for a specific backend.
+    pub fn get(&self, backend: Backend) -> BackendStatus {
+        self.state(backend).status
+    }
+
+    /// Sets the status for a specific backend, clearing any error detail.
+    pub fn set(&mut self, backend: Backend, status: BackendStatus) {
+        let state = self.state_mut(backend);
+        state.status = status;
+        state.error_detail = None;
+    }
+
+    /// Puts a backend into the `Error` status with the reason.
+    pub fn set_error(&mut self, backend: Backend, detail: impl Into<String>) {
+        let state = self.state_mut(backend);
+        state.status = BackendStatus::Error;
+        state.error_detail = Some(detail.into());
+    }
+
+    /// Returns why the backend is in the `Error` status, if it is.
+    pub fn error_detail(&self, backend: Backend) -> Option<&str> {
+        self.state(backend).error_detail.as_deref()
+    }
+
+    /// Returns the number of jobs generating with the backend.
+    pub fn generating(&self, backend: Backend) -> usize {
+        self.state(backend).generating
+    }
+
+    /// Records that a job started generating with the backend.
+    pub fn begin_job(&mut self, backend: Backend) {
+        self.state_mut(backend).generating += 1;
+    }
+
+    /// Records that a job finished generating with the backend.
+    pub fn end_job(&mut self, backend: Backend) {
+        let state = self.state_mut(backend);
+        state.generating = state.generating.saturating_sub(1);
+    }
 }
 
 impl ServerState {
     /// Creates new server state.
     pub fn new(config: DaemonConfig) -> Self {
-        Self {
+        let mut state = Self {
             models: LoadedModels::None,
+            standby_models: LoadedModels::None,
+            pending_load: None,
             cache: TrackCache::new(),
-            config,
+            ratings: Ratings::default(),
+            config: DaemonConfig::default(),
+            config_path: None,
             queue: GenerationQueue::new(),
             shutdown: Arc::new(AtomicBool::new(false)),
             backend_status: BackendStatuses::default(),
+            models_last_used: Instant::now(),
+            notifications: NotificationRouter::stdio(),
+            connection_id: STDIO_CONNECTION_ID,
+            rate_limits: HashMap::new(),
+            updated_backends: HashSet::new(),
+            started_at: Instant::now(),
+            published_health: None,
+            metrics_served: false,
+            metrics: Metrics::new(),
+            cache_read_only: false,
+            disk_usage: None,
+        };
+        state.apply_config(config);
+        state.detect_installed_backends();
+        state
+    }
+
+    /// Sets the config file that persistent settings are written back to.
+    pub fn with_config_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
+    /// Makes the track cache read-only, for an instance started with
+    /// `--allow-multiple` while another instance holds the cache lock.
+    ///
+    /// Cached tracks can still be listed and played, but generating new
+    /// tracks, rating, purging and pruning fail with CACHE_READ_ONLY.
+    pub fn with_read_only_cache(mut self) -> Self {
+        self.cache_read_only = true;
+        self
+    }
+
+    /// Returns true if the track cache is read-only for this instance.
+    pub fn is_cache_read_only(&self) -> bool {
+        self.cache_read_only
+    }
+
+    /// Returns CACHE_READ_ONLY if the cache is read-only, naming `action`
+    /// as what was refused.
+    pub fn ensure_cache_writable(&self, action: &str) -> Result<()> {
+        if self.cache_read_only {
+            return Err(DaemonError::cache_read_only(action));
         }
+        Ok(())
+    }
+
+    /// Replaces the configuration and pushes runtime settings to subsystems.
+    ///
+    /// The cache re-evaluates its byte budget (evicting tracks if needed) and
+    /// the queue adopts the new capacity and scheduling policy. Standby models
+    /// are unloaded when `parallel_backends` is turned off. Returns the
+    /// tracks evicted from the cache.
+    pub fn apply_config(&mut self, config: DaemonConfig) -> Vec<Track> {
+        if !config.parallel_backends {
+            self.unload_standby();
+        }
+        set_redact_prompts(config.redact_prompts);
+        self.queue.set_max_size(config.max_queue_size);
+        self.queue.set_policy(config.queue_policy, config.max_starvation_sec);
+        let evicted = self.cache.set_max_bytes(config.cache_max_bytes);
+        self.config = config;
+        evicted
+    }
+
+    /// Changes the backend used when a generate request does not name one.
+    ///
+    /// If a config file path is known the change is written to it first and
+    /// nothing is applied if writing fails. Only the backend is written on top
+    /// of the file's contents, so settings taken from LOFI_* variables stay
+    /// out of the file. Returns true if it was persisted.
+    pub fn set_default_backend(&mut self, backend: Backend) -> std::result::Result<bool, String> {
+        let persisted = match self.config_path {
+            Some(ref path) => {
+                let mut file_config = if path.exists() {
+                    DaemonConfig::load(path)?
+                } else {
+                    DaemonConfig::default()
+                };
+                file_config.default_backend = backend;
+                file_config.save(path)?;
+                true
+            }
+            None => false,
+        };
+        let mut config = self.config.clone();
+        config.default_backend = backend;
+        self.apply_config(config);
+        Ok(persisted)
     }
 
     /// Sets the loaded models.
+    ///
+    /// The backend becomes `Loaded` and a previously loaded backend `Ready`,
+    /// unless `parallel_backends` is set: then the previous backend's models
+    /// become the standby models and stay loaded. If the backend's models
+    /// were downloaded since it was last loaded and `invalidate_on_model_update`
+    /// was set, its cached tracks are purged.
     pub fn set_models(&mut self, models: LoadedModels) {
+        let previous = std::mem::take(&mut self.models);
+        let keep_previous = self.config.parallel_backends
+            && previous.backend().is_some()
+            && previous.backend() != models.backend();
+        if keep_previous || self.standby_models.backend() == models.backend() {
+            self.unload_standby();
+        }
+        if keep_previous {
+            self.standby_models = previous;
+        } else {
+            self.mark_unloaded(previous.backend());
+        }
         if let Some(backend) = models.backend() {
-            self.backend_status.set(backend, BackendStatus::Ready);
+            self.set_backend_status(backend, BackendStatus::Loaded);
+            self.purge_if_updated(backend);
         }
         self.models = models;
+        self.mark_models_used();
+    }
+
+    /// Sets the standby models, which generate alongside `models` while
+    /// `parallel_backends` is set.
+    ///
+    /// The backend becomes `Loaded`; see [`set_models`](Self::set_models).
+    pub fn set_standby_models(&mut self, models: LoadedModels) {
+        self.unload_standby();
+        if let Some(backend) = models.backend() {
+            self.set_backend_status(backend, BackendStatus::Loaded);
+            self.purge_if_updated(backend);
+        }
+        self.standby_models = models;
+        self.mark_models_used();
+    }
+
+    /// Makes the standby models the active ones if they are for `backend`,
+    /// keeping the active models on standby.
+    ///
+    /// Returns true if the models were swapped.
+    pub fn activate_standby(&mut self, backend: Backend) -> bool {
+        if self.standby_models.backend() != Some(backend) {
+            return false;
+        }
+        std::mem::swap(&mut self.models, &mut self.standby_models);
+        true
+    }
+
+    /// Returns the loaded models for `backend`, active or on standby.
+    pub fn loaded_models(&self, backend: Backend) -> Option<&LoadedModels> {
+        [&self.models, &self.standby_models]
+            .into_iter()
+            .find(|models| models.backend() == Some(backend))
+    }
+
+    /// Drops the standby models, if any.
+    fn unload_standby(&mut self) {
+        let standby = std::mem::take(&mut self.standby_models);
+        self.mark_unloaded(standby.backend());
+    }
+
+    /// Marks `backend` as loading.
+    pub fn begin_loading(&mut self, backend: Backend) {
+        self.set_backend_status(backend, BackendStatus::Loading);
+    }
+
+    /// Records that loading `backend` failed; it stays in `Error` until a
+    /// later load or download succeeds.
+    pub fn loading_failed(&mut self, backend: Backend, error: &DaemonError) {
+        eprintln!("Failed to load {} models: {}", backend, error.message);
+        self.set_backend_error(backend, error.message.clone());
+    }
+
+    /// Returns a loaded backend's status to `Ready` when its models are dropped.
+    fn mark_unloaded(&mut self, backend: Option<Backend>) {
+        if let Some(backend) = backend {
+            if self.backend_status.get(backend) == BackendStatus::Loaded {
+                self.set_backend_status(backend, BackendStatus::Ready);
+            }
+        }
+    }
+
+    /// Returns the status of `backend`, as reported by `get_backends`.
+    pub fn get_backend_status(&self, backend: Backend) -> BackendStatus {
+        self.backend_status.get(backend)
+    }
+
+    /// Sets the status of `backend`, clearing any error detail, and sends a
+    /// `backend_status` notification if that changed anything.
+    pub fn set_backend_status(&mut self, backend: Backend, status: BackendStatus) {
+        let changed = self.backend_status.get(backend) != status
+            || self.backend_status.error_detail(backend).is_some();
+        self.backend_status.set(backend, status);
+        if changed {
+            self.notify_backend_status(backend);
+        }
+    }
+
+    /// Puts `backend` into the `Error` status with the reason and sends a
+    /// `backend_status` notification.
+    pub fn set_backend_error(&mut self, backend: Backend, detail: impl Into<String>) {
+        self.backend_status.set_error(backend, detail);
+        self.notify_backend_status(backend);
+    }
+
+    /// Records that a job started generating with `backend` and sends a
+    /// `backend_status` notification with the new active job count.
+    pub fn begin_backend_job(&mut self, backend: Backend) {
+        self.backend_status.begin_job(backend);
+        self.notify_backend_status(backend);
+    }
+
+    /// Records that a job finished generating with `backend` and sends a
+    /// `backend_status` notification with the new active job count.
+    pub fn end_backend_job(&mut self, backend: Backend) {
+        self.backend_status.end_job(backend);
+        self.notify_backend_status(backend);
+    }
+
+    /// Sends the current status of `backend` as a `backend_status`
+    /// notification.
+    ///
+    /// Downloads, loads and generations run inside a single request, so
+    /// notifications are the only way clients see them in progress.
+    fn notify_backend_status(&self, backend: Backend) {
+        self.notifications.notify(
+            "backend_status",
+            BackendStatusParams {
+                backend: backend.as_str().to_string(),
+                status: self.backend_status.get(backend),
+                error_detail: self.backend_status.error_detail(backend).map(str::to_string),
+                active_jobs: self.active_jobs(backend),
+            },
+        );
+    }
+
+    /// Marks backends whose model files are on disk as `Ready` and the rest as
+    /// `NotInstalled`.
+    ///
+    /// Downloading, loading, loaded and failed backends keep their status.
+    pub fn detect_installed_backends(&mut self) {
+        for backend in [Backend::MusicGen, Backend::AceStep] {
+            if !matches!(
+                self.get_backend_status(backend),
+                BackendStatus::Ready | BackendStatus::NotInstalled
+            ) {
+                continue;
+            }
+            let model_dir = match backend {
+                Backend::MusicGen => self.config.effective_model_path(),
+                Backend::AceStep => self.config.effective_ace_step_model_path(),
+            };
+            let installed =
+                check_backend_available(backend, &model_dir, self.config.tokenizer_override(backend));
+            let status = if installed {
+                BackendStatus::Ready
+            } else {
+                BackendStatus::NotInstalled
+            };
+            self.set_backend_status(backend, status);
+        }
+    }
+
+    /// Returns the number of jobs for `backend` that are generating or queued.
+    pub fn active_jobs(&self, backend: Backend) -> usize {
+        self.backend_status.generating(backend) + self.queue.count_for_backend(backend)
+    }
+
+    /// Purges cached tracks of `backend` if its models were updated since it
+    /// was last loaded. Returns the removed tracks.
+    fn purge_if_updated(&mut self, backend: Backend) -> Vec<Track> {
+        if self.cache_read_only || !self.updated_backends.remove(&backend) {
+            return Vec::new();
+        }
+        let purged = self.purge_backend(backend);
+        eprintln!(
+            "Removed {} cached {} tracks generated with previous models",
+            purged.len(),
+            backend
+        );
+        purged
+    }
+
+    /// Records a completed model download for `backend`.
+    ///
+    /// With `invalidate_on_model_update` set, the backend's cached tracks are
+    /// purged the next time its models are loaded.
+    pub fn mark_models_updated(&mut self, backend: Backend) {
+        if self.config.invalidate_on_model_update {
+            self.updated_backends.insert(backend);
+        }
+    }
+
+    /// Removes every cached track generated by `backend` and deletes its files.
+    ///
+    /// Returns the removed tracks.
+    pub fn purge_backend(&mut self, backend: Backend) -> Vec<Track> {
+        let removed = self.cache.invalidate_backend(backend);
+        for track in &removed {
+            delete_track_files(track);
+        }
+        removed
+    }
+
+    /// Deletes WAV files in the cache directory that no cached track refers to.
+    ///
+    /// See [`TrackCache::prune_orphans`]. Only a dry run is allowed while the
+    /// cache is read-only.
+    pub fn prune_orphans(&self, dry_run: bool) -> Result<PruneReport> {
+        if !dry_run {
+            self.ensure_cache_writable("prune orphaned tracks")?;
+        }
+        self.cache.prune_orphans(&self.config.effective_cache_path(), dry_run)
+    }
+
+    /// Returns the disk usage of the cache directory and the age of the scan.
+    ///
+    /// See [`TrackCache::disk_usage`]. A scan is reused for
+    /// [`DISK_USAGE_MAX_AGE`] unless `refresh` is set, so clients can poll
+    /// cache stats cheaply.
+    pub fn cache_disk_usage(&mut self, refresh: bool) -> Result<(DiskUsage, Duration)> {
+        if let Some((scanned_at, usage)) = &self.disk_usage {
+            if !refresh && scanned_at.elapsed() < DISK_USAGE_MAX_AGE {
+                return Ok((usage.clone(), scanned_at.elapsed()));
+            }
+        }
+        let usage = self.cache.disk_usage(&self.config.effective_cache_path())?;
+        self.disk_usage = Some((Instant::now(), usage.clone()));
+        Ok((usage, Duration::ZERO))
+    }
+
+    /// Records that the loaded models were just used.
+    pub fn mark_models_used(&mut self) {
+        self.models_last_used = Instant::now();
+    }
+
+    /// Unloads models that have been idle longer than `model_idle_timeout_sec`.
+    ///
+    /// Returns true if models were unloaded.
+    pub fn unload_if_idle(&mut self) -> bool {
+        let Some(timeout_sec) = self.config.model_idle_timeout_sec else {
+            return false;
+        };
+        if self.models.is_none()
+            || self.models_last_used.elapsed() < Duration::from_secs(timeout_sec)
+        {
+            return false;
+        }
+
+        eprintln!("Unloading models after {}s idle", timeout_sec);
+        self.unload_standby();
+        let models = std::mem::take(&mut self.models);
+        self.mark_unloaded(models.backend());
+        true
+    }
+
+    /// Records a call to `method` and checks it against the configured limits.
+    ///
+    /// Calls are counted per method over a 60 second window that starts with the
+    /// first call after the previous window expired. Limits are global since
+    /// stdio has a single client. Returns an error once the limit is exceeded.
+    ///
+    /// Takes one of the [`METHODS`] names, so the table never holds more
+    /// entries than there are methods. Methods in `RATE_LIMIT_EXEMPT` are
+    /// always allowed.
+    pub fn check_rate_limit(&mut self, method: &'static str) -> std::result::Result<(), JsonRpcError> {
+        if RATE_LIMIT_EXEMPT.contains(&method) {
+            return Ok(());
+        }
+
+        let limit = if method == "generate" {
+            self.config.rate_limit_generate
+        } else {
+            self.config.rate_limit_other
+        };
+
+        let now = Instant::now();
+        let (count, window_start) = self
+            .rate_limits
+            .entry(method)
+            .or_insert((0, now));
+        if now.duration_since(*window_start) >= RATE_LIMIT_WINDOW {
+            *count = 0;
+            *window_start = now;
+        }
+
+        if *count >= limit {
+            return Err(JsonRpcError::rate_limit_exceeded(method, limit));
+        }
+        *count += 1;
+        Ok(())
     }
 
     /// Signals the server to shut down.
@@ -96,31 +584,110 @@ impl ServerState {
         self.shutdown.load(Ordering::SeqCst)
     }
 
+    /// Returns the daemon's health without touching models or the disk.
+    ///
+    /// The daemon is unhealthy once shutdown has been requested or while its
+    /// queue is full.
+    pub fn health(&self) -> HealthResult {
+        let healthy = !self.is_shutdown() && !self.queue.is_full();
+        HealthResult {
+            status: if healthy { "healthy" } else { "unhealthy" }.to_string(),
+            uptime_sec: self.started_at.elapsed().as_secs_f32(),
+            backend_loaded: !self.models.is_none(),
+            queue_depth: self.queue.len(),
+            cache_size: self.cache.len(),
+        }
+    }
+
+    /// Starts the HTTP health endpoint (`GET /health`) on `addr`.
+    ///
+    /// Returns the bound address.
+    pub fn serve_health(&mut self, addr: impl ToSocketAddrs) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let health = Arc::new(Mutex::new(self.health()));
+        spawn_health_server(listener, health.clone(), self.started_at);
+        self.published_health = Some(health);
+        Ok(local_addr)
+    }
+
+    /// Starts the HTTP metrics endpoint (`GET /metrics`) on `addr`.
+    ///
+    /// Returns the bound address.
+    #[cfg(feature = "metrics-http")]
+    pub fn serve_metrics(&mut self, addr: SocketAddr) -> io::Result<SocketAddr> {
+        self.refresh_metrics();
+        let local_addr = spawn_metrics_server(addr, self.metrics.clone())?;
+        self.metrics_served = true;
+        Ok(local_addr)
+    }
+
+    /// Updates the report and metrics served over HTTP, for the endpoints
+    /// that are running.
+    pub fn publish_health(&self) {
+        if let Some(published) = &self.published_health {
+            *published.lock().unwrap_or_else(|e| e.into_inner()) = self.health();
+        }
+        if self.metrics_served {
+            self.refresh_metrics();
+        }
+    }
+
+    /// Sets the metrics gauges from the queue, cache and loaded models.
+    pub fn refresh_metrics(&self) {
+        let loaded: Vec<Backend> = [self.models.backend(), self.standby_models.backend()]
+            .into_iter()
+            .flatten()
+            .collect();
+        self.metrics
+            .set_gauges(self.queue.len(), self.cache.total_size_bytes(), &loaded);
+    }
+
     /// Returns true if a specific backend is ready for generation.
     pub fn is_backend_ready(&self, backend: Backend) -> bool {
-        self.backend_status.get(backend) == BackendStatus::Ready
+        matches!(
+            self.backend_status.get(backend),
+            BackendStatus::Ready | BackendStatus::Loaded
+        )
     }
 }
 
 /// Runs the JSON-RPC server, reading from stdin and writing to stdout.
 pub fn run_server(mut state: ServerState) -> Result<()> {
-    let stdin = io::stdin();
     let mut stdout = io::stdout();
-    let reader = stdin.lock();
+
+    // Read stdin on a separate thread so idle models can be unloaded between requests
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
 
     eprintln!("JSON-RPC server started, waiting for requests...");
 
-    for line in reader.lines() {
-        let line = match line {
-            Ok(l) => l,
-            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+    loop {
+        let line = match rx.recv_timeout(IDLE_CHECK_INTERVAL) {
+            Ok(Ok(l)) => l,
+            Ok(Err(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
                 eprintln!("Stdin closed (EOF), shutting down gracefully...");
                 break;
             }
-            Err(e) => {
+            Ok(Err(e)) => {
                 eprintln!("Error reading stdin: {}", e);
                 break;
             }
+            Err(RecvTimeoutError::Timeout) => {
+                state.unload_if_idle();
+                state.publish_health();
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                eprintln!("Stdin closed (EOF), shutting down gracefully...");
+                break;
+            }
         };
 
         // Skip empty lines
@@ -137,6 +704,8 @@ pub fn run_server(mut state: ServerState) -> Result<()> {
             stdout.flush().ok();
         }
 
+        state.publish_health();
+
         // Check for shutdown
         if state.is_shutdown() {
             eprintln!("Server shutdown requested");
@@ -165,32 +734,49 @@ fn process_request(line: &str, state: &mut ServerState) -> Option<String> {
     // Validate JSON-RPC version
     if request.jsonrpc != "2.0" {
         let error = JsonRpcErrorResponse::new(
-            Some(request.id),
+            request.id,
             JsonRpcError::invalid_request("Invalid JSON-RPC version (expected 2.0)"),
         );
         return Some(serde_json::to_string(&error).unwrap_or_default());
     }
 
-    // Handle the request
-    let result = handle_request(&request.method, request.params.clone(), state);
+    // Without an ID the client could not match the response to the request
+    let Some(id) = request.id else {
+        let error = JsonRpcErrorResponse::new(
+            None,
+            JsonRpcError::invalid_request("Missing request id (notifications are not supported)"),
+        );
+        return Some(serde_json::to_string(&error).unwrap_or_default());
+    };
+
+    // Route first, so unknown methods are rejected without being counted,
+    // then handle the request unless the method is over its rate limit
+    let result = match METHODS.iter().find(|m| **m == request.method) {
+        Some(method) => state
+            .check_rate_limit(method)
+            .and_then(|()| handle_request(method, request.params.clone(), state)),
+        None => Err(JsonRpcError::method_not_found(&request.method)),
+    };
 
     match result {
         Ok(response) => Some(
             serde_json::to_string(&serde_json::json!({
                 "jsonrpc": "2.0",
-                "id": request.id,
+                "id": id,
                 "result": response
             }))
             .unwrap_or_default(),
         ),
         Err(error) => Some(
-            serde_json::to_string(&JsonRpcErrorResponse::new(Some(request.id), error))
+            serde_json::to_string(&JsonRpcErrorResponse::new(Some(id), error))
                 .unwrap_or_default(),
         ),
     }
 }
 
 /// Sends a JSON-RPC notification to stdout.
+///
+/// Bypasses subscription filters; request handlers use [`ServerState::notifications`].
 pub fn send_notification<T: serde::Serialize>(method: &'static str, params: T) {
     let notification = JsonRpcNotification::new(method, params);
     if let Ok(json) = serde_json::to_string(&notification) {
@@ -203,6 +789,9 @@ pub fn send_notification<T: serde::Serialize>(method: &'static str, params: T) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::generation::QueuePolicy;
+    use crate::rpc::methods::ensure_backend_loaded;
+    use crate::rpc::notifications::RecordingSink;
 
     fn test_config() -> DaemonConfig {
         DaemonConfig::default()
@@ -251,6 +840,233 @@ mod tests {
         assert!(response.contains("-32601")); // Method not found
     }
 
+    #[test]
+    fn string_ids_are_echoed() {
+        let mut state = ServerState::new(test_config());
+        let request = r#"{"jsonrpc":"2.0","method":"ping","id":"abc"}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&process_request(request, &mut state).unwrap()).unwrap();
+        assert_eq!(response["id"], "abc");
+        assert_eq!(response["result"]["status"], "ok");
+
+        let request = r#"{"jsonrpc":"2.0","method":"unknown","id":"abc"}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&process_request(request, &mut state).unwrap()).unwrap();
+        assert_eq!(response["id"], "abc");
+        assert_eq!(response["error"]["code"], -32601);
+    }
+
+    #[test]
+    fn requests_without_id_are_rejected_with_null_id() {
+        let mut state = ServerState::new(test_config());
+        for request in [
+            r#"{"jsonrpc":"2.0","method":"ping","id":null}"#,
+            r#"{"jsonrpc":"2.0","method":"ping"}"#,
+        ] {
+            let response: serde_json::Value =
+                serde_json::from_str(&process_request(request, &mut state).unwrap()).unwrap();
+            assert!(response["id"].is_null());
+            assert_eq!(response["error"]["code"], -32600);
+        }
+    }
+
+    #[test]
+    fn process_rate_limits_generate() {
+        let mut state = ServerState::new(test_config());
+        let request = r#"{"jsonrpc":"2.0","method":"generate","params":{"prompt":""},"id":1}"#;
+
+        let responses: Vec<String> = (0..15)
+            .map(|_| process_request(request, &mut state).unwrap())
+            .collect();
+
+        // The first 10 are processed (and rejected for the empty prompt)
+        assert!(responses[..10].iter().all(|r| r.contains("-32006")));
+        assert!(responses[10..].iter().all(|r| r.contains("-32029")));
+        assert!(responses[10].contains("Rate limit exceeded"));
+    }
+
+    #[test]
+    fn rate_limits_are_counted_per_method() {
+        let mut state = ServerState::new(test_config());
+        state.config.rate_limit_other = 2;
+        let get_queue = r#"{"jsonrpc":"2.0","method":"get_queue","id":1}"#;
+
+        assert!(process_request(get_queue, &mut state).unwrap().contains("result"));
+        assert!(process_request(get_queue, &mut state).unwrap().contains("result"));
+        assert!(process_request(get_queue, &mut state).unwrap().contains("-32029"));
+
+        // Other methods have their own budget
+        assert!(state.check_rate_limit("list_tracks").is_ok());
+    }
+
+    #[test]
+    fn unknown_and_exempt_methods_are_not_rate_limited() {
+        let mut state = ServerState::new(test_config());
+        state.config.rate_limit_other = 1;
+
+        for i in 0..5 {
+            let request = format!(r#"{{"jsonrpc":"2.0","method":"made_up_{}","id":1}}"#, i);
+            assert!(process_request(&request, &mut state).unwrap().contains("-32601"));
+        }
+        assert!(state.rate_limits.is_empty());
+
+        let ping = r#"{"jsonrpc":"2.0","method":"ping","id":1}"#;
+        for _ in 0..3 {
+            assert!(process_request(ping, &mut state).unwrap().contains("ok"));
+        }
+        assert!(state.check_rate_limit("shutdown").is_ok());
+        assert!(state.check_rate_limit("shutdown").is_ok());
+        assert!(state.rate_limits.is_empty());
+    }
+
+    #[test]
+    fn health_reports_inconsistent_state() {
+        let mut config = test_config();
+        config.max_queue_size = 1;
+        let mut state = ServerState::new(config);
+
+        let health = state.health();
+        assert!(health.is_healthy());
+        assert!(!health.backend_loaded);
+        assert_eq!(health.queue_depth, 0);
+        assert_eq!(health.cache_size, 0);
+
+        let job = crate::types::GenerationJob::new(
+            "rain".to_string(),
+            10,
+            Some(1),
+            crate::types::JobPriority::Normal,
+            "v1",
+        );
+        state.queue.add(job).unwrap();
+        let health = state.health();
+        assert_eq!(health.status, "unhealthy");
+        assert_eq!(health.queue_depth, 1);
+
+        let state = ServerState::new(test_config());
+        state.shutdown();
+        assert!(!state.health().is_healthy());
+    }
+
+    #[test]
+    fn health_endpoint_serves_published_report() {
+        use std::io::Read;
+
+        let get_health = |addr: SocketAddr| {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            stream.write_all(b"GET /health HTTP/1.1\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let mut state = ServerState::new(test_config());
+        let addr = state.serve_health("127.0.0.1:0").unwrap();
+        let response = get_health(addr);
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"status\":\"healthy\""));
+
+        // Changes are visible once the request loop publishes them
+        state.shutdown();
+        assert!(get_health(addr).starts_with("HTTP/1.1 200 OK"));
+        state.publish_health();
+        let response = get_health(addr);
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+        assert!(response.contains("\"status\":\"unhealthy\""));
+    }
+
+    #[cfg(feature = "metrics-http")]
+    #[test]
+    fn metrics_endpoint_matches_get_metrics() {
+        use std::io::Read;
+
+        let mut state = ServerState::new(test_config());
+        let addr = state.serve_metrics("127.0.0.1:0".parse().unwrap()).unwrap();
+        state.metrics.record(Backend::AceStep, crate::metrics::GenerationOutcome::Started);
+        state.metrics.record_completed(Backend::AceStep, 30.0, 60.0, 0);
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let rpc = handle_request("get_metrics", serde_json::Value::Null, &mut state).unwrap();
+        assert_eq!(body, rpc.as_str().unwrap());
+        assert!(body.contains("lofi_generations_total{backend=\"ace_step\",status=\"completed\"} 1\n"));
+    }
+
+    #[test]
+    fn apply_config_updates_subsystems() {
+        let mut state = ServerState::new(test_config());
+        let mut config = test_config();
+        config.max_queue_size = 3;
+        config.cache_max_bytes = Some(4096);
+        config.queue_policy = QueuePolicy::BackendAffinity;
+
+        let evicted = state.apply_config(config);
+        assert!(evicted.is_empty());
+        assert_eq!(state.queue.max_size(), 3);
+        assert_eq!(state.queue.policy(), QueuePolicy::BackendAffinity);
+        assert_eq!(state.cache.max_bytes(), Some(4096));
+    }
+
+    #[test]
+    fn unload_if_idle_requires_timeout() {
+        let mut state = ServerState::new(test_config());
+        assert!(!state.unload_if_idle());
+
+        state.config.model_idle_timeout_sec = Some(1);
+        // Nothing loaded, nothing to unload
+        assert!(!state.unload_if_idle());
+    }
+
+    #[test]
+    fn backend_status_follows_load_cycle() {
+        let musicgen_dir = tempfile::tempdir().unwrap();
+        for file in crate::models::musicgen::REQUIRED_MODEL_FILES {
+            std::fs::write(musicgen_dir.path().join(file), b"").unwrap();
+        }
+        let mut config = test_config();
+        config.model_path = Some(musicgen_dir.path().to_path_buf());
+        config.ace_step_model_path = Some(musicgen_dir.path().join("missing"));
+        let mut state = ServerState::new(config);
+        let sink = RecordingSink::default();
+        state.notifications = NotificationRouter::new();
+        state.notifications.add_connection(state.connection_id, Box::new(sink.clone()));
+
+        // Model files are only checked when the state is created
+        assert_eq!(state.get_backend_status(Backend::MusicGen), BackendStatus::Ready);
+        assert_eq!(state.get_backend_status(Backend::AceStep), BackendStatus::NotInstalled);
+
+        // A model file goes missing after detection, so the load fails
+        std::fs::remove_file(musicgen_dir.path().join("decoder_model.onnx")).unwrap();
+        let error = ensure_backend_loaded(&mut state, Backend::MusicGen).unwrap_err();
+        assert!(error.message.contains("decoder_model.onnx"), "{}", error.message);
+        assert!(state.models.is_none());
+
+        // Not loaded, then loading, then error, as reported while it happened
+        let statuses: Vec<serde_json::Value> = sink
+            .received()
+            .iter()
+            .filter(|n| n["method"] == "backend_status")
+            .map(|n| n["params"]["status"].clone())
+            .collect();
+        assert_eq!(statuses, ["loading", "error"]);
+        let value = handle_request("get_backends", serde_json::Value::Null, &mut state).unwrap();
+        assert_eq!(value["backends"][0]["status"], "error");
+        assert!(value["backends"][0]["error_detail"]
+            .as_str()
+            .unwrap()
+            .contains("decoder_model.onnx"));
+
+        // Detection leaves failed backends alone
+        state.detect_installed_backends();
+        assert_eq!(state.get_backend_status(Backend::MusicGen), BackendStatus::Error);
+    }
+
     #[test]
     fn backend_statuses() {
         let mut statuses = BackendStatuses::default();
@@ -260,5 +1076,54 @@ mod tests {
         statuses.set(Backend::MusicGen, BackendStatus::Ready);
         assert_eq!(statuses.get(Backend::MusicGen), BackendStatus::Ready);
         assert_eq!(statuses.get(Backend::AceStep), BackendStatus::NotInstalled);
+
+        // The error detail lasts until the status changes again
+        statuses.set_error(Backend::AceStep, "missing vocoder.onnx");
+        assert_eq!(statuses.get(Backend::AceStep), BackendStatus::Error);
+        assert_eq!(statuses.error_detail(Backend::AceStep), Some("missing vocoder.onnx"));
+        statuses.set(Backend::AceStep, BackendStatus::Loading);
+        assert!(statuses.error_detail(Backend::AceStep).is_none());
+
+        statuses.begin_job(Backend::MusicGen);
+        assert_eq!(statuses.generating(Backend::MusicGen), 1);
+        statuses.end_job(Backend::MusicGen);
+        statuses.end_job(Backend::MusicGen);
+        assert_eq!(statuses.generating(Backend::MusicGen), 0);
+    }
+
+    fn cached_track(backend: Backend) -> Track {
+        Track::new(
+            PathBuf::from("/nonexistent/track.wav"),
+            "rain".to_string(),
+            10.0,
+            1,
+            "v1".to_string(),
+            backend,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn download_invalidates_tracks_on_next_load() {
+        let mut state = ServerState::new(test_config());
+        state.cache.put(cached_track(Backend::MusicGen));
+
+        // Disabled by default
+        state.mark_models_updated(Backend::MusicGen);
+        assert!(state.updated_backends.is_empty());
+
+        state.config.invalidate_on_model_update = true;
+        state.mark_models_updated(Backend::MusicGen);
+        state.mark_models_updated(Backend::AceStep);
+        state.cache.put(cached_track(Backend::AceStep));
+        assert_eq!(state.cache.len(), 2);
+
+        assert_eq!(state.purge_if_updated(Backend::AceStep).len(), 1);
+        assert_eq!(state.cache.len(), 1);
+        // Only the first load after a download purges
+        state.cache.put(cached_track(Backend::AceStep));
+        assert!(state.purge_if_updated(Backend::AceStep).is_empty());
+        assert_eq!(state.cache.len(), 2);
+        assert!(state.updated_backends.contains(&Backend::MusicGen));
     }
 }
@@ -2,9 +2,26 @@
 //!
 //! Implements the contracts defined in contracts/generate.json, notifications.json, and errors.json.
 
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::audio::{AudioStats, Reverb, ReverbPreset, SilenceTrim, OUTPUT_SAMPLE_RATES};
+use crate::cache::NowPlaying;
+use crate::config::{AceStepConfig, PromptSanitization, MAX_NOTIFY_INTERVAL_MS};
+use crate::error::{DaemonError, ErrorCode};
+use crate::generation::PromptTags;
+use crate::models::ace_step::{
+    blend_label, normalize_blend, MAX_GUIDANCE_SCALE, MAX_INFERENCE_STEPS, MIN_GUIDANCE_SCALE,
+    MIN_INFERENCE_STEPS,
+};
 use crate::models::Backend;
+use crate::types::{
+    clamp_duration, clamp_guidance_scale, clamp_inference_steps, compute_track_id,
+    resolve_scheduler, sanitize_prompt, JobPriority, Manifest, ParamAdjustment,
+    ProvenanceDifference, StepsParam, Track, TrackVariant,
+};
 
 /// JSON-RPC version constant.
 pub const JSONRPC_VERSION: &str = "2.0";
@@ -30,11 +47,16 @@ impl From<String> for RequestId {
 }
 
 /// A JSON-RPC request wrapper.
+///
+/// `id` is None when it is missing or null. The daemon does not accept
+/// client notifications, so such requests are answered with an error
+/// whose `id` is null.
 #[derive(Debug, Deserialize)]
 pub struct JsonRpcRequest {
     pub jsonrpc: String,
     pub method: String,
-    pub id: RequestId,
+    #[serde(default)]
+    pub id: Option<RequestId>,
     #[serde(default)]
     pub params: serde_json::Value,
 }
@@ -76,7 +98,7 @@ impl JsonRpcErrorResponse {
 }
 
 /// A JSON-RPC error object.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct JsonRpcError {
     pub code: i32,
     pub message: String,
@@ -85,7 +107,7 @@ pub struct JsonRpcError {
 }
 
 /// Extended error data for application-specific errors.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct JsonRpcErrorData {
     pub error_code: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -187,13 +209,16 @@ impl JsonRpcError {
     }
 
     /// Creates a queue full error (-32004).
-    pub fn queue_full(current_size: usize) -> Self {
+    pub fn queue_full(current_size: usize, max_size: usize) -> Self {
         Self {
             code: -32004,
             message: "Queue full".to_string(),
             data: Some(JsonRpcErrorData {
                 error_code: "QUEUE_FULL".to_string(),
-                details: Some(format!("Maximum 10 pending requests. Current queue: {}", current_size)),
+                details: Some(format!(
+                    "Maximum {} pending requests. Current queue: {}",
+                    max_size, current_size
+                )),
             }),
         }
     }
@@ -281,8 +306,8 @@ impl JsonRpcError {
             data: Some(JsonRpcErrorData {
                 error_code: "INVALID_INFERENCE_STEPS".to_string(),
                 details: Some(format!(
-                    "Inference steps {} is outside valid range of 1-200",
-                    steps
+                    "Inference steps {} is outside valid range of {}-{}",
+                    steps, MIN_INFERENCE_STEPS, MAX_INFERENCE_STEPS
                 )),
             }),
         }
@@ -296,8 +321,8 @@ impl JsonRpcError {
             data: Some(JsonRpcErrorData {
                 error_code: "INVALID_GUIDANCE_SCALE".to_string(),
                 details: Some(format!(
-                    "Guidance scale {} is outside valid range of 1.0-30.0",
-                    scale
+                    "Guidance scale {} is outside valid range of {:.1}-{:.1}",
+                    scale, MIN_GUIDANCE_SCALE, MAX_GUIDANCE_SCALE
                 )),
             }),
         }
@@ -317,6 +342,156 @@ impl JsonRpcError {
             }),
         }
     }
+
+    /// Creates a rate limit exceeded error (-32029).
+    pub fn rate_limit_exceeded(method: &str, limit: u32) -> Self {
+        Self {
+            code: -32029,
+            message: "Rate limit exceeded".to_string(),
+            data: Some(JsonRpcErrorData {
+                error_code: "RATE_LIMIT_EXCEEDED".to_string(),
+                details: Some(format!(
+                    "Method '{}' is limited to {} calls per minute",
+                    method, limit
+                )),
+            }),
+        }
+    }
+
+    /// Creates an insufficient memory error (-32012).
+    pub fn insufficient_memory(details: impl Into<String>) -> Self {
+        Self {
+            code: -32012,
+            message: "Insufficient memory".to_string(),
+            data: Some(JsonRpcErrorData {
+                error_code: "INSUFFICIENT_MEMORY".to_string(),
+                details: Some(details.into()),
+            }),
+        }
+    }
+
+    /// Creates a generation cancelled error (-32013).
+    pub fn generation_cancelled(details: impl Into<String>) -> Self {
+        Self {
+            code: -32013,
+            message: "Generation cancelled".to_string(),
+            data: Some(JsonRpcErrorData {
+                error_code: "GENERATION_CANCELLED".to_string(),
+                details: Some(details.into()),
+            }),
+        }
+    }
+
+    /// Creates a tokenizer missing error (-32014).
+    pub fn tokenizer_missing(details: impl Into<String>) -> Self {
+        Self {
+            code: -32014,
+            message: "Tokenizer missing".to_string(),
+            data: Some(JsonRpcErrorData {
+                error_code: "TOKENIZER_MISSING".to_string(),
+                details: Some(details.into()),
+            }),
+        }
+    }
+
+    /// Creates a timed out error (-32015).
+    pub fn timed_out(details: impl Into<String>) -> Self {
+        Self {
+            code: -32015,
+            message: "Timed out".to_string(),
+            data: Some(JsonRpcErrorData {
+                error_code: "TIMED_OUT".to_string(),
+                details: Some(details.into()),
+            }),
+        }
+    }
+
+    /// Creates a cache read-only error (-32016).
+    pub fn cache_read_only(details: impl Into<String>) -> Self {
+        Self {
+            code: -32016,
+            message: "Cache read-only".to_string(),
+            data: Some(JsonRpcErrorData {
+                error_code: "CACHE_READ_ONLY".to_string(),
+                details: Some(details.into()),
+            }),
+        }
+    }
+
+    /// Creates an output path invalid error (-32017).
+    pub fn output_path_invalid(details: impl Into<String>) -> Self {
+        Self {
+            code: -32017,
+            message: "Output path invalid".to_string(),
+            data: Some(JsonRpcErrorData {
+                error_code: "OUTPUT_PATH_INVALID".to_string(),
+                details: Some(details.into()),
+            }),
+        }
+    }
+
+    /// Creates a storage failed error (-32018).
+    pub fn storage_failed(details: impl Into<String>) -> Self {
+        Self {
+            code: -32018,
+            message: "Storage failed".to_string(),
+            data: Some(JsonRpcErrorData {
+                error_code: "STORAGE_FAILED".to_string(),
+                details: Some(details.into()),
+            }),
+        }
+    }
+
+    /// Creates an application error whose details were already formatted.
+    fn with_details(code: i32, message: &str, error_code: ErrorCode, details: String) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            data: Some(JsonRpcErrorData {
+                error_code: error_code.as_str().to_string(),
+                details: Some(details),
+            }),
+        }
+    }
+}
+
+impl From<DaemonError> for JsonRpcError {
+    /// Maps the error to the JSON-RPC error for its code, with the error
+    /// message as details.
+    fn from(error: DaemonError) -> Self {
+        let code = error.code;
+        let details = error.message;
+        match code {
+            ErrorCode::ModelNotFound => Self::model_not_found(details),
+            ErrorCode::ModelLoadFailed => Self::model_load_failed(details),
+            ErrorCode::ModelDownloadFailed => Self::model_download_failed(details),
+            ErrorCode::ModelInferenceFailed => Self::model_inference_failed(details),
+            ErrorCode::QueueFull => Self::with_details(-32004, "Queue full", code, details),
+            ErrorCode::InvalidDuration => {
+                Self::with_details(-32005, "Invalid duration", code, details)
+            }
+            ErrorCode::InvalidPrompt => Self::invalid_prompt(details),
+            ErrorCode::BackendNotInstalled => {
+                Self::with_details(-32008, "Backend not installed", code, details)
+            }
+            ErrorCode::InvalidInferenceSteps => {
+                Self::with_details(-32009, "Invalid inference steps", code, details)
+            }
+            ErrorCode::InvalidGuidanceScale => {
+                Self::with_details(-32010, "Invalid guidance scale", code, details)
+            }
+            ErrorCode::InvalidScheduler => {
+                Self::with_details(-32011, "Invalid scheduler", code, details)
+            }
+            ErrorCode::InsufficientMemory => Self::insufficient_memory(details),
+            ErrorCode::GenerationCancelled => Self::generation_cancelled(details),
+            ErrorCode::TokenizerMissing => Self::tokenizer_missing(details),
+            ErrorCode::TimedOut => Self::timed_out(details),
+            ErrorCode::CacheReadOnly => Self::cache_read_only(details),
+            ErrorCode::OutputPathInvalid => Self::output_path_invalid(details),
+            ErrorCode::StorageFailed => Self::storage_failed(details),
+        }
+    }
 }
 
 // ============================================================================
@@ -324,7 +499,7 @@ impl JsonRpcError {
 // ============================================================================
 
 /// Priority level for generation requests.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum Priority {
     #[default]
@@ -333,11 +508,24 @@ pub enum Priority {
 }
 
 /// Parameters for a generate request.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct GenerateParams {
-    /// Text description of desired music.
+    /// Text description of desired music. Replaced by the blend label when
+    /// `prompt_blend` is set. Omit when giving `prompt_tags`.
+    #[serde(default)]
     pub prompt: String,
 
+    /// Structured tags composed into a tag-style prompt (e.g. `jazz, lofi,
+    /// piano, 80 bpm, chill`) instead of `prompt`. Exactly one of the two
+    /// must be given; the composed prompt is returned as `prompt`.
+    #[serde(default)]
+    pub prompt_tags: Option<PromptTags>,
+
+    /// ACE-Step only: `[prompt, weight]` pairs to blend instead of `prompt`.
+    /// Weights are normalized to sum to 1.
+    #[serde(default)]
+    pub prompt_blend: Option<Vec<(String, f32)>>,
+
     /// Duration of audio to generate in seconds (5-120 for MusicGen, 5-240 for ACE-Step).
     #[serde(default = "default_duration")]
     pub duration_sec: u32,
@@ -345,6 +533,14 @@ pub struct GenerateParams {
     /// Random seed for reproducibility; null for random.
     pub seed: Option<u64>,
 
+    /// Number of tracks to generate (1-10), with consecutive seeds starting
+    /// at `seed` (random if omitted). Ignored if `seeds` is given.
+    pub batch_size: Option<u32>,
+
+    /// Seeds to generate one track each for (1-10). Takes precedence over
+    /// `seed` and `batch_size`; repeated seeds are generated once.
+    pub seeds: Option<Vec<u64>>,
+
     /// Queue priority.
     #[serde(default)]
     pub priority: Priority,
@@ -352,20 +548,78 @@ pub struct GenerateParams {
     /// Backend to use for generation. Defaults to config default_backend.
     pub backend: Option<String>,
 
-    /// ACE-Step only: Number of diffusion inference steps (1-200, default 60).
-    pub inference_steps: Option<u32>,
+    /// ACE-Step only: Number of diffusion inference steps (1-200), or "auto"
+    /// to choose them from the duration. Defaults to the configured steps.
+    pub inference_steps: Option<StepsParam>,
 
     /// ACE-Step only: Scheduler type ("euler", "heun", "pingpong", default "euler").
     pub scheduler: Option<String>,
 
-    /// ACE-Step only: Classifier-free guidance scale (1.0-30.0, default 15.0).
+    /// ACE-Step only: Classifier-free guidance scale (1.0-20.0, default from config).
     pub guidance_scale: Option<f32>,
+
+    /// Tags for organizing the generated track (max 10, each max 32 characters).
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Sample rate of the written WAV (22050, 32000, 44100 or 48000).
+    /// Defaults to the backend's native rate; other rates are resampled.
+    pub output_sample_rate: Option<u32>,
+
+    /// Reverb preset applied before loudness normalization ("room", "hall",
+    /// "lofi-tape"). No reverb if omitted.
+    pub reverb: Option<String>,
+
+    /// Reverb wet mix (0.0-1.0). Requires `reverb`; defaults to the preset's mix.
+    pub reverb_wet: Option<f32>,
+
+    /// Trim near-silent padding from the start and end of the track, using
+    /// the `trim_silence_threshold_db` and `trim_min_silence_sec` settings.
+    #[serde(default)]
+    pub trim_silence: bool,
+
+    /// Minimum milliseconds between `generation_progress` notifications for
+    /// this request (0-60000). Defaults to the `min_notify_interval_ms` setting.
+    pub notify_interval_ms: Option<u64>,
+
+    /// Without `seed` or `seeds`, pick a new random seed that was not rated
+    /// down, recording the prompt's best-rated seed (see `rate_track`) as
+    /// `derived_from_seed`.
+    #[serde(default)]
+    pub prefer_rated: bool,
+
+    /// Copy the finished track into the library directory (`library_path`),
+    /// which cache pruning never touches. The copy's path is reported as
+    /// `library_path` in `generation_complete`.
+    #[serde(default)]
+    pub save_to_output: bool,
+
+    /// File to write the track to instead of the cache directory, for
+    /// scripts driving the daemon. Its directory must exist; relative paths
+    /// are taken relative to `restrict_output_root` if configured. The cache
+    /// is not consulted, so identical requests generate again. Cannot be
+    /// combined with a batch.
+    pub output_path: Option<String>,
+
+    /// Requires `output_path`: also copy the track into the cache, where
+    /// later requests without `output_path` find it.
+    #[serde(default)]
+    pub also_cache: bool,
 }
 
 fn default_duration() -> u32 {
     30
 }
 
+/// Maximum number of tags per track.
+pub const MAX_TAGS: usize = 10;
+
+/// Maximum length of a single tag in characters.
+pub const MAX_TAG_LENGTH: usize = 32;
+
+/// Maximum number of tracks a single generate request can produce.
+pub const MAX_BATCH_SIZE: usize = 10;
+
 impl GenerateParams {
     /// Parses the backend parameter, returning the default if not specified.
     pub fn resolve_backend(&self, default: Backend) -> Result<Backend, JsonRpcError> {
@@ -376,6 +630,252 @@ impl GenerateParams {
         }
     }
 
+    /// Creates the request that regenerates a manifest's track.
+    ///
+    /// ACE-Step sampling parameters missing from the manifest use the
+    /// configured defaults.
+    pub fn from_manifest(manifest: &Manifest) -> Self {
+        let is_ace_step = manifest.backend == Backend::AceStep;
+        let sampling = &manifest.sampling;
+        Self {
+            prompt: manifest.prompt.clone(),
+            prompt_tags: None,
+            prompt_blend: manifest.prompt_blend.clone(),
+            duration_sec: manifest.duration_sec,
+            seed: Some(manifest.seed),
+            batch_size: None,
+            seeds: None,
+            priority: Priority::Normal,
+            backend: Some(manifest.backend.as_str().to_string()),
+            inference_steps: sampling.inference_steps.filter(|_| is_ace_step).map(StepsParam::Fixed),
+            scheduler: sampling.scheduler.clone().filter(|_| is_ace_step),
+            guidance_scale: sampling.guidance_scale.filter(|_| is_ace_step),
+            tags: Vec::new(),
+            output_sample_rate: manifest.output_sample_rate,
+            reverb: manifest.reverb.map(|r| r.preset.as_str().to_string()),
+            reverb_wet: manifest.reverb.map(|r| r.wet),
+            trim_silence: manifest.trim_silence,
+            notify_interval_ms: None,
+            prefer_rated: false,
+            save_to_output: false,
+            output_path: None,
+            also_cache: false,
+        }
+    }
+
+    /// Replaces the requested inference steps with the number that will run.
+    ///
+    /// For ACE-Step this is the explicit count, the `auto` curve's choice for
+    /// the duration, or the configured default. Steps are cleared for
+    /// MusicGen, which ignores them. Returns the resolved steps.
+    pub fn resolve_inference_steps(
+        &mut self,
+        backend: Backend,
+        defaults: &AceStepConfig,
+    ) -> Option<u32> {
+        let resolved = (backend == Backend::AceStep).then(|| match self.inference_steps {
+            Some(steps) => steps.resolve(self.duration_sec as f32, defaults),
+            None => defaults.inference_steps,
+        });
+        self.inference_steps = resolved.map(StepsParam::Fixed);
+        resolved
+    }
+
+    /// Replaces the requested ACE-Step scheduler and guidance scale with the
+    /// values that will be used.
+    ///
+    /// Unset values take the configured defaults. Both are cleared for
+    /// MusicGen, which ignores them.
+    pub fn resolve_sampling(&mut self, backend: Backend, defaults: &AceStepConfig) {
+        if backend == Backend::AceStep {
+            self.scheduler =
+                Some(defaults.effective_scheduler(self.scheduler.as_deref()).to_string());
+            self.guidance_scale = Some(defaults.effective_guidance_scale(self.guidance_scale));
+        } else {
+            self.scheduler = None;
+            self.guidance_scale = None;
+        }
+    }
+
+    /// Computes the ID of the track this request produces.
+    ///
+    /// Derived from the prompt, seed, duration, and model version, then from
+    /// fixed ACE-Step steps, the output sample rate, reverb and silence
+    /// trimming with `trim`, if any.
+    /// Resolve `auto` steps with
+    /// [`resolve_inference_steps`](Self::resolve_inference_steps) first.
+    pub fn track_id(
+        &self,
+        backend: Backend,
+        seed: u64,
+        model_version: &str,
+        trim: &SilenceTrim,
+    ) -> String {
+        let track_id = compute_track_id(
+            backend,
+            &self.prompt,
+            seed,
+            self.duration_sec as f32,
+            model_version,
+        );
+        let reverb = self.reverb_settings();
+        let variant = TrackVariant {
+            inference_steps: self.inference_steps.and_then(StepsParam::fixed),
+            output_sample_rate: self.output_sample_rate,
+            reverb: reverb.as_ref(),
+            trim: self.trim_silence.then_some(trim),
+        };
+        variant.track_id(&track_id, backend)
+    }
+
+    /// Returns the seeds of a batch request, or None for a single track.
+    ///
+    /// Explicit `seeds` are returned in order without repeats. Otherwise
+    /// `batch_size` consecutive seeds are counted up from `base_seed`, which
+    /// is `seed` or a random one.
+    pub fn batch_seeds(&self, base_seed: u64) -> Option<Vec<u64>> {
+        if let Some(seeds) = &self.seeds {
+            let mut unique = Vec::with_capacity(seeds.len());
+            for &seed in seeds {
+                if !unique.contains(&seed) {
+                    unique.push(seed);
+                }
+            }
+            return Some(unique);
+        }
+        let count = self.batch_size?;
+        Some((0..count as u64).map(|i| base_seed.wrapping_add(i)).collect())
+    }
+
+    /// Returns warnings for batch parameters that are ignored: repeated
+    /// seeds, and `seed` or `batch_size` next to explicit `seeds`.
+    pub fn batch_warnings(&self) -> Vec<String> {
+        let Some(seeds) = &self.seeds else {
+            return Vec::new();
+        };
+        let mut warnings = Vec::new();
+        let mut repeated: Vec<u64> = Vec::new();
+        for (i, seed) in seeds.iter().enumerate() {
+            if seeds[..i].contains(seed) && !repeated.contains(seed) {
+                repeated.push(*seed);
+                warnings.push(format!("Seed {} is listed more than once", seed));
+            }
+        }
+        if self.batch_size.is_some() {
+            warnings.push("batch_size is ignored because seeds are given".to_string());
+        }
+        if self.seed.is_some() {
+            warnings.push("seed is ignored because seeds are given".to_string());
+        }
+        warnings
+    }
+
+    /// Returns the requested reverb, if any.
+    ///
+    /// An unknown preset yields None; [`validate`](Self::validate) rejects it.
+    pub fn reverb_settings(&self) -> Option<Reverb> {
+        let preset = ReverbPreset::parse(self.reverb.as_deref()?)?;
+        Some(Reverb::new(preset, self.reverb_wet))
+    }
+
+    /// Replaces the prompt with the one composed from `prompt_tags`, if
+    /// given, returning warnings for tags outside the known vocabularies.
+    ///
+    /// Fails if `prompt_tags` is combined with `prompt` or `prompt_blend`, or
+    /// if the tags are invalid. Should be called before
+    /// [`sanitize`](Self::sanitize).
+    pub fn compose_prompt_tags(&mut self) -> Result<Vec<String>, JsonRpcError> {
+        let Some(tags) = &self.prompt_tags else {
+            return Ok(Vec::new());
+        };
+        if !self.prompt.trim().is_empty() {
+            return Err(JsonRpcError::invalid_params(
+                "prompt and prompt_tags cannot both be given; provide exactly one",
+            ));
+        }
+        if self.prompt_blend.is_some() {
+            return Err(JsonRpcError::invalid_params(
+                "prompt_blend and prompt_tags cannot both be given",
+            ));
+        }
+        let (prompt, warnings) = tags.compose().map_err(JsonRpcError::invalid_params)?;
+        self.prompt = prompt;
+        Ok(warnings)
+    }
+
+    /// Sanitizes the prompt in place (NFC normalization, control characters, trimming).
+    ///
+    /// A prompt blend is sanitized and normalized, and its label (e.g.
+    /// `70% jazzy piano + 30% rain`) replaces the prompt so that track IDs and
+    /// listings describe the blend.
+    ///
+    /// Should be called before [`validate`](Self::validate) so that length and
+    /// emptiness checks apply to the cleaned prompt.
+    pub fn sanitize(&mut self, mode: PromptSanitization) -> Result<(), JsonRpcError> {
+        if let Some(blend) = &self.prompt_blend {
+            let sanitized = blend
+                .iter()
+                .map(|(prompt, weight)| Ok((sanitize_prompt(prompt, mode)?, *weight)))
+                .collect::<Result<Vec<_>, String>>()
+                .and_then(|blend| normalize_blend(&blend))
+                .map_err(JsonRpcError::invalid_prompt)?;
+            self.prompt = blend_label(&sanitized);
+            self.prompt_blend = Some(sanitized);
+            return Ok(());
+        }
+        self.prompt = sanitize_prompt(&self.prompt, mode).map_err(JsonRpcError::invalid_prompt)?;
+        Ok(())
+    }
+
+    /// Returns a warning if the duration exceeds the recommended maximum.
+    ///
+    /// Such requests are still accepted; the warning is returned in the response.
+    pub fn duration_warning(&self, recommended_max_sec: Option<f32>) -> Option<String> {
+        let recommended = recommended_max_sec?;
+        if self.duration_sec as f32 > recommended {
+            Some(format!(
+                "Duration {}s exceeds the recommended maximum of {:.0}s for available memory",
+                self.duration_sec, recommended
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Replaces out-of-range parameters with the nearest valid values.
+    ///
+    /// Used when `param_strictness` is `clamp`, before [`validate`](Self::validate).
+    /// The duration is clamped to the backend's range; for ACE-Step, steps and
+    /// guidance are clamped and an unknown scheduler falls back to the configured
+    /// default. The prompt is never changed. Returns the adjustments made.
+    pub fn clamp(&mut self, backend: Backend, defaults: &AceStepConfig) -> Vec<ParamAdjustment> {
+        let mut adjustments = Vec::new();
+        self.duration_sec = clamp_duration(self.duration_sec, backend, &mut adjustments);
+
+        if backend == Backend::AceStep {
+            if let Some(StepsParam::Fixed(steps)) = self.inference_steps {
+                let steps = clamp_inference_steps(steps, &mut adjustments);
+                self.inference_steps = Some(StepsParam::Fixed(steps));
+            }
+            if let Some(scale) = self.guidance_scale {
+                self.guidance_scale = Some(clamp_guidance_scale(
+                    scale,
+                    defaults.guidance_scale,
+                    &mut adjustments,
+                ));
+            }
+            if let Some(scheduler) = &self.scheduler {
+                self.scheduler = Some(resolve_scheduler(
+                    scheduler,
+                    &defaults.scheduler,
+                    &mut adjustments,
+                ));
+            }
+        }
+
+        adjustments
+    }
+
     /// Validates the request parameters for a specific backend.
     pub fn validate(&self, backend: Backend) -> Result<(), JsonRpcError> {
         // Check prompt
@@ -389,6 +889,101 @@ impl GenerateParams {
             )));
         }
 
+        if self.prompt_blend.is_some() && backend != Backend::AceStep {
+            return Err(JsonRpcError::invalid_params(
+                "prompt_blend is only supported by the ace_step backend",
+            ));
+        }
+
+        if let Some(rate) = self.output_sample_rate {
+            if !OUTPUT_SAMPLE_RATES.contains(&rate) {
+                return Err(JsonRpcError::invalid_params(format!(
+                    "output_sample_rate must be one of {:?}, got {}",
+                    OUTPUT_SAMPLE_RATES, rate
+                )));
+            }
+        }
+
+        if let Some(ref reverb) = self.reverb {
+            if ReverbPreset::parse(reverb).is_none() {
+                let presets: Vec<&str> = ReverbPreset::ALL.iter().map(|p| p.as_str()).collect();
+                return Err(JsonRpcError::invalid_params(format!(
+                    "reverb must be one of {:?}, got '{}'",
+                    presets, reverb
+                )));
+            }
+        }
+        if let Some(wet) = self.reverb_wet {
+            if self.reverb.is_none() {
+                return Err(JsonRpcError::invalid_params("reverb_wet requires reverb"));
+            }
+            if !(0.0..=1.0).contains(&wet) {
+                return Err(JsonRpcError::invalid_params(format!(
+                    "reverb_wet must be between 0.0 and 1.0, got {}",
+                    wet
+                )));
+            }
+        }
+
+        if self.output_path.is_some() && (self.seeds.is_some() || self.batch_size.is_some()) {
+            return Err(JsonRpcError::invalid_params(
+                "output_path cannot be combined with seeds or batch_size",
+            ));
+        }
+        if self.also_cache && self.output_path.is_none() {
+            return Err(JsonRpcError::invalid_params("also_cache requires output_path"));
+        }
+
+        if let Some(seeds) = &self.seeds {
+            if seeds.is_empty() || seeds.len() > MAX_BATCH_SIZE {
+                return Err(JsonRpcError::invalid_params(format!(
+                    "seeds must list 1-{} seeds, got {}",
+                    MAX_BATCH_SIZE,
+                    seeds.len()
+                )));
+            }
+        }
+        if let Some(batch_size) = self.batch_size {
+            if batch_size == 0 || batch_size as usize > MAX_BATCH_SIZE {
+                return Err(JsonRpcError::invalid_params(format!(
+                    "batch_size must be between 1 and {}, got {}",
+                    MAX_BATCH_SIZE, batch_size
+                )));
+            }
+        }
+
+        if let Some(interval) = self.notify_interval_ms {
+            if interval > MAX_NOTIFY_INTERVAL_MS {
+                return Err(JsonRpcError::invalid_params(format!(
+                    "notify_interval_ms must be at most {}, got {}",
+                    MAX_NOTIFY_INTERVAL_MS, interval
+                )));
+            }
+        }
+
+        // Check tags
+        if self.tags.len() > MAX_TAGS {
+            return Err(JsonRpcError::invalid_params(format!(
+                "Too many tags: {} (max {})",
+                self.tags.len(),
+                MAX_TAGS
+            )));
+        }
+        for tag in &self.tags {
+            if tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH {
+                return Err(JsonRpcError::invalid_params(format!(
+                    "Tag must be 1-{} characters: '{}'",
+                    MAX_TAG_LENGTH, tag
+                )));
+            }
+            if tag.chars().any(char::is_whitespace) {
+                return Err(JsonRpcError::invalid_params(format!(
+                    "Tag must not contain whitespace: '{}'",
+                    tag
+                )));
+            }
+        }
+
         // Check duration based on backend
         let min_duration = backend.min_duration_sec();
         let max_duration = backend.max_duration_sec();
@@ -401,13 +996,13 @@ impl GenerateParams {
 
         // Validate ACE-Step specific parameters
         if backend == Backend::AceStep {
-            if let Some(steps) = self.inference_steps {
-                if steps < 1 || steps > 200 {
+            if let Some(StepsParam::Fixed(steps)) = self.inference_steps {
+                if !(MIN_INFERENCE_STEPS..=MAX_INFERENCE_STEPS).contains(&steps) {
                     return Err(JsonRpcError::invalid_inference_steps(steps));
                 }
             }
             if let Some(scale) = self.guidance_scale {
-                if !(1.0..=30.0).contains(&scale) {
+                if !(MIN_GUIDANCE_SCALE..=MAX_GUIDANCE_SCALE).contains(&scale) {
                     return Err(JsonRpcError::invalid_guidance_scale(scale));
                 }
             }
@@ -424,7 +1019,7 @@ impl GenerateParams {
 }
 
 /// Response for a generate request.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct GenerateResult {
     /// Unique identifier for this generation.
     pub track_id: String,
@@ -440,10 +1035,56 @@ pub struct GenerateResult {
 
     /// Backend being used for generation.
     pub backend: String,
+
+    /// Prompt after normalization, as used for the track ID (redacted if
+    /// `redact_prompts` is set).
+    pub prompt: String,
+
+    /// Requested duration in seconds.
+    pub duration_sec: u32,
+
+    /// Diffusion steps the track is generated with (ACE-Step only), after
+    /// resolving `auto` or the configured default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inference_steps: Option<u32>,
+
+    /// Scheduler the track is generated with (ACE-Step only), after applying
+    /// the configured default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduler: Option<String>,
+
+    /// Guidance scale the track is generated with (ACE-Step only), after
+    /// applying the configured default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guidance_scale: Option<f32>,
+
+    /// Non-fatal issues with the request, such as a duration above the
+    /// recommended maximum for this machine.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+
+    /// Out-of-range parameters replaced in clamp mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub adjusted_params: Vec<ParamAdjustment>,
+
+    /// For `prefer_rated` requests, the best-rated seed of the prompt that
+    /// `seed` was chosen in place of. Also recorded on the track.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub derived_from_seed: Option<u64>,
+
+    /// For batch requests (`seeds` or `batch_size`), the track ID generated
+    /// for each seed. `track_id` and `seed` are those of the first one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_ids_by_seed: Option<BTreeMap<u64, String>>,
+
+    /// File the track is written to, for requests with `output_path`, after
+    /// resolving it against `restrict_output_root`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
 }
 
 /// Status of a generation job.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum GenerationStatus {
     Queued,
@@ -475,7 +1116,7 @@ impl<T: Serialize> JsonRpcNotification<T> {
 }
 
 /// Progress notification sent every 5% during generation.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct GenerationProgressParams {
     /// Track being generated.
     pub track_id: String,
@@ -503,10 +1144,20 @@ pub struct GenerationProgressParams {
     /// None for MusicGen token-based generation.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_steps: Option<usize>,
+
+    /// Seconds of audio decoded so far (ACE-Step only). Set once the
+    /// diffusion steps are done; `percent` is then the decoded share of
+    /// `total_sec`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded_sec: Option<f32>,
+
+    /// Seconds of audio being generated (ACE-Step only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_sec: Option<f32>,
 }
 
 /// Notification sent when generation finishes successfully.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct GenerationCompleteParams {
     /// Completed track identifier.
     pub track_id: String,
@@ -520,10 +1171,11 @@ pub struct GenerationCompleteParams {
     /// Audio sample rate in Hz.
     pub sample_rate: u32,
 
-    /// Original prompt used.
+    /// Original prompt used, shortened and hashed when prompt redaction is enabled.
     pub prompt: String,
 
-    /// Seed used for generation.
+    /// Seed used for generation. MusicGen retries with the next seed when a
+    /// seed produces invalid audio, so this can differ from the requested seed.
     pub seed: u64,
 
     /// Wall-clock time for generation.
@@ -534,10 +1186,33 @@ pub struct GenerationCompleteParams {
 
     /// Backend used for generation.
     pub backend: String,
+
+    /// Diffusion steps the track was generated with (ACE-Step only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inference_steps: Option<u32>,
+
+    /// Size of the generated WAV file in bytes.
+    pub file_size_bytes: u64,
+
+    /// Tags attached to the track.
+    pub tags: Vec<String>,
+
+    /// Absolute path to the spectrogram PNG, if thumbnails are enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_path: Option<String>,
+
+    /// Absolute path to the copy in the library directory, if
+    /// `save_to_output` was requested and the copy succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub library_path: Option<String>,
+
+    /// Out-of-range parameters replaced in clamp mode.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub adjusted_params: Vec<ParamAdjustment>,
 }
 
 /// Notification sent when generation fails.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct GenerationErrorParams {
     /// Track that failed.
     pub track_id: String,
@@ -547,10 +1222,44 @@ pub struct GenerationErrorParams {
 
     /// Human-readable error message.
     pub message: String,
+
+    /// Suggestion for resolving the error.
+    pub recovery_hint: String,
+
+    /// Whether resubmitting the same request may succeed.
+    pub retriable: bool,
+
+    /// Pipeline phase that failed (text_encode, diffusion, decode, vocode, write), if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<String>,
+
+    /// Underlying cause chain, e.g. the ONNX Runtime error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+
+    /// Folder containing failure diagnostics, if `debug_dump_dir` is configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dump_path: Option<String>,
+}
+
+impl GenerationErrorParams {
+    /// Builds the notification for a failed generation of `track_id`.
+    pub fn from_error(track_id: impl Into<String>, error: &DaemonError) -> Self {
+        Self {
+            track_id: track_id.into(),
+            code: error.code.as_str().to_string(),
+            message: error.message.clone(),
+            recovery_hint: error.code.recovery_hint().to_string(),
+            retriable: error.code.is_retriable(),
+            phase: error.phase.map(|phase| phase.as_str().to_string()),
+            details: error.details(),
+            dump_path: error.dump_path.as_ref().map(|p| p.to_string_lossy().to_string()),
+        }
+    }
 }
 
 /// Download progress notification.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct DownloadProgressParams {
     /// Current file being downloaded.
     pub file_name: String,
@@ -568,23 +1277,70 @@ pub struct DownloadProgressParams {
     pub files_total: usize,
 }
 
-// ============================================================================
-// get_backends Request/Response
-// ============================================================================
+/// Download complete notification, sent once a backend's missing model files
+/// have been downloaded.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DownloadCompleteParams {
+    /// Backend whose models were downloaded.
+    pub backend: String,
 
-/// Status of a backend.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum BackendStatus {
-    /// Backend is not installed (model weights not downloaded).
-    NotInstalled,
-    /// Backend is currently downloading.
-    Downloading,
+    /// Number of files downloaded.
+    pub total_files: usize,
+
+    /// Combined size of the downloaded files in bytes.
+    pub total_bytes: u64,
+
+    /// Time spent downloading in seconds.
+    pub duration_sec: f32,
+}
+
+/// Backend status notification, sent whenever a backend's status or its
+/// number of active jobs changes.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BackendStatusParams {
+    /// Backend whose status changed.
+    pub backend: String,
+
+    /// New status.
+    pub status: BackendStatus,
+
+    /// Why the backend failed, in the `error` status.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_detail: Option<String>,
+
+    /// Jobs for the backend that are generating or queued.
+    pub active_jobs: usize,
+}
+
+// ============================================================================
+// get_backends Request/Response
+// ============================================================================
+
+/// Status of a backend.
+///
+/// A backend moves from `not_installed` through `downloading` to `ready`
+/// once its models are on disk, then through `loading` to `loaded` when a
+/// generation needs it. Only one backend is loaded at a time; the other
+/// returns to `ready`. A failed download or load leaves it in `error` until
+/// a later download or load succeeds.
+///
+/// Downloads and loads run inside a single request, so `get_backends` never
+/// sees `downloading` or `loading`; every change is also sent as a
+/// `backend_status` notification while it happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendStatus {
+    /// Backend is not installed (model weights not downloaded).
+    NotInstalled,
+    /// Backend is currently downloading.
+    Downloading,
     /// Backend is loading into memory.
     Loading,
-    /// Backend is ready for generation.
+    /// Backend models are on disk and load on the next generation.
     Ready,
-    /// Backend encountered an error.
+    /// Backend models are loaded in memory.
+    Loaded,
+    /// Backend failed to download or load.
     Error,
 }
 
@@ -595,7 +1351,7 @@ impl Default for BackendStatus {
 }
 
 /// Information about a specific backend.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 pub struct BackendInfo {
     /// Backend type identifier (e.g., "musicgen", "ace_step").
     #[serde(rename = "type")]
@@ -619,6 +1375,20 @@ pub struct BackendInfo {
     /// Model version string (None if not installed).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model_version: Option<String>,
+
+    /// Longest duration that comfortably fits into currently available memory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recommended_max_duration_sec: Option<f32>,
+
+    /// Whether the backend's models are loaded in memory.
+    pub loaded: bool,
+
+    /// Jobs for this backend that are generating or queued.
+    pub active_jobs: usize,
+
+    /// Why the last download or load failed, while the status is `error`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_detail: Option<String>,
 }
 
 impl BackendInfo {
@@ -637,12 +1407,34 @@ impl BackendInfo {
             max_duration_sec: backend.max_duration_sec(),
             sample_rate: backend.sample_rate(),
             model_version,
+            recommended_max_duration_sec: None,
+            loaded: status == BackendStatus::Loaded,
+            active_jobs: 0,
+            error_detail: None,
         }
     }
+
+    /// Sets the number of generating or queued jobs.
+    pub fn with_active_jobs(mut self, active_jobs: usize) -> Self {
+        self.active_jobs = active_jobs;
+        self
+    }
+
+    /// Sets the reason for the `error` status.
+    pub fn with_error_detail(mut self, error_detail: Option<String>) -> Self {
+        self.error_detail = error_detail;
+        self
+    }
+
+    /// Sets the recommended maximum duration for this machine.
+    pub fn with_recommended_max_duration(mut self, duration_sec: Option<f32>) -> Self {
+        self.recommended_max_duration_sec = duration_sec;
+        self
+    }
 }
 
 /// Response for get_backends request.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct GetBackendsResult {
     /// List of available backends with their status.
     pub backends: Vec<BackendInfo>,
@@ -656,7 +1448,7 @@ pub struct GetBackendsResult {
 // ============================================================================
 
 /// Parameters for a download_backend request.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct DownloadBackendParams {
     /// Backend to download models for ("musicgen" or "ace_step").
     pub backend: String,
@@ -671,7 +1463,7 @@ impl DownloadBackendParams {
 }
 
 /// Response for a download_backend request.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct DownloadBackendResult {
     /// Backend that was downloaded.
     pub backend: String,
@@ -683,23 +1475,538 @@ pub struct DownloadBackendResult {
     pub files_downloaded: usize,
 }
 
+// ============================================================================
+// purge_backend Request/Response
+// ============================================================================
+
+/// Parameters for a purge_backend request.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PurgeBackendParams {
+    /// Backend whose cached tracks are removed ("musicgen" or "ace_step").
+    pub backend: String,
+}
+
+impl PurgeBackendParams {
+    /// Parses and validates the backend parameter.
+    pub fn validate(&self) -> Result<Backend, JsonRpcError> {
+        Backend::parse(&self.backend)
+            .ok_or_else(|| JsonRpcError::invalid_backend(&self.backend))
+    }
+}
+
+/// Response for a purge_backend request.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PurgeBackendResult {
+    /// Backend whose tracks were removed.
+    pub backend: String,
+
+    /// Number of cached tracks removed.
+    pub tracks_removed: usize,
+
+    /// Combined size of the deleted track files in bytes.
+    pub bytes_freed: u64,
+}
+
+// ============================================================================
+// get_cache_stats Request/Response
+// ============================================================================
+
+/// Parameters for a get_cache_stats request.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct CacheStatsParams {
+    /// Also measure the files in the cache directory. A scan is reused for 30
+    /// seconds.
+    #[serde(default)]
+    pub scan_disk: bool,
+
+    /// Scan the cache directory even if a recent scan is available. Implies
+    /// `scan_disk`.
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+/// Response for a get_cache_stats request.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CacheStatsResult {
+    /// Number of cached tracks.
+    pub track_count: usize,
+
+    /// Combined duration of the cached tracks in seconds.
+    pub total_duration_sec: f32,
+
+    /// Combined size of the cached WAV files in bytes.
+    pub total_size_bytes: u64,
+
+    /// Byte budget of the cache, or null if unbounded.
+    pub max_bytes: Option<u64>,
+
+    /// Creation time of the oldest cached track, in seconds since the Unix
+    /// epoch, or null if the cache is empty.
+    pub oldest_created_at: Option<u64>,
+
+    /// Creation time of the newest cached track, in seconds since the Unix
+    /// epoch, or null if the cache is empty.
+    pub newest_created_at: Option<u64>,
+
+    /// Path to the cache directory.
+    pub cache_dir: String,
+
+    /// The same totals for each backend.
+    pub by_backend: Vec<BackendCacheStats>,
+
+    /// Files in the cache directory, if `scan_disk` was requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disk: Option<CacheDiskUsage>,
+}
+
+/// Files in the cache directory in a get_cache_stats response.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CacheDiskUsage {
+    /// Number of files, including sidecars, thumbnails and orphaned WAVs.
+    pub file_count: usize,
+
+    /// Combined size of the files in bytes.
+    pub total_bytes: u64,
+
+    /// Orphaned WAVs that prune_orphans would delete.
+    pub orphan_count: usize,
+
+    /// Combined size of the orphans and their sidecars and thumbnails in bytes.
+    pub orphan_bytes: u64,
+
+    /// Seconds since the directory was scanned.
+    pub age_sec: f32,
+}
+
+/// Cached tracks of one backend in a get_cache_stats response.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BackendCacheStats {
+    /// Backend identifier ("musicgen" or "ace_step").
+    pub backend: String,
+
+    /// Number of cached tracks generated by the backend.
+    pub track_count: usize,
+
+    /// Combined duration of those tracks in seconds.
+    pub total_duration_sec: f32,
+
+    /// Combined size of their WAV files in bytes.
+    pub total_size_bytes: u64,
+}
+
+// ============================================================================
+// prune_orphans Request/Response
+// ============================================================================
+
+/// Parameters for a prune_orphans request.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PruneOrphansParams {
+    /// List the orphaned files without deleting them. Defaults to true, so
+    /// files are only deleted when `dry_run` is explicitly false.
+    #[serde(default = "default_dry_run")]
+    pub dry_run: bool,
+}
+
+impl Default for PruneOrphansParams {
+    fn default() -> Self {
+        Self {
+            dry_run: default_dry_run(),
+        }
+    }
+}
+
+fn default_dry_run() -> bool {
+    true
+}
+
+/// Response for a prune_orphans request.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PruneOrphansResult {
+    /// Number of orphaned WAV files removed (or found, for a dry run).
+    pub files_removed: usize,
+
+    /// Combined size of the WAVs and their sidecars and thumbnails in bytes.
+    pub bytes_freed: u64,
+
+    /// Paths of the orphaned WAV files.
+    pub files: Vec<String>,
+
+    /// Whether this was a dry run and nothing was deleted.
+    pub dry_run: bool,
+}
+
+// ============================================================================
+// get_queue Response
+// ============================================================================
+
+/// Response for a get_queue request.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GetQueueResult {
+    /// Scheduling policy in effect ("fifo" or "backend_affinity").
+    pub policy: String,
+
+    /// Backend whose models are loaded, if any.
+    pub current_backend: Option<String>,
+
+    /// Queued jobs in the order they will be processed.
+    pub jobs: Vec<QueuedJobInfo>,
+}
+
+/// A queued job as reported by get_queue.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct QueuedJobInfo {
+    /// Job identifier.
+    pub job_id: String,
+
+    /// Track the job will produce.
+    pub track_id: String,
+
+    /// Prompt, redacted if prompt redaction is enabled.
+    pub prompt: String,
+
+    /// Backend that will generate the job.
+    pub backend: String,
+
+    /// Job priority ("high" or "normal").
+    pub priority: JobPriority,
+
+    /// Requested duration in seconds.
+    pub duration_sec: u32,
+
+    /// Seconds since the job was submitted.
+    pub waiting_sec: f32,
+}
+
+// ============================================================================
+// list_tracks / get_track Request/Response
+// ============================================================================
+
+/// Parameters for a list_tracks request.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct ListTracksParams {
+    /// Only return tracks with this tag.
+    pub tag: Option<String>,
+
+    /// Only return tracks rated at least this (-1 to 1); unrated tracks
+    /// count as 0.
+    pub min_rating: Option<i8>,
+}
+
+/// Response for a list_tracks request.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ListTracksResult {
+    /// Cached tracks, newest first, with prompts replaced by `prompt_hash`
+    /// when prompt redaction is enabled.
+    #[schemars(with = "Vec<Track>")]
+    pub tracks: Vec<serde_json::Value>,
+
+    /// Number of cached tracks.
+    pub count: usize,
+
+    /// Combined size of all cached track files in bytes.
+    pub total_size_bytes: u64,
+}
+
+/// Parameters for a get_track request.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetTrackParams {
+    /// Identifier of the track to look up.
+    pub track_id: String,
+}
+
+// ============================================================================
+// rate_track Request/Response
+// ============================================================================
+
+/// Parameters for a rate_track request.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RateTrackParams {
+    /// Identifier of the track to rate.
+    pub track_id: String,
+
+    /// Rating from -1 (bad) to 1 (good); 0 is neutral.
+    pub rating: i8,
+}
+
+/// Response for a rate_track request.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RateTrackResult {
+    /// Identifier of the rated track.
+    pub track_id: String,
+
+    /// Recorded rating.
+    pub rating: i8,
+
+    /// Seed the rating was recorded for.
+    pub seed: u64,
+}
+
+// ============================================================================
+// compare_tracks Request/Response
+// ============================================================================
+
+/// Parameters for a compare_tracks request.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CompareTracksParams {
+    /// Identifier of the first track (the baseline).
+    pub track_id_a: String,
+
+    /// Identifier of the second track.
+    pub track_id_b: String,
+}
+
+/// Response for a compare_tracks request.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CompareTracksResult {
+    /// Identifier of the first track.
+    pub track_id_a: String,
+
+    /// Identifier of the second track.
+    pub track_id_b: String,
+
+    /// Provenance fields whose values differ.
+    pub provenance_diff: Vec<ProvenanceDifference>,
+
+    /// Statistics of the first track's audio.
+    pub audio_a: AudioStats,
+
+    /// Statistics of the second track's audio.
+    pub audio_b: AudioStats,
+
+    /// `audio_b - audio_a` for every statistic.
+    pub audio_delta: AudioStats,
+}
+
+// ============================================================================
+// export_manifest Request/Response
+// ============================================================================
+
+/// Parameters for an export_manifest request.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ExportManifestParams {
+    /// Identifier of the cached track to export.
+    pub track_id: String,
+
+    /// File to also write the manifest to, as pretty-printed JSON. Checked
+    /// like the `output_path` of `generate`: its directory must exist, and
+    /// it must lie inside `restrict_output_root` if configured.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Response for an export_manifest request.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ExportManifestResult {
+    /// The track's manifest.
+    pub manifest: Manifest,
+
+    /// Resolved file the manifest was written to, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+// ============================================================================
+// generate_from_manifest Request
+// ============================================================================
+
+/// Parameters for a generate_from_manifest request.
+///
+/// The response is a [`GenerateResult`] whose warnings include unknown
+/// manifest fields and differences from the exporter's models.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GenerateFromManifestParams {
+    /// Manifest returned by export_manifest.
+    pub manifest: serde_json::Value,
+
+    /// Queue priority.
+    #[serde(default)]
+    pub priority: Priority,
+}
+
+// ============================================================================
+// health Request/Response
+// ============================================================================
+
+/// Response for a health request.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct HealthResult {
+    /// "healthy", or "unhealthy" if the daemon is shutting down or its queue is full.
+    pub status: String,
+
+    /// Seconds since the daemon started.
+    pub uptime_sec: f32,
+
+    /// Whether models of any backend are loaded.
+    pub backend_loaded: bool,
+
+    /// Number of jobs waiting in the queue.
+    pub queue_depth: usize,
+
+    /// Number of cached tracks.
+    pub cache_size: usize,
+}
+
+impl HealthResult {
+    /// Returns true if the status is "healthy".
+    pub fn is_healthy(&self) -> bool {
+        self.status == "healthy"
+    }
+}
+
+// ============================================================================
+// get_now_playing / get_history Request/Response
+// ============================================================================
+
+/// Default number of entries returned by get_history.
+pub const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+/// Response for a get_now_playing request.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GetNowPlayingResult {
+    /// The most recently completed track, or null if none has completed.
+    pub now_playing: Option<NowPlaying>,
+}
+
+/// Parameters for a get_history request.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct GetHistoryParams {
+    /// Maximum number of entries to return.
+    /// Default: 20
+    pub limit: Option<usize>,
+}
+
+/// Response for a get_history request.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GetHistoryResult {
+    /// Completed tracks, newest first.
+    pub entries: Vec<NowPlaying>,
+}
+
+// ============================================================================
+// subscribe / unsubscribe Request/Response
+// ============================================================================
+
+/// Parameters for a subscribe request.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SubscribeParams {
+    /// Notification methods to receive (see `NOTIFICATION_METHODS`).
+    pub methods: Vec<String>,
+
+    /// Only receive job notifications for these tracks.
+    #[serde(default)]
+    pub track_ids: Option<Vec<String>>,
+}
+
+/// Response for subscribe and unsubscribe requests.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SubscribeResult {
+    /// Subscribed notification methods, or None if all are delivered.
+    pub methods: Option<Vec<String>>,
+
+    /// Subscribed track ids, or None if all tracks are delivered.
+    pub track_ids: Option<Vec<String>>,
+}
+
+/// Parameters for a set_default_backend request.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SetDefaultBackendParams {
+    /// Backend to use when a generate request does not name one.
+    pub backend: String,
+}
+
+/// Response for a set_default_backend request.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SetDefaultBackendResult {
+    /// The new default backend.
+    pub default_backend: String,
+
+    /// Whether the setting was written to the config file.
+    pub persisted: bool,
+}
+
+/// Response for ping and shutdown requests.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct StatusResult {
+    /// `ok` for ping, `shutting_down` for shutdown.
+    pub status: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Returns the ACE-Step track ID of `params` with the default silence trim.
+    fn ace_step_track_id(params: &GenerateParams, seed: u64, model_version: &str) -> String {
+        params.track_id(Backend::AceStep, seed, model_version, &SilenceTrim::default())
+    }
+
     fn make_params(prompt: &str, duration_sec: u32) -> GenerateParams {
         GenerateParams {
             prompt: prompt.to_string(),
+            prompt_tags: None,
+            prompt_blend: None,
             duration_sec,
             seed: None,
+            batch_size: None,
+            seeds: None,
             priority: Priority::Normal,
             backend: None,
             inference_steps: None,
             scheduler: None,
             guidance_scale: None,
+            tags: Vec::new(),
+            output_sample_rate: None,
+            reverb: None,
+            reverb_wet: None,
+            trim_silence: false,
+            notify_interval_ms: None,
+            prefer_rated: false,
+            save_to_output: false,
+            output_path: None,
+            also_cache: false,
         }
     }
 
+    #[test]
+    fn clamp_adjusts_out_of_range_ace_step_params() {
+        let mut params = make_params("rain", 300);
+        params.inference_steps = Some(StepsParam::Fixed(0));
+        params.guidance_scale = Some(35.0);
+        params.scheduler = Some("ddim".to_string());
+        assert!(params.validate(Backend::AceStep).is_err());
+
+        let adjustments = params.clamp(Backend::AceStep, &AceStepConfig::default());
+        assert_eq!(params.duration_sec, 240);
+        assert_eq!(params.inference_steps, Some(StepsParam::Fixed(1)));
+        assert_eq!(params.guidance_scale, Some(20.0));
+        assert_eq!(params.scheduler.as_deref(), Some("euler"));
+        let fields: Vec<&str> = adjustments.iter().map(|a| a.field.as_str()).collect();
+        assert_eq!(fields, ["duration_sec", "inference_steps", "guidance_scale", "scheduler"]);
+        assert_eq!(adjustments[0], ParamAdjustment::new("duration_sec", 300, 240));
+        assert!(params.validate(Backend::AceStep).is_ok());
+
+        let json = serde_json::to_value(&adjustments[2]).unwrap();
+        assert_eq!(json, serde_json::json!({ "field": "guidance_scale", "requested": 35.0, "used": 20.0 }));
+    }
+
+    #[test]
+    fn clamp_leaves_valid_params_and_prompt_alone() {
+        let mut params = make_params("", 200);
+        params.guidance_scale = Some(35.0);
+        // MusicGen only clamps the duration
+        let adjustments = params.clamp(Backend::MusicGen, &AceStepConfig::default());
+        assert_eq!(adjustments, vec![ParamAdjustment::new("duration_sec", 200, 120)]);
+        assert_eq!(params.guidance_scale, Some(35.0));
+        // Prompt emptiness is never clamped
+        assert_eq!(params.validate(Backend::MusicGen).unwrap_err().code, -32006);
+
+        let mut params = make_params("rain", 60);
+        params.inference_steps = Some(StepsParam::Fixed(200));
+        params.guidance_scale = Some(1.0);
+        params.scheduler = Some("pingpong".to_string());
+        assert!(params.clamp(Backend::AceStep, &AceStepConfig::default()).is_empty());
+    }
+
     #[test]
     fn request_id_from_int() {
         let id: RequestId = 42.into();
@@ -712,6 +2019,30 @@ mod tests {
         assert_eq!(id, RequestId::String("abc".to_string()));
     }
 
+    #[test]
+    fn request_id_round_trips_as_json() {
+        let request: JsonRpcRequest =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","method":"ping","id":"abc"}"#).unwrap();
+        assert_eq!(request.id, Some(RequestId::String("abc".to_string())));
+        let response = JsonRpcResponse::new(request.id.unwrap(), "ok");
+        assert_eq!(serde_json::to_value(&response).unwrap()["id"], "abc");
+
+        let request: JsonRpcRequest =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","method":"ping","id":7}"#).unwrap();
+        assert_eq!(request.id, Some(RequestId::Integer(7)));
+
+        for line in [
+            r#"{"jsonrpc":"2.0","method":"ping","id":null}"#,
+            r#"{"jsonrpc":"2.0","method":"ping"}"#,
+        ] {
+            let request: JsonRpcRequest = serde_json::from_str(line).unwrap();
+            assert_eq!(request.id, None);
+        }
+
+        let error = JsonRpcErrorResponse::new(None, JsonRpcError::method_not_found("nope"));
+        assert!(serde_json::to_value(&error).unwrap()["id"].is_null());
+    }
+
     #[test]
     fn priority_default() {
         assert_eq!(Priority::default(), Priority::Normal);
@@ -763,34 +2094,339 @@ mod tests {
     fn generate_params_validate_ok() {
         let params = GenerateParams {
             prompt: "test".to_string(),
+            prompt_tags: None,
+            prompt_blend: None,
             duration_sec: 30,
             seed: Some(42),
+            batch_size: None,
+            seeds: None,
             priority: Priority::High,
             backend: None,
             inference_steps: None,
             scheduler: None,
             guidance_scale: None,
+            tags: Vec::new(),
+            output_sample_rate: None,
+            reverb: None,
+            reverb_wet: None,
+            trim_silence: false,
+            notify_interval_ms: None,
+            prefer_rated: false,
+            save_to_output: false,
+            output_path: None,
+            also_cache: false,
+        };
+        assert!(params.validate(Backend::MusicGen).is_ok());
+    }
+
+    #[test]
+    fn generate_params_sanitize() {
+        let mut params = make_params("  lofi\u{0} beats ", 30);
+        params.sanitize(PromptSanitization::Strip).unwrap();
+        assert_eq!(params.prompt, "lofi beats");
+
+        let mut params = make_params("lofi\u{0} beats", 30);
+        let err = params.sanitize(PromptSanitization::Reject).unwrap_err();
+        assert_eq!(err.code, -32006);
+
+        // Whitespace-only prompts become empty and fail validation
+        let mut params = make_params(" \t\r ", 30);
+        params.sanitize(PromptSanitization::Strip).unwrap();
+        assert_eq!(params.validate(Backend::MusicGen).unwrap_err().code, -32006);
+    }
+
+    #[test]
+    fn generate_params_validate_output_sample_rate() {
+        let mut params = make_params("test", 30);
+        params.output_sample_rate = Some(44100);
+        assert!(params.validate(Backend::MusicGen).is_ok());
+
+        params.output_sample_rate = Some(96000);
+        let err = params.validate(Backend::AceStep).unwrap_err();
+        assert_eq!(err.code, -32602);
+        assert!(err.message.contains("44100"));
+    }
+
+    #[test]
+    fn generate_params_validate_reverb() {
+        let mut params = make_params("test", 30);
+        params.reverb = Some("lofi-tape".to_string());
+        assert!(params.validate(Backend::MusicGen).is_ok());
+        assert_eq!(
+            params.reverb_settings(),
+            Some(Reverb::new(ReverbPreset::LofiTape, None))
+        );
+
+        params.reverb_wet = Some(0.6);
+        assert!(params.validate(Backend::AceStep).is_ok());
+        assert_eq!(params.reverb_settings().unwrap().wet, 0.6);
+
+        params.reverb_wet = Some(1.5);
+        let err = params.validate(Backend::AceStep).unwrap_err();
+        assert_eq!(err.code, -32602);
+
+        params.reverb_wet = Some(0.5);
+        params.reverb = Some("cathedral".to_string());
+        let err = params.validate(Backend::MusicGen).unwrap_err();
+        assert!(err.message.contains("lofi-tape"));
+        assert_eq!(params.reverb_settings(), None);
+
+        // A wet mix without a preset is rejected rather than ignored
+        params.reverb = None;
+        assert!(params.validate(Backend::MusicGen).is_err());
+    }
+
+    #[test]
+    fn manifest_request_reproduces_track_id() {
+        let blend = vec![("jazzy piano".to_string(), 0.7), ("rain".to_string(), 0.3)];
+        let mut original = make_params("", 45);
+        original.prompt_blend = Some(blend.clone());
+        original.output_sample_rate = Some(44100);
+        original.reverb = Some("lofi-tape".to_string());
+        original.sanitize(PromptSanitization::Strip).unwrap();
+        let track = Track::new(
+            std::path::PathBuf::from("/nonexistent/a.wav"),
+            original.prompt.clone(),
+            45.0,
+            9,
+            "ace-step-v1-noise2".to_string(),
+            Backend::AceStep,
+            1.0,
+        )
+        .with_prompt_blend(Some(blend))
+        .with_output_sample_rate(44100)
+        .with_reverb(original.reverb_settings().as_ref());
+        assert_eq!(
+            track.track_id,
+            ace_step_track_id(&original, 9, "ace-step-v1-noise2")
+        );
+
+        let manifest = Manifest::from_track(&track, None);
+        let mut params = GenerateParams::from_manifest(&manifest);
+        params.sanitize(PromptSanitization::Strip).unwrap();
+        assert!(params.validate(Backend::AceStep).is_ok());
+        assert_eq!(params.resolve_backend(Backend::MusicGen).unwrap(), Backend::AceStep);
+        assert_eq!(ace_step_track_id(&params, 9, &manifest.model_version), track.track_id);
+    }
+
+    #[test]
+    fn generate_params_validate_notify_interval() {
+        let mut params = make_params("test", 30);
+        params.notify_interval_ms = Some(0);
+        assert!(params.validate(Backend::MusicGen).is_ok());
+
+        params.notify_interval_ms = Some(MAX_NOTIFY_INTERVAL_MS + 1);
+        let err = params.validate(Backend::MusicGen).unwrap_err();
+        assert!(err.message.contains("notify_interval_ms"));
+    }
+
+    #[test]
+    fn generate_params_prompt_blend() {
+        let mut params: GenerateParams = serde_json::from_value(serde_json::json!({
+            "prompt": "",
+            "prompt_blend": [["jazzy piano ", 7.0], ["rain\u{0}", 3.0]],
+        }))
+        .unwrap();
+        params.sanitize(PromptSanitization::Strip).unwrap();
+        assert_eq!(params.prompt, "70% jazzy piano + 30% rain");
+        let blend = params.prompt_blend.as_ref().unwrap();
+        assert_eq!(blend[1].0, "rain");
+        assert!((blend[0].1 - 0.7).abs() < 1e-6);
+        assert!(params.validate(Backend::AceStep).is_ok());
+        assert_eq!(params.validate(Backend::MusicGen).unwrap_err().code, -32602);
+
+        params.prompt_blend = Some(vec![("rain".to_string(), 0.0)]);
+        let err = params.sanitize(PromptSanitization::Strip).unwrap_err();
+        assert_eq!(err.code, -32006);
+    }
+
+    #[test]
+    fn generate_params_prompt_tags() {
+        let mut params: GenerateParams = serde_json::from_value(serde_json::json!({
+            "prompt_tags": { "genre": ["lofi", "jazz"], "bpm": 80, "mood": ["chill"] },
+        }))
+        .unwrap();
+        assert!(params.compose_prompt_tags().unwrap().is_empty());
+        params.sanitize(PromptSanitization::Strip).unwrap();
+        assert_eq!(params.prompt, "jazz, lofi, 80 bpm, chill");
+        assert!(params.validate(Backend::AceStep).is_ok());
+
+        // Hashing sees the composed prompt, so equivalent tags share a track
+        let reordered = PromptTags {
+            genre: vec!["Jazz".to_string(), "lofi".to_string()],
+            ..params.prompt_tags.clone().unwrap()
         };
+        let mut other = make_params("", 30);
+        other.prompt_tags = Some(reordered);
+        other.compose_prompt_tags().unwrap();
+        assert_eq!(
+            ace_step_track_id(&other, 1, "v1"),
+            ace_step_track_id(&params, 1, "v1")
+        );
+
+        // Exactly one of prompt and prompt_tags
+        let mut both = make_params("lofi beats", 30);
+        both.prompt_tags = params.prompt_tags.clone();
+        let err = both.compose_prompt_tags().unwrap_err();
+        assert_eq!(err.code, -32602);
+        assert!(err.message.contains("prompt and prompt_tags"));
+
+        let mut neither = make_params("", 30);
+        assert!(neither.compose_prompt_tags().unwrap().is_empty());
+        assert_eq!(neither.validate(Backend::AceStep).unwrap_err().code, -32006);
+
+        let mut blend = make_params("", 30);
+        blend.prompt_tags = params.prompt_tags.clone();
+        blend.prompt_blend = Some(vec![("rain".to_string(), 1.0)]);
+        assert_eq!(blend.compose_prompt_tags().unwrap_err().code, -32602);
+
+        let mut fast = make_params("", 30);
+        fast.prompt_tags = Some(PromptTags { bpm: Some(240), ..PromptTags::default() });
+        assert_eq!(fast.compose_prompt_tags().unwrap_err().code, -32602);
+    }
+
+    #[test]
+    fn generate_params_validate_tags() {
+        let mut params = make_params("test", 30);
+        params.tags = vec!["late night".to_string()];
+        assert_eq!(params.validate(Backend::MusicGen).unwrap_err().code, -32602);
+
+        params.tags = vec!["x".repeat(MAX_TAG_LENGTH + 1)];
+        assert_eq!(params.validate(Backend::MusicGen).unwrap_err().code, -32602);
+
+        params.tags = (0..=MAX_TAGS).map(|i| format!("tag{}", i)).collect();
+        assert_eq!(params.validate(Backend::MusicGen).unwrap_err().code, -32602);
+
+        params.tags = vec!["Chill".to_string(), "study".to_string()];
         assert!(params.validate(Backend::MusicGen).is_ok());
     }
 
+    #[test]
+    fn generate_params_duration_warning() {
+        let params = make_params("lofi beats", 120);
+        assert!(params.duration_warning(None).is_none());
+        assert!(params.duration_warning(Some(120.0)).is_none());
+
+        let warning = params.duration_warning(Some(90.4)).unwrap();
+        assert!(warning.contains("120s"));
+        assert!(warning.contains("90s"));
+    }
+
     #[test]
     fn generate_params_validate_ace_step_params() {
         let mut params = make_params("test", 60);
-        params.inference_steps = Some(30);
+        params.inference_steps = Some(StepsParam::Fixed(30));
         params.scheduler = Some("euler".to_string());
         params.guidance_scale = Some(7.0);
         assert!(params.validate(Backend::AceStep).is_ok());
     }
 
+    #[test]
+    fn batch_seeds_prefer_explicit_list() {
+        let mut params = make_params("test", 30);
+        assert_eq!(params.batch_seeds(7), None);
+
+        params.batch_size = Some(3);
+        assert_eq!(params.batch_seeds(7), Some(vec![7, 8, 9]));
+        assert_eq!(params.batch_seeds(u64::MAX), Some(vec![u64::MAX, 0, 1]));
+        assert!(params.batch_warnings().is_empty());
+
+        params.seed = Some(1);
+        params.seeds = Some(vec![42, 100, 42, 7, 42]);
+        assert!(params.validate(Backend::MusicGen).is_ok());
+        assert_eq!(params.batch_seeds(7), Some(vec![42, 100, 7]));
+        assert_eq!(
+            params.batch_warnings(),
+            [
+                "Seed 42 is listed more than once",
+                "batch_size is ignored because seeds are given",
+                "seed is ignored because seeds are given",
+            ]
+        );
+
+        params.seeds = Some(Vec::new());
+        assert_eq!(params.validate(Backend::MusicGen).unwrap_err().code, -32602);
+        params.seeds = Some((0..11).collect());
+        assert!(params.validate(Backend::MusicGen).is_err());
+        params.seeds = None;
+        params.batch_size = Some(0);
+        assert!(params.validate(Backend::MusicGen).is_err());
+    }
+
+    #[test]
+    fn generate_params_output_path_is_single_track() {
+        let mut params = make_params("test", 30);
+        params.also_cache = true;
+        assert!(params.validate(Backend::MusicGen).is_err());
+        params.output_path = Some("/tmp/render.wav".to_string());
+        assert!(params.validate(Backend::MusicGen).is_ok());
+        params.batch_size = Some(2);
+        let err = params.validate(Backend::MusicGen).unwrap_err();
+        assert!(err.message.contains("output_path"));
+    }
+
     #[test]
     fn generate_params_invalid_inference_steps() {
         let mut params = make_params("test", 60);
-        params.inference_steps = Some(300);
+        params.inference_steps = Some(StepsParam::Fixed(300));
         let err = params.validate(Backend::AceStep).unwrap_err();
         assert_eq!(err.code, -32009);
     }
 
+    #[test]
+    fn generate_params_auto_inference_steps() {
+        let defaults = AceStepConfig::default();
+        let mut auto: GenerateParams = serde_json::from_value(serde_json::json!({
+            "prompt": "rain", "duration_sec": 120, "inference_steps": "auto"
+        }))
+        .unwrap();
+        assert_eq!(auto.inference_steps, Some(StepsParam::Auto));
+        assert!(auto.validate(Backend::AceStep).is_ok());
+        assert_eq!(auto.resolve_inference_steps(Backend::AceStep, &defaults), Some(57));
+
+        // Auto steps share the track of the explicit count they resolve to
+        let mut explicit: GenerateParams = serde_json::from_value(serde_json::json!({
+            "prompt": "rain", "duration_sec": 120, "inference_steps": 57
+        }))
+        .unwrap();
+        assert_eq!(explicit.resolve_inference_steps(Backend::AceStep, &defaults), Some(57));
+        let track_id = ace_step_track_id(&explicit, 1, "v1");
+        assert_eq!(ace_step_track_id(&auto, 1, "v1"), track_id);
+
+        explicit.inference_steps = Some(StepsParam::Fixed(90));
+        assert_eq!(explicit.resolve_inference_steps(Backend::AceStep, &defaults), Some(90));
+        assert_ne!(ace_step_track_id(&explicit, 1, "v1"), track_id);
+
+        // The configured default keeps the track ID of a request without steps
+        let mut default = make_params("rain", 120);
+        let unresolved = ace_step_track_id(&default, 1, "v1");
+        assert_eq!(default.resolve_inference_steps(Backend::AceStep, &defaults), Some(60));
+        assert_eq!(ace_step_track_id(&default, 1, "v1"), unresolved);
+        assert_eq!(default.resolve_inference_steps(Backend::MusicGen, &defaults), None);
+        assert_eq!(default.inference_steps, None);
+    }
+
+    #[test]
+    fn generate_params_resolve_sampling() {
+        let defaults = AceStepConfig::default();
+        let mut params = make_params("rain", 60);
+        let track_id = ace_step_track_id(&params, 1, "v1");
+        params.resolve_sampling(Backend::AceStep, &defaults);
+        assert_eq!(params.scheduler.as_deref(), Some(defaults.scheduler.as_str()));
+        assert_eq!(params.guidance_scale, Some(defaults.guidance_scale));
+        assert_eq!(ace_step_track_id(&params, 1, "v1"), track_id);
+
+        params.scheduler = Some("heun".to_string());
+        params.guidance_scale = Some(1.0);
+        params.resolve_sampling(Backend::AceStep, &defaults);
+        assert_eq!(params.scheduler.as_deref(), Some("heun"));
+        assert_eq!(params.guidance_scale, Some(1.0));
+
+        params.resolve_sampling(Backend::MusicGen, &defaults);
+        assert_eq!(params.scheduler, None);
+        assert_eq!(params.guidance_scale, None);
+    }
+
     #[test]
     fn generate_params_invalid_guidance_scale() {
         let mut params = make_params("test", 60);
@@ -849,7 +2485,7 @@ mod tests {
         assert_eq!(JsonRpcError::model_load_failed("").code, -32001);
         assert_eq!(JsonRpcError::model_download_failed("").code, -32002);
         assert_eq!(JsonRpcError::model_inference_failed("").code, -32003);
-        assert_eq!(JsonRpcError::queue_full(10).code, -32004);
+        assert_eq!(JsonRpcError::queue_full(10, 10).code, -32004);
         assert_eq!(JsonRpcError::invalid_duration(0).code, -32005);
         assert_eq!(JsonRpcError::invalid_prompt("").code, -32006);
         assert_eq!(JsonRpcError::invalid_backend("").code, -32007);
@@ -857,6 +2493,45 @@ mod tests {
         assert_eq!(JsonRpcError::invalid_inference_steps(0).code, -32009);
         assert_eq!(JsonRpcError::invalid_guidance_scale(0.0).code, -32010);
         assert_eq!(JsonRpcError::invalid_scheduler("").code, -32011);
+        assert_eq!(JsonRpcError::insufficient_memory("").code, -32012);
+        assert_eq!(JsonRpcError::generation_cancelled("").code, -32013);
+        assert_eq!(JsonRpcError::tokenizer_missing("").code, -32014);
+        assert_eq!(JsonRpcError::timed_out("").code, -32015);
+        assert_eq!(JsonRpcError::cache_read_only("").code, -32016);
+        assert_eq!(JsonRpcError::output_path_invalid("").code, -32017);
+        assert_eq!(JsonRpcError::storage_failed("").code, -32018);
+        assert_eq!(JsonRpcError::rate_limit_exceeded("generate", 10).code, -32029);
+    }
+
+    #[test]
+    fn json_rpc_error_from_daemon_error() {
+        let cases = [
+            (ErrorCode::ModelNotFound, -32000),
+            (ErrorCode::ModelLoadFailed, -32001),
+            (ErrorCode::ModelDownloadFailed, -32002),
+            (ErrorCode::ModelInferenceFailed, -32003),
+            (ErrorCode::QueueFull, -32004),
+            (ErrorCode::InvalidDuration, -32005),
+            (ErrorCode::InvalidPrompt, -32006),
+            (ErrorCode::BackendNotInstalled, -32008),
+            (ErrorCode::InvalidInferenceSteps, -32009),
+            (ErrorCode::InvalidGuidanceScale, -32010),
+            (ErrorCode::InvalidScheduler, -32011),
+            (ErrorCode::InsufficientMemory, -32012),
+            (ErrorCode::GenerationCancelled, -32013),
+            (ErrorCode::TokenizerMissing, -32014),
+            (ErrorCode::TimedOut, -32015),
+            (ErrorCode::CacheReadOnly, -32016),
+            (ErrorCode::OutputPathInvalid, -32017),
+            (ErrorCode::StorageFailed, -32018),
+        ];
+        for (code, rpc_code) in cases {
+            let err = JsonRpcError::from(DaemonError::new(code, "something broke"));
+            assert_eq!(err.code, rpc_code, "{}", code);
+            let data = err.data.unwrap();
+            assert_eq!(data.error_code, code.as_str());
+            assert_eq!(data.details.as_deref(), Some("something broke"));
+        }
     }
 
     #[test]
@@ -869,6 +2544,8 @@ mod tests {
         assert_eq!(info.max_duration_sec, 120);
         assert_eq!(info.sample_rate, 32000);
         assert_eq!(info.model_version, Some("v1".to_string()));
+        assert!(!info.loaded);
+        assert!(BackendInfo::new(Backend::MusicGen, BackendStatus::Loaded, None).loaded);
 
         let info = BackendInfo::new(Backend::AceStep, BackendStatus::NotInstalled, None);
         assert_eq!(info.backend_type, "ace_step");
@@ -879,4 +2556,31 @@ mod tests {
         assert_eq!(info.sample_rate, 48000);
         assert!(info.model_version.is_none());
     }
+
+    #[test]
+    fn generation_error_params_from_error() {
+        use crate::error::{ErrorCode, GenerationPhase};
+
+        let cause = std::io::Error::other("onnxruntime: Non-zero status code");
+        let error = DaemonError::model_inference_failed_with_source("Vocoder inference failed", cause)
+            .with_phase(GenerationPhase::Vocode);
+        let value = serde_json::to_value(GenerationErrorParams::from_error("abc", &error)).unwrap();
+
+        assert_eq!(value["track_id"], "abc");
+        assert_eq!(value["code"], "MODEL_INFERENCE_FAILED");
+        assert_eq!(value["message"], "Inference failed: Vocoder inference failed");
+        assert_eq!(value["recovery_hint"], ErrorCode::ModelInferenceFailed.recovery_hint());
+        assert_eq!(value["retriable"], true);
+        assert_eq!(value["phase"], "vocode");
+        assert_eq!(value["details"], "onnxruntime: Non-zero status code");
+        assert!(value.get("dump_path").is_none());
+
+        // Validation errors carry no phase or details and are not retriable
+        let value =
+            serde_json::to_value(GenerationErrorParams::from_error("abc", &DaemonError::empty_prompt()))
+                .unwrap();
+        assert_eq!(value["retriable"], false);
+        assert!(value.get("phase").is_none());
+        assert!(value.get("details").is_none());
+    }
 }
@@ -3,8 +3,46 @@
 //! Contains the configuration parameters for the MusicGen ONNX model
 //! ensemble, matching the model's architecture requirements.
 
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
+/// Floating-point precision of the MusicGen ONNX weights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    /// Half precision, as in the `small_fp16` export the daemon downloads.
+    Fp16,
+    /// Single precision.
+    Fp32,
+}
+
+impl Precision {
+    /// Returns the precision name, `fp16` or `fp32`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Precision::Fp16 => "fp16",
+            Precision::Fp32 => "fp32",
+        }
+    }
+
+    /// Parses a precision name, also accepting the `float16` and `float32`
+    /// spellings of `torch_dtype`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "fp16" | "float16" => Some(Precision::Fp16),
+            "fp32" | "float32" => Some(Precision::Fp32),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Precision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Configuration parameters for the MusicGen model architecture.
 ///
 /// These values are derived from the model's config.json and are required
@@ -38,6 +76,12 @@ pub struct ModelConfig {
 
     /// Padding token ID for the decoder.
     pub pad_token_id: i64,
+
+    /// Precision declared by the `precision` key of config.json.
+    ///
+    /// None leaves it to the decoder, which reads it from its ONNX graph.
+    #[serde(default)]
+    pub precision: Option<Precision>,
 }
 
 impl ModelConfig {
@@ -56,6 +100,7 @@ impl ModelConfig {
             sample_rate: 32000,
             codebooks: 4,
             pad_token_id: 2048, // vocab_size is used as pad token
+            precision: None,
         }
     }
 
@@ -151,4 +196,12 @@ mod tests {
         let size = config.kv_cache_size_per_layer(100);
         assert_eq!(size, 8 * 16 * 100 * 64);
     }
+
+    #[test]
+    fn precision_names() {
+        assert_eq!(Precision::parse("fp16"), Some(Precision::Fp16));
+        assert_eq!(Precision::parse("Float32"), Some(Precision::Fp32));
+        assert_eq!(Precision::parse("bf16"), None);
+        assert_eq!(Precision::Fp16.to_string(), "fp16");
+    }
 }
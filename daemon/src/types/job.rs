@@ -3,15 +3,27 @@
 //! A GenerationJob tracks a request for music generation from submission
 //! through completion, including progress updates and error information.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::time::SystemTime;
 
+use crate::audio::{Reverb, SilenceTrim};
 use crate::models::Backend;
 
-use super::track::compute_track_id;
+use super::params::ParamAdjustment;
+use super::track::{
+    compute_track_id, output_track_id, reverb_track_id, steps_track_id, trim_track_id,
+};
+
+/// Identifies the client connection a request arrived on.
+pub type ConnectionId = u64;
+
+/// Connection id used for the stdio transport, which has a single client.
+pub const STDIO_CONNECTION_ID: ConnectionId = 0;
 
 /// Priority level for generation jobs.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum JobPriority {
     /// Normal priority - processed in FIFO order.
@@ -65,15 +77,88 @@ pub struct GenerationJob {
     /// Text description of desired music (1-1000 characters).
     pub prompt: String,
 
+    /// Weighted prompts to blend instead of `prompt` (ACE-Step only).
+    #[serde(default)]
+    pub prompt_blend: Option<Vec<(String, f32)>>,
+
     /// Requested audio duration in seconds (5-120, default 30).
     pub duration_sec: u32,
 
+    /// Backend that generates the job.
+    #[serde(default)]
+    pub backend: Backend,
+
+    /// Sample rate to resample the output to, if not the backend's native rate.
+    #[serde(default)]
+    pub output_sample_rate: Option<u32>,
+
+    /// Reverb applied to the output before loudness normalization.
+    #[serde(default)]
+    pub reverb: Option<Reverb>,
+
+    /// Whether near-silent padding is trimmed from the ends of the output.
+    #[serde(default)]
+    pub trim_silence: bool,
+
+    /// ACE-Step diffusion steps; the configured default if None.
+    #[serde(default)]
+    pub inference_steps: Option<u32>,
+
+    /// ACE-Step scheduler; the configured default if None.
+    #[serde(default)]
+    pub scheduler: Option<String>,
+
+    /// ACE-Step guidance scale; the configured default if None.
+    #[serde(default)]
+    pub guidance_scale: Option<f32>,
+
+    /// Minimum milliseconds between progress notifications, overriding
+    /// the configured `min_notify_interval_ms`.
+    #[serde(default)]
+    pub notify_interval_ms: Option<u64>,
+
     /// Random seed for generation. If None, system generates random seed.
     pub seed: Option<u64>,
 
     /// Queue priority for this job.
     pub priority: JobPriority,
 
+    /// Order of submission to the queue, assigned by `GenerationQueue::add`.
+    ///
+    /// Breaks ties between jobs of the same priority.
+    #[serde(default)]
+    pub submission_seq: u64,
+
+    /// User-supplied tags copied to the resulting track.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Request parameters replaced in clamp mode, reported on completion.
+    #[serde(default)]
+    pub adjusted_params: Vec<ParamAdjustment>,
+
+    /// Whether to copy the finished track into the library directory.
+    #[serde(default)]
+    pub save_to_output: bool,
+
+    /// File to write the finished track to instead of the cache directory.
+    #[serde(default)]
+    pub output_path: Option<PathBuf>,
+
+    /// Whether a track written to `output_path` is also copied into the cache.
+    #[serde(default)]
+    pub also_cache: bool,
+
+    /// Rated seed of the prompt that a `prefer_rated` request chose this
+    /// job's seed in place of, recorded on the track.
+    #[serde(default)]
+    pub derived_from_seed: Option<u64>,
+
+    /// Connection that submitted the job. Its terminal notifications always
+    /// reach this connection, regardless of subscription filters.
+    #[serde(default)]
+    pub connection_id: ConnectionId,
+
     /// Current job state.
     pub status: JobStatus,
 
@@ -145,9 +230,26 @@ impl GenerationJob {
             job_id,
             track_id,
             prompt,
+            prompt_blend: None,
             duration_sec,
+            backend,
+            output_sample_rate: None,
+            reverb: None,
+            trim_silence: false,
+            inference_steps: None,
+            scheduler: None,
+            guidance_scale: None,
+            notify_interval_ms: None,
             seed: Some(actual_seed),
             priority,
+            submission_seq: 0,
+            tags: Vec::new(),
+            adjusted_params: Vec::new(),
+            save_to_output: false,
+            output_path: None,
+            also_cache: false,
+            derived_from_seed: None,
+            connection_id: STDIO_CONNECTION_ID,
             status: JobStatus::Pending,
             queue_position: None,
             progress_percent: 0,
@@ -162,6 +264,104 @@ impl GenerationJob {
         }
     }
 
+    /// Sets the tags to copy onto the generated track.
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Sets the weighted prompts to blend.
+    pub fn with_prompt_blend(mut self, prompt_blend: Option<Vec<(String, f32)>>) -> Self {
+        self.prompt_blend = prompt_blend;
+        self
+    }
+
+    /// Sets the sample rate of the delivered WAV.
+    ///
+    /// The track ID is updated to match [`output_track_id`].
+    pub fn with_output_sample_rate(mut self, output_sample_rate: Option<u32>) -> Self {
+        if let Some(rate) = output_sample_rate {
+            self.track_id = output_track_id(&self.track_id, self.backend, rate);
+        }
+        self.output_sample_rate = output_sample_rate;
+        self
+    }
+
+    /// Sets the reverb applied to the output.
+    ///
+    /// The track ID is updated to match [`reverb_track_id`]. Call after
+    /// [`with_output_sample_rate`](Self::with_output_sample_rate).
+    pub fn with_reverb(mut self, reverb: Option<Reverb>) -> Self {
+        self.track_id = reverb_track_id(&self.track_id, reverb.as_ref());
+        self.reverb = reverb;
+        self
+    }
+
+    /// Sets the trim applied to near-silent padding in the output, if any.
+    ///
+    /// The track ID is updated to match [`trim_track_id`]. Call after
+    /// [`with_reverb`](Self::with_reverb).
+    pub fn with_trim_silence(mut self, trim: Option<&SilenceTrim>) -> Self {
+        self.track_id = trim_track_id(&self.track_id, trim);
+        self.trim_silence = trim.is_some();
+        self
+    }
+
+    /// Sets the requested ACE-Step parameters.
+    ///
+    /// The track ID is updated to match [`steps_track_id`]. Call before
+    /// [`with_output_sample_rate`](Self::with_output_sample_rate).
+    pub fn with_ace_step_params(
+        mut self,
+        inference_steps: Option<u32>,
+        scheduler: Option<String>,
+        guidance_scale: Option<f32>,
+    ) -> Self {
+        self.track_id = steps_track_id(&self.track_id, self.backend, inference_steps);
+        self.inference_steps = inference_steps;
+        self.scheduler = scheduler;
+        self.guidance_scale = guidance_scale;
+        self
+    }
+
+    /// Sets the minimum interval between progress notifications.
+    pub fn with_notify_interval_ms(mut self, notify_interval_ms: Option<u64>) -> Self {
+        self.notify_interval_ms = notify_interval_ms;
+        self
+    }
+
+    /// Sets the parameter adjustments made in clamp mode.
+    pub fn with_adjusted_params(mut self, adjusted_params: Vec<ParamAdjustment>) -> Self {
+        self.adjusted_params = adjusted_params;
+        self
+    }
+
+    /// Sets whether the finished track is copied into the library directory.
+    pub fn with_save_to_output(mut self, save_to_output: bool) -> Self {
+        self.save_to_output = save_to_output;
+        self
+    }
+
+    /// Sets the file to write the finished track to, and whether it is also
+    /// copied into the cache.
+    pub fn with_output_path(mut self, output_path: Option<PathBuf>, also_cache: bool) -> Self {
+        self.output_path = output_path;
+        self.also_cache = also_cache;
+        self
+    }
+
+    /// Records the rated seed the job's seed was chosen in place of.
+    pub fn with_derived_from_seed(mut self, derived_from_seed: Option<u64>) -> Self {
+        self.derived_from_seed = derived_from_seed;
+        self
+    }
+
+    /// Sets the connection that submitted the job.
+    pub fn with_connection_id(mut self, connection_id: ConnectionId) -> Self {
+        self.connection_id = connection_id;
+        self
+    }
+
     /// Validates job parameters.
     ///
     /// Returns an error message if validation fails, None otherwise.
@@ -0,0 +1,363 @@
+//! Reproducibility manifests for sharing how a track was made.
+//!
+//! A [`Manifest`] is a small JSON recipe exported from a cached track: the
+//! prompt, seed, duration, backend, model identity, sampling parameters, and
+//! post-processing. Importing it on another machine regenerates the same
+//! track, bit-identical when the model files match.
+//!
+//! Manifests carry a `manifest_version`. Readers reject versions newer than
+//! [`MANIFEST_VERSION`] and manifests missing a required field, and warn
+//! about (but otherwise ignore) fields they do not know, so that manifests
+//! from newer daemons still import when they only add optional fields.
+
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::Reverb;
+use crate::models::ace_step::{DEFAULT_OMEGA, DEFAULT_SHIFT, UNCONDITIONAL_PROMPT};
+use crate::models::{musicgen, Backend};
+
+use super::provenance::SamplingParams;
+use super::track::Track;
+
+/// Current manifest format version.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Top-level manifest fields understood by this daemon.
+const MANIFEST_FIELDS: &[&str] = &[
+    "manifest_version",
+    "daemon_version",
+    "backend",
+    "prompt",
+    "prompt_blend",
+    "negative_prompt",
+    "seed",
+    "duration_sec",
+    "model_version",
+    "model_fingerprint",
+    "sampling",
+    "shift",
+    "omega",
+    "output_sample_rate",
+    "reverb",
+    "trim_silence",
+    "post_processing",
+];
+
+/// Everything needed to regenerate a track.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Manifest {
+    /// Manifest format version (currently 1).
+    pub manifest_version: u32,
+
+    /// lofi-daemon version that exported the manifest.
+    pub daemon_version: String,
+
+    /// Backend that generated the track.
+    pub backend: Backend,
+
+    /// Text prompt. For prompt blends this is the blend label.
+    pub prompt: String,
+
+    /// Weighted prompts blended to produce the track (ACE-Step only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_blend: Option<Vec<(String, f32)>>,
+
+    /// Prompt of the unconditional guidance branch (ACE-Step only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub negative_prompt: Option<String>,
+
+    /// Random seed.
+    pub seed: u64,
+
+    /// Requested duration in seconds.
+    pub duration_sec: u32,
+
+    /// Model identifier, part of the track ID.
+    pub model_version: String,
+
+    /// Fingerprint of the exporter's model files, if they were installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_fingerprint: Option<String>,
+
+    /// Sampling parameters. Missing ACE-Step values use the importer's defaults.
+    #[serde(default)]
+    pub sampling: SamplingParams,
+
+    /// Flow matching schedule shift (ACE-Step only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shift: Option<f32>,
+
+    /// Omega scale for mean shifting (ACE-Step only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub omega: Option<f32>,
+
+    /// Sample rate of the delivered WAV, if not the backend's native rate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_sample_rate: Option<u32>,
+
+    /// Reverb applied before loudness normalization.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverb: Option<Reverb>,
+
+    /// Whether near-silent padding was trimmed from the ends. `duration_sec`
+    /// is then the requested duration, before trimming.
+    #[serde(default)]
+    pub trim_silence: bool,
+
+    /// Processing applied to the exported track, in order. Informational:
+    /// the importer applies its own loudness settings.
+    #[serde(default)]
+    pub post_processing: Vec<String>,
+}
+
+impl Manifest {
+    /// Creates the manifest of a cached track.
+    ///
+    /// `model_fingerprint` identifies the local model files (see
+    /// [`get_backend_fingerprint`](crate::models::get_backend_fingerprint)).
+    pub fn from_track(track: &Track, model_fingerprint: Option<String>) -> Self {
+        let is_ace_step = track.backend == Backend::AceStep;
+        let provenance = track.provenance.as_ref();
+        Self {
+            manifest_version: MANIFEST_VERSION,
+            daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+            backend: track.backend,
+            prompt: track.prompt.clone(),
+            prompt_blend: track.prompt_blend.clone(),
+            negative_prompt: is_ace_step.then(|| UNCONDITIONAL_PROMPT.to_string()),
+            seed: track.seed,
+            duration_sec: (track.duration_sec + track.trimmed_sec.unwrap_or(0.0)).round() as u32,
+            model_version: track.model_version.clone(),
+            model_fingerprint,
+            sampling: provenance.map(|p| p.sampling.clone()).unwrap_or_default(),
+            shift: is_ace_step.then_some(DEFAULT_SHIFT),
+            omega: is_ace_step.then_some(DEFAULT_OMEGA),
+            output_sample_rate: (track.sample_rate != track.backend.sample_rate())
+                .then_some(track.sample_rate),
+            reverb: track.reverb,
+            trim_silence: track.trimmed_sec.is_some(),
+            post_processing: provenance
+                .map(|p| p.post_processing.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Parses a manifest from JSON.
+    ///
+    /// Returns the manifest and a warning for each unknown field, or an
+    /// error if the version is unsupported or a required field is missing.
+    pub fn from_value(value: serde_json::Value) -> Result<(Self, Vec<String>), String> {
+        let object = value
+            .as_object()
+            .ok_or_else(|| "Manifest must be a JSON object".to_string())?;
+        let version = object
+            .get("manifest_version")
+            .ok_or_else(|| "Manifest is missing manifest_version".to_string())?
+            .as_u64()
+            .ok_or_else(|| "manifest_version must be a positive integer".to_string())?;
+        if version == 0 || version > MANIFEST_VERSION as u64 {
+            return Err(format!(
+                "Unsupported manifest version {} (this daemon reads version {})",
+                version, MANIFEST_VERSION
+            ));
+        }
+        let warnings = object
+            .keys()
+            .filter(|key| !MANIFEST_FIELDS.contains(&key.as_str()))
+            .map(|key| format!("Ignoring unknown manifest field '{}'", key))
+            .collect();
+
+        let manifest = serde_path_to_error::deserialize(value).map_err(|e| {
+            let path = e.path().to_string();
+            if path == "." {
+                format!("Invalid manifest: {}", e.inner())
+            } else {
+                format!("Invalid manifest: {}: {}", path, e.inner())
+            }
+        })?;
+        Ok((manifest, warnings))
+    }
+
+    /// Lists the reasons regenerating here may not reproduce the track exactly.
+    ///
+    /// `local_model_version` and `local_fingerprint` describe the installed
+    /// models of the manifest's backend; None skips the comparison.
+    pub fn compatibility_warnings(
+        &self,
+        local_model_version: Option<&str>,
+        local_fingerprint: Option<&str>,
+    ) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some(local) = local_model_version {
+            if local != self.model_version {
+                warnings.push(format!(
+                    "Manifest was generated with model {} but {} is installed; the track will differ",
+                    self.model_version, local
+                ));
+            }
+        }
+        if let (Some(expected), Some(local)) = (&self.model_fingerprint, local_fingerprint) {
+            if expected != local {
+                warnings.push(format!(
+                    "Local {} model files differ from the exporter's (fingerprint {} vs {}); the result won't be bit-identical",
+                    self.backend, local, expected
+                ));
+            }
+        }
+
+        let mut unsupported = |name: &str, value: String, local: String| {
+            warnings.push(format!(
+                "{} {} is not supported; generating with {}",
+                name, value, local
+            ));
+        };
+        match self.backend {
+            Backend::AceStep => {
+                if let Some(prompt) = self.negative_prompt.as_deref() {
+                    if prompt != UNCONDITIONAL_PROMPT {
+                        unsupported(
+                            "negative_prompt",
+                            format!("{:?}", prompt),
+                            format!("{:?}", UNCONDITIONAL_PROMPT),
+                        );
+                    }
+                }
+                for (name, value, local) in [
+                    ("shift", self.shift, DEFAULT_SHIFT),
+                    ("omega", self.omega, DEFAULT_OMEGA),
+                ] {
+                    if let Some(value) = value.filter(|&v| v != local) {
+                        unsupported(name, value.to_string(), local.to_string());
+                    }
+                }
+            }
+            Backend::MusicGen => {
+                let top_k = musicgen::DEFAULT_TOP_K;
+                if let Some(value) = self.sampling.top_k.filter(|&k| k != top_k) {
+                    unsupported("top_k", value.to_string(), top_k.to_string());
+                }
+                let guidance = musicgen::DEFAULT_GUIDANCE_SCALE as f32;
+                if let Some(value) = self.sampling.guidance_scale.filter(|&g| g != guidance) {
+                    unsupported("guidance_scale", value.to_string(), guidance.to_string());
+                }
+            }
+        }
+        warnings
+    }
+}
+
+/// Reads and parses a manifest file, returning it with its warnings.
+pub fn read_manifest(path: &Path) -> Result<(Manifest, Vec<String>), String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read manifest {}: {}", path.display(), e))?;
+    let value = serde_json::from_str(&contents)
+        .map_err(|e| format!("Manifest {} is not valid JSON: {}", path.display(), e))?;
+    Manifest::from_value(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::audio::ReverbPreset;
+    use crate::types::Provenance;
+
+    fn ace_step_track() -> Track {
+        let sampling = SamplingParams {
+            scheduler: Some("heun".to_string()),
+            inference_steps: Some(80),
+            guidance_scale: Some(9.5),
+            top_k: None,
+        };
+        Track::new(
+            PathBuf::from("/nonexistent/track.wav"),
+            "rainy night piano".to_string(),
+            60.0,
+            7,
+            "ace-step-v1-noise2".to_string(),
+            Backend::AceStep,
+            12.0,
+        )
+        .with_output_sample_rate(44100)
+        .with_reverb(Some(&Reverb::new(ReverbPreset::Hall, Some(0.4))))
+        .with_provenance(
+            Provenance::new("CPU")
+                .with_sampling(sampling)
+                .with_post_processing("loudness:-14.0 LUFS"),
+        )
+    }
+
+    #[test]
+    fn manifest_round_trips_through_json() {
+        let manifest = Manifest::from_track(&ace_step_track(), Some("abc".to_string()));
+        assert_eq!(manifest.manifest_version, MANIFEST_VERSION);
+        assert_eq!(manifest.duration_sec, 60);
+        assert_eq!(manifest.output_sample_rate, Some(44100));
+        assert_eq!(manifest.sampling.inference_steps, Some(80));
+        assert_eq!(manifest.shift, Some(DEFAULT_SHIFT));
+
+        let value = serde_json::to_value(&manifest).unwrap();
+        for key in value.as_object().unwrap().keys() {
+            assert!(MANIFEST_FIELDS.contains(&key.as_str()), "{} is not listed", key);
+        }
+        let (parsed, warnings) = Manifest::from_value(value).unwrap();
+        assert_eq!(parsed, manifest);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn trimmed_tracks_record_the_requested_duration() {
+        let trim = crate::audio::SilenceTrim::default();
+        let track = ace_step_track().with_trimmed_duration(Some(&trim), 57.2);
+        let manifest = Manifest::from_track(&track, None);
+        assert_eq!(manifest.duration_sec, 60);
+        assert!(manifest.trim_silence);
+        assert!(!Manifest::from_track(&ace_step_track(), None).trim_silence);
+    }
+
+    #[test]
+    fn unknown_fields_warn_and_missing_fields_fail() {
+        let manifest = Manifest::from_track(&ace_step_track(), None);
+        let mut value = serde_json::to_value(&manifest).unwrap();
+        value["lyrics"] = serde_json::json!("la la");
+        let (_, warnings) = Manifest::from_value(value.clone()).unwrap();
+        assert_eq!(warnings, vec!["Ignoring unknown manifest field 'lyrics'"]);
+
+        value.as_object_mut().unwrap().remove("seed");
+        let err = Manifest::from_value(value.clone()).unwrap_err();
+        assert!(err.contains("seed"), "{}", err);
+
+        value["manifest_version"] = serde_json::json!(MANIFEST_VERSION + 1);
+        let err = Manifest::from_value(value.clone()).unwrap_err();
+        assert!(err.contains("Unsupported manifest version"), "{}", err);
+
+        value.as_object_mut().unwrap().remove("manifest_version");
+        assert!(Manifest::from_value(value).unwrap_err().contains("manifest_version"));
+        assert!(Manifest::from_value(serde_json::json!([1])).is_err());
+    }
+
+    #[test]
+    fn compatibility_warnings_flag_model_mismatches() {
+        let mut manifest = Manifest::from_track(&ace_step_track(), Some("abc".to_string()));
+        let version = manifest.model_version.clone();
+        assert!(manifest
+            .compatibility_warnings(Some(&version), Some("abc"))
+            .is_empty());
+        assert!(manifest.compatibility_warnings(None, None).is_empty());
+
+        let warnings = manifest.compatibility_warnings(Some(&version), Some("def"));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("bit-identical"));
+
+        let warnings = manifest.compatibility_warnings(Some("ace-step-v2"), Some("abc"));
+        assert!(warnings[0].contains("ace-step-v2"));
+
+        manifest.shift = Some(5.0);
+        let warnings = manifest.compatibility_warnings(None, None);
+        assert_eq!(warnings, vec!["shift 5 is not supported; generating with 3"]);
+    }
+}
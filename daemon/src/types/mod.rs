@@ -4,12 +4,32 @@
 //! - [`Track`]: A successfully generated audio file stored in the cache
 //! - [`GenerationJob`]: A request for music generation with status tracking
 //! - [`ModelConfig`]: Configuration parameters for the MusicGen model
+//! - [`sanitize_prompt`]: Prompt normalization before validation and hashing
+//! - [`DisplayPrompt`]: Prompt formatting for logs that honors redaction
+//! - [`Provenance`]: How a track was produced, for regression comparisons
+//! - [`ParamAdjustment`]: An out-of-range parameter replaced in clamp mode
+//! - [`StepsParam`]: Requested ACE-Step inference steps, fixed or `auto`
+//! - [`Manifest`]: A shareable recipe for regenerating a track
 
 mod config;
 mod job;
+mod manifest;
+mod params;
+mod prompt;
+mod provenance;
 mod track;
 
 // Re-export all types at the module level
-pub use config::ModelConfig;
-pub use job::{GenerationJob, JobPriority, JobStatus};
-pub use track::{compute_track_id, Track};
+pub use config::{ModelConfig, Precision};
+pub use job::{ConnectionId, GenerationJob, JobPriority, JobStatus, STDIO_CONNECTION_ID};
+pub use manifest::{read_manifest, Manifest, MANIFEST_VERSION};
+pub use params::{
+    clamp_duration, clamp_guidance_scale, clamp_inference_steps, resolve_scheduler,
+    ParamAdjustment, StepsParam,
+};
+pub use prompt::{prompt_hash, redact_prompts, sanitize_prompt, set_redact_prompts, DisplayPrompt};
+pub use provenance::{diff_provenance, Provenance, ProvenanceDifference, SamplingParams};
+pub use track::{
+    compute_track_id, normalize_tags, output_track_id, reverb_track_id, steps_track_id,
+    trim_track_id, Track, TrackVariant,
+};
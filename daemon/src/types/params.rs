@@ -0,0 +1,336 @@
+//! Clamping of out-of-range generation parameters.
+//!
+//! With `param_strictness` set to `clamp`, requests with out-of-range values
+//! are adjusted to the nearest valid value instead of being rejected. Each
+//! change is recorded as a [`ParamAdjustment`] so clients can show what was
+//! actually used. The daemon and the CLI share these rules, as well as
+//! [`StepsParam`], the inference steps either of them accepts.
+
+use std::fmt;
+use std::str::FromStr;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, SubschemaValidation};
+use schemars::JsonSchema;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::config::AceStepConfig;
+use crate::models::ace_step::{
+    auto_inference_steps, SchedulerType, MAX_GUIDANCE_SCALE, MAX_INFERENCE_STEPS,
+    MIN_GUIDANCE_SCALE, MIN_INFERENCE_STEPS,
+};
+use crate::models::Backend;
+
+/// A request parameter replaced by a valid value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ParamAdjustment {
+    /// Name of the parameter, e.g. `guidance_scale`.
+    pub field: String,
+
+    /// Value given in the request.
+    pub requested: serde_json::Value,
+
+    /// Value used for generation.
+    pub used: serde_json::Value,
+}
+
+impl ParamAdjustment {
+    /// Creates an adjustment record.
+    pub fn new(
+        field: impl Into<String>,
+        requested: impl Into<serde_json::Value>,
+        used: impl Into<serde_json::Value>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            requested: requested.into(),
+            used: used.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ParamAdjustment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.requested, self.used)
+    }
+}
+
+/// Requested ACE-Step inference steps: an exact count, or `"auto"` to pick
+/// one from the clip duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepsParam {
+    /// Steps chosen by [`auto_inference_steps`].
+    Auto,
+    /// Exactly this many steps.
+    Fixed(u32),
+}
+
+impl StepsParam {
+    /// Returns the number of steps to run for a clip of `duration_sec`.
+    ///
+    /// A fixed count is returned unchanged; `auto` follows the curve
+    /// configured in `config`.
+    pub fn resolve(self, duration_sec: f32, config: &AceStepConfig) -> u32 {
+        match self {
+            StepsParam::Auto => auto_inference_steps(duration_sec, config),
+            StepsParam::Fixed(steps) => steps,
+        }
+    }
+
+    /// Returns the step count if it is fixed.
+    pub fn fixed(self) -> Option<u32> {
+        match self {
+            StepsParam::Auto => None,
+            StepsParam::Fixed(steps) => Some(steps),
+        }
+    }
+}
+
+impl From<u32> for StepsParam {
+    fn from(steps: u32) -> Self {
+        StepsParam::Fixed(steps)
+    }
+}
+
+impl fmt::Display for StepsParam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepsParam::Auto => f.write_str("auto"),
+            StepsParam::Fixed(steps) => write!(f, "{}", steps),
+        }
+    }
+}
+
+impl FromStr for StepsParam {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(StepsParam::Auto);
+        }
+        s.parse()
+            .map(StepsParam::Fixed)
+            .map_err(|_| format!("expected a number of steps or 'auto', got '{}'", s))
+    }
+}
+
+impl Serialize for StepsParam {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            StepsParam::Auto => serializer.serialize_str("auto"),
+            StepsParam::Fixed(steps) => serializer.serialize_u32(*steps),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StepsParam {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StepsVisitor;
+
+        impl Visitor<'_> for StepsVisitor {
+            type Value = StepsParam;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a number of steps or \"auto\"")
+            }
+
+            fn visit_u64<E: de::Error>(self, steps: u64) -> Result<StepsParam, E> {
+                u32::try_from(steps)
+                    .map(StepsParam::Fixed)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(steps), &self))
+            }
+
+            fn visit_i64<E: de::Error>(self, steps: i64) -> Result<StepsParam, E> {
+                u32::try_from(steps)
+                    .map(StepsParam::Fixed)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Signed(steps), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<StepsParam, E> {
+                if value.eq_ignore_ascii_case("auto") {
+                    Ok(StepsParam::Auto)
+                } else {
+                    Err(E::invalid_value(de::Unexpected::Str(value), &self))
+                }
+            }
+        }
+
+        deserializer.deserialize_any(StepsVisitor)
+    }
+}
+
+impl JsonSchema for StepsParam {
+    fn schema_name() -> String {
+        "StepsParam".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let auto = SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            enum_values: Some(vec!["auto".into()]),
+            ..Default::default()
+        };
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![gen.subschema_for::<u32>(), auto.into()]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Clamps a duration to the backend's supported range.
+pub fn clamp_duration(
+    duration_sec: u32,
+    backend: Backend,
+    adjustments: &mut Vec<ParamAdjustment>,
+) -> u32 {
+    let used = duration_sec.clamp(backend.min_duration_sec(), backend.max_duration_sec());
+    if used != duration_sec {
+        adjustments.push(ParamAdjustment::new("duration_sec", duration_sec, used));
+    }
+    used
+}
+
+/// Clamps ACE-Step inference steps to 1-200.
+pub fn clamp_inference_steps(steps: u32, adjustments: &mut Vec<ParamAdjustment>) -> u32 {
+    let used = steps.clamp(MIN_INFERENCE_STEPS, MAX_INFERENCE_STEPS);
+    if used != steps {
+        adjustments.push(ParamAdjustment::new("inference_steps", steps, used));
+    }
+    used
+}
+
+/// Clamps an ACE-Step guidance scale to 1.0-20.0.
+///
+/// A non-finite scale is replaced by `fallback`.
+pub fn clamp_guidance_scale(
+    scale: f32,
+    fallback: f32,
+    adjustments: &mut Vec<ParamAdjustment>,
+) -> f32 {
+    let used = if scale.is_finite() {
+        scale.clamp(MIN_GUIDANCE_SCALE, MAX_GUIDANCE_SCALE)
+    } else {
+        fallback
+    };
+    if used != scale {
+        // serde_json turns non-finite floats into null
+        adjustments.push(ParamAdjustment::new("guidance_scale", scale, used));
+    }
+    used
+}
+
+/// Returns `scheduler` if it names a known scheduler, or `fallback` otherwise.
+///
+/// Aliases such as `ping_pong` are replaced by the canonical name.
+pub fn resolve_scheduler(
+    scheduler: &str,
+    fallback: &str,
+    adjustments: &mut Vec<ParamAdjustment>,
+) -> String {
+    let used = match SchedulerType::parse(scheduler) {
+        Some(parsed) if parsed.as_str() == scheduler.to_lowercase() => {
+            return scheduler.to_string()
+        }
+        Some(parsed) => parsed.as_str(),
+        None => fallback,
+    };
+    adjustments.push(ParamAdjustment::new("scheduler", scheduler, used));
+    used.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_param_accepts_numbers_and_auto() {
+        let fixed: StepsParam = serde_json::from_value(serde_json::json!(45)).unwrap();
+        assert_eq!(fixed, StepsParam::Fixed(45));
+        let auto: StepsParam = serde_json::from_value(serde_json::json!("auto")).unwrap();
+        assert_eq!(auto, StepsParam::Auto);
+        assert_eq!(serde_json::to_value(fixed).unwrap(), serde_json::json!(45));
+        assert_eq!(serde_json::to_value(auto).unwrap(), serde_json::json!("auto"));
+
+        for invalid in [serde_json::json!("fast"), serde_json::json!(-1), serde_json::json!(2.5)] {
+            assert!(serde_json::from_value::<StepsParam>(invalid).is_err());
+        }
+
+        assert_eq!("auto".parse::<StepsParam>().unwrap(), StepsParam::Auto);
+        assert_eq!("80".parse::<StepsParam>().unwrap(), StepsParam::Fixed(80));
+        assert!("many".parse::<StepsParam>().is_err());
+    }
+
+    #[test]
+    fn fixed_steps_bypass_the_curve() {
+        let config = AceStepConfig::default();
+        for duration in [5.0, 30.0, 240.0] {
+            assert_eq!(StepsParam::Fixed(60).resolve(duration, &config), 60);
+            assert_eq!(StepsParam::Fixed(3).resolve(duration, &config), 3);
+        }
+        assert_eq!(StepsParam::Auto.resolve(30.0, &config), 34);
+        assert_eq!(StepsParam::Auto.resolve(240.0, &config), 80);
+    }
+
+    #[test]
+    fn duration_is_clamped_per_backend() {
+        let mut adjustments = Vec::new();
+        assert_eq!(clamp_duration(2, Backend::MusicGen, &mut adjustments), 5);
+        assert_eq!(clamp_duration(5, Backend::MusicGen, &mut adjustments), 5);
+        assert_eq!(clamp_duration(120, Backend::MusicGen, &mut adjustments), 120);
+        assert_eq!(clamp_duration(200, Backend::MusicGen, &mut adjustments), 120);
+        assert_eq!(clamp_duration(200, Backend::AceStep, &mut adjustments), 200);
+        assert_eq!(clamp_duration(241, Backend::AceStep, &mut adjustments), 240);
+        assert_eq!(
+            adjustments,
+            vec![
+                ParamAdjustment::new("duration_sec", 2, 5),
+                ParamAdjustment::new("duration_sec", 200, 120),
+                ParamAdjustment::new("duration_sec", 241, 240),
+            ]
+        );
+    }
+
+    #[test]
+    fn inference_steps_are_clamped() {
+        let mut adjustments = Vec::new();
+        assert_eq!(clamp_inference_steps(0, &mut adjustments), 1);
+        assert_eq!(clamp_inference_steps(1, &mut adjustments), 1);
+        assert_eq!(clamp_inference_steps(200, &mut adjustments), 200);
+        assert_eq!(clamp_inference_steps(500, &mut adjustments), 200);
+        assert_eq!(adjustments.len(), 2);
+        assert_eq!(adjustments[1].to_string(), "inference_steps: 500 -> 200");
+    }
+
+    #[test]
+    fn guidance_scale_is_clamped() {
+        let mut adjustments = Vec::new();
+        assert_eq!(clamp_guidance_scale(0.5, 7.0, &mut adjustments), 1.0);
+        assert_eq!(clamp_guidance_scale(1.0, 7.0, &mut adjustments), 1.0);
+        assert_eq!(clamp_guidance_scale(20.0, 7.0, &mut adjustments), 20.0);
+        assert_eq!(clamp_guidance_scale(35.0, 7.0, &mut adjustments), 20.0);
+        assert_eq!(clamp_guidance_scale(f32::NAN, 7.0, &mut adjustments), 7.0);
+        assert_eq!(adjustments.len(), 3);
+        assert_eq!(adjustments[1], ParamAdjustment::new("guidance_scale", 35.0, 20.0));
+    }
+
+    #[test]
+    fn unknown_scheduler_falls_back() {
+        let mut adjustments = Vec::new();
+        assert_eq!(resolve_scheduler("heun", "euler", &mut adjustments), "heun");
+        assert_eq!(resolve_scheduler("Heun", "euler", &mut adjustments), "Heun");
+        assert_eq!(resolve_scheduler("ping_pong", "euler", &mut adjustments), "pingpong");
+        assert_eq!(resolve_scheduler("ddim", "euler", &mut adjustments), "euler");
+        assert_eq!(
+            adjustments,
+            vec![
+                ParamAdjustment::new("scheduler", "ping_pong", "pingpong"),
+                ParamAdjustment::new("scheduler", "ddim", "euler"),
+            ]
+        );
+    }
+}
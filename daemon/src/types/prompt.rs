@@ -0,0 +1,176 @@
+//! Prompt sanitization and display.
+//!
+//! Prompts are normalized before validation and track ID computation so that
+//! visually identical prompts hash to the same track and never carry control
+//! characters into the tokenizer or written metadata.
+//!
+//! Prompts can contain personal context, so anything that echoes one to logs
+//! or diagnostics goes through [`DisplayPrompt`], which shows only a short
+//! prefix and a hash while prompt redaction is enabled.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use sha2::{Digest, Sha256};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::PromptSanitization;
+
+/// Number of leading characters shown for a redacted prompt.
+const REDACTED_PREFIX_CHARS: usize = 8;
+
+/// Process-wide redaction flag, mirrored from `DaemonConfig::redact_prompts`.
+static REDACT_PROMPTS: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables prompt redaction for [`DisplayPrompt::new`].
+pub fn set_redact_prompts(enabled: bool) {
+    REDACT_PROMPTS.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns true if prompts are redacted from logs and diagnostics.
+pub fn redact_prompts() -> bool {
+    REDACT_PROMPTS.load(Ordering::Relaxed)
+}
+
+/// Returns a stable identifier for a prompt that does not reveal its text.
+///
+/// The first 16 hex characters of the SHA256 hash of the prompt.
+pub fn prompt_hash(prompt: &str) -> String {
+    let digest = Sha256::digest(prompt.as_bytes());
+    hex::encode(&digest[..8])
+}
+
+/// Formats a prompt for logs, diagnostics, and notifications.
+///
+/// When redaction is on, only the first 8 characters and the
+/// [`prompt_hash`] are shown, e.g. `music fo…#3f2a9c1b0d4e5f60`.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayPrompt<'a> {
+    prompt: &'a str,
+    redact: bool,
+}
+
+impl<'a> DisplayPrompt<'a> {
+    /// Displays `prompt` according to the process-wide redaction flag.
+    pub fn new(prompt: &'a str) -> Self {
+        Self::with_redaction(prompt, redact_prompts())
+    }
+
+    /// Displays `prompt`, redacting it if `redact` is true.
+    pub fn with_redaction(prompt: &'a str, redact: bool) -> Self {
+        Self { prompt, redact }
+    }
+}
+
+impl fmt::Display for DisplayPrompt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.redact {
+            return f.write_str(self.prompt);
+        }
+
+        let prefix: String = self.prompt.chars().take(REDACTED_PREFIX_CHARS).collect();
+        let ellipsis = if prefix.len() < self.prompt.len() { "…" } else { "" };
+        write!(f, "{}{}#{}", prefix, ellipsis, prompt_hash(self.prompt))
+    }
+}
+
+/// Sanitizes a prompt for generation.
+///
+/// The prompt is NFC-normalized, tabs and carriage returns become spaces, and
+/// surrounding whitespace is trimmed. Newlines are kept. Any other control
+/// character is removed in [`PromptSanitization::Strip`] mode or causes an
+/// error in [`PromptSanitization::Reject`] mode.
+///
+/// Emptiness and length are left to the caller's validation.
+pub fn sanitize_prompt(prompt: &str, mode: PromptSanitization) -> Result<String, String> {
+    let mut sanitized = String::with_capacity(prompt.len());
+
+    for c in prompt.nfc() {
+        match c {
+            '\n' => sanitized.push(c),
+            '\t' | '\r' => sanitized.push(' '),
+            c if c.is_control() => match mode {
+                PromptSanitization::Strip => {}
+                PromptSanitization::Reject => {
+                    return Err(format!(
+                        "Prompt contains control character U+{:04X}",
+                        c as u32
+                    ));
+                }
+            },
+            c => sanitized.push(c),
+        }
+    }
+
+    Ok(sanitized.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Backend;
+    use crate::types::compute_track_id;
+
+    #[test]
+    fn strips_control_characters() {
+        let prompt = "  lofi\u{0}\tbeats\u{7}\r\nwith rain\u{1b}  ";
+        let sanitized = sanitize_prompt(prompt, PromptSanitization::Strip).unwrap();
+        assert_eq!(sanitized, "lofi beats \nwith rain");
+    }
+
+    #[test]
+    fn rejects_control_characters() {
+        let err = sanitize_prompt("lofi\u{0}beats", PromptSanitization::Reject).unwrap_err();
+        assert!(err.contains("U+0000"));
+
+        // Whitespace controls are normalized, not rejected
+        let ok = sanitize_prompt("lofi\tbeats\n", PromptSanitization::Reject).unwrap();
+        assert_eq!(ok, "lofi beats");
+    }
+
+    #[test]
+    fn combining_characters_hash_consistently() {
+        // "café" precomposed vs. "e" + combining acute accent
+        let composed = sanitize_prompt("caf\u{e9} jazz", PromptSanitization::Strip).unwrap();
+        let decomposed = sanitize_prompt("cafe\u{301} jazz", PromptSanitization::Strip).unwrap();
+        assert_eq!(composed, decomposed);
+
+        let id1 = compute_track_id(Backend::MusicGen, &composed, 42, 30.0, "v1");
+        let id2 = compute_track_id(Backend::MusicGen, &decomposed, 42, 30.0, "v1");
+        assert_eq!(id1, id2);
+    }
+
+    #[test]
+    fn emoji_are_preserved() {
+        let prompt = "rainy night 🌧️ lofi 🎧";
+        let sanitized = sanitize_prompt(prompt, PromptSanitization::Strip).unwrap();
+        assert_eq!(sanitized, prompt);
+
+        let id1 = compute_track_id(Backend::MusicGen, &sanitized, 1, 30.0, "v1");
+        let id2 = compute_track_id(Backend::MusicGen, prompt, 1, 30.0, "v1");
+        assert_eq!(id1, id2);
+    }
+
+    #[test]
+    fn display_prompt_redacts() {
+        let prompt = "music for writing my resignation letter";
+        assert_eq!(DisplayPrompt::with_redaction(prompt, false).to_string(), prompt);
+
+        let redacted = DisplayPrompt::with_redaction(prompt, true).to_string();
+        assert_eq!(redacted, format!("music fo…#{}", prompt_hash(prompt)));
+        assert!(!redacted.contains("resignation"));
+
+        // Short and multi-byte prompts are cut on character boundaries
+        let short = DisplayPrompt::with_redaction("rain", true).to_string();
+        assert!(short.starts_with("rain#"));
+        let emoji = DisplayPrompt::with_redaction("🌧️🌧️🌧️🌧️🌧️ lofi", true).to_string();
+        assert!(emoji.starts_with("🌧️🌧️🌧️🌧️…#"));
+    }
+
+    #[test]
+    fn prompt_hash_is_stable() {
+        assert_eq!(prompt_hash("lofi beats"), prompt_hash("lofi beats"));
+        assert_ne!(prompt_hash("lofi beats"), prompt_hash("lofi beat"));
+        assert_eq!(prompt_hash("lofi beats").len(), 16);
+    }
+}
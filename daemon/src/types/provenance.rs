@@ -0,0 +1,236 @@
+//! Generation provenance for quality regression testing.
+//!
+//! Every generated track records how it was produced: daemon and runtime
+//! versions, execution provider, sampling parameters, prompt and audio
+//! processing, and timings. Comparing the provenance of two tracks generated
+//! from the same prompt and seed by different releases narrows down what
+//! changed when output quality regresses.
+
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Sampling parameters used for a generation.
+///
+/// Fields that do not apply to the backend are omitted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SamplingParams {
+    /// ACE-Step scheduler (euler, heun, pingpong).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduler: Option<String>,
+
+    /// ACE-Step diffusion steps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inference_steps: Option<u32>,
+
+    /// Classifier-free guidance scale.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guidance_scale: Option<f32>,
+
+    /// MusicGen top-k token sampling.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<usize>,
+}
+
+/// How a track was produced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Provenance {
+    /// lofi-daemon version that generated the track.
+    pub daemon_version: String,
+
+    /// ONNX Runtime API version the daemon was built against.
+    pub ort_api_version: String,
+
+    /// Execution provider used for inference (e.g. "CPU", "CUDA").
+    pub execution_provider: String,
+
+    /// Sampling parameters.
+    #[serde(default)]
+    pub sampling: SamplingParams,
+
+    /// Transformations applied to the prompt before encoding, in order.
+    #[serde(default)]
+    pub prompt_enrichment: Vec<String>,
+
+    /// Processing applied to the generated audio, in order.
+    #[serde(default)]
+    pub post_processing: Vec<String>,
+
+    /// Wall-clock time per stage in seconds.
+    #[serde(default)]
+    pub timings_sec: BTreeMap<String, f32>,
+}
+
+impl Provenance {
+    /// Creates provenance for the running daemon and execution provider.
+    pub fn new(execution_provider: impl Into<String>) -> Self {
+        Self {
+            daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+            ort_api_version: format!("1.{}", ort::MINOR_VERSION),
+            execution_provider: execution_provider.into(),
+            sampling: SamplingParams::default(),
+            prompt_enrichment: Vec::new(),
+            post_processing: Vec::new(),
+            timings_sec: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the sampling parameters.
+    pub fn with_sampling(mut self, sampling: SamplingParams) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Records a prompt transformation.
+    pub fn with_prompt_enrichment(mut self, step: impl Into<String>) -> Self {
+        self.prompt_enrichment.push(step.into());
+        self
+    }
+
+    /// Records an audio processing step.
+    pub fn with_post_processing(mut self, step: impl Into<String>) -> Self {
+        self.post_processing.push(step.into());
+        self
+    }
+
+    /// Records the time spent in a stage.
+    pub fn with_timing(mut self, stage: impl Into<String>, seconds: f32) -> Self {
+        self.timings_sec.insert(stage.into(), seconds);
+        self
+    }
+}
+
+/// A provenance field whose value differs between two tracks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ProvenanceDifference {
+    /// Dotted path of the field (e.g. "sampling.guidance_scale").
+    pub field: String,
+    /// Value for the first track, null if absent.
+    pub a: serde_json::Value,
+    /// Value for the second track, null if absent.
+    pub b: serde_json::Value,
+}
+
+/// Lists the fields that differ between two provenance blocks.
+///
+/// Nested objects are compared field by field; lists are compared as a
+/// whole. A missing block counts as every field being absent. Fields are
+/// returned in alphabetical order.
+pub fn diff_provenance(
+    a: Option<&Provenance>,
+    b: Option<&Provenance>,
+) -> Vec<ProvenanceDifference> {
+    let flatten_block = |provenance: Option<&Provenance>| {
+        let mut fields = BTreeMap::new();
+        if let Some(provenance) = provenance {
+            let value = serde_json::to_value(provenance).unwrap_or_default();
+            flatten("", &value, &mut fields);
+        }
+        fields
+    };
+    let a = flatten_block(a);
+    let b = flatten_block(b);
+
+    let mut fields: Vec<&String> = a.keys().chain(b.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let a = a.get(field).cloned().unwrap_or_default();
+            let b = b.get(field).cloned().unwrap_or_default();
+            (a != b).then(|| ProvenanceDifference {
+                field: field.clone(),
+                a,
+                b,
+            })
+        })
+        .collect()
+}
+
+/// Flattens nested JSON objects into dotted paths.
+fn flatten(
+    prefix: &str,
+    value: &serde_json::Value,
+    fields: &mut BTreeMap<String, serde_json::Value>,
+) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&path, value, fields);
+            }
+        }
+        _ => {
+            fields.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ace_step_provenance() -> Provenance {
+        Provenance::new("CPU")
+            .with_sampling(SamplingParams {
+                scheduler: Some("euler".to_string()),
+                inference_steps: Some(60),
+                guidance_scale: Some(7.0),
+                top_k: None,
+            })
+            .with_prompt_enrichment("sanitize:strip")
+            .with_post_processing("resample:44100->48000")
+            .with_timing("generate", 12.5)
+    }
+
+    #[test]
+    fn serialization_round_trip() {
+        let provenance = ace_step_provenance();
+        let json = serde_json::to_string(&provenance).unwrap();
+        assert!(!json.contains("top_k"));
+
+        let parsed: Provenance = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, provenance);
+        assert_eq!(parsed.daemon_version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn diff_reports_changed_params() {
+        let a = ace_step_provenance();
+        assert!(diff_provenance(Some(&a), Some(&a)).is_empty());
+
+        let mut b = a.clone().with_timing("generate", 14.0);
+        b.sampling.inference_steps = Some(80);
+        b.sampling.scheduler = None;
+
+        let diff = diff_provenance(Some(&a), Some(&b));
+        let fields: Vec<&str> = diff.iter().map(|d| d.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "sampling.inference_steps",
+                "sampling.scheduler",
+                "timings_sec.generate"
+            ]
+        );
+        assert_eq!(diff[0].a, 60);
+        assert_eq!(diff[0].b, 80);
+        assert_eq!(diff[1].b, serde_json::Value::Null);
+    }
+
+    #[test]
+    fn diff_against_missing_provenance() {
+        let a = ace_step_provenance();
+        let diff = diff_provenance(Some(&a), None);
+        assert!(diff.iter().any(|d| d.field == "daemon_version"));
+        assert!(diff.iter().all(|d| d.b.is_null()));
+        assert!(diff_provenance(None, None).is_empty());
+    }
+}
@@ -3,19 +3,24 @@
 //! A Track represents a successfully generated audio file stored in the cache.
 //! Tracks are identified by a deterministic track_id computed from generation parameters.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::path::PathBuf;
 use std::time::SystemTime;
 
+use crate::audio::{Reverb, SilenceTrim};
+use crate::models::ace_step::DEFAULT_INFERENCE_STEPS;
 use crate::models::Backend;
 
+use super::provenance::Provenance;
+
 /// A successfully generated audio file stored in the cache.
 ///
 /// Tracks are immutable once created and are uniquely identified by their
 /// `track_id`, which is computed from the generation parameters to enable
 /// deduplication of identical requests.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Track {
     /// Primary key - SHA256 hash of (backend + prompt + seed + duration + model_version).
     /// Format: 16 hex characters.
@@ -26,19 +31,34 @@ pub struct Track {
 
     /// Original text prompt used for generation.
     /// Constraints: 1-1000 characters.
+    /// For prompt blends this is the blend label, e.g. `70% jazzy piano + 30% rain`.
     pub prompt: String,
 
+    /// Weighted prompts blended to produce the track (ACE-Step only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_blend: Option<Vec<(String, f32)>>,
+
     /// Actual duration of generated audio in seconds.
     pub duration_sec: f32,
 
-    /// Audio sample rate in Hz. 32000 for MusicGen, 48000 for ACE-Step.
+    /// Sample rate of the WAV file in Hz. The backend's native rate (32000 for
+    /// MusicGen, 48000 for ACE-Step) unless another output rate was requested.
     pub sample_rate: u32,
 
+    /// Reverb applied to the audio, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverb: Option<Reverb>,
+
+    /// Seconds of near-silence removed from the ends, if the request asked
+    /// for `trim_silence`. `duration_sec` is the length after trimming.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trimmed_sec: Option<f32>,
+
     /// Random seed used for generation.
     pub seed: u64,
 
     /// Model identifier for reproducibility.
-    /// Example: "musicgen-small-fp16-v1" or "ace-step-v1"
+    /// Example: "musicgen-small-fp16-v1" or "ace-step-v1-noise2"
     pub model_version: String,
 
     /// Backend used for generation.
@@ -47,15 +67,41 @@ pub struct Track {
     /// Time taken to generate the audio in seconds.
     pub generation_time_sec: f32,
 
+    /// Size of the WAV file on disk in bytes.
+    /// Zero if the file did not exist when the track was created.
+    #[serde(default)]
+    pub file_size_bytes: u64,
+
+    /// User-supplied tags for organizing tracks (lowercase).
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// User rating from -1 (bad) to 1 (good), if the track was rated.
+    /// Lower-rated tracks are evicted from the cache first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<i8>,
+
+    /// Well-rated seed of the same prompt that a `prefer_rated` request chose
+    /// this track's new seed in place of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derived_from_seed: Option<u64>,
+
     /// When the track was created (ISO 8601 timestamp).
     #[serde(with = "system_time_serde")]
+    #[schemars(with = "String")]
     pub created_at: SystemTime,
+
+    /// How the track was produced. Absent for tracks from older daemons.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
 }
 
 impl Track {
     /// Creates a new Track with the given parameters.
     ///
-    /// The track_id is automatically computed from the generation parameters.
+    /// The track_id is automatically computed from the generation parameters,
+    /// and the file size is read from the WAV at `path`, so tracks should be
+    /// created after the audio has been written.
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         path: PathBuf,
@@ -67,18 +113,107 @@ impl Track {
         generation_time_sec: f32,
     ) -> Self {
         let track_id = compute_track_id(backend, &prompt, seed, duration_sec, &model_version);
+        let file_size_bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
         Self {
             track_id,
             path,
             prompt,
+            prompt_blend: None,
             duration_sec,
             sample_rate: backend.sample_rate(),
+            reverb: None,
+            trimmed_sec: None,
             seed,
             model_version,
             backend,
             generation_time_sec,
+            file_size_bytes,
+            tags: Vec::new(),
+            rating: None,
+            derived_from_seed: None,
             created_at: SystemTime::now(),
+            provenance: None,
+        }
+    }
+
+    /// Sets the track's tags, normalizing them to lowercase.
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = normalize_tags(&tags);
+        self
+    }
+
+    /// Sets the weighted prompts the track was blended from.
+    pub fn with_prompt_blend(mut self, prompt_blend: Option<Vec<(String, f32)>>) -> Self {
+        self.prompt_blend = prompt_blend;
+        self
+    }
+
+    /// Derives the track ID for audio diffused with `inference_steps`,
+    /// matching [`steps_track_id`]. Call before
+    /// [`with_output_sample_rate`](Self::with_output_sample_rate).
+    pub fn with_inference_steps(mut self, inference_steps: Option<u32>) -> Self {
+        self.track_id = steps_track_id(&self.track_id, self.backend, inference_steps);
+        self
+    }
+
+    /// Records that the WAV was written at `sample_rate`.
+    ///
+    /// The track ID changes if this differs from the backend's native rate,
+    /// matching [`output_track_id`].
+    pub fn with_output_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.track_id = output_track_id(&self.track_id, self.backend, sample_rate);
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Records the reverb applied to the WAV, if any.
+    ///
+    /// The track ID changes when reverb was applied, matching
+    /// [`reverb_track_id`].
+    pub fn with_reverb(mut self, reverb: Option<&Reverb>) -> Self {
+        self.track_id = reverb_track_id(&self.track_id, reverb);
+        self.reverb = reverb.copied();
+        self
+    }
+
+    /// Records the duration left after `trim`, if the track was trimmed.
+    ///
+    /// The track ID changes for trimmed tracks, matching [`trim_track_id`].
+    pub fn with_trimmed_duration(mut self, trim: Option<&SilenceTrim>, duration_sec: f32) -> Self {
+        if trim.is_some() {
+            self.track_id = trim_track_id(&self.track_id, trim);
+            self.trimmed_sec = Some((self.duration_sec - duration_sec).max(0.0));
+            self.duration_sec = duration_sec;
         }
+        self
+    }
+
+    /// Records the seed the audio was generated with, when generation retried
+    /// with another seed after invalid output.
+    ///
+    /// The track ID stays that of the requested seed, so repeating the request
+    /// still finds the track in the cache.
+    pub fn with_generated_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Records the rated seed the track's seed was chosen in place of.
+    pub fn with_derived_from_seed(mut self, derived_from_seed: Option<u64>) -> Self {
+        self.derived_from_seed = derived_from_seed;
+        self
+    }
+
+    /// Sets the track's generation provenance.
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    /// Returns true if the track has the given tag (case-insensitive).
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.to_lowercase();
+        self.tags.contains(&tag)
     }
 
     /// Validates that the track meets all constraints.
@@ -131,6 +266,18 @@ impl Track {
     }
 }
 
+/// Lowercases tags and removes duplicates, preserving first-seen order.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.to_lowercase();
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
 /// Computes a deterministic track ID from generation parameters.
 ///
 /// The track ID is the first 16 hex characters of the SHA256 hash of:
@@ -161,6 +308,91 @@ pub fn compute_track_id(
     hex::encode(&result[..8])
 }
 
+/// Returns the track ID for audio delivered at `sample_rate`.
+///
+/// Audio at the backend's native rate keeps `track_id`. Resampled audio gets
+/// an ID derived from it, so native and resampled copies are cached separately.
+pub fn output_track_id(track_id: &str, backend: Backend, sample_rate: u32) -> String {
+    if sample_rate == backend.sample_rate() {
+        return track_id.to_string();
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}@{}", track_id, sample_rate).as_bytes());
+    hex::encode(&hasher.finalize()[..8])
+}
+
+/// Returns the track ID for ACE-Step audio diffused with `inference_steps`.
+///
+/// The default step count keeps `track_id`, as does MusicGen audio, which
+/// has no steps. Other counts get an ID derived from it and the steps.
+pub fn steps_track_id(track_id: &str, backend: Backend, inference_steps: Option<u32>) -> String {
+    match inference_steps {
+        Some(steps) if backend == Backend::AceStep && steps != DEFAULT_INFERENCE_STEPS => {
+            let mut hasher = Sha256::new();
+            hasher.update(format!("{}#{}", track_id, steps).as_bytes());
+            hex::encode(&hasher.finalize()[..8])
+        }
+        _ => track_id.to_string(),
+    }
+}
+
+/// Returns the track ID for audio with `reverb` applied.
+///
+/// Dry audio keeps `track_id`. Reverberated audio gets an ID derived from it
+/// and the reverb settings, so each mix is cached separately.
+pub fn reverb_track_id(track_id: &str, reverb: Option<&Reverb>) -> String {
+    let Some(reverb) = reverb else {
+        return track_id.to_string();
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}+{}", track_id, reverb.label()).as_bytes());
+    hex::encode(&hasher.finalize()[..8])
+}
+
+/// Settings beyond the prompt, seed, duration and model version that give a
+/// track a different ID.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrackVariant<'a> {
+    /// Diffusion steps, for ACE-Step.
+    pub inference_steps: Option<u32>,
+    /// Sample rate the WAV is written at, if not the backend's native rate.
+    pub output_sample_rate: Option<u32>,
+    /// Reverb applied to the audio.
+    pub reverb: Option<&'a Reverb>,
+    /// Silence trimming applied to the audio.
+    pub trim: Option<&'a SilenceTrim>,
+}
+
+impl TrackVariant<'_> {
+    /// Derives the ID of this variant of the track with `track_id`, from
+    /// [`compute_track_id`].
+    ///
+    /// Applies [`steps_track_id`], [`output_track_id`], [`reverb_track_id`]
+    /// and [`trim_track_id`] in the order the daemon does.
+    pub fn track_id(&self, track_id: &str, backend: Backend) -> String {
+        let track_id = steps_track_id(track_id, backend, self.inference_steps);
+        let track_id = match self.output_sample_rate {
+            Some(rate) => output_track_id(&track_id, backend, rate),
+            None => track_id,
+        };
+        let track_id = reverb_track_id(&track_id, self.reverb);
+        trim_track_id(&track_id, self.trim)
+    }
+}
+
+/// Returns the track ID for audio with near-silence trimmed from its ends.
+///
+/// Untrimmed audio keeps `track_id`. Trimmed audio gets an ID derived from
+/// it and the trim settings, so each trim is cached separately.
+pub fn trim_track_id(track_id: &str, trim: Option<&SilenceTrim>) -> String {
+    let Some(trim) = trim else {
+        return track_id.to_string();
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}~{}", track_id, trim.label()).as_bytes());
+    hex::encode(&hasher.finalize()[..8])
+}
+
 /// Custom serde implementation for SystemTime to use ISO 8601 format.
 mod system_time_serde {
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -257,6 +489,129 @@ mod tests {
         assert_ne!(id1, id2, "Different backends should produce different track IDs");
     }
 
+    #[test]
+    fn output_track_id_keeps_native_rate() {
+        let id = compute_track_id(Backend::AceStep, "lofi beats", 42, 30.0, "v1");
+        assert_eq!(output_track_id(&id, Backend::AceStep, 48000), id);
+
+        let resampled = output_track_id(&id, Backend::AceStep, 44100);
+        assert_ne!(resampled, id);
+        assert_eq!(resampled.len(), 16);
+
+        let track = Track::new(
+            PathBuf::from("/nonexistent/a.wav"),
+            "lofi beats".to_string(),
+            30.0,
+            42,
+            "v1".to_string(),
+            Backend::AceStep,
+            1.0,
+        )
+        .with_output_sample_rate(44100);
+        assert_eq!(track.track_id, resampled);
+        assert_eq!(track.sample_rate, 44100);
+    }
+
+    #[test]
+    fn steps_track_id_keeps_default_steps() {
+        let id = compute_track_id(Backend::AceStep, "lofi beats", 42, 30.0, "v1");
+        assert_eq!(steps_track_id(&id, Backend::AceStep, None), id);
+        assert_eq!(steps_track_id(&id, Backend::AceStep, Some(DEFAULT_INFERENCE_STEPS)), id);
+        assert_eq!(steps_track_id(&id, Backend::MusicGen, Some(34)), id);
+
+        let auto = steps_track_id(&id, Backend::AceStep, Some(34));
+        assert_ne!(auto, id);
+        assert_eq!(auto.len(), 16);
+        assert_ne!(steps_track_id(&id, Backend::AceStep, Some(80)), auto);
+    }
+
+    #[test]
+    fn reverb_track_id_depends_on_settings() {
+        use crate::audio::ReverbPreset;
+
+        let id = compute_track_id(Backend::MusicGen, "lofi beats", 42, 30.0, "v1");
+        assert_eq!(reverb_track_id(&id, None), id);
+
+        let hall = Reverb::new(ReverbPreset::Hall, None);
+        let wetter = Reverb::new(ReverbPreset::Hall, Some(0.5));
+        let reverberated = reverb_track_id(&id, Some(&hall));
+        assert_ne!(reverberated, id);
+        assert_eq!(reverberated.len(), 16);
+        assert_ne!(reverb_track_id(&id, Some(&wetter)), reverberated);
+    }
+
+    #[test]
+    fn trimmed_tracks_get_their_own_id() {
+        let id = compute_track_id(Backend::MusicGen, "lofi beats", 42, 30.0, "v1");
+        let trim = SilenceTrim::default();
+        let stricter = SilenceTrim::new(-50.0, 0.5);
+        assert_eq!(trim_track_id(&id, None), id);
+        assert_ne!(trim_track_id(&id, Some(&trim)), id);
+        assert_ne!(trim_track_id(&id, Some(&stricter)), trim_track_id(&id, Some(&trim)));
+
+        let track = Track::new(
+            PathBuf::from("/nonexistent.wav"),
+            "lofi beats".to_string(),
+            30.0,
+            42,
+            "v1".to_string(),
+            Backend::MusicGen,
+            1.0,
+        );
+        assert_eq!(track.clone().with_trimmed_duration(None, 28.5).track_id, id);
+        let trimmed = track.with_trimmed_duration(Some(&trim), 28.5);
+        assert_eq!(trimmed.track_id, trim_track_id(&id, Some(&trim)));
+        assert_eq!(trimmed.duration_sec, 28.5);
+        assert_eq!(trimmed.trimmed_sec, Some(1.5));
+    }
+
+    #[test]
+    fn track_new_reads_file_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("track.wav");
+        std::fs::write(&path, vec![0u8; 1234]).unwrap();
+
+        let track = Track::new(
+            path,
+            "lofi".to_string(),
+            10.0,
+            1,
+            "v1".to_string(),
+            Backend::MusicGen,
+            1.0,
+        );
+        assert_eq!(track.file_size_bytes, 1234);
+
+        let missing = Track::new(
+            dir.path().join("missing.wav"),
+            "lofi".to_string(),
+            10.0,
+            1,
+            "v1".to_string(),
+            Backend::MusicGen,
+            1.0,
+        );
+        assert_eq!(missing.file_size_bytes, 0);
+    }
+
+    #[test]
+    fn track_tags_are_normalized() {
+        let track = Track::new(
+            PathBuf::from("/nonexistent/track.wav"),
+            "lofi beats".to_string(),
+            30.0,
+            42,
+            "v1".to_string(),
+            Backend::MusicGen,
+            1.0,
+        )
+        .with_tags(vec!["Chill".to_string(), "chill".to_string(), "Study".to_string()]);
+
+        assert_eq!(track.tags, vec!["chill", "study"]);
+        assert!(track.has_tag("CHILL"));
+        assert!(!track.has_tag("jazz"));
+    }
+
     #[test]
     fn track_id_hex_format() {
         let id = compute_track_id(Backend::MusicGen, "test", 0, 10.0, "v1");
@@ -0,0 +1,188 @@
+//! Prompt file watching for `--watch` CLI mode.
+//!
+//! Reads a prompt from a file, watches it for modifications, and opens
+//! generated tracks with the system default audio player.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::error::{DaemonError, ErrorCode, Result};
+
+/// How long to wait for further events after a change before regenerating.
+///
+/// Editors typically emit several events per save (truncate, write, rename),
+/// which should only trigger a single regeneration.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// How often the watch loop checks whether it has been asked to stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Reads and trims a prompt from a file.
+///
+/// Returns an error if the file cannot be read or contains only whitespace.
+pub fn read_prompt_file(path: &Path) -> Result<String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        DaemonError::with_source(
+            ErrorCode::InvalidPrompt,
+            format!("Failed to read prompt file {}", path.display()),
+            e,
+        )
+    })?;
+
+    let prompt = contents.trim();
+    if prompt.is_empty() {
+        return Err(DaemonError::empty_prompt());
+    }
+
+    Ok(prompt.to_string())
+}
+
+/// Builds the platform-specific command that opens `path` in the default player.
+pub fn player_command(path: &Path) -> Command {
+    if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg(path);
+        command
+    } else if cfg!(target_os = "windows") {
+        // `start` is a cmd builtin; the empty string is the window title
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]).arg(path);
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(path);
+        command
+    }
+}
+
+/// Opens a WAV file with the system default audio player.
+///
+/// The player is spawned detached; failures are reported but not fatal to callers
+/// that choose to ignore them.
+pub fn open_with_default_player(path: &Path) -> std::io::Result<()> {
+    player_command(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
+/// Returns true if a filesystem event modifies the watched file.
+fn is_change_to(event: &Event, target: &Path) -> bool {
+    let relevant_kind = matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any
+    );
+    relevant_kind
+        && event
+            .paths
+            .iter()
+            .any(|p| p.file_name() == target.file_name())
+}
+
+/// Watches a prompt file and calls `on_change` each time it is modified.
+///
+/// The parent directory is watched rather than the file itself so that editors
+/// which save by writing a new file and renaming it over the old one are
+/// still detected. Returns when `stop` is set.
+pub fn watch_prompt_file<F>(path: &Path, stop: Arc<AtomicBool>, mut on_change: F) -> Result<()>
+where
+    F: FnMut() -> Result<()>,
+{
+    let target = path.to_path_buf();
+    let watch_dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        if let Ok(event) = res {
+            let _ = tx.send(event);
+        }
+    })
+    .map_err(|e| {
+        DaemonError::with_source(ErrorCode::InvalidPrompt, "Failed to create prompt file watcher", e)
+    })?;
+
+    watcher
+        .watch(&watch_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| {
+            DaemonError::with_source(
+                ErrorCode::InvalidPrompt,
+                format!("Failed to watch prompt file in {}", watch_dir.display()),
+                e,
+            )
+        })?;
+
+    while !stop.load(Ordering::SeqCst) {
+        let event = match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        if !is_change_to(&event, &target) {
+            continue;
+        }
+
+        // Collapse the burst of events a single save produces
+        while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+
+        eprintln!("Detected change, regenerating...");
+        if let Err(e) = on_change() {
+            eprintln!("Error: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_prompt_file_trims() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prompt.txt");
+        std::fs::write(&path, "  lofi beats\n").unwrap();
+        assert_eq!(read_prompt_file(&path).unwrap(), "lofi beats");
+    }
+
+    #[test]
+    fn read_prompt_file_rejects_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prompt.txt");
+        std::fs::write(&path, "\n\n").unwrap();
+        assert!(read_prompt_file(&path).is_err());
+        assert!(read_prompt_file(&dir.path().join("missing.txt")).is_err());
+    }
+
+    #[test]
+    fn is_change_to_matches_file_name() {
+        let target = Path::new("/tmp/prompt.txt");
+        let event = Event::new(EventKind::Modify(notify::event::ModifyKind::Any))
+            .add_path(PathBuf::from("/tmp/prompt.txt"));
+        assert!(is_change_to(&event, target));
+
+        let other = Event::new(EventKind::Modify(notify::event::ModifyKind::Any))
+            .add_path(PathBuf::from("/tmp/other.txt"));
+        assert!(!is_change_to(&other, target));
+
+        let removed = Event::new(EventKind::Remove(notify::event::RemoveKind::Any))
+            .add_path(PathBuf::from("/tmp/prompt.txt"));
+        assert!(!is_change_to(&removed, target));
+    }
+}
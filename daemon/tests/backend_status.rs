@@ -0,0 +1,51 @@
+//! Backend status reported by `get_backends` through a full load cycle.
+
+mod common;
+
+use common::fixture_dir;
+use lofi_daemon::rpc::methods::handle_request;
+use lofi_daemon::rpc::ServerState;
+use lofi_daemon::DaemonConfig;
+use serde_json::{json, Value};
+
+fn musicgen_info(state: &mut ServerState) -> Value {
+    let value = handle_request("get_backends", Value::Null, state).unwrap();
+    value["backends"][0].clone()
+}
+
+#[test]
+fn backend_recovers_from_failed_load() {
+    let model_dir = tempfile::tempdir().unwrap();
+    for entry in std::fs::read_dir(fixture_dir()).unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(&path, model_dir.path().join(path.file_name().unwrap())).unwrap();
+    }
+    let cache_dir = tempfile::tempdir().unwrap();
+    let mut state = ServerState::new(DaemonConfig {
+        model_path: Some(model_dir.path().to_path_buf()),
+        ..common::fixture_config(cache_dir.path())
+    });
+
+    let info = musicgen_info(&mut state);
+    assert_eq!(info["status"], "ready");
+    assert_eq!(info["loaded"], false);
+
+    // A corrupt decoder fails to load
+    let decoder = model_dir.path().join("decoder_model.onnx");
+    std::fs::write(&decoder, b"not a model").unwrap();
+    let params = json!({ "prompt": "lofi beats", "duration_sec": 5, "seed": 1 });
+    assert!(handle_request("generate", params.clone(), &mut state).is_err());
+    let info = musicgen_info(&mut state);
+    assert_eq!(info["status"], "error");
+    assert!(info["error_detail"].as_str().unwrap().contains("decoder_model.onnx"));
+    assert_eq!(info["loaded"], false);
+
+    // Restoring it and retrying loads the backend and clears the error
+    std::fs::copy(fixture_dir().join("decoder_model.onnx"), &decoder).unwrap();
+    handle_request("generate", params, &mut state).unwrap();
+    let info = musicgen_info(&mut state);
+    assert_eq!(info["status"], "loaded");
+    assert_eq!(info["loaded"], true);
+    assert_eq!(info["active_jobs"], 0);
+    assert!(info.get("error_detail").is_none());
+}
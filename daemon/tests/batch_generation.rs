@@ -0,0 +1,46 @@
+//! Batch generate requests with explicit seeds.
+
+mod common;
+
+use lofi_daemon::rpc::methods::handle_request;
+use serde_json::{json, Value};
+
+#[test]
+fn generates_one_track_per_listed_seed() {
+    let cache_dir = tempfile::tempdir().unwrap();
+    let mut state = common::fixture_state(cache_dir.path());
+
+    let params = json!({
+        "prompt": "lofi beats",
+        "duration_sec": 5,
+        "seeds": [42, 7, 42],
+        "batch_size": 5,
+    });
+    let result = handle_request("generate", params, &mut state).unwrap();
+    assert_eq!(result["seed"], 42);
+
+    let track_ids = result["track_ids_by_seed"].as_object().unwrap();
+    let seeds: Vec<&str> = track_ids.keys().map(String::as_str).collect();
+    assert_eq!(seeds, ["42", "7"]);
+    assert_eq!(result["track_id"], track_ids["42"]);
+    let warnings: Vec<&str> = result["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|w| w.as_str().unwrap())
+        .collect();
+    assert!(warnings.contains(&"Seed 42 is listed more than once"));
+    assert!(warnings.contains(&"batch_size is ignored because seeds are given"));
+
+    // The whole batch was worked through before the response
+    assert_eq!(state.queue.len(), 0);
+    let tracks = handle_request("list_tracks", Value::Null, &mut state).unwrap();
+    let mut generated: Vec<u64> = tracks["tracks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|track| track["seed"].as_u64().unwrap())
+        .collect();
+    generated.sort_unstable();
+    assert_eq!(generated, [7, 42]);
+}
@@ -0,0 +1,34 @@
+//! Setup shared by the integration tests.
+//!
+//! The tiny MusicGen fixture models in `tests/fixtures/musicgen-tiny` are
+//! described in `musicgen_pipeline.rs`; they let a real `generate` request
+//! load the backend without downloading anything.
+
+// Each test crate uses only some of these helpers
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use lofi_daemon::rpc::ServerState;
+use lofi_daemon::DaemonConfig;
+
+/// Directory holding the tiny MusicGen fixture models.
+pub fn fixture_dir() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/musicgen-tiny"))
+}
+
+/// Config that loads the fixture models and caches tracks in `cache_dir`.
+pub fn fixture_config(cache_dir: &Path) -> DaemonConfig {
+    DaemonConfig {
+        model_path: Some(fixture_dir().to_path_buf()),
+        cache_path: Some(cache_dir.to_path_buf()),
+        now_playing: false,
+        memory_safety_margin_bytes: 0,
+        ..DaemonConfig::default()
+    }
+}
+
+/// Server state using [`fixture_config`].
+pub fn fixture_state(cache_dir: &Path) -> ServerState {
+    ServerState::new(fixture_config(cache_dir))
+}
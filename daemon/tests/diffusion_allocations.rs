@@ -0,0 +1,91 @@
+//! Steady-state allocations of ACE-Step diffusion steps.
+//!
+//! A counting global allocator tallies allocations made by the current
+//! thread, so guidance and scheduler steps can be checked to reuse their
+//! buffers instead of allocating latent-sized arrays every step.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use lofi_daemon::models::ace_step::{
+    apply_cfg_into, calculate_frame_length, create_scheduler, SchedulerType,
+};
+use ndarray::Array4;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations `f` made on this thread.
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn diffusion_steps_reuse_their_buffers() {
+    let shape = (1, 8, 16, calculate_frame_length(30.0));
+    let cond = Array4::from_elem(shape, 0.3f32);
+    let uncond = Array4::from_elem(shape, 0.1f32);
+    let mut guided = Array4::zeros(shape);
+
+    let cfg = allocations(|| {
+        for _ in 0..10 {
+            apply_cfg_into(&cond, &uncond, 7.0, &mut guided).unwrap();
+        }
+    });
+    assert_eq!(cfg, 0, "guidance allocated");
+
+    for scheduler_type in SchedulerType::ALL {
+        let mut scheduler = create_scheduler(scheduler_type, 20, 42);
+        let mut latent = Array4::from_elem(shape, 0.5f32);
+
+        // The first steps size the scheduler's buffers to the latent
+        for _ in 0..2 {
+            scheduler.step_in_place(&mut latent, &guided);
+        }
+
+        let mut steps = 0;
+        let count = allocations(|| {
+            while !scheduler.is_done() {
+                scheduler.step_in_place(&mut latent, &guided);
+                steps += 1;
+            }
+        });
+        // PingPong's noise is drawn on the rayon pool, which may allocate a
+        // little bookkeeping, never latent-sized buffers
+        let limit = match scheduler_type {
+            SchedulerType::PingPong => steps,
+            _ => 0,
+        };
+        assert!(
+            count <= limit,
+            "{} made {} allocations in {} steps",
+            scheduler_type.as_str(),
+            count,
+            steps
+        );
+    }
+}
@@ -0,0 +1,381 @@
+#!/usr/bin/env python3
+"""Generates the tiny MusicGen ONNX fixtures used by tests/musicgen_pipeline.rs.
+
+Only the standard library is needed: the ONNX protobuf messages are encoded by
+hand, so the fixtures can be regenerated without installing onnx or numpy.
+
+The models are not trained networks. They are wired so that every decoder step
+has exactly two possible outcomes, which the tests can check without a
+reference implementation of the sampler:
+
+- text_encoder.onnx: last_hidden_state = attention_mask as float, shape (1, S, 1)
+- decoder_model.onnx / decoder_with_past_model.onnx: logits are a table lookup
+  of the input token (spike of 200 at g(t)) plus, for conditional rows only, a
+  second lookup (spikes of 150 at h(t) and h2(t)). Classifier-free guidance
+  with scale 3 turns that into 200 at g(t) and 450 at both h(t) and h2(t), so
+  sampling picks h(t) or h2(t) with equal probability, depending on the seed,
+  and every other token has probability exactly zero in f32. Without guidance
+  g(t) would win. The conditional flag travels through the encoder KV cache,
+  so dropping or mixing up cache entries changes the output.
+- encodec_decode.onnx: audio_values = the token codes as floats, flattened in
+  codebook-major order, so the output samples are a snapshot of the tokens.
+
+expected.json lists the two allowed successors of every input token, with the
+pad token last. Each codebook starts from the pad token, so the first frame's
+tokens are successors of the pad token and every later token is a successor of
+the same codebook's token in the previous frame.
+
+Usage: python3 generate_musicgen_tiny.py
+Writes musicgen-tiny/ and musicgen-tiny/expected.json next to this script.
+"""
+
+import json
+import os
+import struct
+
+VOCAB_SIZE = 16
+PAD_TOKEN_ID = VOCAB_SIZE
+NUM_LAYERS = 2
+CODEBOOKS = 4
+BATCH = 2 * CODEBOOKS
+GUIDANCE_SCALE = 3
+TOKEN_SPIKE = 200.0
+COND_SPIKE = 150.0
+
+PROMPT = "lofi beats"
+TARGET_FRAMES = 12
+
+TOKENIZER_VOCAB = ["<pad>", "</s>", "<unk>", "lofi", "beats", "chill", "piano", "rain"]
+
+OPSET = 13
+IR_VERSION = 8
+FLOAT = 1
+INT64 = 7
+
+
+def g(token):
+    """Token favoured by the unconditional lookup."""
+    return (3 * token + 1) % VOCAB_SIZE
+
+
+def h(token):
+    """First token favoured by the conditional lookup; wins after guidance."""
+    return (5 * token + 2) % VOCAB_SIZE
+
+
+def h2(token):
+    """Second conditional token, tied with h(t) after guidance."""
+    return (h(token) + VOCAB_SIZE // 2) % VOCAB_SIZE
+
+
+def successors(token):
+    """Tokens sampling can pick after `token`."""
+    return [h(token), h2(token)]
+
+
+# --- Protobuf encoding -------------------------------------------------------
+
+
+def varint(value):
+    if value < 0:
+        value += 1 << 64
+    out = bytearray()
+    while True:
+        byte = value & 0x7F
+        value >>= 7
+        if value:
+            out.append(byte | 0x80)
+        else:
+            out.append(byte)
+            return bytes(out)
+
+
+def key(field, wire_type):
+    return varint((field << 3) | wire_type)
+
+
+def int_field(field, value):
+    return key(field, 0) + varint(value)
+
+
+def bytes_field(field, data):
+    if isinstance(data, str):
+        data = data.encode()
+    return key(field, 2) + varint(len(data)) + data
+
+
+def tensor(name, dims, data_type, values):
+    fmt = "<%d%s" % (len(values), "f" if data_type == FLOAT else "q")
+    msg = b"".join(int_field(1, d) for d in dims)
+    msg += int_field(2, data_type)
+    msg += bytes_field(8, name)
+    msg += bytes_field(9, struct.pack(fmt, *values))
+    return msg
+
+
+def int64_tensor(name, values):
+    return tensor(name, [len(values)], INT64, values)
+
+
+def attribute_int(name, value):
+    return bytes_field(1, name) + int_field(3, value) + int_field(20, 2)
+
+
+def attribute_ints(name, values):
+    msg = bytes_field(1, name)
+    msg += b"".join(int_field(8, v) for v in values)
+    return msg + int_field(20, 7)
+
+
+def node(op_type, inputs, outputs, name, attributes=()):
+    msg = b"".join(bytes_field(1, i) for i in inputs)
+    msg += b"".join(bytes_field(2, o) for o in outputs)
+    msg += bytes_field(3, name)
+    msg += bytes_field(4, op_type)
+    msg += b"".join(bytes_field(5, a) for a in attributes)
+    return msg
+
+
+def value_info(name, elem_type, dims=None):
+    tensor_type = int_field(1, elem_type)
+    if dims is not None:
+        shape = b""
+        for dim in dims:
+            if isinstance(dim, str):
+                shape += bytes_field(1, bytes_field(2, dim))
+            else:
+                shape += bytes_field(1, int_field(1, dim))
+        tensor_type += bytes_field(2, shape)
+    return bytes_field(1, name) + bytes_field(2, bytes_field(1, tensor_type))
+
+
+def model(name, nodes, initializers, inputs, outputs):
+    graph = b"".join(bytes_field(1, n) for n in nodes)
+    graph += bytes_field(2, name)
+    graph += b"".join(bytes_field(5, t) for t in initializers)
+    graph += b"".join(bytes_field(11, i) for i in inputs)
+    graph += b"".join(bytes_field(12, o) for o in outputs)
+
+    msg = int_field(1, IR_VERSION)
+    msg += bytes_field(2, "lofi-daemon-fixtures")
+    msg += bytes_field(7, graph)
+    msg += bytes_field(8, bytes_field(1, "") + int_field(2, OPSET))
+    return msg
+
+
+# --- Models ------------------------------------------------------------------
+
+
+def spike_table(name, targets, value):
+    rows = []
+    for token in range(VOCAB_SIZE + 1):
+        row = [0.0] * VOCAB_SIZE
+        for target in targets(token):
+            row[target] = value
+        rows.extend(row)
+    return tensor(name, [VOCAB_SIZE + 1, VOCAB_SIZE], FLOAT, rows)
+
+
+def logits_nodes(cond_flag):
+    """Nodes computing `logits` from `input_ids` and a (BATCH, 1, 1) flag tensor."""
+    return [
+        node("Gather", ["token_table", "input_ids"], ["token_logits"], "token_lookup"),
+        node("Gather", ["cond_table", "input_ids"], ["cond_logits"], "cond_lookup"),
+        node("Mul", ["cond_logits", cond_flag], ["cond_term"], "cond_gate"),
+        node("Add", ["token_logits", "cond_term"], ["logits"], "logits"),
+    ]
+
+
+def logits_initializers():
+    return [
+        spike_table("token_table", lambda token: [g(token)], TOKEN_SPIKE),
+        spike_table("cond_table", successors, COND_SPIKE),
+    ]
+
+
+def text_encoder():
+    nodes = [
+        node("Cast", ["attention_mask"], ["mask_float"], "mask_float", [attribute_int("to", FLOAT)]),
+        node("Unsqueeze", ["mask_float", "hidden_axes"], ["last_hidden_state"], "hidden"),
+    ]
+    return model(
+        "tiny_text_encoder",
+        nodes,
+        [int64_tensor("hidden_axes", [2])],
+        [
+            value_info("input_ids", INT64, [1, "sequence"]),
+            value_info("attention_mask", INT64, [1, "sequence"]),
+        ],
+        [value_info("last_hidden_state", FLOAT, [1, "sequence", 1])],
+    )
+
+
+def decoder_model():
+    nodes = [
+        # 1.0 for rows with a prompt, 0.0 for the zeroed unconditional rows
+        node(
+            "ReduceMax",
+            ["encoder_hidden_states"],
+            ["has_prompt"],
+            "has_prompt",
+            [attribute_ints("axes", [1, 2]), attribute_int("keepdims", 0)],
+        ),
+        node("Reshape", ["has_prompt", "shape_2_1"], ["has_prompt_col"], "has_prompt_col"),
+        node("Expand", ["has_prompt_col", "shape_2_4"], ["has_prompt_rows"], "has_prompt_rows"),
+        node("Reshape", ["has_prompt_rows", "shape_b_1_1"], ["cond_flag"], "cond_flag"),
+        node("Cast", ["input_ids"], ["ids_float"], "ids_float", [attribute_int("to", FLOAT)]),
+        node("Reshape", ["ids_float", "shape_b_1_1_1"], ["step_kv"], "step_kv"),
+        node("Reshape", ["cond_flag", "shape_b_1_1_1"], ["encoder_kv"], "encoder_kv"),
+    ] + logits_nodes("cond_flag")
+
+    outputs = [value_info("logits", FLOAT, [BATCH, 1, VOCAB_SIZE])]
+    for layer in range(NUM_LAYERS):
+        for kind, source in [("decoder", "step_kv"), ("encoder", "encoder_kv")]:
+            for part in ["key", "value"]:
+                name = "present.%d.%s.%s" % (layer, kind, part)
+                nodes.append(node("Identity", [source], [name], name))
+                outputs.append(value_info(name, FLOAT))
+
+    return model(
+        "tiny_decoder",
+        nodes,
+        logits_initializers()
+        + [
+            int64_tensor("shape_2_1", [2, 1]),
+            int64_tensor("shape_2_4", [2, CODEBOOKS]),
+            int64_tensor("shape_b_1_1", [BATCH, 1, 1]),
+            int64_tensor("shape_b_1_1_1", [BATCH, 1, 1, 1]),
+        ],
+        [
+            value_info("encoder_attention_mask", INT64, [2, "encoder_sequence"]),
+            value_info("encoder_hidden_states", FLOAT, [2, "encoder_sequence", 1]),
+            value_info("input_ids", INT64, [BATCH, 1]),
+        ],
+        outputs,
+    )
+
+
+def decoder_with_past_model():
+    last = NUM_LAYERS - 1
+    nodes = [
+        # Both the first and last layer's encoder cache must arrive intact
+        node(
+            "Mul",
+            ["past_key_values.0.encoder.key", "past_key_values.%d.encoder.value" % last],
+            ["cached_flag"],
+            "cached_flag",
+        ),
+        node("Reshape", ["cached_flag", "shape_b_1_1"], ["cond_flag"], "cond_flag"),
+        node("Cast", ["input_ids"], ["ids_float"], "ids_float", [attribute_int("to", FLOAT)]),
+        node("Reshape", ["ids_float", "shape_b_1_1_1"], ["step_kv"], "step_kv"),
+    ] + logits_nodes("cond_flag")
+
+    inputs = [
+        value_info("input_ids", INT64, [BATCH, 1]),
+        value_info("encoder_attention_mask", INT64, [2, "encoder_sequence"]),
+    ]
+    outputs = [value_info("logits", FLOAT, [BATCH, 1, VOCAB_SIZE])]
+    for layer in range(NUM_LAYERS):
+        for kind in ["decoder", "encoder"]:
+            for part in ["key", "value"]:
+                past = "past_key_values.%d.%s.%s" % (layer, kind, part)
+                length = "past_sequence" if kind == "decoder" else 1
+                inputs.append(value_info(past, FLOAT, [BATCH, 1, length, 1]))
+        for part in ["key", "value"]:
+            past = "past_key_values.%d.decoder.%s" % (layer, part)
+            present = "present.%d.decoder.%s" % (layer, part)
+            nodes.append(node("Concat", [past, "step_kv"], [present], present, [attribute_int("axis", 2)]))
+            outputs.append(value_info(present, FLOAT))
+
+    return model(
+        "tiny_decoder_with_past",
+        nodes,
+        logits_initializers()
+        + [
+            int64_tensor("shape_b_1_1", [BATCH, 1, 1]),
+            int64_tensor("shape_b_1_1_1", [BATCH, 1, 1, 1]),
+        ],
+        inputs,
+        outputs,
+    )
+
+
+def audio_codec():
+    nodes = [
+        node("Cast", ["audio_codes"], ["codes_float"], "codes_float", [attribute_int("to", FLOAT)]),
+        node("Reshape", ["codes_float", "audio_shape"], ["audio_values"], "audio_values"),
+    ]
+    return model(
+        "tiny_audio_codec",
+        nodes,
+        [int64_tensor("audio_shape", [1, 1, -1])],
+        [value_info("audio_codes", INT64, [1, 1, CODEBOOKS, "frames"])],
+        [value_info("audio_values", FLOAT, [1, 1, "samples"])],
+    )
+
+
+def tokenizer_json():
+    return {
+        "version": "1.0",
+        "truncation": None,
+        "padding": None,
+        "added_tokens": [],
+        "normalizer": None,
+        "pre_tokenizer": {"type": "Whitespace"},
+        "post_processor": None,
+        "decoder": None,
+        "model": {
+            "type": "WordLevel",
+            "vocab": {token: i for i, token in enumerate(TOKENIZER_VOCAB)},
+            "unk_token": "<unk>",
+        },
+    }
+
+
+def config_json():
+    return {
+        "decoder": {
+            "num_hidden_layers": NUM_LAYERS,
+            "num_attention_heads": 1,
+            "vocab_size": VOCAB_SIZE,
+            "pad_token_id": PAD_TOKEN_ID,
+        },
+        "text_encoder": {"d_kv": 1, "d_model": 1},
+    }
+
+
+def main():
+    assert GUIDANCE_SCALE * COND_SPIKE - TOKEN_SPIKE > 0, "guidance must change the winner"
+    for token in range(VOCAB_SIZE + 1):
+        assert len({g(token), h(token), h2(token)}) == 3, "lookups must not overlap"
+
+    out_dir = os.path.join(os.path.dirname(os.path.abspath(__file__)), "musicgen-tiny")
+    os.makedirs(out_dir, exist_ok=True)
+
+    files = {
+        "text_encoder.onnx": text_encoder(),
+        "decoder_model.onnx": decoder_model(),
+        "decoder_with_past_model.onnx": decoder_with_past_model(),
+        "encodec_decode.onnx": audio_codec(),
+    }
+    for name, data in files.items():
+        with open(os.path.join(out_dir, name), "wb") as f:
+            f.write(data)
+
+    for name, value in [("tokenizer.json", tokenizer_json()), ("config.json", config_json())]:
+        with open(os.path.join(out_dir, name), "w") as f:
+            json.dump(value, f, indent=2)
+            f.write("\n")
+
+    expected = {
+        "prompt": PROMPT,
+        "target_frames": TARGET_FRAMES,
+        "successors": [successors(token) for token in range(VOCAB_SIZE + 1)],
+    }
+    with open(os.path.join(out_dir, "expected.json"), "w") as f:
+        json.dump(expected, f, indent=2)
+        f.write("\n")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,50 @@
+//! Generation metrics through the `get_metrics` method.
+
+mod common;
+
+use lofi_daemon::rpc::methods::handle_request;
+use lofi_daemon::rpc::ServerState;
+use serde_json::{json, Value};
+
+fn metrics(state: &mut ServerState) -> String {
+    let value = handle_request("get_metrics", Value::Null, state).unwrap();
+    value.as_str().unwrap().to_string()
+}
+
+#[test]
+fn generations_are_counted() {
+    let cache_dir = tempfile::tempdir().unwrap();
+    let mut state = common::fixture_state(cache_dir.path());
+
+    let params = json!({ "prompt": "lofi beats", "duration_sec": 5, "seeds": [1, 2] });
+    handle_request("generate", params, &mut state).unwrap();
+    // Cached tracks are not generated again
+    let params = json!({ "prompt": "lofi beats", "duration_sec": 5, "seed": 1 });
+    handle_request("generate", params, &mut state).unwrap();
+
+    let text = metrics(&mut state);
+    for line in [
+        "lofi_generations_total{backend=\"musicgen\",status=\"started\"} 2",
+        "lofi_generations_total{backend=\"musicgen\",status=\"completed\"} 2",
+        "lofi_generations_total{backend=\"musicgen\",status=\"failed\"} 0",
+        "lofi_cache_lookups_total{backend=\"musicgen\",status=\"hit\"} 1",
+        "lofi_cache_lookups_total{backend=\"musicgen\",status=\"miss\"} 2",
+        "lofi_generation_duration_seconds_count{backend=\"musicgen\"} 2",
+        "lofi_generation_realtime_factor_count{backend=\"musicgen\"} 2",
+        "lofi_queue_depth 0",
+        "lofi_backend_loaded{backend=\"musicgen\"} 1",
+        "lofi_backend_loaded{backend=\"ace_step\"} 0",
+    ] {
+        assert!(text.lines().any(|l| l == line), "missing {}", line);
+    }
+    let cache_bytes = text
+        .lines()
+        .find_map(|l| l.strip_prefix("lofi_cache_bytes "))
+        .unwrap();
+    assert!(cache_bytes.parse::<u64>().unwrap() > 0);
+    let generated_bytes = text
+        .lines()
+        .find_map(|l| l.strip_prefix("lofi_generated_bytes_total{backend=\"musicgen\"} "))
+        .unwrap();
+    assert_eq!(generated_bytes, cache_bytes);
+}
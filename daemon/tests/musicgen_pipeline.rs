@@ -0,0 +1,209 @@
+//! End-to-end MusicGen token pipeline tests against tiny fixture models.
+//!
+//! The models in `tests/fixtures/musicgen-tiny` and their expected output are
+//! generated by `tests/fixtures/generate_musicgen_tiny.py`. The fixture decoder
+//! leaves sampling two equally likely successors for every token, and only
+//! when guidance and the KV cache are wired correctly; the fixture codec
+//! returns the token codes as samples. Any change to the decoder loop,
+//! KV-cache wiring, delay pattern or guidance produces a token outside those
+//! successors, and seeding is checked by comparing whole generations.
+
+mod common;
+
+use std::path::Path;
+
+use common::fixture_dir;
+use lofi_daemon::generation::generate_with_models_diagnosed;
+use lofi_daemon::models::musicgen::{ContextWindow, MusicGenModels};
+use lofi_daemon::models::load_sessions_with_device;
+use lofi_daemon::Device;
+use serde::Deserialize;
+
+/// Description of the fixture written by its generator.
+#[derive(Debug, Deserialize)]
+struct Expected {
+    prompt: String,
+    target_frames: usize,
+    /// The two tokens sampling can pick after each input token, indexed by
+    /// token id with the pad token last.
+    successors: Vec<[i64; 2]>,
+}
+
+impl Expected {
+    /// Asserts that `frames` is a sequence the fixture decoder can produce.
+    fn check_frames(&self, frames: &[[i64; 4]]) {
+        assert_eq!(frames.len(), self.target_frames);
+        let pad = self.successors.len() as i64 - 1;
+        let mut previous = [pad; 4];
+        for (i, frame) in frames.iter().enumerate() {
+            for (codebook, &token) in frame.iter().enumerate() {
+                let allowed = self.successors[previous[codebook] as usize];
+                assert!(
+                    allowed.contains(&token),
+                    "frame {} codebook {}: {} is not one of {:?}",
+                    i,
+                    codebook,
+                    token,
+                    allowed
+                );
+            }
+            previous = *frame;
+        }
+    }
+}
+
+fn expected() -> Expected {
+    let json = std::fs::read_to_string(fixture_dir().join("expected.json")).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+fn generate(model_dir: &Path, expected: &Expected, seed: u64) -> lofi_daemon::Result<Vec<f32>> {
+    let mut models = load_sessions_with_device(model_dir, Device::Cpu, Some(1))?;
+    generate_with_models_diagnosed(
+        &mut models,
+        &expected.prompt,
+        expected.target_frames,
+        seed,
+        None,
+        None,
+        None,
+        None,
+        |_, _| {},
+    )
+}
+
+/// Reads the token frames back from the fixture codec's codebook-major samples.
+fn frames_from_samples(samples: &[f32]) -> Vec<[i64; 4]> {
+    let frames = samples.len() / 4;
+    assert_eq!(samples.len(), frames * 4);
+    (0..frames)
+        .map(|i| std::array::from_fn(|codebook| samples[codebook * frames + i] as i64))
+        .collect()
+}
+
+#[test]
+fn tokens_follow_guided_successors() {
+    let expected = expected();
+    for seed in [1, 7, 42] {
+        let samples = generate(fixture_dir(), &expected, seed).unwrap();
+        expected.check_frames(&frames_from_samples(&samples));
+    }
+}
+
+#[test]
+fn same_seed_is_reproducible() {
+    let expected = expected();
+    let first = generate(fixture_dir(), &expected, 7).unwrap();
+    let second = generate(fixture_dir(), &expected, 7).unwrap();
+    assert_eq!(first, second);
+
+    // Every token is a coin flip, so other seeds take other paths
+    for seed in [8, 42] {
+        let other = generate(fixture_dir(), &expected, seed).unwrap();
+        assert_ne!(first, other, "seeds 7 and {} produced the same tokens", seed);
+    }
+}
+
+#[test]
+fn warmup_can_run_repeatedly() {
+    let mut models = load_sessions_with_device(fixture_dir(), Device::Cpu, Some(1)).unwrap();
+    models.warmup().unwrap();
+    models.warmup().unwrap();
+}
+
+#[test]
+fn vocab_size_mismatch_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    for entry in std::fs::read_dir(fixture_dir()).unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(&path, dir.path().join(path.file_name().unwrap())).unwrap();
+    }
+    let config = r#"{ "decoder": { "num_hidden_layers": 2, "vocab_size": 32, "pad_token_id": 16 } }"#;
+    std::fs::write(dir.path().join("config.json"), config).unwrap();
+
+    let err = generate(dir.path(), &expected(), 42).unwrap_err();
+    assert!(err.message.contains("vocab_size 32"), "{}", err);
+}
+
+/// Generates `target_frames` frames with seed 42 in a single run.
+fn single_generation(models: &mut MusicGenModels, prompt: &str, target_frames: usize) -> Vec<[i64; 4]> {
+    let (hidden, mask) = models.text_encoder.encode(prompt).unwrap();
+    let frames = models
+        .decoder
+        .generate_tokens_seeded(hidden, mask, target_frames, 42, None, |_, _| {})
+        .unwrap();
+    Vec::from(frames)
+}
+
+#[test]
+fn continued_chunks_match_single_generation() {
+    let expected = expected();
+    let target = expected.target_frames;
+    let mut models = load_sessions_with_device(fixture_dir(), Device::Cpu, Some(1)).unwrap();
+    let single = single_generation(&mut models, &expected.prompt, target + 3);
+    expected.check_frames(&single[..target]);
+
+    // Skipping the full decoder pass must not change what comes next
+    let (hidden, mask) = models.text_encoder.encode(&expected.prompt).unwrap();
+    let first = target / 2;
+    let (mut frames, mut state) = models
+        .decoder
+        .generate_tokens_resumable(hidden, mask, first, 42, None, |_, _| {})
+        .unwrap();
+    frames.extend(models.decoder.continue_tokens(&mut state, target - first, None, |_, _| {}).unwrap());
+    assert_eq!(Vec::from(frames), single[..target]);
+    assert_eq!(state.frames_emitted(), target);
+    let steps = state.steps();
+
+    // Truncation only rebuilds the cache; the fixture's logits depend on the
+    // current input alone, so the tokens continue exactly as before
+    models.decoder.truncate_kv_cache(&mut state, 4).unwrap();
+    assert_eq!(state.context_len(), 4);
+    assert_eq!(state.steps(), steps);
+    let more = models.decoder.continue_tokens(&mut state, 3, None, |_, _| {}).unwrap();
+    assert_eq!(Vec::from(more), single[target..]);
+    assert_eq!(state.context_len(), 7);
+    assert_eq!(state.steps(), steps + 3);
+}
+
+#[test]
+fn windowed_generation_matches_single_generation() {
+    let expected = expected();
+    let target = expected.target_frames;
+    let mut models = load_sessions_with_device(fixture_dir(), Device::Cpu, Some(1)).unwrap();
+    let single = single_generation(&mut models, &expected.prompt, target);
+
+    // A tiny window truncates the cache several times during generation
+    let window = ContextWindow { max_len: 5, keep_len: 2 };
+    models.decoder.set_context_window(window);
+    let (hidden, mask) = models.text_encoder.encode(&expected.prompt).unwrap();
+    let progress = std::sync::Mutex::new(Vec::new());
+    let (frames, state) = models
+        .decoder
+        .generate_tokens_resumable(hidden, mask, target, 42, None, |current, total| {
+            assert_eq!(total, target);
+            progress.lock().unwrap().push(current);
+        })
+        .unwrap();
+    assert_eq!(Vec::from(frames), single);
+    assert!(state.context_len() <= window.max_len, "{:?}", state);
+
+    let progress = progress.into_inner().unwrap();
+    assert!(progress.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", progress);
+    assert_eq!(progress.last(), Some(&target));
+
+    // The generation pipeline goes through the same window
+    let samples = generate_with_models_diagnosed(
+        &mut models,
+        &expected.prompt,
+        target,
+        42,
+        None,
+        None,
+        None,
+        None,
+        |_, _| {},
+    )
+    .unwrap();
+    assert_eq!(frames_from_samples(&samples), single);
+}
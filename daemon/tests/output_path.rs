@@ -0,0 +1,57 @@
+//! Generate requests that write to a caller-specified `output_path`.
+
+mod common;
+
+use lofi_daemon::rpc::methods::handle_request;
+use lofi_daemon::rpc::ServerState;
+use lofi_daemon::DaemonConfig;
+use serde_json::{json, Value};
+
+fn cached_tracks(state: &mut ServerState) -> usize {
+    let tracks = handle_request("list_tracks", Value::Null, state).unwrap();
+    tracks["tracks"].as_array().unwrap().len()
+}
+
+#[test]
+fn output_path_bypasses_the_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache_dir = dir.path().join("cache");
+    let render_dir = dir.path().join("renders");
+    std::fs::create_dir(&render_dir).unwrap();
+    let mut state = ServerState::new(DaemonConfig {
+        restrict_output_root: Some(render_dir.clone()),
+        ..common::fixture_config(&cache_dir)
+    });
+
+    let params = json!({
+        "prompt": "lofi beats",
+        "duration_sec": 5,
+        "seed": 42,
+        "output_path": "rain.wav",
+    });
+    let expected = render_dir.canonicalize().unwrap().join("rain.wav");
+
+    // Identical requests generate each time instead of hitting the cache
+    for _ in 0..2 {
+        let result = handle_request("generate", params.clone(), &mut state).unwrap();
+        assert_eq!(result["status"], "generating");
+        assert_eq!(result["path"], expected.to_string_lossy().as_ref());
+        assert!(expected.is_file());
+        assert_eq!(cached_tracks(&mut state), 0);
+    }
+
+    // With also_cache a copy lands in the cache, leaving the caller's file
+    let mut params = params;
+    params["also_cache"] = json!(true);
+    handle_request("generate", params, &mut state).unwrap();
+    assert_eq!(cached_tracks(&mut state), 1);
+    assert!(expected.is_file());
+
+    let cached = handle_request(
+        "generate",
+        json!({ "prompt": "lofi beats", "duration_sec": 5, "seed": 42 }),
+        &mut state,
+    )
+    .unwrap();
+    assert_eq!(cached["status"], "complete");
+}
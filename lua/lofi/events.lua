@@ -9,6 +9,8 @@ M.EVENTS = {
   GENERATION_COMPLETE = "generation_complete",
   GENERATION_ERROR = "generation_error",
   DOWNLOAD_PROGRESS = "download_progress",
+  DOWNLOAD_COMPLETE = "download_complete",
+  BACKEND_STATUS = "backend_status",
 }
 
 --- Registered event handlers
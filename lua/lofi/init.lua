@@ -26,6 +26,8 @@ local notification_to_event = {
   generation_complete = events.EVENTS.GENERATION_COMPLETE,
   generation_error = events.EVENTS.GENERATION_ERROR,
   download_progress = events.EVENTS.DOWNLOAD_PROGRESS,
+  download_complete = events.EVENTS.DOWNLOAD_COMPLETE,
+  backend_status = events.EVENTS.BACKEND_STATUS,
 }
 
 --- Handle notifications from daemon
@@ -98,14 +100,21 @@ end
 
 --- Generate music from a text prompt
 --- @param opts string|table prompt string or generation options table
----   - prompt: string - Text description of desired music (required)
+---   - prompt: string - Text description of desired music (required unless prompt_tags is given)
+---   - prompt_tags: table|nil - Instead of prompt: { genre = {..}, instruments = {..}, bpm = 40-200, mood = {..}, extra = string }, composed by the daemon into a tag-style prompt
 ---   - duration_sec: number|nil - Duration in seconds (5-120 for MusicGen, 5-240 for ACE-Step, default 30)
 ---   - seed: number|nil - Random seed for reproducibility (nil = random)
+---   - seeds: number[]|nil - Generate one track per seed (up to 10); callback receives the first
+---   - batch_size: number|nil - Generate this many tracks with consecutive seeds (up to 10)
 ---   - priority: string|nil - "normal" or "high" (default "normal")
 ---   - backend: string|nil - Backend to use: "musicgen" or "ace_step" (default from config)
----   - inference_steps: number|nil - ACE-Step only: diffusion steps (1-200, default 60)
+---   - inference_steps: number|"auto"|nil - ACE-Step only: diffusion steps (1-200, default 60), or "auto" to choose from the duration
 ---   - scheduler: string|nil - ACE-Step only: "euler", "heun", or "pingpong" (default "euler")
----   - guidance_scale: number|nil - ACE-Step only: CFG scale (1.0-30.0, default 15.0)
+---   - guidance_scale: number|nil - ACE-Step only: CFG scale (1.0-20.0, default 15.0; 1.0 disables guidance and halves diffusion cost)
+---   - reverb: string|nil - Reverb preset: "room", "hall", or "lofi-tape" (default none)
+---   - reverb_wet: number|nil - Reverb wet mix (0.0-1.0, default from preset)
+---   - trim_silence: boolean|nil - Cut near-silent padding from the start and end of the track
+---   - save_to_output: boolean|nil - Also copy the track into the daemon's library directory (result.library_path)
 --- @param callback function|nil callback receiving (error, result)
 ---   - error: table|nil - { code, message } on failure
 ---   - result: table|nil - { track_id, path, duration_sec, backend, ... } on success
@@ -121,7 +130,7 @@ function M.generate(opts, callback)
   end
 
   -- Validate options
-  if not opts or not opts.prompt or opts.prompt == "" then
+  if not opts or ((not opts.prompt or opts.prompt == "") and not opts.prompt_tags) then
     if callback then
       vim.schedule(function()
         callback({ code = -32006, message = "Prompt is required" }, nil)
@@ -182,14 +191,21 @@ function M.generate(opts, callback)
   -- Build request params
   local params = {
     prompt = opts.prompt,
+    prompt_tags = opts.prompt_tags,
     duration_sec = opts.duration_sec or 30,
     seed = opts.seed,
+    seeds = opts.seeds,
+    batch_size = opts.batch_size,
     priority = opts.priority or "normal",
     backend = backend,
     -- ACE-Step specific parameters
     inference_steps = opts.inference_steps,
     scheduler = opts.scheduler,
     guidance_scale = opts.guidance_scale,
+    reverb = opts.reverb,
+    reverb_wet = opts.reverb_wet,
+    trim_silence = opts.trim_silence,
+    save_to_output = opts.save_to_output,
   }
 
   -- Send generate request
@@ -213,7 +229,7 @@ function M.generate(opts, callback)
     -- Emit generation_start event
     events.emit(events.EVENTS.GENERATION_START, {
       track_id = track_id,
-      prompt = opts.prompt,
+      prompt = result.prompt or opts.prompt,
       duration_sec = params.duration_sec,
       seed = result.seed,
       position = result.position,
@@ -359,7 +375,8 @@ local function run_generation(prompt, duration, backend)
 
   unsub_error = M.on("generation_error", function(data)
     cleanup()
-    vim.notify("[lofi] Error: " .. data.message, vim.log.levels.ERROR)
+    local hint = data.recovery_hint and ("\n" .. data.recovery_hint) or ""
+    vim.notify("[lofi] Error: " .. data.message .. hint, vim.log.levels.ERROR)
   end)
 
   M.generate({ prompt = prompt, duration_sec = duration, backend = backend })
@@ -421,9 +438,13 @@ vim.api.nvim_create_user_command("LofiBackends", function()
     vim.schedule(function()
       local lines = { "Available backends (default: " .. result.default_backend .. "):" }
       for _, b in ipairs(result.backends) do
-        local status_icon = b.status == "ready" and "✓" or "✗"
-        table.insert(lines, string.format("  %s %s (%s) - %d-%ds @ %dHz",
-          status_icon, b.name, b.type, b.min_duration_sec, b.max_duration_sec, b.sample_rate))
+        local available = b.status == "ready" or b.status == "loaded"
+        local status_icon = available and "✓" or "✗"
+        table.insert(lines, string.format("  %s %s (%s) - %d-%ds @ %dHz [%s]",
+          status_icon, b.name, b.type, b.min_duration_sec, b.max_duration_sec, b.sample_rate, b.status))
+        if b.error_detail then
+          table.insert(lines, "      " .. b.error_detail)
+        end
       end
       vim.notify(table.concat(lines, "\n"), vim.log.levels.INFO)
     end)